@@ -0,0 +1,545 @@
+//! Comparison of `QCell` and `TCell` with `RwLock`, `Mutex`,
+//! `RefCell` and atomics under three workloads, printing a markdown
+//! report.
+//!
+//! Regenerate the checked-in report with:
+//!
+//! ```text
+//! cargo bench -q --bench comparison > docs/comparison.md
+//! ```
+//!
+//! The workloads are:
+//!
+//! - Read-heavy shared config: several threads reading fields of a
+//!   shared config, with 1 access in 100 being a write
+//!
+//! - Graph mutation: one thread chasing links through 10,000 nodes,
+//!   moving part of the value of each node to the next, which needs
+//!   two nodes borrowed mutably at once (`rw2`)
+//!
+//! - Producer/consumer handoff: one thread filling buffer slots and
+//!   passing their indices over a channel to another thread, which
+//!   reads them and passes them back
+//!
+//! Each workload is implemented for each variant that makes sense for
+//! it.  `RefCell` is single-threaded, so it only appears in the graph
+//! workload, and atomics only fit the config workload.  In the
+//! multi-threaded workloads the owner is shared in a `Mutex` or
+//! `RwLock`, and in the single-threaded one it is also measured held
+//! directly, since that is how an owner is normally used on one
+//! thread.
+//!
+//! All variants use the same pseudo-random access sequence.  The
+//! numbers only describe the machine they were measured on, and the
+//! multi-threaded cases need a multi-core machine to mean anything.
+
+use qcell::{QCell, QCellOwner, TCell, TCellOwner};
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Barrier, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+qcell::marker!(struct ConfigMarker;);
+qcell::marker!(struct GraphMarker;);
+qcell::marker!(struct SlotMarker;);
+
+const CONFIG_FIELDS: usize = 16;
+const CONFIG_THREADS: usize = 4;
+const CONFIG_OPS: u64 = 1_000_000;
+
+const GRAPH_NODES: usize = 10_000;
+const GRAPH_STEPS: u64 = 2_000_000;
+
+const HANDOFF_SLOTS: usize = 64;
+const HANDOFF_ITEMS: u64 = 200_000;
+
+// Linear congruential generator, so that every variant sees the same
+// sequence of accesses without depending on a random number crate
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+// Measured results for one workload, in nanoseconds per operation
+struct Workload {
+    title: &'static str,
+    description: &'static str,
+    results: Vec<(&'static str, f64)>,
+}
+
+impl Workload {
+    fn new(title: &'static str, description: &'static str) -> Self {
+        Self {
+            title,
+            description,
+            results: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, variant: &'static str, elapsed: Duration, ops: u64) {
+        eprintln!("{}: {}", self.title, variant);
+        let ns = elapsed.as_secs_f64() * 1e9 / ops as f64;
+        self.results.push((variant, ns));
+    }
+
+    fn fastest(&self) -> f64 {
+        self.results
+            .iter()
+            .map(|r| r.1)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn relative(&self, variant: &str) -> Option<f64> {
+        let fastest = self.fastest();
+        self.results
+            .iter()
+            .find(|r| r.0 == variant)
+            .map(|r| r.1 / fastest)
+    }
+
+    fn print(&self) {
+        println!("## {}", self.title);
+        println!();
+        println!("{}", self.description);
+        println!();
+        println!("| Variant | ns/op | Relative to fastest |");
+        println!("|---|---:|---:|");
+        let fastest = self.fastest();
+        for (variant, ns) in &self.results {
+            println!("| {} | {:.1} | {:.2}x |", variant, ns, ns / fastest);
+        }
+        println!();
+    }
+}
+
+// Run `op(thread_index)` on `threads` threads at once, and return the
+// time taken for all of them to finish
+#[clippy::msrv = "1.66"]
+fn run_threads(threads: usize, op: impl Fn(usize) + Sync) -> Duration {
+    let barrier = Barrier::new(threads + 1);
+    let mut elapsed = Duration::default();
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|index| {
+                let barrier = &barrier;
+                let op = &op;
+                s.spawn(move || {
+                    barrier.wait();
+                    op(index);
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        for h in handles {
+            h.join().unwrap();
+        }
+        elapsed = start.elapsed();
+    });
+    elapsed
+}
+
+// Run `access(field, is_write)` for each config access of one thread
+fn config_accesses(thread: usize, mut access: impl FnMut(usize, bool)) {
+    let mut rng = Lcg(thread as u64 + 1);
+    for _ in 0..CONFIG_OPS {
+        let r = rng.next();
+        access(r as usize % CONFIG_FIELDS, r % 100 == 0);
+    }
+}
+
+#[clippy::msrv = "1.66"]
+fn config() -> Workload {
+    let mut w = Workload::new(
+        "Read-heavy shared config",
+        "4 threads each make 1,000,000 accesses to random fields of a \
+         shared config of 16 `u64` fields.  99% of accesses are reads, \
+         and 1% increment the field.  Time is per access per thread.",
+    );
+    let run = |op: &(dyn Fn(usize) + Sync)| run_threads(CONFIG_THREADS, op);
+
+    let owner = Mutex::new(QCellOwner::new());
+    let cells: Vec<QCell<u64>> = {
+        let o = owner.lock().unwrap();
+        (0..CONFIG_FIELDS).map(|_| o.cell(0)).collect()
+    };
+    let elapsed = run(&|t| {
+        config_accesses(t, |i, write| {
+            let mut o = owner.lock().unwrap();
+            if write {
+                *o.rw(&cells[i]) += 1;
+            } else {
+                black_box(*o.ro(&cells[i]));
+            }
+        })
+    });
+    w.add("`QCell` + `Mutex<QCellOwner>`", elapsed, CONFIG_OPS);
+
+    let owner = RwLock::new(QCellOwner::new());
+    let cells: Vec<QCell<u64>> = {
+        let o = owner.read().unwrap();
+        (0..CONFIG_FIELDS).map(|_| o.cell(0)).collect()
+    };
+    let elapsed = run(&|t| {
+        config_accesses(t, |i, write| {
+            if write {
+                *owner.write().unwrap().rw(&cells[i]) += 1;
+            } else {
+                black_box(*owner.read().unwrap().ro(&cells[i]));
+            }
+        })
+    });
+    w.add("`QCell` + `RwLock<QCellOwner>`", elapsed, CONFIG_OPS);
+
+    let owner = RwLock::new(TCellOwner::<ConfigMarker>::new());
+    let cells: Vec<TCell<ConfigMarker, u64>> = (0..CONFIG_FIELDS).map(|_| TCell::new(0)).collect();
+    let elapsed = run(&|t| {
+        config_accesses(t, |i, write| {
+            if write {
+                *owner.write().unwrap().rw(&cells[i]) += 1;
+            } else {
+                black_box(*owner.read().unwrap().ro(&cells[i]));
+            }
+        })
+    });
+    w.add("`TCell` + `RwLock<TCellOwner>`", elapsed, CONFIG_OPS);
+
+    let items: Vec<RwLock<u64>> = (0..CONFIG_FIELDS).map(|_| RwLock::new(0)).collect();
+    let elapsed = run(&|t| {
+        config_accesses(t, |i, write| {
+            if write {
+                *items[i].write().unwrap() += 1;
+            } else {
+                black_box(*items[i].read().unwrap());
+            }
+        })
+    });
+    w.add("`RwLock` per item", elapsed, CONFIG_OPS);
+
+    let items: Vec<AtomicU64> = (0..CONFIG_FIELDS).map(|_| AtomicU64::new(0)).collect();
+    let elapsed = run(&|t| {
+        config_accesses(t, |i, write| {
+            if write {
+                items[i].fetch_add(1, Ordering::Relaxed);
+            } else {
+                black_box(items[i].load(Ordering::Relaxed));
+            }
+        })
+    });
+    w.add("`AtomicU64` per item", elapsed, CONFIG_OPS);
+    w
+}
+
+struct Node {
+    value: u64,
+    next: usize,
+}
+
+// Random links, never to the node itself
+fn graph_links() -> Vec<usize> {
+    let mut rng = Lcg(12345);
+    (0..GRAPH_NODES)
+        .map(|i| (i + 1 + rng.next() as usize % (GRAPH_NODES - 1)) % GRAPH_NODES)
+        .collect()
+}
+
+// Move half of the value of `a` to `b`, returning the next node
+#[inline]
+fn graph_step(a: &mut Node, b: &mut Node) -> usize {
+    let moved = a.value / 2;
+    a.value -= moved;
+    b.value += moved;
+    b.next
+}
+
+fn graph_nodes<C>(new: impl Fn(Node) -> C) -> Vec<C> {
+    graph_links()
+        .into_iter()
+        .map(|next| new(Node { value: 1000, next }))
+        .collect()
+}
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+#[clippy::msrv = "1.66"]
+fn graph() -> Workload {
+    let mut w = Workload::new(
+        "Graph mutation",
+        "One thread follows random links through 10,000 nodes for \
+         2,000,000 steps, each step borrowing the current and next \
+         node mutably together (`rw2` for the cell types) to move half \
+         the value across.  Where the owner is in a lock, the lock is \
+         taken for each step.  Time is per step.",
+    );
+
+    let mut owner = QCellOwner::new();
+    let nodes = graph_nodes(|n| owner.cell(n));
+    let elapsed = time(|| {
+        let mut cur = 0;
+        for _ in 0..GRAPH_STEPS {
+            let next = owner.ro(&nodes[cur]).next;
+            let (a, b) = owner.rw2(&nodes[cur], &nodes[next]);
+            cur = graph_step(a, b);
+        }
+        black_box(cur);
+    });
+    w.add("`QCell`, owner held", elapsed, GRAPH_STEPS);
+
+    let owner = Mutex::new(QCellOwner::new());
+    let nodes = graph_nodes(|n| owner.lock().unwrap().cell(n));
+    let elapsed = time(|| {
+        let mut cur = 0;
+        for _ in 0..GRAPH_STEPS {
+            let mut o = owner.lock().unwrap();
+            let next = o.ro(&nodes[cur]).next;
+            let (a, b) = o.rw2(&nodes[cur], &nodes[next]);
+            cur = graph_step(a, b);
+        }
+        black_box(cur);
+    });
+    w.add("`QCell` + `Mutex<QCellOwner>`", elapsed, GRAPH_STEPS);
+
+    let owner = RwLock::new(QCellOwner::new());
+    let nodes = graph_nodes(|n| owner.read().unwrap().cell(n));
+    let elapsed = time(|| {
+        let mut cur = 0;
+        for _ in 0..GRAPH_STEPS {
+            let mut o = owner.write().unwrap();
+            let next = o.ro(&nodes[cur]).next;
+            let (a, b) = o.rw2(&nodes[cur], &nodes[next]);
+            cur = graph_step(a, b);
+        }
+        black_box(cur);
+    });
+    w.add("`QCell` + `RwLock<QCellOwner>`", elapsed, GRAPH_STEPS);
+
+    let mut owner = TCellOwner::<GraphMarker>::new();
+    let nodes = graph_nodes(TCell::new);
+    let elapsed = time(|| {
+        let mut cur = 0;
+        for _ in 0..GRAPH_STEPS {
+            let next = owner.ro(&nodes[cur]).next;
+            let (a, b) = owner.rw2(&nodes[cur], &nodes[next]);
+            cur = graph_step(a, b);
+        }
+        black_box(cur);
+    });
+    w.add("`TCell`, owner held", elapsed, GRAPH_STEPS);
+
+    let nodes = graph_nodes(RwLock::new);
+    let elapsed = time(|| {
+        let mut cur = 0;
+        for _ in 0..GRAPH_STEPS {
+            let next = nodes[cur].read().unwrap().next;
+            let mut a = nodes[cur].write().unwrap();
+            let mut b = nodes[next].write().unwrap();
+            cur = graph_step(&mut a, &mut b);
+        }
+        black_box(cur);
+    });
+    w.add("`RwLock` per item", elapsed, GRAPH_STEPS);
+
+    let nodes = graph_nodes(RefCell::new);
+    let elapsed = time(|| {
+        let mut cur = 0;
+        for _ in 0..GRAPH_STEPS {
+            let next = nodes[cur].borrow().next;
+            let mut a = nodes[cur].borrow_mut();
+            let mut b = nodes[next].borrow_mut();
+            cur = graph_step(&mut a, &mut b);
+        }
+        black_box(cur);
+    });
+    w.add("`RefCell`", elapsed, GRAPH_STEPS);
+    w
+}
+
+type Payload = [u64; 8];
+
+// Pass `HANDOFF_ITEMS` items from a producer thread to a consumer
+// thread through `HANDOFF_SLOTS` slots.  `write(slot, item)` fills a
+// slot and `read(slot)` sums it.
+#[clippy::msrv = "1.66"]
+fn handoff(write: impl Fn(usize, u64) + Sync, read: impl Fn(usize) -> u64 + Sync) -> Duration {
+    let (full_tx, full_rx) = mpsc::sync_channel::<usize>(HANDOFF_SLOTS);
+    let (free_tx, free_rx) = mpsc::sync_channel::<usize>(HANDOFF_SLOTS);
+    for slot in 0..HANDOFF_SLOTS {
+        free_tx.send(slot).unwrap();
+    }
+    time(|| {
+        thread::scope(|s| {
+            let (write, read) = (&write, &read);
+            s.spawn(move || {
+                for item in 0..HANDOFF_ITEMS {
+                    let slot = free_rx.recv().unwrap();
+                    write(slot, item);
+                    full_tx.send(slot).unwrap();
+                }
+            });
+            s.spawn(move || {
+                let mut total = 0;
+                for _ in 0..HANDOFF_ITEMS {
+                    let slot = full_rx.recv().unwrap();
+                    total += read(slot);
+                    // Fails once the producer has finished, which is fine
+                    let _ = free_tx.send(slot);
+                }
+                black_box(total);
+            });
+        });
+    })
+}
+
+#[clippy::msrv = "1.66"]
+fn handoff_all() -> Workload {
+    let mut w = Workload::new(
+        "Producer/consumer handoff",
+        "A producer thread fills 200,000 items of 8 `u64` values into \
+         a pool of 64 slots, passing each slot index to a consumer \
+         thread over a bounded channel.  The consumer sums the slot \
+         and passes the index back.  The channel costs the same for \
+         every variant.  Time is per item.",
+    );
+    let fill = |p: &mut Payload, item: u64| p.iter_mut().for_each(|v| *v = item);
+    let sum = |p: &Payload| p.iter().sum::<u64>();
+
+    let owner = Mutex::new(QCellOwner::new());
+    let slots: Vec<QCell<Payload>> = {
+        let o = owner.lock().unwrap();
+        (0..HANDOFF_SLOTS).map(|_| o.cell([0; 8])).collect()
+    };
+    let elapsed = handoff(
+        |s, item| fill(owner.lock().unwrap().rw(&slots[s]), item),
+        |s| sum(owner.lock().unwrap().ro(&slots[s])),
+    );
+    w.add("`QCell` + `Mutex<QCellOwner>`", elapsed, HANDOFF_ITEMS);
+
+    let owner = RwLock::new(QCellOwner::new());
+    let slots: Vec<QCell<Payload>> = {
+        let o = owner.read().unwrap();
+        (0..HANDOFF_SLOTS).map(|_| o.cell([0; 8])).collect()
+    };
+    let elapsed = handoff(
+        |s, item| fill(owner.write().unwrap().rw(&slots[s]), item),
+        |s| sum(owner.read().unwrap().ro(&slots[s])),
+    );
+    w.add("`QCell` + `RwLock<QCellOwner>`", elapsed, HANDOFF_ITEMS);
+
+    let owner = RwLock::new(TCellOwner::<SlotMarker>::new());
+    let slots: Vec<TCell<SlotMarker, Payload>> =
+        (0..HANDOFF_SLOTS).map(|_| TCell::new([0; 8])).collect();
+    let elapsed = handoff(
+        |s, item| fill(owner.write().unwrap().rw(&slots[s]), item),
+        |s| sum(owner.read().unwrap().ro(&slots[s])),
+    );
+    w.add("`TCell` + `RwLock<TCellOwner>`", elapsed, HANDOFF_ITEMS);
+
+    let slots: Vec<RwLock<Payload>> = (0..HANDOFF_SLOTS).map(|_| RwLock::new([0; 8])).collect();
+    let elapsed = handoff(
+        |s, item| fill(&mut slots[s].write().unwrap(), item),
+        |s| sum(&slots[s].read().unwrap()),
+    );
+    w.add("`RwLock` per item", elapsed, HANDOFF_ITEMS);
+
+    let slots: Vec<Mutex<Payload>> = (0..HANDOFF_SLOTS).map(|_| Mutex::new([0; 8])).collect();
+    let elapsed = handoff(
+        |s, item| fill(&mut slots[s].lock().unwrap(), item),
+        |s| sum(&slots[s].lock().unwrap()),
+    );
+    w.add("`Mutex` per item", elapsed, HANDOFF_ITEMS);
+    w
+}
+
+// Print the variants against the workloads, as cost relative to the
+// fastest variant for each workload
+fn print_matrix(workloads: &[Workload]) {
+    let mut variants: Vec<&str> = Vec::new();
+    for w in workloads {
+        for (v, _) in &w.results {
+            if !variants.contains(v) {
+                variants.push(v);
+            }
+        }
+    }
+    println!("## Decision matrix");
+    println!();
+    println!(
+        "Cost of each variant relative to the fastest for each \
+         workload (1.00x is the fastest), or \"-\" where the variant \
+         does not apply.  Details of each workload follow."
+    );
+    println!();
+    print!("| Variant |");
+    for w in workloads {
+        print!(" {} |", w.title);
+    }
+    println!();
+    print!("|---|");
+    for _ in workloads {
+        print!("---:|");
+    }
+    println!();
+    for v in variants {
+        print!("| {} |", v);
+        for w in workloads {
+            match w.relative(v) {
+                Some(r) => print!(" {:.2}x |", r),
+                None => print!(" - |"),
+            }
+        }
+        println!();
+    }
+    println!();
+}
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    let workloads = [config(), graph(), handoff_all()];
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+
+    println!("# Interior mutability comparison");
+    println!();
+    println!(
+        "Generated by `benches/comparison.rs` on a machine with {} \
+         hardware thread{}.  Regenerate with:",
+        cpus,
+        if cpus == 1 { "" } else { "s" }
+    );
+    println!();
+    println!("```text");
+    println!("cargo bench -q --bench comparison > docs/comparison.md");
+    println!("```");
+    println!();
+    println!(
+        "These figures only describe the machine they were measured \
+         on.  Measure on your own hardware with your own access \
+         pattern before relying on small differences."
+    );
+    if cpus == 1 {
+        println!();
+        println!(
+            "With only one hardware thread, the threads in the \
+             multi-threaded workloads never run at the same time, so \
+             those figures show no contention and are not \
+             representative of a multi-core machine."
+        );
+    }
+    println!();
+    print_matrix(&workloads);
+    for w in &workloads {
+        w.print();
+    }
+}
@@ -0,0 +1,46 @@
+//! `util::are_all_distinct` benchmark, comparing the pairwise and
+//! sorting strategies at a range of lengths, to choose
+//! `util::PAIRWISE_MAX`.
+//!
+//! Run with `cargo bench --bench distinct`.
+//!
+//! The pointers are all distinct, which is the usual case and also
+//! the worst case, since neither strategy can stop early.  They are
+//! shuffled, so that the sort has real work to do.  Pairwise checking
+//! is quadratic but needs no allocation, so it wins for short inputs.
+//! The crossover should be close to `PAIRWISE_MAX`, which is printed
+//! at the end.  On the machine used to choose it, sorting started to
+//! win somewhere between 24 and 40 pointers.
+
+use qcell::util::{are_all_distinct_pairwise, are_all_distinct_sorted, PAIRWISE_MAX};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::hint::black_box;
+use std::time::Instant;
+
+const CHECKS: usize = 20_000_000;
+
+#[clippy::msrv = "1.66"]
+fn time(ptrs: &[*const ()], check: fn(&[*const ()]) -> Option<(usize, usize)>) -> f64 {
+    let iterations = (CHECKS / (ptrs.len() * ptrs.len())).max(1000);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        assert!(check(black_box(ptrs)).is_none());
+    }
+    start.elapsed().as_secs_f64() * 1e9 / iterations as f64
+}
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    println!("{:>6} {:>12} {:>12}", "len", "pairwise ns", "sorted ns");
+    for len in [4, 8, 16, 24, 32, 40, 48, 64, 96, 128] {
+        let values = vec![0u64; len];
+        let mut ptrs: Vec<*const ()> = values.iter().map(|v| v as *const u64 as *const ()).collect();
+        ptrs.shuffle(&mut rng);
+        let pairwise = time(&ptrs, are_all_distinct_pairwise);
+        let sorted = time(&ptrs, are_all_distinct_sorted);
+        println!("{:6} {:12.1} {:12.1}", len, pairwise, sorted);
+    }
+    println!("PAIRWISE_MAX is {}", PAIRWISE_MAX);
+}
@@ -0,0 +1,95 @@
+//! Snapshot read benchmark for `RcuCell`, versus cloning an
+//! `Arc<T>` held in a `QCell` with the owner behind a `Mutex`.
+//!
+//! Run with `cargo bench --bench rcu_cell`.
+//!
+//! Several reader threads each take snapshots as fast as they can,
+//! whilst one writer thread stores a new snapshot every 100us.  The
+//! cases are:
+//!
+//! - `rcu-load`: readers call `RcuCell::load`, which doesn't need the
+//!   owner
+//!
+//! - `mutex+ro`: readers lock the `Mutex` holding the owner, and
+//!   clone the `Arc` out of the cell with `ro`
+//!
+//! Both cases clone an `Arc`, so both contend on its strong count.
+//! The difference is the cost of the lock, and of readers waiting for
+//! each other and for the writer to release it.  On a single-core
+//! machine the two cases are much the same, since nothing runs in
+//! parallel, so this needs to be run on a multi-core machine to show
+//! anything.
+
+use qcell::{QCell, QCellOwner, RcuCell};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const READERS: usize = 3;
+const ITERATIONS: u64 = 2_000_000;
+
+#[clippy::msrv = "1.66"]
+fn run(name: &str, read: impl Fn() -> u64 + Sync, write: impl Fn(u64) + Sync) {
+    let stop = AtomicBool::new(false);
+    let barrier = Barrier::new(READERS + 2);
+    let mut elapsed = Duration::default();
+    thread::scope(|s| {
+        s.spawn(|| {
+            barrier.wait();
+            let mut n = 0;
+            while !stop.load(Ordering::Relaxed) {
+                n += 1;
+                write(n);
+                thread::sleep(Duration::from_micros(100));
+            }
+        });
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                s.spawn(|| {
+                    barrier.wait();
+                    let mut sum = 0;
+                    for _ in 0..ITERATIONS {
+                        sum += read();
+                    }
+                    black_box(sum);
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        for r in readers {
+            r.join().unwrap();
+        }
+        elapsed = start.elapsed();
+        stop.store(true, Ordering::Relaxed);
+    });
+    let per_read = elapsed.as_secs_f64() * 1e9 / ITERATIONS as f64;
+    println!("{:14} {:8.2} ns/read per thread", name, per_read);
+}
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    let owner = Mutex::new(QCellOwner::new());
+    let rcu = RcuCell::new(&*owner.lock().unwrap(), 0_u64);
+    run(
+        "rcu-load",
+        || *black_box(&rcu).load(),
+        |n| {
+            rcu.store(&mut *owner.lock().unwrap(), n);
+        },
+    );
+
+    let cell: QCell<Arc<u64>> = owner.lock().unwrap().cell(Arc::new(0));
+    run(
+        "mutex+ro",
+        || {
+            let snapshot = owner.lock().unwrap().ro(black_box(&cell)).clone();
+            *snapshot
+        },
+        |n| {
+            *owner.lock().unwrap().rw(&cell) = Arc::new(n);
+        },
+    );
+}
@@ -0,0 +1,45 @@
+//! `QCellOwner::read_into` benchmark, comparing a batch copy of 64
+//! `f32` parameters into an array with a manual loop of `ro` calls.
+//!
+//! Run with `cargo bench --bench read_into`.
+//!
+//! This is the pattern of a real-time audio thread copying its
+//! parameters at the start of each block.  `read_into` checks the
+//! owner of each cell just like `ro` does, so the two should come out
+//! much the same.  If `read_into` is noticeably slower, then the
+//! length check or the loop is not being optimised as expected.
+
+use qcell::{QCell, QCellOwner};
+use std::convert::TryInto;
+use std::hint::black_box;
+use std::time::Instant;
+
+const PARAMS: usize = 64;
+const ITERATIONS: u64 = 2_000_000;
+
+#[clippy::msrv = "1.66"]
+fn run(name: &str, mut block: impl FnMut(&mut [f32; PARAMS])) {
+    let mut out = [0.0; PARAMS];
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        block(black_box(&mut out));
+        black_box(&out);
+    }
+    let per_block = start.elapsed().as_secs_f64() * 1e9 / ITERATIONS as f64;
+    println!("{:10} {:8.2} ns/block", name, per_block);
+}
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    let owner = QCellOwner::new();
+    let params: Vec<QCell<f32>> = (0..PARAMS).map(|i| owner.cell(i as f32)).collect();
+    let refs: Vec<&QCell<f32>> = params.iter().collect();
+    let cells: &[&QCell<f32>; PARAMS] = refs.as_slice().try_into().unwrap();
+
+    run("ro loop", |out| {
+        for (value, cell) in out.iter_mut().zip(cells) {
+            *value = *owner.ro(cell);
+        }
+    });
+    run("read_into", |out| owner.read_into(cells, out));
+}
@@ -0,0 +1,129 @@
+//! Concurrent `QCellOwner::ro` benchmark, to check for false sharing
+//! on the owner's ID allocation.
+//!
+//! Run with `cargo bench --bench ro_contention`.
+//!
+//! `QCellOwner` derives its ID from the address of a small boxed
+//! `OwnerIDTarget`.  The concern was that if that allocation shares a
+//! cache line with unrelated frequently-written data, then readers
+//! calling `owner.ro(&cell)` would suffer from false sharing.  However
+//! `QCellOwner::id()` only converts the `Box` pointer held in the
+//! owner into an integer.  It never reads the target memory, so the
+//! cache line containing the target is never touched by `ro`.  The
+//! only memory read is the `Box` pointer itself (wherever the owner
+//! lives, typically shared read-only between the reader threads) and
+//! the ID stored in the cell.  So the expectation is that padding the
+//! target allocation to a cache line would cost memory and gain
+//! nothing.
+//!
+//! That is only a hypothesis, and the decision on padding is still
+//! open.  The target is left unpadded, as it always has been, until
+//! this benchmark has been run on a multi-core machine.  The only
+//! machine available so far had a single core, which can't show false
+//! sharing, so its numbers are not evidence either way.  It runs
+//! three cases, each with several reader threads and one writer
+//! thread:
+//!
+//! - `ro`: readers call `owner.ro(&cell)`, writer idle
+//!
+//! - `ro+neighbour`: readers call `owner.ro(&cell)`, while the writer
+//!   hammers an allocation made immediately after the owner's, which
+//!   with typical allocators lands in the same cache line as the ID
+//!   target
+//!
+//! - `shared-line`: control case, where the readers read an atomic
+//!   that is in the same cache line as the one the writer hammers.
+//!   This shows what real false sharing looks like on the machine.
+//!
+//! If `ro` read the target allocation, `ro+neighbour` would slow down
+//! like `shared-line`.  On a single-core machine all three cases are
+//! much the same, since nothing runs in parallel, so the benchmark
+//! refuses to run there.  With enough cores, if `shared-line` is
+//! clearly slower than `ro` and `ro+neighbour` is not, then the
+//! hypothesis holds and padding isn't needed.  If `ro+neighbour` slows
+//! down too, the target needs padding.  Record the numbers, and the
+//! machine, with whichever change they support.
+
+use qcell::QCellOwner;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const READERS: usize = 3;
+const ITERATIONS: u64 = 20_000_000;
+
+#[repr(align(64))]
+struct Line([AtomicU64; 8]);
+
+#[clippy::msrv = "1.66"]
+fn run(name: &str, read: impl Fn() -> u64 + Sync, write: impl Fn() + Sync) {
+    let stop = AtomicBool::new(false);
+    let barrier = Barrier::new(READERS + 2);
+    let mut elapsed = Duration::default();
+    thread::scope(|s| {
+        s.spawn(|| {
+            barrier.wait();
+            while !stop.load(Ordering::Relaxed) {
+                write();
+            }
+        });
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                s.spawn(|| {
+                    barrier.wait();
+                    let mut sum = 0;
+                    for _ in 0..ITERATIONS {
+                        sum += read();
+                    }
+                    black_box(sum);
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        for r in readers {
+            r.join().unwrap();
+        }
+        elapsed = start.elapsed();
+        stop.store(true, Ordering::Relaxed);
+    });
+    let per_read = elapsed.as_secs_f64() * 1e9 / ITERATIONS as f64;
+    println!("{:14} {:8.2} ns/read per thread", name, per_read);
+}
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    // Readers and the writer all need a core of their own
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    if cores < READERS + 1 {
+        println!(
+            "ro_contention needs at least {} cores to show false sharing, but only {} available",
+            READERS + 1,
+            cores
+        );
+        return;
+    }
+    let owner = Arc::new(QCellOwner::new());
+    // Allocated straight after the owner's ID target
+    let neighbour = Box::new(Line(Default::default()));
+    let cell = owner.cell(1u64);
+    let shared = Line(Default::default());
+
+    run("ro", || *black_box(&*owner).ro(black_box(&cell)), || ());
+    run(
+        "ro+neighbour",
+        || *black_box(&*owner).ro(black_box(&cell)),
+        || {
+            neighbour.0[0].fetch_add(1, Ordering::Relaxed);
+        },
+    );
+    run(
+        "shared-line",
+        || black_box(&shared).0[1].load(Ordering::Relaxed),
+        || {
+            shared.0[0].fetch_add(1, Ordering::Relaxed);
+        },
+    );
+}
@@ -0,0 +1,54 @@
+//! `rw!` benchmark, comparing the checked macro with the unchecked
+//! `rw_distinct!`, borrowing eight cells at once.
+//!
+//! Run with `cargo bench --bench rw_tuple`.
+//!
+//! `rw!` compares every pair of the eight addresses, which is 28
+//! comparisons, before handing out the references.  In a release
+//! build `rw_distinct!` skips those, so it only pays for the eight
+//! owner checks.  The cell references are passed through
+//! `black_box` each time, so that the compiler can't prove the cells
+//! distinct and remove the check from `rw!` by itself.
+
+use qcell::{rw, rw_distinct, QCell, QCellOwner};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u64 = 20_000_000;
+
+#[clippy::msrv = "1.66"]
+fn run(name: &str, mut step: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        step();
+    }
+    let per_step = start.elapsed().as_secs_f64() * 1e9 / ITERATIONS as f64;
+    println!("{:26} {:6.2} ns/borrow", name, per_step);
+}
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    let mut owner = QCellOwner::new();
+    let cells: Vec<QCell<u64>> = (0..8).map(|i| owner.cell(i)).collect();
+    let refs = || -> [&QCell<u64>; 8] {
+        let mut refs = [&cells[0]; 8];
+        for (r, c) in refs.iter_mut().zip(&cells) {
+            *r = black_box(c);
+        }
+        refs
+    };
+
+    run("rw!", || {
+        let [c0, c1, c2, c3, c4, c5, c6, c7] = refs();
+        let (a, b, c, d, e, f, g, h) = rw!(owner => c0, c1, c2, c3, c4, c5, c6, c7);
+        *a += *b + *c + *d + *e + *f + *g + *h;
+    });
+    run("rw_distinct!", || {
+        let [c0, c1, c2, c3, c4, c5, c6, c7] = refs();
+        // Safety: The eight cells were created separately above
+        let (a, b, c, d, e, f, g, h) =
+            unsafe { rw_distinct!(owner => &c0, &c1, &c2, &c3, &c4, &c5, &c6, &c7) };
+        *a += *b + *c + *d + *e + *f + *g + *h;
+    });
+    println!("checksum {}", owner.get(&cells[0]));
+}
@@ -0,0 +1,179 @@
+//! `TCellOwner` registry benchmark, comparing a single global lock
+//! with the sharded registry, for many marker types at once.
+//!
+//! Run with `cargo bench --bench tcell_registry`.
+//!
+//! Sixteen marker types each have four threads repeatedly waiting
+//! for a new owner, holding it briefly, and dropping it.  Since the
+//! registry itself is private, this runs a copy of its locking logic
+//! with a wakeup counter, once with a single shard, which is how the
+//! registry used to be, and once with as many shards as the real
+//! one:
+//!
+//! - `single lock`: all markers share one mutex and condvar, so every
+//!   drop wakes every waiting thread
+//!
+//! - `sharded`: markers are spread over the shards by a hash of their
+//!   ID, so a drop only wakes the waiters in the same shard
+//!
+//! For each, "wakeups" counts the times a waiting thread was woken,
+//! and "spurious" the wakeups that found the marker still taken.
+//! The time for `TCellOwner::wait_for_new` itself is shown last.  On
+//! a single-core machine there is little waiting, so this needs to be
+//! run on a multi-core machine to show the difference in contention.
+
+use qcell::{marker, TCellOwner};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Barrier, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MARKERS: usize = 16;
+const THREADS_PER_MARKER: usize = 4;
+const CYCLES: usize = 20_000;
+const SHARDS: usize = 32;
+
+#[derive(Default)]
+struct Shard {
+    owners: Mutex<HashSet<usize>>,
+    released: Condvar,
+}
+
+struct Registry {
+    shards: Vec<Shard>,
+    wakeups: AtomicU64,
+    spurious: AtomicU64,
+}
+
+impl Registry {
+    fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards).map(|_| Shard::default()).collect(),
+            wakeups: AtomicU64::new(0),
+            spurious: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, marker: usize) -> &Shard {
+        let mut hasher = DefaultHasher::new();
+        marker.hash(&mut hasher);
+        &self.shards[(hasher.finish() % self.shards.len() as u64) as usize]
+    }
+
+    fn wait_for_new(&self, marker: usize) {
+        let shard = self.shard(marker);
+        let mut woken = false;
+        let mut owners = shard
+            .released
+            .wait_while(shard.owners.lock().unwrap(), |owners| {
+                let taken = owners.contains(&marker);
+                if woken {
+                    self.wakeups.fetch_add(1, Ordering::Relaxed);
+                    if taken {
+                        self.spurious.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                woken = true;
+                taken
+            })
+            .unwrap();
+        owners.insert(marker);
+    }
+
+    fn release(&self, marker: usize) {
+        let shard = self.shard(marker);
+        shard.owners.lock().unwrap().remove(&marker);
+        shard.released.notify_all();
+    }
+}
+
+// Work done while holding an owner, so that other threads for the
+// same marker have to wait
+#[clippy::msrv = "1.66"]
+fn hold() {
+    for i in 0..50 {
+        black_box(i);
+    }
+}
+
+// Run `cycle` on four threads per marker, returning the time per
+// cycle per thread
+#[clippy::msrv = "1.66"]
+fn run(cycle: impl Fn(usize) + Sync) -> f64 {
+    let barrier = Barrier::new(MARKERS * THREADS_PER_MARKER + 1);
+    let mut elapsed = Duration::default();
+    thread::scope(|s| {
+        let threads: Vec<_> = (0..MARKERS * THREADS_PER_MARKER)
+            .map(|index| {
+                let barrier = &barrier;
+                let cycle = &cycle;
+                s.spawn(move || {
+                    barrier.wait();
+                    for _ in 0..CYCLES {
+                        cycle(index % MARKERS);
+                    }
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        for t in threads {
+            t.join().unwrap();
+        }
+        elapsed = start.elapsed();
+    });
+    elapsed.as_secs_f64() * 1e9 / CYCLES as f64
+}
+
+#[clippy::msrv = "1.66"]
+fn run_registry(name: &str, shards: usize) {
+    let registry = Registry::new(shards);
+    let per_cycle = run(|marker| {
+        registry.wait_for_new(marker);
+        hold();
+        registry.release(marker);
+    });
+    println!(
+        "{:12} {:10.1} ns/cycle  wakeups {:9}  spurious {:9}",
+        name,
+        per_cycle,
+        registry.wakeups.load(Ordering::Relaxed),
+        registry.spurious.load(Ordering::Relaxed)
+    );
+}
+
+macro_rules! markers {
+    ($($index:literal $name:ident),*) => {
+        $(marker!(struct $name;);)*
+
+        fn owner_cycle(marker: usize) {
+            match marker {
+                $($index => {
+                    let _owner = TCellOwner::<$name>::wait_for_new();
+                    hold();
+                })*
+                _ => unreachable!(),
+            }
+        }
+    };
+}
+
+markers!(
+    0 M0, 1 M1, 2 M2, 3 M3, 4 M4, 5 M5, 6 M6, 7 M7,
+    8 M8, 9 M9, 10 M10, 11 M11, 12 M12, 13 M13, 14 M14, 15 M15
+);
+
+#[clippy::msrv = "1.66"]
+fn main() {
+    println!(
+        "{} markers, {} threads per marker, {} cycles per thread",
+        MARKERS, THREADS_PER_MARKER, CYCLES
+    );
+    run_registry("single lock", 1);
+    run_registry("sharded", SHARDS);
+    println!("{:12} {:10.1} ns/cycle", "TCellOwner", run(owner_cycle));
+}
@@ -0,0 +1,100 @@
+//! `LCell` workload for a `#![no_std]` crate with `alloc`.
+//!
+//! A graph of nodes is kept in an arena (a `Vec`), with edges as
+//! indices into the arena.  Each node's value is an `LCell`, so all
+//! the values can be updated through one owner whilst the arena
+//! itself is only borrowed immutably.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use qcell::generativity::make_guard;
+use qcell::{LCell, LCellOwner};
+
+/// Node in the arena
+pub struct Node<'id> {
+    /// Value updated on each round
+    pub value: LCell<'id, u64>,
+    /// Incoming edges, kept in a cell so that they can be rewired
+    /// through the owner
+    pub edges: LCell<'id, Vec<usize>>,
+}
+
+/// Arena of nodes making up a graph
+pub struct Graph<'id> {
+    nodes: Vec<Node<'id>>,
+}
+
+impl<'id> Graph<'id> {
+    /// Build a ring of `n` nodes, each also linked to the node
+    /// halfway round, with node `i` starting with value `i`
+    pub fn ring(owner: &LCellOwner<'id>, n: usize) -> Self {
+        let nodes = (0..n)
+            .map(|i| Node {
+                value: owner.cell(i as u64),
+                edges: owner.cell(alloc::vec![(i + n - 1) % n, (i + n / 2) % n]),
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    /// Add the value of each node's first incoming edge to its
+    /// second, one node at a time, so that later nodes see earlier
+    /// updates
+    pub fn propagate(&self, owner: &mut LCellOwner<'id>) {
+        for node in &self.nodes {
+            let (from, to) = {
+                let edges = owner.ro(&node.edges);
+                (edges[0], edges[1])
+            };
+            if from == to {
+                continue;
+            }
+            let (src, dst) = owner.rw2(&self.nodes[from].value, &self.nodes[to].value);
+            *dst = dst.wrapping_add(*src);
+        }
+    }
+
+    /// Swap the two incoming edges of every node
+    pub fn rewire(&self, owner: &mut LCellOwner<'id>) {
+        for node in &self.nodes {
+            owner.rw(&node.edges).swap(0, 1);
+        }
+    }
+
+    /// Combine all the values into one checksum
+    pub fn checksum(&self, owner: &LCellOwner<'id>) -> u64 {
+        self.nodes
+            .iter()
+            .fold(0, |acc, node| acc.rotate_left(5) ^ owner.get(&node.value))
+    }
+}
+
+fn run(owner: &mut LCellOwner<'_>, n: usize, rounds: usize) -> u64 {
+    let graph = Graph::ring(owner, n);
+    for round in 0..rounds {
+        graph.propagate(owner);
+        if round % 2 == 1 {
+            graph.rewire(owner);
+        }
+    }
+    graph.checksum(owner)
+}
+
+/// Run the workload under an owner created from a `make_guard!`
+/// guard, returning the checksum
+pub fn run_with_guard(n: usize, rounds: usize) -> u64 {
+    make_guard!(guard);
+    let mut owner = LCellOwner::new(guard);
+    run(&mut owner, n, rounds)
+}
+
+/// Run the workload under an owner from `LCellOwner::scope`,
+/// returning the checksum
+pub fn run_with_scope(n: usize, rounds: usize) -> u64 {
+    let mut checksum = 0;
+    LCellOwner::scope(|mut owner| checksum = run(&mut owner, n, rounds));
+    checksum
+}
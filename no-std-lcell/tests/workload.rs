@@ -0,0 +1,29 @@
+use no_std_lcell::{run_with_guard, run_with_scope};
+
+// The same workload on a plain `Vec`, to check the results
+fn reference(n: usize, rounds: usize) -> u64 {
+    let mut values: Vec<u64> = (0..n as u64).collect();
+    let mut edges: Vec<[usize; 2]> = (0..n).map(|i| [(i + n - 1) % n, (i + n / 2) % n]).collect();
+    for round in 0..rounds {
+        for e in &edges {
+            if e[0] != e[1] {
+                values[e[1]] = values[e[1]].wrapping_add(values[e[0]]);
+            }
+        }
+        if round % 2 == 1 {
+            for e in &mut edges {
+                e.swap(0, 1);
+            }
+        }
+    }
+    values.iter().fold(0, |acc, v| acc.rotate_left(5) ^ v)
+}
+
+#[test]
+fn lcell_graph_in_arena() {
+    for &(n, rounds) in &[(1, 3), (2, 3), (7, 10), (1000, 50)] {
+        let expected = reference(n, rounds);
+        assert_eq!(run_with_guard(n, rounds), expected);
+        assert_eq!(run_with_scope(n, rounds), expected);
+    }
+}
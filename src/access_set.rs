@@ -0,0 +1,235 @@
+use core::marker::PhantomData;
+
+/// Cells which a system reads and writes, declared with
+/// [`access_set!`], so that conflicts between systems can be found at
+/// compile time using [`AssertCompatible`].
+///
+/// Cells are identified by name.  See [`access_set!`] for details.
+///
+/// [`access_set!`]: macro.access_set.html
+/// [`AssertCompatible`]: struct.AssertCompatible.html
+pub trait AccessSet {
+    /// Names of the cells which are read
+    const READ: &'static [&'static str];
+
+    /// Names of the cells which are written
+    const WRITE: &'static [&'static str];
+}
+
+/// Compile-time check that two systems with access sets `A` and `B`
+/// may run at the same time.
+///
+/// Two access sets conflict if one of them writes a cell which the
+/// other one reads or writes.  Any number of sets may read the same
+/// cell.  Evaluating [`AssertCompatible::OK`] fails to compile if `A`
+/// and `B` conflict, so a scheduler can require compatible systems
+/// in its signature:
+///
+/// ```
+///# use qcell::{access_set, marker, AccessSet, AssertCompatible, TCell};
+///# marker!(struct World;);
+///# static POSITION: TCell<World, f32> = TCell::new(0.0);
+///# static VOLUME: TCell<World, f32> = TCell::new(0.0);
+///# access_set! {
+///#     struct Movement { write: [POSITION] }
+///#     struct Audio { write: [VOLUME] }
+///# }
+/// fn par_run<A: AccessSet, B: AccessSet>(a: fn(), b: fn()) {
+///     let () = AssertCompatible::<A, B>::OK;
+///     // A real scheduler would run these on different threads
+///     a();
+///     b();
+/// }
+///
+/// par_run::<Movement, Audio>(|| (), || ());
+/// ```
+///
+/// In a generic function like this, the check is made when the
+/// function is instantiated, so conflicts are reported by `cargo
+/// build`, but may not be reported by `cargo check`.  For concrete
+/// types, [`assert_compatible!`] checks at the point of declaration.
+///
+/// [`AssertCompatible::OK`]: struct.AssertCompatible.html#associatedconstant.OK
+/// [`assert_compatible!`]: macro.assert_compatible.html
+pub struct AssertCompatible<A, B>(PhantomData<(A, B)>);
+
+impl<A: AccessSet, B: AccessSet> AssertCompatible<A, B> {
+    /// `true` if `A` and `B` may run at the same time
+    pub const COMPATIBLE: bool = compatible(A::READ, A::WRITE, B::READ, B::WRITE);
+
+    /// Fails to compile if `A` and `B` conflict
+    pub const OK: () = assert!(
+        Self::COMPATIBLE,
+        "Access sets conflict: one writes a cell that the other reads or writes"
+    );
+}
+
+const fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn overlap(a: &[&str], b: &[&str]) -> bool {
+    let mut i = 0;
+    while i < a.len() {
+        let mut j = 0;
+        while j < b.len() {
+            if same_name(a[i], b[j]) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn compatible(
+    a_read: &[&str],
+    a_write: &[&str],
+    b_read: &[&str],
+    b_write: &[&str],
+) -> bool {
+    !overlap(a_write, b_write) && !overlap(a_write, b_read) && !overlap(a_read, b_write)
+}
+
+/// Declare zero-sized types listing the cells that systems read and
+/// write, implementing [`AccessSet`].
+///
+/// Each cell is named by an identifier in scope, typically a
+/// `static` [`TCell`], and the names are checked to exist.  Cells are
+/// compared by name, so the same cell must be given the same name in
+/// every set, i.e. not renamed with `use ... as`.  Two different
+/// cells with the same name, for example from different modules, are
+/// treated as the same cell, so may be reported as a conflict when
+/// there is none, but a real conflict is never missed that way.
+///
+/// Use [`assert_compatible!`] or [`AssertCompatible`] to check at
+/// compile time that systems may run at the same time.  Here three
+/// systems are compatible, since only reads are shared:
+///
+/// ```
+///# use qcell::{access_set, assert_compatible, marker, TCell};
+/// marker!(struct World;);
+/// static POSITION: TCell<World, [f32; 2]> = TCell::new([0.0; 2]);
+/// static VELOCITY: TCell<World, [f32; 2]> = TCell::new([1.0, 0.5]);
+/// static SCORE: TCell<World, u32> = TCell::new(0);
+/// static VOLUME: TCell<World, f32> = TCell::new(1.0);
+///
+/// access_set! {
+///     /// Moves everything along by its velocity
+///     pub struct Movement { read: [VELOCITY], write: [POSITION] }
+///     /// Scores points for speed
+///     pub struct Scoring { read: [VELOCITY], write: [SCORE] }
+///     pub struct Audio { write: [VOLUME] }
+/// }
+///
+/// assert_compatible!(Movement, Scoring, Audio);
+/// ```
+///
+/// See the [`doctest_access_set`] module for conflicts which fail to
+/// compile.
+///
+/// [`AccessSet`]: trait.AccessSet.html
+/// [`TCell`]: struct.TCell.html
+/// [`assert_compatible!`]: macro.assert_compatible.html
+/// [`AssertCompatible`]: struct.AssertCompatible.html
+/// [`doctest_access_set`]: doctest_access_set/index.html
+#[macro_export]
+macro_rules! access_set {
+    ($(
+        $(#[$meta:meta])* $vis:vis struct $name:ident {
+            $(read: [$($read:ident),* $(,)?] $(,)?)?
+            $(write: [$($write:ident),* $(,)?] $(,)?)?
+        }
+    )*) => {$(
+        $(#[$meta])* $vis struct $name;
+
+        impl $crate::AccessSet for $name {
+            const READ: &'static [&'static str] = &[$($(stringify!($read)),*)?];
+            const WRITE: &'static [&'static str] = &[$($(stringify!($write)),*)?];
+        }
+
+        // Fails to compile if a name isn't in scope
+        const _: () = {
+            #[allow(dead_code)]
+            fn names_exist() {
+                $($(let _ = &$read;)*)?
+                $($(let _ = &$write;)*)?
+            }
+        };
+    )*};
+}
+
+/// Check at compile time that every pair of the given [`AccessSet`]
+/// types is compatible, i.e. that no set writes a cell which another
+/// one reads or writes.  See [`access_set!`] for an example.
+///
+/// [`AccessSet`]: trait.AccessSet.html
+/// [`access_set!`]: macro.access_set.html
+#[macro_export]
+macro_rules! assert_compatible {
+    () => {};
+    ($first:ty $(, $rest:ty)* $(,)?) => {
+        $(const _: () = $crate::AssertCompatible::<$first, $rest>::OK;)*
+        $crate::assert_compatible!($($rest),*);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssertCompatible, TCell};
+
+    crate::marker!(struct World;);
+    static A: TCell<World, u32> = TCell::new(0);
+    static B: TCell<World, u32> = TCell::new(0);
+    static AB: TCell<World, u32> = TCell::new(0);
+
+    crate::access_set! {
+        struct Empty {}
+        struct ReadA { read: [A] }
+        struct ReadAB { read: [A, B,], }
+        struct WriteA { write: [A] }
+        struct WriteB { write: [B] }
+        struct ReadAWriteB { read: [A], write: [B] }
+        struct WriteAB { write: [AB] }
+    }
+
+    crate::assert_compatible!(Empty, ReadA, ReadAB, WriteAB);
+    crate::assert_compatible!(ReadA, WriteB);
+    crate::assert_compatible!(WriteA, WriteB, WriteAB);
+
+    #[test]
+    fn access_set_conflicts() {
+        macro_rules! compatible {
+            ($a:ty, $b:ty) => {
+                AssertCompatible::<$a, $b>::COMPATIBLE
+            };
+        }
+        let cases = [
+            (compatible!(ReadA, WriteA), false),
+            (compatible!(WriteA, ReadA), false),
+            (compatible!(WriteA, WriteA), false),
+            (compatible!(ReadAB, WriteB), false),
+            (compatible!(ReadAWriteB, ReadAB), false),
+            (compatible!(ReadAWriteB, ReadAWriteB), false),
+            (compatible!(ReadAWriteB, WriteA), false),
+            (compatible!(ReadAB, ReadAB), true),
+            (compatible!(WriteAB, ReadAWriteB), true),
+            (compatible!(Empty, WriteA), true),
+        ];
+        for (i, (actual, expected)) in cases.iter().enumerate() {
+            assert_eq!(actual, expected, "case {}", i);
+        }
+    }
+}
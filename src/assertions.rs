@@ -6,50 +6,161 @@ use std::panic::RefUnwindSafe;
 use std::panic::UnwindSafe;
 use std::rc::Rc;
 
-use crate::{LCell, LCellOwner, QCell, QCellOwnerPinned};
+use crate::intrusive::{Node, PinnedListHeader};
+use crate::{
+    CellPoisoned, LCell, LCellOwner, OwnerMut, OwnerRef, PoisonCell, QCell, QCellOwnerNamespace,
+    QCellOwnerPinned, QCellOwnerSeq, QCellOwnerSingle, QCellSingle, RoSlice, RoSliceIter,
+    VersionConflict, VersionedCell, OwnerIdScheme, XCell, XCellOwner,
+};
 
-#[cfg(features = "alloc")]
-use crate::QCellOwner;
+#[cfg(feature = "alloc")]
+use crate::{
+    ObservedCell, ObservedGuard, OwnedCells, QCellNoId, QCellOwner, QCellOwnerGen, QCellOwnerLocal,
+    QCellOwnerLocalRef, QuotaExceeded, QuotaHandle, QuotaOwnerID, SubscriptionId, WeakCellRef,
+};
+
+#[cfg(feature = "async-tokio")]
+use crate::AsyncOwner;
 
 #[cfg(feature = "std")]
-use crate::{TCell, TCellOwner, TLCell, TLCellOwner};
+use crate::{
+    migration::QRefCell, ErasedLCell, IndexedTCellOwner, OwnerLifecycle, RcuCell, ShardedTCellOwner, TCell,
+    TCellOwner, TLCell, TLCellLend, TLCellOwner, TShardCell, TShardOwner,
+};
 
 // Doesn't do anything, but shows up in list to prove that this file
 // has compiled
 #[test]
 fn test_static_assertions() {}
 
+crate::marker!(
+    #[allow(dead_code)]
+    struct Q;
+);
+crate::marker!(
+    #[allow(dead_code)]
+    struct QIndexed = 63;
+);
+
+// Never creates an owner, so doesn't need to mint unique IDs
 #[allow(dead_code)]
-struct Q;
+struct Ids;
+unsafe impl OwnerIdScheme for Ids {
+    type Id = u64;
+    fn new_id() -> u64 {
+        unreachable!()
+    }
+}
 
 // Check owners
 assert_impl_all!(LCellOwner<'_>: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
-#[cfg(features = "alloc")]
+#[cfg(feature = "std")]
+assert_impl_all!(crate::LCellTokenOwner<'_>: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+
+// The brand owner costs nothing, whatever features are enabled.  The
+// brand token for erase/restore lives in `LCellTokenOwner` instead.
+static_assertions::const_assert_eq!(core::mem::size_of::<LCellOwner<'static>>(), 0);
+#[cfg(feature = "alloc")]
 assert_impl_all!(QCellOwner: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
 assert_impl_all!(QCellOwnerPinned: Send, Sync, UnwindSafe, RefUnwindSafe);
 assert_not_impl_any!(QCellOwnerPinned: Unpin);
+assert_impl_all!(QCellOwnerSeq: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(QCellOwnerSeq: Clone, Default);
+assert_impl_all!(QCellOwnerSingle: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+assert_impl_all!(XCellOwner<Ids>: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(XCellOwner<Ids>: Clone);
+assert_impl_all!(QCellOwnerNamespace: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(PinnedListHeader<'static, i32>: Send, Sync, Unpin);
+assert_not_impl_any!(Node<i32>: Send, Sync);
 #[cfg(feature = "std")]
 assert_impl_all!(TCellOwner<Q>: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
 #[cfg(feature = "std")]
 assert_impl_all!(TLCellOwner<Q>: Unpin, UnwindSafe, RefUnwindSafe);
 #[cfg(feature = "std")]
 assert_not_impl_any!(TLCellOwner<Q>: Send, Sync);
+#[cfg(feature = "std")]
+assert_impl_all!(IndexedTCellOwner<QIndexed>: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_impl_all!(ShardedTCellOwner<Q, 4>: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_impl_all!(TShardOwner<Q>: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_impl_all!(OwnerLifecycle: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_impl_all!(TLCellLend<'static, Q, i32, i32>: Unpin);
+#[cfg(feature = "async-tokio")]
+assert_impl_all!(AsyncOwner<QCellOwner>: Send, Sync, Unpin);
+#[cfg(feature = "async-tokio")]
+assert_impl_all!(AsyncOwner<TCellOwner<Q>>: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TLCellLend<'static, Q, i32, i32>: Send, Sync);
+
+// Check read-only slice views, which act like `&[&U]`
+assert_impl_all!(RoSlice<'static, i32>: Send, Sync, Copy, Unpin);
+assert_impl_all!(RoSliceIter<'static, i32>: Send, Sync, Unpin);
+assert_not_impl_any!(RoSlice<'static, Cell<i32>>: Send, Sync);
+assert_not_impl_any!(RoSliceIter<'static, Cell<i32>>: Send, Sync);
+
+// Generational owner is `Send` and `Sync` like `QCellOwner`, but
+// weak references are `Rc`-based
+#[cfg(feature = "alloc")]
+assert_impl_all!(QCellOwnerGen: Send, Sync, Unpin, UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(WeakCellRef<i32>: Send, Sync);
+
+// Check poisoning cells, which act like the `QCell` they wrap
+assert_impl_all!(PoisonCell<i32>: Send, Sync, Unpin, UnwindSafe);
+assert_not_impl_any!(PoisonCell<Cell<i32>>: Sync);
+assert_impl_all!(CellPoisoned: Send, Sync, Copy, Unpin);
 
 // Check cells for simple type: i32
 assert_impl_all!(LCell<'_, i32>: Send, Sync, Unpin, UnwindSafe);
 assert_impl_all!(QCell<i32>: Send, Sync, Unpin, UnwindSafe);
+assert_impl_all!(QCellSingle<i32>: Send, Sync, Unpin, UnwindSafe);
+assert_impl_all!(XCell<Ids, i32>: Send, Sync, Unpin, UnwindSafe);
+#[cfg(feature = "alloc")]
+assert_impl_all!(QCellNoId<i32>: Send, Sync, Unpin, UnwindSafe);
+#[cfg(feature = "alloc")]
+assert_impl_all!(OwnedCells<i32>: Send, Sync, Unpin, UnwindSafe);
 #[cfg(feature = "std")]
 assert_impl_all!(TCell<Q, i32>: Send, Sync, Unpin, UnwindSafe);
 #[cfg(feature = "std")]
 assert_impl_all!(TLCell<Q, i32>: Send, Unpin, UnwindSafe);
 #[cfg(feature = "std")]
 assert_not_impl_any!(TLCell<Q, i32>: Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(QRefCell<i32>: Send, Sync);
+#[cfg(feature = "std")]
+assert_impl_all!(TShardCell<Q, i32>: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TShardCell<Q, Rc<i32>>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TShardCell<Q, Cell<i32>>: Sync);
+#[cfg(feature = "std")]
+assert_impl_all!(ErasedLCell<i32>: Send, Sync, Unpin, UnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(ErasedLCell<Rc<i32>>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(ErasedLCell<Cell<i32>>: Sync);
 
 // Check cells for a !Send !Sync type: Rc<i32>
 assert_impl_all!(LCell<'_, Rc<i32>>: Unpin, UnwindSafe);
 assert_impl_all!(QCell<Rc<i32>>: Unpin, UnwindSafe);
 assert_not_impl_any!(LCell<'_, Rc<i32>>: Send, Sync);
 assert_not_impl_any!(QCell<Rc<i32>>: Send, Sync);
+assert_not_impl_any!(QCellSingle<Rc<i32>>: Send, Sync);
+assert_not_impl_any!(XCell<Ids, Rc<i32>>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(OwnedCells<Rc<i32>>: Send, Sync);
+
+// A phase cell holds a `Box<dyn Any + Send>`, so it can be sent but
+// not shared.  Handles are `Rc` clones, so are neither.
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::PhaseCell: Send);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::PhaseCell: Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::PhaseHandle<i32>: Send, Sync);
 #[cfg(feature = "std")]
 assert_impl_all!(TCell<Q, Rc<i32>>: Unpin, UnwindSafe);
 #[cfg(feature = "std")]
@@ -64,6 +175,12 @@ assert_impl_all!(LCell<'_, Cell<i32>>: Send, Unpin, UnwindSafe);
 assert_impl_all!(QCell<Cell<i32>>: Send, Unpin, UnwindSafe);
 assert_not_impl_any!(LCell<'_, Cell<i32>>: Sync);
 assert_not_impl_any!(QCell<Cell<i32>>: Sync);
+assert_impl_all!(QCellSingle<Cell<i32>>: Send);
+assert_not_impl_any!(QCellSingle<Cell<i32>>: Sync);
+assert_impl_all!(XCell<Ids, Cell<i32>>: Send);
+assert_not_impl_any!(XCell<Ids, Cell<i32>>: Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(OwnedCells<Cell<i32>>: Sync);
 #[cfg(feature = "std")]
 assert_impl_all!(TCell<Q, Cell<i32>>: Send, Unpin, UnwindSafe);
 #[cfg(feature = "std")]
@@ -74,6 +191,7 @@ assert_not_impl_any!(TCell<Q, Cell<i32>>: Sync);
 assert_not_impl_any!(TLCell<Q, Cell<i32>>: Sync);
 
 // Check cells for a !Send Sync type
+#[allow(dead_code)]
 struct Test(*const i32);
 unsafe impl Sync for Test {}
 assert_impl_all!(LCell<'_, Test>: Unpin, UnwindSafe);
@@ -88,3 +206,330 @@ assert_impl_all!(TLCell<Q, Test>: Unpin, UnwindSafe);
 assert_not_impl_any!(TCell<Q, Test>: Send, Sync);
 #[cfg(feature = "std")]
 assert_not_impl_any!(TLCell<Q, Test>: Send, Sync);
+
+// Quota IDs and handles share an atomic counter, so can be passed
+// between threads
+#[cfg(feature = "alloc")]
+assert_impl_all!(QuotaOwnerID: Send, Sync, Clone, Unpin, UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_impl_all!(QuotaHandle: Send, Sync, Clone, Unpin, UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_impl_all!(QuotaExceeded: Send, Sync, Copy, Unpin);
+
+// Observed cells hold non-`Send` callbacks, and a plain `Cell` for
+// deferred removals
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(ObservedCell<i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(ObservedGuard<'static, i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(SubscriptionId: Send, Sync, Copy, Unpin);
+
+// Versioned cells can be shared between threads like a `QCell`, with
+// the version protected by the owner along with the value
+assert_impl_all!(VersionedCell<i32>: Send, Sync);
+assert_not_impl_any!(VersionedCell<Cell<i32>>: Sync);
+assert_impl_all!(VersionConflict<i32>: Send, Sync, Unpin);
+
+// Bit cells can be shared between threads like a `QCell<bool>`, and
+// the disjoint views may be sent to different threads, since the bits
+// are stored in atomic bytes
+assert_impl_all!(crate::BitCellArray<4>: Send, Sync, Unpin, UnwindSafe);
+assert_impl_all!(crate::BitSliceMut<'static>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::BitCells: Send, Sync, Unpin, UnwindSafe);
+
+// Owner lock guards must be released on the thread that took the
+// lock, like the standard guards they wrap
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::lock_order::OwnerMutexGuard<'static, QCellOwner>: Send);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::lock_order::OwnerReadGuard<'static, QCellOwner>: Send);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::lock_order::OwnerWriteGuard<'static, QCellOwner>: Send);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::lock_order::OwnerMutexGuard<'static, QCellOwner>: Sync);
+
+// The local owner and its creator handles share an `Rc`, so must stay
+// on one thread
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(QCellOwnerLocal: Send, Sync, Clone);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(QCellOwnerLocalRef: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(QCellOwnerLocalRef: Clone, Unpin);
+
+// The owner enums are just borrows of owners which are all `Send` and
+// `Sync`, so can be passed to other threads like the borrows
+assert_impl_all!(OwnerMut<'static>: Send, Sync, Unpin);
+assert_impl_all!(OwnerRef<'static>: Send, Sync, Copy, Unpin);
+
+// Views are just borrows of the cell contents, so follow the `Send`
+// and `Sync` of the element type like a slice borrow does
+assert_impl_all!(crate::SliceView<'static, u8>: Send, Sync, Copy);
+assert_not_impl_any!(crate::SliceView<'static, Cell<u8>>: Send, Sync);
+assert_impl_all!(crate::SliceViewMut<'static, Cell<u8>>: Send);
+assert_not_impl_any!(crate::SliceViewMut<'static, Cell<u8>>: Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::VecViewMut<'static, Cell<u8>>: Send);
+
+// The pool's free list is shared through an `Rc`
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::CellPool<u8>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::PooledCell<u8>: Send, Sync);
+
+// `SharedSync` can go to other threads like the cell itself, but
+// `Shared` can't
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::SharedSync<u8>: Send, Sync, Clone);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::Shared<u8>: Send, Sync);
+
+// A capability is just a mutable borrow of the owner
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::WriteOnly<'static, QCellOwner>: Send, Sync);
+assert_not_impl_any!(crate::WriteOnly<'static, crate::QCellOwnerSeq>: Clone, Copy);
+
+// A migration package holds only the plain values and indices, so
+// can be sent whenever the values can
+#[cfg(feature = "std")]
+assert_impl_all!(crate::MigrationPackage<u32>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::MigrationPackage<Rc<u32>>: Send, Sync);
+
+// The GhostCell wrappers have the same traits as the LCell types
+#[cfg(feature = "ghost-compat")]
+assert_impl_all!(crate::ghost_compat::GhostToken<'_>: Send, Sync);
+#[cfg(feature = "ghost-compat")]
+assert_impl_all!(crate::ghost_compat::GhostCell<'_, i32>: Send, Sync);
+#[cfg(feature = "ghost-compat")]
+assert_not_impl_any!(crate::ghost_compat::GhostCell<'_, Cell<i32>>: Sync);
+
+// The closure in an `FnCell` need not be `Send`
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::FnCell<u32>: Send, Sync);
+
+// A `StepCell` keeps its flags in `Cell`s, which the owner doesn't
+// protect, so it can't be shared between threads
+assert_impl_all!(crate::StepCell<u32>: Send);
+assert_not_impl_any!(crate::StepCell<u32>: Sync);
+assert_not_impl_any!(crate::StepCell<Rc<u32>>: Send);
+assert_impl_all!(crate::LStepCell<'static, u32>: Send);
+assert_not_impl_any!(crate::LStepCell<'static, u32>: Sync);
+
+// RCU cells share their snapshots between threads like an `Arc`
+#[cfg(feature = "std")]
+assert_impl_all!(RcuCell<i32>: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_not_impl_any!(RcuCell<Cell<i32>>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(RcuCell<Rc<i32>>: Send, Sync);
+
+// Cell handles act like the `Rc` or `Arc` they wrap
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::CellPtr<i32>: Clone, Eq, core::hash::Hash, Unpin);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::CellPtr<i32>: Send, Sync, Copy);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::WeakCellPtr<i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::CellArc<i32>: Send, Sync, Clone, Eq, core::hash::Hash, Unpin);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::WeakCellArc<i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::CellArc<Cell<i32>>: Sync);
+
+// A recorder travels with its owner, so mustn't stop it being sent
+#[cfg(feature = "record")]
+assert_impl_all!(crate::record::Recorder: Send, Sync, UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "record")]
+assert_impl_all!(crate::record::Event: Send, Sync, Copy);
+
+// Without the diagnostics feature, owners carry no registration, so
+// the marker-based owners stay zero-sized.  With it, registering
+// mustn't stop an owner being sent or shared.
+#[cfg(all(feature = "std", not(feature = "diagnostics")))]
+static_assertions::const_assert_eq!(core::mem::size_of::<TCellOwner<Q>>(), 0);
+#[cfg(all(feature = "std", not(feature = "diagnostics")))]
+static_assertions::const_assert_eq!(core::mem::size_of::<TLCellOwner<Q>>(), 0);
+#[cfg(feature = "diagnostics")]
+assert_impl_all!(crate::diagnostics::OwnerInfo: Send, Sync, Clone);
+
+// A `CellArena` allocates through a `RefCell`, so can be sent but not
+// shared, and `SyncCellArena` can be shared.  Handles act like the
+// references they wrap.
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::CellArena<i32>: Send);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::CellArena<i32>: Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::CellArena<Rc<i32>>: Send);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::SyncCellArena<i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::ArenaRef<'static, i32>: Send, Sync, Copy, Eq, core::hash::Hash);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::ArenaRef<'static, Cell<i32>>: Send, Sync);
+
+// A transfer carries its owner between threads
+#[cfg(feature = "std")]
+assert_impl_all!(crate::OwnerTransfer: Send, Sync);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::OwnerTransfer<crate::QCellOwnerSeq>: Send, Sync);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::TCellOwnerTransfer<Q>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::OwnerTransfer: Clone);
+
+// The branded arenas also allocate through a `RefCell`
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::LCellArena<'static, i32>: Send, Default);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::LCellArena<'static, i32>: Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::TCellArena<Q, i32>: Send, Default);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::TCellArena<Q, i32>: Sync);
+
+// Witnesses act like the owner references they stand for, and are
+// zero-sized.  `Guarded` is `Send` and `Sync` under the same
+// conditions as `TCell`.
+#[cfg(feature = "std")]
+assert_impl_all!(crate::RoWitness<'static, Q>: Send, Sync, Copy, Unpin, UnwindSafe);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::RwWitness<'static, Q>: Send, Sync, Unpin);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::RwWitness<'static, Q>: Clone, UnwindSafe);
+#[cfg(feature = "std")]
+static_assertions::const_assert_eq!(core::mem::size_of::<crate::RwWitness<'static, Q>>(), 0);
+#[cfg(feature = "std")]
+static_assertions::const_assert_eq!(core::mem::size_of::<crate::RoWitness<'static, Q>>(), 0);
+assert_impl_all!(crate::Guarded<Q, i32>: Send, Sync, Unpin, UnwindSafe);
+assert_impl_all!(crate::Guarded<Q, Cell<i32>>: Send);
+assert_not_impl_any!(crate::Guarded<Q, Cell<i32>>: Sync);
+assert_not_impl_any!(crate::Guarded<Q, Rc<i32>>: Send, Sync);
+
+// A `GraphCells` and its edges hold `Rc` and `Weak` references, so
+// stay on one thread
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::GraphCells<i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(crate::CellEdge<i32>: Send, Sync);
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::NodeId: Send, Sync, Copy, Eq, core::hash::Hash, Ord);
+
+// The owner is structurally pinned within a `PinnedOwnerFuture`
+assert_not_impl_any!(crate::PinnedOwnerFuture<(), fn()>: Unpin);
+assert_impl_all!(crate::PinnedOwnerFuture<(), fn()>: Send, Sync);
+
+// A process-wide claim may be released from any thread, but a
+// thread-local claim must stay on its thread.  Neither can be copied,
+// since that would release the claim twice.
+#[cfg(feature = "std")]
+assert_impl_all!(crate::typeid_gate::ClaimGuard<Q>: Send, Sync, Unpin, UnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::typeid_gate::ClaimGuard<Q>: Clone);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::typeid_gate::LocalClaimGuard<Q>: Send, Sync, Clone);
+#[cfg(feature = "std")]
+static_assertions::const_assert_eq!(core::mem::size_of::<crate::typeid_gate::ClaimGuard<Q>>(), 0);
+
+// Built cells are held in `Rc`, so stay on one thread
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::CellGraphBuilder: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::CellLink<i32>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::Declared<i32>: Send, Sync, Copy);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::GraphBuildError: Send, Sync, std::error::Error);
+
+// The host is shared with plugin threads, and the handles cross the C
+// ABI as plain integers
+#[cfg(feature = "ffi-host")]
+assert_impl_all!(crate::ffi_host::FfiHost: Send, Sync);
+#[cfg(feature = "ffi-host")]
+static_assertions::const_assert_eq!(core::mem::size_of::<crate::ffi_host::CellHandle>(), 8);
+#[cfg(feature = "ffi-host")]
+static_assertions::const_assert_eq!(core::mem::size_of::<crate::ffi_host::SessionId>(), 8);
+
+// Parts go to worker threads, but hold the owner's borrow, so can't
+// outlive the partition
+#[cfg(feature = "std")]
+assert_impl_all!(crate::PartOwner<'static, i32>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::PartOwner<'static, core::cell::Cell<i32>>: Send, Sync);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::PartitionError: Send, Sync, std::error::Error);
+
+// Global cells are shared between threads, like `QCell`
+#[cfg(feature = "std")]
+assert_impl_all!(crate::global::GCell<i32>: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::global::GCell<core::cell::Cell<i32>>: Sync);
+
+// Readers share or clone the read half between threads, and the
+// writer half moves to the writer thread
+#[cfg(feature = "std")]
+assert_impl_all!(crate::ReadHalf: Send, Sync, Clone);
+#[cfg(feature = "std")]
+assert_impl_all!(crate::WriteHalf: Send, Sync);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::WriteHalf: Clone);
+
+// A registry only holds weak references to `Send + Sync` cells, so
+// can be kept with an owner that moves between threads
+#[cfg(feature = "alloc")]
+assert_impl_all!(crate::CompactRegistry: Send, Sync, Default);
+
+// Cells treat unwind safety like `RefCell`.  A cell is `UnwindSafe`
+// only if its contents are, and is never `RefUnwindSafe`, since the
+// contents can be changed through a shared reference to the cell.  So
+// a closure passed to `catch_unwind` can't borrow a cell without
+// `AssertUnwindSafe`, and code which catches a panic has to say that
+// it accepts contents that may have been left half-updated.  Owners
+// hold no contents, so they are unaffected.
+type NotUnwindSafe = &'static mut i32;
+assert_not_impl_any!(LCell<'_, i32>: RefUnwindSafe);
+assert_not_impl_any!(LCell<'_, NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(QCell<i32>: RefUnwindSafe);
+assert_not_impl_any!(QCell<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(QCellSingle<i32>: RefUnwindSafe);
+assert_not_impl_any!(QCellSingle<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(XCell<Ids, i32>: RefUnwindSafe);
+assert_not_impl_any!(XCell<Ids, NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(PoisonCell<i32>: RefUnwindSafe);
+assert_not_impl_any!(PoisonCell<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+assert_not_impl_any!(VersionedCell<i32>: RefUnwindSafe);
+assert_not_impl_any!(VersionedCell<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(QCellNoId<i32>: RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(QCellNoId<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(OwnedCells<i32>: RefUnwindSafe);
+#[cfg(feature = "alloc")]
+assert_not_impl_any!(OwnedCells<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TCell<Q, i32>: RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TCell<Q, NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TLCell<Q, i32>: RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TLCell<Q, NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TShardCell<Q, i32>: RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(TShardCell<Q, NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(ErasedLCell<i32>: RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(ErasedLCell<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::global::GCell<i32>: RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(crate::global::GCell<NotUnwindSafe>: UnwindSafe, RefUnwindSafe);
+#[cfg(feature = "std")]
+assert_not_impl_any!(QRefCell<i32>: RefUnwindSafe);
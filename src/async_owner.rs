@@ -0,0 +1,200 @@
+use tokio::sync::Mutex;
+
+use crate::{QCell, QCellOwner, TCell, TCellMarker, TCellOwner};
+
+/// Owner shared between async tasks, behind a `tokio` mutex.
+///
+/// Holding a borrow of a cell across an `.await` is a common source
+/// of trouble when sharing an owner between tasks, because the mutex
+/// guard has to be kept alive for as long as the borrow, and that
+/// gets awkward inside async blocks.  So instead this type only gives
+/// access to the owner within a synchronous closure.  The mutex is
+/// locked, the closure is run, and the mutex is released before the
+/// returned future completes.  Since the closure is not async, and
+/// its result can't borrow from the owner, no borrow can cross an
+/// `.await` point.
+///
+/// Typically this would be shared between tasks using an `Arc`:
+///
+/// ```
+///# use qcell::{AsyncOwner, QCellOwner};
+///# use std::sync::Arc;
+///# #[tokio::main(flavor = "multi_thread", worker_threads = 3)]
+///# async fn main() {
+/// let owner = Arc::new(AsyncOwner::new(QCellOwner::new()));
+/// let count = owner.with_ro(|o| Arc::new(o.cell(0u32))).await;
+/// let total = owner.with_ro(|o| Arc::new(o.cell(0u32))).await;
+///
+/// let tasks: Vec<_> = (1..=3)
+///     .map(|n| {
+///         let (owner, count, total) = (owner.clone(), count.clone(), total.clone());
+///         tokio::spawn(async move {
+///             for _ in 0..100 {
+///                 owner.with_cells2(&count, &total, |c, t| {
+///                     *c += 1;
+///                     *t += n;
+///                 }).await;
+///                 tokio::task::yield_now().await;
+///             }
+///         })
+///     })
+///     .collect();
+/// for task in tasks {
+///     task.await.unwrap();
+/// }
+/// assert_eq!(owner.with_cell(&count, |c| *c).await, 300);
+/// assert_eq!(owner.with_cell(&total, |t| *t).await, 600);
+///# }
+/// ```
+///
+/// See the [`doctest_async_owner`] module for examples of what is
+/// rejected at compile time.
+///
+/// [`doctest_async_owner`]: doctest_async_owner/index.html
+#[cfg_attr(docsrs, doc(cfg(feature = "async-tokio")))]
+pub struct AsyncOwner<O> {
+    owner: Mutex<O>,
+}
+
+impl<O> AsyncOwner<O> {
+    /// Wrap an owner so that it can be shared between async tasks.
+    pub fn new(owner: O) -> Self {
+        Self {
+            owner: Mutex::new(owner),
+        }
+    }
+
+    /// Lock the owner, and call `f` with an immutable reference to
+    /// it.  The lock is released when `f` returns.
+    pub async fn with_ro<R>(&self, f: impl FnOnce(&O) -> R) -> R {
+        let guard = self.owner.lock().await;
+        f(&guard)
+    }
+
+    /// Lock the owner, and call `f` with a mutable reference to it.
+    /// The lock is released when `f` returns.
+    pub async fn with_rw<R>(&self, f: impl FnOnce(&mut O) -> R) -> R {
+        let mut guard = self.owner.lock().await;
+        f(&mut guard)
+    }
+
+    /// Get a mutable reference to the owner.  No locking is required
+    /// since this borrows `self` mutably.
+    pub fn get_mut(&mut self) -> &mut O {
+        self.owner.get_mut()
+    }
+
+    /// Unwrap the owner.
+    pub fn into_inner(self) -> O {
+        self.owner.into_inner()
+    }
+}
+
+macro_rules! async_owner_cells {
+    ([$($gen:tt)*] $owner:ty, $cell:ident $(<$q:ident>)?) => {
+        impl<$($gen)*> AsyncOwner<$owner> {
+            /// Lock the owner, borrow the contents of `cell` mutably,
+            /// and call `f` on it.  The lock is released when `f`
+            /// returns.  Panics if the cell is not owned by this
+            /// owner.
+            pub async fn with_cell<T: ?Sized, R>(
+                &self,
+                cell: &$cell<$($q,)? T>,
+                f: impl FnOnce(&mut T) -> R,
+            ) -> R {
+                self.with_rw(|owner| f(owner.rw(cell))).await
+            }
+
+            /// Lock the owner, borrow the contents of two cells
+            /// mutably, and call `f` on them.  The lock is released
+            /// when `f` returns.  Panics if the two cells are the
+            /// same, or if either is not owned by this owner.
+            pub async fn with_cells2<T: ?Sized, U: ?Sized, R>(
+                &self,
+                cell1: &$cell<$($q,)? T>,
+                cell2: &$cell<$($q,)? U>,
+                f: impl FnOnce(&mut T, &mut U) -> R,
+            ) -> R {
+                self.with_rw(|owner| {
+                    let (v1, v2) = owner.rw2(cell1, cell2);
+                    f(v1, v2)
+                })
+                .await
+            }
+        }
+    };
+}
+
+async_owner_cells!([] QCellOwner, QCell);
+async_owner_cells!([Q: TCellMarker] TCellOwner<Q>, TCell<Q>);
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncOwner;
+    use crate::{QCell, QCellOwner, TCell, TCellOwner};
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+    async fn async_owner_tasks() {
+        let owner = Arc::new(AsyncOwner::new(QCellOwner::new()));
+        let cells: Arc<Vec<QCell<u64>>> =
+            Arc::new(owner.with_ro(|o| (0..3).map(|_| o.cell(0)).collect()).await);
+        let tasks: Vec<_> = (0..3)
+            .map(|n| {
+                let (owner, cells) = (owner.clone(), cells.clone());
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        owner.with_cell(&cells[n], |v| *v += 1).await;
+                        owner
+                            .with_cells2(&cells[n], &cells[(n + 1) % 3], |a, b| {
+                                *a += 1;
+                                *b += 10;
+                            })
+                            .await;
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let totals = owner
+            .with_ro(|o| cells.iter().map(|c| *o.ro(c)).collect::<Vec<_>>())
+            .await;
+        assert_eq!(totals, [12000, 12000, 12000]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn async_owner_tcell() {
+        crate::marker!(struct Marker;);
+        let owner = Arc::new(AsyncOwner::new(TCellOwner::<Marker>::new()));
+        let cell1 = Arc::new(TCell::new(String::new()));
+        let cell2 = Arc::new(TCell::new(0usize));
+        let task = {
+            let (owner, cell1, cell2) = (owner.clone(), cell1.clone(), cell2.clone());
+            tokio::spawn(async move {
+                owner
+                    .with_cells2(&*cell1, &*cell2, |s, n| {
+                        s.push_str("abc");
+                        *n = s.len();
+                    })
+                    .await
+            })
+        };
+        task.await.unwrap();
+        assert_eq!(owner.with_cell(&*cell2, |n| *n).await, 3);
+        let mut owner = Arc::try_unwrap(owner).ok().unwrap();
+        assert_eq!(owner.get_mut().ro(&cell1), "abc");
+        let owner = owner.into_inner();
+        assert_eq!(owner.ro(&cell1), "abc");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Illegal to borrow same QCell twice")]
+    async fn async_owner_same_cell() {
+        let owner = AsyncOwner::new(QCellOwner::new());
+        let cell = owner.with_ro(|o| o.cell(0)).await;
+        owner.with_cells2(&cell, &cell, |_, _| ()).await;
+    }
+}
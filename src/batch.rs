@@ -0,0 +1,158 @@
+use crate::panic_messages;
+
+#[cold]
+#[inline(never)]
+fn length_panic(cells: usize, values: usize) -> ! {
+    panic!(
+        "{}: {} cells but {} values",
+        panic_messages::BATCH_LENGTH_MISMATCH,
+        cells,
+        values
+    );
+}
+
+// Panic if the number of cells passed to `read_into_slice()` or
+// `write_from_slice()` doesn't match the number of values.  This is
+// checked before anything is read or written.
+#[inline]
+pub(crate) fn length_check(cells: usize, values: usize) {
+    if cells != values {
+        length_panic(cells, values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LCellOwner, QCell, QCellOwnerPinned, QCellOwnerSeq};
+    use core::pin::Pin;
+    use std::convert::TryInto;
+
+    #[test]
+    fn batch_round_trip_64() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let params: Vec<QCell<f32>> = (0..64).map(|i| owner.cell(i as f32)).collect();
+        let refs: Vec<&QCell<f32>> = params.iter().collect();
+        let cells: &[&QCell<f32>; 64] = refs.as_slice().try_into().unwrap();
+
+        let mut block = [0.0; 64];
+        owner.read_into(cells, &mut block);
+        assert!(block.iter().enumerate().all(|(i, v)| *v == i as f32));
+        for v in &mut block {
+            *v *= 0.5;
+        }
+        owner.write_from(cells, &block);
+        let mut check = [0.0; 64];
+        owner.read_into_slice(&refs, &mut check);
+        assert!(check.iter().enumerate().all(|(i, v)| *v == i as f32 * 0.5));
+        assert_eq!(owner.get(&params[63]), 31.5);
+    }
+
+    #[test]
+    fn batch_write_from_aliased() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let c1 = owner.cell(0);
+        let c2 = owner.cell(0);
+        owner.write_from(&[&c1, &c2, &c1], &[1, 2, 3]);
+        assert_eq!((owner.get(&c1), owner.get(&c2)), (3, 2));
+        owner.write_from_slice(&[&c2, &c2], &[4, 5]);
+        assert_eq!(owner.get(&c2), 5);
+
+        let mut out = [0; 3];
+        owner.read_into(&[&c2, &c1, &c2], &mut out);
+        assert_eq!(out, [5, 3, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "3 cells but 2 values")]
+    fn batch_read_into_slice_length() {
+        // Safety: No ID collisions are being exploited
+        let owner = unsafe { QCellOwnerSeq::new() };
+        let c = owner.cell(1);
+        owner.read_into_slice(&[&c, &c, &c], &mut [0; 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 cells but 2 values")]
+    fn batch_write_from_slice_length() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let c = owner.cell(1);
+        owner.write_from_slice(&[&c], &[2, 3]);
+    }
+
+    #[test]
+    fn batch_write_from_slice_length_writes_nothing() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let c = owner.cell(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            owner.write_from_slice(&[&c, &c, &c], &[2, 3]);
+        }));
+        assert!(result.is_err());
+        assert_eq!(owner.get(&c), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn batch_read_into_wrong_owner() {
+        // Safety: No ID collisions are being exploited
+        let owner1 = unsafe { QCellOwnerSeq::new() };
+        let owner2 = unsafe { QCellOwnerSeq::new() };
+        let c1 = owner1.cell(1);
+        let c2 = owner2.cell(2);
+        owner1.read_into(&[&c1, &c2], &mut [0; 2]);
+    }
+
+    #[test]
+    fn batch_pinned() {
+        let mut owner = Box::pin(QCellOwnerPinned::new());
+        let c1 = owner.as_ref().cell(1);
+        let c2 = owner.as_ref().cell(2);
+        owner.as_mut().write_from(&[&c1, &c2], &[10, 20]);
+        let mut out = [0; 2];
+        Pin::as_ref(&owner).read_into(&[&c2, &c1], &mut out);
+        assert_eq!(out, [20, 10]);
+    }
+
+    #[test]
+    fn batch_lcell() {
+        LCellOwner::scope(|mut owner| {
+            let c1 = owner.cell(1);
+            let c2 = owner.cell(2);
+            owner.write_from(&[&c1, &c2], &[3, 4]);
+            let mut out = [0; 2];
+            owner.read_into_slice(&[&c2, &c1], &mut out);
+            assert_eq!(out, [4, 3]);
+        });
+    }
+
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    #[test]
+    fn batch_tcell() {
+        use crate::{TCell, TCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let c1 = TCell::new(1);
+        let c2 = TCell::new(2);
+        owner.write_from_slice(&[&c1, &c2, &c1], &[3, 4, 5]);
+        let mut out = [0; 2];
+        owner.read_into(&[&c1, &c2], &mut out);
+        assert_eq!(out, [5, 4]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn batch_tlcell() {
+        use crate::{TLCell, TLCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let c1 = TLCell::new(1);
+        let c2 = TLCell::new(2);
+        owner.write_from(&[&c1, &c2], &[3, 4]);
+        let mut out = [0; 2];
+        owner.read_into(&[&c2, &c1], &mut out);
+        assert_eq!(out, [4, 3]);
+    }
+}
@@ -0,0 +1,657 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[cfg(feature = "alloc")]
+use crate::QCellOwner;
+use crate::{panic_messages, QCellOwnerID, QCellOwnerSeq};
+
+#[cold]
+#[inline(never)]
+fn bad_owner_panic() -> ! {
+    panic!("{}", panic_messages::BIT_CELLS_INCORRECT_OWNER);
+}
+
+#[cold]
+#[inline(never)]
+fn index_panic(index: usize, len: usize) -> ! {
+    panic!(
+        "{}: index {} but length {}",
+        panic_messages::BIT_CELLS_INDEX_OUT_OF_RANGE,
+        index,
+        len
+    );
+}
+
+#[cold]
+#[inline(never)]
+fn range_panic(range: &Range<usize>, len: usize) -> ! {
+    panic!(
+        "{}: range {:?} but length {}",
+        panic_messages::BIT_CELLS_INDEX_OUT_OF_RANGE,
+        range,
+        len
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[cold]
+#[inline(never)]
+fn overlap_panic(range1: &Range<usize>, range2: &Range<usize>) -> ! {
+    panic!(
+        "{}: ranges {:?} and {:?}",
+        panic_messages::BIT_CELLS_RANGES_OVERLAP,
+        range1,
+        range2
+    );
+}
+
+// The bits are kept in atomic bytes, although access is still
+// controlled by the owner.  This is so that the views handed out by
+// `split_disjoint` may share a byte at the ends of their ranges and
+// yet be used from different threads.  Since the owner already
+// orders all other accesses, `Relaxed` is enough.
+const ORDER: Ordering = Ordering::Relaxed;
+
+// A run of bits within the bytes, with the indices already checked.
+// Public only so that it may appear in the sealed trait.
+#[derive(Clone, Copy)]
+pub struct Bits<'a> {
+    bytes: &'a [AtomicU8],
+    start: usize,
+    len: usize,
+}
+
+impl<'a> Bits<'a> {
+    #[inline]
+    fn check(&self, index: usize) -> (&'a AtomicU8, u8) {
+        if index >= self.len {
+            index_panic(index, self.len);
+        }
+        let bit = self.start + index;
+        (&self.bytes[bit / 8], 1 << (bit % 8))
+    }
+
+    #[inline]
+    fn sub(self, range: Range<usize>) -> Self {
+        if range.start > range.end || range.end > self.len {
+            range_panic(&range, self.len);
+        }
+        Self {
+            bytes: self.bytes,
+            start: self.start + range.start,
+            len: range.end - range.start,
+        }
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> bool {
+        let (byte, mask) = self.check(index);
+        byte.load(ORDER) & mask != 0
+    }
+
+    #[inline]
+    fn set(&self, index: usize, value: bool) {
+        let (byte, mask) = self.check(index);
+        if value {
+            byte.fetch_or(mask, ORDER);
+        } else {
+            byte.fetch_and(!mask, ORDER);
+        }
+    }
+
+    #[inline]
+    fn toggle(&self, index: usize) -> bool {
+        let (byte, mask) = self.check(index);
+        byte.fetch_xor(mask, ORDER) & mask == 0
+    }
+
+    // Call `f` with each byte covering the bits, and a mask of the
+    // bits within it
+    fn for_each_byte(&self, mut f: impl FnMut(&AtomicU8, u8)) {
+        let end = self.start + self.len;
+        let mut bit = self.start;
+        while bit < end {
+            let shift = bit % 8;
+            let count = (8 - shift).min(end - bit);
+            let mask = ((1_u16 << count) - 1) << shift;
+            f(&self.bytes[bit / 8], mask as u8);
+            bit += count;
+        }
+    }
+
+    fn fill(&self, value: bool) {
+        self.for_each_byte(|byte, mask| {
+            if value {
+                byte.fetch_or(mask, ORDER);
+            } else {
+                byte.fetch_and(!mask, ORDER);
+            }
+        });
+    }
+
+    fn count_ones(&self) -> usize {
+        let mut count = 0;
+        self.for_each_byte(|byte, mask| count += (byte.load(ORDER) & mask).count_ones() as usize);
+        count
+    }
+}
+
+mod sealed {
+    use super::Bits;
+    use crate::QCellOwnerID;
+
+    pub trait Sealed {
+        fn owner_id(&self) -> QCellOwnerID;
+        fn bits(&self) -> Bits<'_>;
+    }
+}
+
+/// A set of bits owned by a single owner, each of which acts like a
+/// separate `QCell<bool>`.  Implemented by [`BitCells`] and
+/// [`BitCellArray`].  This trait is sealed, so can't be implemented
+/// outside this crate.
+///
+/// [`BitCells`]: struct.BitCells.html
+/// [`BitCellArray`]: struct.BitCellArray.html
+pub trait BitCellSet: sealed::Sealed {}
+
+/// Growable set of bits owned by one owner, where each bit acts like
+/// a separate `QCell<bool>`, but takes only one bit of memory.
+///
+/// A `QCell<bool>` takes the same space as the owner ID, typically 8
+/// or 16 bytes.  With thousands of flags that adds up.  This stores
+/// the owner ID once, and the bits are accessed by index through the
+/// owner using calls such as [`QCellOwner::get_bit`] and
+/// [`QCellOwner::set_bit`].  The ID is checked on each access, as for
+/// [`QCell`].
+///
+/// Different parts of the code can be given exclusive ranges of bits
+/// at the same time with [`QCellOwner::split_disjoint`], which hands
+/// out a [`BitSliceMut`] for each range after checking that they
+/// don't overlap.
+///
+/// ```
+///# use qcell::{BitCells, QCellOwner};
+/// let mut owner = QCellOwner::new();
+/// let mut flags = BitCells::with_len(&owner, 100);
+/// owner.set_bit(&flags, 3, true);
+/// owner.set_range(&flags, 50..60, true);
+/// assert!(owner.toggle_bit(&flags, 4));
+/// assert_eq!(owner.count_ones(&flags), 12);
+///
+/// let mut views = owner.split_disjoint(&flags, &[0..50, 50..100]);
+/// let (low, high) = views.split_at_mut(1);
+/// low[0].fill(false);
+/// high[0].set(0, false);
+/// assert_eq!(high[0].count_ones(), 9);
+///
+/// flags.push(true);
+/// assert!(owner.get_bit(&flags, 100));
+/// ```
+///
+/// For a fixed number of bits without `alloc`, see
+/// [`BitCellArray`].
+///
+/// [`BitCellArray`]: struct.BitCellArray.html
+/// [`BitSliceMut`]: struct.BitSliceMut.html
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner::get_bit`]: struct.QCellOwner.html#method.get_bit
+/// [`QCellOwner::set_bit`]: struct.QCellOwner.html#method.set_bit
+/// [`QCellOwner::split_disjoint`]: struct.QCellOwner.html#method.split_disjoint
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub struct BitCells {
+    owner: QCellOwnerID,
+    bytes: Vec<AtomicU8>,
+    len: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl BitCells {
+    /// Create an empty set owned by the given owner, or owner-ID.
+    /// See [`QCell::new`] for the forms accepted.
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn new(id: impl Into<QCellOwnerID>) -> Self {
+        Self::with_len(id, 0)
+    }
+
+    /// Create a set of `len` bits, all clear
+    pub fn with_len(id: impl Into<QCellOwnerID>, len: usize) -> Self {
+        let mut bytes = Vec::new();
+        bytes.resize_with((len + 7) / 8, || AtomicU8::new(0));
+        Self {
+            owner: id.into(),
+            bytes,
+            len,
+        }
+    }
+
+    /// Get the ID of the owner of the bits
+    #[inline]
+    pub fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    /// Get the number of bits
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Test whether there are no bits
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a bit to the end
+    #[inline]
+    pub fn push(&mut self, value: bool) {
+        self.resize(self.len + 1, value);
+    }
+
+    /// Change the number of bits, setting any added bits to `value`.
+    /// This borrows the set mutably, so there can be no active
+    /// borrows.
+    pub fn resize(&mut self, len: usize, value: bool) {
+        if len < self.len {
+            // Clear the dropped bits in the last byte kept, so that
+            // growing again later starts from clear bits
+            sealed::Sealed::bits(self).sub(len..self.len).fill(false);
+        }
+        self.bytes.resize_with((len + 7) / 8, || AtomicU8::new(0));
+        let old_len = self.len;
+        self.len = len;
+        if len > old_len && value {
+            sealed::Sealed::bits(self).sub(old_len..len).fill(true);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl sealed::Sealed for BitCells {
+    #[inline]
+    fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    #[inline]
+    fn bits(&self) -> Bits<'_> {
+        Bits {
+            bytes: &self.bytes,
+            start: 0,
+            len: self.len,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl BitCellSet for BitCells {}
+
+/// Fixed-size set of `8 * BYTES` bits owned by one owner, where each
+/// bit acts like a separate `QCell<bool>`.  This is the same as
+/// [`BitCells`] except that it doesn't need `alloc`, and can't grow.
+///
+/// ```
+///# use qcell::{BitCellArray, QCellOwnerSeq};
+/// // Safety: No other owner is created with this ID
+/// let mut owner = unsafe { QCellOwnerSeq::new() };
+/// let flags = BitCellArray::<4>::new(&owner);
+/// assert_eq!(flags.len(), 32);
+/// owner.set_bit(&flags, 31, true);
+/// assert!(!owner.toggle_bit(&flags, 31));
+/// assert_eq!(owner.count_ones(&flags), 0);
+/// ```
+///
+/// [`BitCells`]: struct.BitCells.html
+pub struct BitCellArray<const BYTES: usize> {
+    owner: QCellOwnerID,
+    bytes: [AtomicU8; BYTES],
+}
+
+impl<const BYTES: usize> BitCellArray<BYTES> {
+    /// Create a set of bits, all clear, owned by the given owner, or
+    /// owner-ID.  See [`QCell::new`] for the forms accepted.
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn new(id: impl Into<QCellOwnerID>) -> Self {
+        Self {
+            owner: id.into(),
+            bytes: [(); BYTES].map(|_| AtomicU8::new(0)),
+        }
+    }
+
+    /// Get the ID of the owner of the bits
+    #[inline]
+    pub fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    /// Get the number of bits, which is `8 * BYTES`
+    #[inline]
+    pub const fn len(&self) -> usize {
+        BYTES * 8
+    }
+
+    /// Test whether there are no bits, i.e. `BYTES` is 0
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        BYTES == 0
+    }
+}
+
+impl<const BYTES: usize> sealed::Sealed for BitCellArray<BYTES> {
+    #[inline]
+    fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    #[inline]
+    fn bits(&self) -> Bits<'_> {
+        Bits {
+            bytes: &self.bytes,
+            start: 0,
+            len: BYTES * 8,
+        }
+    }
+}
+
+impl<const BYTES: usize> BitCellSet for BitCellArray<BYTES> {}
+
+/// Exclusive view of a range of bits of a [`BitCells`] or
+/// [`BitCellArray`], returned by `split_disjoint` on the owner.
+/// Indices are relative to the start of the range.
+///
+/// The views of one call may be used at the same time, including from
+/// different threads, even where their ranges share a byte.
+///
+/// [`BitCells`]: struct.BitCells.html
+/// [`BitCellArray`]: struct.BitCellArray.html
+pub struct BitSliceMut<'a> {
+    bits: Bits<'a>,
+}
+
+impl<'a> BitSliceMut<'a> {
+    /// Get the number of bits in the view
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bits.len
+    }
+
+    /// Test whether the view is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bits.len == 0
+    }
+
+    /// Get a bit.  Panics if `index` is out of range.
+    #[inline]
+    pub fn get(&self, index: usize) -> bool {
+        self.bits.get(index)
+    }
+
+    /// Set a bit to `value`.  Panics if `index` is out of range.
+    #[inline]
+    pub fn set(&mut self, index: usize, value: bool) {
+        self.bits.set(index, value);
+    }
+
+    /// Invert a bit, returning its new value.  Panics if `index` is
+    /// out of range.
+    #[inline]
+    pub fn toggle(&mut self, index: usize) -> bool {
+        self.bits.toggle(index)
+    }
+
+    /// Set all the bits of the view to `value`
+    #[inline]
+    pub fn fill(&mut self, value: bool) {
+        self.bits.fill(value);
+    }
+
+    /// Count the bits of the view which are set
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones()
+    }
+}
+
+// Get the bits after checking the owner
+#[inline]
+fn owned_bits(cells: &impl BitCellSet, id: QCellOwnerID) -> Bits<'_> {
+    if !cells.owner_id().matches(id) {
+        bad_owner_panic();
+    }
+    cells.bits()
+}
+
+macro_rules! bit_cells_access {
+    ($owner:ty, $name:literal) => {
+        impl $owner {
+            #[doc = concat!("Get a bit of a [`BitCells`] or [`BitCellArray`].  Panics if the bits are not owned by this `", $name, "`, or if `index` is out of range.")]
+            ///
+            /// [`BitCells`]: struct.BitCells.html
+            /// [`BitCellArray`]: struct.BitCellArray.html
+            #[inline]
+            pub fn get_bit(&self, cells: &impl BitCellSet, index: usize) -> bool {
+                owned_bits(cells, self.id()).get(index)
+            }
+
+            /// Set a bit of a [`BitCells`] or [`BitCellArray`] to
+            /// `value`.  Panics if the bits are not owned by this
+            /// owner, or if `index` is out of range.
+            ///
+            /// [`BitCells`]: struct.BitCells.html
+            /// [`BitCellArray`]: struct.BitCellArray.html
+            #[inline]
+            pub fn set_bit(&mut self, cells: &impl BitCellSet, index: usize, value: bool) {
+                owned_bits(cells, self.id()).set(index, value);
+            }
+
+            /// Invert a bit of a [`BitCells`] or [`BitCellArray`],
+            /// returning its new value.  Panics if the bits are not
+            /// owned by this owner, or if `index` is out of range.
+            ///
+            /// [`BitCells`]: struct.BitCells.html
+            /// [`BitCellArray`]: struct.BitCellArray.html
+            #[inline]
+            pub fn toggle_bit(&mut self, cells: &impl BitCellSet, index: usize) -> bool {
+                owned_bits(cells, self.id()).toggle(index)
+            }
+
+            /// Set a range of bits of a [`BitCells`] or
+            /// [`BitCellArray`] to `value`.  Panics if the bits are
+            /// not owned by this owner, or if `range` is out of
+            /// range.
+            ///
+            /// [`BitCells`]: struct.BitCells.html
+            /// [`BitCellArray`]: struct.BitCellArray.html
+            pub fn set_range(&mut self, cells: &impl BitCellSet, range: Range<usize>, value: bool) {
+                owned_bits(cells, self.id()).sub(range).fill(value);
+            }
+
+            /// Count the bits of a [`BitCells`] or [`BitCellArray`]
+            /// which are set.  Panics if the bits are not owned by
+            /// this owner.
+            ///
+            /// [`BitCells`]: struct.BitCells.html
+            /// [`BitCellArray`]: struct.BitCellArray.html
+            pub fn count_ones(&self, cells: &impl BitCellSet) -> usize {
+                owned_bits(cells, self.id()).count_ones()
+            }
+
+            /// Get exclusive views of several ranges of bits of a
+            /// [`BitCells`] or [`BitCellArray`] at the same time, one
+            /// for each range, in the order given.  This allows
+            /// different parts of the code to be handed their own
+            /// bits under a single borrow of the owner.  Panics if
+            /// any two ranges overlap, or if any is out of range, or
+            /// if the bits are not owned by this owner.  Empty ranges
+            /// overlap nothing.
+            ///
+            /// [`BitCells`]: struct.BitCells.html
+            /// [`BitCellArray`]: struct.BitCellArray.html
+            #[cfg(feature = "alloc")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+            pub fn split_disjoint<'a>(
+                &'a mut self,
+                cells: &'a impl BitCellSet,
+                ranges: &[Range<usize>],
+            ) -> Vec<BitSliceMut<'a>> {
+                let bits = owned_bits(cells, self.id());
+                let views: Vec<BitSliceMut<'a>> = ranges
+                    .iter()
+                    .map(|r| BitSliceMut { bits: bits.sub(r.clone()) })
+                    .collect();
+                let mut order: Vec<&Range<usize>> = ranges.iter().filter(|r| r.start < r.end).collect();
+                order.sort_unstable_by_key(|r| r.start);
+                for pair in order.windows(2) {
+                    if pair[0].end > pair[1].start {
+                        overlap_panic(pair[0], pair[1]);
+                    }
+                }
+                views
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+bit_cells_access!(QCellOwner, "QCellOwner");
+bit_cells_access!(QCellOwnerSeq, "QCellOwnerSeq");
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{BitCellArray, BitCells};
+    use crate::{QCell, QCellOwner, QCellOwnerSeq};
+
+    #[test]
+    fn bit_cells_byte_boundaries() {
+        let mut owner = QCellOwner::new();
+        let flags = BitCells::with_len(&owner, 20);
+        for i in [0, 7, 8, 15, 16, 19] {
+            owner.set_bit(&flags, i, true);
+        }
+        let set: Vec<usize> = (0..20).filter(|&i| owner.get_bit(&flags, i)).collect();
+        assert_eq!(set, [0, 7, 8, 15, 16, 19]);
+        owner.set_bit(&flags, 8, false);
+        assert!(!owner.get_bit(&flags, 8));
+        assert!(owner.get_bit(&flags, 7));
+        assert!(owner.toggle_bit(&flags, 9));
+        assert!(!owner.toggle_bit(&flags, 7));
+        assert_eq!(owner.count_ones(&flags), 5);
+
+        owner.set_range(&flags, 6..17, true);
+        assert_eq!(owner.count_ones(&flags), 13);
+        assert!(!owner.get_bit(&flags, 5) && !owner.get_bit(&flags, 17));
+        owner.set_range(&flags, 1..19, false);
+        assert_eq!(owner.count_ones(&flags), 2);
+        owner.set_range(&flags, 4..4, true);
+        assert_eq!(owner.count_ones(&flags), 2);
+    }
+
+    #[test]
+    fn bit_cells_resize() {
+        let mut owner = QCellOwner::new();
+        let mut flags = BitCells::new(&owner);
+        assert!(flags.is_empty());
+        flags.push(true);
+        flags.resize(10, true);
+        assert_eq!(flags.len(), 10);
+        assert_eq!(owner.count_ones(&flags), 10);
+        // Bits dropped by shrinking come back clear
+        flags.resize(3, false);
+        flags.resize(12, false);
+        assert_eq!(owner.count_ones(&flags), 3);
+        owner.set_bit(&flags, 11, true);
+        assert!(flags.owner_id() == owner.id());
+    }
+
+    #[test]
+    fn bit_cells_disjoint_views() {
+        let mut owner = QCellOwner::new();
+        let flags = BitCells::with_len(&owner, 40);
+        let mut views = owner.split_disjoint(&flags, &[20..40, 3..11, 11..20, 0..0]);
+        assert_eq!(
+            views.iter().map(|v| v.len()).collect::<Vec<_>>(),
+            [20, 8, 9, 0]
+        );
+        // Mutate the views in turn whilst they are all held
+        for i in 0..20 {
+            for view in views.iter_mut() {
+                if i < view.len() && i % 2 == 0 {
+                    view.toggle(i);
+                }
+            }
+        }
+        views[0].fill(true);
+        assert!(views[1].get(0) && !views[1].get(1));
+        views[2].set(8, true);
+        assert_eq!(views[0].count_ones(), 20);
+        drop(views);
+        let set: Vec<usize> = (0..40).filter(|&i| owner.get_bit(&flags, i)).collect();
+        let expected: Vec<usize> = [3, 5, 7, 9, 11, 13, 15, 17, 19]
+            .iter()
+            .copied()
+            .chain(20..40)
+            .collect();
+        assert_eq!(set, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap")]
+    fn bit_cells_overlapping_ranges() {
+        let mut owner = QCellOwner::new();
+        let flags = BitCells::with_len(&owner, 16);
+        let _ = owner.split_disjoint(&flags, &[0..4, 10..16, 3..8]); // Panic here
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn bit_cells_range_out_of_range() {
+        let mut owner = QCellOwner::new();
+        let flags = BitCells::with_len(&owner, 16);
+        let _ = owner.split_disjoint(&flags, &[0..8, 8..17]); // Panic here
+    }
+
+    #[test]
+    #[should_panic(expected = "incorrect owner")]
+    fn bit_cells_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let flags = BitCells::with_len(&owner1, 8);
+        owner2.get_bit(&flags, 0); // Panic here
+    }
+
+    #[test]
+    fn bit_cell_array() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let flags = BitCellArray::<3>::new(&owner);
+        assert_eq!(flags.len(), 24);
+        owner.set_range(&flags, 5..19, true);
+        owner.set_bit(&flags, 23, true);
+        assert_eq!(owner.count_ones(&flags), 15);
+        let mut views = owner.split_disjoint(&flags, &[0..8, 8..24]);
+        views[0].fill(false);
+        views[1].set(15, false);
+        assert_eq!(views[1].count_ones(), 11);
+    }
+
+    #[test]
+    fn bit_cells_size() {
+        use core::mem::size_of;
+        // 1000 flags as bits versus as separate cells
+        let owner = QCellOwner::new();
+        let flags = BitCells::with_len(&owner, 1000);
+        assert_eq!(flags.bytes.len(), 125);
+        let array = size_of::<BitCellArray<125>>();
+        let cells = 1000 * size_of::<QCell<bool>>();
+        assert!(array <= 125 + size_of::<QCell<()>>() + 8, "{}", array);
+        assert!(array * 30 < cells, "{} vs {}", array, cells);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{LCell, LCellOwner, QCell, QCellBorrow, TCell, TCellMarker, TCellOwner};
+#[cfg(feature = "std")]
+use crate::{TLCell, TLCellOwner};
+
+/// Mutable borrow of an owner which only allows reading cells if
+/// `READ` is `true`, and only allows writing cells if `WRITE` is
+/// `true`.
+///
+/// This is for handing a component a restricted view of an owner,
+/// for example letting it append records to audit log cells which it
+/// must not be able to read back.  Create one with the `capability`
+/// method on [`QCellOwner`], [`QCellOwnerSeq`], [`TCellOwner`],
+/// [`TLCellOwner`] or [`LCellOwner`], or with [`Capability::new`]
+/// for any other [`QCellBorrow`] owner.  There is no cost over using
+/// the owner directly.  The usual combinations have aliases:
+/// [`ReadOnly`] and [`WriteOnly`].
+///
+/// The methods available depend on the capabilities:
+///
+/// - With `READ`: `ro`, `get` and `get_cloned`
+/// - With `WRITE`: `set`, `write_from` and `write_from_slice`
+/// - With both: `rw` and `update` as well
+///
+/// A `&mut T` can be read through as well as written, so a
+/// write-only capability doesn't offer `rw`, or anything else that
+/// hands out a reference to the contents or returns the old value.
+/// Values can only be moved or copied in.
+///
+/// ```
+///# use qcell::{QCell, QCellOwnerSeq, WriteOnly};
+/// struct Auditor<'a> {
+///     log: WriteOnly<'a, QCellOwnerSeq>,
+/// }
+///
+/// impl Auditor<'_> {
+///     fn record(&mut self, slot: &QCell<Option<String>>, event: &str) {
+///         self.log.set(slot, Some(event.to_string()));
+///         // self.log.ro(slot) doesn't compile, so earlier
+///         // records can't be read back
+///     }
+/// }
+///
+/// // Safety: No other owner is created with this ID
+/// let mut owner = unsafe { QCellOwnerSeq::new() };
+/// let slots: Vec<QCell<Option<String>>> = (0..2).map(|_| owner.cell(None)).collect();
+/// let mut auditor = Auditor { log: owner.capability::<false, true>() };
+/// auditor.record(&slots[0], "login");
+/// auditor.record(&slots[1], "logout");
+///
+/// // The full owner can read the log afterwards
+/// assert_eq!(owner.ro(&slots[1]).as_deref(), Some("logout"));
+/// ```
+///
+/// # Threat model
+///
+/// This stops code holding a capability from getting at cell
+/// contents through the capability's own API.  It is not a sandbox,
+/// and it doesn't cover these cases:
+///
+/// - A value written may share state with values that the writer
+///   keeps, for example an `Rc<RefCell<_>>` or an `Arc`, or may have
+///   interior mutability.  Later changes made through that shared
+///   state can be seen by the writer.
+/// - Writing drops the old value, so the old value's `Drop`
+///   implementation runs, although the writer doesn't get to see it.
+/// - Passing a cell which belongs to a different owner panics, so
+///   the writer can learn which owner a cell belongs to.
+/// - Unsafe code can of course do anything.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`TLCellOwner`]: struct.TLCellOwner.html
+/// [`LCellOwner`]: struct.LCellOwner.html
+/// [`QCellBorrow`]: trait.QCellBorrow.html
+/// [`Capability::new`]: struct.Capability.html#method.new
+/// [`ReadOnly`]: type.ReadOnly.html
+/// [`WriteOnly`]: type.WriteOnly.html
+pub struct Capability<'a, O, const READ: bool, const WRITE: bool> {
+    owner: &'a mut O,
+}
+
+/// A [`Capability`] which can read cells but not write them
+///
+/// [`Capability`]: struct.Capability.html
+pub type ReadOnly<'a, O> = Capability<'a, O, true, false>;
+
+/// A [`Capability`] which can write cells but not read them
+///
+/// [`Capability`]: struct.Capability.html
+pub type WriteOnly<'a, O> = Capability<'a, O, false, true>;
+
+impl<'a, O, const READ: bool, const WRITE: bool> Capability<'a, O, READ, WRITE> {
+    /// Wrap a mutable borrow of an owner.  The owner can't be used
+    /// directly until the capability is dropped.
+    #[inline]
+    pub fn new(owner: &'a mut O) -> Self {
+        Self { owner }
+    }
+
+    /// Reborrow this capability for a shorter lifetime, to pass it
+    /// to a call that takes a `Capability` by value and still be able
+    /// to use it afterwards
+    #[inline]
+    pub fn reborrow(&mut self) -> Capability<'_, O, READ, WRITE> {
+        Capability { owner: self.owner }
+    }
+}
+
+macro_rules! capability_methods {
+    ([$($gen:tt)*] $owner:ty, $cell:ty, $ro:ident, $rw:ident) => {
+        impl<'a, $($gen)* const WRITE: bool> Capability<'a, $owner, true, WRITE> {
+            /// Borrow the contents of a cell immutably, like the
+            /// owner's `ro`.  Panics if the cell is not owned by this
+            /// owner.
+            #[inline]
+            pub fn ro<'b, T: ?Sized>(&'b self, c: &'b $cell) -> &'b T {
+                self.owner.$ro(c)
+            }
+
+            /// Copy the contents of a cell out, like the owner's
+            /// `get`.  Panics if the cell is not owned by this owner.
+            #[inline]
+            pub fn get<T: Copy>(&self, c: &$cell) -> T {
+                *self.ro(c)
+            }
+
+            /// Clone the contents of a cell out, like the owner's
+            /// `get_cloned`.  Panics if the cell is not owned by this
+            /// owner.
+            #[inline]
+            pub fn get_cloned<T: Clone>(&self, c: &$cell) -> T {
+                self.ro(c).clone()
+            }
+        }
+
+        impl<'a, $($gen)* const READ: bool> Capability<'a, $owner, READ, true> {
+            /// Replace the contents of a cell with `value`, dropping
+            /// the old value, like the owner's `set`.  Panics if the
+            /// cell is not owned by this owner.
+            #[inline]
+            pub fn set<T>(&mut self, c: &$cell, value: T) {
+                *self.owner.$rw(c) = value;
+            }
+
+            /// Copy the values in `src` into many cells, like the
+            /// owner's `write_from`.  Panics if any cell is not owned
+            /// by this owner, in which case the cells before it have
+            /// already been written.
+            #[inline]
+            pub fn write_from<T: Copy, const N: usize>(
+                &mut self,
+                cells: &[&$cell; N],
+                src: &[T; N],
+            ) {
+                self.write_from_slice(cells, src);
+            }
+
+            /// Copy the values in `src` into many cells, like
+            /// `write_from`, but with the lengths checked at runtime.
+            /// Panics before writing anything if `cells` and `src`
+            /// have different lengths.  Also panics if any cell is not
+            /// owned by this owner.
+            #[inline]
+            pub fn write_from_slice<T: Copy>(&mut self, cells: &[&$cell], src: &[T]) {
+                crate::batch::length_check(cells.len(), src.len());
+                for (c, value) in cells.iter().zip(src) {
+                    *self.owner.$rw(c) = *value;
+                }
+            }
+        }
+
+        impl<'a, $($gen)*> Capability<'a, $owner, true, true> {
+            /// Borrow the contents of a cell mutably, like the owner's
+            /// `rw`.  Panics if the cell is not owned by this owner.
+            #[inline]
+            pub fn rw<'b, T: ?Sized>(&'b mut self, c: &'b $cell) -> &'b mut T {
+                self.owner.$rw(c)
+            }
+
+            /// Replace the contents of a cell with the result of
+            /// calling `f` on a copy of the old contents, and return
+            /// the new value, like the owner's `update`.  Panics if
+            /// the cell is not owned by this owner.
+            #[inline]
+            pub fn update<T: Copy>(&mut self, c: &$cell, f: impl FnOnce(T) -> T) -> T {
+                let value = self.rw(c);
+                *value = f(*value);
+                *value
+            }
+        }
+    };
+}
+
+capability_methods!([O: QCellBorrow,] O, QCell<T>, qcell_ro, qcell_rw);
+capability_methods!([Q: TCellMarker,] TCellOwner<Q>, TCell<Q, T>, ro, rw);
+#[cfg(feature = "std")]
+capability_methods!([Q: TCellMarker,] TLCellOwner<Q>, TLCell<Q, T>, ro, rw);
+capability_methods!(['id,] LCellOwner<'id>, LCell<'id, T>, ro, rw);
+
+#[cfg(all(test, feature = "std", not(feature = "strict-markers")))]
+mod tests {
+    use crate::{Capability, LCellOwner, QCell, QCellOwner, ReadOnly, TCell, TCellOwner, TLCell};
+    use crate::{QCellOwnerPinned, QCellOwnerSeq, TLCellOwner, WriteOnly};
+
+    #[test]
+    fn capability_write_only_qcell() {
+        let mut owner = QCellOwner::new();
+        let a = QCell::new(&owner, String::new());
+        let b = QCell::new(&owner, 0_u32);
+        let c = QCell::new(&owner, 0_u32);
+        let mut wo = owner.capability::<false, true>();
+        wo.set(&a, "abc".to_string());
+        wo.write_from(&[&b, &c], &[1, 2]);
+        assert_eq!(owner.ro(&a), "abc");
+        assert_eq!((owner.get(&b), owner.get(&c)), (1, 2));
+    }
+
+    #[test]
+    fn capability_read_only_and_full() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = QCell::new(&owner, 10_u32);
+        let ro: ReadOnly<'_, _> = owner.capability();
+        assert_eq!(ro.get(&cell), 10);
+        assert_eq!(ro.get_cloned(&cell), 10);
+        let mut rw = owner.capability::<true, true>();
+        *rw.rw(&cell) += 1;
+        assert_eq!(rw.update(&cell, |v| v * 2), 22);
+        assert_eq!(*rw.ro(&cell), 22);
+        rw.set(&cell, 5);
+        assert_eq!(owner.get(&cell), 5);
+    }
+
+    #[test]
+    fn capability_reborrow() {
+        fn log(mut wo: WriteOnly<'_, QCellOwner>, cells: &[QCell<u8>]) {
+            for (i, c) in cells.iter().enumerate() {
+                wo.set(c, i as u8);
+            }
+        }
+        let mut owner = QCellOwner::new();
+        let cells: Vec<_> = (0..3).map(|_| QCell::new(&owner, 9)).collect();
+        let mut wo = owner.capability();
+        log(wo.reborrow(), &cells[..2]);
+        wo.set(&cells[2], 7);
+        assert_eq!(
+            cells.iter().map(|c| owner.get(c)).collect::<Vec<_>>(),
+            [0, 1, 7]
+        );
+    }
+
+    #[test]
+    fn capability_pinned() {
+        let mut owner = Box::pin(QCellOwnerPinned::new());
+        let cell = owner.as_ref().cell(1);
+        let mut pinned = owner.as_mut();
+        Capability::<_, false, true>::new(&mut pinned).set(&cell, 2);
+        assert_eq!(owner.as_ref().get(&cell), 2);
+    }
+
+    #[test]
+    fn capability_tcell_tlcell_lcell() {
+        struct Marker;
+        let mut towner = TCellOwner::<Marker>::new();
+        let tcell = TCell::new(0);
+        towner.capability::<false, true>().set(&tcell, 1);
+        assert_eq!(towner.capability::<true, false>().get(&tcell), 1);
+
+        let mut tlowner = TLCellOwner::<Marker>::new();
+        let tlcell = TLCell::new(0);
+        tlowner
+            .capability::<false, true>()
+            .write_from_slice(&[&tlcell], &[2]);
+        assert_eq!(tlowner.get(&tlcell), 2);
+
+        LCellOwner::scope(|mut owner| {
+            let cell = owner.cell(vec![1]);
+            owner.capability::<true, true>().rw(&cell).push(2);
+            owner.capability::<false, true>().set(&cell, vec![3]);
+            assert_eq!(*owner.ro(&cell), [3]);
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn capability_wrong_owner() {
+        let mut owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cell = QCell::new(&owner2, 0);
+        owner1.capability::<false, true>().set(&cell, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn capability_write_from_slice_length() {
+        let mut owner = QCellOwner::new();
+        let cell = QCell::new(&owner, 0);
+        owner
+            .capability::<false, true>()
+            .write_from_slice(&[&cell], &[1, 2]);
+    }
+}
@@ -0,0 +1,737 @@
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem;
+use core::ops::Deref;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+
+use crate::sound;
+use crate::{LCell, QCell, QCellBorrow, QCellOwnerID, TCell};
+
+// Chunks are sized to hold about this many bytes of cells by default
+const DEFAULT_CHUNK_BYTES: usize = 64 * 1024;
+
+// The cells of an arena.  A chunk is only pushed to whilst it has
+// spare capacity, so its buffer never moves, and cells are never
+// removed, so handles to them stay valid for as long as the arena
+// is borrowed.
+struct Chunks<C> {
+    list: Vec<Vec<C>>,
+    len: usize,
+}
+
+impl<C> Chunks<C> {
+    const fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            len: 0,
+        }
+    }
+
+    // Add a cell, and return the chunk it was added to, with the new
+    // cell last
+    fn push(&mut self, cell: C, chunk_size: usize) -> &[C] {
+        if self.list.last().map_or(true, |c| c.len() == c.capacity()) {
+            self.list.push(Vec::with_capacity(chunk_size));
+        }
+        let chunk = self.list.last_mut().unwrap();
+        chunk.push(cell);
+        self.len += 1;
+        chunk
+    }
+
+    // Add all the cells to one chunk, starting a new chunk if they
+    // don't fit in the last one, and return that chunk, with the new
+    // cells last
+    fn extend(&mut self, cells: Vec<C>, chunk_size: usize) -> &[C] {
+        let count = cells.len();
+        if self.list.last().map_or(true, |c| c.capacity() - c.len() < count) {
+            self.list.push(Vec::with_capacity(chunk_size.max(count)));
+        }
+        let chunk = self.list.last_mut().unwrap();
+        chunk.extend(cells);
+        self.len += count;
+        chunk
+    }
+}
+
+fn default_chunk_size<C>() -> usize {
+    (DEFAULT_CHUNK_BYTES / mem::size_of::<C>().max(1)).max(1)
+}
+
+macro_rules! cell_arena {
+    (
+        $(#[$meta:meta])*
+        $name:ident($lock:ident),
+        |$chunks:ident| $guard:expr
+    ) => {
+        $(#[$meta])*
+        pub struct $name<T> {
+            id: QCellOwnerID,
+            chunk_size: usize,
+            chunks: $lock<Chunks<QCell<T>>>,
+        }
+
+        impl<T> $name<T> {
+            /// Create an empty arena of cells owned by the given owner,
+            /// allocating chunks of about 64KiB
+            #[inline]
+            pub fn new(owner: impl Into<QCellOwnerID>) -> Self {
+                Self::with_chunk_size(owner, default_chunk_size::<QCell<T>>())
+            }
+
+            /// Create an empty arena of cells owned by the given owner,
+            /// allocating room for `chunk_size` cells at a time.  A
+            /// `chunk_size` of zero is taken as one.
+            #[inline]
+            pub fn with_chunk_size(owner: impl Into<QCellOwnerID>, chunk_size: usize) -> Self {
+                Self {
+                    id: owner.into(),
+                    chunk_size: chunk_size.max(1),
+                    chunks: $lock::new(Chunks::new()),
+                }
+            }
+
+            /// Get the ID of the owner of the cells in the arena
+            #[inline]
+            pub fn id(&self) -> QCellOwnerID {
+                self.id
+            }
+
+            /// Get the number of cells allocated
+            pub fn len(&self) -> usize {
+                let $chunks = &self.chunks;
+                $guard.len
+            }
+
+            /// Test whether no cells have been allocated
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Allocate a new cell holding `value`, owned by the arena's
+            /// owner.  The cell lives until the arena is dropped.
+            pub fn alloc(&self, value: T) -> ArenaRef<'_, T> {
+                let cell = QCell::new(self.id, value);
+                let $chunks = &self.chunks;
+                let mut guard = $guard;
+                let chunk = guard.push(cell, self.chunk_size);
+                // Safety: The chunk is never reallocated and its cells
+                // are never removed whilst the arena is borrowed
+                ArenaRef(unsafe { sound::arena_last(chunk) })
+            }
+        }
+
+        impl<T> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("id", &self.id)
+                    .field("len", &self.len())
+                    .finish()
+            }
+        }
+    };
+}
+
+cell_arena!(
+    /// Arena of [`QCell`] instances all owned by one owner, which
+    /// frees all the cells at once when dropped.
+    ///
+    /// A large graph of `Rc<QCell<T>>` nodes is slow to tear down,
+    /// since each `Rc` is freed separately, in whatever order the
+    /// graph is traversed.  Cells in an arena are allocated in large
+    /// chunks, and [`CellArena::alloc`] hands out an [`ArenaRef`],
+    /// which borrows the arena rather than counting references.  So
+    /// there is no per-cell bookkeeping, and dropping the arena drops
+    /// the values and frees the memory one chunk at a time.  The
+    /// catch is that no cell can be freed before the others.
+    ///
+    /// Allocating only needs `&self`, so the nodes of a graph can
+    /// hold handles to other nodes, including in cycles.  The
+    /// contents are accessed through the owner as usual, since the
+    /// handle derefs to the [`QCell`]:
+    ///
+    /// ```
+    ///# use qcell::{ArenaRef, CellArena, QCellOwner};
+    /// struct Node<'a> {
+    ///     value: u32,
+    ///     next: Option<ArenaRef<'a, Node<'a>>>,
+    /// }
+    ///
+    /// let mut owner = QCellOwner::new();
+    /// let arena = CellArena::new(&owner);
+    /// let first = arena.alloc(Node { value: 1, next: None });
+    /// let second = arena.alloc(Node { value: 2, next: Some(first) });
+    /// // Close the loop
+    /// owner.rw(&first).next = Some(second);
+    ///
+    /// let mut node = first;
+    /// let mut sum = 0;
+    /// for _ in 0..4 {
+    ///     sum += owner.ro(&node).value;
+    ///     node = owner.ro(&node).next.unwrap();
+    /// }
+    /// assert_eq!(sum, 6);
+    /// ```
+    ///
+    /// The arena uses a `RefCell` internally, so is not `Sync`.  See
+    /// [`SyncCellArena`] for one that can be shared between threads.
+    /// See the [`doctest_cell_arena`] module for the misuses of
+    /// handles which fail to compile.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    /// [`ArenaRef`]: struct.ArenaRef.html
+    /// [`CellArena::alloc`]: struct.CellArena.html#method.alloc
+    /// [`SyncCellArena`]: struct.SyncCellArena.html
+    /// [`doctest_cell_arena`]: doctest_cell_arena/index.html
+    CellArena(RefCell),
+    |chunks| chunks.borrow_mut()
+);
+
+#[cfg(feature = "std")]
+cell_arena!(
+    /// Arena of [`QCell`] instances which may be shared between
+    /// threads.  This is the same as [`CellArena`] apart from using a
+    /// `Mutex` internally, so that cells may be allocated from
+    /// several threads at once.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    /// [`CellArena`]: struct.CellArena.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    SyncCellArena(Mutex),
+    |chunks| chunks.lock().unwrap_or_else(PoisonError::into_inner)
+);
+
+/// Handle to a [`QCell`] in a [`CellArena`] or [`SyncCellArena`],
+/// which borrows the arena.
+///
+/// The handle is `Copy`, so can be passed around and stored freely,
+/// and derefs to the [`QCell`], so it can be passed to the owner's
+/// methods.  Like [`CellPtr`], it compares and hashes by identity.
+///
+/// [`QCell`]: struct.QCell.html
+/// [`CellArena`]: struct.CellArena.html
+/// [`SyncCellArena`]: struct.SyncCellArena.html
+/// [`CellPtr`]: struct.CellPtr.html
+pub struct ArenaRef<'arena, T>(&'arena QCell<T>);
+
+impl<'arena, T> ArenaRef<'arena, T> {
+    /// Borrow the contents immutably.  Panics if the cell is not
+    /// owned by this owner.
+    #[inline]
+    pub fn ro<'a, O: QCellBorrow>(self, owner: &'a O) -> &'a T
+    where
+        'arena: 'a,
+    {
+        owner.qcell_ro(self.0)
+    }
+
+    /// Borrow the contents mutably.  Panics if the cell is not owned
+    /// by this owner.
+    #[inline]
+    pub fn rw<'a, O: QCellBorrow>(self, owner: &'a mut O) -> &'a mut T
+    where
+        'arena: 'a,
+    {
+        owner.qcell_rw(self.0)
+    }
+
+    /// Get the cell, for as long as the arena is borrowed
+    #[inline]
+    pub fn as_cell(self) -> &'arena QCell<T> {
+        self.0
+    }
+
+    /// Test whether two handles refer to the same cell
+    #[inline]
+    pub fn ptr_eq(self, other: Self) -> bool {
+        core::ptr::eq(self.0, other.0)
+    }
+}
+
+impl<T> Clone for ArenaRef<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaRef<'_, T> {}
+
+impl<T> Deref for ArenaRef<'_, T> {
+    type Target = QCell<T>;
+
+    #[inline]
+    fn deref(&self) -> &QCell<T> {
+        self.0
+    }
+}
+
+impl<T> PartialEq for ArenaRef<'_, T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(*other)
+    }
+}
+
+impl<T> Eq for ArenaRef<'_, T> {}
+
+impl<T> Hash for ArenaRef<'_, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 as *const QCell<T>).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for ArenaRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ArenaRef")
+            .field(&(self.0 as *const QCell<T>))
+            .finish()
+    }
+}
+
+// The shared part of `LCellArena` and `TCellArena`.  These hand out
+// plain references, since the brand or marker type already ties the
+// cells to their owner.
+struct PlainArena<C> {
+    chunk_size: usize,
+    chunks: RefCell<Chunks<C>>,
+}
+
+impl<C> PlainArena<C> {
+    const fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            chunks: RefCell::new(Chunks::new()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.chunks.borrow().len
+    }
+
+    fn alloc(&self, cell: C) -> &C {
+        let mut guard = self.chunks.borrow_mut();
+        let chunk = guard.push(cell, self.chunk_size);
+        // Safety: The chunk is never reallocated and its cells are
+        // never removed whilst the arena is borrowed
+        unsafe { sound::arena_last(chunk) }
+    }
+
+    fn alloc_extend(&self, cells: impl Iterator<Item = C>) -> &[C] {
+        // Collected first, in case the iterator allocates from the
+        // arena too
+        let cells: Vec<C> = cells.collect();
+        let count = cells.len();
+        let mut guard = self.chunks.borrow_mut();
+        let chunk = guard.extend(cells, self.chunk_size);
+        // Safety: The chunk is never reallocated and its cells are
+        // never removed whilst the arena is borrowed
+        unsafe { sound::arena_tail(chunk, chunk.len() - count) }
+    }
+
+    // Iterate by position rather than holding a borrow of the chunk
+    // list, so that cells can be allocated during iteration.  Those
+    // cells are included.
+    fn iter(&self) -> impl Iterator<Item = &C> {
+        let (mut chunk, mut index) = (0, 0);
+        core::iter::from_fn(move || loop {
+            let guard = self.chunks.borrow();
+            let cells = guard.list.get(chunk)?;
+            if index < cells.len() {
+                // Safety: The chunk is never reallocated and its
+                // cells are never removed whilst the arena is borrowed
+                let tail = unsafe { sound::arena_tail(cells, index) };
+                index += 1;
+                return Some(&tail[0]);
+            }
+            if chunk + 1 >= guard.list.len() {
+                return None;
+            }
+            chunk += 1;
+            index = 0;
+        })
+    }
+}
+
+macro_rules! plain_arena {
+    (
+        $(#[$meta:meta])*
+        $name:ident<$brand:tt>($cell:ident)
+    ) => {
+        $(#[$meta])*
+        pub struct $name<$brand, T> {
+            arena: PlainArena<$cell<$brand, T>>,
+        }
+
+        impl<$brand, T> $name<$brand, T> {
+            /// Create an empty arena, allocating chunks of about
+            /// 64KiB
+            #[inline]
+            pub fn new() -> Self {
+                Self::with_chunk_size(default_chunk_size::<$cell<$brand, T>>())
+            }
+
+            /// Create an empty arena, allocating room for `chunk_size`
+            /// cells at a time.  A `chunk_size` of zero is taken as
+            /// one.
+            #[inline]
+            pub fn with_chunk_size(chunk_size: usize) -> Self {
+                Self {
+                    arena: PlainArena::new(chunk_size.max(1)),
+                }
+            }
+
+            /// Get the number of cells allocated
+            pub fn len(&self) -> usize {
+                self.arena.len()
+            }
+
+            /// Test whether no cells have been allocated
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Allocate a new cell holding `value`.  The cell lives
+            /// until the arena is dropped, and its address never
+            /// changes.
+            pub fn alloc(&self, value: T) -> &$cell<$brand, T> {
+                self.arena.alloc($cell::new(value))
+            }
+
+            /// Allocate a cell for each of the values, next to each
+            /// other, and return them as a slice.  The iterator may
+            /// itself allocate from the arena, in which case those
+            /// cells come before the slice.
+            pub fn alloc_extend(&self, values: impl IntoIterator<Item = T>) -> &[$cell<$brand, T>] {
+                self.arena.alloc_extend(values.into_iter().map($cell::new))
+            }
+
+            /// Iterate over the cells in the order that they were
+            /// allocated.  Cells allocated before the iterator reaches
+            /// the end are included.
+            pub fn iter(&self) -> impl Iterator<Item = &$cell<$brand, T>> {
+                self.arena.iter()
+            }
+        }
+
+        impl<$brand, T> Default for $name<$brand, T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<$brand, T> fmt::Debug for $name<$brand, T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("len", &self.len())
+                    .finish()
+            }
+        }
+    };
+}
+
+plain_arena!(
+    /// Arena of [`LCell`] instances of one brand, which hands out
+    /// references that stay valid whilst more cells are allocated.
+    ///
+    /// Within [`LCellOwner::scope`], cells are often built from an
+    /// iterator into a `Vec`.  But then references into the `Vec`
+    /// can't be kept whilst pushing more, since pushing may move the
+    /// contents.  The arena allocates with `&self` and never moves a
+    /// cell, so references handed out earlier remain usable.  The
+    /// arena borrows nothing from the owner, so allocating never
+    /// conflicts with borrows of the cell contents:
+    ///
+    /// ```
+    ///# use qcell::{LCellArena, LCellOwner};
+    /// LCellOwner::scope(|mut owner| {
+    ///     let arena = LCellArena::new();
+    ///     let words = arena.alloc_extend(["one", "two"].iter().map(|w| w.to_string()));
+    ///     let first = owner.ro(&words[0]);
+    ///     let third = arena.alloc(format!("{}-three", first));
+    ///     assert_eq!(first, "one");
+    ///
+    ///     let (a, b) = owner.rw2(&words[1], third);
+    ///     std::mem::swap(a, b);
+    ///     let all: Vec<&str> = arena.iter().map(|c| owner.ro(c).as_str()).collect();
+    ///     assert_eq!(all, ["one", "one-three", "two"]);
+    /// });
+    /// ```
+    ///
+    /// The brand ties the arena to the scope, so neither the arena
+    /// nor its cells can escape it.  See the [`doctest_cell_arena`]
+    /// module for the misuses which fail to compile.
+    ///
+    /// [`LCell`]: struct.LCell.html
+    /// [`LCellOwner::scope`]: struct.LCellOwner.html#method.scope
+    /// [`doctest_cell_arena`]: doctest_cell_arena/index.html
+    LCellArena<'id>(LCell)
+);
+
+plain_arena!(
+    /// Arena of [`TCell`] instances of one marker type, which hands
+    /// out references that stay valid whilst more cells are
+    /// allocated.  This works like [`LCellArena`].
+    ///
+    #[cfg_attr(
+        feature = "std",
+        doc = "
+ ```
+ # use qcell::{TCellArena, TCellOwner};
+ qcell::marker!(struct Marker;);
+ let mut owner = TCellOwner::<Marker>::new();
+ let arena = TCellArena::with_chunk_size(2);
+ let cells: Vec<_> = (0..5).map(|i| arena.alloc(i)).collect();
+ *owner.rw(cells[4]) += *owner.ro(cells[0]) + 10;
+ let sum: i32 = arena.iter().map(|c| *owner.ro(c)).sum();
+ assert_eq!(sum, 20);
+ ```
+"
+    )]
+    ///
+    /// [`TCell`]: struct.TCell.html
+    /// [`LCellArena`]: struct.LCellArena.html
+    TCellArena<Q>(TCell)
+);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        ArenaRef, CellArena, LCellArena, LCellOwner, QCellOwner, QCellOwnerSeq, SyncCellArena,
+        TCellArena, TCellOwner,
+    };
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    struct Node<'a> {
+        value: u64,
+        edges: Vec<ArenaRef<'a, Node<'a>>>,
+    }
+
+    #[test]
+    // Hashed by identity, so changing the contents is fine
+    #[allow(clippy::mutable_key_type)]
+    fn cell_arena_graph_access() {
+        let mut owner = QCellOwner::new();
+        let arena = CellArena::with_chunk_size(&owner, 4);
+        assert!(arena.is_empty());
+        let nodes: Vec<_> = (0..10)
+            .map(|value| {
+                arena.alloc(Node {
+                    value,
+                    edges: Vec::new(),
+                })
+            })
+            .collect();
+        assert_eq!(arena.len(), 10);
+        // Ring with chords, crossing chunk boundaries
+        for (i, node) in nodes.iter().enumerate() {
+            let edges = vec![nodes[(i + 1) % 10], nodes[(i + 5) % 10]];
+            node.rw(&mut owner).edges = edges;
+        }
+        let mut seen = HashSet::new();
+        let mut stack = vec![nodes[3]];
+        while let Some(node) = stack.pop() {
+            if seen.insert(node) {
+                stack.extend(owner.ro(&node).edges.iter().copied());
+            }
+        }
+        assert_eq!(seen.len(), 10);
+        let total: u64 = seen.iter().map(|n| n.ro(&owner).value).sum();
+        assert_eq!(total, 45);
+
+        // Handles compare by identity and go straight to the cell
+        let a = nodes[0];
+        assert!(a.ptr_eq(nodes[0]) && a != nodes[1]);
+        *owner.rw(a.as_cell()) = Node {
+            value: 100,
+            edges: Vec::new(),
+        };
+        assert_eq!(a.ro(&owner).value, 100);
+        assert!(format!("{:?}", a).starts_with("ArenaRef(0x"));
+        assert_eq!(
+            format!("{:?}", arena),
+            format!("CellArena {{ id: {:?}, len: 10 }}", owner.id())
+        );
+    }
+
+    #[test]
+    fn cell_arena_drops_values_once() {
+        struct Counted<'a>(&'a Cell<usize>);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        // Safety: No ID collisions are being exploited
+        let owner = unsafe { QCellOwnerSeq::new() };
+        let arena = CellArena::with_chunk_size(&owner, 3);
+        let handles: Vec<_> = (0..10).map(|_| arena.alloc(Counted(&drops))).collect();
+        // Addresses are stable as chunks are added
+        let first: *const _ = handles[0].as_cell();
+        arena.alloc(Counted(&drops));
+        assert_eq!(first, handles[0].as_cell() as *const _);
+        assert!(std::ptr::eq(owner.ro(&handles[9]).0, &drops));
+        drop(handles);
+        assert_eq!(drops.get(), 0);
+        drop(arena);
+        assert_eq!(drops.get(), 11);
+
+        // Zero-sized values, and a zero chunk size
+        let arena = CellArena::with_chunk_size(&owner, 0);
+        let units: Vec<_> = (0..5).map(|_| arena.alloc(())).collect();
+        assert!(!units[0].ptr_eq(units[1]));
+        let arena = CellArena::new(&owner);
+        arena.alloc(());
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn cell_arena_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let arena = CellArena::new(&owner2);
+        let cell = arena.alloc(0);
+        cell.ro(&owner1);
+    }
+
+    #[test]
+    fn sync_cell_arena_threads() {
+        let mut owner = QCellOwner::new();
+        let arena = SyncCellArena::with_chunk_size(&owner, 16);
+        let handles: Vec<Vec<ArenaRef<'_, usize>>> = crossbeam::scope(|s| {
+            let threads: Vec<_> = (0..4)
+                .map(|t| {
+                    let arena = &arena;
+                    s.spawn(move |_| (0..100).map(|i| arena.alloc(t * 100 + i)).collect())
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        })
+        .unwrap();
+        assert_eq!(arena.len(), 400);
+        for (t, list) in handles.iter().enumerate() {
+            for (i, cell) in list.iter().enumerate() {
+                *cell.rw(&mut owner) += 1;
+                assert_eq!(*cell.ro(&owner), t * 100 + i + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn lcell_arena_alloc_during_borrow() {
+        LCellOwner::scope(|mut owner| {
+            let arena = LCellArena::with_chunk_size(3);
+            let cells = arena.alloc_extend((0..5).map(|i| i * 10));
+            assert_eq!(arena.len(), 5);
+            // Allocating whilst holding borrows of earlier contents
+            let first = owner.ro(&cells[0]);
+            let last = owner.ro(&cells[4]);
+            let sum = arena.alloc(first + last);
+            let more = arena.alloc_extend(vec![*first; 4]);
+            assert_eq!(*owner.ro(sum), 40);
+            assert_eq!(*first, 0);
+            assert_eq!(more.len(), 4);
+
+            let (a, b) = owner.rw2(&cells[1], sum);
+            std::mem::swap(a, b);
+            *owner.rw(&more[3]) = 99;
+            let all: Vec<u32> = arena.iter().map(|c| *owner.ro(c)).collect();
+            assert_eq!(all, [0, 40, 20, 30, 40, 10, 0, 0, 0, 99]);
+            assert!(format!("{:?}", arena).contains("len: 10"));
+        });
+    }
+
+    #[test]
+    fn lcell_arena_addresses_stable() {
+        LCellOwner::scope(|mut owner| {
+            let arena = LCellArena::with_chunk_size(64);
+            let mut cells = Vec::new();
+            let mut addrs = Vec::new();
+            for i in 0..10_000_usize {
+                let cell = if i % 100 == 0 {
+                    &arena.alloc_extend(i..i + 1)[0]
+                } else {
+                    arena.alloc(i)
+                };
+                cells.push(cell);
+                addrs.push(cell as *const _);
+            }
+            for (i, cell) in cells.iter().enumerate() {
+                assert!(std::ptr::eq(*cell, addrs[i]));
+                *owner.rw(cell) += 1;
+            }
+            assert!(arena.iter().zip(addrs).all(|(c, a)| std::ptr::eq(c, a)));
+            let total: usize = arena.iter().map(|c| *owner.ro(c)).sum();
+            assert_eq!(total, (1..=10_000).sum());
+        });
+    }
+
+    #[test]
+    fn lcell_arena_iter_sees_new_cells() {
+        LCellOwner::scope(|owner| {
+            let arena = LCellArena::with_chunk_size(2);
+            assert_eq!(arena.iter().count(), 0);
+            arena.alloc(1);
+            let mut seen = Vec::new();
+            for cell in arena.iter() {
+                let value = *owner.ro(cell);
+                if value < 5 {
+                    arena.alloc(value + 1);
+                }
+                seen.push(value);
+            }
+            assert_eq!(seen, [1, 2, 3, 4, 5]);
+
+            // An iterator which allocates from the arena itself
+            let nested = arena.alloc_extend((0..3).inspect(|_| {
+                arena.alloc(100);
+            }));
+            assert_eq!(nested.iter().map(|c| *owner.ro(c)).collect::<Vec<_>>(), [0, 1, 2]);
+            assert_eq!(arena.len(), 11);
+        });
+    }
+
+    #[test]
+    fn tcell_arena_basics() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let arena = TCellArena::<Marker, String>::default();
+        assert!(arena.is_empty());
+        let a = arena.alloc("a".to_string());
+        let rest = arena.alloc_extend(["b", "c"].iter().map(|s| s.to_string()));
+        let (x, y) = owner.rw2(a, &rest[1]);
+        x.push_str(y);
+        assert_eq!(owner.ro(a), "ac");
+        assert_eq!(arena.len(), 3);
+    }
+
+    // Teardown of a large arena drops every cell's contents, however
+    // many chunks they span
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn cell_arena_teardown_large() {
+        const COUNT: usize = 1_000_000;
+        let owner = QCellOwner::new();
+        let token = Rc::new(());
+
+        let arena = CellArena::new(&owner);
+        let handles: Vec<_> = (0..COUNT)
+            .map(|i| arena.alloc((i as u64, token.clone())))
+            .collect();
+        assert_eq!(handles[COUNT - 1].ro(&owner).0, COUNT as u64 - 1);
+        assert_eq!(Rc::strong_count(&token), COUNT + 1);
+        drop(handles);
+        assert_eq!(Rc::strong_count(&token), COUNT + 1);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&token), 1);
+    }
+}
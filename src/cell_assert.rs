@@ -0,0 +1,365 @@
+// Assertion and debugging macros for tests which check cell contents
+//
+// The macros take the owner expression and borrow it with `&`, so
+// they work the same way for every owner type which has a `ro`
+// method, and for `&mut` references to owners.  The expected value is
+// always evaluated before the cell is borrowed, so it may itself
+// read other cells through the same owner.
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use crate::panic_messages::CELL_ASSERTION_FAILED;
+
+/// Assert that the contents of a cell are equal to an expected value,
+/// like `assert_eq!(*owner.ro(&cell), expected)`.
+///
+/// The invocation is `assert_cell_eq!(owner, cell, expected)`,
+/// optionally followed by a format string and arguments as for
+/// `assert_eq!`.  The owner expression is borrowed, so it may be an
+/// owner of any type or a reference to one, and the cell is borrowed
+/// too, so give it as it would be passed to `owner.ro(&cell)`.  The
+/// cell's contents are compared with `==`, which may compare
+/// different types with a suitable `PartialEq` implementation.
+///
+/// On failure, the panic message starts with
+/// [`panic_messages::CELL_ASSERTION_FAILED`], and gives the cell
+/// expression as written, the source location, and the `Debug` output
+/// of both values.  With many cells in a test, this shows which one
+/// was wrong.
+///
+/// `expected` is evaluated first, before the cell is borrowed, so it
+/// may read other cells through the same owner.  The borrow of the
+/// cell ends before the panic is raised.
+///
+/// ```
+///# use qcell::{assert_cell_eq, QCellOwner};
+/// fn check_totals() {
+///     let mut owner = QCellOwner::new();
+///     let cells: Vec<_> = (0..40).map(|i| owner.cell(i)).collect();
+///     for cell in &cells[1..] {
+///         *owner.rw(cell) += 100;
+///     }
+///     assert_cell_eq!(owner, cells[0], 0);
+///     assert_cell_eq!(owner, cells[39], 139, "after adding to cell {}", 39);
+///     // Reads another cell through the same owner
+///     assert_cell_eq!(owner, cells[2], *owner.ro(&cells[1]) + 1);
+/// }
+///# check_totals();
+/// ```
+///
+/// A failure with the cell expression `cells[3]` panics with a
+/// message like this:
+///
+/// ```text
+/// cell assertion failed: `cells[3]` at src/lib.rs:12:5
+///     value: 103
+///  expected: 3
+/// ```
+///
+/// [`panic_messages::CELL_ASSERTION_FAILED`]: panic_messages/constant.CELL_ASSERTION_FAILED.html
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[macro_export]
+macro_rules! assert_cell_eq {
+    ($owner:expr, $cell:expr, $expected:expr $(,)?) => {
+        $crate::assert_cell_eq!(@imp $owner, $cell, $expected, ::core::option::Option::None)
+    };
+    ($owner:expr, $cell:expr, $expected:expr, $($arg:tt)+) => {
+        $crate::assert_cell_eq!(
+            @imp $owner, $cell, $expected,
+            ::core::option::Option::Some(::core::format_args!($($arg)+))
+        )
+    };
+    (@imp $owner:expr, $cell:expr, $expected:expr, $args:expr) => {
+        match &$expected {
+            expected => {
+                let owner = &$owner;
+                let failure = {
+                    let value = owner.ro(&$cell);
+                    if *value == *expected {
+                        ::core::option::Option::None
+                    } else {
+                        ::core::option::Option::Some($crate::__cell_debug(value))
+                    }
+                };
+                if let ::core::option::Option::Some(value) = failure {
+                    $crate::__cell_assert_failed(
+                        (::core::file!(), ::core::line!(), ::core::column!()),
+                        ::core::stringify!($cell),
+                        value,
+                        "expected",
+                        $crate::__cell_debug(expected),
+                        $args,
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// Assert that the contents of a cell match a pattern, like
+/// `assert!(matches!(*owner.ro(&cell), pattern))`.
+///
+/// The invocation is `assert_cell_matches!(owner, cell, pattern)`,
+/// where the pattern may have alternatives separated by `|` and an
+/// `if` guard, as for `matches!`.  It may be followed by a format
+/// string and arguments.  The owner and cell are given as for
+/// [`assert_cell_eq!`], and a failure panics in the same form, giving
+/// the pattern as written in place of the expected value.  The
+/// contents must implement `Debug`.  They are matched in place, so
+/// a binding of a part which isn't `Copy` needs `ref`.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "
+ ```
+ # use qcell::{assert_cell_matches, TCell, TCellOwner};
+ # qcell::marker!(struct Marker;);
+ let mut owner = TCellOwner::<Marker>::new();
+ let state = TCell::<Marker, _>::new(Some(3_u32));
+ let total = TCell::<Marker, _>::new(Ok::<u32, String>(10));
+ assert_cell_matches!(owner, state, Some(n) if n > 2);
+ *owner.rw(&state) = None;
+ assert_cell_matches!(owner, state, None | Some(0));
+ assert_cell_matches!(owner, total, Ok(10), \"total was reset\");
+ ```
+"
+)]
+///
+/// [`assert_cell_eq!`]: macro.assert_cell_eq.html
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[macro_export]
+macro_rules! assert_cell_matches {
+    ($owner:expr, $cell:expr, $(|)? $($pat:pat_param)|+ $(if $guard:expr)? $(,)?) => {
+        $crate::assert_cell_matches!(
+            @imp $owner, $cell, ($($pat)|+ $(if $guard)?),
+            ::core::option::Option::None
+        )
+    };
+    ($owner:expr, $cell:expr, $(|)? $($pat:pat_param)|+ $(if $guard:expr)?, $($arg:tt)+) => {
+        $crate::assert_cell_matches!(
+            @imp $owner, $cell, ($($pat)|+ $(if $guard)?),
+            ::core::option::Option::Some(::core::format_args!($($arg)+))
+        )
+    };
+    (@imp $owner:expr, $cell:expr, ($($pattern:tt)+), $args:expr) => {{
+        let owner = &$owner;
+        let failure = {
+            let value = owner.ro(&$cell);
+            match *value {
+                $($pattern)+ => ::core::option::Option::None,
+                _ => ::core::option::Option::Some($crate::__cell_debug(value)),
+            }
+        };
+        if let ::core::option::Option::Some(value) = failure {
+            $crate::__cell_assert_failed(
+                (::core::file!(), ::core::line!(), ::core::column!()),
+                ::core::stringify!($cell),
+                value,
+                "pattern",
+                ::core::stringify!($($pattern)+).into(),
+                $args,
+            );
+        }
+    }};
+}
+
+/// Print the contents of one or more cells to standard error, like
+/// `dbg!`, labelled with the source location and the cell expression
+/// as written.
+///
+/// The invocation is `debug_cell!(owner, cell, ...)`, with the owner
+/// and cells given as for [`assert_cell_eq!`].  The contents are
+/// printed with the alternate `{:#?}` form.  Nothing is returned,
+/// and each borrow ends once its cell has been printed.
+///
+/// ```
+///# use qcell::{debug_cell, LCellOwner};
+/// LCellOwner::scope(|mut owner| {
+///     let queue = owner.cell(vec![1, 2]);
+///     let head = owner.cell(0);
+///     owner.rw(&queue).push(3);
+///     // Prints `[src/main.rs:7:5] queue = [` ... and `[src/main.rs:7:5] head = 0`
+///     debug_cell!(owner, queue, head);
+/// });
+/// ```
+///
+/// [`assert_cell_eq!`]: macro.assert_cell_eq.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+macro_rules! debug_cell {
+    ($owner:expr, $($cell:expr),+ $(,)?) => {{
+        let owner = &$owner;
+        $(
+            $crate::__debug_cell(
+                (::core::file!(), ::core::line!(), ::core::column!()),
+                ::core::stringify!($cell),
+                owner.ro(&$cell),
+            );
+        )+
+    }};
+}
+
+/// Clone the contents of several cells into a tuple, for comparing
+/// them all in one assertion.
+///
+/// The invocation is `cells_snapshot!(owner, [c1, c2, ...])`, with
+/// the owner and cells given as for [`assert_cell_eq!`].  The
+/// contents of each cell must implement `Clone`.  The result is a
+/// tuple with one element per cell, even when there is only one, and
+/// no borrow of the owner is kept, so the owner may be used mutably
+/// straight afterwards.
+///
+#[cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ # use qcell::{cells_snapshot, QCell, QCellOwner};
+ let mut owner = QCellOwner::new();
+ let name = QCell::new(&owner, String::from(\"a\"));
+ let count = QCell::new(&owner, 1);
+ let before = cells_snapshot!(owner, [name, count]);
+ owner.rw(&name).push('b');
+ *owner.rw(&count) += 1;
+ assert_eq!(before, (String::from(\"a\"), 1));
+ assert_eq!(cells_snapshot!(owner, [name, count]), (String::from(\"ab\"), 2));
+ ```
+"
+)]
+///
+/// [`assert_cell_eq!`]: macro.assert_cell_eq.html
+#[macro_export]
+macro_rules! cells_snapshot {
+    ($owner:expr, [$($cell:expr),+ $(,)?] $(,)?) => {{
+        let owner = &$owner;
+        ($(::core::clone::Clone::clone(owner.ro(&$cell)),)+)
+    }};
+}
+
+#[doc(hidden)]
+#[cfg(feature = "alloc")]
+pub fn cell_debug<T: fmt::Debug + ?Sized>(value: &T) -> String {
+    alloc::format!("{:?}", value)
+}
+
+#[doc(hidden)]
+#[cfg(feature = "alloc")]
+#[cold]
+#[track_caller]
+pub fn cell_assert_failed(
+    (file, line, column): (&str, u32, u32),
+    cell: &str,
+    value: String,
+    label: &str,
+    expected: String,
+    args: Option<fmt::Arguments<'_>>,
+) -> ! {
+    let message = match args {
+        Some(args) => alloc::format!(": {}", args),
+        None => String::new(),
+    };
+    panic!(
+        "{}: `{}` at {}:{}:{}{}\n    value: {}\n{:>9}: {}",
+        CELL_ASSERTION_FAILED, cell, file, line, column, message, value, label, expected
+    );
+}
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub fn debug_cell<T: fmt::Debug + ?Sized>(
+    (file, line, column): (&str, u32, u32),
+    cell: &str,
+    value: &T,
+) {
+    std::eprintln!("[{}:{}:{}] {} = {:#?}", file, line, column, cell, value);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::panic_messages::tests::panic_message;
+    use crate::panic_messages::CELL_ASSERTION_FAILED;
+    use crate::{LCellOwner, QCell, QCellOwner, TCell, TCellOwner, TLCell, TLCellOwner};
+    use std::rc::Rc;
+
+    #[test]
+    fn cell_assert_all_owner_types() {
+        let mut qowner = QCellOwner::new();
+        let qcell = Rc::new(QCell::new(&qowner, vec![1, 2]));
+        qowner.rw(&qcell).push(3);
+        assert_cell_eq!(qowner, qcell, [1, 2, 3]);
+        let owner_ref = &mut qowner;
+        assert_cell_eq!(owner_ref, qcell, vec![1, 2, 3]);
+        assert_cell_matches!(&qowner, &qcell, ref v if v.len() == 3);
+
+        crate::marker!(struct Marker;);
+        let towner = TCellOwner::<Marker>::new();
+        let tcell = TCell::<Marker, _>::new(Some('a'));
+        assert_cell_eq!(towner, tcell, Some('a'));
+        assert_cell_matches!(towner, tcell, Some('a'..='z'));
+
+        let tlowner = TLCellOwner::<Marker>::new();
+        let tlcell = TLCell::<Marker, _>::new("x");
+        assert_cell_eq!(tlowner, tlcell, "x", "with a {}", "message");
+
+        LCellOwner::scope(|owner| {
+            let cells: Vec<_> = (0..4).map(|i| owner.cell(i)).collect();
+            assert_cell_eq!(owner, cells[3], 3);
+            assert_cell_matches!(owner, cells[0], 0 | 1);
+            assert_eq!(cells_snapshot!(owner, [cells[1]]), (1,));
+            debug_cell!(owner, cells[2], cells[3]);
+        });
+    }
+
+    #[test]
+    fn cell_assert_expected_reads_owner() {
+        let mut owner = QCellOwner::new();
+        let a = owner.cell(5);
+        let b = owner.cell(4);
+        assert_cell_eq!(owner, a, *owner.ro(&b) + 1);
+        // Even a mutable borrow, since `expected` is evaluated first
+        assert_cell_eq!(owner, a, {
+            *owner.rw(&b) += 1;
+            *owner.ro(&b)
+        });
+        let before = cells_snapshot!(owner, [a, b]);
+        *owner.rw(&a) += 1;
+        assert_eq!(before, (5, 5));
+        assert_eq!(cells_snapshot!(owner, [a, b,]), (6, 5));
+    }
+
+    #[test]
+    fn cell_assert_failure_messages() {
+        let mut owner = QCellOwner::new();
+        let cells: Vec<_> = (0..40).map(|i| owner.cell(i)).collect();
+        *owner.rw(&cells[17]) = 99;
+
+        let (line, msg) = (line!(), panic_message(|| assert_cell_eq!(owner, cells[17], 17)));
+        assert_eq!(
+            msg,
+            format!(
+                "{}: `cells[17]` at {}:{}:54\n    value: 99\n expected: 17",
+                CELL_ASSERTION_FAILED,
+                file!(),
+                line
+            )
+        );
+
+        let msg = panic_message(|| assert_cell_eq!(owner, cells[17], 17, "step {}", 3));
+        assert!(msg.starts_with(CELL_ASSERTION_FAILED));
+        assert!(msg.contains(":36: step 3\n"), "{}", msg);
+
+        let msg = panic_message(|| assert_cell_matches!(owner, cells[17], 0..=9 | 40));
+        assert!(msg.starts_with("cell assertion failed: `cells[17]` at "));
+        assert!(msg.ends_with("\n    value: 99\n  pattern: 0..=9 | 40"));
+
+        let msg = panic_message(|| assert_cell_matches!(owner, cells[1], n if n > 1, "n={}", 1));
+        assert!(msg.contains(": n=1\n"), "{}", msg);
+        assert!(msg.ends_with("\n  pattern: n if n > 1"), "{}", msg);
+    }
+}
@@ -0,0 +1,519 @@
+use core::any::{type_name, Any};
+use core::fmt;
+use core::ops::Deref;
+use std::rc::Rc;
+
+use once_cell::unsync::OnceCell;
+
+use crate::{panic_messages, QCell, QCellBorrow, QCellOwnerID};
+
+// Shared by a `Declared` and all the `CellLink` instances made from
+// it.  `builder` identifies the builder that the cell was declared
+// with.
+struct Slot<T> {
+    index: usize,
+    builder: Rc<()>,
+    cell: OnceCell<Rc<QCell<T>>>,
+}
+
+struct Entry {
+    type_name: &'static str,
+    definitions: usize,
+    slot: Rc<dyn Any>,
+    // Resolves the links to the cell from the first definition
+    resolve: Option<Box<dyn FnOnce()>>,
+}
+
+#[cold]
+#[inline(never)]
+fn foreign_declared_panic(index: usize) -> ! {
+    panic!("{}: #{}", panic_messages::CELL_GRAPH_FOREIGN_DECLARED, index);
+}
+
+/// Builder for a set of `Rc<QCell<T>>` cells which refer to each
+/// other.
+///
+/// Where two cells must refer to each other, for example a parent and
+/// child with a back-link, the first cell to be created can't refer
+/// to the second, since it doesn't exist yet.  The usual workaround is
+/// an `Option` field which is patched up afterwards, but forgetting to
+/// patch it leaves a `None` which causes a panic later on.
+///
+/// Here construction has two phases.  First each cell is declared
+/// with [`CellGraphBuilder::declare`], which gives a [`Declared`]
+/// handle.  [`Declared::handle`] gives a [`CellLink`] to store in the
+/// link fields of the values.  Then each cell is given its value with
+/// [`CellGraphBuilder::define`], in any order.  Finally
+/// [`CellGraphBuilder::finish`] checks that every cell was defined
+/// exactly once, and resolves all the links at once.  So after a
+/// successful `finish`, every link is valid, and before that, none of
+/// them are.
+///
+/// A [`CellLink`] is an extra indirection to the real
+/// `Rc<QCell<T>>`, which can't be allocated before its value exists.
+/// It derefs to the [`QCell`], so it can be passed to the owner's
+/// methods directly.
+///
+/// ```
+///# use qcell::{CellGraphBuilder, CellLink, QCellOwner};
+/// struct Parent {
+///     name: &'static str,
+///     child: CellLink<Child>,
+/// }
+/// struct Child {
+///     parent: CellLink<Parent>,
+/// }
+///
+/// let mut owner = QCellOwner::new();
+/// let mut builder = CellGraphBuilder::new(&owner);
+/// let parent = builder.declare::<Parent>();
+/// let child = builder.declare::<Child>();
+/// builder.define(&parent, Parent { name: "root", child: child.handle() });
+/// builder.define(&child, Child { parent: parent.handle() });
+/// let graph = builder.finish(&mut owner).unwrap();
+///
+/// let child = graph.get(&child);
+/// let link = owner.ro(child).parent.clone();
+/// assert_eq!(owner.ro(&link).name, "root");
+/// owner.rw(&link).name = "top";
+/// assert_eq!(owner.ro(graph.get(&parent)).name, "top");
+/// ```
+///
+/// Links are strong references, like `Rc`, so cells which link to
+/// each other in a cycle keep each other alive.  To free them, replace
+/// a link in the cycle, for example with a [`CellLink::new`] to some
+/// other cell.
+///
+/// [`CellGraphBuilder::declare`]: #method.declare
+/// [`CellGraphBuilder::define`]: #method.define
+/// [`CellGraphBuilder::finish`]: #method.finish
+/// [`Declared`]: struct.Declared.html
+/// [`Declared::handle`]: struct.Declared.html#method.handle
+/// [`CellLink`]: struct.CellLink.html
+/// [`CellLink::new`]: struct.CellLink.html#method.new
+/// [`QCell`]: struct.QCell.html
+pub struct CellGraphBuilder {
+    owner: QCellOwnerID,
+    token: Rc<()>,
+    entries: Vec<Entry>,
+}
+
+impl CellGraphBuilder {
+    /// Create a builder for cells owned by the given owner
+    #[inline]
+    pub fn new(owner: impl Into<QCellOwnerID>) -> Self {
+        Self {
+            owner: owner.into(),
+            token: Rc::new(()),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Declare a cell, to be given its value later with
+    /// [`CellGraphBuilder::define`]
+    ///
+    /// [`CellGraphBuilder::define`]: #method.define
+    pub fn declare<T: 'static>(&mut self) -> Declared<T> {
+        let slot = Rc::new(Slot {
+            index: self.entries.len(),
+            builder: self.token.clone(),
+            cell: OnceCell::new(),
+        });
+        self.entries.push(Entry {
+            type_name: type_name::<T>(),
+            definitions: 0,
+            slot: slot.clone(),
+            resolve: None,
+        });
+        Declared { slot }
+    }
+
+    /// Give a declared cell its value.  If the cell is defined more
+    /// than once, the first value is kept, and
+    /// [`CellGraphBuilder::finish`] reports the error.  Panics if
+    /// `declared` came from a different builder.
+    ///
+    /// [`CellGraphBuilder::finish`]: #method.finish
+    pub fn define<T: 'static>(&mut self, declared: &Declared<T>, value: T) {
+        let index = declared.slot.index;
+        if !Rc::ptr_eq(&declared.slot.builder, &self.token) {
+            foreign_declared_panic(index);
+        }
+        let entry = &mut self.entries[index];
+        entry.definitions += 1;
+        if entry.resolve.is_none() {
+            let slot = declared.slot.clone();
+            let cell = Rc::new(QCell::new(self.owner, value));
+            entry.resolve = Some(Box::new(move || {
+                let _ = slot.cell.set(cell);
+            }));
+        }
+    }
+
+    /// Check that every declared cell was defined exactly once, and
+    /// if so, resolve all the links.  Otherwise the error lists the
+    /// cells which were not, and no links are resolved.  Panics if
+    /// `owner` is not the owner given to [`CellGraphBuilder::new`].
+    ///
+    /// [`CellGraphBuilder::new`]: #method.new
+    pub fn finish(self, owner: &mut impl QCellBorrow) -> Result<GraphHandles, GraphBuildError> {
+        let mut missing = Vec::new();
+        let mut duplicated = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let cell = DeclaredCell {
+                index,
+                type_name: entry.type_name,
+                definitions: entry.definitions,
+            };
+            match entry.definitions {
+                0 => missing.push(cell),
+                1 => (),
+                _ => duplicated.push(cell),
+            }
+        }
+        if !missing.is_empty() || !duplicated.is_empty() {
+            return Err(GraphBuildError {
+                missing,
+                duplicated,
+            });
+        }
+
+        // Check the owner before resolving anything
+        owner.qcell_ro(&QCell::new(self.owner, ()));
+
+        let mut slots = Vec::with_capacity(self.entries.len());
+        for mut entry in self.entries {
+            if let Some(resolve) = entry.resolve.take() {
+                resolve();
+            }
+            slots.push(entry.slot);
+        }
+        Ok(GraphHandles {
+            token: self.token,
+            slots,
+        })
+    }
+}
+
+/// A cell declared with [`CellGraphBuilder::declare`], but not yet
+/// built.
+///
+/// [`CellGraphBuilder::declare`]: struct.CellGraphBuilder.html#method.declare
+pub struct Declared<T> {
+    slot: Rc<Slot<T>>,
+}
+
+impl<T> Declared<T> {
+    /// Get a link to this cell, to store in the values of other
+    /// cells.  The link is resolved by
+    /// [`CellGraphBuilder::finish`].
+    ///
+    /// [`CellGraphBuilder::finish`]: struct.CellGraphBuilder.html#method.finish
+    #[inline]
+    pub fn handle(&self) -> CellLink<T> {
+        CellLink {
+            slot: self.slot.clone(),
+        }
+    }
+
+    /// Get the position of this cell in the order of declaration, as
+    /// used in [`GraphBuildError`]
+    ///
+    /// [`GraphBuildError`]: struct.GraphBuildError.html
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.slot.index
+    }
+}
+
+impl<T> Clone for Declared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+/// A link to a cell built by a [`CellGraphBuilder`].
+///
+/// Once [`CellGraphBuilder::finish`] has succeeded, this refers to an
+/// `Rc<QCell<T>>`, and derefs to the [`QCell`].  Using it before then
+/// panics.
+///
+/// [`CellGraphBuilder`]: struct.CellGraphBuilder.html
+/// [`CellGraphBuilder::finish`]: struct.CellGraphBuilder.html#method.finish
+/// [`QCell`]: struct.QCell.html
+pub struct CellLink<T> {
+    slot: Rc<Slot<T>>,
+}
+
+impl<T> CellLink<T> {
+    /// Create a link which is already resolved to an existing cell
+    pub fn new(cell: Rc<QCell<T>>) -> Self {
+        let slot = Slot {
+            index: 0,
+            builder: Rc::new(()),
+            cell: OnceCell::new(),
+        };
+        let _ = slot.cell.set(cell);
+        Self {
+            slot: Rc::new(slot),
+        }
+    }
+
+    /// Get the `Rc` of the cell.  Panics if the link has not been
+    /// resolved yet.
+    #[inline]
+    pub fn get(&self) -> &Rc<QCell<T>> {
+        match self.slot.cell.get() {
+            Some(cell) => cell,
+            None => panic!("{}", panic_messages::CELL_LINK_UNRESOLVED),
+        }
+    }
+
+    /// Test whether the link has been resolved yet
+    #[inline]
+    pub fn is_resolved(&self) -> bool {
+        self.slot.cell.get().is_some()
+    }
+}
+
+impl<T> Deref for CellLink<T> {
+    type Target = QCell<T>;
+
+    #[inline]
+    fn deref(&self) -> &QCell<T> {
+        self.get()
+    }
+}
+
+impl<T> Clone for CellLink<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for CellLink<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CellLink")
+            .field("resolved", &self.is_resolved())
+            .finish()
+    }
+}
+
+/// The cells built by [`CellGraphBuilder::finish`].
+///
+/// [`CellGraphBuilder::finish`]: struct.CellGraphBuilder.html#method.finish
+pub struct GraphHandles {
+    token: Rc<()>,
+    slots: Vec<Rc<dyn Any>>,
+}
+
+impl GraphHandles {
+    /// Get the `Rc` of a built cell.  Panics if `declared` came from a
+    /// different builder.
+    pub fn get<T: 'static>(&self, declared: &Declared<T>) -> &Rc<QCell<T>> {
+        let index = declared.slot.index;
+        if !Rc::ptr_eq(&declared.slot.builder, &self.token) {
+            foreign_declared_panic(index);
+        }
+        let slot = self.slots[index].downcast_ref::<Slot<T>>();
+        // Every slot is resolved once `finish` succeeds
+        slot.and_then(|slot| slot.cell.get()).unwrap()
+    }
+
+    /// Get the number of cells built
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Test whether no cells were built
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// A cell which [`GraphBuildError`] reports was not defined exactly
+/// once.
+///
+/// [`GraphBuildError`]: struct.GraphBuildError.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DeclaredCell {
+    /// Position in the order of declaration, as returned by
+    /// `Declared::index`
+    pub index: usize,
+    /// Type of the cell's value
+    pub type_name: &'static str,
+    /// Number of times the cell was defined
+    pub definitions: usize,
+}
+
+/// Error from [`CellGraphBuilder::finish`], listing the cells which
+/// were not defined exactly once.
+///
+/// [`CellGraphBuilder::finish`]: struct.CellGraphBuilder.html#method.finish
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GraphBuildError {
+    /// Cells which were declared but never defined
+    pub missing: Vec<DeclaredCell>,
+    /// Cells which were defined more than once
+    pub duplicated: Vec<DeclaredCell>,
+}
+
+impl fmt::Display for GraphBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cells not defined exactly once:")?;
+        for cell in &self.missing {
+            write!(f, " #{} ({}) missing;", cell.index, cell.type_name)?;
+        }
+        for cell in &self.duplicated {
+            write!(
+                f,
+                " #{} ({}) defined {} times;",
+                cell.index, cell.type_name, cell.definitions
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GraphBuildError {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{CellGraphBuilder, CellLink, DeclaredCell};
+    use crate::QCellOwner;
+    use std::rc::Rc;
+
+    struct Node {
+        value: u32,
+        next: CellLink<Node>,
+    }
+
+    struct Parent {
+        child: CellLink<Child>,
+    }
+
+    struct Child {
+        parent: CellLink<Parent>,
+    }
+
+    #[test]
+    fn mutual_pair() {
+        let mut owner = QCellOwner::new();
+        let mut builder = CellGraphBuilder::new(&owner);
+        let p = builder.declare::<Parent>();
+        let c = builder.declare::<Child>();
+        let link = c.handle();
+        assert!(!link.is_resolved());
+        builder.define(&c, Child { parent: p.handle() });
+        builder.define(&p, Parent { child: c.handle() });
+        let graph = builder.finish(&mut owner).unwrap();
+        assert_eq!(graph.len(), 2);
+        assert!(link.is_resolved());
+
+        let parent = graph.get(&p);
+        let child = graph.get(&c);
+        assert!(Rc::ptr_eq(owner.ro(parent).child.get(), child));
+        assert!(Rc::ptr_eq(owner.ro(child).parent.get(), parent));
+        assert!(Rc::ptr_eq(link.get(), child));
+    }
+
+    #[test]
+    fn cycle_of_three() {
+        let mut owner = QCellOwner::new();
+        let mut builder = CellGraphBuilder::new(&owner);
+        let nodes: Vec<_> = (0..3).map(|_| builder.declare::<Node>()).collect();
+        for (i, node) in nodes.iter().enumerate() {
+            let next = nodes[(i + 1) % 3].handle();
+            builder.define(node, Node { value: i as u32, next });
+        }
+        let graph = builder.finish(&mut owner).unwrap();
+
+        // Walk round the cycle twice, changing each value on the way
+        let mut cell = graph.get(&nodes[0]).clone();
+        let mut seen = Vec::new();
+        for _ in 0..6 {
+            owner.rw(&cell).value += 10;
+            seen.push(owner.ro(&cell).value);
+            let next = owner.ro(&cell).next.get().clone();
+            cell = next;
+        }
+        assert_eq!(seen, [10, 11, 12, 20, 21, 22]);
+
+        // Replacing the link back to the first cell breaks the cycle,
+        // so that the first cell is freed
+        let weak = Rc::downgrade(graph.get(&nodes[0]));
+        let last = graph.get(&nodes[2]).clone();
+        owner.rw(&last).next = CellLink::new(last.clone());
+        drop((graph, nodes, cell));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn missing_definition() {
+        let mut owner = QCellOwner::new();
+        let mut builder = CellGraphBuilder::new(&owner);
+        let p = builder.declare::<Parent>();
+        let c = builder.declare::<Child>();
+        builder.define(&p, Parent { child: c.handle() });
+        let err = builder.finish(&mut owner).err().unwrap();
+        assert_eq!(
+            err.missing,
+            [DeclaredCell {
+                index: 1,
+                type_name: std::any::type_name::<Child>(),
+                definitions: 0,
+            }]
+        );
+        assert!(err.duplicated.is_empty());
+        assert!(err.to_string().contains("#1"));
+        // The link was never resolved
+        assert!(!p.handle().is_resolved());
+    }
+
+    #[test]
+    fn double_definition() {
+        let mut owner = QCellOwner::new();
+        let mut builder = CellGraphBuilder::new(&owner);
+        let a = builder.declare::<Node>();
+        builder.define(&a, Node { value: 1, next: a.handle() });
+        builder.define(&a, Node { value: 2, next: a.handle() });
+        let err = builder.finish(&mut owner).err().unwrap();
+        assert!(err.missing.is_empty());
+        assert_eq!(err.duplicated.len(), 1);
+        assert_eq!((err.duplicated[0].index, err.duplicated[0].definitions), (0, 2));
+        assert!(err.to_string().contains("defined 2 times"));
+    }
+
+    #[test]
+    fn wrong_owner_or_builder_panics() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        let owner = QCellOwner::new();
+        let mut other = QCellOwner::new();
+        let mut builder = CellGraphBuilder::new(&owner);
+        let a = builder.declare::<u32>();
+        let mut builder2 = CellGraphBuilder::new(&owner);
+        assert!(catch_unwind(AssertUnwindSafe(|| builder2.define(&a, 1))).is_err());
+        builder.define(&a, 1);
+        assert!(catch_unwind(AssertUnwindSafe(|| builder.finish(&mut other))).is_err());
+    }
+
+    #[test]
+    fn unresolved_link_panics() {
+        let owner = QCellOwner::new();
+        let mut builder = CellGraphBuilder::new(&owner);
+        let a = builder.declare::<u32>();
+        let link = a.handle();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            link.get();
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use alloc::rc::{self, Rc};
+use alloc::sync::{self, Arc};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+use crate::{QCell, QCellBorrow, QCellOwnerID};
+
+macro_rules! cell_ptr {
+    (
+        $(#[$meta:meta])* $name:ident($ptr:ident),
+        $(#[$weak_meta:meta])* $weak:ident($weak_ptr:ty)
+    ) => {
+        $(#[$meta])*
+        pub struct $name<T: ?Sized>($ptr<QCell<T>>);
+
+        $(#[$weak_meta])*
+        pub struct $weak<T: ?Sized>($weak_ptr);
+
+        impl<T> $name<T> {
+            /// Create a new cell owned by the given owner ID
+            #[inline]
+            pub fn new(owner: impl Into<QCellOwnerID>, value: T) -> Self {
+                Self($ptr::new(QCell::new(owner, value)))
+            }
+        }
+
+        impl<T: ?Sized> $name<T> {
+            /// Borrow the contents immutably.  Panics if the cell is
+            /// not owned by this owner.
+            #[inline]
+            pub fn ro<'a, O: QCellBorrow>(&'a self, owner: &'a O) -> &'a T {
+                owner.qcell_ro(&self.0)
+            }
+
+            /// Borrow the contents mutably.  Panics if the cell is not
+            /// owned by this owner.
+            #[inline]
+            pub fn rw<'a, O: QCellBorrow>(&'a self, owner: &'a mut O) -> &'a mut T {
+                owner.qcell_rw(&self.0)
+            }
+
+            /// Get the cell, to pass to the owner's own methods.  The
+            /// handle also derefs to the cell.
+            #[inline]
+            pub fn as_cell(&self) -> &QCell<T> {
+                &self.0
+            }
+
+            /// Test whether two handles refer to the same cell
+            #[inline]
+            pub fn ptr_eq(&self, other: &Self) -> bool {
+                $ptr::ptr_eq(&self.0, &other.0)
+            }
+
+            /// Create a weak handle to the same cell
+            #[inline]
+            pub fn downgrade(&self) -> $weak<T> {
+                $weak($ptr::downgrade(&self.0))
+            }
+
+            /// Create a new cell with the same owner, holding the value
+            /// returned by `f`, which is passed the contents of this
+            /// cell.  Panics if this cell is not owned by this owner.
+            pub fn map_new<O: QCellBorrow, U>(&self, owner: &O, f: impl FnOnce(&T) -> U) -> $name<U> {
+                let value = f(owner.qcell_ro(&self.0));
+                $name::new(self.0.owner, value)
+            }
+
+            /// Get a wrapper which formats the contents with `Debug`,
+            /// since the handle itself can't see them without the
+            /// owner.  Panics when formatted if the cell is not owned
+            /// by this owner.
+            #[inline]
+            pub fn debug<'a, O: QCellBorrow>(&'a self, owner: &'a O) -> impl fmt::Debug + 'a
+            where
+                T: fmt::Debug,
+            {
+                DebugWith(self.ro(owner))
+            }
+        }
+
+        impl<T: ?Sized> Clone for $name<T> {
+            #[inline]
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T: ?Sized> Deref for $name<T> {
+            type Target = QCell<T>;
+
+            #[inline]
+            fn deref(&self) -> &QCell<T> {
+                &self.0
+            }
+        }
+
+        impl<T: ?Sized> From<$ptr<QCell<T>>> for $name<T> {
+            #[inline]
+            fn from(ptr: $ptr<QCell<T>>) -> Self {
+                Self(ptr)
+            }
+        }
+
+        impl<T: ?Sized> From<$name<T>> for $ptr<QCell<T>> {
+            #[inline]
+            fn from(ptr: $name<T>) -> Self {
+                ptr.0
+            }
+        }
+
+        impl<T: ?Sized> PartialEq for $name<T> {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                self.ptr_eq(other)
+            }
+        }
+
+        impl<T: ?Sized> Eq for $name<T> {}
+
+        impl<T: ?Sized> Hash for $name<T> {
+            #[inline]
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                $ptr::as_ptr(&self.0).cast::<()>().hash(state);
+            }
+        }
+
+        impl<T: ?Sized> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_tuple(stringify!($name))
+                    .field(&$ptr::as_ptr(&self.0).cast::<()>())
+                    .finish()
+            }
+        }
+
+        impl<T: ?Sized> $weak<T> {
+            /// Get a strong handle to the cell, or `None` if it has
+            /// been dropped
+            #[inline]
+            pub fn upgrade(&self) -> Option<$name<T>> {
+                self.0.upgrade().map($name)
+            }
+
+            /// Test whether two weak handles refer to the same cell
+            #[inline]
+            pub fn ptr_eq(&self, other: &Self) -> bool {
+                self.0.ptr_eq(&other.0)
+            }
+        }
+
+        impl<T: ?Sized> Clone for $weak<T> {
+            #[inline]
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl<T: ?Sized> fmt::Debug for $weak<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(concat!(stringify!($weak), "(..)"))
+            }
+        }
+    };
+}
+
+cell_ptr!(
+    /// Handle to a [`QCell`] behind an `Rc`, for sharing a cell
+    /// within one thread.
+    ///
+    /// This wraps `Rc<QCell<T>>` with methods taking the owner, and
+    /// compares and hashes by identity, so that handles can be kept
+    /// in a `HashSet` or used as `HashMap` keys directly.  Changing
+    /// the contents doesn't change the hash, although Clippy's
+    /// `mutable_key_type` lint can't tell, so may need allowing.
+    /// `Clone` clones the handle, not the contents.  The handle derefs to the
+    /// [`QCell`], so it can also be passed to the owner's own methods.
+    /// `Debug` only shows the address, since the contents can't be
+    /// read without the owner, but [`CellPtr::debug`] gives a
+    /// wrapper that formats the contents.
+    ///
+    /// ```
+    ///# use qcell::{CellPtr, QCellOwner};
+    ///# use std::collections::HashSet;
+    /// let mut owner = QCellOwner::new();
+    /// let a = CellPtr::new(&owner, String::from("a"));
+    /// let b = a.map_new(&owner, |s| s.clone() + "b");
+    /// a.rw(&mut owner).push('!');
+    /// assert_eq!((a.ro(&owner).as_str(), owner.ro(&b).as_str()), ("a!", "ab"));
+    ///
+    /// let mut visited = HashSet::new();
+    /// assert!(visited.insert(a.clone()));
+    /// assert!(visited.insert(b));
+    /// assert!(!visited.insert(a.clone()));
+    /// assert_eq!(format!("{:?}", a.debug(&owner)), "\"a!\"");
+    /// ```
+    ///
+    /// For a handle that can be shared between threads, see
+    /// [`CellArc`].
+    ///
+    /// [`QCell`]: struct.QCell.html
+    /// [`CellPtr::debug`]: struct.CellPtr.html#method.debug
+    /// [`CellArc`]: struct.CellArc.html
+    CellPtr(Rc),
+    /// Weak handle to a [`CellPtr`] cell, which doesn't keep the cell
+    /// alive
+    ///
+    /// [`CellPtr`]: struct.CellPtr.html
+    WeakCellPtr(rc::Weak<QCell<T>>)
+);
+
+cell_ptr!(
+    /// Handle to a [`QCell`] behind an `Arc`, which may be shared
+    /// between threads.  This is the same as [`CellPtr`] apart from
+    /// using `Arc`.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    /// [`CellPtr`]: struct.CellPtr.html
+    CellArc(Arc),
+    /// Weak handle to a [`CellArc`] cell, which doesn't keep the cell
+    /// alive
+    ///
+    /// [`CellArc`]: struct.CellArc.html
+    WeakCellArc(sync::Weak<QCell<T>>)
+);
+
+struct DebugWith<'a, T: ?Sized>(&'a T);
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for DebugWith<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{CellArc, CellPtr, QCell, QCellOwner, QCellOwnerSeq};
+    use std::collections::{HashMap, HashSet};
+    use std::rc::Rc;
+
+    #[test]
+    // Hashed by identity, so changing the contents is fine
+    #[allow(clippy::mutable_key_type)]
+    fn cell_ptr_hash_set_by_identity() {
+        let mut owner = QCellOwner::new();
+        let a = CellPtr::new(&owner, 1);
+        let b = CellPtr::new(&owner, 1);
+        assert_ne!(a, b);
+        assert_eq!(a, a.clone());
+        let mut set = HashSet::new();
+        assert!(set.insert(a.clone()));
+        assert!(set.insert(b.clone()));
+        assert!(!set.insert(a.clone()));
+        // Changing the contents doesn't move it in the set
+        *a.rw(&mut owner) = 2;
+        assert!(set.contains(&a));
+        let mut names = HashMap::new();
+        names.insert(b.clone(), "b");
+        assert_eq!(names[&b], "b");
+        assert!(!names.contains_key(&a));
+    }
+
+    #[test]
+    fn cell_ptr_upgrade_downgrade() {
+        let owner = QCellOwner::new();
+        let a = CellPtr::new(&owner, vec![1]);
+        let weak = a.downgrade();
+        assert!(weak.ptr_eq(&a.downgrade()));
+        for _ in 0..3 {
+            let strong = weak.upgrade().unwrap();
+            assert_eq!(strong, a);
+            assert!(strong.downgrade().upgrade().unwrap().ptr_eq(&a));
+        }
+        drop(a);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(format!("{:?}", weak), "WeakCellPtr(..)");
+    }
+
+    #[test]
+    fn cell_ptr_owner_interop() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let a = CellPtr::new(&owner, 10_u32);
+        // Derefs to the cell, for the owner's own methods
+        *owner.rw(&a) += 1;
+        owner.set(a.as_cell(), owner.get(&a) * 2);
+        assert_eq!(*a.ro(&owner), 22);
+        let sibling = a.map_new(&owner, |v| v.to_string());
+        assert_eq!(sibling.ro(&owner), "22");
+
+        let shared: Rc<QCell<u32>> = a.clone().into();
+        assert_eq!(owner.get(&shared), 22);
+        assert!(CellPtr::from(shared).ptr_eq(&a));
+        assert!(format!("{:?}", a).starts_with("CellPtr(0x"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cell_ptr_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let a = CellPtr::new(&owner2, 0);
+        a.ro(&owner1);
+    }
+
+    #[test]
+    fn cell_arc_across_threads() {
+        let mut owner = QCellOwner::new();
+        let a = CellArc::new(&owner, 0_u64);
+        let handle = {
+            let a = a.clone();
+            std::thread::spawn(move || {
+                *a.rw(&mut owner) += 5;
+                owner
+            })
+        };
+        let owner = handle.join().unwrap();
+        assert_eq!(*a.ro(&owner), 5);
+        let weak = a.downgrade();
+        assert_eq!(weak.upgrade().unwrap(), a);
+    }
+}
@@ -0,0 +1,265 @@
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+
+use crate::{QCell, QCellOwner, QCellOwnerID};
+
+/// Implemented by cell contents that hold `Rc` references to other
+/// cells of the same type, to allow [`clone_graph_rewrite`] to point
+/// those references at the copies.
+///
+/// [`clone_graph_rewrite`]: fn.clone_graph_rewrite.html
+pub trait RewriteRefs: Sized {
+    /// Replace every `Rc<QCell<Self>>` held by this value with the
+    /// result of calling `map` on it
+    #[allow(clippy::type_complexity)]
+    fn rewrite(&mut self, map: &dyn Fn(&Rc<QCell<Self>>) -> Rc<QCell<Self>>);
+}
+
+type CellMap<T> = BTreeMap<*const QCell<T>, Rc<QCell<T>>>;
+
+// Clone the value of each distinct cell into a new cell owned by
+// `dst`, returning the new cells in order along with the map from
+// old cells to new ones
+fn clone_cells<T: Clone>(
+    src: &QCellOwner,
+    dst: QCellOwnerID,
+    cells: &[Rc<QCell<T>>],
+) -> (Vec<Rc<QCell<T>>>, CellMap<T>) {
+    let mut map = CellMap::new();
+    let copy = cells
+        .iter()
+        .map(|cell| {
+            map.entry(Rc::as_ptr(cell))
+                .or_insert_with(|| Rc::new(dst.cell(src.ro(cell).clone())))
+                .clone()
+        })
+        .collect();
+    (copy, map)
+}
+
+// Point the references held by each new cell at the new cells.
+// References to cells outside the map are left as they are.
+fn rewrite_cells<T: RewriteRefs>(dst: &mut QCellOwner, map: &CellMap<T>) {
+    let lookup = |old: &Rc<QCell<T>>| match map.get(&Rc::as_ptr(old)) {
+        Some(new) => new.clone(),
+        None => old.clone(),
+    };
+    for new in map.values() {
+        dst.rw(new).rewrite(&lookup);
+    }
+}
+
+/// Clone a list of cells owned by `src` into new cells owned by
+/// `dst`, returning them in the same order.
+///
+/// Cells are matched by pointer identity, so where the same cell
+/// appears more than once in `cells`, the copy has a single new cell
+/// shared in those positions.  The values are cloned with `Clone`, so
+/// any `Rc` references that they hold still point to the original
+/// cells.  Use [`clone_graph_rewrite`] to point them at the copies
+/// instead.  Panics if any cell is not owned by `src`.
+///
+/// ```
+///# use qcell::{clone_graph, QCellOwner};
+///# use std::rc::Rc;
+/// let mut owner = QCellOwner::new();
+/// let shared = Rc::new(owner.cell(1));
+/// let cells = vec![shared.clone(), Rc::new(owner.cell(2)), shared];
+/// let copy = clone_graph(&owner, &owner, &cells);
+/// assert!(Rc::ptr_eq(&copy[0], &copy[2]));
+/// assert!(!Rc::ptr_eq(&copy[0], &cells[0]));
+/// *owner.rw(&copy[0]) += 10;
+/// assert_eq!((owner.get(&cells[0]), owner.get(&copy[2])), (1, 11));
+/// ```
+///
+/// [`clone_graph_rewrite`]: fn.clone_graph_rewrite.html
+pub fn clone_graph<T: Clone>(
+    src: &QCellOwner,
+    dst: impl Into<QCellOwnerID>,
+    cells: &[Rc<QCell<T>>],
+) -> Vec<Rc<QCell<T>>> {
+    clone_cells(src, dst.into(), cells).0
+}
+
+/// Deep-copy a graph of cells owned by `src` into new cells owned by
+/// `dst`, rewriting the references between them.
+///
+/// This works like [`clone_graph`], but then calls
+/// [`RewriteRefs::rewrite`] on each new value, so that references to
+/// any of the cells in `cells` are replaced with references to their
+/// copies.  This gives a true deep copy of a self-referential graph,
+/// including cycles.  References to cells not in `cells` are left
+/// pointing at the original cells.  Since the new values are
+/// modified after they are placed in their cells, this needs mutable
+/// access to `dst`.  To copy a graph within the same owner, use
+/// [`clone_graph_rewrite_in`].  Panics if any cell is not owned by
+/// `src`.
+///
+/// [`clone_graph`]: fn.clone_graph.html
+/// [`clone_graph_rewrite_in`]: fn.clone_graph_rewrite_in.html
+/// [`RewriteRefs::rewrite`]: trait.RewriteRefs.html#tymethod.rewrite
+pub fn clone_graph_rewrite<T: Clone + RewriteRefs>(
+    src: &QCellOwner,
+    dst: &mut QCellOwner,
+    cells: &[Rc<QCell<T>>],
+) -> Vec<Rc<QCell<T>>> {
+    let (copy, map) = clone_cells(src, dst.id(), cells);
+    rewrite_cells(dst, &map);
+    copy
+}
+
+/// Same as [`clone_graph_rewrite`], but with the copy owned by the
+/// same owner as the original.
+///
+/// ```
+///# use qcell::{clone_graph_rewrite_in, QCell, QCellOwner, RewriteRefs};
+///# use std::rc::Rc;
+/// #[derive(Clone)]
+/// struct Node {
+///     value: i32,
+///     next: Option<Rc<QCell<Node>>>,
+/// }
+///
+/// impl RewriteRefs for Node {
+///     fn rewrite(&mut self, map: &dyn Fn(&Rc<QCell<Node>>) -> Rc<QCell<Node>>) {
+///         self.next = self.next.as_ref().map(map);
+///     }
+/// }
+///
+/// let mut owner = QCellOwner::new();
+/// let tail = Rc::new(owner.cell(Node { value: 2, next: None }));
+/// let head = Rc::new(owner.cell(Node { value: 1, next: Some(tail.clone()) }));
+/// let copy = clone_graph_rewrite_in(&mut owner, &[head, tail]);
+/// let next = owner.ro(&copy[0]).next.clone().unwrap();
+/// assert!(Rc::ptr_eq(&next, &copy[1]));
+/// ```
+///
+/// [`clone_graph_rewrite`]: fn.clone_graph_rewrite.html
+pub fn clone_graph_rewrite_in<T: Clone + RewriteRefs>(
+    owner: &mut QCellOwner,
+    cells: &[Rc<QCell<T>>],
+) -> Vec<Rc<QCell<T>>> {
+    let (copy, map) = clone_cells(owner, owner.id(), cells);
+    rewrite_cells(owner, &map);
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clone_graph, clone_graph_rewrite, clone_graph_rewrite_in, RewriteRefs};
+    use crate::{QCell, QCellOwner};
+    use std::rc::Rc;
+
+    #[derive(Clone)]
+    struct Node {
+        value: u32,
+        edges: Vec<Rc<QCell<Node>>>,
+    }
+
+    impl RewriteRefs for Node {
+        fn rewrite(&mut self, map: &dyn Fn(&Rc<QCell<Node>>) -> Rc<QCell<Node>>) {
+            for edge in &mut self.edges {
+                *edge = map(edge);
+            }
+        }
+    }
+
+    fn node(owner: &QCellOwner, value: u32) -> Rc<QCell<Node>> {
+        Rc::new(owner.cell(Node {
+            value,
+            edges: Vec::new(),
+        }))
+    }
+
+    // Break cycles so that the test doesn't leak
+    fn unlink(owner: &mut QCellOwner, cells: &[Rc<QCell<Node>>]) {
+        for cell in cells {
+            owner.rw(cell).edges.clear();
+        }
+    }
+
+    fn index_of(cells: &[Rc<QCell<Node>>], cell: &Rc<QCell<Node>>) -> Option<usize> {
+        cells.iter().position(|c| Rc::ptr_eq(c, cell))
+    }
+
+    #[test]
+    fn clone_graph_diamond() {
+        let src = QCellOwner::new();
+        let mut dst = QCellOwner::new();
+        let top = Rc::new(src.cell(1));
+        let left = Rc::new(src.cell(2));
+        let right = Rc::new(src.cell(3));
+        let bottom = Rc::new(src.cell(4));
+        // The two paths through the diamond share `bottom`
+        let cells = [top, left, bottom.clone(), right, bottom];
+        let copy = clone_graph(&src, &dst, &cells);
+        assert_eq!(copy.len(), 5);
+        assert!(Rc::ptr_eq(&copy[2], &copy[4]));
+        assert_eq!(Rc::strong_count(&copy[2]), 2);
+        for (old, new) in cells.iter().zip(&copy) {
+            assert!(!Rc::ptr_eq(old, new));
+            assert_eq!(src.get(old), dst.get(new));
+        }
+
+        *dst.rw(&copy[4]) = 40;
+        assert_eq!(dst.get(&copy[2]), 40);
+        assert_eq!(src.get(&cells[2]), 4);
+    }
+
+    #[test]
+    fn clone_graph_cycle_rewrite() {
+        let mut src = QCellOwner::new();
+        let mut dst = QCellOwner::new();
+        let nodes: Vec<_> = (0..4).map(|i| node(&src, i)).collect();
+        // Ring 0 -> 1 -> 2 -> 3 -> 0, plus 0 -> 2 and a self-loop on 3
+        for i in 0..4 {
+            let next = nodes[(i + 1) % 4].clone();
+            src.rw(&nodes[i]).edges.push(next);
+        }
+        let two = nodes[2].clone();
+        src.rw(&nodes[0]).edges.push(two);
+        let three = nodes[3].clone();
+        src.rw(&nodes[3]).edges.push(three);
+
+        let copy = clone_graph_rewrite(&src, &mut dst, &nodes);
+        for (i, (old, new)) in nodes.iter().zip(&copy).enumerate() {
+            let old = src.ro(old);
+            let new = dst.ro(new);
+            assert_eq!(old.value, new.value);
+            let old_edges: Vec<_> = old.edges.iter().map(|e| index_of(&nodes, e)).collect();
+            let new_edges: Vec<_> = new.edges.iter().map(|e| index_of(&copy, e)).collect();
+            assert_eq!(old_edges, new_edges, "edges of node {}", i);
+            assert!(new_edges.iter().all(Option::is_some));
+        }
+
+        // The copy is independent of the original
+        dst.rw(&copy[1]).value = 100;
+        assert_eq!(src.ro(&nodes[1]).value, 1);
+        let next = dst.ro(&copy[0]).edges[0].clone();
+        assert_eq!(dst.ro(&next).value, 100);
+
+        unlink(&mut src, &nodes);
+        unlink(&mut dst, &copy);
+    }
+
+    #[test]
+    fn clone_graph_rewrite_outside_edge() {
+        let mut owner = QCellOwner::new();
+        let outside = node(&owner, 0);
+        let inside = node(&owner, 1);
+        let edge = outside.clone();
+        owner.rw(&inside).edges.push(edge);
+        let copy = clone_graph_rewrite_in(&mut owner, std::slice::from_ref(&inside));
+        assert!(Rc::ptr_eq(&owner.ro(&copy[0]).edges[0], &outside));
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn clone_graph_bad_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cells = [Rc::new(owner1.cell(0))];
+        clone_graph(&owner2, &owner2, &cells);
+    }
+}
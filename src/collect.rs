@@ -0,0 +1,269 @@
+//! Collecting iterators into containers of cells.
+//!
+//! [`CollectCells::collect_cells`] works like `Iterator::collect`,
+//! but also passes an owner (or owner-ID) through to the container
+//! so that the cells can be created.  For [`QCell`] this is
+//! necessary since every cell records its owner's ID.  [`TCell`],
+//! [`TLCell`] and [`LCell`] don't need it, but the same call is
+//! supported for them so that generic code reads the same whichever
+//! cell type is used.
+//!
+//! Collecting an iterator of `Result<T, E>` values into a
+//! `Result<Vec<_>, (usize, E)>` stops at the first error, and
+//! reports the index of the item that failed along with the error.
+//!
+//! [`CollectCells::collect_cells`]: trait.CollectCells.html#method.collect_cells
+//! [`QCell`]: ../struct.QCell.html
+//! [`TCell`]: ../struct.TCell.html
+//! [`TLCell`]: ../struct.TLCell.html
+//! [`LCell`]: ../struct.LCell.html
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::tcell::TCellMarker;
+use crate::{LCell, LCellOwner, QCell, QCellOwner, QCellOwnerID, QCellOwnerSeq};
+use crate::{TCell, TCellOwner};
+#[cfg(feature = "std")]
+use crate::{TLCell, TLCellOwner};
+
+/// Conversion from an iterator, given an owner of type `O`.  This is
+/// the equivalent of `FromIterator` for containers of cells.  It is
+/// normally used via [`CollectCells::collect_cells`].
+///
+/// [`CollectCells::collect_cells`]: trait.CollectCells.html#method.collect_cells
+pub trait FromIteratorWithOwner<A, O: ?Sized>: Sized {
+    /// Create a value from an iterator, creating cells as necessary
+    /// to be owned by `owner`
+    fn from_iter_with_owner<I: IntoIterator<Item = A>>(iter: I, owner: &O) -> Self;
+}
+
+/// Extension trait for iterators to allow them to be collected into
+/// containers of cells.  Implemented for all iterators.
+pub trait CollectCells: Iterator + Sized {
+    /// Collect the items of this iterator into a container of cells
+    /// owned by `owner`.  The owner may be an owner-ID or an owner,
+    /// depending on the cell type.  If the items are `Result<T, E>`
+    /// values, then collecting into a `Result` stops at the first
+    /// error and returns its index along with the error.
+    ///
+    /// ```
+    ///# use qcell::{QCell, QCellOwner};
+    ///# use qcell::collect::CollectCells;
+    /// let mut owner = QCellOwner::new();
+    /// let id = owner.id();
+    /// let cells: Result<Vec<QCell<i32>>, _> =
+    ///     "1 2 3 4".split(' ').map(str::parse::<i32>).collect_cells(&id);
+    /// let cells = cells.unwrap();
+    /// let (a, b, c) = owner.rw3(&cells[0], &cells[1], &cells[2]);
+    /// std::mem::swap(a, c);
+    /// *b += 10;
+    /// assert_eq!(*owner.ro(&cells[0]), 3);
+    /// assert_eq!(*owner.ro(&cells[1]), 12);
+    /// assert_eq!(*owner.ro(&cells[2]), 1);
+    ///
+    /// let cells: Result<Vec<QCell<i32>>, _> =
+    ///     "1 2 x 4".split(' ').map(str::parse::<i32>).collect_cells(&id);
+    /// assert_eq!(cells.err().map(|(i, _)| i), Some(2));
+    /// ```
+    fn collect_cells<C, O>(self, owner: &O) -> C
+    where
+        C: FromIteratorWithOwner<Self::Item, O>,
+        O: ?Sized,
+    {
+        C::from_iter_with_owner(self, owner)
+    }
+}
+
+impl<I: Iterator> CollectCells for I {}
+
+// Collect `Ok` values into a `Vec` using `f`, stopping at the first
+// error and reporting its index
+fn try_collect<T, E, C>(
+    iter: impl IntoIterator<Item = Result<T, E>>,
+    mut f: impl FnMut(T) -> C,
+) -> Result<Vec<C>, (usize, E)> {
+    let iter = iter.into_iter();
+    let mut vec = Vec::with_capacity(iter.size_hint().0);
+    for (i, item) in iter.enumerate() {
+        vec.push(f(item.map_err(|e| (i, e))?));
+    }
+    Ok(vec)
+}
+
+macro_rules! impl_from_iter {
+    ($owner:ty, $id:ident => $make:expr; $($gen:tt)*) => {
+        impl<$($gen)* T> FromIteratorWithOwner<T, $owner> for Vec<QCell<T>> {
+            fn from_iter_with_owner<I: IntoIterator<Item = T>>(iter: I, owner: &$owner) -> Self {
+                let $id = $make(owner);
+                iter.into_iter().map(|v| $id.cell(v)).collect()
+            }
+        }
+
+        impl<$($gen)* T> FromIteratorWithOwner<T, $owner> for Vec<Arc<QCell<T>>> {
+            fn from_iter_with_owner<I: IntoIterator<Item = T>>(iter: I, owner: &$owner) -> Self {
+                let $id = $make(owner);
+                iter.into_iter().map(|v| Arc::new($id.cell(v))).collect()
+            }
+        }
+
+        impl<$($gen)* T, E> FromIteratorWithOwner<Result<T, E>, $owner>
+            for Result<Vec<QCell<T>>, (usize, E)>
+        {
+            fn from_iter_with_owner<I: IntoIterator<Item = Result<T, E>>>(
+                iter: I,
+                owner: &$owner,
+            ) -> Self {
+                let $id = $make(owner);
+                try_collect(iter, |v| $id.cell(v))
+            }
+        }
+
+        impl<$($gen)* T, E> FromIteratorWithOwner<Result<T, E>, $owner>
+            for Result<Vec<Arc<QCell<T>>>, (usize, E)>
+        {
+            fn from_iter_with_owner<I: IntoIterator<Item = Result<T, E>>>(
+                iter: I,
+                owner: &$owner,
+            ) -> Self {
+                let $id = $make(owner);
+                try_collect(iter, |v| Arc::new($id.cell(v)))
+            }
+        }
+    };
+}
+
+impl_from_iter!(QCellOwnerID, id => |id: &QCellOwnerID| *id;);
+impl_from_iter!(QCellOwner, id => QCellOwner::id;);
+impl_from_iter!(QCellOwnerSeq, id => QCellOwnerSeq::id;);
+
+// The remaining cell types don't need the owner to create a cell, so
+// these just use `$new` directly
+macro_rules! impl_from_iter_ownerless {
+    ($owner:ty, $cell:ty, $new:path; $($gen:tt)*) => {
+        impl<$($gen)* T> FromIteratorWithOwner<T, $owner> for Vec<$cell> {
+            fn from_iter_with_owner<I: IntoIterator<Item = T>>(iter: I, _: &$owner) -> Self {
+                iter.into_iter().map($new).collect()
+            }
+        }
+
+        impl<$($gen)* T, E> FromIteratorWithOwner<Result<T, E>, $owner>
+            for Result<Vec<$cell>, (usize, E)>
+        {
+            fn from_iter_with_owner<I: IntoIterator<Item = Result<T, E>>>(
+                iter: I,
+                _: &$owner,
+            ) -> Self {
+                try_collect(iter, $new)
+            }
+        }
+    };
+}
+
+impl_from_iter_ownerless!(TCellOwner<Q>, TCell<Q, T>, TCell::new; Q: TCellMarker,);
+#[cfg(feature = "std")]
+impl_from_iter_ownerless!(TLCellOwner<Q>, TLCell<Q, T>, TLCell::new; Q: TCellMarker,);
+impl_from_iter_ownerless!(LCellOwner<'id>, LCell<'id, T>, LCell::new; 'id,);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::CollectCells;
+    use crate::{LCell, LCellOwner, QCell, QCellOwner, QCellOwnerSeq, TCell, TCellOwner};
+    use std::sync::Arc;
+
+    fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+        s.parse()
+    }
+
+    #[test]
+    fn collect_qcell() {
+        let mut owner = QCellOwner::new();
+        let id = owner.id();
+        let cells: Vec<QCell<u32>> = (0..4).collect_cells(&id);
+        let arcs: Vec<Arc<QCell<u32>>> = (0..4).collect_cells(&owner);
+        *owner.rw(&cells[3]) += 10;
+        *owner.rw(&arcs[3]) += 20;
+        assert_eq!(*owner.ro(&cells[3]), 13);
+        assert_eq!(*owner.ro(&arcs[3]), 23);
+
+        let owner = unsafe { QCellOwnerSeq::new() };
+        let cells: Vec<QCell<u32>> = (0..4).collect_cells(&owner);
+        assert_eq!(*owner.ro(&cells[2]), 2);
+    }
+
+    #[test]
+    fn collect_qcell_ok() {
+        let owner = QCellOwner::new();
+        let cells: Result<Vec<QCell<u32>>, _> = ["1", "2", "3"]
+            .iter()
+            .map(|s| parse(s))
+            .collect_cells(&owner);
+        let cells = cells.unwrap();
+        assert_eq!(cells.len(), 3);
+        assert_eq!(*owner.ro(&cells[1]), 2);
+    }
+
+    #[test]
+    fn collect_qcell_err_index() {
+        let owner = QCellOwner::new();
+        let cells: Result<Vec<Arc<QCell<u32>>>, _> = ["1", "2", "x", "y"]
+            .iter()
+            .map(|s| parse(s))
+            .collect_cells(&owner.id());
+        assert!(matches!(cells, Err((2, _))));
+        let mut count = 0;
+        let cells: Result<Vec<QCell<u32>>, _> = ["x", "1"]
+            .iter()
+            .inspect(|_| count += 1)
+            .map(|s| parse(s))
+            .collect_cells(&owner);
+        assert!(matches!(cells, Err((0, _))));
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn collect_qcell_empty() {
+        let owner = QCellOwner::new();
+        let cells: Result<Vec<QCell<u32>>, _> =
+            std::iter::empty::<&str>().map(parse).collect_cells(&owner);
+        assert!(cells.unwrap().is_empty());
+        assert!(owner
+            .id()
+            .try_cells_from_iter(std::iter::empty::<Result<u32, ()>>())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn try_cells_from_iter() {
+        let mut owner = QCellOwner::new();
+        let id = owner.id();
+        let cells = id
+            .try_cells_from_iter(["5", "6"].iter().map(|s| parse(s)))
+            .unwrap();
+        *owner.rw(&cells[0]) += 1;
+        assert_eq!(*owner.ro(&cells[0]), 6);
+        let arcs = id.try_arc_cells_from_iter(["5", "6", ""].iter().map(|s| parse(s)));
+        assert!(matches!(arcs, Err((2, _))));
+    }
+
+    #[test]
+    fn collect_ownerless() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cells: Result<Vec<TCell<Marker, u32>>, _> =
+            ["1", "2"].iter().map(|s| parse(s)).collect_cells(&owner);
+        let cells = cells.unwrap();
+        *owner.rw(&cells[1]) += 1;
+        assert_eq!(*owner.ro(&cells[1]), 3);
+
+        LCellOwner::scope(|mut owner| {
+            let cells: Vec<LCell<'_, u32>> = (0..3).collect_cells(&owner);
+            *owner.rw(&cells[2]) += 1;
+            assert_eq!(*owner.ro(&cells[2]), 3);
+            let cells: Result<Vec<LCell<'_, u32>>, _> =
+                ["1", "?"].iter().map(|s| parse(s)).collect_cells(&owner);
+            assert!(matches!(cells, Err((1, _))));
+        });
+    }
+}
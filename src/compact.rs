@@ -0,0 +1,337 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hash};
+
+use crate::{QCell, QCellOwner};
+
+/// A value which can give back memory it holds but isn't using, such
+/// as the spare capacity of a collection.  Cells with contents of
+/// this type can be registered with a [`CompactRegistry`].
+///
+/// [`CompactRegistry`]: struct.CompactRegistry.html
+pub trait Compactable {
+    /// Release any memory which is allocated but not in use
+    fn compact(&mut self);
+
+    /// Estimate the number of bytes which `compact` would release.
+    /// This is only used for the statistics from
+    /// [`CompactRegistry::sweep`], so it may be approximate.  The
+    /// default is zero.
+    ///
+    /// [`CompactRegistry::sweep`]: struct.CompactRegistry.html#method.sweep
+    fn spare_bytes(&self) -> usize {
+        0
+    }
+}
+
+impl<T> Compactable for Vec<T> {
+    fn compact(&mut self) {
+        self.shrink_to_fit();
+    }
+
+    fn spare_bytes(&self) -> usize {
+        (self.capacity() - self.len()) * size_of::<T>()
+    }
+}
+
+impl<T> Compactable for VecDeque<T> {
+    fn compact(&mut self) {
+        self.shrink_to_fit();
+    }
+
+    fn spare_bytes(&self) -> usize {
+        (self.capacity() - self.len()) * size_of::<T>()
+    }
+}
+
+impl Compactable for String {
+    fn compact(&mut self) {
+        self.shrink_to_fit();
+    }
+
+    fn spare_bytes(&self) -> usize {
+        self.capacity() - self.len()
+    }
+}
+
+/// A `BTreeMap` frees its nodes as entries are removed, so has no
+/// spare capacity of its own.  Instead its values are compacted.
+impl<K, V: Compactable> Compactable for BTreeMap<K, V> {
+    fn compact(&mut self) {
+        self.values_mut().for_each(V::compact);
+    }
+
+    fn spare_bytes(&self) -> usize {
+        self.values().map(V::spare_bytes).sum()
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<K: Eq + Hash, V, S: BuildHasher> Compactable for HashMap<K, V, S> {
+    fn compact(&mut self) {
+        self.shrink_to_fit();
+    }
+
+    fn spare_bytes(&self) -> usize {
+        (self.capacity() - self.len()) * size_of::<(K, V)>()
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<T: Eq + Hash, S: BuildHasher> Compactable for HashSet<T, S> {
+    fn compact(&mut self) {
+        self.shrink_to_fit();
+    }
+
+    fn spare_bytes(&self) -> usize {
+        (self.capacity() - self.len()) * size_of::<T>()
+    }
+}
+
+impl<T: Compactable + Send + Sync + 'static> QCell<T> {
+    /// Get a weak handle to a shared cell, with the type of the
+    /// contents erased, for registering with
+    /// [`CompactRegistry::register_dyn`].  This is the same as the
+    /// unsized coercion, but saves spelling out the target type.
+    ///
+    /// [`CompactRegistry::register_dyn`]: struct.CompactRegistry.html#method.register_dyn
+    #[inline]
+    pub fn erase_compactable(this: &Arc<Self>) -> Weak<QCell<dyn Compactable + Send + Sync>> {
+        let weak: Weak<Self> = Arc::downgrade(this);
+        weak
+    }
+}
+
+/// Statistics from [`CompactRegistry::sweep`]
+///
+/// [`CompactRegistry::sweep`]: struct.CompactRegistry.html#method.sweep
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SweepStats {
+    /// Number of entries looked at, which is all of them
+    pub visited: usize,
+    /// Number of cells which were compacted
+    pub compacted: usize,
+    /// Number of entries removed because their cells had been dropped
+    pub pruned: usize,
+    /// Number of cells skipped because they belong to another owner
+    pub skipped: usize,
+    /// Total of [`Compactable::spare_bytes`] released by compacting
+    ///
+    /// [`Compactable::spare_bytes`]: trait.Compactable.html#method.spare_bytes
+    pub bytes_released: usize,
+}
+
+/// A list of cells whose contents should be compacted from time to
+/// time, to give back memory that collections have grown into but no
+/// longer use.
+///
+/// The registry only holds weak references, so registering a cell
+/// doesn't keep it alive.  Entries for cells that have been dropped
+/// are removed by the next sweep.  Cells of different types may be
+/// registered together, since their types are erased to
+/// `dyn Compactable`.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "
+ ```
+ # use qcell::{CompactRegistry, QCellOwner};
+ # use std::collections::HashMap;
+ # use std::sync::Arc;
+ let mut owner = QCellOwner::new();
+ let mut registry = CompactRegistry::new();
+ let queue = Arc::new(owner.cell(Vec::<u64>::new()));
+ let names = Arc::new(owner.cell(HashMap::<u64, String>::new()));
+ registry.register(&queue);
+ registry.register(&names);
+
+ for tick in 0..1000 {
+     // Bursts of work grow the collections, which then drain
+     owner.rw(&queue).extend(0..tick % 100);
+     owner.rw(&names).insert(tick, tick.to_string());
+     owner.rw(&queue).clear();
+     owner.rw(&names).retain(|&k, _| k + 10 > tick);
+
+     if tick % 250 == 249 {
+         let stats = registry.sweep(&mut owner);
+         assert_eq!(stats.compacted, 2);
+     }
+ }
+ assert_eq!(owner.ro(&queue).capacity(), 0);
+ ```
+"
+)]
+#[derive(Default)]
+pub struct CompactRegistry {
+    entries: Vec<Weak<QCell<dyn Compactable + Send + Sync>>>,
+}
+
+impl CompactRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a shared cell, to be compacted by later sweeps.  A
+    /// cell registered twice is compacted twice per sweep.
+    pub fn register<T: Compactable + Send + Sync + 'static>(&mut self, cell: &Arc<QCell<T>>) {
+        self.register_dyn(QCell::erase_compactable(cell));
+    }
+
+    /// Register a cell whose type has already been erased, for
+    /// example with [`QCell::erase_compactable`]
+    ///
+    /// [`QCell::erase_compactable`]: struct.QCell.html#method.erase_compactable
+    pub fn register_dyn(&mut self, cell: Weak<QCell<dyn Compactable + Send + Sync>>) {
+        self.entries.push(cell);
+    }
+
+    /// Get the number of entries, including any for cells which have
+    /// been dropped since the last sweep
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Test whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compact the contents of every registered cell that is still
+    /// alive, borrowing one cell at a time through the owner.  Entries
+    /// for dropped cells are removed.  Cells which belong to another
+    /// owner are left alone, and counted in [`SweepStats::skipped`].
+    ///
+    /// [`SweepStats::skipped`]: struct.SweepStats.html#structfield.skipped
+    pub fn sweep(&mut self, owner: &mut QCellOwner) -> SweepStats {
+        let mut stats = SweepStats::default();
+        let id = owner.id();
+        self.entries.retain(|entry| {
+            stats.visited += 1;
+            let cell = match entry.upgrade() {
+                Some(cell) => cell,
+                None => {
+                    stats.pruned += 1;
+                    return false;
+                }
+            };
+            if !cell.owner.matches(id) {
+                stats.skipped += 1;
+                return true;
+            }
+            let value = owner.rw(&cell);
+            let before = value.spare_bytes();
+            value.compact();
+            stats.bytes_released += before.saturating_sub(value.spare_bytes());
+            stats.compacted += 1;
+            true
+        });
+        stats
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{CompactRegistry, Compactable, SweepStats};
+    use crate::{QCell, QCellOwner};
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+
+    #[test]
+    fn compact_reduces_capacity() {
+        let mut owner = QCellOwner::new();
+        let mut registry = CompactRegistry::new();
+        let mut grown = Vec::<u32>::with_capacity(1000);
+        grown.extend(0..10);
+        let numbers = Arc::new(owner.cell(grown));
+        let mut text = String::with_capacity(100);
+        text.push_str("abc");
+        let text = Arc::new(owner.cell(text));
+        let mut map = HashMap::<u64, u64>::with_capacity(1000);
+        map.insert(1, 1);
+        let map = Arc::new(owner.cell(map));
+        registry.register(&numbers);
+        registry.register(&text);
+        registry.register(&map);
+
+        let stats = registry.sweep(&mut owner);
+        assert_eq!(stats.visited, 3);
+        assert_eq!(stats.compacted, 3);
+        assert!(stats.bytes_released >= 990 * 4 + 97);
+        assert_eq!(owner.ro(&numbers).capacity(), 10);
+        assert_eq!(owner.ro(&numbers)[..], (0..10).collect::<Vec<_>>()[..]);
+        assert_eq!(owner.ro(&text).capacity(), 3);
+        assert!(owner.ro(&map).capacity() < 100);
+
+        // Nothing left to release the second time
+        let stats = registry.sweep(&mut owner);
+        assert_eq!(stats.bytes_released, 0);
+    }
+
+    #[test]
+    fn compact_btreemap_values() {
+        let mut map = BTreeMap::new();
+        map.insert(1, Vec::<u8>::with_capacity(50));
+        map.insert(2, Vec::<u8>::with_capacity(20));
+        assert_eq!(map.spare_bytes(), 70);
+        map.compact();
+        assert_eq!(map.spare_bytes(), 0);
+        assert_eq!(map[&1].capacity(), 0);
+    }
+
+    #[test]
+    fn compact_prunes_dropped_cells() {
+        let mut owner = QCellOwner::new();
+        let mut registry = CompactRegistry::new();
+        let kept = Arc::new(owner.cell(Vec::<u8>::new()));
+        registry.register(&kept);
+        for _ in 0..3 {
+            registry.register(&Arc::new(owner.cell(String::new())));
+        }
+        let erased = Arc::new(owner.cell(Vec::<u16>::with_capacity(8)));
+        registry.register_dyn(QCell::erase_compactable(&erased));
+        drop(erased);
+        assert_eq!(registry.len(), 5);
+
+        let stats = registry.sweep(&mut owner);
+        assert_eq!(stats.visited, 5);
+        assert_eq!(stats.pruned, 4);
+        assert_eq!(stats.compacted, 1);
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.sweep(&mut owner).pruned, 0);
+    }
+
+    #[test]
+    fn compact_skips_other_owner() {
+        let mut owner = QCellOwner::new();
+        let other = QCellOwner::new();
+        let mut registry = CompactRegistry::new();
+        let mine = Arc::new(owner.cell(Vec::<u8>::with_capacity(10)));
+        let theirs = Arc::new(other.cell(Vec::<u8>::with_capacity(10)));
+        registry.register(&mine);
+        registry.register(&theirs);
+
+        let stats = registry.sweep(&mut owner);
+        let expected = SweepStats {
+            visited: 2,
+            compacted: 1,
+            pruned: 0,
+            skipped: 1,
+            bytes_released: 10,
+        };
+        assert_eq!(stats, expected);
+        assert_eq!(other.ro(&theirs).capacity(), 10);
+        // The entry is kept, in case it's swept with its own owner
+        assert_eq!(registry.len(), 2);
+    }
+}
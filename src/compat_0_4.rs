@@ -0,0 +1,137 @@
+//! Source compatibility with the 0.4 public API, to let a dependency
+//! on **qcell** be upgraded before the code that uses it.
+//!
+//! With the **compat-0-4** feature enabled, items which were removed
+//! since 0.4 are restored as deprecated shims over their
+//! replacements.  Code written for 0.4 then builds against this
+//! version, with a deprecation warning at each use of a shim, and the
+//! warnings can be worked through one at a time.  Once they are all
+//! gone, the feature can be turned off.
+//!
+//! This is the full list of differences in the public API between
+//! 0.4.3 and this version, taken from the changelog.  Everything else
+//! in the 0.4 API is still present under the same name with the same
+//! signature.
+//!
+//! | 0.4 item                    | Since | Status here                                      |
+//! |-----------------------------|-------|--------------------------------------------------|
+//! | `QCellOwner::fast_new()`    | 0.5.0 | Deprecated shim, see [`QCellOwner::fast_new`]    |
+//! | IDs from `QCellOwner::new()`| 0.5.0 | Behaviour changed, no shim needed, see below     |
+//!
+//! Earlier breaking changes were already in place in 0.4, so are not
+//! restored: the `get()` and `get_mut()` owner methods became `ro()`
+//! and `rw()` in 0.2.0, and the "no-thread-local" feature was
+//! replaced by the separate [`TCell`] and [`TLCell`] types in 0.4.0.
+//! `LCellOwner::scope` has kept the same signature since `LCell` was
+//! added in 0.1.2.
+//!
+//! # Owner IDs
+//!
+//! In 0.4, `QCellOwner::new()` took each ID from a global list of
+//! free IDs, and an ID was reused as soon as its owner was dropped.
+//! Since 0.5.0 the ID is derived from a small heap allocation made
+//! by the owner, so it is still unique among live owners, and may
+//! still be reused once an owner is dropped.  Code which only relies
+//! on live owners having distinct IDs, which is all that 0.4
+//! promised, is unaffected.
+//!
+//! [`QCellOwner::fast_new`]: ../struct.QCellOwner.html#method.fast_new
+//! [`TCell`]: ../struct.TCell.html
+//! [`TLCell`]: ../struct.TLCell.html
+
+use crate::QCellOwner;
+
+impl QCellOwner {
+    /// Create an owner, as in 0.4.  This is now the same as
+    /// [`QCellOwner::new`], and so no longer needs `unsafe`, but the
+    /// signature is kept so that existing `unsafe` blocks around the
+    /// call don't need changing yet.
+    ///
+    /// In 0.4 this was a faster alternative to `new`, taking IDs from
+    /// a counter which could in principle wrap around and collide.
+    /// Since 0.5.0 that is [`QCellOwnerSeq::new`], which is a
+    /// different type, so switching to it may need other changes.
+    /// Switching to [`QCellOwner::new`] needs none.
+    ///
+    /// ```
+    ///# use qcell::{QCell, QCellOwner};
+    /// #[allow(deprecated)]
+    /// // Safety: No longer has any requirements
+    /// let mut owner = unsafe { QCellOwner::fast_new() };
+    /// let cell = QCell::new(&owner, 1);
+    /// *owner.rw(&cell) += 1;
+    /// assert_eq!(*owner.ro(&cell), 2);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// None: this is safe to call in any context.
+    ///
+    /// [`QCellOwner::new`]: struct.QCellOwner.html#method.new
+    /// [`QCellOwnerSeq::new`]: struct.QCellOwnerSeq.html#method.new
+    #[deprecated(
+        since = "0.5.0",
+        note = "use `QCellOwner::new`, which is now equivalent, or `QCellOwnerSeq::new` for counter-based IDs"
+    )]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compat-0-4")))]
+    #[inline]
+    pub unsafe fn fast_new() -> Self {
+        QCellOwner::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    #![allow(deprecated)]
+
+    use crate::{QCell, QCellOwner};
+    use std::panic::AssertUnwindSafe;
+
+    #[test]
+    fn compat_fast_new() {
+        // Safety: No requirements
+        let mut owner1 = unsafe { QCellOwner::fast_new() };
+        // Safety: No requirements
+        let owner2 = unsafe { QCellOwner::fast_new() };
+        assert_ne!(owner1.id(), owner2.id());
+        let cell = QCell::new(&owner1, vec![1]);
+        owner1.rw(&cell).push(2);
+        assert_eq!(owner1.ro(&cell)[..], [1, 2]);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| owner2.ro(&cell).len()));
+        assert!(result.is_err());
+    }
+
+    // The rest of the 0.4 surface, written as 0.4 code would be, to
+    // catch any accidental break of it
+    #[test]
+    fn compat_unchanged_surface() {
+        use crate::{LCell, LCellOwner, QCellOwnerID, TCell, TCellOwner, TLCell, TLCellOwner};
+
+        let mut owner = QCellOwner::new();
+        let id: QCellOwnerID = owner.id();
+        let a = QCell::new(id, 1);
+        let b = owner.cell(2);
+        let (x, y) = owner.rw2(&a, &b);
+        std::mem::swap(x, y);
+        *a.rw(&mut owner) += 10;
+        assert_eq!((*a.ro(&owner), *owner.ro(&b)), (12, 1));
+
+        crate::marker!(struct Marker;);
+        let mut towner = TCellOwner::<Marker>::new();
+        let tcell = TCell::<Marker, _>::new(5);
+        *towner.rw(&tcell) += 1;
+        assert_eq!(*towner.ro(&tcell), 6);
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+
+        let mut tlowner = TLCellOwner::<Marker>::new();
+        let tlcell = TLCell::<Marker, _>::new(7);
+        *tlowner.rw(&tlcell) += 1;
+        assert_eq!(*tlowner.ro(&tlcell), 8);
+
+        LCellOwner::scope(|mut lowner| {
+            let lcell = LCell::new(3);
+            *lowner.rw(&lcell) += 1;
+            assert_eq!(*lowner.ro(&lcell), 4);
+        });
+    }
+}
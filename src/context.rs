@@ -0,0 +1,305 @@
+/// Define a context struct holding an owner along with the cells it
+/// owns, with accessors for each cell which borrow through the owner.
+///
+/// Applications often keep an owner and a set of `Rc<QCell<...>>`
+/// values together in one "context" struct that is passed around.
+/// Hand-written accessors such as `fn physics_mut(&mut self) -> &mut
+/// Physics` borrow the whole context, so two cells can't be borrowed
+/// mutably at the same time that way, even though the owner's `rw2`
+/// could do it.  This macro generates those accessors, along with
+/// combined ones which borrow two cells at once using `rw2`, so that
+/// the owner itself doesn't need to be exposed.
+///
+/// ```
+///# use qcell::{context, QCellOwner};
+/// #[derive(Default)]
+/// pub struct Physics { speed: f32 }
+/// #[derive(Default)]
+/// pub struct Audio { volume: f32 }
+///
+/// context! {
+///     /// Everything the game loop needs
+///     pub struct Game {
+///         owner: QCellOwner,
+///         cells: GameCells {
+///             physics: Physics => ro_physics, rw_physics;
+///             audio: Audio => ro_audio, rw_audio;
+///         }
+///         pairs {
+///             rw_physics_and_audio(physics, audio);
+///         }
+///     }
+/// }
+///
+/// let mut game = Game::new(QCellOwner::new(), Physics::default(), Audio::default());
+/// game.rw_physics().speed = 2.0;
+/// let (physics, audio) = game.rw_physics_and_audio();
+/// audio.volume = physics.speed * 0.5;
+/// assert_eq!(game.ro_audio().volume, 1.0);
+/// ```
+///
+/// The owner may be a [`QCellOwner`], in which case the cells are
+/// [`QCell`] instances, or a `TCellOwner<Marker>`, in which case they
+/// are `TCell<Marker, _>` instances.  The owner field must be called
+/// `owner`.  This generates:
+///
+/// - The context struct, here `Game`, holding the owner and the cells.
+///   Its fields are private.
+///
+/// - A struct holding the cells, here `GameCells`, with a field of
+///   type `Rc<QCell<T>>` (or `Rc<TCell<Marker, T>>`) for each cell.
+///   The fields have the same visibility as the context struct.
+///
+/// - `new(owner, ...)`, taking the owner and the initial value of each
+///   cell in order, and creating the cells.
+///
+/// - For each cell, the two accessors named after `=>`, the first
+///   taking `&self` and returning `&T`, and the second taking `&mut
+///   self` and returning `&mut T`.  Rust macros can't build new
+///   identifiers, so the names have to be given.
+///
+/// - For each line under `pairs`, which may be left out, a method
+///   taking `&mut self` which returns a tuple of `&mut` references to
+///   the contents of the two cells named.  Naming the same cell twice
+///   fails to compile, since it could only panic at runtime.
+///
+/// - `cells(&self) -> &GameCells`, which allows the `Rc` references to
+///   be cloned to keep elsewhere, and `split(&mut self) -> (&mut
+///   QCellOwner, &GameCells)`, to use the owner directly, for example
+///   to borrow three cells at once with [`rw!`].
+///
+/// See the [`doctest_context`] module for uses which fail to compile.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCell`]: struct.QCell.html
+/// [`rw!`]: macro.rw.html
+/// [`doctest_context`]: doctest_context/index.html
+#[macro_export]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+macro_rules! context {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            owner: QCellOwner,
+            cells: $cells:ident {
+                $($(#[$fmeta:meta])* $field:ident: $t:ty => $ro:ident, $rw:ident;)*
+            }
+            $(pairs { $($pair:ident($a:ident, $b:ident);)* })?
+        }
+    ) => {
+        $crate::context! {
+            @impl [$(#[$meta])*] $vis $name, $cells,
+            [$crate::QCellOwner],
+            [$($(#[$fmeta])* $field: $t, $crate::QCell<$t> => $ro, $rw;)*]
+            [$($($pair($a, $b);)*)?]
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            owner: TCellOwner<$q:ty>,
+            cells: $cells:ident {
+                $($(#[$fmeta:meta])* $field:ident: $t:ty => $ro:ident, $rw:ident;)*
+            }
+            $(pairs { $($pair:ident($a:ident, $b:ident);)* })?
+        }
+    ) => {
+        $crate::context! {
+            @impl [$(#[$meta])*] $vis $name, $cells,
+            [$crate::TCellOwner<$q>],
+            [$($(#[$fmeta])* $field: $t, $crate::TCell<$q, $t> => $ro, $rw;)*]
+            [$($($pair($a, $b);)*)?]
+        }
+    };
+    (
+        @impl [$($meta:tt)*] $vis:vis $name:ident, $cells:ident,
+        [$($owner:tt)*],
+        [$($(#[$fmeta:meta])* $field:ident: $t:ty, $cell:ty => $ro:ident, $rw:ident;)*]
+        [$($pair:ident($a:ident, $b:ident);)*]
+    ) => {
+        $($meta)*
+        $vis struct $name {
+            owner: $($owner)*,
+            cells: $cells,
+        }
+
+        #[doc = concat!("Cells held by a [`", stringify!($name), "`]")]
+        $vis struct $cells {
+            $($(#[$fmeta])* $vis $field: $crate::__ContextRc<$cell>,)*
+        }
+
+        // Gives the type of each cell's contents, for the return types
+        // of the combined accessors
+        macro_rules! __qcell_context_type {
+            $(($field) => { $t };)*
+        }
+
+        // Not every context uses every accessor
+        #[allow(dead_code)]
+        impl $name {
+            /// Create the context, creating each cell from its
+            /// initial value
+            #[allow(clippy::too_many_arguments)]
+            $vis fn new(owner: $($owner)*, $($field: $t),*) -> Self {
+                let cells = $cells {
+                    $($field: $crate::__ContextRc::new(owner.cell($field)),)*
+                };
+                Self { owner, cells }
+            }
+
+            $(
+                #[doc = concat!("Borrow the contents of the `", stringify!($field), "` cell immutably")]
+                #[inline]
+                $vis fn $ro(&self) -> &$t {
+                    self.owner.ro(&self.cells.$field)
+                }
+
+                #[doc = concat!("Borrow the contents of the `", stringify!($field), "` cell mutably")]
+                #[inline]
+                $vis fn $rw(&mut self) -> &mut $t {
+                    self.owner.rw(&self.cells.$field)
+                }
+            )*
+
+            $(
+                #[doc = concat!("Borrow the contents of the `", stringify!($a), "` and `", stringify!($b), "` cells mutably at the same time")]
+                #[inline]
+                $vis fn $pair(&mut self) -> (&mut __qcell_context_type!($a), &mut __qcell_context_type!($b)) {
+                    // Fails to compile if the same cell is named twice
+                    #[allow(dead_code, unused_variables)]
+                    fn distinct_names($a: (), $b: ()) {}
+                    self.owner.rw2(&self.cells.$a, &self.cells.$b)
+                }
+            )*
+
+            /// Get the cells, for example to clone the `Rc` references
+            #[inline]
+            $vis fn cells(&self) -> &$cells {
+                &self.cells
+            }
+
+            /// Get the owner and the cells, to use the owner directly
+            #[inline]
+            $vis fn split(&mut self) -> (&mut $($owner)*, &$cells) {
+                (&mut self.owner, &self.cells)
+            }
+        }
+    };
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{QCellOwner, TCellOwner};
+    use std::rc::Rc;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Physics {
+        speed: u32,
+    }
+
+    crate::context! {
+        struct Game {
+            owner: QCellOwner,
+            cells: GameCells {
+                physics: Physics => ro_physics, rw_physics;
+                /// Volume level
+                audio: u8 => ro_audio, rw_audio;
+                input: Vec<char> => ro_input, rw_input;
+                score: u64 => ro_score, rw_score;
+            }
+            pairs {
+                rw_physics_and_score(physics, score);
+                rw_input_and_audio(input, audio);
+            }
+        }
+    }
+
+    fn game() -> Game {
+        Game::new(QCellOwner::new(), Physics::default(), 5, vec![], 0)
+    }
+
+    #[test]
+    fn context_accessors() {
+        let mut game = game();
+        game.rw_physics().speed = 3;
+        *game.rw_audio() += 1;
+        game.rw_input().push('w');
+        *game.rw_score() = 100;
+        assert_eq!(game.ro_physics(), &Physics { speed: 3 });
+        assert_eq!(*game.ro_audio(), 6);
+        assert_eq!(game.ro_input(), &['w']);
+        assert_eq!(*game.ro_score(), 100);
+    }
+
+    #[test]
+    fn context_pairs() {
+        let mut game = game();
+        game.rw_physics().speed = 7;
+        let (physics, score) = game.rw_physics_and_score();
+        *score += u64::from(physics.speed);
+        physics.speed = 0;
+        assert_eq!(*game.ro_score(), 7);
+        assert_eq!(game.ro_physics().speed, 0);
+
+        let (input, audio) = game.rw_input_and_audio();
+        input.extend(['a', 'b'].iter());
+        *audio = input.len() as u8;
+        assert_eq!(*game.ro_audio(), 2);
+    }
+
+    #[test]
+    fn context_split_and_cells() {
+        let mut game = game();
+        // Cells can be shared outside the context
+        let score = Rc::clone(&game.cells().score);
+        let (owner, cells) = game.split();
+        let (physics, audio, input) = crate::rw!(owner => &cells.physics, &cells.audio, &cells.input);
+        physics.speed = 1;
+        *audio = 2;
+        input.push('x');
+        *owner.rw(&score) = 9;
+        assert_eq!(*game.ro_score(), 9);
+        assert_eq!(*game.ro_audio(), 2);
+    }
+
+    #[test]
+    fn context_tcell() {
+        crate::marker!(struct Marker;);
+        crate::context! {
+            pub(crate) struct Shared {
+                owner: TCellOwner<Marker>,
+                cells: SharedCells {
+                    name: String => ro_name, rw_name;
+                    hits: u32 => ro_hits, rw_hits;
+                }
+                pairs {
+                    rw_name_and_hits(name, hits);
+                }
+            }
+        }
+        let mut shared = Shared::new(TCellOwner::new(), "x".into(), 0);
+        let (name, hits) = shared.rw_name_and_hits();
+        name.push('y');
+        *hits += 1;
+        assert_eq!(shared.ro_name(), "xy");
+        assert_eq!(*shared.ro_hits(), 1);
+        let (owner, cells) = shared.split();
+        *owner.rw(&cells.hits) += 1;
+        assert_eq!(*shared.ro_hits(), 2);
+    }
+
+    #[test]
+    fn context_without_pairs() {
+        crate::context! {
+            struct Small {
+                owner: QCellOwner,
+                cells: SmallCells {
+                    only: i32 => ro_only, rw_only;
+                }
+            }
+        }
+        let mut small = Small::new(QCellOwner::new(), -1);
+        *small.rw_only() *= -1;
+        assert_eq!(*small.ro_only(), 1);
+    }
+}
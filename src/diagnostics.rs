@@ -0,0 +1,470 @@
+//! Registry of the owners which currently exist, for diagnostics.
+//!
+//! A long-running service may want to list which owners are live, for
+//! example on a diagnostics page: how many [`QCellOwner`] instances
+//! exist, or which [`TCellOwner`] and [`TLCellOwner`] marker types are
+//! currently claimed.  With the **diagnostics** feature, owners can be
+//! entered into a process-wide table when they are created, and are
+//! removed again when dropped.  [`snapshot`] returns a copy of the
+//! table to render.
+//!
+//! Owners are only registered if they are created with one of the
+//! `new_registered` constructors, which also attach a label, or if
+//! registration of all owners has been switched on with [`enable`].
+//! Registering costs one lock of a global mutex when the owner is
+//! created and another when it is dropped.  Otherwise the only cost
+//! is an atomic load at creation.  Without the feature, none of this
+//! exists, and the owners are the same size as before.
+//!
+//! The owners covered are [`QCellOwner`], [`QCellOwnerSeq`],
+//! [`QCellOwnerPinned`], [`TCellOwner`] (including those from
+//! `TCellOwner::new_indexed`) and [`TLCellOwner`].  A
+//! [`QCellOwnerPinned`] can only be registered with
+//! `QCellOwnerPinned::new_registered`, since `QCellOwnerPinned::new`
+//! is a `const fn`.  [`LCellOwner`] instances aren't covered, since
+//! they only exist within a closure or a scope.
+//!
+//! ```
+//!# use qcell::{diagnostics, QCellOwner, TCellOwner};
+//! qcell::marker!(struct Sessions;);
+//! let _cache = QCellOwner::new_registered("cache");
+//! let _sessions = TCellOwner::<Sessions>::new_registered("sessions");
+//!
+//! let mut table = String::from("| ID | Owner | Marker | Label |\n|---|---|---|---|\n");
+//! for info in diagnostics::snapshot() {
+//!     table.push_str(&format!(
+//!         "| {} | {} | {} | {} |\n",
+//!         info.id,
+//!         info.kind,
+//!         info.marker.unwrap_or("-"),
+//!         info.label.as_deref().unwrap_or("-"),
+//!     ));
+//! }
+//! assert!(table.contains("| QCellOwner | - | cache |"));
+//! assert!(table.contains("Sessions | sessions |"));
+//! ```
+//!
+//! Registered owners handed from one thread to another with an
+//! [`OwnerTransfer`] are also entered in an audit log of recent
+//! transfers, which [`transfers`] returns.
+//!
+//! [`QCellOwner`]: ../struct.QCellOwner.html
+//! [`QCellOwnerSeq`]: ../struct.QCellOwnerSeq.html
+//! [`QCellOwnerPinned`]: ../struct.QCellOwnerPinned.html
+//! [`TCellOwner`]: ../struct.TCellOwner.html
+//! [`TLCellOwner`]: ../struct.TLCellOwner.html
+//! [`LCellOwner`]: ../struct.LCellOwner.html
+//! [`snapshot`]: fn.snapshot.html
+//! [`enable`]: fn.enable.html
+//! [`OwnerTransfer`]: ../struct.OwnerTransfer.html
+//! [`transfers`]: fn.transfers.html
+
+use std::any::type_name;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+
+use crate::{QCellOwner, QCellOwnerPinned, QCellOwnerSeq, TCellMarker, TCellOwner, TLCellOwner};
+
+/// The type of a registered owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OwnerKind {
+    QCellOwner,
+    QCellOwnerSeq,
+    QCellOwnerPinned,
+    TCellOwner,
+    TLCellOwner,
+}
+
+impl fmt::Display for OwnerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Details of a registered owner, as returned by [`snapshot`]
+///
+/// [`snapshot`]: fn.snapshot.html
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OwnerInfo {
+    /// Number identifying this registration, unique for the life of
+    /// the process.  Owners registered later have larger numbers.
+    pub id: u64,
+    /// The type of owner
+    pub kind: OwnerKind,
+    /// The type name of the marker, for [`TCellOwner`] and
+    /// [`TLCellOwner`]
+    ///
+    /// [`TCellOwner`]: ../struct.TCellOwner.html
+    /// [`TLCellOwner`]: ../struct.TLCellOwner.html
+    pub marker: Option<&'static str>,
+    /// The label given to `new_registered`, if any
+    pub label: Option<String>,
+    /// When the owner was created
+    pub created: SystemTime,
+    /// The thread which created the owner.  A [`TLCellOwner`] is
+    /// always used on this thread.
+    ///
+    /// [`TLCellOwner`]: ../struct.TLCellOwner.html
+    pub thread: ThreadId,
+}
+
+/// Record of an owner handed from one thread to another with an
+/// [`OwnerTransfer`], as returned by [`transfers`]
+///
+/// [`OwnerTransfer`]: ../struct.OwnerTransfer.html
+/// [`transfers`]: fn.transfers.html
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TransferRecord {
+    /// The owner's registration number, as in [`OwnerInfo::id`]
+    ///
+    /// [`OwnerInfo::id`]: struct.OwnerInfo.html#structfield.id
+    pub owner: u64,
+    /// The type of owner
+    pub kind: OwnerKind,
+    /// The type name of the marker, for [`TCellOwner`]
+    ///
+    /// [`TCellOwner`]: ../struct.TCellOwner.html
+    pub marker: Option<&'static str>,
+    /// The thread which called `into_transfer`
+    pub from: ThreadId,
+    /// The thread which called `redeem`
+    pub to: ThreadId,
+    /// When `into_transfer` was called
+    pub sent: SystemTime,
+    /// When `redeem` was called
+    pub redeemed: SystemTime,
+}
+
+/// The number of records kept in the transfer log.  Once it is full,
+/// the oldest record is dropped to make room for each new one.
+pub const TRANSFER_LOG_LEN: usize = 256;
+
+struct Registry {
+    next: u64,
+    owners: BTreeMap<u64, OwnerInfo>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(|| {
+    Mutex::new(Registry {
+        next: 1,
+        owners: BTreeMap::new(),
+    })
+});
+
+static TRANSFERS: Lazy<Mutex<VecDeque<TransferRecord>>> = Lazy::new(Default::default);
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    // The table is never left half-updated, so a panic elsewhere
+    // whilst holding the lock doesn't matter
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Register all owners created from now on, even those created with
+/// the plain constructors.  Those have no label.  Owners which
+/// already exist are not affected.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Go back to only registering owners created with `new_registered`.
+/// Owners already registered stay registered until dropped.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Test whether all new owners are being registered, i.e. whether
+/// [`enable`] was called
+///
+/// [`enable`]: fn.enable.html
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Get a copy of the details of all the registered owners which
+/// currently exist, in the order they were registered
+pub fn snapshot() -> Vec<OwnerInfo> {
+    registry().owners.values().cloned().collect()
+}
+
+/// Get a copy of the log of recent transfers of registered owners,
+/// oldest first.  At most [`TRANSFER_LOG_LEN`] are kept.
+///
+/// [`TRANSFER_LOG_LEN`]: constant.TRANSFER_LOG_LEN.html
+pub fn transfers() -> Vec<TransferRecord> {
+    let log = TRANSFERS.lock().unwrap_or_else(PoisonError::into_inner);
+    log.iter().cloned().collect()
+}
+
+pub(crate) fn record_transfer(record: TransferRecord) {
+    let mut log = TRANSFERS.lock().unwrap_or_else(PoisonError::into_inner);
+    if log.len() == TRANSFER_LOG_LEN {
+        log.pop_front();
+    }
+    log.push_back(record);
+}
+
+// Held by each owner, to remove its entry from the table when it is
+// dropped
+pub(crate) struct Registration {
+    id: Option<u64>,
+}
+
+impl Registration {
+    pub(crate) const NONE: Self = Self { id: None };
+
+    // The registration number, if the owner is registered
+    pub(crate) fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    // Register a new owner if `enable` has been called
+    #[inline]
+    pub(crate) fn auto(kind: OwnerKind, marker: Option<&'static str>) -> Self {
+        if is_enabled() {
+            Self::register(kind, marker, None)
+        } else {
+            Self::NONE
+        }
+    }
+
+    fn register(kind: OwnerKind, marker: Option<&'static str>, label: Option<String>) -> Self {
+        let info = OwnerInfo {
+            id: 0,
+            kind,
+            marker,
+            label,
+            created: SystemTime::now(),
+            thread: thread::current().id(),
+        };
+        let mut registry = registry();
+        let id = registry.next;
+        registry.next += 1;
+        registry.owners.insert(id, OwnerInfo { id, ..info });
+        Self { id: Some(id) }
+    }
+
+    // Set the label, registering the owner first if necessary
+    fn label(&mut self, kind: OwnerKind, marker: Option<&'static str>, label: String) {
+        match self.id {
+            Some(id) => {
+                if let Some(info) = registry().owners.get_mut(&id) {
+                    info.label = Some(label);
+                }
+            }
+            None => *self = Self::register(kind, marker, Some(label)),
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            registry().owners.remove(&id);
+        }
+    }
+}
+
+impl QCellOwner {
+    /// Create an owner as for [`QCellOwner::new`], and enter it in
+    /// the [`diagnostics`] registry with the given label.
+    ///
+    /// [`QCellOwner::new`]: struct.QCellOwner.html#method.new
+    /// [`diagnostics`]: diagnostics/index.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+    pub fn new_registered(label: impl Into<String>) -> Self {
+        let mut owner = Self::new();
+        owner.diag.label(OwnerKind::QCellOwner, None, label.into());
+        owner
+    }
+}
+
+impl QCellOwnerSeq {
+    /// Create an owner as for [`QCellOwnerSeq::new`], and enter it
+    /// in the [`diagnostics`] registry with the given label.
+    ///
+    /// # Safety
+    ///
+    /// The same contract applies as for [`QCellOwnerSeq::new`].
+    ///
+    /// [`QCellOwnerSeq::new`]: struct.QCellOwnerSeq.html#method.new
+    /// [`diagnostics`]: diagnostics/index.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+    pub unsafe fn new_registered(label: impl Into<String>) -> Self {
+        let mut owner = Self::new();
+        owner.diag.label(OwnerKind::QCellOwnerSeq, None, label.into());
+        owner
+    }
+}
+
+impl QCellOwnerPinned {
+    /// Create an owner as for [`QCellOwnerPinned::new`], and enter it
+    /// in the [`diagnostics`] registry with the given label.  Unlike
+    /// `new`, this is not a `const fn`.
+    ///
+    /// [`QCellOwnerPinned::new`]: struct.QCellOwnerPinned.html#method.new
+    /// [`diagnostics`]: diagnostics/index.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+    pub fn new_registered(label: impl Into<String>) -> Pin<Box<Self>> {
+        let mut owner = Self::new();
+        owner.diag.label(OwnerKind::QCellOwnerPinned, None, label.into());
+        Box::pin(owner)
+    }
+}
+
+impl<Q: TCellMarker> TCellOwner<Q> {
+    /// Create the singleton owner as for [`TCellOwner::new`], and
+    /// enter it in the [`diagnostics`] registry with the given label.
+    /// Panics if an owner for `Q` already exists.
+    ///
+    /// [`TCellOwner::new`]: struct.TCellOwner.html#method.new
+    /// [`diagnostics`]: diagnostics/index.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+    #[track_caller]
+    pub fn new_registered(label: impl Into<String>) -> Self {
+        let mut owner = Self::new();
+        owner.diag.label(OwnerKind::TCellOwner, Some(type_name::<Q>()), label.into());
+        owner
+    }
+}
+
+impl<Q: TCellMarker> TLCellOwner<Q> {
+    /// Create the singleton owner for this thread as for
+    /// [`TLCellOwner::new`], and enter it in the [`diagnostics`]
+    /// registry with the given label.  Panics if this thread already
+    /// has an owner for `Q`.
+    ///
+    /// [`TLCellOwner::new`]: struct.TLCellOwner.html#method.new
+    /// [`diagnostics`]: diagnostics/index.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+    #[track_caller]
+    pub fn new_registered(label: impl Into<String>) -> Self {
+        let mut owner = Self::new();
+        owner.diag.label(OwnerKind::TLCellOwner, Some(type_name::<Q>()), label.into());
+        owner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnerInfo, OwnerKind};
+    use crate::{QCellOwner, QCellOwnerPinned, QCellOwnerSeq, TCellOwner, TLCellOwner};
+    use std::thread;
+
+    // Owners registered by this test's thread, ignoring those of any
+    // other tests running at the same time
+    fn mine() -> Vec<OwnerInfo> {
+        let thread = thread::current().id();
+        super::snapshot()
+            .into_iter()
+            .filter(|info| info.thread == thread)
+            .collect()
+    }
+
+    fn labels() -> Vec<String> {
+        mine().into_iter().filter_map(|info| info.label).collect()
+    }
+
+    #[test]
+    fn diagnostics_create_drop() {
+        crate::marker!(struct MarkerT;);
+        crate::marker!(struct MarkerTL;);
+        let q = QCellOwner::new_registered("q");
+        // Safety: No other owner is created with this ID
+        let seq = unsafe { QCellOwnerSeq::new_registered("seq") };
+        let pinned = QCellOwnerPinned::new_registered("pinned");
+        let t = TCellOwner::<MarkerT>::new_registered("t");
+        let tl = TLCellOwner::<MarkerTL>::new_registered("tl");
+
+        let all = mine();
+        let kinds: Vec<OwnerKind> = all.iter().map(|info| info.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                OwnerKind::QCellOwner,
+                OwnerKind::QCellOwnerSeq,
+                OwnerKind::QCellOwnerPinned,
+                OwnerKind::TCellOwner,
+                OwnerKind::TLCellOwner
+            ]
+        );
+        assert_eq!(labels(), ["q", "seq", "pinned", "t", "tl"]);
+        assert!(all.windows(2).all(|w| w[0].id < w[1].id));
+        assert!(all.windows(2).all(|w| w[0].created <= w[1].created));
+        assert_eq!(all[0].marker, None);
+        assert!(all[3].marker.unwrap().ends_with("MarkerT"));
+        assert!(all[4].marker.unwrap().ends_with("MarkerTL"));
+
+        drop(seq);
+        drop(t);
+        assert_eq!(labels(), ["q", "pinned", "tl"]);
+        drop(pinned);
+        drop(q);
+        drop(tl);
+        assert!(mine().is_empty());
+
+        // The marker can be claimed again, with a new registration
+        let t = TCellOwner::<MarkerT>::new_registered("t2");
+        assert_eq!(labels(), ["t2"]);
+        assert!(mine()[0].id > all[4].id);
+        drop(t);
+        assert!(mine().is_empty());
+    }
+
+    #[test]
+    fn diagnostics_other_threads() {
+        crate::marker!(struct Marker;);
+        let owner = thread::spawn(|| TCellOwner::<Marker>::new_registered("elsewhere"))
+            .join()
+            .unwrap();
+        assert!(mine().is_empty());
+        let info = super::snapshot()
+            .into_iter()
+            .find(|info| info.label.as_deref() == Some("elsewhere"))
+            .unwrap();
+        assert_ne!(info.thread, thread::current().id());
+        drop(owner);
+        assert!(super::snapshot().iter().all(|other| other.id != info.id));
+    }
+
+    // The only test that switches on registration of all owners, so
+    // that no other test sees the switch change under it
+    #[test]
+    fn diagnostics_enable() {
+        crate::marker!(struct Marker;);
+        assert!(!super::is_enabled());
+        let plain = QCellOwner::new();
+        assert!(mine().is_empty());
+
+        super::enable();
+        assert!(super::is_enabled());
+        let q = QCellOwner::new();
+        // Safety: No other owner is created with this ID
+        let seq = unsafe { QCellOwnerSeq::new() };
+        let t = TCellOwner::<Marker>::new();
+        let labelled = QCellOwner::new_registered("labelled");
+        super::disable();
+        let after = QCellOwner::new();
+
+        let all = mine();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].kind, OwnerKind::QCellOwner);
+        assert_eq!(all[1].kind, OwnerKind::QCellOwnerSeq);
+        assert_eq!(all[2].kind, OwnerKind::TCellOwner);
+        // Registered once, with the label set afterwards
+        assert_eq!(labels(), ["labelled"]);
+        drop((plain, q, seq, t, labelled, after));
+        assert!(mine().is_empty());
+    }
+}
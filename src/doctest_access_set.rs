@@ -0,0 +1,66 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests that conflicting access sets are rejected at compile
+//! time.
+//!
+//! For comparison, this compiles, since both sets only read
+//! `POSITION`:
+//!
+//! ```
+//!# use qcell::{access_set, assert_compatible, marker, TCell};
+//! marker!(struct World;);
+//! static POSITION: TCell<World, f32> = TCell::new(0.0);
+//! static VELOCITY: TCell<World, f32> = TCell::new(0.0);
+//! static SCORE: TCell<World, u32> = TCell::new(0);
+//! access_set! {
+//!     struct Movement { read: [POSITION], write: [VELOCITY] }
+//!     struct Scoring { read: [POSITION], write: [SCORE] }
+//! }
+//! assert_compatible!(Movement, Scoring);
+//! ```
+//!
+//! Two sets writing the same cell conflict:
+//!
+//! ```compile_fail
+//!# use qcell::{access_set, assert_compatible, marker, TCell};
+//! marker!(struct World;);
+//! static POSITION: TCell<World, f32> = TCell::new(0.0);
+//! access_set! {
+//!     struct Movement { write: [POSITION] }
+//!     struct Teleport { write: [POSITION] }
+//! }
+//! assert_compatible!(Movement, Teleport);  // Compile fail
+//! ```
+//!
+//! So do two sets where one reads a cell that the other writes:
+//!
+//! ```compile_fail
+//!# use qcell::{access_set, assert_compatible, marker, TCell};
+//! marker!(struct World;);
+//! static POSITION: TCell<World, f32> = TCell::new(0.0);
+//! static VELOCITY: TCell<World, f32> = TCell::new(0.0);
+//! access_set! {
+//!     struct Movement { read: [VELOCITY], write: [POSITION] }
+//!     struct Render { read: [POSITION] }
+//! }
+//! assert_compatible!(Movement, Render);  // Compile fail
+//! ```
+//!
+//! A generic scheduler using [`AssertCompatible::OK`] rejects
+//! conflicting sets too, but only when it is instantiated, which
+//! `cargo check` doesn't do, so that case isn't tested here.
+//!
+//! Cells are named by identifiers which must be in scope, so a typo
+//! is caught:
+//!
+//! ```compile_fail
+//!# use qcell::{access_set, marker, TCell};
+//! marker!(struct World;);
+//! static POSITION: TCell<World, f32> = TCell::new(0.0);
+//! access_set! {
+//!     struct Movement { write: [POSITON] }  // Compile fail
+//! }
+//! ```
+//!
+//! [`AssertCompatible::OK`]: ../struct.AssertCompatible.html#associatedconstant.OK
@@ -0,0 +1,61 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the `AsyncOwner` wrapper.
+//!
+//! Access to the owner is only given within a synchronous closure,
+//! and the lock is released when the closure returns:
+//!
+//! ```
+//!# use qcell::{AsyncOwner, QCellOwner};
+//!# #[tokio::main(flavor = "current_thread")]
+//!# async fn main() {
+//! let owner = AsyncOwner::new(QCellOwner::new());
+//! let cell = owner.with_ro(|o| o.cell(100u32)).await;
+//! let value = owner.with_ro(|o| *o.ro(&cell)).await;
+//! tokio::task::yield_now().await;
+//! owner.with_cell(&cell, |v| *v += value).await;
+//! assert_eq!(owner.with_ro(|o| *o.ro(&cell)).await, 200);
+//!# }
+//! ```
+//!
+//! The closure can't be async, since then the borrow of the owner
+//! would have to cross an `.await`:
+//!
+//! ```compile_fail
+//!# use qcell::{AsyncOwner, QCellOwner};
+//!# #[tokio::main(flavor = "current_thread")]
+//!# async fn main() {
+//! let owner = AsyncOwner::new(QCellOwner::new());
+//! let cell = owner.with_ro(|o| o.cell(100u32)).await;
+//! let value = owner.with_ro(|o| async move {  // Compile fail
+//!     tokio::task::yield_now().await;
+//!     *o.ro(&cell)
+//! }).await.await;
+//!# }
+//! ```
+//!
+//! A borrow of a cell can't be returned from the closure:
+//!
+//! ```compile_fail
+//!# use qcell::{AsyncOwner, QCellOwner};
+//!# #[tokio::main(flavor = "current_thread")]
+//!# async fn main() {
+//! let owner = AsyncOwner::new(QCellOwner::new());
+//! let cell = owner.with_ro(|o| o.cell(100u32)).await;
+//! let value = owner.with_ro(|o| o.ro(&cell)).await;  // Compile fail
+//! tokio::task::yield_now().await;
+//! assert_eq!(*value, 100);
+//!# }
+//! ```
+//!
+//! And neither can the owner itself:
+//!
+//! ```compile_fail
+//!# use qcell::{AsyncOwner, QCellOwner};
+//!# #[tokio::main(flavor = "current_thread")]
+//!# async fn main() {
+//! let owner = AsyncOwner::new(QCellOwner::new());
+//! let escaped = owner.with_rw(|o| o).await;  // Compile fail
+//!# }
+//! ```
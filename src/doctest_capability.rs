@@ -0,0 +1,85 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the restrictions of `Capability`.
+//!
+//! A write-only capability can't borrow cell contents immutably:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let wo = owner.capability::<false, true>();
+//! assert_eq!(*wo.ro(&cell), 1);  // Compile fail
+//! ```
+//!
+//! Or mutably, since the old contents could be read through the
+//! mutable reference:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let mut wo = owner.capability::<false, true>();
+//! let old = *wo.rw(&cell);  // Compile fail
+//! ```
+//!
+//! And it can't copy the contents out or update them either:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let wo = owner.capability::<false, true>();
+//! let old = wo.get(&cell);  // Compile fail
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let mut wo = owner.capability::<false, true>();
+//! wo.update(&cell, |old| old);  // Compile fail
+//! ```
+//!
+//! A read-only capability can't write:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let mut ro = owner.capability::<true, false>();
+//! ro.set(&cell, 2);  // Compile fail
+//! ```
+//!
+//! The same applies for `TCell` and `LCell`:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TCell, TCellOwner};
+//! marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cell = TCell::new(1);
+//! let wo = owner.capability::<false, true>();
+//! assert_eq!(*wo.ro(&cell), 1);  // Compile fail
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::LCellOwner;
+//! LCellOwner::scope(|mut owner| {
+//!     let cell = owner.cell(1);
+//!     let mut wo = owner.capability::<false, true>();
+//!     *wo.rw(&cell) += 1;  // Compile fail
+//! });
+//! ```
+//!
+//! The owner itself is mutably borrowed while the capability exists,
+//! so it can't be used to get around the restriction:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let mut wo = owner.capability::<false, true>();
+//! let value = owner.ro(&cell);  // Compile fail
+//! wo.set(&cell, 2);
+//! ```
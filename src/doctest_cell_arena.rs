@@ -0,0 +1,105 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests that an `ArenaRef` can't outlive its `CellArena`, and
+//! that the cells of an `LCellArena` can't escape their scope.
+//!
+//! The arena can't be dropped whilst a handle is still in use:
+//!
+//! ```compile_fail
+//!# use qcell::{CellArena, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let arena = CellArena::new(&owner);
+//! let cell = arena.alloc(1);
+//! drop(arena);  // Compile fail
+//! assert_eq!(*owner.ro(&cell), 1);
+//! ```
+//!
+//! Or moved away, since that would move the chunk list:
+//!
+//! ```compile_fail
+//!# use qcell::{CellArena, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let arena = CellArena::new(&owner);
+//! let cell = arena.alloc(1);
+//! let moved = arena;  // Compile fail
+//! assert_eq!(*owner.ro(&cell), 1);
+//! ```
+//!
+//! A handle can't be returned from a function that owns the arena:
+//!
+//! ```compile_fail
+//!# use qcell::{ArenaRef, CellArena, QCellOwner};
+//! fn make(owner: &QCellOwner) -> ArenaRef<'static, u32> {
+//!     let arena = CellArena::new(owner);
+//!     arena.alloc(1)  // Compile fail
+//! }
+//! ```
+//!
+//! Or stored somewhere that outlives the arena:
+//!
+//! ```compile_fail
+//!# use qcell::{ArenaRef, CellArena, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let mut saved: Vec<ArenaRef<'_, u32>> = Vec::new();
+//! {
+//!     let arena = CellArena::new(&owner);
+//!     saved.push(arena.alloc(1));  // Compile fail
+//! }
+//! assert_eq!(*owner.ro(&saved[0]), 1);
+//! ```
+//!
+//! A `CellArena` can't be shared between threads, since its chunk
+//! list is in a `RefCell`.  Use `SyncCellArena` for that:
+//!
+//! ```compile_fail
+//!# use qcell::{CellArena, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let arena = CellArena::<u32>::new(&owner);
+//! crossbeam::scope(|s| {
+//!     s.spawn(|_| arena.alloc(1));  // Compile fail
+//! }).unwrap();
+//! ```
+//!
+//! An `LCellArena` takes the brand of the owner whose scope it is
+//! used in, so neither it nor its cells can be kept after the scope
+//! ends:
+//!
+//! ```compile_fail
+//!# use qcell::{LCellArena, LCellOwner};
+//! let mut saved = None;
+//! LCellOwner::scope(|owner| {
+//!     let arena = LCellArena::new();
+//!     assert_eq!(*owner.ro(arena.alloc(1)), 1);
+//!     saved = Some(arena);  // Compile fail
+//! });
+//! ```
+//!
+//! Nor can its cells be used with the owner of another scope:
+//!
+//! ```compile_fail
+//!# use qcell::{LCellArena, LCellOwner};
+//! LCellOwner::scope(|owner1| {
+//!     let arena = LCellArena::new();
+//!     let cell = arena.alloc(1);
+//!     assert_eq!(*owner1.ro(cell), 1);
+//!     LCellOwner::scope(|owner2| {
+//!         assert_eq!(*owner2.ro(cell), 1);  // Compile fail
+//!     });
+//! });
+//! ```
+//!
+//! And as with `CellArena`, a cell can't outlive its arena, even
+//! within the scope:
+//!
+//! ```compile_fail
+//!# use qcell::{LCellArena, LCellOwner};
+//! LCellOwner::scope(|owner| {
+//!     let cell;
+//!     {
+//!         let arena = LCellArena::new();
+//!         cell = arena.alloc(1);  // Compile fail
+//!     }
+//!     assert_eq!(*owner.ro(cell), 1);
+//! });
+//! ```
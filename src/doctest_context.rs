@@ -0,0 +1,86 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests that the accessors generated by [`context!`] are
+//! checked at compile time.
+//!
+//! [`context!`]: ../macro.context.html
+//!
+//! For comparison, this compiles, since the two cells are borrowed
+//! at once through the combined accessor:
+//!
+//! ```
+//!# use qcell::{context, QCellOwner};
+//! context! {
+//!     struct Ctx {
+//!         owner: QCellOwner,
+//!         cells: CtxCells {
+//!             a: u32 => ro_a, rw_a;
+//!             b: u32 => ro_b, rw_b;
+//!         }
+//!         pairs {
+//!             rw_a_and_b(a, b);
+//!         }
+//!     }
+//! }
+//! let mut ctx = Ctx::new(QCellOwner::new(), 1, 2);
+//! let (a, b) = ctx.rw_a_and_b();
+//! std::mem::swap(a, b);
+//! assert_eq!((*ctx.ro_a(), *ctx.ro_b()), (2, 1));
+//! ```
+//!
+//! The single-cell accessors each borrow the whole context, so two
+//! of them can't be used at once:
+//!
+//! ```compile_fail
+//!# use qcell::{context, QCellOwner};
+//! context! {
+//!     struct Ctx {
+//!         owner: QCellOwner,
+//!         cells: CtxCells {
+//!             a: u32 => ro_a, rw_a;
+//!             b: u32 => ro_b, rw_b;
+//!         }
+//!     }
+//! }
+//! let mut ctx = Ctx::new(QCellOwner::new(), 1, 2);
+//! let a = ctx.rw_a();
+//! let b = ctx.rw_b();  // Compile fail
+//! std::mem::swap(a, b);
+//! ```
+//!
+//! Naming the same cell twice in a combined accessor is rejected when
+//! the macro is expanded, rather than panicking in `rw2`:
+//!
+//! ```compile_fail
+//!# use qcell::{context, QCellOwner};
+//! context! {
+//!     struct Ctx {
+//!         owner: QCellOwner,
+//!         cells: CtxCells {
+//!             a: u32 => ro_a, rw_a;
+//!             b: u32 => ro_b, rw_b;
+//!         }
+//!         pairs {
+//!             rw_a_and_a(a, a);  // Compile fail
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! So is naming a cell which doesn't exist:
+//!
+//! ```compile_fail
+//!# use qcell::{context, QCellOwner};
+//! context! {
+//!     struct Ctx {
+//!         owner: QCellOwner,
+//!         cells: CtxCells {
+//!             a: u32 => ro_a, rw_a;
+//!         }
+//!         pairs {
+//!             rw_a_and_c(a, c);  // Compile fail
+//!         }
+//!     }
+//! }
+//! ```
@@ -0,0 +1,66 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the node lifetime rules of the `intrusive` module.
+//!
+//! Nodes must be declared before the list header, so that they
+//! outlive it:
+//!
+//! ```
+//!# use qcell::intrusive::{Node, PinnedListHeader};
+//!# use pin_utils::pin_mut;
+//! let node: Node<u32>;
+//! let list = PinnedListHeader::new();
+//! pin_mut!(list);
+//! node = list.as_ref().node(100);
+//! list.as_ref().push_back(&node);
+//! ```
+//!
+//! A node declared after the header would be dropped first, whilst
+//! still linked:
+//!
+//! ```compile_fail
+//!# use qcell::intrusive::{Node, PinnedListHeader};
+//!# use pin_utils::pin_mut;
+//! let list = PinnedListHeader::new();
+//! pin_mut!(list);
+//! let node = list.as_ref().node(100);
+//! list.as_ref().push_back(&node);  // Compile fail
+//! ```
+//!
+//! A node in an inner scope can't be linked either, even if it is
+//! removed again before the end of the scope:
+//!
+//! ```compile_fail
+//!# use qcell::intrusive::{Node, PinnedListHeader};
+//!# use pin_utils::pin_mut;
+//! let list = PinnedListHeader::new();
+//! pin_mut!(list);
+//! {
+//!     let node = list.as_ref().node(100);
+//!     list.as_ref().push_back(&node);  // Compile fail
+//!     list.as_ref().remove(&node);
+//! }
+//! ```
+//!
+//! A node can't be moved whilst it is linked:
+//!
+//! ```compile_fail
+//!# use qcell::intrusive::{Node, PinnedListHeader};
+//!# use pin_utils::pin_mut;
+//! let node: Node<u32>;
+//! let list = PinnedListHeader::new();
+//! pin_mut!(list);
+//! node = list.as_ref().node(100);
+//! list.as_ref().push_back(&node);
+//! let moved = node;  // Compile fail
+//! ```
+//!
+//! And the header can't be used without being pinned:
+//!
+//! ```compile_fail
+//!# use qcell::intrusive::{Node, PinnedListHeader};
+//! let node: Node<u32>;
+//! let list = PinnedListHeader::new();
+//! node = list.node(100);  // Compile fail
+//! ```
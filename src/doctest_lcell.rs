@@ -1,4 +1,4 @@
-// Run ./update-compiletest-from-doctest.pl in crate base directory
+// Run `cargo xtask sync-compiletests` in crate base directory
 // after making any modification to compile_fail tests here.
 
 //! This tests the `LCell` implementation.
@@ -24,20 +24,8 @@
 //! });
 //! ```
 //!
-//! Two different owners can't borrow each other's cells immutably:
-//!
-//! ```compile_fail
-//!# use qcell::{LCell, LCellOwner};
-//!# use std::rc::Rc;
-//! LCellOwner::scope(|mut owner1| {
-//!     LCellOwner::scope(|mut owner2| {
-//!         let c1 = Rc::new(LCell::new(100u32));
-//!         let c1ref1 = owner1.ro(&c1);
-//!         let c1ref2 = owner2.ro(&c1);   // Compile error
-//!         println!("{}", *c1ref2);
-//!     });
-//! });
-//! ```
+//! Two different owners can't borrow each other's cells immutably.
+//! See [QCELL-E010].
 //!
 //! Or mutably:
 //!
@@ -376,3 +364,252 @@
 //!     assert_eq!(0, owner.ro(&cell).0);
 //! });
 //! ```
+//!
+//! For `Copy` contents, `get` and `set` copy values in and out
+//! without holding a borrow, so the owner is free to be passed on
+//! to other code in between:
+//!
+//! ```
+//!# use qcell::{LCell, LCellOwner};
+//! fn bump<'id>(owner: &mut LCellOwner<'id>, cell: &LCell<'id, u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! LCellOwner::scope(|mut owner| {
+//!     let hits = LCell::new(0u64);
+//!     let before = owner.get(&hits);
+//!     bump(&mut owner, &hits);
+//!     owner.set(&hits, owner.get(&hits) + before);
+//!     assert_eq!(1, owner.get(&hits));
+//! });
+//! ```
+//!
+//! Whereas a borrow from `rw` has to end before the owner can be
+//! passed on:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! fn bump<'id>(owner: &mut LCellOwner<'id>, cell: &LCell<'id, u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! LCellOwner::scope(|mut owner| {
+//!     let hits = LCell::new(0u64);
+//!     let count = owner.rw(&hits);
+//!     bump(&mut owner, &hits); // Compile fail
+//!     *count += 1;
+//! });
+//! ```
+//!
+//! `rw_with` borrows the contents of a cell alongside data that is
+//! already borrowed mutably from elsewhere, such as a local buffer.
+//! No runtime check is needed between the two.  `rw2_with` does the
+//! same for two cells:
+//!
+//! ```
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let log = LCell::new(vec![1, 2]);
+//!     let pending = LCell::new(vec![3]);
+//!     let mut buf = vec![0];
+//!     let n = owner.rw_with(&log, &mut buf, |log, buf| {
+//!         buf.append(log);
+//!         buf.len()
+//!     });
+//!     assert_eq!(n, 3);
+//!     owner.rw2_with(&log, &pending, &mut buf, |log, pending, buf| {
+//!         log.append(pending);
+//!         log.extend(buf.drain(..));
+//!     });
+//!     assert_eq!(*owner.ro(&log), [3, 0, 1, 2]);
+//!     assert!(buf.is_empty());
+//! });
+//! ```
+//!
+//! The owner is borrowed for the call, so the closure can't use it
+//! to reach other cells:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let log = LCell::new(vec![1, 2]);
+//!     let other = LCell::new(3);
+//!     let mut buf = Vec::new();
+//!     owner.rw_with(&log, &mut buf, |log, buf| {
+//!         buf.push(*owner.ro(&other)); // Compile fail
+//!         buf.append(log);
+//!     });
+//! });
+//! ```
+//!
+//! With `LCellOwner::scope_build`, the structure built in the first
+//! phase is passed to the second phase as a `Branded` value of the
+//! same brand:
+//!
+//! ```
+//!# use qcell::{BrandFamily, Branded, LCell, LCellOwner};
+//! struct CellFamily;
+//! impl<'id> BrandFamily<'id> for CellFamily {
+//!     type Of = LCell<'id, u32>;
+//! }
+//! let n = LCellOwner::scope_build(
+//!     1,
+//!     |owner, n| Branded::<CellFamily>::new(owner.cell(n)),
+//!     |owner, cell| {
+//!         *owner.rw(&cell) += 1;
+//!         owner.get(&cell)
+//!     },
+//! );
+//! assert_eq!(n, 2);
+//! ```
+//!
+//! The `Branded` value can't be returned out of the scope:
+//!
+//! ```compile_fail
+//!# use qcell::{BrandFamily, Branded, LCell, LCellOwner};
+//! struct CellFamily;
+//! impl<'id> BrandFamily<'id> for CellFamily {
+//!     type Of = LCell<'id, u32>;
+//! }
+//! let cell = LCellOwner::scope_build(
+//!     1,
+//!     |owner, n| Branded::<CellFamily>::new(owner.cell(n)),
+//!     |_, cell| cell, // Compile fail
+//! );
+//! ```
+//!
+//! Or stored outside of it:
+//!
+//! ```compile_fail
+//!# use qcell::{BrandFamily, Branded, LCell, LCellOwner};
+//! struct CellFamily;
+//! impl<'id> BrandFamily<'id> for CellFamily {
+//!     type Of = LCell<'id, u32>;
+//! }
+//! let mut stash = None;
+//! LCellOwner::scope_build(
+//!     1,
+//!     |owner, n| Branded::<CellFamily>::new(owner.cell(n)),
+//!     |_, cell| stash = Some(cell), // Compile fail
+//! );
+//! ```
+//!
+//! Or used with the owner of another scope:
+//!
+//! ```compile_fail
+//!# use qcell::{BrandFamily, Branded, LCell, LCellOwner};
+//! struct CellFamily;
+//! impl<'id> BrandFamily<'id> for CellFamily {
+//!     type Of = LCell<'id, u32>;
+//! }
+//! LCellOwner::scope_build(
+//!     1,
+//!     |owner, n| Branded::<CellFamily>::new(owner.cell(n)),
+//!     |_, cell| {
+//!         LCellOwner::scope(|mut inner| {
+//!             *inner.rw(&cell) += 1; // Compile fail
+//!         });
+//!     },
+//! );
+//! ```
+//!
+//! During `LCellOwner::read_phase`, the owner is only available by
+//! shared reference, so it can be handed to scoped reader threads:
+//!
+#![cfg_attr(
+    feature = "scoped-threads",
+    doc = "
+ ```
+ # use qcell::{LCell, LCellOwner};
+ LCellOwner::scope(|mut owner| {
+     let cell = LCell::new(1);
+     let sum = owner.read_phase(|owner| {
+         std::thread::scope(|s| {
+             let reader = s.spawn(|| owner.get(&cell));
+             owner.get(&cell) + reader.join().unwrap()
+         })
+     });
+     *owner.rw(&cell) += sum;
+     assert_eq!(owner.get(&cell), 3);
+ });
+ ```
+"
+)]
+//!
+//! But a reader thread can't borrow a cell mutably:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let cell = LCell::new(1);
+//!     owner.read_phase(|owner| {
+//!         std::thread::scope(|s| {
+//!             s.spawn(|| *owner.rw(&cell) += 1); // Compile fail
+//!         });
+//!     });
+//! });
+//! ```
+//!
+//! The outer owner can't be used for writing during the phase:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let cell = LCell::new(1);
+//!     owner.read_phase(|reader| {
+//!         let value = reader.get(&cell);
+//!         owner.set(&cell, value + 1); // Compile fail
+//!     });
+//! });
+//! ```
+//!
+//! And borrows can't be carried out of the phase, so write access
+//! resumes as soon as it ends:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let cell = LCell::new(1);
+//!     let value = owner.read_phase(|reader| reader.ro(&cell)); // Compile fail
+//!     *owner.rw(&cell) += 1;
+//!     assert_eq!(*value, 1);
+//! });
+//! ```
+//!
+//! A cell reference that lives longer than the owner borrow is
+//! shortened to fit, so a holder of the cell reference can lend out
+//! the contents for as long as it's given the owner, but no longer:
+//!
+//! ```
+//!# use qcell::{LCell, LCellOwner};
+//! struct Holder<'c, 'id> {
+//!     cell: &'c LCell<'id, i32>,
+//! }
+//! impl<'c, 'id> Holder<'c, 'id> {
+//!     fn write<'o>(&self, owner: &'o mut LCellOwner<'id>) -> &'o mut i32
+//!     where
+//!         'c: 'o,
+//!     {
+//!         owner.rw(self.cell)
+//!     }
+//! }
+//! LCellOwner::scope(|mut owner| {
+//!     let cell = LCell::new(1);
+//!     let holder = Holder { cell: &cell };
+//!     let value = holder.write(&mut owner);
+//!     drop(holder);
+//!     *value += 1;
+//!     assert_eq!(*owner.ro(&cell), 2);
+//! });
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let cell = LCell::new(1);
+//!     let holder = &cell;
+//!     let value = owner.ro(holder);
+//!     *owner.rw(holder) += 1; // Compile fail
+//!     assert_eq!(*value, 1);
+//! });
+//! ```
+//!
+//! [QCELL-E010]: ../errors/e010/index.html
@@ -0,0 +1,89 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the `ErasedLCell` implementation.
+//!
+//! A restored cell can be borrowed through the owner in the scope
+//! where it was restored:
+//!
+//! ```
+//!# use qcell::{LCell, LCellOwner};
+//! let token = LCellOwner::brand_token();
+//! let mut erased = None;
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     erased = Some(owner.erase(LCell::new(100u32)));
+//! });
+//! LCellOwner::scope_with_token(token, |mut owner| {
+//!     let cell = owner.restore(erased.as_ref().unwrap()).unwrap();
+//!     *owner.rw(cell) += 1;
+//! });
+//! ```
+//!
+//! But a restored cell can't escape the scope it was restored in:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! let token = LCellOwner::brand_token();
+//! let erased = std::cell::RefCell::new(None);
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     *erased.borrow_mut() = Some(owner.erase(LCell::new(100u32)));
+//! });
+//! let erased = erased.into_inner().unwrap();
+//! let mut escaped = None;
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     escaped = owner.restore(&erased);  // Compile fail
+//! });
+//! ```
+//!
+//! Nor can a reference to the contents escape:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! let token = LCellOwner::brand_token();
+//! let mut erased = None;
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     erased = Some(owner.erase(LCell::new(100u32)));
+//! });
+//! let mut escaped = None;
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     let cell = owner.restore(erased.as_ref().unwrap()).unwrap();
+//!     escaped = Some(owner.ro(cell));  // Compile fail
+//! });
+//! ```
+//!
+//! A restored cell can't be used with an owner from a different
+//! scope, even one created with the same token later:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! let token = LCellOwner::brand_token();
+//! let mut erased = None;
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     erased = Some(owner.erase(LCell::new(100u32)));
+//! });
+//! LCellOwner::scope_with_token(token, |owner1| {
+//!     let cell = owner1.restore(erased.as_ref().unwrap()).unwrap();
+//!     LCellOwner::scope(|mut owner2| {
+//!         *owner2.rw(cell) += 1;  // Compile fail
+//!     });
+//! });
+//! ```
+//!
+//! The erased cell can't be borrowed mutably twice through the same
+//! owner:
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! let token = LCellOwner::brand_token();
+//! let mut erased = None;
+//! LCellOwner::scope_with_token(token, |owner| {
+//!     erased = Some(owner.erase(LCell::new(100u32)));
+//! });
+//! LCellOwner::scope_with_token(token, |mut owner| {
+//!     let c1 = owner.restore(erased.as_ref().unwrap()).unwrap();
+//!     let c2 = owner.restore(erased.as_ref().unwrap()).unwrap();
+//!     let r1 = owner.rw(c1);
+//!     let r2 = owner.rw(c2);  // Compile fail
+//!     *r1 += *r2;
+//! });
+//! ```
@@ -1,4 +1,4 @@
-// Run ./update-compiletest-from-doctest.pl in crate base directory
+// Run `cargo xtask sync-compiletests` in crate base directory
 // after making any modification to compile_fail tests here.
 
 //! This tests the `LCell` implementation when using `generativity`.
@@ -0,0 +1,55 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the `par_iter` and `par_values` helpers.
+//!
+//! The owner is borrowed immutably for as long as the parallel
+//! iterator exists, so the cell contents can be read in parallel:
+//!
+//! ```
+//!# use qcell::QCellOwner;
+//!# use rayon::iter::ParallelIterator;
+//!# use std::collections::HashMap;
+//! let mut owner = QCellOwner::new();
+//! let cell = owner.cell((0..100).map(|i| (i, i * 2)).collect::<HashMap<u32, u32>>());
+//! let iter = owner.par_values(&cell);
+//! assert_eq!(owner.ro(&cell).len(), 100);
+//! assert_eq!(iter.sum::<u32>(), 9900);
+//! owner.rw(&cell).clear();
+//! ```
+//!
+//! But not modified until it is dropped:
+//!
+//! ```compile_fail
+//!# use qcell::QCellOwner;
+//!# use rayon::iter::ParallelIterator;
+//!# use std::collections::HashMap;
+//! let mut owner = QCellOwner::new();
+//! let cell = owner.cell((0..100).map(|i| (i, i * 2)).collect::<HashMap<u32, u32>>());
+//! let iter = owner.par_values(&cell);
+//! owner.rw(&cell).clear();  // Compile fail
+//! assert_eq!(iter.sum::<u32>(), 9900);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TCell, TCellOwner};
+//!# use rayon::iter::ParallelIterator;
+//! marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cell = TCell::new(vec![1, 2, 3]);
+//! let iter = owner.par_iter(&cell);
+//! owner.rw(&cell).push(4);  // Compile fail
+//! assert_eq!(iter.sum::<i32>(), 6);
+//! ```
+//!
+//! The items are shared between threads, so the contents must be
+//! `Sync`:
+//!
+//! ```compile_fail
+//!# use qcell::QCellOwner;
+//!# use rayon::iter::ParallelIterator;
+//!# use std::cell::Cell;
+//! let owner = QCellOwner::new();
+//! let cell = owner.cell(vec![Cell::new(1)]);
+//! owner.par_iter(&cell).for_each(|c| c.set(2));  // Compile fail
+//! ```
@@ -1,4 +1,4 @@
-// Run ./update-compiletest-from-doctest.pl in crate base directory
+// Run `cargo xtask sync-compiletests` in crate base directory
 // after making any modification to compile_fail tests here.
 
 //! This tests the `QCell` implementation.
@@ -13,14 +13,7 @@
 //! let rc = Rc::new(QCell::new(&owner1, 100u32));  // Compile fail
 //! ```
 //!
-//! It should be impossible to clone a QCellOwner:
-//!
-//! ```compile_fail
-//!# use qcell::{QCell, QCellOwner};
-//!# use std::rc::Rc;
-//! let mut owner1 = QCellOwner::new();
-//! let owner2 = owner1.clone();  // Compile fail
-//! ```
+//! It should be impossible to clone a QCellOwner.  See [QCELL-E006].
 //!
 //! Two different owners can't borrow each other's cells immutably:
 //!
@@ -49,20 +42,7 @@
 //! ```
 //!
 //! You can't have two separate mutable borrows active on the same
-//! owner at the same time:
-//!
-//! ```compile_fail
-//!# use qcell::{QCell, QCellOwner};
-//!# use std::rc::Rc;
-//! let mut owner = QCellOwner::new();
-//! let c1 = Rc::new(QCell::new(&owner, 100u32));
-//! let c2 = Rc::new(QCell::new(&owner, 200u32));
-//!
-//! let c1mutref = owner.rw(&c1);
-//! let c2mutref=  owner.rw(&c2);  // Compile error
-//! *c1mutref += 1;
-//! *c2mutref += 2;
-//! ```
+//! owner at the same time.  See [QCELL-E002].
 //!
 //! However with `rw2()` you can do two mutable borrows at the
 //! same time, since this call checks at runtime that the two
@@ -81,18 +61,7 @@
 //! ```
 //!
 //! You can't have a mutable borrow at the same time as an immutable
-//! borrow:
-//!
-//! ```compile_fail
-//!# use qcell::{QCell, QCellOwner};
-//!# use std::rc::Rc;
-//!# let mut owner = QCellOwner::new();
-//!# let c1 = Rc::new(QCell::new(&owner, 100u32));
-//!# let c2 = Rc::new(QCell::new(&owner, 200u32));
-//! let c1ref = owner.ro(&c1);
-//! let c1mutref = owner.rw(&c1);    // Compile error
-//! println!("{}", *c1ref);
-//! ```
+//! borrow.  See [QCELL-E001].
 //!
 //! Not even if it's borrowing a different object:
 //!
@@ -122,18 +91,8 @@
 //! assert_eq!(600, *c1ref + *c2ref + *c1ref2 + *c2ref2);   // Success!
 //! ```
 //!
-//! Whilst a reference is active, it's impossible to drop the `Rc`:
-//!
-//! ```compile_fail
-//!# use qcell::{QCell, QCellOwner};
-//!# use std::rc::Rc;
-//!# let mut owner = QCellOwner::new();
-//!# let c1 = Rc::new(QCell::new(&owner, 100u32));
-//!# let c2 = Rc::new(QCell::new(&owner, 200u32));
-//! let c1ref = owner.ro(&c1);
-//! drop(c1);    // Compile error
-//! println!("{}", *c1ref);
-//! ```
+//! Whilst a reference is active, it's impossible to drop the `Rc`.
+//! See [QCELL-E005].
 //!
 //! Also, whilst a reference is active, it's impossible to call
 //! anything else that uses the `owner` in an incompatible way,
@@ -219,16 +178,7 @@
 //! is_sync::<QCell<Cell<i32>>>();  // Compile fail
 //! ```
 //!
-//! ```compile_fail
-//!# use qcell::{QCell, QCellOwner};
-//!# use std::cell::Cell;
-//! let owner = QCellOwner::new();
-//! let cell = QCell::new(&owner, Cell::new(100));
-//!
-//! // This would be a data race if the compiler permitted it, but it doesn't
-//! std::thread::spawn(|| owner.ro(&cell).set(200));  // Compile fail
-//! owner.ro(&cell).set(300);
-//! ```
+//! Sharing such a cell between threads is blocked.  See [QCELL-E008].
 //!
 //! If the contained type isn't `Send`, the `QCell` should be neither
 //! `Sync` nor `Send`:
@@ -240,12 +190,8 @@
 //! is_sync::<QCell<Rc<()>>>();  // Compile fail
 //! ```
 //!
-//! ```compile_fail
-//!# use qcell::QCell;
-//!# use std::rc::Rc;
-//! fn is_send<T: Send>() {}
-//! is_send::<QCell<Rc<()>>>();  // Compile fail
-//! ```
+//! For `Send`, see [QCELL-E007].  So the cell can't be moved to
+//! another thread either:
 //!
 //! ```compile_fail
 //!# use qcell::{QCell, QCellOwner};
@@ -305,3 +251,141 @@
 //!# use qcell::QCell;
 //! let mut cell: QCell<i32> = QCell::default();
 //! ```
+//!
+//! For `Copy` contents, `get` and `set` copy values in and out
+//! without holding a borrow, so the owner is free to be passed on
+//! to other code in between:
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwner};
+//! fn bump(owner: &mut QCellOwner, cell: &QCell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = QCellOwner::new();
+//! let hits = QCell::new(&owner, 0u64);
+//! let before = owner.get(&hits);
+//! bump(&mut owner, &hits);
+//! owner.set(&hits, owner.get(&hits) + before);
+//! assert_eq!(1, owner.get(&hits));
+//! ```
+//!
+//! Whereas a borrow from `rw` has to end before the owner can be
+//! passed on.  See [QCELL-E003].
+//!
+//! `rw_with` borrows the contents of a cell alongside data that is
+//! already borrowed mutably from elsewhere, such as a local buffer.
+//! No runtime check is needed between the two.  `rw2_with` does the
+//! same for two cells:
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let log = QCell::new(&owner, vec![1, 2]);
+//! let pending = QCell::new(&owner, vec![3]);
+//! let mut buf = vec![0];
+//! let n = owner.rw_with(&log, &mut buf, |log, buf| {
+//!     buf.append(log);
+//!     buf.len()
+//! });
+//! assert_eq!(n, 3);
+//! owner.rw2_with(&log, &pending, &mut buf, |log, pending, buf| {
+//!     log.append(pending);
+//!     log.extend(buf.drain(..));
+//! });
+//! assert_eq!(*owner.ro(&log), [3, 0, 1, 2]);
+//! assert!(buf.is_empty());
+//! ```
+//!
+//! The owner is borrowed for the call, so the closure can't use it
+//! to reach other cells.  See [QCELL-E004].
+//!
+//! A mutable view keeps the owner mutably borrowed, so no other cell
+//! can be borrowed through it until the view is dropped:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let grid = QCell::new(&owner, vec![1, 2, 3]);
+//! let scale = QCell::new(&owner, 10);
+//! let mut view = owner.view_mut(&grid);
+//! view[0] *= *owner.ro(&scale); // Compile fail
+//! ```
+//!
+//! A cell reference that lives longer than the owner borrow is
+//! shortened to fit, so a holder of the cell reference can lend out
+//! the contents for as long as it's given the owner, but no longer:
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwner};
+//! struct Holder<'c> {
+//!     cell: &'c QCell<i32>,
+//! }
+//! impl<'c> Holder<'c> {
+//!     fn write<'o>(&self, owner: &'o mut QCellOwner) -> &'o mut i32
+//!     where
+//!         'c: 'o,
+//!     {
+//!         owner.rw(self.cell)
+//!     }
+//! }
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let holder = Holder { cell: &cell };
+//! let value = holder.write(&mut owner);
+//! drop(holder);
+//! *value += 1;
+//! assert_eq!(*owner.ro(&cell), 2);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let id = QCellOwner::new().id();
+//! let cell: &'static QCell<i32> = Box::leak(Box::new(QCell::new(id, 1)));
+//! let value = {
+//!     let owner = QCellOwner::new();
+//!     owner.ro(cell) // Compile fail
+//! };
+//! assert_eq!(*value, 1);
+//! ```
+//!
+//! A cell is never `RefUnwindSafe`, so a closure that borrows one
+//! can't be passed to `catch_unwind` without `AssertUnwindSafe`:
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwner};
+//!# use std::panic::{catch_unwind, AssertUnwindSafe};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let result = catch_unwind(AssertUnwindSafe(|| *owner.rw(&cell) += 1));
+//! assert!(result.is_ok());
+//! assert_eq!(*owner.ro(&cell), 2);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 1);
+//! let result = std::panic::catch_unwind(|| *owner.ro(&cell)); // Compile fail
+//! ```
+//!
+//! An owner can't be handed to another thread with `into_transfer`
+//! whilst a reference to cell contents obtained through it is still
+//! in use:
+//!
+//! ```compile_fail
+//!# use qcell::QCellOwner;
+//! let owner = QCellOwner::new();
+//! let cell = owner.cell(0_u64);
+//! let value = owner.ro(&cell);
+//! let transfer = owner.into_transfer(); // Compile fail
+//! assert_eq!(*value, 0);
+//! ```
+//!
+//! [QCELL-E001]: ../errors/e001/index.html
+//! [QCELL-E002]: ../errors/e002/index.html
+//! [QCELL-E003]: ../errors/e003/index.html
+//! [QCELL-E004]: ../errors/e004/index.html
+//! [QCELL-E005]: ../errors/e005/index.html
+//! [QCELL-E006]: ../errors/e006/index.html
+//! [QCELL-E007]: ../errors/e007/index.html
+//! [QCELL-E008]: ../errors/e008/index.html
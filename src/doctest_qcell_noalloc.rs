@@ -1,4 +1,4 @@
-// Run ./update-compiletest-from-doctest.pl in crate base directory
+// Run `cargo xtask sync-compiletests` in crate base directory
 // after making any modification to compile_fail tests here.
 
 //! This tests the `QCell` implementation without the `alloc` feature.
@@ -313,3 +313,84 @@
 //!     assert_eq!(100, **owner.as_ref().ro(&cell));
 //! }).join();
 //! ```
+//!
+//! For `Copy` contents, `get` and `set` copy values in and out
+//! without holding a borrow, so the owner is free to be passed on
+//! to other code in between:
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwnerPinned};
+//!# use std::pin::Pin;
+//!# use pin_utils::pin_mut;
+//! fn bump(owner: Pin<&mut QCellOwnerPinned>, cell: &QCell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = QCellOwnerPinned::new();
+//! pin_mut!(owner);
+//! let hits = owner.as_ref().cell(0u64);
+//! let before = owner.as_ref().get(&hits);
+//! bump(owner.as_mut(), &hits);
+//! owner.as_mut().set(&hits, before + 10);
+//! assert_eq!(10, owner.as_ref().get(&hits));
+//! ```
+//!
+//! Whereas a borrow from `rw` has to end before the owner can be
+//! passed on:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwnerPinned};
+//!# use std::pin::Pin;
+//!# use pin_utils::pin_mut;
+//! fn bump(owner: Pin<&mut QCellOwnerPinned>, cell: &QCell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = QCellOwnerPinned::new();
+//! pin_mut!(owner);
+//! let hits = owner.as_ref().cell(0u64);
+//! let count = owner.as_mut().rw(&hits);
+//! bump(owner.as_mut(), &hits); // Compile fail
+//! *count += 1;
+//! ```
+//!
+//! `rw_with` borrows the contents of a cell alongside data that is
+//! already borrowed mutably from elsewhere, such as a local buffer.
+//! No runtime check is needed between the two.  `rw2_with` does the
+//! same for two cells:
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwnerPinned};
+//!# use pin_utils::pin_mut;
+//! let mut owner = QCellOwnerPinned::new();
+//! pin_mut!(owner);
+//! let log = owner.as_ref().cell(vec![1, 2]);
+//! let pending = owner.as_ref().cell(vec![3]);
+//! let mut buf = vec![0];
+//! let n = owner.as_mut().rw_with(&log, &mut buf, |log, buf| {
+//!     buf.append(log);
+//!     buf.len()
+//! });
+//! assert_eq!(n, 3);
+//! owner.as_mut().rw2_with(&log, &pending, &mut buf, |log, pending, buf| {
+//!     log.append(pending);
+//!     log.extend(buf.drain(..));
+//! });
+//! assert_eq!(*owner.as_ref().ro(&log), [3, 0, 1, 2]);
+//! assert!(buf.is_empty());
+//! ```
+//!
+//! The owner is borrowed for the call, so the closure can't use it
+//! to reach other cells:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwnerPinned};
+//!# use pin_utils::pin_mut;
+//! let mut owner = QCellOwnerPinned::new();
+//! pin_mut!(owner);
+//! let log = owner.as_ref().cell(vec![1, 2]);
+//! let other = owner.as_ref().cell(3);
+//! let mut buf = Vec::new();
+//! owner.as_mut().rw_with(&log, &mut buf, |log, buf| {
+//!     buf.push(*owner.as_ref().ro(&other)); // Compile fail
+//!     buf.append(log);
+//! });
+//! ```
@@ -0,0 +1,108 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the argument forms accepted by `rw_distinct!`.
+//!
+//! For comparison, this compiles:
+//!
+//! ```
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let a = owner.cell(1);
+//!     let b = owner.cell(2);
+//!     // Safety: `a` and `b` are separate cells
+//!     let (a, b) = unsafe { rw_distinct!(owner => &a, &b) };
+//!     *a += *b;
+//! });
+//! ```
+//!
+//! It must be used inside an `unsafe` block, since the caller is
+//! promising that the cells are distinct:
+//!
+//! ```compile_fail
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let a = owner.cell(1);
+//!     let b = owner.cell(2);
+//!     let (a, b) = rw_distinct!(owner => &a, &b);  // Compile fail
+//! });
+//! ```
+//!
+//! The same name can't be given twice:
+//!
+//! ```compile_fail
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let a = owner.cell(1);
+//!     let (a1, a2) = unsafe { rw_distinct!(owner => &a, &a) };  // Compile fail
+//! });
+//! ```
+//!
+//! Field accesses, indexing and calls are rejected, since the macro
+//! can't see whether they give different cells:
+//!
+//! ```compile_fail
+//!# use qcell::{rw_distinct, LCell, LCellOwner};
+//! struct Pair<'id> {
+//!     a: LCell<'id, u32>,
+//!     b: LCell<'id, u32>,
+//! }
+//! LCellOwner::scope(|mut owner| {
+//!     let pair = Pair { a: owner.cell(1), b: owner.cell(2) };
+//!     let (a, b) = unsafe { rw_distinct!(owner => &pair.a, &pair.b) };  // Compile fail
+//! });
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let cells = [owner.cell(1), owner.cell(2)];
+//!     let (a, b) = unsafe { rw_distinct!(owner => &cells[0], &cells[1]) };  // Compile fail
+//! });
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let cells = vec![owner.cell(1), owner.cell(2)];
+//!     let (a, b) = unsafe { rw_distinct!(owner => cells.first().unwrap(), &cells[1]) };  // Compile fail
+//! });
+//! ```
+//!
+//! As with `rw!`, the owner stays borrowed while the references are
+//! in use:
+//!
+//! ```compile_fail
+//!# use qcell::{rw, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let a = owner.cell(1);
+//!     let b = owner.cell(2);
+//!     let (ra, rb) = rw!(owner => &a, &b);
+//!     let c = owner.get(&a);  // Compile fail
+//!     *ra += *rb;
+//! });
+//! ```
+//!
+//! The `unsafe` block is the caller's own, so a crate which forbids
+//! `unsafe_code` can't use `rw_distinct!` at all, with or without
+//! the block:
+//!
+//! ```compile_fail
+//!# #![forbid(unsafe_code)]
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let a = owner.cell(1);
+//!     let b = &a;
+//!     let (a, b) = rw_distinct!(owner => &a, &b);  // Compile fail
+//! });
+//! ```
+//!
+//! ```compile_fail
+//!# #![forbid(unsafe_code)]
+//!# use qcell::{rw_distinct, LCellOwner};
+//! LCellOwner::scope(|mut owner| {
+//!     let a = owner.cell(1);
+//!     let b = owner.cell(2);
+//!     let (a, b) = unsafe { rw_distinct!(owner => &a, &b) };  // Compile fail
+//! });
+//! ```
@@ -0,0 +1,58 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the `scope_chunks` helper.
+//!
+//! The contents of the cells are mutated from other threads, so the
+//! contained type must be `Send`:
+//!
+//! ```
+//!# use qcell::{marker, scope_chunks, TCell, TCellOwner};
+//! marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cells: Vec<_> = (0..10).map(|i| TCell::new(i.to_string())).collect();
+//! scope_chunks(&mut owner, &cells, 3, |_, chunk| {
+//!     for s in chunk {
+//!         s.push('!');
+//!     }
+//! });
+//! assert_eq!(owner.ro(&cells[9]), "9!");
+//! ```
+//!
+//! So an `Rc` payload is rejected:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, scope_chunks, TCell, TCellOwner};
+//!# use std::rc::Rc;
+//! marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cells: Vec<_> = (0..10).map(|i| TCell::new(Rc::new(i))).collect();
+//! scope_chunks(&mut owner, &cells, 3, |_, _| {});  // Compile fail
+//! ```
+//!
+//! The chunks can't escape from the closure:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, scope_chunks, TCell, TCellOwner};
+//!# use std::sync::Mutex;
+//! marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cells: Vec<_> = (0..10).map(TCell::new).collect();
+//! let escaped = Mutex::new(None);
+//! scope_chunks(&mut owner, &cells, 3, |_, chunk| {
+//!     *escaped.lock().unwrap() = Some(chunk);  // Compile fail
+//! });
+//! ```
+//!
+//! And the owner is borrowed mutably for the duration, so the cells
+//! can't be accessed through it at the same time:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, scope_chunks, TCell, TCellOwner};
+//! marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cells: Vec<_> = (0..10).map(TCell::new).collect();
+//! scope_chunks(&mut owner, &cells, 3, |_, _| {
+//!     let _ = owner.ro(&cells[0]);  // Compile fail
+//! });
+//! ```
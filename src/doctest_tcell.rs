@@ -1,4 +1,4 @@
-// Run ./update-compiletest-from-doctest.pl in crate base directory
+// Run `cargo xtask sync-compiletests` in crate base directory
 // after making any modification to compile_fail tests here.
 
 //! This tests the `TCell` implementation.
@@ -468,3 +468,186 @@
 //! let mut cell: ACell<NoDefault> = ACell::default(); // Compile fail
 //! assert_eq!(0, owner.ro(&cell).0);
 //! ```
+//!
+//! For `Copy` contents, `get` and `set` copy values in and out
+//! without holding a borrow, so the owner is free to be passed on
+//! to other code in between:
+//!
+//! ```
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TCell<Marker, T>;
+//!# type ACellOwner = TCellOwner<Marker>;
+//! fn bump(owner: &mut ACellOwner, cell: &ACell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = ACellOwner::new();
+//! let hits = ACell::new(0u64);
+//! let before = owner.get(&hits);
+//! bump(&mut owner, &hits);
+//! owner.set(&hits, owner.get(&hits) + before);
+//! assert_eq!(1, owner.get(&hits));
+//! ```
+//!
+//! Whereas a borrow from `rw` has to end before the owner can be
+//! passed on:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TCell<Marker, T>;
+//!# type ACellOwner = TCellOwner<Marker>;
+//! fn bump(owner: &mut ACellOwner, cell: &ACell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = ACellOwner::new();
+//! let hits = ACell::new(0u64);
+//! let count = owner.rw(&hits);
+//! bump(&mut owner, &hits); // Compile fail
+//! *count += 1;
+//! ```
+//!
+//! `rw_with` borrows the contents of a cell alongside data that is
+//! already borrowed mutably from elsewhere, such as a local buffer.
+//! No runtime check is needed between the two.  `rw2_with` does the
+//! same for two cells:
+//!
+//! ```
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TCell<Marker, T>;
+//!# type ACellOwner = TCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();
+//! let log = ACell::new(vec![1, 2]);
+//! let pending = ACell::new(vec![3]);
+//! let mut buf = vec![0];
+//! let n = owner.rw_with(&log, &mut buf, |log, buf| {
+//!     buf.append(log);
+//!     buf.len()
+//! });
+//! assert_eq!(n, 3);
+//! owner.rw2_with(&log, &pending, &mut buf, |log, pending, buf| {
+//!     log.append(pending);
+//!     log.extend(buf.drain(..));
+//! });
+//! assert_eq!(*owner.ro(&log), [3, 0, 1, 2]);
+//! assert!(buf.is_empty());
+//! ```
+//!
+//! The owner is borrowed for the call, so the closure can't use it
+//! to reach other cells:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TCell<Marker, T>;
+//!# type ACellOwner = TCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();
+//! let log = ACell::new(vec![1, 2]);
+//! let other = ACell::new(3);
+//! let mut buf = Vec::new();
+//! owner.rw_with(&log, &mut buf, |log, buf| {
+//!     buf.push(*owner.ro(&other)); // Compile fail
+//!     buf.append(log);
+//! });
+//! ```
+//!
+//! Slot owners of the same marker type coexist, but each can only
+//! access the cells of its own slot, since the slot is part of the
+//! type:
+//!
+//! ```compile_fail
+//!# use qcell::{TCellSlot, TCellSlotOwner};
+//!# struct Marker;
+//! let owner0 = TCellSlotOwner::<Marker, 0>::new();
+//! let owner1 = TCellSlotOwner::<Marker, 1>::new();
+//! let c1 = TCellSlot::<Marker, 1, _>::new(1);
+//! assert_eq!(*owner1.ro(&c1), 1);
+//! let _ = owner0.ro(&c1); // Compile fail
+//! ```
+//!
+//! An [`IndexedMarker`](../trait.IndexedMarker.html) index must be in
+//! the range `0..64`:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TCellOwner};
+//! marker!(struct Marker = 64;); // Compile fail
+//! let owner = TCellOwner::<Marker>::new_indexed();
+//! ```
+//!
+//! `ro` and `rw` borrow the owner and the cell for the same lifetime,
+//! but a longer-lived cell reference is shortened to fit, so code that
+//! holds a cell reference can still lend out the contents for just as
+//! long as it's given the owner:
+//!
+//! ```
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACellOwner = TCellOwner<Marker>;
+//!# type ACell<T> = TCell<Marker, T>;
+//! struct Holder<'c> {
+//!     cell: &'c ACell<i32>,
+//! }
+//! impl<'c> Holder<'c> {
+//!     fn read<'o>(&self, owner: &'o ACellOwner) -> &'o i32
+//!     where
+//!         'c: 'o,
+//!     {
+//!         owner.ro(self.cell)
+//!     }
+//!     fn write<'o>(&self, owner: &'o mut ACellOwner) -> &'o mut i32
+//!     where
+//!         'c: 'o,
+//!     {
+//!         owner.rw(self.cell)
+//!     }
+//! }
+//! let cell = ACell::new(1);
+//! let mut owner = ACellOwner::new();
+//! let holder = Holder { cell: &cell };
+//! *holder.write(&mut owner) += 1;
+//! let value = holder.read(&owner);
+//! drop(holder); // `value` borrows the owner and cell, not the holder
+//! assert_eq!(*value, 2);
+//! ```
+//!
+//! The contents still can't be borrowed for longer than the owner,
+//! however long the cell lives:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACellOwner = TCellOwner<Marker>;
+//!# type ACell<T> = TCell<Marker, T>;
+//! let cell: &'static ACell<i32> = Box::leak(Box::new(ACell::new(1)));
+//! let value = {
+//!     let owner = ACellOwner::new();
+//!     owner.ro(cell) // Compile fail
+//! };
+//! assert_eq!(*value, 1);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACellOwner = TCellOwner<Marker>;
+//!# type ACell<T> = TCell<Marker, T>;
+//! let cell: &'static ACell<i32> = Box::leak(Box::new(ACell::new(1)));
+//! let mut owner = ACellOwner::new();
+//! let value = owner.ro(cell);
+//! *owner.rw(cell) += 1; // Compile fail
+//! assert_eq!(*value, 1);
+//! ```
+//!
+//! A cell is never `RefUnwindSafe`, so a closure that borrows one
+//! can't be passed to `catch_unwind` without `AssertUnwindSafe`:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//!# type ACellOwner = TCellOwner<Marker>;
+//!# type ACell<T> = TCell<Marker, T>;
+//! let cell = ACell::new(1);
+//! let owner = ACellOwner::new();
+//! let _ = std::panic::catch_unwind(|| *owner.ro(&cell)); // Compile fail
+//! ```
@@ -0,0 +1,74 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests the `TCell` implementation with the **strict-markers**
+//! feature enabled.
+//!
+//! A marker declared with the `marker!` macro can be used to create
+//! an owner as normal:
+//!
+//! ```
+//!# use qcell::{marker, TCell, TCellOwner};
+//! marker!(struct Marker;);
+//! type ACell<T> = TCell<Marker, T>;
+//! type ACellOwner = TCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();
+//! let cell = ACell::new(100);
+//! *owner.rw(&cell) += 1;
+//! assert_eq!(*owner.ro(&cell), 101);
+//! ```
+//!
+//! However a plain struct can't be used as a marker:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//! struct Marker;
+//! type ACellOwner = TCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();  // Compile fail
+//! ```
+//!
+//! Neither can any other type from another crate, even a private
+//! one, so it's not possible to create an owner for a marker that
+//! happens to be reachable from outside:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//! let mut owner = TCellOwner::<std::string::String>::new();  // Compile fail
+//! ```
+//!
+//! The same applies to `TLCellOwner`:
+//!
+//! ```compile_fail
+//!# use qcell::{TLCell, TLCellOwner};
+//! struct Marker;
+//! type ACellOwner = TLCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();  // Compile fail
+//! ```
+//!
+//! Whereas a macro-declared marker works fine:
+//!
+//! ```
+//!# use qcell::{marker, TLCell, TLCellOwner};
+//! marker!(struct Marker;);
+//! type ACell<T> = TLCell<Marker, T>;
+//! type ACellOwner = TLCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();
+//! let cell = ACell::new(100);
+//! *owner.rw(&cell) += 1;
+//! assert_eq!(*owner.ro(&cell), 101);
+//! ```
+//!
+//! The `marker!` macro won't accept a public marker, because that
+//! would allow other crates to construct an owner for it:
+//!
+//! ```compile_fail
+//!# use qcell::marker;
+//! marker!(pub struct Marker;);  // Compile fail
+//! ```
+//!
+//! Nor will it accept anything other than a unit struct:
+//!
+//! ```compile_fail
+//!# use qcell::marker;
+//! marker!(struct Marker(u32););  // Compile fail
+//! ```
@@ -0,0 +1,88 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests that the witnesses for [`Guarded`] hold the same
+//! borrow of the owner as the references they stand for.
+//!
+//! [`Guarded`]: ../struct.Guarded.html
+//!
+//! For comparison, this compiles, since immutable witnesses and
+//! immutable borrows of the owner may be used together:
+//!
+//! ```
+//!# use qcell::{Guarded, TCell, TCellOwner};
+//! qcell::marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cell = TCell::<Marker, u32>::new(1);
+//! let data = Guarded::<Marker, u32>::new(2);
+//! let w = owner.witness();
+//! assert_eq!(*owner.ro(&cell) + *data.get(w), 3);
+//! *data.get_mut(owner.witness_mut()) += 1;
+//! assert_eq!(*data.get(owner.witness()), 3);
+//! ```
+//!
+//! An `RwWitness` can't coexist with another borrow of the owner:
+//!
+//! ```compile_fail
+//!# use qcell::{Guarded, TCell, TCellOwner};
+//! qcell::marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let cell = TCell::<Marker, u32>::new(1);
+//! let data = Guarded::<Marker, u32>::new(2);
+//! let w = owner.witness_mut();
+//! let c = owner.ro(&cell);  // Compile fail
+//! *data.get_mut(w) += *c;
+//! ```
+//!
+//! Nor with a second `RwWitness`, so the data can't be borrowed
+//! mutably twice:
+//!
+//! ```compile_fail
+//!# use qcell::{Guarded, TCellOwner};
+//! qcell::marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let data = Guarded::<Marker, u32>::new(2);
+//! let w1 = owner.witness_mut();
+//! let w2 = owner.witness_mut();  // Compile fail
+//! let a = data.get_mut(w1);
+//! let b = data.get_mut(w2);
+//! std::mem::swap(a, b);
+//! ```
+//!
+//! A reborrowed `RwWitness` can't be used whilst the reborrow is
+//! still in use:
+//!
+//! ```compile_fail
+//!# use qcell::{Guarded, TCellOwner};
+//! qcell::marker!(struct Marker;);
+//! let mut owner = TCellOwner::<Marker>::new();
+//! let data = Guarded::<Marker, u32>::new(2);
+//! let mut w = owner.witness_mut();
+//! let a = data.get_mut(w.reborrow());
+//! let b = data.get_mut(w);  // Compile fail
+//! std::mem::swap(a, b);
+//! ```
+//!
+//! A witness can't outlive its owner:
+//!
+//! ```compile_fail
+//!# use qcell::{Guarded, TCellOwner};
+//! qcell::marker!(struct Marker;);
+//! let owner = TCellOwner::<Marker>::new();
+//! let data = Guarded::<Marker, u32>::new(2);
+//! let w = owner.witness();
+//! drop(owner);  // Compile fail
+//! assert_eq!(*data.get(w), 2);
+//! ```
+//!
+//! A witness for one marker type can't access data guarded by
+//! another:
+//!
+//! ```compile_fail
+//!# use qcell::{Guarded, TCellOwner};
+//! qcell::marker!(struct MarkerA;);
+//! qcell::marker!(struct MarkerB;);
+//! let mut owner_a = TCellOwner::<MarkerA>::new();
+//! let data = Guarded::<MarkerB, u32>::new(2);
+//! *data.get_mut(owner_a.witness_mut()) += 1;  // Compile fail
+//! ```
@@ -1,4 +1,4 @@
-// Run ./update-compiletest-from-doctest.pl in crate base directory
+// Run `cargo xtask sync-compiletests` in crate base directory
 // after making any modification to compile_fail tests here.
 
 //! This tests the `TLCell` implementation.
@@ -229,14 +229,8 @@
 //! ```
 //!
 //! `TLCellOwner` should be neither `Send` nor `Sync`, because it must
-//! not escape the thread in which it was created:
-//!
-//! ```compile_fail
-//!# use qcell::TLCellOwner;
-//! struct Marker;
-//! fn is_send<T: Send>() {}
-//! is_send::<TLCellOwner<Marker>>();  // Compile fail
-//! ```
+//! not escape the thread in which it was created.  See [QCELL-E009]
+//! for `Send`, and for `Sync`:
 //!
 //! ```compile_fail
 //!# use qcell::TLCellOwner;
@@ -418,3 +412,102 @@
 //! let mut cell: ACell<NoDefault> = ACell::default(); // Compile fail
 //! assert_eq!(0, owner.ro(&cell).0);
 //! ```
+//!
+//! For `Copy` contents, `get` and `set` copy values in and out
+//! without holding a borrow, so the owner is free to be passed on
+//! to other code in between:
+//!
+//! ```
+//!# use qcell::{TLCell, TLCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TLCell<Marker, T>;
+//!# type ACellOwner = TLCellOwner<Marker>;
+//! fn bump(owner: &mut ACellOwner, cell: &ACell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = ACellOwner::new();
+//! let hits = ACell::new(0u64);
+//! let before = owner.get(&hits);
+//! bump(&mut owner, &hits);
+//! owner.set(&hits, owner.get(&hits) + before);
+//! assert_eq!(1, owner.get(&hits));
+//! ```
+//!
+//! Whereas a borrow from `rw` has to end before the owner can be
+//! passed on:
+//!
+//! ```compile_fail
+//!# use qcell::{TLCell, TLCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TLCell<Marker, T>;
+//!# type ACellOwner = TLCellOwner<Marker>;
+//! fn bump(owner: &mut ACellOwner, cell: &ACell<u64>) {
+//!     owner.update(cell, |n| n + 1);
+//! }
+//! let mut owner = ACellOwner::new();
+//! let hits = ACell::new(0u64);
+//! let count = owner.rw(&hits);
+//! bump(&mut owner, &hits); // Compile fail
+//! *count += 1;
+//! ```
+//!
+//! `rw_with` borrows the contents of a cell alongside data that is
+//! already borrowed mutably from elsewhere, such as a local buffer.
+//! No runtime check is needed between the two.  `rw2_with` does the
+//! same for two cells:
+//!
+//! ```
+//!# use qcell::{TLCell, TLCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TLCell<Marker, T>;
+//!# type ACellOwner = TLCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();
+//! let log = ACell::new(vec![1, 2]);
+//! let pending = ACell::new(vec![3]);
+//! let mut buf = vec![0];
+//! let n = owner.rw_with(&log, &mut buf, |log, buf| {
+//!     buf.append(log);
+//!     buf.len()
+//! });
+//! assert_eq!(n, 3);
+//! owner.rw2_with(&log, &pending, &mut buf, |log, pending, buf| {
+//!     log.append(pending);
+//!     log.extend(buf.drain(..));
+//! });
+//! assert_eq!(*owner.ro(&log), [3, 0, 1, 2]);
+//! assert!(buf.is_empty());
+//! ```
+//!
+//! The owner is borrowed for the call, so the closure can't use it
+//! to reach other cells:
+//!
+//! ```compile_fail
+//!# use qcell::{TLCell, TLCellOwner};
+//!# struct Marker;
+//!# type ACell<T> = TLCell<Marker, T>;
+//!# type ACellOwner = TLCellOwner<Marker>;
+//! let mut owner = ACellOwner::new();
+//! let log = ACell::new(vec![1, 2]);
+//! let other = ACell::new(3);
+//! let mut buf = Vec::new();
+//! owner.rw_with(&log, &mut buf, |log, buf| {
+//!     buf.push(*owner.ro(&other)); // Compile fail
+//!     buf.append(log);
+//! });
+//! ```
+//!
+//! Slot owners of the same marker type coexist, but each can only
+//! access the cells of its own slot, since the slot is part of the
+//! type:
+//!
+//! ```compile_fail
+//!# use qcell::{TLCellSlot, TLCellSlotOwner};
+//!# struct Marker;
+//! let owner0 = TLCellSlotOwner::<Marker, 0>::new();
+//! let owner1 = TLCellSlotOwner::<Marker, 1>::new();
+//! let c1 = TLCellSlot::<Marker, 1, _>::new(1);
+//! assert_eq!(*owner1.ro(&c1), 1);
+//! let _ = owner0.ro(&c1); // Compile fail
+//! ```
+//!
+//! [QCELL-E009]: ../errors/e009/index.html
@@ -0,0 +1,55 @@
+// Run `cargo xtask sync-compiletests` in crate base directory
+// after making any modification to compile_fail tests here.
+
+//! This tests lending `TLCell` contents to another thread.
+//!
+//! Whilst the value is lent out by `TLCellOwner::lend_to_thread`, the
+//! cell holds a placeholder.  The owner is borrowed by the handle, so
+//! the placeholder can't be observed:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TLCell, TLCellOwner};
+//! marker!(struct Marker;);
+//! let mut owner = TLCellOwner::<Marker>::new();
+//! let cell = TLCell::new(vec![1, 2, 3]);
+//! let lend = owner.lend_to_thread(&cell, |v| v.push(4));
+//! assert!(owner.ro(&cell).is_empty());  // Compile fail
+//! lend.join();
+//! ```
+//!
+//! The value is sent to the other thread, so must be `Send`:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TLCell, TLCellOwner};
+//!# use std::rc::Rc;
+//! marker!(struct Marker;);
+//! let mut owner = TLCellOwner::<Marker>::new();
+//! let cell = TLCell::new(Rc::new(1));
+//! owner.lend_to_thread(&cell, |_| ()).join();  // Compile fail
+//! ```
+//!
+//! The thread may outlive the caller's stack frame, so the closure
+//! can't borrow from it:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TLCell, TLCellOwner};
+//! marker!(struct Marker;);
+//! let mut owner = TLCellOwner::<Marker>::new();
+//! let cell = TLCell::new(String::new());
+//! let suffix = String::from("abc");
+//! owner.lend_to_thread(&cell, |s| s.push_str(&suffix)).join();  // Compile fail
+//! ```
+//!
+//! And the handle can't outlive the cell, since the value has to be
+//! moved back into it:
+//!
+//! ```compile_fail
+//!# use qcell::{marker, TLCell, TLCellOwner};
+//! marker!(struct Marker;);
+//! let mut owner = TLCellOwner::<Marker>::new();
+//! let lend = {
+//!     let cell = TLCell::new(0u32);
+//!     owner.lend_to_thread(&cell, |v| *v += 1)  // Compile fail
+//! };
+//! lend.join();
+//! ```
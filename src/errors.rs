@@ -0,0 +1,281 @@
+//! Explanations of the compile errors that `qcell` is designed to
+//! produce.
+//!
+//! Most misuses of the cell types are rejected by the borrow checker
+//! or by missing `Send` and `Sync` implementations, so the compiler's
+//! message talks about borrowing the owner or about auto traits,
+//! without mentioning why `qcell` works that way.  Each page here
+//! covers one such pattern, with a stable code such as `QCELL-E001`,
+//! a minimal example which fails to compile, the compiler's output
+//! for it, and the recommended fix.  Search the documentation for the
+//! code, or for the `rustc` error code, to find the page.
+//!
+//! The examples are run as doc-tests, and the compiler output is
+//! checked with `trybuild` in `trybuild-qcell`, from the same source
+//! files in `src/errors/`.  [`explain!`] produces the same text as a
+//! compile error, for use in teaching material and tests.
+//!
+//! The other cases which are blocked are shown in the `doctest_*`
+//! modules, such as [`doctest_qcell`].
+//!
+//! [`explain!`]: ../macro.explain.html
+//! [`doctest_qcell`]: ../doctest_qcell/index.html
+
+// Each page is built from the text in `__qcell_error_text!`, and
+// from `errors/<code>.rs` and `errors/<code>.stderr`.  Add the example
+// and run `TRYBUILD=overwrite cargo test` in `trybuild-qcell` to
+// create the `.stderr` file.
+macro_rules! error_pages {
+    ($(
+        $(#[$attr:meta])*
+        $code:ident $page:ident [$($alias:literal),*];
+    )*) => {
+        $(
+            $(#[$attr])*
+            #[doc = crate::__qcell_error_text!($code)]
+            #[doc = ""]
+            #[doc = "# Example"]
+            #[doc = ""]
+            #[doc = concat!(
+                "```compile_fail\n",
+                include_str!(concat!("errors/", stringify!($code), ".rs")),
+                "```"
+            )]
+            #[doc = ""]
+            #[doc = "# Compiler output"]
+            #[doc = ""]
+            #[doc = concat!(
+                "```text\n",
+                include_str!(concat!("errors/", stringify!($code), ".stderr")),
+                "```"
+            )]
+            $(#[doc(alias = $alias)])*
+            pub mod $page {}
+        )*
+
+        #[cfg(all(test, feature = "std"))]
+        const PAGES: &[(&str, &str)] = &[
+            $((stringify!($code), crate::__qcell_error_text!($code))),*
+        ];
+    };
+}
+
+error_pages! {
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E001 e001 ["QCELL-E001", "E001", "E0502"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E002 e002 ["QCELL-E002", "E002", "E0499"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E003 e003 ["QCELL-E003", "E003", "E0499", "E0502"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E004 e004 ["QCELL-E004", "E004", "E0502"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E005 e005 ["QCELL-E005", "E005", "E0505"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E006 e006 ["QCELL-E006", "E006", "E0599", "E0382"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E007 e007 ["QCELL-E007", "E007", "E0277"];
+
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    E008 e008 ["QCELL-E008", "E008", "E0277"];
+
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    E009 e009 ["QCELL-E009", "E009", "E0277"];
+
+    E010 e010 ["QCELL-E010", "E010", "E0521"];
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    E011 e011 ["QCELL-E011", "E011", "E0502"];
+}
+
+/// Fail to compile, with the explanation of the given `qcell` error
+/// code as the error message.
+///
+/// This is intended for teaching material and tests, to show the
+/// explanation from the [`errors`] module alongside other compiler
+/// output.  The codes are the ones listed there, without the `QCELL-`
+/// prefix.
+///
+/// ```compile_fail
+/// qcell::explain!(E001);
+/// ```
+///
+/// [`errors`]: errors/index.html
+#[macro_export]
+macro_rules! explain {
+    ($code:ident) => {
+        compile_error!($crate::__qcell_error_text!($code));
+    };
+}
+
+// The text of each error page, shared by `explain!` and the
+// documentation in the `errors` module
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __qcell_error_text {
+    (E001) => {
+        "QCELL-E001: mutable borrow while owner is shared\n\n\
+         A cell's contents are borrowed through its owner, so a \
+         reference returned by `ro` keeps the owner borrowed immutably \
+         for as long as the reference is in use.  Borrowing any cell \
+         mutably with `rw` needs the owner borrowed mutably, so the \
+         compiler reports a conflicting borrow of the owner (E0502), \
+         even though it is the cell's contents that are being \
+         accessed.\n\n\
+         Fix: end the immutable borrow before borrowing mutably, by \
+         copying the value out with `get` or `ro(..).clone()`, or by \
+         moving the `rw` call after the last use of the reference.  \
+         To change a value based on its old value, use `update`."
+    };
+    (E002) => {
+        "QCELL-E002: two mutable borrows through one owner\n\n\
+         Each call to `rw` borrows the owner mutably for as long as the \
+         returned reference is in use, so only one cell can be \
+         borrowed mutably at a time this way, even when the cells are \
+         different (E0499).\n\n\
+         Fix: use `rw2` or `rw3` to borrow two or three cells mutably \
+         at once, which checks at runtime that they are different \
+         cells.  Otherwise finish with one reference before borrowing \
+         the next."
+    };
+    (E003) => {
+        "QCELL-E003: owner passed on while a cell is borrowed\n\n\
+         Passing `&mut owner` to a function needs the owner borrowed \
+         mutably, which conflicts with any reference from `ro` or `rw` \
+         which is still in use (E0499 or E0502).  The function could \
+         otherwise modify the cell that the reference points to.\n\n\
+         Fix: finish with the reference before the call, for example \
+         by doing the whole change inside the function, or by copying \
+         the value out first."
+    };
+    (E004) => {
+        "QCELL-E004: owner used inside a closure that already borrows it\n\n\
+         Methods such as `rw_with` borrow the owner for the duration \
+         of the call, so the closure passed to them can't use the \
+         owner to reach other cells (E0502).\n\n\
+         Fix: read the other cells before the call and pass in the \
+         values, or use `rw2_with` to borrow a second cell.  For \
+         callbacks which need the owner, store them in an `FnCell`, \
+         which passes the owner to the closure when it is called."
+    };
+    (E005) => {
+        "QCELL-E005: cell dropped or moved while borrowed\n\n\
+         A reference from `ro` or `rw` borrows the cell as well as the \
+         owner, so the cell, or the `Rc` holding it, can't be dropped \
+         or moved whilst the reference is in use (E0505).\n\n\
+         Fix: finish with the reference first, or copy out the value.  \
+         To keep a shared cell alive, clone the `Rc` and borrow \
+         through the clone."
+    };
+    (E006) => {
+        "QCELL-E006: owner cloned or copied\n\n\
+         The owner is what proves exclusive access to its cells, so a \
+         second owner with the same ID would allow two mutable borrows \
+         of the same cell.  The owner types don't implement `Clone` or \
+         `Copy` (E0599, or E0382 where an owner is used after being \
+         moved).\n\n\
+         Fix: pass the owner by reference, `&owner` for reading and \
+         `&mut owner` for writing.  To share an owner between threads, \
+         put it in a `Mutex` or `RwLock`.  To create cells without \
+         access to the owner, pass around the `QCellOwnerID` from \
+         `owner.id()`, which is `Copy`."
+    };
+    (E007) => {
+        "QCELL-E007: cell contents not `Send`\n\n\
+         A cell is only `Send` if its contents are, since sending the \
+         cell moves the contents to the other thread.  Keeping a value \
+         in a cell doesn't change this, so for example a cell holding \
+         an `Rc` can't be moved to another thread (E0277).\n\n\
+         Fix: use thread-safe contents, such as `Arc` in place of \
+         `Rc`, or keep the cell on one thread."
+    };
+    (E008) => {
+        "QCELL-E008: cell contents not `Sync`\n\n\
+         Sharing `&QCell<T>` between threads lets each thread borrow \
+         the contents immutably through a shared owner, so the cell is \
+         only `Sync` if `T` is `Send + Sync`.  Otherwise a type with \
+         interior mutability such as `Cell` would allow a data race \
+         (E0277).\n\n\
+         Fix: since changes already go through the owner, a plain \
+         value borrowed with `rw` is usually enough in place of a \
+         `Cell`.  Otherwise use a thread-safe type such as an atomic."
+    };
+    (E009) => {
+        "QCELL-E009: `TLCellOwner` sent to another thread\n\n\
+         `TLCellOwner` allows one owner per marker type per thread, so \
+         an owner moved to another thread could be used alongside that \
+         thread's own owner for the same marker.  It is therefore \
+         neither `Send` nor `Sync` (E0277).\n\n\
+         Fix: create a new owner on the other thread, and send it the \
+         cells, which are `Send` if their contents are.  To move one \
+         owner between threads, use `TCellOwner` or `QCellOwner` \
+         instead."
+    };
+    (E010) => {
+        "QCELL-E010: cell used with the owner of another `LCellOwner` scope\n\n\
+         An `LCell` is tied to one owner by a lifetime, which is fixed \
+         by the first owner that borrows it.  Each call to \
+         `LCellOwner::scope` has its own lifetime, so using the same \
+         cell with the owners from two scopes gives an error about \
+         borrowed data escaping the closure (E0521), rather than a \
+         message about the owner.\n\n\
+         Fix: use one owner for all the cells which are used together, \
+         passing `&owner` or `&mut owner` to inner code rather than \
+         starting a new scope."
+    };
+    (E011) => {
+        "QCELL-E011: owner borrowed by a `match` on cell contents\n\n\
+         Matching on the reference returned by `ro` keeps the owner \
+         borrowed immutably in every arm which uses the bindings from \
+         the pattern, so those arms can't borrow the owner mutably to \
+         change the same cell or any other (E0502).\n\n\
+         Fix: use the owner's `inspect` to match on the contents and \
+         return a decision that doesn't borrow them, then match on the \
+         decision with arms that use `rw`.  For a change to the same \
+         cell based on its contents, use `modify_if`."
+    };
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::PAGES;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn error_pages_match_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/errors");
+        for (code, text) in PAGES {
+            assert!(text.starts_with(&format!("QCELL-{}: ", code)), "{}", code);
+            for ext in ["rs", "stderr"] {
+                assert!(dir.join(format!("{}.{}", code, ext)).is_file(), "{}.{}", code, ext);
+            }
+        }
+        let mut files = 0;
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "rs") {
+                let code = path.file_stem().unwrap().to_str().unwrap();
+                assert!(PAGES.iter().any(|(c, _)| *c == code), "No page for {}", code);
+                files += 1;
+            }
+        }
+        assert_eq!(files, PAGES.len());
+    }
+}
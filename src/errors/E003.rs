@@ -0,0 +1,13 @@
+use qcell::{QCell, QCellOwner};
+
+fn bump(owner: &mut QCellOwner, cell: &QCell<u64>) {
+    owner.update(cell, |n| n + 1);
+}
+
+fn main() {
+    let mut owner = QCellOwner::new();
+    let hits = QCell::new(&owner, 0u64);
+    let count = owner.rw(&hits);
+    bump(&mut owner, &hits); // Compile error
+    *count += 1;
+}
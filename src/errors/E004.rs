@@ -0,0 +1,12 @@
+use qcell::{QCell, QCellOwner};
+
+fn main() {
+    let mut owner = QCellOwner::new();
+    let log = QCell::new(&owner, vec![1, 2]);
+    let other = QCell::new(&owner, 3);
+    let mut buf = Vec::new();
+    owner.rw_with(&log, &mut buf, |log, buf| {
+        buf.push(*owner.ro(&other)); // Compile error
+        buf.append(log);
+    });
+}
@@ -0,0 +1,10 @@
+use qcell::{QCell, QCellOwner};
+use std::rc::Rc;
+
+fn main() {
+    let owner = QCellOwner::new();
+    let cell = Rc::new(QCell::new(&owner, 100u32));
+    let value = owner.ro(&cell);
+    drop(cell); // Compile error
+    println!("{}", *value);
+}
@@ -0,0 +1,7 @@
+use qcell::QCellOwner;
+
+fn main() {
+    let owner1 = QCellOwner::new();
+    let owner2 = owner1.clone(); // Compile error
+    drop((owner1, owner2));
+}
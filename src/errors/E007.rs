@@ -0,0 +1,9 @@
+use qcell::QCell;
+use std::rc::Rc;
+
+fn is_send<T: Send>() {}
+
+fn main() {
+    // A cell can only be sent if its contents can be
+    is_send::<QCell<Rc<()>>>(); // Compile error
+}
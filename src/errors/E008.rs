@@ -0,0 +1,14 @@
+use qcell::{QCell, QCellOwner};
+use std::cell::Cell;
+
+fn main() {
+    let owner = QCellOwner::new();
+    let cell = QCell::new(&owner, Cell::new(100));
+
+    // This would be a data race if the compiler permitted it
+    crossbeam::scope(|s| {
+        s.spawn(|_| owner.ro(&cell).set(200)); // Compile error
+        owner.ro(&cell).set(300);
+    })
+    .unwrap();
+}
@@ -0,0 +1,10 @@
+use qcell::TLCellOwner;
+
+qcell::marker!(struct Marker;);
+
+fn is_send<T: Send>() {}
+
+fn main() {
+    // The owner must stay on the thread which created it
+    is_send::<TLCellOwner<Marker>>(); // Compile error
+}
@@ -0,0 +1,11 @@
+use qcell::{LCell, LCellOwner};
+
+fn main() {
+    LCellOwner::scope(|owner1| {
+        LCellOwner::scope(|owner2| {
+            let cell = LCell::new(100u32);
+            println!("{}", owner1.ro(&cell));
+            println!("{}", owner2.ro(&cell)); // Compile error
+        });
+    });
+}
@@ -0,0 +1,16 @@
+use qcell::{QCell, QCellOwner};
+
+enum Conn {
+    Idle,
+    Retrying(u32),
+}
+
+fn main() {
+    let mut owner = QCellOwner::new();
+    let conn = QCell::new(&owner, Conn::Retrying(3));
+    let log = QCell::new(&owner, Vec::new());
+    match owner.ro(&conn) {
+        Conn::Idle => *owner.rw(&conn) = Conn::Retrying(0),
+        Conn::Retrying(n) => owner.rw(&log).push(format!("retry {}", n)), // Compile error
+    }
+}
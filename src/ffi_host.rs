@@ -0,0 +1,733 @@
+//! Access to host-owned cells from native plugins through a C ABI.
+//!
+//! A plugin loaded through a C ABI can't be given Rust references,
+//! and can't borrow an owner.  An [`FfiHost`] keeps a [`QCellOwner`]
+//! and a registry of cells belonging to it, which are shared with
+//! the plugin as opaque [`CellHandle`] integers.  The plugin accesses
+//! them through the function pointers in a [`QCellHostApi`], each of
+//! which locks the owner for the duration of the call, checks the
+//! handle, the owner and the type of the cell, and reports problems
+//! as a [`QCellStatus`] instead of panicking.
+//!
+//! Plain numbers can be read and written in a single call, with
+//! `ro_u64`, `rw_set_u64` and the like.  For anything else,
+//! `rw_begin` returns a pointer to the contents of the cell, and
+//! keeps the owner locked for that plugin session until `rw_end` is
+//! called, so the host and other sessions can't get at the cell in
+//! the meantime.  `rw_begin` takes a [`TypeTag`], which must match
+//! the [`FfiType::TAG`] of the type the cell was registered with.
+//!
+//! Each plugin gets a [`SessionId`] from [`FfiHost::open_session`],
+//! which it passes to every call.  If a plugin forgets to call
+//! `rw_end`, the host can still get the owner back by closing the
+//! session with [`FfiHost::close_session`], which releases the lock
+//! and returns the handle of the cell that was left open.  The plugin
+//! must not use the pointer after that.
+//!
+//! ```
+//!# use qcell::ffi_host::{CellHandle, FfiHost, QCellHostApi, QCellStatus, SessionId};
+//!# use qcell::QCell;
+//!# use std::sync::Arc;
+//! // The plugin side, using only the C ABI
+//! extern "C" fn plugin_tick(api: &QCellHostApi, session: SessionId, counter: CellHandle) {
+//!     let old = (api.ro_u64)(session, counter);
+//!     assert_eq!(old.status, QCellStatus::Ok);
+//!     assert_eq!((api.rw_set_u64)(session, counter, old.value + 1), QCellStatus::Ok);
+//! }
+//!
+//! let host = FfiHost::new();
+//! let counter = Arc::new(QCell::new(host.owner_id(), 41_u64));
+//! let handle = host.register(counter.clone());
+//! let session = host.open_session();
+//! plugin_tick(&host.api(), session, handle);
+//! assert_eq!(host.close_session(session), None);
+//! assert_eq!(host.with_owner(|owner| *owner.ro(&counter)), 42);
+//! ```
+//!
+//! [`FfiHost`]: struct.FfiHost.html
+//! [`FfiHost::open_session`]: struct.FfiHost.html#method.open_session
+//! [`FfiHost::close_session`]: struct.FfiHost.html#method.close_session
+//! [`QCellOwner`]: ../struct.QCellOwner.html
+//! [`CellHandle`]: struct.CellHandle.html
+//! [`SessionId`]: struct.SessionId.html
+//! [`QCellHostApi`]: struct.QCellHostApi.html
+//! [`QCellStatus`]: enum.QCellStatus.html
+//! [`TypeTag`]: struct.TypeTag.html
+//! [`FfiType::TAG`]: trait.FfiType.html#associatedconstant.TAG
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak};
+
+use once_cell::sync::Lazy;
+
+use crate::{QCell, QCellOwner, QCellOwnerID};
+
+/// Version of the [`QCellHostApi`] layout, which is stored in its
+/// `version` field.  This is increased whenever fields are added.
+///
+/// [`QCellHostApi`]: struct.QCellHostApi.html
+pub const QCELL_HOST_API_VERSION: u32 = 1;
+
+/// Opaque handle to a cell registered with an [`FfiHost`].
+///
+/// [`FfiHost`]: struct.FfiHost.html
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CellHandle(pub u64);
+
+/// Identifies a plugin session with an [`FfiHost`].  Session IDs are
+/// unique across all the hosts in the process, so the session alone
+/// is enough to find the host.
+///
+/// [`FfiHost`]: struct.FfiHost.html
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SessionId(pub u64);
+
+/// Tag for the type of the contents of a cell, agreed between the
+/// host and the plugin.  See [`FfiType`].
+///
+/// [`FfiType`]: trait.FfiType.html
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeTag(pub u64);
+
+/// A type which can be the contents of a cell shared with plugins.
+///
+/// The tag is how `rw_begin` checks that the plugin has the type it
+/// expects.  Tags below 256 are used by the impls for the primitive
+/// number types here.  A type used through `rw_begin` should be
+/// `#[repr(C)]`, or else the plugin can't know its layout.
+pub trait FfiType: Any + Send + Sync {
+    /// The tag for this type, unique among the types registered with
+    /// a host.
+    const TAG: TypeTag;
+}
+
+macro_rules! ffi_type {
+    ($($t:ty => $tag:expr),*) => {
+        $(impl FfiType for $t {
+            const TAG: TypeTag = TypeTag($tag);
+        })*
+    }
+}
+ffi_type!(u8 => 1, u16 => 2, u32 => 3, u64 => 4, i8 => 5, i16 => 6, i32 => 7, i64 => 8, f32 => 9, f64 => 10);
+
+/// Outcome of a call through a [`QCellHostApi`].
+///
+/// [`QCellHostApi`]: struct.QCellHostApi.html
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QCellStatus {
+    /// Success
+    Ok = 0,
+    /// The session is not open
+    InvalidSession = 1,
+    /// No cell is registered with this handle
+    InvalidHandle = 2,
+    /// The cell doesn't contain the type asked for
+    TypeMismatch = 3,
+    /// The cell is between `rw_begin` and `rw_end` in this session,
+    /// under this or another handle, or the session already has a
+    /// cell open with `rw_begin`
+    Busy = 4,
+    /// `rw_end` was called for a cell which isn't open with
+    /// `rw_begin` in this session
+    NotBegun = 5,
+    /// The cell doesn't belong to the host's owner, which happens if
+    /// the host replaced its owner within [`FfiHost::with_owner`]
+    ///
+    /// [`FfiHost::with_owner`]: struct.FfiHost.html#method.with_owner
+    WrongOwner = 6,
+}
+
+/// Result of a call through a [`QCellHostApi`] which returns a
+/// value.  The value is only meaningful if `status` is
+/// `QCellStatus::Ok`, and otherwise is zero or null.
+///
+/// [`QCellHostApi`]: struct.QCellHostApi.html
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QCellResult<T> {
+    /// Whether the call succeeded
+    pub status: QCellStatus,
+    /// The value returned
+    pub value: T,
+}
+
+/// Table of functions for plugins to access the cells of an
+/// [`FfiHost`], obtained from [`FfiHost::api`].
+///
+/// The `ro_*` functions read a number from a cell, and the
+/// `rw_set_*` functions write one.  `rw_begin` gives a pointer to
+/// the contents of a cell, which may be read and written until
+/// `rw_end` is called for the same cell.  Whilst a session has a cell
+/// open, calls from other sessions and from the host wait, and calls
+/// from the same session may access other cells, but not that one.
+/// A session can only have one cell open at a time.
+///
+/// [`FfiHost`]: struct.FfiHost.html
+/// [`FfiHost::api`]: struct.FfiHost.html#method.api
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct QCellHostApi {
+    /// The value of [`QCELL_HOST_API_VERSION`] that the table was
+    /// built for
+    ///
+    /// [`QCELL_HOST_API_VERSION`]: constant.QCELL_HOST_API_VERSION.html
+    pub version: u32,
+    /// Read a `u64` cell
+    pub ro_u64: extern "C" fn(SessionId, CellHandle) -> QCellResult<u64>,
+    /// Read an `i64` cell
+    pub ro_i64: extern "C" fn(SessionId, CellHandle) -> QCellResult<i64>,
+    /// Read an `f64` cell
+    pub ro_f64: extern "C" fn(SessionId, CellHandle) -> QCellResult<f64>,
+    /// Write a `u64` cell
+    pub rw_set_u64: extern "C" fn(SessionId, CellHandle, u64) -> QCellStatus,
+    /// Write an `i64` cell
+    pub rw_set_i64: extern "C" fn(SessionId, CellHandle, i64) -> QCellStatus,
+    /// Write an `f64` cell
+    pub rw_set_f64: extern "C" fn(SessionId, CellHandle, f64) -> QCellStatus,
+    /// Open a cell whose type has the given tag, and return a pointer
+    /// to its contents
+    pub rw_begin: extern "C" fn(SessionId, CellHandle, TypeTag) -> QCellResult<*mut c_void>,
+    /// Close a cell opened with `rw_begin`
+    pub rw_end: extern "C" fn(SessionId, CellHandle) -> QCellStatus,
+}
+
+struct Entry {
+    cell: Arc<QCell<dyn Any + Send + Sync>>,
+    tag: TypeTag,
+}
+
+// A cell opened with `rw_begin`.  The `Arc` keeps the contents alive
+// for the plugin even if the handle is removed in the meantime.
+struct Open {
+    session: SessionId,
+    handle: CellHandle,
+    cell: Arc<QCell<dyn Any + Send + Sync>>,
+}
+
+struct State {
+    owner: QCellOwner,
+    cells: HashMap<CellHandle, Entry>,
+    next_handle: u64,
+    sessions: Vec<SessionId>,
+    open: Option<Open>,
+}
+
+struct Host {
+    state: Mutex<State>,
+    // Notified when `open` is cleared
+    closed: Condvar,
+}
+
+// Sessions of all hosts, so that the C functions can find the host
+// from the session ID alone.  A stale or made-up ID just isn't found.
+static SESSIONS: Lazy<Mutex<HashMap<SessionId, Weak<Host>>>> = Lazy::new(Default::default);
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+// The C functions must not panic, so ignore poisoning.  The state is
+// only changed in ways that leave it consistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Host {
+    // Lock the state once no other session has a cell open.  `None`
+    // means that the session isn't open, or was closed whilst
+    // waiting.
+    fn lock_for(&self, session: SessionId) -> Option<MutexGuard<'_, State>> {
+        let state = lock(&self.state);
+        let state = self
+            .closed
+            .wait_while(state, |s| {
+                s.sessions.contains(&session) && s.open.as_ref().map_or(false, |o| o.session != session)
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        if state.sessions.contains(&session) {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    fn release(&self, state: &mut State) -> Option<CellHandle> {
+        let open = state.open.take()?;
+        self.closed.notify_all();
+        Some(open.handle)
+    }
+}
+
+fn host_of(session: SessionId) -> Option<Arc<Host>> {
+    lock(&SESSIONS).get(&session).and_then(Weak::upgrade)
+}
+
+// Run `f` with the owner and the entry for a cell which is free for
+// this session to access
+fn with_entry<R>(
+    session: SessionId,
+    handle: CellHandle,
+    f: impl FnOnce(&mut State) -> Result<R, QCellStatus>,
+) -> Result<R, QCellStatus> {
+    let host = host_of(session).ok_or(QCellStatus::InvalidSession)?;
+    let mut state = host.lock_for(session).ok_or(QCellStatus::InvalidSession)?;
+    let entry = state.cells.get(&handle).ok_or(QCellStatus::InvalidHandle)?;
+    // Accessing it through the wrong owner would panic, which would
+    // abort the process in an `extern "C"` function
+    if entry.cell.owner_id() != state.owner.id() {
+        return Err(QCellStatus::WrongOwner);
+    }
+    // Compare cells rather than handles, since a cell may be
+    // registered under several handles
+    if let Some(ref open) = state.open {
+        if Arc::as_ptr(&open.cell) as *const u8 == Arc::as_ptr(&entry.cell) as *const u8 {
+            return Err(QCellStatus::Busy);
+        }
+    }
+    f(&mut state)
+}
+
+fn ro<T: FfiType + Copy + Default>(session: SessionId, handle: CellHandle) -> QCellResult<T> {
+    let result = with_entry(session, handle, |state| {
+        state
+            .owner
+            .ro_downcast::<T>(&state.cells[&handle].cell)
+            .copied()
+            .ok_or(QCellStatus::TypeMismatch)
+    });
+    match result {
+        Ok(value) => QCellResult {
+            status: QCellStatus::Ok,
+            value,
+        },
+        Err(status) => QCellResult {
+            status,
+            value: T::default(),
+        },
+    }
+}
+
+fn rw_set<T: FfiType>(session: SessionId, handle: CellHandle, value: T) -> QCellStatus {
+    let result = with_entry(session, handle, |state| {
+        let state = &mut *state;
+        match state.owner.rw_downcast::<T>(&state.cells[&handle].cell) {
+            Some(contents) => {
+                *contents = value;
+                Ok(())
+            }
+            None => Err(QCellStatus::TypeMismatch),
+        }
+    });
+    result.err().unwrap_or(QCellStatus::Ok)
+}
+
+extern "C" fn ro_u64(session: SessionId, handle: CellHandle) -> QCellResult<u64> {
+    ro(session, handle)
+}
+
+extern "C" fn ro_i64(session: SessionId, handle: CellHandle) -> QCellResult<i64> {
+    ro(session, handle)
+}
+
+extern "C" fn ro_f64(session: SessionId, handle: CellHandle) -> QCellResult<f64> {
+    ro(session, handle)
+}
+
+extern "C" fn rw_set_u64(session: SessionId, handle: CellHandle, value: u64) -> QCellStatus {
+    rw_set(session, handle, value)
+}
+
+extern "C" fn rw_set_i64(session: SessionId, handle: CellHandle, value: i64) -> QCellStatus {
+    rw_set(session, handle, value)
+}
+
+extern "C" fn rw_set_f64(session: SessionId, handle: CellHandle, value: f64) -> QCellStatus {
+    rw_set(session, handle, value)
+}
+
+extern "C" fn rw_begin(
+    session: SessionId,
+    handle: CellHandle,
+    tag: TypeTag,
+) -> QCellResult<*mut c_void> {
+    let result = with_entry(session, handle, |state| {
+        if state.open.is_some() {
+            return Err(QCellStatus::Busy);
+        }
+        let state = &mut *state;
+        let entry = &state.cells[&handle];
+        if entry.tag != tag {
+            return Err(QCellStatus::TypeMismatch);
+        }
+        let ptr = state.owner.rw(&*entry.cell) as *mut dyn Any as *mut c_void;
+        state.open = Some(Open {
+            session,
+            handle,
+            cell: entry.cell.clone(),
+        });
+        Ok(ptr)
+    });
+    match result {
+        Ok(value) => QCellResult {
+            status: QCellStatus::Ok,
+            value,
+        },
+        Err(status) => QCellResult {
+            status,
+            value: std::ptr::null_mut(),
+        },
+    }
+}
+
+extern "C" fn rw_end(session: SessionId, handle: CellHandle) -> QCellStatus {
+    let host = match host_of(session) {
+        Some(host) => host,
+        None => return QCellStatus::InvalidSession,
+    };
+    let mut state = match host.lock_for(session) {
+        Some(state) => state,
+        None => return QCellStatus::InvalidSession,
+    };
+    match state.open {
+        Some(ref open) if open.session == session && open.handle == handle => {
+            host.release(&mut state);
+            QCellStatus::Ok
+        }
+        _ => QCellStatus::NotBegun,
+    }
+}
+
+/// Host side of sharing cells with native plugins.  See the [module
+/// documentation](index.html).
+///
+/// The host has its own [`QCellOwner`], and cells registered with it
+/// must belong to that owner.  The host accesses them through
+/// [`FfiHost::with_owner`].
+///
+/// [`QCellOwner`]: ../struct.QCellOwner.html
+/// [`FfiHost::with_owner`]: #method.with_owner
+pub struct FfiHost {
+    host: Arc<Host>,
+}
+
+impl FfiHost {
+    /// Create a host with a new owner
+    pub fn new() -> Self {
+        Self {
+            host: Arc::new(Host {
+                state: Mutex::new(State {
+                    owner: QCellOwner::new(),
+                    cells: HashMap::new(),
+                    next_handle: 1,
+                    sessions: Vec::new(),
+                    open: None,
+                }),
+                closed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Get the ID of the host's owner, for creating cells to register
+    pub fn owner_id(&self) -> QCellOwnerID {
+        lock(&self.host.state).owner.id()
+    }
+
+    /// Get the table of functions to hand to plugins
+    pub fn api(&self) -> QCellHostApi {
+        QCellHostApi {
+            version: QCELL_HOST_API_VERSION,
+            ro_u64,
+            ro_i64,
+            ro_f64,
+            rw_set_u64,
+            rw_set_i64,
+            rw_set_f64,
+            rw_begin,
+            rw_end,
+        }
+    }
+
+    /// Register a cell for access by plugins, returning its handle.
+    /// The same cell may be registered more than once, with different
+    /// handles.  Whilst it is open with `rw_begin` under one handle,
+    /// access under any of its handles fails with
+    /// `QCellStatus::Busy`.
+    ///
+    /// Panics if the cell doesn't belong to the host's owner.
+    pub fn register<T: FfiType>(&self, cell: Arc<QCell<T>>) -> CellHandle {
+        let mut state = self.lock();
+        let _ = state.owner.ro(&*cell);
+        let handle = CellHandle(state.next_handle);
+        state.next_handle += 1;
+        state.cells.insert(
+            handle,
+            Entry {
+                cell,
+                tag: T::TAG,
+            },
+        );
+        handle
+    }
+
+    /// Remove a cell from the registry, so that plugins can't access
+    /// it any more.  Returns false if there was no such handle.  If a
+    /// plugin has the cell open with `rw_begin`, its pointer stays
+    /// valid until `rw_end`.
+    pub fn unregister(&self, handle: CellHandle) -> bool {
+        self.lock().cells.remove(&handle).is_some()
+    }
+
+    /// Access the host's owner.  This waits until no plugin session
+    /// has a cell open with `rw_begin`, so it deadlocks if called on
+    /// the thread of a plugin which has one open.
+    ///
+    /// If `f` replaces the owner, for example with `mem::replace`,
+    /// then the cells already registered no longer belong to it, and
+    /// plugin calls on them fail with `QCellStatus::WrongOwner`.
+    pub fn with_owner<R>(&self, f: impl FnOnce(&mut QCellOwner) -> R) -> R {
+        f(&mut self.lock().owner)
+    }
+
+    /// Start a session for a plugin
+    pub fn open_session(&self) -> SessionId {
+        let session = SessionId(NEXT_SESSION.fetch_add(1, Ordering::Relaxed));
+        lock(&self.host.state).sessions.push(session);
+        lock(&SESSIONS).insert(session, Arc::downgrade(&self.host));
+        session
+    }
+
+    /// End a plugin session, after which calls with that session ID
+    /// fail with `QCellStatus::InvalidSession`.
+    ///
+    /// If the session still has a cell open with `rw_begin`, the
+    /// cell is released and its handle is returned.  The plugin must
+    /// not use the pointer it got from `rw_begin` after this.
+    /// Closing a session which isn't open does nothing and returns
+    /// `None`.
+    pub fn close_session(&self, session: SessionId) -> Option<CellHandle> {
+        lock(&SESSIONS).remove(&session);
+        let mut state = lock(&self.host.state);
+        state.sessions.retain(|s| *s != session);
+        // Waiters for this session's calls find it closed
+        self.host.closed.notify_all();
+        if state.open.as_ref()?.session != session {
+            return None;
+        }
+        self.host.release(&mut state)
+    }
+
+    // Lock the state once no plugin session has a cell open
+    fn lock(&self) -> MutexGuard<'_, State> {
+        let state = lock(&self.host.state);
+        self.host
+            .closed
+            .wait_while(state, |s| s.open.is_some())
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for FfiHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FfiHost {
+    fn drop(&mut self) {
+        let state = lock(&self.host.state);
+        let mut sessions = lock(&SESSIONS);
+        for session in &state.sessions {
+            sessions.remove(session);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{CellHandle, FfiHost, FfiType, QCellHostApi, QCellStatus, SessionId, TypeTag};
+    use crate::QCell;
+    use std::ffi::c_void;
+    use std::sync::Arc;
+
+    #[repr(C)]
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    impl FfiType for Point {
+        const TAG: TypeTag = TypeTag(1000);
+    }
+
+    // A plugin only sees the table, the session and the handles
+    extern "C" fn plugin_move(api: &QCellHostApi, session: SessionId, point: CellHandle) -> bool {
+        let result = (api.rw_begin)(session, point, TypeTag(1000));
+        if result.status != QCellStatus::Ok {
+            return false;
+        }
+        let ptr = result.value as *mut i32;
+        unsafe {
+            *ptr += 1;
+            *ptr.add(1) *= 2;
+        }
+        (api.rw_end)(session, point) == QCellStatus::Ok
+    }
+
+    #[test]
+    fn plugin_read_write() {
+        let host = FfiHost::new();
+        let api = host.api();
+        let count = Arc::new(QCell::new(host.owner_id(), 5_u64));
+        let offset = Arc::new(QCell::new(host.owner_id(), -3_i64));
+        let scale = Arc::new(QCell::new(host.owner_id(), 0.5_f64));
+        let point = Arc::new(QCell::new(host.owner_id(), Point { x: 1, y: 2 }));
+        let (h_count, h_offset, h_scale, h_point) = (
+            host.register(count.clone()),
+            host.register(offset.clone()),
+            host.register(scale.clone()),
+            host.register(point.clone()),
+        );
+        let session = host.open_session();
+
+        let read = (api.ro_u64)(session, h_count);
+        assert_eq!(read.status, QCellStatus::Ok);
+        assert_eq!((api.rw_set_u64)(session, h_count, read.value * 2), QCellStatus::Ok);
+        assert_eq!((api.rw_set_i64)(session, h_offset, 7), QCellStatus::Ok);
+        assert_eq!((api.ro_i64)(session, h_offset).value, 7);
+        assert_eq!((api.ro_f64)(session, h_scale).value, 0.5);
+        assert_eq!((api.rw_set_f64)(session, h_scale, 1.5), QCellStatus::Ok);
+        assert!(plugin_move(&api, session, h_point));
+
+        assert_eq!(host.close_session(session), None);
+        host.with_owner(|owner| {
+            assert_eq!(*owner.ro(&count), 10);
+            assert_eq!(*owner.ro(&offset), 7);
+            assert_eq!(*owner.ro(&scale), 1.5);
+            assert_eq!(*owner.ro(&point), Point { x: 2, y: 4 });
+        });
+    }
+
+    #[test]
+    fn plugin_errors() {
+        let host = FfiHost::new();
+        let api = host.api();
+        let count = Arc::new(QCell::new(host.owner_id(), 5_u64));
+        let h_count = host.register(count);
+        let session = host.open_session();
+
+        // Wrong types
+        let read = (api.ro_i64)(session, h_count);
+        assert_eq!((read.status, read.value), (QCellStatus::TypeMismatch, 0));
+        assert_eq!((api.rw_set_f64)(session, h_count, 1.0), QCellStatus::TypeMismatch);
+        let begin = (api.rw_begin)(session, h_count, Point::TAG);
+        assert_eq!(begin.status, QCellStatus::TypeMismatch);
+        assert!(begin.value.is_null());
+
+        // Unknown handles and sessions
+        assert_eq!((api.ro_u64)(session, CellHandle(999)).status, QCellStatus::InvalidHandle);
+        assert_eq!((api.ro_u64)(SessionId(0), h_count).status, QCellStatus::InvalidSession);
+        assert_eq!((api.rw_end)(session, h_count), QCellStatus::NotBegun);
+        assert!(host.unregister(h_count));
+        assert_eq!((api.ro_u64)(session, h_count).status, QCellStatus::InvalidHandle);
+        host.close_session(session);
+        assert_eq!((api.rw_end)(session, h_count), QCellStatus::InvalidSession);
+    }
+
+    #[test]
+    fn plugin_begin_excludes() {
+        let host = FfiHost::new();
+        let api = host.api();
+        let a = host.register(Arc::new(QCell::new(host.owner_id(), 1_u64)));
+        let b = host.register(Arc::new(QCell::new(host.owner_id(), 2_u64)));
+        let session = host.open_session();
+        let begin = (api.rw_begin)(session, a, u64::TAG);
+        assert_eq!(begin.status, QCellStatus::Ok);
+
+        // The open cell can't be used any other way, and no other
+        // cell can be opened, but other cells can be used
+        assert_eq!((api.ro_u64)(session, a).status, QCellStatus::Busy);
+        assert_eq!((api.rw_begin)(session, b, u64::TAG).status, QCellStatus::Busy);
+        assert_eq!((api.rw_end)(session, b), QCellStatus::NotBegun);
+        assert_eq!((api.ro_u64)(session, b).value, 2);
+
+        // Another session waits until `rw_end`
+        let other = host.open_session();
+        let waiter = std::thread::spawn(move || (api.ro_u64)(other, a).value);
+        unsafe { *(begin.value as *mut u64) = 10 };
+        assert_eq!((api.rw_end)(session, a), QCellStatus::Ok);
+        assert_eq!(waiter.join().unwrap(), 10);
+    }
+
+    #[test]
+    fn plugin_begin_excludes_other_handles() {
+        let host = FfiHost::new();
+        let api = host.api();
+        let cell = Arc::new(QCell::new(host.owner_id(), 1_u64));
+        let h1 = host.register(cell.clone());
+        let h2 = host.register(cell);
+        let session = host.open_session();
+        let begin = (api.rw_begin)(session, h1, u64::TAG);
+        assert_eq!(begin.status, QCellStatus::Ok);
+
+        // The same cell under its other handle is also busy
+        assert_eq!((api.ro_u64)(session, h2).status, QCellStatus::Busy);
+        assert_eq!((api.rw_set_u64)(session, h2, 5), QCellStatus::Busy);
+        assert_eq!((api.rw_end)(session, h2), QCellStatus::NotBegun);
+        assert_eq!((api.rw_end)(session, h1), QCellStatus::Ok);
+        assert_eq!((api.ro_u64)(session, h2).value, 1);
+    }
+
+    #[test]
+    fn plugin_after_owner_replaced() {
+        let host = FfiHost::new();
+        let api = host.api();
+        let cell = Arc::new(QCell::new(host.owner_id(), 1_u64));
+        let handle = host.register(cell.clone());
+        let session = host.open_session();
+        // The host swaps a new owner in
+        let old = host.with_owner(std::mem::take);
+
+        // The cell belongs to the old owner, so every call reports it
+        // instead of panicking across the C ABI
+        assert_eq!((api.ro_u64)(session, handle).status, QCellStatus::WrongOwner);
+        assert_eq!((api.rw_set_u64)(session, handle, 2), QCellStatus::WrongOwner);
+        let begin = (api.rw_begin)(session, handle, u64::TAG);
+        assert_eq!(begin.status, QCellStatus::WrongOwner);
+        assert!(begin.value.is_null());
+        assert_eq!((api.rw_end)(session, handle), QCellStatus::NotBegun);
+        assert_eq!(*old.ro(&cell), 1);
+
+        // Cells of the new owner still work
+        let h2 = host.register(Arc::new(QCell::new(host.owner_id(), 3_u64)));
+        assert_eq!((api.ro_u64)(session, h2).value, 3);
+    }
+
+    #[test]
+    fn leaked_begin_released_on_close() {
+        let host = FfiHost::new();
+        let api = host.api();
+        let cell = Arc::new(QCell::new(host.owner_id(), 1_u64));
+        let handle = host.register(cell.clone());
+        let session = host.open_session();
+
+        // The plugin forgets to call `rw_end`
+        let begin = (api.rw_begin)(session, handle, u64::TAG);
+        let ptr: *mut c_void = begin.value;
+        unsafe { *(ptr as *mut u64) = 2 };
+
+        assert_eq!(host.close_session(session), Some(handle));
+        assert_eq!(host.close_session(session), None);
+        assert_eq!(host.with_owner(|owner| *owner.ro(&cell)), 2);
+        assert_eq!((api.rw_end)(session, handle), QCellStatus::InvalidSession);
+
+        // Dropping the host ends its sessions
+        let session = host.open_session();
+        drop(host);
+        assert_eq!((api.ro_u64)(session, handle).status, QCellStatus::InvalidSession);
+    }
+}
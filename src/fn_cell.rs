@@ -0,0 +1,294 @@
+use alloc::boxed::Box;
+use core::cell::Cell;
+use core::fmt;
+
+use crate::{
+    panic_messages, OwnerMut, QCell, QCellBorrow, QCellOwner, QCellOwnerID, QCellOwnerSeq,
+};
+
+type BoxFn<A, R> = Box<dyn FnMut(OwnerMut<'_>, A) -> R>;
+
+/// [`QCell`] holding a closure which may be called with the owner
+/// borrowed mutably, including access to the cell's own owner.
+///
+/// Callbacks are often stored as `Rc<QCell<Box<dyn FnMut(..)>>>`, but
+/// calling one with `owner.rw(&cb)(..)` keeps the owner borrowed for
+/// the whole call, so the callback can't access any other cells.
+/// `FnCell` handles this by moving the closure out of the cell for
+/// the duration of the call, so that the owner can be passed to the
+/// closure as an [`OwnerMut`], and then moving it back in again
+/// afterwards.  Call it with `call` on a [`QCellOwner`] or
+/// [`QCellOwnerSeq`], or with [`OwnerMut::call`] for any other
+/// owner.
+///
+/// Since the closure isn't in the cell whilst it runs, the cell has
+/// these states:
+///
+/// - **Ready**: holding its closure, ready to be called
+///
+/// - **Running**: the closure has been moved out and is running.
+///   Calling the same `FnCell` again from within the closure panics,
+///   since there is no closure to call, and unless that panic is
+///   caught within the closure, it poisons the cell.  Use a different
+///   `FnCell` for recursive callbacks.
+///
+/// - **Poisoned**: the closure panicked, and was dropped during the
+///   unwind.  Calling the cell panics.  [`FnCell::replace`] stores a
+///   new closure and makes the cell ready again.
+///
+/// [`FnCell::replace`] may also be called whilst the cell is running,
+/// for example by the closure itself.  In that case, the running
+/// closure is dropped when it returns, and the new one is kept.
+///
+/// ```
+///# use qcell::{FnCell, QCellOwner};
+///# use std::rc::Rc;
+/// let mut owner = QCellOwner::new();
+/// let total = Rc::new(owner.cell(0));
+/// let on_event: FnCell<i32, i32> = FnCell::new(&owner, {
+///     let total = total.clone();
+///     move |mut owner, amount| {
+///         // The owner is available to access other cells
+///         *owner.rw(&total) += amount;
+///         *owner.ro(&total)
+///     }
+/// });
+/// assert_eq!(owner.call(&on_event, 5), 5);
+/// assert_eq!(owner.call(&on_event, 2), 7);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`OwnerMut`]: enum.OwnerMut.html
+/// [`OwnerMut::call`]: enum.OwnerMut.html#method.call
+/// [`FnCell::replace`]: struct.FnCell.html#method.replace
+pub struct FnCell<A, R = ()> {
+    // `None` when running or poisoned
+    cell: QCell<Option<BoxFn<A, R>>>,
+    running: Cell<bool>,
+}
+
+// Clears the running flag when the call ends, even on a panic
+struct Running<'a>(&'a Cell<bool>);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+impl<A, R> FnCell<A, R> {
+    /// Create a new cell owned by the given owner ID, holding the
+    /// closure `f`
+    pub fn new(id: impl Into<QCellOwnerID>, f: impl FnMut(OwnerMut<'_>, A) -> R + 'static) -> Self {
+        Self {
+            cell: QCell::new(id, Some(Box::new(f))),
+            running: Cell::new(false),
+        }
+    }
+
+    /// Replace the closure with `f`, dropping the old one, which
+    /// makes a poisoned cell ready again.  If the cell is running,
+    /// the running closure is dropped when it returns.  Panics if the
+    /// cell is not owned by this owner.
+    pub fn replace<O: QCellBorrow>(
+        &self,
+        owner: &mut O,
+        f: impl FnMut(OwnerMut<'_>, A) -> R + 'static,
+    ) {
+        *owner.qcell_rw(&self.cell) = Some(Box::new(f));
+    }
+
+    /// Test whether the closure is currently running
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.running.get()
+    }
+
+    /// Test whether the cell is poisoned, because the closure panicked
+    /// and hasn't been replaced since.  Panics if the cell is not
+    /// owned by this owner.
+    #[inline]
+    pub fn is_poisoned<O: QCellBorrow>(&self, owner: &O) -> bool {
+        owner.qcell_ro(&self.cell).is_none() && !self.is_running()
+    }
+
+    fn call_with(&self, mut owner: OwnerMut<'_>, args: A) -> R {
+        if self.running.get() {
+            panic!("{}", panic_messages::FN_CELL_REENTERED);
+        }
+        let mut f = match owner.rw(&self.cell).take() {
+            Some(f) => f,
+            None => panic!("{}", panic_messages::FN_CELL_POISONED),
+        };
+        self.running.set(true);
+        let _running = Running(&self.running);
+        let result = f(owner.reborrow(), args);
+        let slot = owner.rw(&self.cell);
+        if slot.is_none() {
+            *slot = Some(f);
+        }
+        result
+    }
+}
+
+impl<A, R> fmt::Debug for FnCell<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnCell")
+            .field("running", &self.running.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> OwnerMut<'a> {
+    /// Call the closure in an [`FnCell`] with `args`, passing it this
+    /// owner.  Panics if the cell is running or poisoned, or is not
+    /// owned by this owner.  If the closure panics, the cell is left
+    /// poisoned.
+    ///
+    /// [`FnCell`]: struct.FnCell.html
+    pub fn call<A, R>(&mut self, cell: &FnCell<A, R>, args: A) -> R {
+        cell.call_with(self.reborrow(), args)
+    }
+}
+
+impl QCellOwner {
+    /// Call the closure in an [`FnCell`] with `args`, passing it this
+    /// owner.  See [`OwnerMut::call`].
+    ///
+    /// [`FnCell`]: struct.FnCell.html
+    /// [`OwnerMut::call`]: enum.OwnerMut.html#method.call
+    pub fn call<A, R>(&mut self, cell: &FnCell<A, R>, args: A) -> R {
+        cell.call_with(self.into(), args)
+    }
+}
+
+impl QCellOwnerSeq {
+    /// Call the closure in an [`FnCell`] with `args`, passing it this
+    /// owner.  See [`OwnerMut::call`].
+    ///
+    /// [`FnCell`]: struct.FnCell.html
+    /// [`OwnerMut::call`]: enum.OwnerMut.html#method.call
+    pub fn call<A, R>(&mut self, cell: &FnCell<A, R>, args: A) -> R {
+        cell.call_with(self.into(), args)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::panic_messages::tests::panic_message;
+    use crate::panic_messages::{FN_CELL_POISONED, FN_CELL_REENTERED};
+    use crate::{FnCell, OwnerMut, QCellOwner, QCellOwnerPinned};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    #[test]
+    fn fn_cell_callback_borrows_other_cells() {
+        let mut owner = QCellOwner::new();
+        let log = Rc::new(owner.cell(Vec::new()));
+        let mut registry: Vec<FnCell<&str>> = Vec::new();
+        for n in 0..3 {
+            let log = log.clone();
+            let count = owner.cell(0);
+            registry.push(FnCell::new(&owner, move |mut owner, event| {
+                *owner.rw(&count) += 1;
+                let entry = format!("{}{}{}", n, event, owner.ro(&count));
+                owner.rw(&log).push(entry);
+            }));
+        }
+        for event in ["a", "b"] {
+            for cb in &registry {
+                owner.call(cb, event);
+            }
+        }
+        assert_eq!(*owner.ro(&log), ["0a1", "1a1", "2a1", "0b2", "1b2", "2b2"]);
+    }
+
+    #[test]
+    fn fn_cell_nested_calls() {
+        let mut owner = QCellOwner::new();
+        let inner: Rc<FnCell<u32, u32>> = Rc::new(FnCell::new(&owner, |_, x| x * 2));
+        let outer: FnCell<u32, u32> = FnCell::new(&owner, {
+            let inner = inner.clone();
+            move |mut owner, x| owner.call(&inner, x) + 1
+        });
+        assert_eq!(owner.call(&outer, 20), 41);
+        assert!(!outer.is_running());
+    }
+
+    #[test]
+    fn fn_cell_reentrant_call_panics() {
+        let mut owner = QCellOwner::new();
+        let cell: Rc<FnCell<u32>> = Rc::new(FnCell::new(&owner, |_, _| ()));
+        let weak = Rc::downgrade(&cell);
+        cell.replace(&mut owner, move |mut owner, depth| {
+            if depth == 0 {
+                owner.call(&weak.upgrade().unwrap(), 1);
+            }
+        });
+        let msg = panic_message(|| owner.call(&cell, 0));
+        assert_eq!(msg, FN_CELL_REENTERED);
+        // The panic unwound through the outer call too
+        assert!(cell.is_poisoned(&owner));
+        assert!(!cell.is_running());
+    }
+
+    #[test]
+    fn fn_cell_panic_poisons_and_replace_recovers() {
+        let mut owner = QCellOwner::new();
+        let calls = Rc::new(owner.cell(0));
+        let cell: FnCell<bool> = FnCell::new(&owner, {
+            let calls = calls.clone();
+            move |mut owner, fail: bool| {
+                *owner.rw(&calls) += 1;
+                assert!(!fail, "callback failed");
+            }
+        });
+        owner.call(&cell, false);
+        let result = catch_unwind(AssertUnwindSafe(|| owner.call(&cell, true)));
+        assert!(result.is_err());
+        assert!(cell.is_poisoned(&owner));
+        assert_eq!(owner.get(&calls), 2);
+
+        let msg = panic_message(|| owner.call(&cell, false));
+        assert_eq!(msg, FN_CELL_POISONED);
+        assert_eq!(owner.get(&calls), 2);
+
+        cell.replace(&mut owner, {
+            let calls = calls.clone();
+            move |mut owner, _| *owner.rw(&calls) += 10
+        });
+        assert!(!cell.is_poisoned(&owner));
+        owner.call(&cell, true);
+        owner.call(&cell, true);
+        assert_eq!(owner.get(&calls), 22);
+    }
+
+    #[test]
+    fn fn_cell_replace_while_running() {
+        let mut owner = QCellOwner::new();
+        let cell: Rc<FnCell<(), u32>> = Rc::new(FnCell::new(&owner, |_, _| 0));
+        let weak = Rc::downgrade(&cell);
+        cell.replace(&mut owner, move |mut owner, _| {
+            let cell = weak.upgrade().unwrap();
+            assert!(cell.is_running());
+            cell.replace(&mut owner, |_, _| 2);
+            1
+        });
+        assert_eq!(owner.call(&cell, ()), 1);
+        assert_eq!(owner.call(&cell, ()), 2);
+    }
+
+    #[test]
+    fn fn_cell_pinned_owner() {
+        let mut owner = Box::pin(QCellOwnerPinned::new());
+        let value = Rc::new(owner.as_ref().cell(1));
+        let cell: FnCell<u32> = FnCell::new(owner.as_ref(), {
+            let value = value.clone();
+            move |mut owner, x| *owner.rw(&value) += x
+        });
+        OwnerMut::from(owner.as_mut()).call(&cell, 2);
+        assert_eq!(owner.as_ref().get(&value), 3);
+    }
+}
@@ -0,0 +1,346 @@
+//! Wrappers with the API of the [**ghost-cell**] crate, to ease
+//! migration from `GhostCell` to [`LCell`].
+//!
+//! [`LCell`] works in the same way as `GhostCell`, with the cells
+//! branded by an invariant lifetime, but the naming is different, and
+//! access is usually through the owner rather than the cell.  Code
+//! written for **ghost-cell** can be moved over by changing just the
+//! crate path in its imports, from `ghost_cell::` to
+//! `qcell::ghost_compat::`.  [`GhostToken`] wraps an [`LCellOwner`]
+//! and [`GhostCell`] wraps an [`LCell`], with the methods forwarding
+//! to the **qcell** ones, so there is no cost over using **qcell**
+//! directly.
+//!
+//! After that, the code can be converted to the **qcell** names one
+//! piece at a time.  [`GhostToken::owner`] and [`GhostCell::inner`]
+//! give access to the underlying types, so converted and unconverted
+//! code can share the same cells.  The renames are:
+//!
+//! | **ghost-cell**                       | **qcell**                                |
+//! |--------------------------------------|------------------------------------------|
+//! | `GhostToken<'brand>`                 | `LCellOwner<'id>`                        |
+//! | `GhostCell<'brand, T>`               | `LCell<'id, T>`                          |
+//! | `GhostToken::new(\|token\| ...)`       | `LCellOwner::scope(\|owner\| ...)`         |
+//! | `cell.borrow(&token)`                | `owner.ro(&cell)` or `cell.ro(&owner)`   |
+//! | `cell.borrow_mut(&mut token)`        | `owner.rw(&cell)` or `cell.rw(&mut owner)` |
+//! | `cell.replace(value, &mut token)`    | `cell.replace(value, &mut owner)`        |
+//! | `cell.take(&mut token)`              | `cell.take(&mut owner)`                  |
+//! | `GhostCell::from_mut(value)`         | `LCell::from_mut(value)`                 |
+//! | `cell.as_slice_of_cells()`           | `cell.as_slice_of_cells()`               |
+//!
+//! Unlike `GhostToken::new`, `LCellOwner::scope` doesn't return the
+//! closure's result.  Where that is needed, store it in a variable
+//! outside the closure, or use `LCellOwner::scope_build`.  **qcell**
+//! also offers `rw2` and `rw3` to borrow several cells mutably at
+//! once with a runtime check, in place of **ghost-cell**'s
+//! `GhostBorrowMut` trait, which has no wrapper here.
+//!
+//! ```
+//!# use qcell::ghost_compat::{GhostCell, GhostToken};
+//! let sum = GhostToken::new(|mut token| {
+//!     let a = GhostCell::new(1);
+//!     let b = GhostCell::new(2);
+//!     *a.borrow_mut(&mut token) += *b.borrow(&token);
+//!     a.replace(10, &mut token) + b.take(&mut token)
+//! });
+//! assert_eq!(sum, 5);
+//! ```
+//!
+//! [**ghost-cell**]: https://crates.io/crates/ghost-cell
+//! [`LCell`]: ../struct.LCell.html
+//! [`LCellOwner`]: ../struct.LCellOwner.html
+//! [`GhostToken`]: struct.GhostToken.html
+//! [`GhostCell`]: struct.GhostCell.html
+//! [`GhostToken::owner`]: struct.GhostToken.html#method.owner
+//! [`GhostCell::inner`]: struct.GhostCell.html#method.inner
+
+use crate::{sound, LCell, LCellOwner};
+
+/// Token giving access to the [`GhostCell`] instances of its brand,
+/// with the API of `ghost_cell::GhostToken`.  Wraps an
+/// [`LCellOwner`].
+///
+/// [`GhostCell`]: struct.GhostCell.html
+/// [`LCellOwner`]: ../struct.LCellOwner.html
+pub struct GhostToken<'brand>(LCellOwner<'brand>);
+
+impl<'brand> GhostToken<'brand> {
+    /// Call `fun` with a token of a new brand, and return its
+    /// result.  Same as `LCellOwner::scope`, apart from passing the
+    /// result back.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<R, F>(fun: F) -> R
+    where
+        F: for<'new_brand> FnOnce(GhostToken<'new_brand>) -> R,
+    {
+        let mut result = None;
+        LCellOwner::scope(|owner| result = Some(fun(GhostToken(owner))));
+        match result {
+            Some(result) => result,
+            None => unreachable!(),
+        }
+    }
+
+    /// Get the underlying owner, for code that has been converted to
+    /// the **qcell** API
+    #[inline]
+    pub fn owner(&mut self) -> &mut LCellOwner<'brand> {
+        &mut self.0
+    }
+}
+
+/// Cell whose contents are accessed through a [`GhostToken`] of the
+/// same brand, with the API of `ghost_cell::GhostCell`.  Wraps an
+/// [`LCell`], with the same layout.
+///
+/// [`GhostToken`]: struct.GhostToken.html
+/// [`LCell`]: ../struct.LCell.html
+#[repr(transparent)]
+pub struct GhostCell<'brand, T: ?Sized>(LCell<'brand, T>);
+
+impl<'brand, T> GhostCell<'brand, T> {
+    /// Create a new cell containing `value`
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Self(LCell::new(value))
+    }
+
+    /// Destroy the cell and return the contained value
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+
+    /// Replace the contents with `value`, and return the old contents
+    #[inline]
+    pub fn replace(&self, value: T, token: &mut GhostToken<'brand>) -> T {
+        self.0.replace(value, &mut token.0)
+    }
+
+    /// Take the contents, leaving `T::default()` in their place
+    #[inline]
+    pub fn take(&self, token: &mut GhostToken<'brand>) -> T
+    where
+        T: Default,
+    {
+        self.0.take(&mut token.0)
+    }
+}
+
+impl<'brand, T: ?Sized> GhostCell<'brand, T> {
+    /// Borrow the contents immutably, for as long as the token is
+    /// borrowed.  Same as [`LCell::ro`].
+    ///
+    /// [`LCell::ro`]: ../struct.LCell.html#method.ro
+    #[inline]
+    pub fn borrow<'a>(&'a self, token: &'a GhostToken<'brand>) -> &'a T {
+        self.0.ro(&token.0)
+    }
+
+    /// Borrow the contents mutably, for as long as the token is
+    /// borrowed.  Same as [`LCell::rw`].
+    ///
+    /// [`LCell::rw`]: ../struct.LCell.html#method.rw
+    #[inline]
+    pub fn borrow_mut<'a>(&'a self, token: &'a mut GhostToken<'brand>) -> &'a mut T {
+        self.0.rw(&mut token.0)
+    }
+
+    /// Get a raw pointer to the contents
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T {
+        self.0.as_ptr()
+    }
+
+    /// Borrow the contents mutably through a unique borrow of the
+    /// cell, without needing the token
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+
+    /// Convert a mutable reference to a value into a mutable
+    /// reference to a cell containing it
+    #[inline]
+    pub fn from_mut(value: &mut T) -> &mut Self {
+        sound::ghost_cell_from_lcell(LCell::from_mut(value))
+    }
+
+    /// Get the underlying cell, for code that has been converted to
+    /// the **qcell** API
+    #[inline]
+    pub fn inner(&self) -> &LCell<'brand, T> {
+        &self.0
+    }
+}
+
+impl<'brand, T> GhostCell<'brand, [T]> {
+    /// Access a cell containing a slice as a slice of cells, one per
+    /// element
+    #[inline]
+    pub fn as_slice_of_cells(&self) -> &[GhostCell<'brand, T>] {
+        sound::ghost_cells_from_lcells(self.0.as_slice_of_cells())
+    }
+}
+
+impl<'brand, T: Default> Default for GhostCell<'brand, T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<'brand, T> From<T> for GhostCell<'brand, T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    // Ported from typical `ghost_cell` code, with only the crate path
+    // changed
+    use crate::ghost_compat::{GhostCell, GhostToken};
+    use std::rc::{Rc, Weak};
+
+    // The classic branded doubly-linked list
+    struct Node<'id, T> {
+        value: T,
+        prev: Option<Weak<GhostCell<'id, Node<'id, T>>>>,
+        next: Option<Rc<GhostCell<'id, Node<'id, T>>>>,
+    }
+
+    type NodePtr<'id, T> = Rc<GhostCell<'id, Node<'id, T>>>;
+
+    fn new_node<'id, T>(value: T) -> NodePtr<'id, T> {
+        Rc::new(GhostCell::new(Node {
+            value,
+            prev: None,
+            next: None,
+        }))
+    }
+
+    // Insert `node2` after `node1`
+    fn insert_next<'id, T>(
+        node1: &NodePtr<'id, T>,
+        node2: NodePtr<'id, T>,
+        token: &mut GhostToken<'id>,
+    ) {
+        let next = node1.borrow_mut(token).next.take();
+        if let Some(next) = &next {
+            next.borrow_mut(token).prev = Some(Rc::downgrade(&node2));
+        }
+        let node2_mut = node2.borrow_mut(token);
+        node2_mut.prev = Some(Rc::downgrade(node1));
+        node2_mut.next = next;
+        node1.borrow_mut(token).next = Some(node2);
+    }
+
+    // Unlink `node` from the list, and join up its neighbours
+    fn remove<'id, T>(node: &NodePtr<'id, T>, token: &mut GhostToken<'id>) {
+        let node_mut = node.borrow_mut(token);
+        let prev = node_mut.prev.take().and_then(|p| p.upgrade());
+        let next = node_mut.next.take();
+        if let Some(next) = &next {
+            next.borrow_mut(token).prev = prev.as_ref().map(Rc::downgrade);
+        }
+        if let Some(prev) = &prev {
+            prev.borrow_mut(token).next = next;
+        }
+    }
+
+    fn collect<'id, T: Clone>(head: &NodePtr<'id, T>, token: &GhostToken<'id>) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut node = Some(head.clone());
+        while let Some(n) = node {
+            out.push(n.borrow(token).value.clone());
+            node = n.borrow(token).next.clone();
+        }
+        out
+    }
+
+    fn collect_back<'id, T: Clone>(tail: &NodePtr<'id, T>, token: &GhostToken<'id>) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut node = Some(tail.clone());
+        while let Some(n) = node {
+            out.push(n.borrow(token).value.clone());
+            node = n.borrow(token).prev.as_ref().and_then(|p| p.upgrade());
+        }
+        out
+    }
+
+    fn for_each_mut<'id, T>(
+        head: &NodePtr<'id, T>,
+        token: &mut GhostToken<'id>,
+        mut f: impl FnMut(&mut T),
+    ) {
+        let mut node = Some(head.clone());
+        while let Some(n) = node {
+            let n = n.borrow_mut(token);
+            f(&mut n.value);
+            node = n.next.clone();
+        }
+    }
+
+    #[test]
+    fn ghost_dlist() {
+        let (forward, backward) = GhostToken::new(|mut token| {
+            let head = new_node(1);
+            let tail = new_node(4);
+            insert_next(&head, tail.clone(), &mut token);
+            let two = new_node(2);
+            insert_next(&head, two.clone(), &mut token);
+            insert_next(&two, new_node(3), &mut token);
+            assert_eq!(collect(&head, &token), [1, 2, 3, 4]);
+
+            remove(&two, &mut token);
+            for_each_mut(&head, &mut token, |v| *v *= 10);
+            let result = (collect(&head, &token), collect_back(&tail, &token));
+            while let Some(next) = head.borrow_mut(&mut token).next.take() {
+                remove(&next, &mut token);
+            }
+            result
+        });
+        assert_eq!(forward, [10, 30, 40]);
+        assert_eq!(backward, [40, 30, 10]);
+    }
+
+    #[test]
+    fn ghost_cell_methods() {
+        GhostToken::new(|mut token| {
+            let cell = GhostCell::from(vec![1]);
+            cell.borrow_mut(&mut token).push(2);
+            assert_eq!(cell.replace(vec![3], &mut token), [1, 2]);
+            assert_eq!(cell.take(&mut token), [3]);
+            assert!(cell.borrow(&token).is_empty());
+            let mut cell = GhostCell::<Vec<u8>>::default();
+            cell.get_mut().push(4);
+            assert_eq!(*cell.borrow(&token), [4]);
+            assert_eq!(cell.into_inner(), [4]);
+        });
+    }
+
+    #[test]
+    fn ghost_slice_of_cells() {
+        let mut values = [1, 2, 3, 4];
+        GhostToken::new(|mut token| {
+            let cells = GhostCell::from_mut(&mut values[..]).as_slice_of_cells();
+            for pair in cells.windows(2) {
+                let sum = *pair[0].borrow(&token) + *pair[1].borrow(&token);
+                *pair[1].borrow_mut(&mut token) = sum;
+            }
+            assert_eq!(*cells[3].inner().ro(token.owner()), 10);
+        });
+        assert_eq!(values, [1, 3, 6, 10]);
+    }
+
+    #[test]
+    fn ghost_from_mut() {
+        let mut value = 1;
+        GhostToken::new(|mut token| {
+            let cell = GhostCell::from_mut(&mut value);
+            *cell.borrow_mut(&mut token) += 1;
+            assert_eq!(*cell.inner().ro(token.owner()), 2);
+        });
+        assert_eq!(value, 2);
+    }
+}
@@ -0,0 +1,505 @@
+//! A process-wide owner, for prototypes, examples and tests.
+//!
+//! **This is not the recommended way to use this crate.**  All the
+//! cells created by [`cell`] belong to one [`QCellOwner`] which is
+//! kept behind a lock, and every access takes that lock for the
+//! duration of a closure.  So the compile-time guarantees that make
+//! the other cell types worth using are traded away for runtime
+//! checks, just as with `Mutex` or `RefCell`:
+//!
+//! - Accesses from different threads are serialized by the lock, even
+//!   when they are to different cells.
+//!
+//! - An access can't be made from within another on the same thread,
+//!   since the lock is already held.  Instead of deadlocking, this
+//!   panics, reporting both call sites.  Use [`with_rw2`] to access
+//!   two cells at once.
+//!
+//! It exists so that code can start out as simply as with `RefCell`,
+//! and then be converted to explicit owners once its structure has
+//! settled.  See below for how to do that.
+//!
+//! ```
+//!# use qcell::global::{self, GCell};
+//! let count: GCell<u32> = global::cell(0);
+//! global::with_rw(&count, |c| *c += 1);
+//! assert_eq!(global::with_ro(&count, |c| *c), 1);
+//! ```
+//!
+//! If the closure panics, the lock is released, and the cell contents
+//! are left as they were at the time of the panic.
+//!
+//! # Converting to an explicit owner
+//!
+//! Take a prototype which keeps its state in global cells:
+//!
+//! ```
+//!# use qcell::global::{self, GCell};
+//! struct Account {
+//!     balance: GCell<i64>,
+//! }
+//!
+//! fn transfer(from: &Account, to: &Account, amount: i64) {
+//!     global::with_rw2(&from.balance, &to.balance, |from, to| {
+//!         *from -= amount;
+//!         *to += amount;
+//!     });
+//! }
+//!
+//! let a = Account { balance: global::cell(100) };
+//! let b = Account { balance: global::cell(0) };
+//! transfer(&a, &b, 30);
+//! assert_eq!(global::with_ro(&b.balance, |b| *b), 30);
+//! ```
+//!
+//! First, code can be converted one function at a time by taking an
+//! owner argument and accessing the cells through
+//! [`GCell::inner`].  Callers which aren't converted yet get the
+//! owner from [`with_owner`], as a [`GlobalOwner`].  That can't be
+//! passed as `&mut QCellOwner`, since then the global owner could be
+//! swapped out, so the converted functions are generic over
+//! [`QCellBorrow`] instead:
+//!
+//! ```
+//!# use qcell::global::{self, GCell};
+//!# use qcell::QCellBorrow;
+//!# struct Account {
+//!#     balance: GCell<i64>,
+//!# }
+//! fn transfer(owner: &mut impl QCellBorrow, from: &Account, to: &Account, amount: i64) {
+//!     *owner.qcell_rw(from.balance.inner()) -= amount;
+//!     *owner.qcell_rw(to.balance.inner()) += amount;
+//! }
+//!
+//! let a = Account { balance: global::cell(100) };
+//! let b = Account { balance: global::cell(0) };
+//! global::with_owner(|owner| transfer(owner, &a, &b, 30));
+//! assert_eq!(global::with_ro(&b.balance, |b| *b), 30);
+//! ```
+//!
+//! Once no code uses the closure-based functions any more, replace
+//! `GCell<T>` with [`QCell<T>`], and create the cells with an owner
+//! of your own.  The converted functions don't change:
+//!
+//! ```
+//!# use qcell::{QCell, QCellBorrow, QCellOwner};
+//! struct Account {
+//!     balance: QCell<i64>,
+//! }
+//!
+//! fn transfer(owner: &mut impl QCellBorrow, from: &Account, to: &Account, amount: i64) {
+//!     *owner.qcell_rw(&from.balance) -= amount;
+//!     *owner.qcell_rw(&to.balance) += amount;
+//! }
+//!
+//! let mut owner = QCellOwner::new();
+//! let a = Account { balance: owner.cell(100) };
+//! let b = Account { balance: owner.cell(0) };
+//! transfer(&mut owner, &a, &b, 30);
+//! assert_eq!(*owner.ro(&b.balance), 30);
+//! ```
+//!
+//! [`cell`]: fn.cell.html
+//! [`with_rw2`]: fn.with_rw2.html
+//! [`with_owner`]: fn.with_owner.html
+//! [`GCell::inner`]: struct.GCell.html#method.inner
+//! [`GlobalOwner`]: struct.GlobalOwner.html
+//! [`QCellBorrow`]: ../trait.QCellBorrow.html
+//! [`QCellOwner`]: ../struct.QCellOwner.html
+//! [`QCell<T>`]: ../struct.QCell.html
+
+use std::cell::Cell;
+use std::ops::Deref;
+use std::panic::Location;
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+
+use once_cell::sync::Lazy;
+
+use crate::qcell::sealed;
+use crate::{panic_messages, QCell, QCellBorrow, QCellOwner, QCellOwnerID};
+
+struct Global {
+    // Keep a copy of the ID, so that cells can be created without
+    // taking the lock
+    id: QCellOwnerID,
+    owner: Mutex<QCellOwner>,
+}
+
+static GLOBAL: Lazy<Global> = Lazy::new(|| {
+    let owner = QCellOwner::new();
+    Global {
+        id: owner.id(),
+        owner: Mutex::new(owner),
+    }
+});
+
+std::thread_local! {
+    // Where this thread took the lock, if it holds it
+    static ACTIVE: Cell<Option<&'static Location<'static>>> = const { Cell::new(None) };
+}
+
+// Holds the lock, and clears this thread's marker when dropped, even
+// if the closure panics
+struct Access {
+    guard: MutexGuard<'static, QCellOwner>,
+}
+
+impl Drop for Access {
+    fn drop(&mut self) {
+        ACTIVE.with(|a| a.set(None));
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn reentered_panic(caller: &Location<'_>, active: &Location<'_>) -> ! {
+    panic!(
+        "{}: called at {}, active from {}",
+        panic_messages::GLOBAL_OWNER_REENTERED,
+        caller,
+        active
+    );
+}
+
+impl Access {
+    fn lock(caller: &'static Location<'static>) -> Self {
+        if let Some(active) = ACTIVE.with(Cell::get) {
+            reentered_panic(caller, active);
+        }
+        // A panic in an earlier closure leaves the owner itself in a
+        // fine state, so poisoning is ignored
+        let guard = GLOBAL.owner.lock().unwrap_or_else(PoisonError::into_inner);
+        ACTIVE.with(|a| a.set(Some(caller)));
+        Self { guard }
+    }
+
+    fn try_lock(caller: &'static Location<'static>) -> Option<Self> {
+        if ACTIVE.with(Cell::get).is_some() {
+            return None;
+        }
+        let guard = match GLOBAL.owner.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        ACTIVE.with(|a| a.set(Some(caller)));
+        Some(Self { guard })
+    }
+}
+
+/// A [`QCell`] owned by the global owner.  Create one with [`cell`].
+///
+/// [`QCell`]: ../struct.QCell.html
+/// [`cell`]: fn.cell.html
+pub struct GCell<T: ?Sized> {
+    inner: QCell<T>,
+}
+
+impl<T> GCell<T> {
+    /// Create a new cell owned by the global owner.  This doesn't
+    /// take the lock, so may be called from within a closure.
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: QCell::new(GLOBAL.id, value),
+        }
+    }
+
+    /// Destroy the cell and return the contained value
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> GCell<T> {
+    /// Get the [`QCell`] contained within this cell, which is owned by
+    /// the owner lent out by [`with_owner`].  This is used to convert
+    /// code to explicit owners.
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    /// [`with_owner`]: fn.with_owner.html
+    pub fn inner(&self) -> &QCell<T> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying data.  See
+    /// [`QCell::get_mut`](../struct.QCell.html#method.get_mut).
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Default> Default for GCell<T> {
+    fn default() -> Self {
+        GCell::new(T::default())
+    }
+}
+
+/// Create a new cell owned by the global owner
+pub fn cell<T>(value: T) -> GCell<T> {
+    GCell::new(value)
+}
+
+/// Get the ID of the global owner, which may be used to create
+/// [`QCell`] instances that are accessed through [`with_owner`]
+///
+/// [`QCell`]: ../struct.QCell.html
+/// [`with_owner`]: fn.with_owner.html
+pub fn owner_id() -> QCellOwnerID {
+    GLOBAL.id
+}
+
+/// Borrow the contents of a cell immutably for the duration of the
+/// closure call.  Blocks whilst another thread is accessing any
+/// global cell.  Panics if called from within another access on this
+/// thread.
+#[track_caller]
+pub fn with_ro<T: ?Sized, R>(cell: &GCell<T>, f: impl FnOnce(&T) -> R) -> R {
+    let access = Access::lock(Location::caller());
+    f(access.guard.ro(&cell.inner))
+}
+
+/// Borrow the contents of a cell mutably for the duration of the
+/// closure call.  Blocks whilst another thread is accessing any
+/// global cell.  Panics if called from within another access on this
+/// thread.
+#[track_caller]
+pub fn with_rw<T: ?Sized, R>(cell: &GCell<T>, f: impl FnOnce(&mut T) -> R) -> R {
+    let mut access = Access::lock(Location::caller());
+    f(access.guard.rw(&cell.inner))
+}
+
+/// Borrow the contents of two cells mutably for the duration of the
+/// closure call.  Panics if both are the same cell.  Otherwise the
+/// same as [`with_rw`].
+///
+/// [`with_rw`]: fn.with_rw.html
+#[track_caller]
+pub fn with_rw2<T: ?Sized, U: ?Sized, R>(
+    cell1: &GCell<T>,
+    cell2: &GCell<U>,
+    f: impl FnOnce(&mut T, &mut U) -> R,
+) -> R {
+    let mut access = Access::lock(Location::caller());
+    let (v1, v2) = access.guard.rw2(&cell1.inner, &cell2.inner);
+    f(v1, v2)
+}
+
+/// Borrow the contents of a cell mutably for the duration of the
+/// closure call, if that's possible without blocking.  Returns `None`
+/// if another thread is accessing a global cell, or if called from
+/// within another access on this thread.
+#[track_caller]
+pub fn try_with_rw<T: ?Sized, R>(cell: &GCell<T>, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+    let mut access = Access::try_lock(Location::caller())?;
+    Some(f(access.guard.rw(&cell.inner)))
+}
+
+/// The global owner, as lent out by [`with_owner`].
+///
+/// This derefs to the [`QCellOwner`] for read-only access, and has
+/// its own `rw` calls, but never gives out `&mut QCellOwner`.  That
+/// way the owner can't be replaced with another one, which would
+/// leave every existing [`GCell`] unusable.  It implements
+/// [`QCellBorrow`], so converted code can take it and a plain
+/// [`QCellOwner`] alike.
+///
+/// A replacement owner doesn't type-check:
+///
+/// ```compile_fail
+///# use qcell::{global, QCellOwner};
+/// global::with_owner(|owner| std::mem::replace(owner, QCellOwner::new()));  // Compile fail
+/// ```
+///
+/// and the owner it derefs to can't be borrowed mutably:
+///
+/// ```compile_fail
+///# use qcell::{global, QCellOwner};
+/// global::with_owner(|owner| std::mem::replace(&mut **owner, QCellOwner::new()));  // Compile fail
+/// ```
+///
+/// [`with_owner`]: fn.with_owner.html
+/// [`QCellOwner`]: ../struct.QCellOwner.html
+/// [`GCell`]: struct.GCell.html
+/// [`QCellBorrow`]: ../trait.QCellBorrow.html
+pub struct GlobalOwner<'a> {
+    owner: &'a mut QCellOwner,
+}
+
+impl GlobalOwner<'_> {
+    /// Borrow contents of a [`QCell`] mutably.  See
+    /// [`QCellOwner::rw`](../struct.QCellOwner.html#method.rw).
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.owner.rw(qc)
+    }
+
+    /// Borrow contents of two [`QCell`] instances mutably.  See
+    /// [`QCellOwner::rw2`](../struct.QCellOwner.html#method.rw2).
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn rw2<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+    ) -> (&'a mut T, &'a mut U) {
+        self.owner.rw2(qc1, qc2)
+    }
+
+    /// Borrow contents of three [`QCell`] instances mutably.  See
+    /// [`QCellOwner::rw3`](../struct.QCellOwner.html#method.rw3).
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
+        &'a mut self,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+        qc3: &'a QCell<V>,
+    ) -> (&'a mut T, &'a mut U, &'a mut V) {
+        self.owner.rw3(qc1, qc2, qc3)
+    }
+}
+
+impl Deref for GlobalOwner<'_> {
+    type Target = QCellOwner;
+
+    fn deref(&self) -> &QCellOwner {
+        self.owner
+    }
+}
+
+impl sealed::Sealed for GlobalOwner<'_> {}
+
+impl QCellBorrow for GlobalOwner<'_> {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.owner.ro(qc)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.owner.rw(qc)
+    }
+}
+
+/// Lend out the global owner for the duration of the closure call.
+/// This lets code which has been converted to take an owner argument
+/// be called with the global owner.  Blocks and panics in the same
+/// way as [`with_rw`].
+///
+/// [`with_rw`]: fn.with_rw.html
+#[track_caller]
+pub fn with_owner<R>(f: impl FnOnce(&mut GlobalOwner<'_>) -> R) -> R {
+    let mut access = Access::lock(Location::caller());
+    f(&mut GlobalOwner {
+        owner: &mut access.guard,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cell, try_with_rw, with_owner, with_ro, with_rw, with_rw2};
+    use crate::{QCell, QCellBorrow};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+
+    #[test]
+    fn global_threads() {
+        let cells: Arc<Vec<_>> = Arc::new((0..4).map(|_| cell(0u32)).collect());
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let cells = cells.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        with_rw(&cells[(t + i) % 4], |v| *v += 1);
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let total: u32 = cells.iter().map(|c| with_ro(c, |v| *v)).sum();
+        assert_eq!(total, 4000);
+    }
+
+    #[test]
+    fn global_reentered() {
+        let a = cell(1);
+        let b = cell(2);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            with_rw(&a, |a| with_ro(&b, |b| *a += *b));
+        }));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("called at src/global.rs"));
+        assert!(message.contains(", active from src/global.rs"));
+
+        // The panic released the lock, and cleared the marker
+        with_rw2(&a, &b, std::mem::swap);
+        assert_eq!(with_ro(&a, |a| *a), 2);
+        assert_eq!(with_owner(|owner| *owner.ro(b.inner())), 1);
+    }
+
+    #[test]
+    fn global_with_owner() {
+        fn add(owner: &mut impl QCellBorrow, from: &QCell<u32>, to: &QCell<u32>) {
+            let v = *owner.qcell_ro(from);
+            *owner.qcell_rw(to) += v;
+        }
+
+        let a = cell(1);
+        let b = cell(2);
+        let c = cell(3);
+        with_owner(|owner| {
+            add(owner, a.inner(), b.inner());
+            let (a, b, c) = owner.rw3(a.inner(), b.inner(), c.inner());
+            *c += *a + *b;
+        });
+        with_owner(|owner| {
+            let (a, c) = owner.rw2(a.inner(), c.inner());
+            *a += *c;
+            *owner.rw(b.inner()) += 1;
+        });
+        assert_eq!(with_ro(&a, |a| *a), 8);
+        assert_eq!(with_ro(&b, |b| *b), 4);
+        assert_eq!(with_owner(|owner| *owner.ro(c.inner())), 7);
+    }
+
+    #[test]
+    fn global_try_with_rw() {
+        let a = Arc::new(cell(0));
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let holder = {
+            let a = a.clone();
+            thread::spawn(move || {
+                with_rw(&a, |v| {
+                    *v += 1;
+                    locked_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                })
+            })
+        };
+        locked_rx.recv().unwrap();
+        assert_eq!(try_with_rw(&a, |v| *v += 10), None);
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+
+        // Other tests may take the lock briefly, so keep trying
+        while try_with_rw(&a, |v| *v += 10).is_none() {
+            thread::yield_now();
+        }
+        assert_eq!(with_ro(&a, |v| *v), 11);
+
+        // Never blocks or panics on the thread that holds the lock
+        with_rw(&a, |_| assert_eq!(try_with_rw(&a, |_| ()), None));
+    }
+}
@@ -0,0 +1,478 @@
+use alloc::rc::{Rc, Weak};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{QCell, QCellBorrow, QCellOwnerID};
+
+/// Identifies a node of a [`GraphCells`].
+///
+/// This is the node's slot in the graph plus the graph's epoch when
+/// the node was inserted.  The epoch moves on with every removal, so
+/// an ID never matches a node inserted later in the same slot.
+///
+/// [`GraphCells`]: struct.GraphCells.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId {
+    index: usize,
+    epoch: u64,
+}
+
+struct Slot<T> {
+    epoch: u64,
+    node: Option<Rc<QCell<T>>>,
+}
+
+/// A graph of `Rc<QCell<T>>` nodes which tracks which nodes are
+/// members, so that edges to removed nodes can't be followed.
+///
+/// In a graph built from plain `Rc` or `Weak` references, removing a
+/// node leaves its neighbours holding references to a node which is
+/// logically dead, and traversals may still walk into it.  Using
+/// `Weak` doesn't fix this if anything else still holds a strong
+/// reference to the removed node, since the upgrade still succeeds.
+/// Here the graph holds the only strong references to its nodes that
+/// it cares about, and the edges between nodes are [`CellEdge`]
+/// instances, which resolve only whilst their target is still a
+/// member.  [`GraphCells::remove`] takes the node out of the graph
+/// and moves the graph on to a new epoch, and from then on every
+/// edge to that node gives `None`, even if the node's allocation is
+/// kept alive elsewhere, and even if a new node is inserted into the
+/// same slot.
+///
+/// All the nodes are owned by the owner ID given to
+/// [`GraphCells::new`], and accessing them requires that owner.
+///
+/// ```
+///# use qcell::{CellEdge, GraphCells, QCellOwner};
+/// struct Node {
+///     name: &'static str,
+///     next: Option<CellEdge<Node>>,
+/// }
+///
+/// let mut owner = QCellOwner::new();
+/// let mut graph = GraphCells::new(&owner);
+/// let b = graph.insert(Node { name: "b", next: None });
+/// let a = graph.insert(Node { name: "a", next: graph.edge(b) });
+///
+/// let next = |owner: &QCellOwner, graph: &GraphCells<Node>| {
+///     let a = graph.get(a).unwrap();
+///     let edge = owner.ro(a).next.as_ref().unwrap();
+///     edge.get(owner, graph).map(|n| n.name)
+/// };
+/// assert_eq!(next(&owner, &graph), Some("b"));
+///
+/// // Keep `b` alive outside the graph, then remove it
+/// let kept = graph.get(b).unwrap().clone();
+/// graph.remove(&mut owner, b);
+/// assert_eq!(next(&owner, &graph), None);
+/// assert_eq!(owner.ro(&kept).name, "b");
+/// ```
+///
+/// [`CellEdge`]: struct.CellEdge.html
+/// [`GraphCells::new`]: struct.GraphCells.html#method.new
+/// [`GraphCells::remove`]: struct.GraphCells.html#method.remove
+pub struct GraphCells<T> {
+    id: QCellOwnerID,
+    epoch: u64,
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> GraphCells<T> {
+    /// Create an empty graph whose nodes will be owned by `owner`
+    pub fn new(owner: impl Into<QCellOwnerID>) -> Self {
+        Self {
+            id: owner.into(),
+            epoch: 0,
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Get the ID of the owner of the nodes
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        self.id
+    }
+
+    /// Get the current epoch, which starts at 0 and moves on by one
+    /// on each removal
+    #[inline]
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Get the number of nodes in the graph
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Test whether the graph has no nodes
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a node to the graph containing `value`, returning its ID
+    pub fn insert(&mut self, value: T) -> NodeId {
+        let node = Some(Rc::new(self.id.cell(value)));
+        let epoch = self.epoch;
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Slot { epoch, node };
+                index
+            }
+            None => {
+                self.slots.push(Slot { epoch, node });
+                self.slots.len() - 1
+            }
+        };
+        self.len += 1;
+        NodeId { index, epoch }
+    }
+
+    // Get the slot for `id`, if it still holds that node
+    fn slot(&self, id: NodeId) -> Option<&Rc<QCell<T>>> {
+        match self.slots.get(id.index) {
+            Some(slot) if slot.epoch == id.epoch => slot.node.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Test whether the node with the given ID is still a member of
+    /// the graph
+    #[inline]
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.slot(id).is_some()
+    }
+
+    /// Get the node with the given ID, or `None` if it has been
+    /// removed
+    #[inline]
+    pub fn get(&self, id: NodeId) -> Option<&Rc<QCell<T>>> {
+        self.slot(id)
+    }
+
+    /// Get an edge to the node with the given ID, or `None` if it has
+    /// been removed
+    pub fn edge(&self, id: NodeId) -> Option<CellEdge<T>> {
+        self.slot(id).map(|node| CellEdge {
+            target: id,
+            weak: Rc::downgrade(node),
+        })
+    }
+
+    /// Remove the node with the given ID from the graph, and move the
+    /// graph on to a new epoch.  Every [`CellEdge`] to the node
+    /// resolves to `None` from now on.  Returns the graph's strong
+    /// reference to the node, or `None` if it was already removed.
+    /// Drop the returned reference to free the node, unless other
+    /// strong references to it exist.  Panics if the node is not
+    /// owned by `owner`.
+    ///
+    /// [`CellEdge`]: struct.CellEdge.html
+    pub fn remove(&mut self, owner: &mut impl QCellBorrow, id: NodeId) -> Option<Rc<QCell<T>>> {
+        // Requiring the owner mutably means that nothing obtained
+        // through an edge to this node can still be borrowed
+        owner.qcell_rw(self.slot(id)?);
+        let node = self.slots[id.index].node.take()?;
+        self.free.push(id.index);
+        self.len -= 1;
+        self.epoch += 1;
+        Some(node)
+    }
+
+    /// Iterate over the IDs and nodes of all the members of the
+    /// graph, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Rc<QCell<T>>)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = NodeId {
+                index,
+                epoch: slot.epoch,
+            };
+            slot.node.as_ref().map(|node| (id, node))
+        })
+    }
+
+    /// Drop the empty slots left at the end of the table by removed
+    /// nodes, and release spare memory.  Slots in the middle of the
+    /// table can't be dropped without changing the IDs of the nodes
+    /// after them, so they are kept for reuse by `insert`.  This
+    /// doesn't affect which edges resolve.
+    pub fn compact(&mut self) {
+        while self.slots.last().map_or(false, |slot| slot.node.is_none()) {
+            self.slots.pop();
+        }
+        let len = self.slots.len();
+        self.free.retain(|&index| index < len);
+        self.slots.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+}
+
+impl<T> fmt::Debug for GraphCells<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GraphCells")
+            .field("id", &self.id)
+            .field("epoch", &self.epoch)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// An edge to a node of a [`GraphCells`], which resolves only whilst
+/// the target node is still a member of the graph.
+///
+/// This holds a `Weak` reference to the node, so doesn't keep it
+/// alive, plus its [`NodeId`], which records the graph's epoch when
+/// the node was inserted.  Create one with [`GraphCells::edge`].  See
+/// [`GraphCells`].
+///
+/// [`GraphCells`]: struct.GraphCells.html
+/// [`GraphCells::edge`]: struct.GraphCells.html#method.edge
+/// [`NodeId`]: struct.NodeId.html
+pub struct CellEdge<T> {
+    target: NodeId,
+    weak: Weak<QCell<T>>,
+}
+
+impl<T> Clone for CellEdge<T> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target,
+            weak: self.weak.clone(),
+        }
+    }
+}
+
+impl<T> CellEdge<T> {
+    /// Get the ID of the target node
+    #[inline]
+    pub fn target(&self) -> NodeId {
+        self.target
+    }
+
+    // Get the target node, if it is still a member of `graph`.
+    // Comparing the pointers also rejects an edge into a different
+    // graph whose node happens to have the same ID.
+    fn resolve<'a>(&self, graph: &'a GraphCells<T>) -> Option<&'a Rc<QCell<T>>> {
+        let node = graph.slot(self.target)?;
+        if Rc::as_ptr(node) != self.weak.as_ptr() {
+            return None;
+        }
+        Some(node)
+    }
+
+    /// Test whether the target node is still a member of `graph`
+    #[inline]
+    pub fn is_live(&self, graph: &GraphCells<T>) -> bool {
+        self.resolve(graph).is_some()
+    }
+
+    /// Get a strong reference to the target node, if it is still a
+    /// member of `graph`
+    #[inline]
+    pub fn upgrade(&self, graph: &GraphCells<T>) -> Option<Rc<QCell<T>>> {
+        self.resolve(graph).cloned()
+    }
+
+    /// Borrow the contents of the target node immutably, if it is
+    /// still a member of `graph`.  Panics if the node is not owned by
+    /// `owner`.
+    #[inline]
+    pub fn get<'a>(&self, owner: &'a impl QCellBorrow, graph: &'a GraphCells<T>) -> Option<&'a T> {
+        Some(owner.qcell_ro(self.resolve(graph)?))
+    }
+
+    /// Borrow the contents of the target node mutably, if it is still
+    /// a member of `graph`.  Panics if the node is not owned by
+    /// `owner`.
+    #[inline]
+    pub fn get_mut<'a>(
+        &self,
+        owner: &'a mut impl QCellBorrow,
+        graph: &'a GraphCells<T>,
+    ) -> Option<&'a mut T> {
+        Some(owner.qcell_rw(self.resolve(graph)?))
+    }
+}
+
+impl<T> fmt::Debug for CellEdge<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CellEdge")
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{CellEdge, GraphCells, NodeId};
+    use crate::QCellOwner;
+    use std::rc::Rc;
+
+    struct Node {
+        value: u32,
+        edges: Vec<CellEdge<Node>>,
+    }
+
+    fn node(value: u32) -> Node {
+        Node {
+            value,
+            edges: Vec::new(),
+        }
+    }
+
+    fn link(owner: &mut QCellOwner, graph: &GraphCells<Node>, from: NodeId, to: NodeId) {
+        let edge = graph.edge(to).unwrap();
+        owner.rw(graph.get(from).unwrap()).edges.push(edge);
+    }
+
+    // Values reachable in one step from `from`, skipping dead edges
+    fn neighbours(owner: &QCellOwner, graph: &GraphCells<Node>, from: NodeId) -> Vec<u32> {
+        owner.ro(graph.get(from).unwrap())
+            .edges
+            .iter()
+            .filter_map(|e| e.get(owner, graph).map(|n| n.value))
+            .collect()
+    }
+
+    #[test]
+    fn graph_remove_kills_edges() {
+        let mut owner = QCellOwner::new();
+        let mut graph = GraphCells::new(&owner);
+        let a = graph.insert(node(1));
+        let b = graph.insert(node(2));
+        let c = graph.insert(node(3));
+        link(&mut owner, &graph, a, b);
+        link(&mut owner, &graph, a, c);
+        assert_eq!(neighbours(&owner, &graph, a), [2, 3]);
+
+        // An external strong reference keeps the allocation alive,
+        // but the edge no longer resolves
+        let external = graph.get(b).unwrap().clone();
+        let edge = graph.edge(b).unwrap();
+        assert!(graph.remove(&mut owner, b).is_some());
+        assert!(graph.remove(&mut owner, b).is_none());
+        assert_eq!(neighbours(&owner, &graph, a), [3]);
+        assert!(!edge.is_live(&graph));
+        assert!(edge.upgrade(&graph).is_none());
+        assert!(graph.edge(b).is_none());
+        assert_eq!(owner.ro(&external).value, 2);
+        assert_eq!((graph.len(), graph.epoch()), (2, 1));
+
+        // Re-inserting reuses the slot but not the epoch
+        let d = graph.insert(node(4));
+        assert_eq!(d.index, b.index);
+        assert!(edge.get(&owner, &graph).is_none());
+        assert_eq!(neighbours(&owner, &graph, a), [3]);
+        link(&mut owner, &graph, a, d);
+        assert_eq!(neighbours(&owner, &graph, a), [3, 4]);
+    }
+
+    #[test]
+    fn graph_edge_wrong_graph() {
+        let owner = QCellOwner::new();
+        let mut g1 = GraphCells::new(&owner);
+        let mut g2 = GraphCells::new(&owner);
+        let a = g1.insert(node(1));
+        let b = g2.insert(node(2));
+        assert_eq!(a, b);
+        let edge = g1.edge(a).unwrap();
+        assert_eq!(edge.get(&owner, &g1).map(|n| n.value), Some(1));
+        assert!(edge.get(&owner, &g2).is_none());
+    }
+
+    #[test]
+    fn graph_traverse_while_mutating() {
+        let mut owner = QCellOwner::new();
+        let mut graph = GraphCells::new(&owner);
+        // A ring of 6 nodes, each linked to the next
+        let ids: Vec<_> = (0..6).map(|v| graph.insert(node(v))).collect();
+        for i in 0..6 {
+            link(&mut owner, &graph, ids[i], ids[(i + 1) % 6]);
+        }
+        // Walk the ring, removing every node with an even value after
+        // visiting it, and bumping the values of the rest
+        let mut visited = Vec::new();
+        let mut at = graph.edge(ids[0]);
+        while let Some(edge) = at.take() {
+            let n = match edge.get_mut(&mut owner, &graph) {
+                Some(n) => n,
+                None => break,
+            };
+            let value = n.value;
+            n.value += 10;
+            visited.push(value);
+            let next = n.edges[0].clone();
+            if value % 2 == 0 {
+                graph.remove(&mut owner, edge.target());
+            }
+            at = Some(next);
+        }
+        // Stops on reaching the removed node 0 again
+        assert_eq!(visited, [0, 1, 2, 3, 4, 5]);
+        let left: Vec<_> = graph.iter().map(|(_, n)| owner.ro(n).value).collect();
+        assert_eq!(left, [11, 13, 15]);
+        // The edges into the removed nodes are now dead
+        assert_eq!(neighbours(&owner, &graph, ids[5]), []);
+        assert_eq!(neighbours(&owner, &graph, ids[3]), []);
+        assert_eq!(neighbours(&owner, &graph, ids[1]), []);
+    }
+
+    #[test]
+    fn graph_no_leaks() {
+        let mut owner = QCellOwner::new();
+        let mut graph = GraphCells::new(&owner);
+        let a = graph.insert(node(1));
+        let b = graph.insert(node(2));
+        link(&mut owner, &graph, a, b);
+        link(&mut owner, &graph, b, a);
+        let a_rc = graph.get(a).unwrap().clone();
+        let b_rc = graph.get(b).unwrap().clone();
+        // Edges are weak, so a cycle holds no strong references
+        assert_eq!((Rc::strong_count(&a_rc), Rc::weak_count(&a_rc)), (2, 1));
+
+        drop(graph.remove(&mut owner, a));
+        assert_eq!(Rc::strong_count(&a_rc), 1);
+        drop(graph);
+        assert_eq!(Rc::strong_count(&b_rc), 1);
+        // `a_rc` still holds the only edge to `b`
+        assert_eq!(Rc::weak_count(&b_rc), 1);
+        drop(a_rc);
+        assert_eq!(Rc::weak_count(&b_rc), 0);
+    }
+
+    #[test]
+    fn graph_compact() {
+        let mut owner = QCellOwner::new();
+        let mut graph = GraphCells::new(&owner);
+        let ids: Vec<_> = (0..4).map(|v| graph.insert(node(v))).collect();
+        let edge = graph.edge(ids[3]).unwrap();
+        for &id in &ids[1..] {
+            graph.remove(&mut owner, id);
+        }
+        graph.compact();
+        assert_eq!(graph.slots.len(), 1);
+        assert!(graph.free.is_empty());
+        // The new node goes in slot 3 again, but with a later epoch
+        for v in 0..3 {
+            graph.insert(node(10 + v));
+        }
+        assert!(edge.get(&owner, &graph).is_none());
+        assert_eq!(graph.len(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn graph_remove_bad_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let mut graph = GraphCells::new(&owner1);
+        let a = graph.insert(node(1));
+        graph.remove(&mut owner2, a);
+    }
+}
@@ -0,0 +1,595 @@
+//! Intrusive doubly-linked list built on [`QCellOwnerPinned`].
+//!
+//! The list header, [`PinnedListHeader`], embeds a
+//! [`QCellOwnerPinned`], and each [`Node`] holds its links to its
+//! neighbours in a [`QCell`] owned by the header.  The nodes are
+//! stored wherever the caller likes, and the list only keeps pointers
+//! to them.  All link surgery is done through the owner, so a node
+//! created for one list can't be linked into another list, and two
+//! borrows of the same links can't overlap.
+//!
+//! # Node lifetime
+//!
+//! A list of type `PinnedListHeader<'node, T>` only accepts nodes
+//! borrowed for `'node`, and `'node` must outlive the header.  So a
+//! node can't be moved or dropped whilst it might still be linked
+//! into the list.  Since nodes are created from the pinned header,
+//! this means that the node variables must be declared before the
+//! header, and initialized afterwards.  When the header is dropped,
+//! any nodes still linked are unlinked.
+//!
+//! Nodes are linked with the `push_*` methods and unlinked with the
+//! `pop_*` methods or [`PinnedListHeader::remove`].  Alternatively
+//! [`PinnedListHeader::join_back`] returns a [`Membership`] guard,
+//! which unlinks the node when it is dropped.
+//!
+//! The header methods take `Pin<&Self>` rather than `Pin<&mut Self>`,
+//! so that membership guards can hold a reference to the header
+//! whilst other nodes are added and removed.  The owner is borrowed
+//! mutably inside each method for the duration of the link surgery
+//! only.  No user code runs during that time, and the header is not
+//! `Sync`, so those borrows can never overlap.
+//!
+//! ```
+//!# use qcell::intrusive::{Node, PinnedListHeader};
+//!# use pin_utils::pin_mut;
+//! let (a, b, c): (Node<char>, Node<char>, Node<char>);
+//! let list = PinnedListHeader::new();
+//! pin_mut!(list);
+//! let list = list.as_ref();
+//! a = list.node('a');
+//! b = list.node('b');
+//! c = list.node('c');
+//!
+//! list.push_back(&b);
+//! list.push_front(&a);
+//! {
+//!     let _member = list.join_back(&c);
+//!     assert_eq!(list.iter().collect::<String>(), "abc");
+//! }
+//! assert_eq!(list.iter().collect::<String>(), "ab");
+//! assert!(list.remove(&a));
+//! assert_eq!(list.pop_back().map(|n| *n.get()), Some('b'));
+//! assert!(list.is_empty());
+//! ```
+//!
+//! [`QCell`]: ../struct.QCell.html
+//! [`QCellOwnerPinned`]: ../struct.QCellOwnerPinned.html
+//! [`PinnedListHeader`]: struct.PinnedListHeader.html
+//! [`PinnedListHeader::remove`]: struct.PinnedListHeader.html#method.remove
+//! [`PinnedListHeader::join_back`]: struct.PinnedListHeader.html#method.join_back
+//! [`Node`]: struct.Node.html
+//! [`Membership`]: struct.Membership.html
+
+use core::cell::{Cell, UnsafeCell};
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use crate::{panic_messages, sound, QCell, QCellOwnerPinned};
+
+#[cold]
+#[inline(never)]
+fn already_linked_panic() -> ! {
+    panic!("{}", panic_messages::INTRUSIVE_ALREADY_LINKED);
+}
+
+// Links of a node to its neighbours, only accessible via the owner
+// in the list header
+struct Links<T> {
+    prev: Option<NonNull<Node<T>>>,
+    next: Option<NonNull<Node<T>>>,
+    linked: bool,
+}
+
+/// Node of an intrusive list, holding a value of type `T`.
+///
+/// Create nodes with [`PinnedListHeader::node`].  The value can be
+/// accessed through a reference to the node at any time, whether or
+/// not it is linked into the list.
+///
+/// [`PinnedListHeader::node`]: struct.PinnedListHeader.html#method.node
+pub struct Node<T> {
+    links: QCell<Links<T>>,
+    value: T,
+}
+
+impl<T> Node<T> {
+    /// Borrow the value immutably
+    #[inline]
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Borrow the value mutably.  Since this requires `&mut`, the
+    /// node can't be linked into a list at the time.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Destroy the node and return the value
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// Header of an intrusive doubly-linked list of [`Node`] instances.
+///
+/// This embeds the [`QCellOwnerPinned`] which owns the links of all
+/// its nodes, so it must be pinned before use.  See the [module
+/// documentation](index.html) for details.
+///
+/// [`Node`]: struct.Node.html
+/// [`QCellOwnerPinned`]: ../struct.QCellOwnerPinned.html
+pub struct PinnedListHeader<'node, T> {
+    owner: UnsafeCell<QCellOwnerPinned>,
+    head: Cell<Option<NonNull<Node<T>>>>,
+    tail: Cell<Option<NonNull<Node<T>>>>,
+    len: Cell<usize>,
+    // Invariant in 'node, so the lifetime of the nodes can't be
+    // shortened to match a shorter borrow of the header
+    nodes: PhantomData<Cell<&'node Node<T>>>,
+}
+
+impl<'node, T> Default for PinnedListHeader<'node, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'node, T> PinnedListHeader<'node, T> {
+    /// Create a new empty list header.  It must be pinned before use.
+    pub fn new() -> Self {
+        Self {
+            owner: UnsafeCell::new(QCellOwnerPinned::new()),
+            head: Cell::new(None),
+            tail: Cell::new(None),
+            len: Cell::new(0),
+            nodes: PhantomData,
+        }
+    }
+
+    // The owner is structurally pinned.  It is only borrowed mutably
+    // during the link surgery of a header method, which never calls
+    // any user code or other header method whilst holding it.  Since
+    // the header is not `Sync`, this ensures that no other borrow of
+    // the owner can be active at the same time.
+    #[inline]
+    fn owner_ro(self: Pin<&Self>) -> Pin<&QCellOwnerPinned> {
+        // Safety: Pinned, and no `&mut` to the owner, as above
+        unsafe { sound::pin_unchecked(sound::deref_shared(&self.get_ref().owner)) }
+    }
+
+    /// Create a new node owned by this list, holding the given value.
+    /// The node may only be linked into this list.
+    pub fn node(self: Pin<&Self>, value: T) -> Node<T> {
+        Node {
+            links: self.owner_ro().cell(Links {
+                prev: None,
+                next: None,
+                linked: false,
+            }),
+            value,
+        }
+    }
+
+    /// Get the number of nodes linked into the list
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Test whether the list is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Test whether a node is currently linked into this list.
+    /// Panics if the node does not belong to this list.
+    pub fn contains(self: Pin<&Self>, node: &Node<T>) -> bool {
+        self.owner_ro().ro(&node.links).linked
+    }
+
+    /// Link a node at the back of the list.  Panics if the node is
+    /// already linked, or if it does not belong to this list.
+    pub fn push_back(self: Pin<&Self>, node: &'node Node<T>) {
+        let ptr = NonNull::from(node);
+        let tail = self.tail.get();
+        {
+            // Safety: Pinned, and no other borrow of the owner, as for
+            // `owner_ro`
+            let mut owner =
+                unsafe { sound::pin_unchecked(sound::deref_unique(&self.get_ref().owner)) };
+            let links = owner.as_mut().rw(&node.links);
+            if links.linked {
+                already_linked_panic();
+            }
+            links.linked = true;
+            links.prev = tail;
+            links.next = None;
+            match tail {
+                Some(tail) => {
+                    // Safety: All linked nodes are borrowed for 'node
+                    let tail = unsafe { sound::deref_raw(tail.as_ptr()) };
+                    owner.rw(&tail.links).next = Some(ptr);
+                }
+                None => self.head.set(Some(ptr)),
+            }
+        }
+        self.tail.set(Some(ptr));
+        self.len.set(self.len.get() + 1);
+    }
+
+    /// Link a node at the front of the list.  Panics if the node is
+    /// already linked, or if it does not belong to this list.
+    pub fn push_front(self: Pin<&Self>, node: &'node Node<T>) {
+        let ptr = NonNull::from(node);
+        let head = self.head.get();
+        {
+            // Safety: Pinned, and no other borrow of the owner, as for
+            // `owner_ro`
+            let mut owner =
+                unsafe { sound::pin_unchecked(sound::deref_unique(&self.get_ref().owner)) };
+            let links = owner.as_mut().rw(&node.links);
+            if links.linked {
+                already_linked_panic();
+            }
+            links.linked = true;
+            links.prev = None;
+            links.next = head;
+            match head {
+                Some(head) => {
+                    // Safety: All linked nodes are borrowed for 'node
+                    let head = unsafe { sound::deref_raw(head.as_ptr()) };
+                    owner.rw(&head.links).prev = Some(ptr);
+                }
+                None => self.tail.set(Some(ptr)),
+            }
+        }
+        self.head.set(Some(ptr));
+        self.len.set(self.len.get() + 1);
+    }
+
+    /// Link a node at the back of the list, and return a guard which
+    /// unlinks it again when dropped.  Panics if the node is already
+    /// linked, or if it does not belong to this list.
+    pub fn join_back<'list>(
+        self: Pin<&'list Self>,
+        node: &'node Node<T>,
+    ) -> Membership<'list, 'node, T> {
+        self.push_back(node);
+        Membership { list: self, node }
+    }
+
+    /// Unlink a node from the list.  Returns `false` if the node was
+    /// not linked.  Panics if the node does not belong to this list.
+    pub fn remove(self: Pin<&Self>, node: &Node<T>) -> bool {
+        let (prev, next) = {
+            // Safety: Pinned, and no other borrow of the owner, as for
+            // `owner_ro`
+            let mut owner =
+                unsafe { sound::pin_unchecked(sound::deref_unique(&self.get_ref().owner)) };
+            let links = owner.as_mut().rw(&node.links);
+            if !links.linked {
+                return false;
+            }
+            links.linked = false;
+            let prev = links.prev.take();
+            let next = links.next.take();
+            // Safety: All linked nodes are borrowed for 'node
+            let p = prev.map(|p| unsafe { sound::deref_raw(p.as_ptr()) });
+            // Safety: All linked nodes are borrowed for 'node
+            let n = next.map(|n| unsafe { sound::deref_raw(n.as_ptr()) });
+            match (p, n) {
+                (Some(p), Some(n)) => {
+                    let (p, n) = owner.rw2(&p.links, &n.links);
+                    p.next = next;
+                    n.prev = prev;
+                }
+                (Some(p), None) => owner.rw(&p.links).next = None,
+                (None, Some(n)) => owner.rw(&n.links).prev = None,
+                (None, None) => (),
+            }
+            (prev, next)
+        };
+        if prev.is_none() {
+            self.head.set(next);
+        }
+        if next.is_none() {
+            self.tail.set(prev);
+        }
+        self.len.set(self.len.get() - 1);
+        true
+    }
+
+    /// Unlink the node at the front of the list and return it
+    pub fn pop_front(self: Pin<&Self>) -> Option<&'node Node<T>> {
+        let head = self.head.get()?;
+        // Safety: All linked nodes are borrowed for 'node
+        let node = unsafe { sound::deref_raw(head.as_ptr()) };
+        self.remove(node);
+        Some(node)
+    }
+
+    /// Unlink the node at the back of the list and return it
+    pub fn pop_back(self: Pin<&Self>) -> Option<&'node Node<T>> {
+        let tail = self.tail.get()?;
+        // Safety: All linked nodes are borrowed for 'node
+        let node = unsafe { sound::deref_raw(tail.as_ptr()) };
+        self.remove(node);
+        Some(node)
+    }
+
+    /// Get the node at the front of the list
+    pub fn front(&self) -> Option<&'node Node<T>> {
+        // Safety: All linked nodes are borrowed for 'node
+        self.head
+            .get()
+            .map(|p| unsafe { sound::deref_raw(p.as_ptr()) })
+    }
+
+    /// Get the node at the back of the list
+    pub fn back(&self) -> Option<&'node Node<T>> {
+        // Safety: All linked nodes are borrowed for 'node
+        self.tail
+            .get()
+            .map(|p| unsafe { sound::deref_raw(p.as_ptr()) })
+    }
+
+    /// Iterate over the values of the nodes in the list, from front
+    /// to back.  If the list is modified during iteration, the
+    /// iterator continues from whichever node follows the last one
+    /// returned at that time.  If the last one returned has been
+    /// unlinked, the iteration ends.
+    pub fn iter<'list>(self: Pin<&'list Self>) -> Iter<'list, 'node, T> {
+        Iter {
+            list: self,
+            prev: None,
+            started: false,
+        }
+    }
+}
+
+impl<'node, T> Drop for PinnedListHeader<'node, T> {
+    fn drop(&mut self) {
+        // Unlink all the nodes, so that they don't point to each
+        // other, or into a dead list.  The header was pinned before any
+        // node could be linked, and is still pinned until this
+        // returns.  All linked nodes are borrowed for 'node, which
+        // outlives the header.
+        // Safety: Pinned, as above
+        let this = unsafe { sound::pin_unchecked(&*self) };
+        let mut next = this.head.take();
+        this.tail.set(None);
+        this.len.set(0);
+        while let Some(node) = next {
+            // Safety: Pinned, and no other borrow of the owner, as for
+            // `owner_ro`
+            let owner = unsafe { sound::pin_unchecked(sound::deref_unique(&this.get_ref().owner)) };
+            // Safety: All linked nodes are borrowed for 'node
+            let node = unsafe { sound::deref_raw(node.as_ptr()) };
+            let links = owner.rw(&node.links);
+            next = links.next.take();
+            links.prev = None;
+            links.linked = false;
+        }
+    }
+}
+
+/// Guard for a node's membership of a [`PinnedListHeader`], returned
+/// by [`PinnedListHeader::join_back`].
+///
+/// Dropping the guard unlinks the node, if it is still linked.
+///
+/// [`PinnedListHeader`]: struct.PinnedListHeader.html
+/// [`PinnedListHeader::join_back`]: struct.PinnedListHeader.html#method.join_back
+#[must_use = "dropping the Membership unlinks the node immediately"]
+pub struct Membership<'list, 'node, T> {
+    list: Pin<&'list PinnedListHeader<'node, T>>,
+    node: &'node Node<T>,
+}
+
+impl<'list, 'node, T> Membership<'list, 'node, T> {
+    /// Get the node
+    #[inline]
+    pub fn node(&self) -> &'node Node<T> {
+        self.node
+    }
+}
+
+impl<'list, 'node, T> Drop for Membership<'list, 'node, T> {
+    fn drop(&mut self) {
+        self.list.remove(self.node);
+    }
+}
+
+/// Iterator over the values in a [`PinnedListHeader`], returned by
+/// [`PinnedListHeader::iter`].
+///
+/// [`PinnedListHeader`]: struct.PinnedListHeader.html
+/// [`PinnedListHeader::iter`]: struct.PinnedListHeader.html#method.iter
+pub struct Iter<'list, 'node, T> {
+    list: Pin<&'list PinnedListHeader<'node, T>>,
+    prev: Option<&'node Node<T>>,
+    started: bool,
+}
+
+impl<'list, 'node, T> Iterator for Iter<'list, 'node, T> {
+    type Item = &'node T;
+
+    fn next(&mut self) -> Option<&'node T> {
+        let next = match (self.started, self.prev) {
+            (false, _) => self.list.head.get(),
+            (true, Some(prev)) => self.list.owner_ro().ro(&prev.links).next,
+            (true, None) => None,
+        };
+        self.started = true;
+        // Safety: All linked nodes are borrowed for 'node
+        self.prev = next.map(|p| unsafe { sound::deref_raw(p.as_ptr()) });
+        self.prev.map(Node::get)
+    }
+}
+
+// Nodes are declared before the list, so that they outlive it, but
+// clippy doesn't see why the initialization is late
+#[cfg(test)]
+#[allow(clippy::needless_late_init)]
+mod tests {
+    use super::{Node, PinnedListHeader};
+    use pin_utils::pin_mut;
+
+    fn contents<T: Copy>(list: core::pin::Pin<&PinnedListHeader<'_, T>>) -> Vec<T> {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn list_push_pop() {
+        let nodes: Vec<Node<u32>>;
+        let list = PinnedListHeader::new();
+        pin_mut!(list);
+        let list = list.as_ref();
+        nodes = (0..5).map(|i| list.node(i)).collect();
+
+        assert!(list.is_empty());
+        assert!(list.pop_front().is_none());
+        assert!(list.pop_back().is_none());
+        list.push_back(&nodes[2]);
+        list.push_back(&nodes[3]);
+        list.push_front(&nodes[1]);
+        list.push_front(&nodes[0]);
+        list.push_back(&nodes[4]);
+        assert_eq!(list.len(), 5);
+        assert_eq!(contents(list), [0, 1, 2, 3, 4]);
+        assert_eq!(list.front().map(|n| *n.get()), Some(0));
+        assert_eq!(list.back().map(|n| *n.get()), Some(4));
+
+        assert_eq!(list.pop_front().map(|n| *n.get()), Some(0));
+        assert_eq!(list.pop_back().map(|n| *n.get()), Some(4));
+        assert_eq!(contents(list), [1, 2, 3]);
+        assert!(!list.contains(&nodes[0]));
+
+        // Popped nodes can be linked again
+        list.push_back(&nodes[0]);
+        assert_eq!(contents(list), [1, 2, 3, 0]);
+        while list.pop_front().is_some() {}
+        assert!(list.is_empty());
+        assert!(list.front().is_none() && list.back().is_none());
+        assert_eq!(contents(list), []);
+    }
+
+    #[test]
+    fn list_remove_middle() {
+        let nodes: Vec<Node<u32>>;
+        let list = PinnedListHeader::new();
+        pin_mut!(list);
+        let list = list.as_ref();
+        nodes = (0..5).map(|i| list.node(i)).collect();
+        for node in &nodes {
+            list.push_back(node);
+        }
+
+        assert!(list.remove(&nodes[2]));
+        assert!(!list.remove(&nodes[2]));
+        assert_eq!(contents(list), [0, 1, 3, 4]);
+        assert!(list.remove(&nodes[0]));
+        assert!(list.remove(&nodes[4]));
+        assert_eq!(contents(list), [1, 3]);
+        assert_eq!(list.len(), 2);
+        assert!(list.remove(&nodes[1]));
+        assert!(list.remove(&nodes[3]));
+        assert!(list.is_empty());
+
+        for node in nodes.iter().rev() {
+            list.push_back(node);
+        }
+        assert_eq!(contents(list), [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn list_membership() {
+        let (a, b, c): (Node<u32>, Node<u32>, Node<u32>);
+        let list = PinnedListHeader::new();
+        pin_mut!(list);
+        let list = list.as_ref();
+        a = list.node(1);
+        b = list.node(2);
+        c = list.node(3);
+
+        let ma = list.join_back(&a);
+        {
+            let mb = list.join_back(&b);
+            list.push_back(&c);
+            assert_eq!(contents(list), [1, 2, 3]);
+            assert_eq!(*mb.node().get(), 2);
+        }
+        assert_eq!(contents(list), [1, 3]);
+        assert!(list.remove(&a));
+        // Guard drops harmlessly after the node was already removed
+        drop(ma);
+        assert_eq!(contents(list), [3]);
+    }
+
+    #[test]
+    fn list_drop_unlinks() {
+        let (a, b): (Node<u32>, Node<u32>);
+        {
+            let list = PinnedListHeader::new();
+            pin_mut!(list);
+            let list = list.as_ref();
+            a = list.node(1);
+            b = list.node(2);
+            list.push_back(&a);
+            list.push_back(&b);
+        }
+        // The nodes outlive the list, and are no longer linked
+        assert_eq!(*a.get() + *b.get(), 3);
+        assert_eq!(a.into_inner(), 1);
+    }
+
+    #[test]
+    fn list_iter_modified() {
+        let nodes: Vec<Node<u32>>;
+        let list = PinnedListHeader::new();
+        pin_mut!(list);
+        let list = list.as_ref();
+        nodes = (0..4).map(|i| list.node(i)).collect();
+        for node in &nodes {
+            list.push_back(node);
+        }
+        let mut seen = Vec::new();
+        for v in list.iter() {
+            seen.push(*v);
+            if *v == 1 {
+                list.remove(&nodes[2]);
+            }
+        }
+        assert_eq!(seen, [0, 1, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already linked")]
+    fn list_double_push() {
+        let a: Node<u32>;
+        let list = PinnedListHeader::new();
+        pin_mut!(list);
+        let list = list.as_ref();
+        a = list.node(1);
+        list.push_back(&a);
+        list.push_front(&a);
+    }
+
+    #[test]
+    #[should_panic(expected = "incorrect owner")]
+    fn list_wrong_list() {
+        let a: Node<u32>;
+        let list1 = PinnedListHeader::new();
+        pin_mut!(list1);
+        let list2 = PinnedListHeader::new();
+        pin_mut!(list2);
+        a = list1.as_ref().node(1);
+        list2.as_ref().push_back(&a);
+    }
+}
@@ -0,0 +1,340 @@
+//! Threading a mutable owner through iterator chains.
+//!
+//! A closure passed to `map` or `filter` can capture `&mut owner`,
+//! but then nothing else can use the owner until the iterator is
+//! dropped, and the closure can't return a borrow of the contents,
+//! since that would have to outlive the closure's use of the owner.
+//! For example, this fails to compile:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//!# use std::sync::Arc;
+//! let mut owner = QCellOwner::new();
+//! let cells: Vec<Arc<QCell<u64>>> = (0..4).map(|n| Arc::new(owner.cell(n))).collect();
+//! cells.iter().map(|c| owner.rw(c)).for_each(|n| *n += 1); // Compile fail
+//! ```
+//!
+//! The `&mut` reference returned by `rw` borrows the captured owner,
+//! so it can't escape the `FnMut` closure, since it would still be
+//! live at the next call to `rw`.  So usually the code ends up as a
+//! `for` loop instead.
+//!
+//! The adapters in [`OwnerIterExt`] take the owner once, and pass a
+//! fresh reborrow of it to the closure for each item.  The closure
+//! may borrow cell contents within the call, but whatever it returns
+//! can't borrow from the owner, so the compiler knows that no borrow
+//! is carried over to the next item:
+//!
+#![cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ # use qcell::{QCell, QCellOwner};
+ # use std::sync::Arc;
+ use qcell::iter::OwnerIterExt;
+
+ let mut owner = QCellOwner::new();
+ let cells: Vec<Arc<QCell<u64>>> = (0..4).map(|n| Arc::new(owner.cell(n))).collect();
+ cells.iter().for_each_with_owner(&mut owner, |owner, c| *owner.rw(c) += 1);
+ let total: u64 = cells
+     .iter()
+     .map_with_owner(&mut owner, |owner, c| *owner.ro(c))
+     .sum();
+ assert_eq!(total, 10);
+ ```
+"
+)]
+//!
+//! The owner may be any of the owner types listed under
+//! [`OwnerMutRef`].
+//!
+//! [`OwnerIterExt`]: trait.OwnerIterExt.html
+//! [`OwnerMutRef`]: trait.OwnerMutRef.html
+
+use core::pin::Pin;
+
+#[cfg(feature = "alloc")]
+use crate::QCellOwner;
+use crate::{
+    LCellOwner, OwnerMut, QCellOwnerPinned, QCellOwnerSeq, QCellOwnerSingle, TCellMarker,
+    TCellOwner,
+};
+#[cfg(feature = "std")]
+use crate::TLCellOwner;
+
+mod sealed {
+    pub trait Sealed {}
+}
+use sealed::Sealed;
+
+/// Owner types which may be passed to the adapters of
+/// [`OwnerIterExt`].
+///
+/// This is implemented for all the owners which borrow their cells
+/// mutably through `&mut self`.  For [`QCellOwnerPinned`], which is
+/// only used pinned, it is implemented for `Pin<&mut
+/// QCellOwnerPinned>` instead, so the closure receives a `&mut Pin<&mut
+/// QCellOwnerPinned>` and calls `owner.as_mut().rw(cell)`.  This
+/// trait is sealed, so can't be implemented outside this crate.
+///
+/// [`OwnerIterExt`]: trait.OwnerIterExt.html
+/// [`QCellOwnerPinned`]: ../struct.QCellOwnerPinned.html
+pub trait OwnerMutRef: Sealed {}
+
+macro_rules! owner_mut_ref {
+    ($([$($gen:tt)*] $owner:ty;)*) => {
+        $(
+            impl<$($gen)*> Sealed for $owner {}
+            impl<$($gen)*> OwnerMutRef for $owner {}
+        )*
+    };
+}
+
+owner_mut_ref! {
+    [] QCellOwnerSeq;
+    [] QCellOwnerSingle;
+    ['a] Pin<&'a mut QCellOwnerPinned>;
+    ['a] OwnerMut<'a>;
+    [Q: TCellMarker] TCellOwner<Q>;
+    ['id] LCellOwner<'id>;
+}
+
+#[cfg(feature = "alloc")]
+owner_mut_ref! {
+    [] QCellOwner;
+}
+
+#[cfg(feature = "std")]
+owner_mut_ref! {
+    [Q: TCellMarker] TLCellOwner<Q>;
+}
+
+/// Iterator adapters which pass a mutable owner to the closure for
+/// each item.  Implemented for all iterators.
+///
+/// In each case, the owner is borrowed for as long as the iteration
+/// runs, and each call to the closure gets a reborrow of it which
+/// ends when the call returns.  See the [module
+/// documentation](index.html).
+pub trait OwnerIterExt: Iterator + Sized {
+    /// Map each item with a closure which is also given the owner.
+    /// This returns an iterator, but since the owner stays borrowed
+    /// by it, the owner can only be used in the closure until the
+    /// iterator is dropped.  The mapped values can't borrow from the
+    /// owner.
+    ///
+    #[cfg_attr(
+        any(feature = "std", feature = "exclusion-set"),
+        doc = "
+ ```
+ # use qcell::{TCell, TCellOwner};
+ use qcell::iter::OwnerIterExt;
+ # qcell::marker!(struct Marker;);
+ let mut owner = TCellOwner::<Marker>::new();
+ let cells: Vec<TCell<Marker, String>> =
+     [\"a\", \"b\"].iter().map(|s| TCell::new(s.to_string())).collect();
+ let lengths: Vec<usize> = cells
+     .iter()
+     .map_with_owner(&mut owner, |owner, c| {
+         let s = owner.rw(c);
+         s.push('!');
+         s.len()
+     })
+     .collect();
+ assert_eq!(lengths, [2, 2]);
+ assert_eq!(owner.ro(&cells[1]), \"b!\");
+ ```
+"
+    )]
+    #[inline]
+    fn map_with_owner<'o, O, R, F>(self, owner: &'o mut O, f: F) -> MapWithOwner<'o, Self, O, F>
+    where
+        O: OwnerMutRef + ?Sized,
+        F: FnMut(&mut O, Self::Item) -> R,
+    {
+        MapWithOwner {
+            iter: self,
+            owner,
+            f,
+        }
+    }
+
+    /// Call a closure on each item, also passing it the owner
+    ///
+    /// ```
+    ///# use qcell::{LCell, LCellOwner};
+    /// use qcell::iter::OwnerIterExt;
+    /// LCellOwner::scope(|mut owner| {
+    ///     let cells = [LCell::new(1), LCell::new(2)];
+    ///     cells.iter().for_each_with_owner(&mut owner, |owner, c| *owner.rw(c) *= 10);
+    ///     assert_eq!(*owner.ro(&cells[1]), 20);
+    /// });
+    /// ```
+    #[inline]
+    fn for_each_with_owner<O, F>(self, owner: &mut O, mut f: F)
+    where
+        O: OwnerMutRef + ?Sized,
+        F: FnMut(&mut O, Self::Item),
+    {
+        self.for_each(|item| f(owner, item));
+    }
+
+    /// Fold the items with a closure which is also given the owner,
+    /// stopping at the first `Err`.  Returns the final accumulator,
+    /// or the first error.
+    ///
+    #[cfg_attr(
+        feature = "alloc",
+        doc = "
+ ```
+ # use qcell::{QCell, QCellOwner};
+ use qcell::iter::OwnerIterExt;
+ let mut owner = QCellOwner::new();
+ let cells: Vec<QCell<u32>> = [5, 10, 20].iter().map(|&n| owner.cell(n)).collect();
+ // Take from each cell in turn until 12 has been taken
+ let taken = cells.iter().try_fold_with_owner(&mut owner, 0, |owner, sum, c| {
+     let n = owner.rw(c);
+     let take = (*n).min(12 - sum);
+     *n -= take;
+     if sum + take == 12 { Err(sum + take) } else { Ok(sum + take) }
+ });
+ assert_eq!(taken, Err(12));
+ assert_eq!(*owner.ro(&cells[1]), 3);
+ assert_eq!(*owner.ro(&cells[2]), 20);
+ ```
+"
+    )]
+    #[inline]
+    fn try_fold_with_owner<O, B, E, F>(mut self, owner: &mut O, init: B, mut f: F) -> Result<B, E>
+    where
+        O: OwnerMutRef + ?Sized,
+        F: FnMut(&mut O, B, Self::Item) -> Result<B, E>,
+    {
+        self.try_fold(init, |acc, item| f(owner, acc, item))
+    }
+}
+
+impl<I: Iterator> OwnerIterExt for I {}
+
+/// Iterator returned by [`OwnerIterExt::map_with_owner`]
+///
+/// [`OwnerIterExt::map_with_owner`]: trait.OwnerIterExt.html#method.map_with_owner
+pub struct MapWithOwner<'o, I, O: ?Sized, F> {
+    iter: I,
+    owner: &'o mut O,
+    f: F,
+}
+
+impl<'o, I, O, R, F> Iterator for MapWithOwner<'o, I, O, F>
+where
+    I: Iterator,
+    O: OwnerMutRef + ?Sized,
+    F: FnMut(&mut O, I::Item) -> R,
+{
+    type Item = R;
+
+    #[inline]
+    fn next(&mut self) -> Option<R> {
+        let item = self.iter.next()?;
+        Some((self.f)(self.owner, item))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::OwnerIterExt;
+    use crate::{QCell, QCellOwner, QCellOwnerPinned, TLCell, TLCellOwner};
+    use std::sync::Arc;
+
+    fn cells(owner: &QCellOwner, n: u64) -> Vec<Arc<QCell<u64>>> {
+        (1..=n).map(|i| Arc::new(owner.cell(i))).collect()
+    }
+
+    #[test]
+    fn iter_sum_and_mutate() {
+        let mut owner = QCellOwner::new();
+        let cells = cells(&owner, 10);
+        let sum: u64 = cells
+            .iter()
+            .map_with_owner(&mut owner, |owner, c| *owner.ro(c))
+            .sum();
+        assert_eq!(sum, 55);
+
+        // Double the odd values, counting them
+        let mut odd = 0;
+        cells.iter().for_each_with_owner(&mut owner, |owner, c| {
+            let n = owner.rw(c);
+            if *n % 2 == 1 {
+                *n *= 2;
+                odd += 1;
+            }
+        });
+        assert_eq!(odd, 5);
+        let values: Vec<u64> = cells.iter().map(|c| *owner.ro(c)).collect();
+        assert_eq!(values, [2, 2, 6, 4, 10, 6, 14, 8, 18, 10]);
+
+        // The adapters can be used again once the last one is done
+        let evens = cells
+            .iter()
+            .map_with_owner(&mut owner, |owner, c| *owner.ro(c))
+            .filter(|n| n % 4 == 0)
+            .count();
+        assert_eq!(evens, 2);
+    }
+
+    #[test]
+    fn iter_try_fold_early_exit() {
+        let mut owner = QCellOwner::new();
+        let cells = cells(&owner, 10);
+        let mut visited = 0;
+        let result = cells
+            .iter()
+            .try_fold_with_owner(&mut owner, 0, |owner, sum, c| {
+                visited += 1;
+                *owner.rw(c) = 0;
+                let sum = sum + visited;
+                if sum > 10 {
+                    Err(visited)
+                } else {
+                    Ok(sum)
+                }
+            });
+        assert_eq!(result, Err(5));
+        // Cells after the exit are untouched
+        assert_eq!(*owner.ro(&cells[4]), 0);
+        assert_eq!(*owner.ro(&cells[5]), 6);
+
+        let result: Result<u64, ()> = cells
+            .iter()
+            .try_fold_with_owner(&mut owner, 0, |owner, sum, c| Ok(sum + *owner.ro(c)));
+        assert_eq!(result, Ok(40));
+    }
+
+    #[test]
+    fn iter_other_owners() {
+        let mut pinned = Box::pin(QCellOwnerPinned::new());
+        let cells: Vec<QCell<u32>> = (0..3).map(|n| pinned.as_ref().cell(n)).collect();
+        let mut owner = pinned.as_mut();
+        cells
+            .iter()
+            .for_each_with_owner(&mut owner, |owner, c| *owner.as_mut().rw(c) += 1);
+        assert_eq!(*pinned.as_ref().ro(&cells[2]), 3);
+
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cells = [TLCell::<Marker, _>::new(1), TLCell::new(2)];
+        let doubled: Vec<i32> = cells
+            .iter()
+            .map_with_owner(&mut owner, |owner, c| {
+                *owner.rw(c) *= 2;
+                *owner.ro(c)
+            })
+            .collect();
+        assert_eq!(doubled, [2, 4]);
+    }
+}
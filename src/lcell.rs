@@ -1,9 +1,63 @@
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::borrow::Borrow;
 use core::cell::UnsafeCell;
 use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use once_cell::sync::Lazy;
+#[cfg(feature = "std")]
+use std::{collections::HashSet, sync::Mutex};
 
 use super::Invariant;
+use crate::batch;
+use crate::capability::Capability;
+use crate::panic_messages;
+use crate::ro_slice::{writer_check, RoSlice, ValuePtr};
+#[cfg(feature = "alloc")]
+use crate::rw_many::distinct_check;
+use crate::sound;
+use crate::util::clash;
+#[cfg(feature = "alloc")]
+use crate::view::VecViewMut;
+use crate::view::{SliceView, SliceViewMut};
 type Id<'id> = PhantomData<Invariant<&'id ()>>;
 
+// Address and size of a cell, to check for clashes in `rw2` and `rw3`
+#[inline]
+fn range<T: ?Sized>(lc: &LCell<'_, T>) -> (usize, usize) {
+    (lc as *const LCell<'_, T> as *const () as usize, mem::size_of_val(lc))
+}
+
+// Brand tokens currently in use by a `scope_with_token` call, and the
+// next token to hand out.  Token 0 is reserved to mean "no token".
+#[cfg(feature = "std")]
+struct BrandRegistry {
+    next: u64,
+    live: HashSet<u64>,
+}
+
+#[cfg(feature = "std")]
+static BRAND_REGISTRY: Lazy<Mutex<BrandRegistry>> = Lazy::new(|| {
+    Mutex::new(BrandRegistry {
+        next: 1,
+        live: HashSet::new(),
+    })
+});
+
+// Releases a brand token on scope exit, even on panic
+#[cfg(feature = "std")]
+struct BrandGuard(u64);
+
+#[cfg(feature = "std")]
+impl Drop for BrandGuard {
+    fn drop(&mut self) {
+        BRAND_REGISTRY.lock().unwrap().live.remove(&self.0);
+    }
+}
+
 /// Borrowing-owner of zero or more [`LCell`](struct.LCell.html)
 /// instances.
 ///
@@ -30,6 +84,15 @@ type Id<'id> = PhantomData<Invariant<&'id ()>>;
 /// **generativity** if you make a mistake and use the wrong owner for
 /// a cell.
 ///
+/// An owner created by `LCellOwner::scope` can't be leaked to keep it
+/// for the rest of the program, because its lifetime is confined to
+/// the closure and so can never be `'static`.  The same goes for
+/// `make_guard!`.  The only way to get a `LCellOwner<'static>` is
+/// from a `Guard<'static>`, which **generativity** can only create
+/// with `unsafe` code, since the caller must make sure that no other
+/// `'static` guard is ever created in the program.  Such an owner
+/// may be leaked with [`LCellOwner::leak`].
+///
 /// Some history: `GhostCell` by
 /// [**pythonesque**](https://github.com/pythonesque) predates the
 /// creation of `LCell`, and inspired it.  Discussion of `GhostCell`
@@ -42,6 +105,8 @@ type Id<'id> = PhantomData<Invariant<&'id ()>>;
 /// `Sync` support for `LCell` was contributed independently.
 ///
 /// See also [crate documentation](index.html).
+///
+/// [`LCellOwner::leak`]: struct.LCellOwner.html#method.leak
 pub struct LCellOwner<'id> {
     _id: Id<'id>,
 }
@@ -66,6 +131,149 @@ impl<'id> LCellOwner<'id> {
         f(Self { _id: PhantomData })
     }
 
+    /// Mint a new unique brand token.  This may be passed to
+    /// [`LCellOwner::scope_with_token`] to create an owner which can
+    /// erase cells to [`ErasedLCell`] and restore them again later.
+    /// Each call returns a different token.
+    ///
+    /// [`ErasedLCell`]: struct.ErasedLCell.html
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn brand_token() -> u64 {
+        let mut reg = BRAND_REGISTRY.lock().unwrap();
+        let token = reg.next;
+        reg.next = token
+            .checked_add(1)
+            .expect(panic_messages::LCELL_BRAND_TOKENS_EXHAUSTED);
+        token
+    }
+
+    /// Same as [`LCellOwner::scope`], except that the owner is
+    /// associated with the given brand token, obtained from
+    /// [`LCellOwner::brand_token`].  The closure gets an
+    /// [`LCellTokenOwner`], which derefs to an [`LCellOwner`] and so
+    /// can be used in the same way, but which can also erase cells
+    /// with [`LCellTokenOwner::erase`] so that they can be stored
+    /// outside of the scope, and then restore them with
+    /// [`LCellTokenOwner::restore`] in a later scope created with the
+    /// same token.
+    ///
+    /// Only one scope may be active for any given token at any one
+    /// time.  This call panics if the token is already in use by
+    /// another scope (including in another thread), or if it was not
+    /// obtained from [`LCellOwner::brand_token`].
+    ///
+    /// ```
+    /// use qcell::{ErasedLCell, LCell, LCellOwner};
+    /// let token = LCellOwner::brand_token();
+    /// let mut stored: Option<ErasedLCell<Vec<u8>>> = None;
+    /// LCellOwner::scope_with_token(token, |mut owner| {
+    ///     let cell = LCell::new(vec![1]);
+    ///     owner.rw(&cell).push(2);
+    ///     stored = Some(owner.erase(cell));
+    /// });
+    /// LCellOwner::scope_with_token(token, |mut owner| {
+    ///     let cell = owner.restore(stored.as_ref().unwrap()).unwrap();
+    ///     owner.rw(cell).push(3);
+    ///     assert_eq!(owner.ro(cell), &[1, 2, 3]);
+    /// });
+    /// ```
+    ///
+    /// [`LCellOwner`]: struct.LCellOwner.html
+    /// [`LCellTokenOwner`]: struct.LCellTokenOwner.html
+    /// [`LCellTokenOwner::erase`]: struct.LCellTokenOwner.html#method.erase
+    /// [`LCellTokenOwner::restore`]: struct.LCellTokenOwner.html#method.restore
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn scope_with_token<F>(token: u64, f: F)
+    where
+        F: for<'scope_id> FnOnce(LCellTokenOwner<'scope_id>),
+    {
+        // Don't panic with the registry locked, else it is poisoned
+        let (valid, inserted) = {
+            let mut reg = BRAND_REGISTRY.lock().unwrap();
+            let valid = token != 0 && token < reg.next;
+            (valid, valid && reg.live.insert(token))
+        };
+        assert!(
+            valid,
+            "{}: {}",
+            panic_messages::LCELL_BRAND_TOKEN_INVALID,
+            token
+        );
+        assert!(
+            inserted,
+            "{}: {}",
+            panic_messages::LCELL_BRAND_TOKEN_IN_USE,
+            token
+        );
+        let _guard = BrandGuard(token);
+        f(LCellTokenOwner {
+            owner: Self { _id: PhantomData },
+            token,
+        })
+    }
+
+    /// Create a new owner with a new lifetime, like
+    /// [`LCellOwner::scope`], and run two phases under it: `build`
+    /// constructs a structure of cells from `seed`, and `use_` is
+    /// then handed that structure, returning the final result.
+    ///
+    /// This allows construction and use to be split across separate
+    /// functions, each generic over the brand lifetime, instead of
+    /// writing everything inside one closure.  The structure is
+    /// passed between the phases as a [`Branded`], which names its
+    /// type through a [`BrandFamily`], since the type depends on the
+    /// brand lifetime.  As the [`Branded`] value carries the brand in
+    /// its type, it can't be returned from `use_` or leak out of the
+    /// scope in any other way.
+    ///
+    /// ```
+    ///# use qcell::{BrandFamily, Branded, LCell, LCellOwner};
+    /// struct Tree<'id> {
+    ///     value: LCell<'id, u32>,
+    ///     children: Vec<Tree<'id>>,
+    /// }
+    ///
+    /// struct TreeFamily;
+    /// impl<'id> BrandFamily<'id> for TreeFamily {
+    ///     type Of = Tree<'id>;
+    /// }
+    ///
+    /// fn build<'id>(owner: &mut LCellOwner<'id>, depth: u32) -> Tree<'id> {
+    ///     Tree {
+    ///         value: owner.cell(depth),
+    ///         children: (0..depth).map(|d| build(owner, d)).collect(),
+    ///     }
+    /// }
+    ///
+    /// fn sum<'id>(owner: &LCellOwner<'id>, tree: &Tree<'id>) -> u32 {
+    ///     owner.get(&tree.value) + tree.children.iter().map(|t| sum(owner, t)).sum::<u32>()
+    /// }
+    ///
+    /// let total = LCellOwner::scope_build(
+    ///     3,
+    ///     |owner, depth| Branded::<TreeFamily>::new(build(owner, depth)),
+    ///     |owner, tree| sum(owner, &tree),
+    /// );
+    /// assert_eq!(total, 7);
+    /// ```
+    ///
+    /// [`Branded`]: struct.Branded.html
+    /// [`BrandFamily`]: trait.BrandFamily.html
+    pub fn scope_build<S, F, R, B, U>(seed: S, build: B, use_: U) -> R
+    where
+        F: for<'scope_id> BrandFamily<'scope_id>,
+        B: for<'scope_id> FnOnce(&mut LCellOwner<'scope_id>, S) -> Branded<'scope_id, F>,
+        U: for<'scope_id> FnOnce(&mut LCellOwner<'scope_id>, Branded<'scope_id, F>) -> R,
+    {
+        let mut owner = Self {
+            _id: PhantomData,
+        };
+        let built = build(&mut owner, seed);
+        use_(&mut owner, built)
+    }
+
     /// Create a new `LCellOwner` with a unique lifetime from a `Guard`.
     ///
     /// ```rust
@@ -89,12 +297,37 @@ impl<'id> LCellOwner<'id> {
         LCell::<T>::new(value)
     }
 
+    /// Check that `cell` belongs to this owner.  This does nothing at
+    /// runtime, since it only compiles if the brands match.  It is
+    /// for making the intent explicit in code with several owners in
+    /// scope: if the cell has another owner's brand, the compiler
+    /// reports the mismatch at this line, rather than somewhere
+    /// inside a later generic call.  See the [`lcell_brands`] module
+    /// for the errors that result.
+    ///
+    /// ```
+    ///# use qcell::{LCell, LCellOwner};
+    /// LCellOwner::scope(|mut owner| {
+    ///     let cell = LCell::new(1);
+    ///     owner.assert_owns(&cell);
+    ///     *owner.rw(&cell) += 1;
+    /// });
+    /// ```
+    ///
+    /// [`lcell_brands`]: lcell_brands/index.html
+    #[inline]
+    #[track_caller]
+    pub fn assert_owns<T: ?Sized>(&self, cell: &LCell<'id, T>) {
+        let _ = cell;
+    }
+
     /// Borrow contents of a `LCell` immutably (read-only).  Many
     /// `LCell` instances can be borrowed immutably at the same time
     /// from the same owner.
     #[inline]
     pub fn ro<'a, T: ?Sized>(&'a self, lc: &'a LCell<'id, T>) -> &'a T {
-        unsafe { &*lc.value.get() }
+        // Safety: Owned (by brand), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&lc.value) }
     }
 
     /// Borrow contents of a `LCell` mutably (read-write).  Only one
@@ -103,11 +336,16 @@ impl<'id> LCellOwner<'id> {
     /// another can be borrowed.
     #[inline]
     pub fn rw<'a, T: ?Sized>(&'a mut self, lc: &'a LCell<'id, T>) -> &'a mut T {
-        unsafe { &mut *lc.value.get() }
+        // Safety: Owned (by brand), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&lc.value) }
     }
 
     /// Borrow contents of two `LCell` instances mutably.  Panics if
-    /// the two `LCell` instances point to the same memory.
+    /// the two `LCell` instances point to the same memory, or
+    /// overlap, as a cell from [`LCell::as_slice_of_cells`] overlaps
+    /// the cell for the whole slice.
+    ///
+    /// [`LCell::as_slice_of_cells`]: struct.LCell.html#method.as_slice_of_cells
     #[inline]
     pub fn rw2<'a, T: ?Sized, U: ?Sized>(
         &'a mut self,
@@ -115,14 +353,17 @@ impl<'id> LCellOwner<'id> {
         lc2: &'a LCell<'id, U>,
     ) -> (&'a mut T, &'a mut U) {
         assert!(
-            lc1 as *const _ as *const () as usize != lc2 as *const _ as *const () as usize,
-            "Illegal to borrow same LCell twice with rw2()"
+            !clash(range(lc1), range(lc2)),
+            "{}",
+            panic_messages::LCELL_BORROWED_TWICE
         );
-        unsafe { (&mut *lc1.value.get(), &mut *lc2.value.get()) }
+        // Safety: Owned (by brand), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&lc1.value, &lc2.value) }
     }
 
     /// Borrow contents of three `LCell` instances mutably.  Panics if
-    /// any pair of `LCell` instances point to the same memory.
+    /// any pair of `LCell` instances point to the same memory, or
+    /// overlap, like `rw2`.
     #[inline]
     pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
         &'a mut self,
@@ -130,20 +371,361 @@ impl<'id> LCellOwner<'id> {
         lc2: &'a LCell<'id, U>,
         lc3: &'a LCell<'id, V>,
     ) -> (&'a mut T, &'a mut U, &'a mut V) {
+        let (r1, r2, r3) = (range(lc1), range(lc2), range(lc3));
         assert!(
-            (lc1 as *const _ as *const () as usize != lc2 as *const _ as *const () as usize)
-                && (lc2 as *const _ as *const () as usize != lc3 as *const _ as *const () as usize)
-                && (lc3 as *const _ as *const () as usize != lc1 as *const _ as *const () as usize),
-            "Illegal to borrow same LCell twice with rw3()"
+            !clash(r1, r2) && !clash(r2, r3) && !clash(r3, r1),
+            "{}",
+            panic_messages::LCELL_BORROWED_TWICE
         );
-        unsafe {
-            (
-                &mut *lc1.value.get(),
-                &mut *lc2.value.get(),
-                &mut *lc3.value.get(),
-            )
+        // Safety: Owned (by brand), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&lc1.value, &lc2.value, &lc3.value) }
+    }
+
+    /// Borrow contents of one `LCell` mutably, and the contents of a
+    /// slice of `LCell` instances immutably.  The readers may repeat,
+    /// but none may be the same cell as the writer, or overlap it,
+    /// like `rw2`.  Panics with the position of the first reader that
+    /// clashes with the writer.  The contents of the readers are
+    /// fetched lazily through the returned [`RoSlice`].
+    ///
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        w: &'a LCell<'id, T>,
+        readers: &'a [&'a LCell<'id, U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        writer_check("LCell", w, readers);
+        // Safety: Owned (by brand), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
+    }
+
+    /// Copy the contents of a `LCell` out, like `Cell::get`.  No
+    /// borrow is held after this returns.
+    #[inline]
+    pub fn get<T: Copy>(&self, lc: &LCell<'id, T>) -> T {
+        *self.ro(lc)
+    }
+
+    /// Clone the contents of a `LCell` out.  This is a separate call
+    /// from [`LCellOwner::get`] so that `get` stays a plain copy.
+    ///
+    /// [`LCellOwner::get`]: struct.LCellOwner.html#method.get
+    #[inline]
+    pub fn get_cloned<T: Clone>(&self, lc: &LCell<'id, T>) -> T {
+        self.ro(lc).clone()
+    }
+
+    /// Replace the contents of a `LCell` with `value`, like
+    /// `Cell::set`, dropping the old value.
+    #[inline]
+    pub fn set<T>(&mut self, lc: &LCell<'id, T>, value: T) {
+        *self.rw(lc) = value;
+    }
+
+    /// Replace the contents of a `LCell` with the result of calling
+    /// `f` on a copy of the old contents, and return the new value.
+    #[inline]
+    pub fn update<T: Copy>(&mut self, lc: &LCell<'id, T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(lc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Copy the contents of many `LCell`s into `out` without
+    /// allocating, for example to take a snapshot of a set of
+    /// parameters.  This is the same as calling [`LCellOwner::get`]
+    /// for each cell.
+    ///
+    /// [`LCellOwner::get`]: struct.LCellOwner.html#method.get
+    #[inline]
+    pub fn read_into<T: Copy, const N: usize>(
+        &self,
+        cells: &[&LCell<'id, T>; N],
+        out: &mut [T; N],
+    ) {
+        self.read_into_slice(cells, out);
+    }
+
+    /// Copy the contents of many `LCell`s into `out`, like
+    /// [`LCellOwner::read_into`], but with the lengths checked at
+    /// runtime.  Panics if `cells` and `out` have different
+    /// lengths.
+    ///
+    /// [`LCellOwner::read_into`]: struct.LCellOwner.html#method.read_into
+    #[inline]
+    pub fn read_into_slice<T: Copy>(&self, cells: &[&LCell<'id, T>], out: &mut [T]) {
+        batch::length_check(cells.len(), out.len());
+        for (lc, value) in cells.iter().zip(out) {
+            *value = *self.ro(lc);
+        }
+    }
+
+    /// Copy the values in `src` into many `LCell`s without
+    /// allocating.  The cells are written in order, and no check
+    /// is made for repeated cells, so if the same `LCell` appears
+    /// more than once then the last write wins.
+    #[inline]
+    pub fn write_from<T: Copy, const N: usize>(
+        &mut self,
+        cells: &[&LCell<'id, T>; N],
+        src: &[T; N],
+    ) {
+        self.write_from_slice(cells, src);
+    }
+
+    /// Copy the values in `src` into many `LCell`s, like
+    /// [`LCellOwner::write_from`], but with the lengths checked at
+    /// runtime.  Panics before writing anything if `cells` and
+    /// `src` have different lengths.
+    ///
+    /// [`LCellOwner::write_from`]: struct.LCellOwner.html#method.write_from
+    #[inline]
+    pub fn write_from_slice<T: Copy>(&mut self, cells: &[&LCell<'id, T>], src: &[T]) {
+        batch::length_check(cells.len(), src.len());
+        for (lc, value) in cells.iter().zip(src) {
+            *self.rw(lc) = *value;
         }
     }
+
+    /// Borrow the contents of a `LCell` holding a slice, `Vec`, boxed
+    /// slice or array as a read-only [`SliceView`], which supports
+    /// indexing by position or range.
+    ///
+    /// [`SliceView`]: struct.SliceView.html
+    #[inline]
+    pub fn view<'a, T, S: AsRef<[T]> + ?Sized>(
+        &'a self,
+        lc: &'a LCell<'id, S>,
+    ) -> SliceView<'a, T> {
+        SliceView::new(self.ro(lc).as_ref())
+    }
+
+    /// Borrow the contents of a `LCell` holding a `Vec` as a
+    /// [`VecViewMut`], which supports indexing for reading and
+    /// writing.  This owner stays mutably borrowed while the view
+    /// exists, so no other cell can be borrowed through it.
+    ///
+    /// [`VecViewMut`]: struct.VecViewMut.html
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[inline]
+    pub fn view_mut<'a, T>(&'a mut self, lc: &'a LCell<'id, Vec<T>>) -> VecViewMut<'a, T> {
+        VecViewMut::new(self.rw(lc))
+    }
+
+    /// Borrow the contents of a `LCell` holding a slice, `Vec`, boxed
+    /// slice or array as a [`SliceViewMut`], which supports indexing
+    /// for reading and writing.  This owner stays mutably borrowed
+    /// while the view exists.
+    ///
+    /// [`SliceViewMut`]: struct.SliceViewMut.html
+    #[inline]
+    pub fn slice_view_mut<'a, T, S: AsMut<[T]> + ?Sized>(
+        &'a mut self,
+        lc: &'a LCell<'id, S>,
+    ) -> SliceViewMut<'a, T> {
+        SliceViewMut::new(self.rw(lc).as_mut())
+    }
+
+    /// Wrap a mutable borrow of this owner in a [`Capability`] which
+    /// can only read [`LCell`] instances if `READ` is `true`, and only
+    /// write them if `WRITE` is `true`, to hand to code which should
+    /// be restricted to one or the other.  See [`ReadOnly`] and
+    /// [`WriteOnly`].
+    ///
+    /// [`Capability`]: struct.Capability.html
+    /// [`ReadOnly`]: type.ReadOnly.html
+    /// [`WriteOnly`]: type.WriteOnly.html
+    #[inline]
+    pub fn capability<const READ: bool, const WRITE: bool>(
+        &mut self,
+    ) -> Capability<'_, LCellOwner<'id>, READ, WRITE> {
+        Capability::new(self)
+    }
+
+    /// Borrow the contents of a `LCell` mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  No runtime check is needed to keep the
+    /// two apart: the cell's contents are reached through the owner,
+    /// and `other` through a separate `&mut`, which safe code can't
+    /// point into the cell's contents.  Since the owner is borrowed
+    /// for the call, `f` can't use it.
+    #[inline]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        &'a mut self,
+        lc: &'a LCell<'id, T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(lc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two `LCell` instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.
+    #[inline]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        &'a mut self,
+        lc1: &'a LCell<'id, T>,
+        lc2: &'a LCell<'id, U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(lc1, lc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of a `LCell` immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.
+    #[inline]
+    pub fn inspect<T: ?Sized, D>(
+        &self,
+        lc: &LCell<'id, T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(lc))
+    }
+
+    /// Same as `inspect`, but borrows two `LCell` instances, which may
+    /// be the same cell.
+    #[inline]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        &self,
+        lc1: &LCell<'id, T>,
+        lc2: &LCell<'id, U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(lc1), self.ro(lc2))
+    }
+
+    /// Read the contents of a `LCell` with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.
+    #[inline]
+    pub fn modify_if<T: ?Sized, D>(
+        &mut self,
+        lc: &LCell<'id, T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.ro(lc)) {
+            Some(decision) => {
+                apply(self.rw(lc), decision);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run a read-only phase.  Calls `f` with a shared reference to
+    /// the owner, and returns its result.  Since the owner is `Sync`,
+    /// that reference may be shared with scoped threads, which can
+    /// all read the cells in parallel.  Write access is suspended
+    /// for the duration of `f`, and since the result can't borrow
+    /// from the owner, it resumes as soon as `f` returns.
+    ///
+    #[cfg_attr(
+        feature = "scoped-threads",
+        doc = "
+ ```
+ # use qcell::{LCell, LCellOwner};
+ LCellOwner::scope(|mut owner| {
+     let cells: Vec<_> = (0..1000u64).map(|i| owner.cell(i)).collect();
+     let sum = owner.read_phase(|owner| {
+         std::thread::scope(|s| {
+             let readers: Vec<_> = cells
+                 .chunks(250)
+                 .map(|chunk| {
+                     s.spawn(move || chunk.iter().map(|c| owner.get(c)).sum::<u64>())
+                 })
+                 .collect();
+             readers.into_iter().map(|r| r.join().unwrap()).sum::<u64>()
+         })
+     });
+     assert_eq!(sum, 999 * 1000 / 2);
+     // Write access resumes once the phase is over
+     for cell in &cells {
+         owner.update(cell, |v| v * 2);
+     }
+ });
+ ```
+"
+    )]
+    ///
+    /// See [`doctest_lcell`] for the cases this rules out.
+    ///
+    /// [`doctest_lcell`]: doctest_lcell/index.html
+    #[inline]
+    pub fn read_phase<R>(&mut self, f: impl FnOnce(&Self) -> R) -> R {
+        f(self)
+    }
+
+    /// Run a read-write phase.  Calls `f` with a mutable reference to
+    /// the owner, and returns its result.  This is the mirror of
+    /// `read_phase`, so that code alternating between phases can
+    /// mark each of them the same way.
+    #[inline]
+    pub fn write_phase<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        f(self)
+    }
+
+    /// Borrow the contents of a slice of `LCell` instances mutably,
+    /// returning a `Vec` of references in the same order.  The slice
+    /// may hold the cells directly, or anything that borrows as a
+    /// `LCell`, such as `Rc<LCell<..>>`, `Arc<LCell<..>>` or
+    /// `&LCell<..>`.  Panics if any two entries are the same cell,
+    /// which includes two `Rc` or `Arc` clones of the same cell, or
+    /// overlap, like `rw2`.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_slice<'a, T: ?Sized, C: Borrow<LCell<'id, T>>>(
+        &'a mut self,
+        cells: &'a [C],
+    ) -> Vec<&'a mut T> {
+        self.rw_from_iter(cells)
+    }
+
+    /// Same as [`LCellOwner::rw_from_slice`], but takes the cells from
+    /// an iterator of references.
+    ///
+    /// [`LCellOwner::rw_from_slice`]: struct.LCellOwner.html#method.rw_from_slice
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_iter<'a, T: ?Sized + 'a, C: Borrow<LCell<'id, T>> + 'a>(
+        &'a mut self,
+        cells: impl IntoIterator<Item = &'a C>,
+    ) -> Vec<&'a mut T> {
+        let cells: Vec<&'a LCell<'id, T>> = cells.into_iter().map(Borrow::borrow).collect();
+        distinct_check("LCell", &cells);
+        cells
+            .into_iter()
+            // Safety: Owned (by brand), Distinct, and owner mutably borrowed
+            .map(|lc| unsafe { sound::deref_unique(&lc.value) })
+            .collect()
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+impl LCellOwner<'static> {
+    /// Leak this owner, keeping it for the rest of the program, for
+    /// use with cells that are referenced from statics.  Only an owner
+    /// with a `'static` brand can be leaked.  See [`LCellOwner`] for
+    /// how to get one.
+    ///
+    /// [`LCellOwner`]: struct.LCellOwner.html
+    pub fn leak(self) -> &'static mut LCellOwner<'static> {
+        Box::leak(Box::new(self))
+    }
 }
 
 /// Cell whose contents are owned (for borrowing purposes) by a
@@ -162,12 +744,19 @@ pub struct LCell<'id, T: ?Sized> {
     value: UnsafeCell<T>,
 }
 
+impl<'id, T: ?Sized> ValuePtr for LCell<'id, T> {
+    type Value = T;
+    fn value_ptr(&self) -> *const T {
+        self.value.get()
+    }
+}
+
 impl<'id, T> LCell<'id, T> {
     /// Create a new `LCell`.  The owner of this cell is inferred by
     /// Rust from the context.  So the owner lifetime is whatever
     /// lifetime is required by the first use of the new `LCell`.
     #[inline]
-    pub fn new(value: T) -> LCell<'id, T> {
+    pub const fn new(value: T) -> LCell<'id, T> {
         LCell {
             _id: PhantomData,
             value: UnsafeCell::new(value),
@@ -182,6 +771,32 @@ impl<'id, T> LCell<'id, T> {
     pub fn into_inner(self) -> T {
         self.value.into_inner()
     }
+
+    /// Replace the contents of this cell with `value`, and return the
+    /// old contents
+    ///
+    /// ```
+    ///# use qcell::LCellOwner;
+    /// LCellOwner::scope(|mut owner| {
+    ///     let cell = owner.cell(1);
+    ///     assert_eq!(cell.replace(2, &mut owner), 1);
+    ///     assert_eq!(owner.get(&cell), 2);
+    /// });
+    /// ```
+    #[inline]
+    pub fn replace(&self, value: T, owner: &mut LCellOwner<'id>) -> T {
+        core::mem::replace(owner.rw(self), value)
+    }
+
+    /// Take the contents of this cell, leaving `T::default()` in its
+    /// place
+    #[inline]
+    pub fn take(&self, owner: &mut LCellOwner<'id>) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default(), owner)
+    }
 }
 
 impl<'id, T: ?Sized> LCell<'id, T> {
@@ -219,8 +834,282 @@ impl<'id, T: ?Sized> LCell<'id, T> {
     pub fn get_mut(&mut self) -> &mut T {
         self.value.get_mut()
     }
+
+    /// Get a raw pointer to the contents of this cell.  Dereferencing
+    /// it is only sound under the same conditions as borrowing the
+    /// contents through the owner.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+
+    /// Convert a mutable reference to a value into a mutable
+    /// reference to an `LCell` containing it, with any brand.  Since
+    /// the value is borrowed mutably, the brand's owner can't reach
+    /// it any other way until the borrow ends.  Together with
+    /// [`LCell::as_slice_of_cells`], this allows the elements of a
+    /// slice to be accessed as separate cells.
+    ///
+    /// [`LCell::as_slice_of_cells`]: struct.LCell.html#method.as_slice_of_cells
+    #[inline]
+    pub fn from_mut(value: &mut T) -> &mut Self {
+        sound::lcell_from_mut(value)
+    }
+}
+
+impl<'id, T> LCell<'id, [T]> {
+    /// Access a cell containing a slice as a slice of cells, one per
+    /// element, all with the same brand.  This is the same as
+    /// `Cell::as_slice_of_cells` in the standard library.
+    ///
+    /// ```
+    ///# use qcell::{LCell, LCellOwner};
+    /// let mut values = [1, 2, 3];
+    /// LCellOwner::scope(|mut owner| {
+    ///     let cells = LCell::from_mut(&mut values[..]).as_slice_of_cells();
+    ///     let (a, b) = owner.rw2(&cells[0], &cells[2]);
+    ///     std::mem::swap(a, b);
+    /// });
+    /// assert_eq!(values, [3, 2, 1]);
+    /// ```
+    ///
+    /// The cell for the whole slice is still usable, and overlaps
+    /// every element's cell, so the calls which borrow several cells
+    /// mutably at once compare the byte range of each cell, not just
+    /// its address, and panic on any overlap.  So this panics:
+    ///
+    /// ```should_panic
+    ///# use qcell::{LCell, LCellOwner};
+    /// let mut values = [1, 2, 3];
+    /// LCellOwner::scope(|mut owner| {
+    ///     let whole = LCell::from_mut(&mut values[..]);
+    ///     let cells = whole.as_slice_of_cells();
+    ///     let (all, last) = owner.rw2(whole, &cells[2]);  // Panics
+    /// });
+    /// ```
+    #[inline]
+    pub fn as_slice_of_cells(&self) -> &[LCell<'id, T>] {
+        sound::lcell_slice_of_cells(self)
+    }
+}
+
+/// Family of types parameterised by an [`LCellOwner`] brand
+/// lifetime, used to name the structure passed between the phases of
+/// [`LCellOwner::scope_build`].
+///
+/// Rust can't yet name a type constructor like `Tree<'_>` directly,
+/// so a marker type implements this trait for every lifetime,
+/// mapping it to the branded type:
+///
+/// ```
+///# use qcell::{BrandFamily, LCell};
+/// struct Tree<'id> {
+///     value: LCell<'id, u32>,
+///     children: Vec<Tree<'id>>,
+/// }
+///
+/// struct TreeFamily;
+/// impl<'id> BrandFamily<'id> for TreeFamily {
+///     type Of = Tree<'id>;
+/// }
+/// ```
+///
+/// [`LCellOwner`]: struct.LCellOwner.html
+/// [`LCellOwner::scope_build`]: struct.LCellOwner.html#method.scope_build
+pub trait BrandFamily<'id> {
+    /// The type for brand lifetime `'id`
+    type Of;
+}
+
+/// Value of a [`BrandFamily`] type, tied to the brand lifetime
+/// `'id` of an [`LCellOwner`].
+///
+/// This is what passes between the phases of
+/// [`LCellOwner::scope_build`].  It derefs to the wrapped value.  The
+/// brand is held in the type even if the family's type doesn't use
+/// it, so a `Branded` can only be used with cells and owners of the
+/// same brand, and can't escape from the scope.  No runtime checks
+/// or unsafe code are involved: this only names the type.
+///
+/// [`BrandFamily`]: trait.BrandFamily.html
+/// [`LCellOwner`]: struct.LCellOwner.html
+/// [`LCellOwner::scope_build`]: struct.LCellOwner.html#method.scope_build
+pub struct Branded<'id, F: BrandFamily<'id>> {
+    _id: Id<'id>,
+    value: F::Of,
+}
+
+impl<'id, F: BrandFamily<'id>> Branded<'id, F> {
+    /// Wrap a value of the family type for brand `'id`
+    #[inline]
+    pub fn new(value: F::Of) -> Self {
+        Self {
+            _id: PhantomData,
+            value,
+        }
+    }
+
+    /// Unwrap the value
+    #[inline]
+    pub fn into_inner(self) -> F::Of {
+        self.value
+    }
+}
+
+impl<'id, F: BrandFamily<'id>> Deref for Branded<'id, F> {
+    type Target = F::Of;
+
+    #[inline]
+    fn deref(&self) -> &F::Of {
+        &self.value
+    }
+}
+
+impl<'id, F: BrandFamily<'id>> DerefMut for Branded<'id, F> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut F::Of {
+        &mut self.value
+    }
+}
+
+/// An [`LCellOwner`] associated with a brand token, as created by
+/// [`LCellOwner::scope_with_token`].
+///
+/// This derefs to the [`LCellOwner`], so it can be used in the same
+/// way.  In addition it can erase cells to [`ErasedLCell`] and
+/// restore them again.  The token is kept here rather than in
+/// [`LCellOwner`] so that [`LCellOwner`] remains zero-sized.
+///
+/// [`LCellOwner`]: struct.LCellOwner.html
+/// [`LCellOwner::scope_with_token`]: struct.LCellOwner.html#method.scope_with_token
+/// [`ErasedLCell`]: struct.ErasedLCell.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct LCellTokenOwner<'id> {
+    owner: LCellOwner<'id>,
+    token: u64,
+}
+
+#[cfg(feature = "std")]
+impl<'id> LCellTokenOwner<'id> {
+    /// Get the brand token of this owner
+    #[inline]
+    pub fn brand_token(&self) -> u64 {
+        self.token
+    }
+
+    /// Consume a cell and convert it into an [`ErasedLCell`], which
+    /// is not tied to the lifetime of this owner, and so may be
+    /// stored anywhere.  The erased cell records this owner's brand
+    /// token, and its contents can only be accessed again by
+    /// restoring it within a scope with the same token.
+    ///
+    /// [`ErasedLCell`]: struct.ErasedLCell.html
+    pub fn erase<T>(&self, cell: LCell<'id, T>) -> ErasedLCell<T> {
+        ErasedLCell {
+            token: self.token,
+            value: cell.value,
+        }
+    }
+
+    /// Get access to a cell previously erased with
+    /// [`LCellTokenOwner::erase`], if it was erased by an owner with
+    /// the same brand token as this one.  Returns `None` if the
+    /// tokens don't match.  The returned cell may be borrowed through
+    /// this owner just like any other cell.  Since its type carries
+    /// this owner's lifetime, it cannot be taken outside of this
+    /// scope.
+    ///
+    /// [`LCellTokenOwner::erase`]: struct.LCellTokenOwner.html#method.erase
+    #[inline]
+    pub fn restore<'a, T: ?Sized>(&self, erased: &'a ErasedLCell<T>) -> Option<&'a LCell<'id, T>> {
+        if erased.token == self.token {
+            // The brand registry guarantees that no other owner with
+            // this token exists right now, so this owner has exclusive
+            // rights to the cell, just as if it had been created in
+            // this scope.
+            // Safety: Exclusive rights, as above
+            Some(unsafe { sound::lcell_from_erased(&erased.value) })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'id> Deref for LCellTokenOwner<'id> {
+    type Target = LCellOwner<'id>;
+
+    #[inline]
+    fn deref(&self) -> &LCellOwner<'id> {
+        &self.owner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'id> DerefMut for LCellTokenOwner<'id> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut LCellOwner<'id> {
+        &mut self.owner
+    }
+}
+
+/// An [`LCell`] which has been detached from the lifetime of its
+/// owner, so that it may be stored in long-lived structures.
+///
+/// Create one with [`LCellTokenOwner::erase`].  The contents can only
+/// be accessed again by calling [`LCellTokenOwner::restore`] on an
+/// owner created by [`LCellOwner::scope_with_token`] with the same brand
+/// token as the owner that erased it.  So within a scope, borrows are
+/// checked at compile-time as normal, but across scopes the owner is
+/// checked at runtime, similar to [`QCell`](struct.QCell.html).
+///
+/// [`LCell`]: struct.LCell.html
+/// [`LCellTokenOwner::erase`]: struct.LCellTokenOwner.html#method.erase
+/// [`LCellTokenOwner::restore`]: struct.LCellTokenOwner.html#method.restore
+/// [`LCellOwner::scope_with_token`]: struct.LCellOwner.html#method.scope_with_token
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct ErasedLCell<T: ?Sized> {
+    token: u64,
+    value: UnsafeCell<T>,
+}
+
+#[cfg(feature = "std")]
+impl<T> ErasedLCell<T> {
+    /// Destroy the cell and return the contained value
+    ///
+    /// Safety: Since this consumes the cell, there can be no other
+    /// references to the cell or the data at this point.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: ?Sized> ErasedLCell<T> {
+    /// Get the brand token of the owner which erased this cell
+    #[inline]
+    pub fn brand_token(&self) -> u64 {
+        self.token
+    }
+
+    /// Returns a mutable reference to the underlying data
+    ///
+    /// Safety: This call borrows `ErasedLCell` mutably which
+    /// guarantees that we possess the only reference.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
 }
 
+// Same reasoning as for `LCell` below
+#[cfg(feature = "std")]
+unsafe impl<T: Send + Sync + ?Sized> Sync for ErasedLCell<T> {}
+
+#[allow(clippy::needless_maybe_sized)]
 impl<'id, T: Default + ?Sized> Default for LCell<'id, T> {
     fn default() -> Self {
         LCell::new(T::default())
@@ -247,9 +1136,113 @@ unsafe impl<'id, T: Send + Sync + ?Sized> Sync for LCell<'id, T> {}
 
 #[cfg(test)]
 mod tests {
-    use super::{LCell, LCellOwner};
+    use super::{BrandFamily, Branded, LCell, LCellOwner};
     use std::rc::Rc;
 
+    #[test]
+    fn lcell_inspect_modify_if() {
+        LCellOwner::scope(|mut owner| {
+            let (state, hits) = (LCell::new(Some(2)), LCell::new(0));
+            // The arms borrow the owner mutably after `inspect` returns
+            match owner.inspect(&state, |s| s.map(|n| n * 2)) {
+                Some(n) => *owner.rw(&hits) += n,
+                None => *owner.rw(&state) = Some(0),
+            }
+            assert_eq!(owner.inspect2(&state, &hits, |s, h| (*s, *h)), (Some(2), 4));
+            assert!(owner.modify_if(&state, |s| s.filter(|n| *n > 1), |s, _| *s = None));
+            assert!(!owner.modify_if(&state, |s| *s, |s, n| *s = Some(n)));
+        });
+    }
+
+    #[test]
+    fn lcell_const_new() {
+        const fn pair<'id>(a: u32, b: u32) -> (LCell<'id, u32>, LCell<'id, u32>) {
+            (LCell::new(a), LCell::new(b))
+        }
+        LCellOwner::scope(|mut owner| {
+            let (c1, c2) = pair(1, 2);
+            let (a, b) = owner.rw2(&c1, &c2);
+            std::mem::swap(a, b);
+            assert_eq!((*owner.ro(&c1), *owner.ro(&c2)), (2, 1));
+        });
+    }
+
+    #[test]
+    fn lcell_replace_take_from_mut() {
+        let mut values = vec![String::from("a"), String::from("b")];
+        LCellOwner::scope(|mut owner| {
+            let c1 = LCell::from_mut(&mut values[0]);
+            let c2 = owner.cell(String::from("c"));
+            let old = c1.replace(String::from("d"), &mut owner);
+            owner.rw(&c2).push_str(&old);
+            assert_eq!(c2.take(&mut owner), "ca");
+            assert_eq!(owner.ro(&c2), "");
+        });
+        assert_eq!(values, ["d", "b"]);
+    }
+
+    #[test]
+    fn lcell_replace_take_slice_of_cells() {
+        let mut values = vec![String::from("a"), String::from("b")];
+        LCellOwner::scope(|mut owner| {
+            let cells = LCell::from_mut(&mut values[..]).as_slice_of_cells();
+            let old = cells[0].replace(String::from("c"), &mut owner);
+            owner.rw(&cells[1]).push_str(&old);
+            assert_eq!(cells[1].take(&mut owner), "ba");
+        });
+        assert_eq!(values, ["c", ""]);
+    }
+
+    // The cell for a whole slice overlaps the cells for its elements,
+    // including the later ones, which start at a different address
+    #[test]
+    fn lcell_slice_of_cells_overlap() {
+        use crate::panic_messages::tests::panic_message;
+        #[cfg(feature = "alloc")]
+        use std::fmt::Debug;
+        let mut values = [1_u32, 2, 3];
+        LCellOwner::scope(|mut owner| {
+            let array: &LCell<'_, [u32; 3]> = LCell::from_mut(&mut values);
+            let whole: &LCell<'_, [u32]> = array;
+            let cells = whole.as_slice_of_cells();
+            let msg = panic_message(|| {
+                owner.rw2(whole, &cells[2]);
+            });
+            assert_eq!(msg, crate::panic_messages::LCELL_BORROWED_TWICE);
+            let msg = panic_message(|| {
+                owner.rw3(&cells[0], &cells[1], whole);
+            });
+            assert_eq!(msg, crate::panic_messages::LCELL_BORROWED_TWICE);
+            let msg = panic_message(|| {
+                owner.rw1_ro_slice(&cells[1], &[whole]);
+            });
+            assert!(msg.ends_with(": readers[0] overlaps the LCell of the writer"), "{}", msg);
+            let msg = panic_message(|| {
+                crate::rw!(owner => &cells[0], whole);
+            });
+            assert!(msg.ends_with(": cells 0 and 1 are the same cell"), "{}", msg);
+            let msg = panic_message(|| {
+                crate::rw!(owner => whole, &cells[1]);
+            });
+            assert!(msg.ends_with(": cells 0 and 1 are overlapping cells"), "{}", msg);
+            #[cfg(feature = "alloc")]
+            {
+                let dyns: [&LCell<'_, dyn Debug>; 3] = [&cells[2], &cells[0], array];
+                let msg = panic_message(|| owner.rw_from_iter(dyns.iter()).len());
+                assert!(msg.ends_with(": cells[0] and cells[2] are overlapping LCells"), "{}", msg);
+            }
+
+            // Separate elements are still fine together
+            let (a, b, c) = owner.rw3(&cells[0], &cells[1], &cells[2]);
+            std::mem::swap(a, c);
+            *b += 10;
+            let readers = [&cells[1], &cells[2]];
+            let (w, r) = owner.rw1_ro_slice(&cells[0], &readers);
+            *w += r[0] + r[1];
+        });
+        assert_eq!(values, [3 + 12 + 1, 12, 1]);
+    }
+
     #[test]
     fn lcell() {
         LCellOwner::scope(|mut owner| {
@@ -264,6 +1257,109 @@ mod tests {
         });
     }
 
+    struct Tree<'id> {
+        value: LCell<'id, u32>,
+        children: Vec<Tree<'id>>,
+    }
+
+    struct TreeFamily;
+    impl<'id> BrandFamily<'id> for TreeFamily {
+        type Of = Tree<'id>;
+    }
+
+    fn build_tree<'id>(owner: &mut LCellOwner<'id>, depth: u32) -> Branded<'id, TreeFamily> {
+        Branded::new(Tree {
+            value: owner.cell(depth),
+            children: (0..depth)
+                .map(|d| build_tree(owner, d).into_inner())
+                .collect(),
+        })
+    }
+
+    // Double every value, returning the number of nodes
+    fn double_tree<'id>(owner: &mut LCellOwner<'id>, tree: &Tree<'id>) -> usize {
+        *owner.rw(&tree.value) *= 2;
+        1 + tree
+            .children
+            .iter()
+            .map(|t| double_tree(owner, t))
+            .sum::<usize>()
+    }
+
+    fn sum_tree<'id>(owner: &LCellOwner<'id>, tree: &Tree<'id>) -> u32 {
+        owner.get(&tree.value)
+            + tree
+                .children
+                .iter()
+                .map(|t| sum_tree(owner, t))
+                .sum::<u32>()
+    }
+
+    #[test]
+    fn lcell_scope_build_tree() {
+        let (nodes, total) = LCellOwner::scope_build(4, build_tree, |owner, mut tree| {
+            let nodes = double_tree(owner, &tree);
+            tree.children.pop();
+            (nodes, sum_tree(owner, &tree))
+        });
+        // Sizes and sums of the subtrees of depth 0..3 are 1, 2, 4, 8
+        // and 0, 1, 3, 7, so removing depth 3 leaves 15 of 16 nodes
+        assert_eq!(nodes, 16);
+        assert_eq!(total, 2 * (4 + 1 + 3));
+    }
+
+    #[cfg(feature = "scoped-threads")]
+    #[test]
+    fn lcell_read_phase_threads() {
+        const READERS: usize = 4;
+        fn parallel_sum<'id>(owner: &LCellOwner<'id>, cells: &[LCell<'id, u64>]) -> u64 {
+            std::thread::scope(|s| {
+                let readers: Vec<_> = cells
+                    .chunks(cells.len() / READERS)
+                    .map(|chunk| s.spawn(move || chunk.iter().map(|c| *owner.ro(c)).sum::<u64>()))
+                    .collect();
+                readers.into_iter().map(|r| r.join().unwrap()).sum()
+            })
+        }
+
+        LCellOwner::scope(|mut owner| {
+            let cells: Vec<LCell<'_, u64>> = (0..10_000).map(|i| owner.cell(i)).collect();
+            let total = 9_999 * 10_000 / 2;
+            assert_eq!(owner.read_phase(|o| parallel_sum(o, &cells)), total);
+            let changed = owner.write_phase(|o| {
+                for cell in &cells[..100] {
+                    *o.rw(cell) += 1;
+                }
+                100
+            });
+            assert_eq!(
+                owner.read_phase(|o| parallel_sum(o, &cells)),
+                total + changed
+            );
+            // Phases may also be entered from within each other
+            owner.write_phase(|o| {
+                let before = o.read_phase(|o| o.get(&cells[0]));
+                o.set(&cells[0], before + 10);
+            });
+            assert_eq!(owner.get(&cells[0]), 11);
+        });
+    }
+
+    #[test]
+    #[cfg(all(feature = "generativity", feature = "alloc"))]
+    fn generativity_leak() {
+        use generativity::{Guard, Id};
+        // Safety: This is the only `'static` guard created in the
+        // program
+        let guard: Guard<'static> = unsafe { Guard::new(Id::new()) };
+        let owner = LCellOwner::new(guard).leak();
+        let cell: &'static LCell<'static, u32> = Box::leak(Box::new(LCell::new(1)));
+        *owner.rw(cell) += 1;
+        let owner: &'static LCellOwner<'static> = owner;
+        let handle = std::thread::spawn(move || *owner.ro(cell));
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+
     #[test]
     #[cfg(feature = "generativity")]
     fn generativity() {
@@ -329,6 +1425,19 @@ mod tests {
         });
     }
 
+    #[test]
+    fn lcell_get_set() {
+        LCellOwner::scope(|mut owner| {
+            let count = owner.cell(2i32);
+            let label = owner.cell(String::from("n"));
+            assert_eq!(owner.update(&count, |n| n * n), 4);
+            owner.set(&count, owner.get(&count) - 1);
+            assert_eq!(owner.get(&count), 3);
+            owner.set(&label, format!("{}={}", owner.get_cloned(&label), owner.get(&count)));
+            assert_eq!(owner.get_cloned(&label), "n=3");
+        });
+    }
+
     #[test]
     fn lcell_get_mut() {
         LCellOwner::scope(|owner| {
@@ -346,6 +1455,61 @@ mod tests {
         assert_eq!(cell.into_inner(), 100);
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn lcell_erase_restore() {
+        let token1 = LCellOwner::brand_token();
+        let token2 = LCellOwner::brand_token();
+        assert_ne!(token1, token2);
+        let mut erased = Vec::new();
+        LCellOwner::scope_with_token(token1, |mut owner| {
+            let c1 = LCell::new(100u32);
+            *owner.rw(&c1) += 1;
+            erased.push(owner.erase(c1));
+        });
+        LCellOwner::scope_with_token(token2, |owner| {
+            assert!(owner.restore(&erased[0]).is_none());
+            erased.push(owner.erase(owner.cell(200u32)));
+        });
+        LCellOwner::scope_with_token(token1, |mut owner| {
+            assert_eq!(owner.brand_token(), token1);
+            assert!(owner.restore(&erased[1]).is_none());
+            let c1 = owner.restore(&erased[0]).unwrap();
+            *owner.rw(c1) += 1;
+            assert_eq!(*owner.ro(c1), 102);
+        });
+        assert_eq!(erased[0].brand_token(), token1);
+        assert_eq!(erased.remove(0).into_inner(), 102);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[should_panic(expected = "already in use")]
+    fn lcell_erase_nested_token() {
+        let token = LCellOwner::brand_token();
+        LCellOwner::scope_with_token(token, |_owner| {
+            LCellOwner::scope_with_token(token, |_owner2| {});
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn lcell_erase_token_released_on_panic() {
+        let token = LCellOwner::brand_token();
+        let result = std::panic::catch_unwind(|| {
+            LCellOwner::scope_with_token(token, |_owner| panic!("test"));
+        });
+        assert!(result.is_err());
+        LCellOwner::scope_with_token(token, |_owner| {});
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[should_panic(expected = "was not obtained from")]
+    fn lcell_erase_bad_token() {
+        LCellOwner::scope_with_token(u64::MAX, |_owner| {});
+    }
+
     #[test]
     fn lcell_unsized() {
         LCellOwner::scope(|mut owner| {
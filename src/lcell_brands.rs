@@ -0,0 +1,173 @@
+//! Worked examples of the compile errors given when an [`LCell`] is
+//! used with the wrong [`LCellOwner`].
+//!
+//! Each owner has a unique brand, which is the `'id` lifetime, and
+//! each cell takes the brand of the first owner that uses it.  Using
+//! the cell with a different owner is then a type error, but the
+//! compiler reports it in terms of lifetimes, often at a point some
+//! way from the real mistake, so the messages can be hard to connect
+//! with the cause.  The three messages below cover most cases.  The
+//! examples are checked by `trybuild` in `trybuild-qcell`, so the
+//! compiler output shown is from a real compiler, and is updated
+//! along with the other compile tests.
+//!
+//! [`LCellOwner::assert_owns`] does nothing at runtime, but states
+//! which owner a cell is meant to belong to.  Adding it where a cell
+//! is first handled moves the error to that line, rather than
+//! somewhere inside a later call.  It is used in most of the examples
+//! below.
+//!
+//! # "borrowed data escapes outside of closure" (E0521)
+//!
+//! Each call to [`LCellOwner::scope`] creates a new brand, which only
+//! exists inside its closure.  A cell created outside an inner scope
+//! can't take the inner owner's brand, since that would let the
+//! brand escape the closure.  The compiler reports both owners as
+//! escaping, pointing at the first use of the cell with the inner
+//! owner.
+//!
+//! Here a cell in a struct is checked against both owners:
+//!
+#![doc = concat!(
+    "```compile_fail\n",
+    include_str!("lcell_brands/escape_nested_scope.rs"),
+    "```"
+)]
+//!
+#![doc = concat!(
+    "```text\n",
+    include_str!("lcell_brands/escape_nested_scope.stderr"),
+    "```"
+)]
+//!
+//! Here the mismatch is hidden inside a helper function taking both
+//! an owner and a cell with the same brand, so the error is reported
+//! at the call:
+//!
+#![doc = concat!(
+    "```compile_fail\n",
+    include_str!("lcell_brands/escape_helper_fn.rs"),
+    "```"
+)]
+//!
+#![doc = concat!(
+    "```text\n",
+    include_str!("lcell_brands/escape_helper_fn.stderr"),
+    "```"
+)]
+//!
+//! Fix: use one owner for all the cells that are used together, by
+//! passing `&owner` or `&mut owner` down to the inner code instead of
+//! starting a new scope.  Cells which really are separate should be
+//! created inside the scope of the owner they belong to.  See also
+//! [QCELL-E010].
+//!
+//! # "lifetime may not live long enough"
+//!
+//! A function which takes an owner and a cell with two different
+//! lifetime parameters can't use that owner for that cell, since the
+//! brands might differ.  The compiler suggests adding a bound such as
+//! `'a: 'b`, and then the reverse, and finally says that the two
+//! lifetimes must be the same.  That last hint is the one to follow.
+//!
+#![doc = concat!(
+    "```compile_fail\n",
+    include_str!("lcell_brands/lifetime_two_brands.rs"),
+    "```"
+)]
+//!
+#![doc = concat!(
+    "```text\n",
+    include_str!("lcell_brands/lifetime_two_brands.stderr"),
+    "```"
+)]
+//!
+//! The same happens when a function creates its own owner from a
+//! [`Guard`] passed in, and is given a cell branded by another owner.
+//! Here the error is reported both where the owner is created and at
+//! the check:
+//!
+#![cfg_attr(feature = "generativity", doc = concat!(
+    "```compile_fail\n",
+    include_str!("lcell_brands/lifetime_guard_param.rs"),
+    "```"
+))]
+#![cfg_attr(not(feature = "generativity"), doc = concat!(
+    "```ignore\n",
+    include_str!("lcell_brands/lifetime_guard_param.rs"),
+    "```"
+))]
+//!
+#![doc = concat!(
+    "```text\n",
+    include_str!("lcell_brands/lifetime_guard_param.stderr"),
+    "```"
+)]
+//!
+//! Fix: use the same lifetime parameter for the owner and for all
+//! the cells it is used with, for example `fn copy_into<'id>(owner:
+//! &mut LCellOwner<'id>, src: &LCell<'id, u32>, dst: &LCell<'id,
+//! u32>)`.  A function should take the owner rather than a guard if
+//! it needs to use cells that already exist.
+//!
+//! # "temporary value dropped while borrowed" (E0716)
+//!
+//! With the **generativity** feature, an owner may be created from a
+//! guard made by `make_guard!`, without a closure.  The brand then
+//! lasts to the end of the enclosing block.  If a cell is used with
+//! owners from two guards, the compiler can't give the two guards
+//! different brands, and reports that the temporary value created
+//! by one of the `make_guard!` calls is dropped while still in use.
+//! The error points at the `make_guard!` line, not at the code using
+//! the cell, even with [`LCellOwner::assert_owns`].  The note about a
+//! `let` binding doesn't help here.
+//!
+#![cfg_attr(feature = "generativity", doc = concat!(
+    "```compile_fail\n",
+    include_str!("lcell_brands/temporary_two_guards.rs"),
+    "```"
+))]
+#![cfg_attr(not(feature = "generativity"), doc = concat!(
+    "```ignore\n",
+    include_str!("lcell_brands/temporary_two_guards.rs"),
+    "```"
+))]
+//!
+#![doc = concat!(
+    "```text\n",
+    include_str!("lcell_brands/temporary_two_guards.stderr"),
+    "```"
+)]
+//!
+//! Here a guard made in an inner block shadows the outer one, and
+//! the cell created with the outer owner is checked against the
+//! inner owner:
+//!
+#![cfg_attr(feature = "generativity", doc = concat!(
+    "```compile_fail\n",
+    include_str!("lcell_brands/temporary_inner_guard.rs"),
+    "```"
+))]
+#![cfg_attr(not(feature = "generativity"), doc = concat!(
+    "```ignore\n",
+    include_str!("lcell_brands/temporary_inner_guard.rs"),
+    "```"
+))]
+//!
+#![doc = concat!(
+    "```text\n",
+    include_str!("lcell_brands/temporary_inner_guard.stderr"),
+    "```"
+)]
+//!
+//! Fix: find the cell that is used with both owners, by adding
+//! [`LCellOwner::assert_owns`] checks and seeing which one makes the
+//! error go away when removed.  Then use a single owner for that
+//! cell, as for the other errors.
+//!
+//! [`LCell`]: ../struct.LCell.html
+//! [`LCellOwner`]: ../struct.LCellOwner.html
+//! [`LCellOwner::assert_owns`]: ../struct.LCellOwner.html#method.assert_owns
+//! [`LCellOwner::scope`]: ../struct.LCellOwner.html#method.scope
+//! [`Guard`]: ../generativity/struct.Guard.html
+//! [QCELL-E010]: ../errors/e010/index.html
@@ -0,0 +1,15 @@
+use qcell::{LCell, LCellOwner};
+
+fn bump<'id>(owner: &mut LCellOwner<'id>, cell: &LCell<'id, u32>) {
+    *owner.rw(cell) += 1;
+}
+
+fn main() {
+    LCellOwner::scope(|mut outer| {
+        let counter = LCell::new(0u32);
+        bump(&mut outer, &counter);
+        LCellOwner::scope(|mut inner| {
+            bump(&mut inner, &counter); // Compile error
+        });
+    });
+}
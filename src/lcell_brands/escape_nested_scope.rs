@@ -0,0 +1,16 @@
+use qcell::{LCell, LCellOwner};
+
+struct Player<'id> {
+    score: LCell<'id, u32>,
+}
+
+fn main() {
+    LCellOwner::scope(|outer| {
+        let player = Player { score: LCell::new(0) };
+        outer.assert_owns(&player.score);
+        LCellOwner::scope(|mut inner| {
+            inner.assert_owns(&player.score); // Compile error
+            *inner.rw(&player.score) += 10;
+        });
+    });
+}
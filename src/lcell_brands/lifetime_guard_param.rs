@@ -0,0 +1,16 @@
+use qcell::generativity::{make_guard, Guard};
+use qcell::{LCell, LCellOwner};
+
+fn reset<'id, 'cell>(guard: Guard<'id>, cell: &LCell<'cell, u32>) {
+    let mut owner = LCellOwner::new(guard); // Compile error
+    owner.assert_owns(cell); // Compile error
+    *owner.rw(cell) = 0;
+}
+
+fn main() {
+    make_guard!(guard);
+    let owner = LCellOwner::new(guard);
+    let cell = owner.cell(5u32);
+    make_guard!(guard2);
+    reset(guard2, &cell);
+}
@@ -0,0 +1,9 @@
+use qcell::{LCell, LCellOwner};
+
+fn copy_into<'a, 'b>(owner: &mut LCellOwner<'a>, src: &LCell<'a, u32>, dst: &LCell<'b, u32>) {
+    let value = *owner.ro(src);
+    owner.assert_owns(dst); // Compile error
+    *owner.rw(dst) = value;
+}
+
+fn main() {}
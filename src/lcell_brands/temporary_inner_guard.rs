@@ -0,0 +1,18 @@
+use qcell::generativity::make_guard;
+use qcell::{LCell, LCellOwner};
+
+struct Counter<'id> {
+    hits: LCell<'id, u32>,
+}
+
+fn main() {
+    make_guard!(guard);
+    let mut owner = LCellOwner::new(guard);
+    let counter = Counter { hits: LCell::new(0) };
+    *owner.rw(&counter.hits) += 1;
+    {
+        make_guard!(guard); // Compile error
+        let owner = LCellOwner::new(guard);
+        owner.assert_owns(&counter.hits);
+    }
+}
@@ -0,0 +1,12 @@
+use qcell::generativity::make_guard;
+use qcell::{LCell, LCellOwner};
+
+fn main() {
+    make_guard!(guard1);
+    make_guard!(guard2); // Compile error
+    let owner1 = LCellOwner::new(guard1);
+    let owner2 = LCellOwner::new(guard2);
+    let cell = LCell::new(100u32);
+    owner1.assert_owns(&cell);
+    owner2.assert_owns(&cell);
+}
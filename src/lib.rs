@@ -53,6 +53,9 @@
 //!
 //! # Examples
 //!
+//! The examples below import everything through the [`prelude`],
+//! which covers the usual setups.
+//!
 //! With [`RefCell`], this compiles but panics:
 //!
 //! ```should_panic
@@ -71,16 +74,15 @@
 //! With [`QCell`], it refuses to compile:
 //!
 //! ```compile_fail
-//!# use qcell::{QCell, QCellOwner};
-//!# use std::rc::Rc;
+//! use qcell::prelude::*;
 //! let mut owner = QCellOwner::new();
 //!
-//! let item = Rc::new(QCell::new(&owner, Vec::<u8>::new()));
+//! let item = new_shared(&owner, Vec::<u8>::new());
 //! let iref = owner.rw(&item);
 //! test(&mut owner, &item);    // Compile error
 //! iref.push(1);
 //!
-//! fn test(owner: &mut QCellOwner, item: &Rc<QCell<Vec<u8>>>) {
+//! fn test(owner: &mut QCellOwner, item: &Shared<Vec<u8>>) {
 //!     owner.rw(&item).push(2);
 //! }
 //! ```
@@ -93,10 +95,10 @@
 //! Here's a working version using [`TCell`] instead:
 //!
 #![cfg_attr(
-    feature = "std",
+    all(feature = "std", not(feature = "strict-markers")),
     doc = "
  ```
-# use qcell::{TCell, TCellOwner};
+ use qcell::prelude::*;
 # use std::rc::Rc;
  struct Marker;
  type ACell<T> = TCell<Marker, T>;
@@ -118,7 +120,7 @@
 //! And the same thing again using [`LCell`]:
 //!
 //! ```
-//!# use qcell::{LCell, LCellOwner};
+//! use qcell::prelude::*;
 //!# use std::rc::Rc;
 //! LCellOwner::scope(|mut owner| {
 //!     let item = Rc::new(LCell::new(Vec::<u8>::new()));
@@ -161,6 +163,11 @@
 //! last strong reference to be released until that borrow is
 //! released.
 //!
+//! The [`soundness_claims`] module states these points as separate
+//! claims (G1 to G3), along with the claims specific to each owner
+//! type, and lists the tests which check each one, including tests
+//! run under Miri.
+//!
 //! If you can see a flaw in this reasoning or in the code, please
 //! raise an issue, preferably with test code which demonstrates the
 //! problem.  MIRI in the Rust playground can report on some kinds of
@@ -296,6 +303,35 @@
 //! that case the right to access the data is being transferred
 //! completely from one thread to another.
 //!
+//! To make a hand-over of an owner between threads explicit in the
+//! code, for example for an audit, use [`OwnerTransfer`].  The owner
+//! is passed by value, so no references obtained through it can still
+//! be in use on the old thread:
+//!
+#![cfg_attr(
+    feature = "std",
+    doc = "
+ ```
+ # use qcell::QCellOwner;
+ # use std::sync::Arc;
+ let mut owner = QCellOwner::new();
+ let cell = Arc::new(owner.cell(vec![1]));
+ owner.rw(&cell).push(2);
+ let transfer = owner.into_transfer();
+ let cell2 = cell.clone();
+ let owner = std::thread::spawn(move || {
+     let mut owner = transfer.redeem();
+     owner.rw(&cell2).push(3);
+     owner.into_transfer()
+ })
+ .join()
+ .unwrap()
+ .redeem();
+ assert_eq!(owner.ro(&cell)[..], [1, 2, 3]);
+ ```
+"
+)]
+//!
 //! # Multi-threaded use: RwLock
 //!
 //! `QCell` and similar types can also be used as a replacement for
@@ -311,6 +347,89 @@
 //! `QCellOwner`, then you get access to the `T` instances essentially
 //! for free.
 //!
+//! Where there is one writer thread and many reader threads, an
+//! `RwLock` around the owner may let the readers starve the writer.
+//! [`QCellOwner::split_rw_sched`] splits the owner into a
+//! [`WriteHalf`] and a [`ReadHalf`] instead, which give the writer
+//! priority and cost readers two atomic operations per read.
+//!
+//! # Panics and unwind safety
+//!
+//! The cells treat unwind safety the same way as `RefCell`.  A cell
+//! is `UnwindSafe` if its contents are, but is never
+//! `RefUnwindSafe`, so a closure passed to `catch_unwind` can't
+//! capture `&cell` without wrapping it in `AssertUnwindSafe`.  This
+//! is deliberate: a panic part-way through a `rw` borrow may leave
+//! the contents with their invariants broken, and nothing records
+//! that it happened.  If you need to find out, use [`PoisonCell`],
+//! which marks its contents as poisoned when a panic escapes
+//! [`PoisonCell::with_mut`].
+//!
+//! The owners hold no contents, so unwinding can't leave an owner in
+//! a bad state.  The bit-set cells are `RefUnwindSafe` like the
+//! atomic types, since each operation on them is a single update.
+//!
+//! # Intrusive lists
+//!
+//! The [`intrusive`] module shows how to build an intrusive list on
+//! [`QCellOwnerPinned`], with the owner embedded in the list header,
+//! and the links of each node in a [`QCell`] owned by it.
+//!
+//! # Iterating with an owner
+//!
+//! A closure in an iterator chain can't easily borrow cells through a
+//! captured `&mut owner`.  The [`iter`] module has adapters such as
+//! `map_with_owner` which pass the owner to the closure for each item
+//! instead.
+//!
+//! # Context structs
+//!
+//! The [`context!`] macro defines a struct holding an owner and the
+//! cells it owns, with an accessor for each cell, and combined
+//! accessors which borrow two cells mutably at once through `rw2`.
+//!
+//! # Migrating from `RefCell`
+//!
+//! The [`migration`] module provides `QRefCell`, a drop-in
+//! replacement for [`RefCell`] built on [`QCell`], which allows a
+//! large codebase to be converted to explicit owner threading
+//! gradually instead of all at once.
+//!
+//! # A global owner for prototyping
+//!
+//! The [`global`] module has a single process-wide owner behind a
+//! lock, with cells that are accessed through closures, much like a
+//! `Mutex`.  This gives up the compile-time guarantees, so it is only
+//! meant for prototypes, examples and tests, and as a first step
+//! towards explicit owners.  The module documentation shows how to
+//! convert code away from it.
+//!
+//! # Migrating from `GhostCell`
+//!
+//! Enabling the **ghost-compat** feature adds the [`ghost_compat`]
+//! module, with `GhostToken` and `GhostCell` wrappers over
+//! [`LCellOwner`] and [`LCell`] that have the API of the
+//! [**ghost-cell**](https://crates.io/crates/ghost-cell) crate, so
+//! that code can be switched over by changing the crate path, and
+//! then converted to the **qcell** names gradually.
+//!
+//! # Upgrading from 0.4
+//!
+//! Enabling the **compat-0-4** feature adds deprecated shims for the
+//! items which have been removed since 0.4, so that a dependency can
+//! be upgraded first and the deprecation warnings fixed afterwards.
+//! The [`compat_0_4`] module lists the changes.
+//!
+//! # Owner IDs from an existing token system
+//!
+//! [`XCell`] and [`XCellOwner`] work like [`QCell`] and
+//! [`QCellOwnerSeq`], but take their owner IDs from a type which
+//! implements the unsafe trait [`OwnerIdScheme`].  An application
+//! which already issues unforgeable tokens can use those as the owner
+//! IDs, so that one token governs both its own resources and the
+//! cells.  The implementation must guarantee that owner IDs are
+//! unique, since soundness depends on it.
+//!
 //! # `no_std` support
 //!
 //! There are four levels at which **qcell** crate can be built:
@@ -327,7 +446,137 @@
 //! - `no_std` without `alloc`, when built with `--no-default-features`
 //!
 //! Both [`QCell`] and [`LCell`] support all four levels, and
-//! [`TCell`] is also available for the first two.
+//! [`TCell`] is also available for the first two.  At the other two
+//! levels, [`TCell`] can still be used with markers that implement
+//! [`IndexedMarker`], whose owners are created with
+//! `TCellOwner::new_indexed`.
+//!
+//! For [`LCell`], everything apart from the brand tokens used by
+//! `ErasedLCell` works without `std`, since those need a global
+//! lock.  That includes `LCellOwner::new` with a `make_guard!` guard
+//! when the **generativity** feature is enabled.  Run
+//! `./run-test-no-std` to build and test an [`LCell`] workload in a
+//! `#![no_std]` crate with just the **generativity** and **alloc**
+//! features.
+//!
+//! # Strict marker types
+//!
+//! By default any `'static` type may be used as the marker type for
+//! [`TCell`] or [`TLCell`].  If a marker type is public, unrelated
+//! code may create an owner for it, which causes a panic when your
+//! own owner is created.  Enabling the **strict-markers** feature
+//! means that only marker types declared with the [`marker!`] macro
+//! are accepted, which ensures that they are all private unit
+//! structs.  See [`TCellMarker`] for details.
+//!
+//! This feature is not additive, so only a final binary should enable
+//! it, never a library.  With it enabled, every crate in the build
+//! that uses a plain struct as a marker stops compiling.  Libraries
+//! should declare their markers with [`marker!`] anyway, so that they
+//! build whether or not the binary enables the feature.
+//!
+//! The panic message for a second owner gives the source location
+//! where the existing owner was created.  Enabling the
+//! **owner-backtrace** feature adds a backtrace of its creation.
+//! This feature requires Rust 1.65.
+//!
+//! Markers declared as `marker!(struct Name = index;)` also implement
+//! [`IndexedMarker`], which gives each marker one of 64 fixed indices.
+//! Owners for these markers may be created with
+//! `TCellOwner::new_indexed`, where the singleton check is a single
+//! atomic bit operation, with no lock, hashing or allocation.  This
+//! suits a bounded set of markers whose owners are created and
+//! dropped often, for example one per plugin.
+//!
+//! # Parallel access to `TCell` contents
+//!
+//! Enabling the **scoped-threads** feature adds [`scope_chunks`],
+//! which borrows the contents of a slice of [`TCell`] instances via
+//! the owner, and mutates them in parallel in disjoint chunks using
+//! scoped threads.  This feature requires Rust 1.63.
+//!
+//! Enabling the **rayon** feature adds `par_iter` and `par_values`
+//! to the owners of `Sync` cells, which borrow a collection in a cell
+//! once, and iterate over it in parallel using `rayon`.  The owner
+//! stays borrowed for as long as the parallel iterator exists, so the
+//! contents can't be modified meanwhile.  See
+//! [`QCellOwner::par_iter`].
+//!
+//! # Lending `TLCell` contents to another thread
+//!
+//! [`TLCellOwner::lend_to_thread`] moves the value out of a [`TLCell`]
+//! to a worker thread, and moves it back when the returned handle is
+//! joined, so the cell and any `Rc` references to it stay put.  With
+//! the **async** feature the handle can also be awaited.  With the
+//! **scoped-threads** feature, `TLCellOwner::lend_to_scope` lends the
+//! value in place to a scoped thread and waits for it.
+//!
+//! # WebAssembly
+//!
+//! All the cell types work on `wasm32-unknown-unknown`.  Without the
+//! "atomics" target feature there is only one thread, so the
+//! [`TCellOwner`] singleton check uses a thread-local set instead of
+//! a global lock, and `TCellOwner::wait_for_new` is not available,
+//! since waiting would never end.  [`scope_chunks`] runs all the
+//! chunks on the calling thread when no more parallelism is
+//! available.  Run `./run-test-wasm` to test on that target.
+//!
+//! # Sharing an owner between async tasks
+//!
+//! Enabling the **async-tokio** feature adds [`AsyncOwner`], which
+//! wraps an owner in a `tokio` mutex so that it can be shared between
+//! tasks.  Access is only given within synchronous closures, so cell
+//! borrows can't be held across an `.await`.
+//!
+//! # Locking several shared owners
+//!
+//! Where owners are shared between threads behind a `Mutex` or
+//! `RwLock`, use [`lock_owners2`] and the other functions in the
+//! [`lock_order`] module to take more than one of them at a time.
+//! These always lock in the same order, so they can't deadlock
+//! against each other.  Enabling the **debug-lock-order** feature
+//! adds runtime checks which panic when owner locks are taken in
+//! inconsistent orders.
+//!
+//! # Recording owner mutations
+//!
+//! Enabling the **record** feature adds the [`record`] module, with a
+//! `Recorder` that can be attached to a [`QCellOwner`] to log the
+//! sequence of mutable accesses made through it, and a test helper to
+//! replay that sequence against other cells.  This helps reproduce
+//! bugs where a value is wrong after a long series of changes.
+//!
+//! # Listing live owners
+//!
+//! Enabling the **diagnostics** feature adds the [`diagnostics`]
+//! module, with a process-wide registry of the owners which currently
+//! exist, for example to show on a diagnostics page how many
+//! [`QCellOwner`] instances there are, or which [`TCellOwner`] marker
+//! types are claimed.  Owners are registered when created with
+//! `new_registered`, or all owners once [`diagnostics::enable`] has
+//! been called.  Without the feature the owners are unchanged.
+//!
+//! # Measuring memory usage
+//!
+//! Enabling the **measure** feature adds the [`measure`] module, with
+//! a [`CellMeasure`] trait for measuring the heap memory owned by a
+//! value, and [`QCellOwner::measure_graph`], which walks a graph of
+//! `Rc<QCell<_>>` nodes and totals the memory used by type.
+//!
+//! # Sharing cells with native plugins
+//!
+//! Enabling the **ffi-host** feature adds the [`ffi_host`] module,
+//! which lets plugins loaded through a C ABI read and write cells
+//! owned by the host.  The cells are passed to the plugin as integer
+//! handles, and accessed through a table of `extern "C"` functions
+//! which lock the host's owner and check the type of each cell.
+//!
+//! # Unsizing cells on stable Rust
+//!
+//! Enabling the **unsize** feature adds the [`unsize`](unsize/index.html)
+//! module, which works with the `unsize` crate to unsize a `Box`, `Rc`
+//! or `Arc` of any of the four cell types to a cell holding a trait
+//! object or slice, using a `Coercion` value for the contents.
 //!
 //! # Origin of names
 //!
@@ -340,7 +589,11 @@
 //! See the [`doctest_qcell`], [`doctest_tcell`], [`doctest_tlcell`],
 //! [`doctest_lcell`], [`doctest_qcell_noalloc`] and
 //! [`doctest_lcell_generativity`] modules, whose docs and doc-tests
-//! show which unsafe patterns are blocked.
+//! show which unsafe patterns are blocked.  The most common of these
+//! are explained in the [`errors`] module, with the compiler output
+//! and how to fix the code.  The errors from using an [`LCell`] with
+//! the wrong owner are covered in more depth in the [`lcell_brands`]
+//! module.
 //!
 //! [`RefCell`]: https://doc.rust-lang.org/std/cell/struct.RefCell.html
 //! [`RwLock`]: https://doc.rust-lang.org/std/sync/struct.RwLock.html
@@ -350,8 +603,45 @@
 //! [`TCellOwner`]: struct.TCellOwner.html
 //! [`TLCell`]: struct.TLCell.html
 //! [`TLCellOwner`]: struct.TLCellOwner.html
+//! [`TLCellOwner::lend_to_thread`]: struct.TLCellOwner.html#method.lend_to_thread
 //! [`LCell`]: struct.LCell.html
 //! [`LCellOwner`]: struct.LCellOwner.html
+//! [`TCellMarker`]: trait.TCellMarker.html
+//! [`IndexedMarker`]: trait.IndexedMarker.html
+//! [`migration`]: migration/index.html
+//! [`ghost_compat`]: ghost_compat/index.html
+//! [`compat_0_4`]: compat_0_4/index.html
+//! [`global`]: global/index.html
+//! [`intrusive`]: intrusive/index.html
+//! [`iter`]: iter/index.html
+//! [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+//! [`PoisonCell`]: struct.PoisonCell.html
+//! [`OwnerTransfer`]: struct.OwnerTransfer.html
+//! [`QCellOwner::split_rw_sched`]: struct.QCellOwner.html#method.split_rw_sched
+//! [`WriteHalf`]: struct.WriteHalf.html
+//! [`ReadHalf`]: struct.ReadHalf.html
+//! [`PoisonCell::with_mut`]: struct.PoisonCell.html#method.with_mut
+//! [`scope_chunks`]: fn.scope_chunks.html
+//! [`QCellOwner::par_iter`]: struct.QCellOwner.html#method.par_iter
+//! [`AsyncOwner`]: struct.AsyncOwner.html
+//! [`lock_owners2`]: fn.lock_owners2.html
+//! [`lock_order`]: lock_order/index.html
+//! [`context!`]: macro.context.html
+//! [`XCell`]: struct.XCell.html
+//! [`XCellOwner`]: struct.XCellOwner.html
+//! [`OwnerIdScheme`]: trait.OwnerIdScheme.html
+//! [`diagnostics`]: diagnostics/index.html
+//! [`diagnostics::enable`]: diagnostics/fn.enable.html
+//! [`record`]: record/index.html
+//! [`ffi_host`]: ffi_host/index.html
+//! [`measure`]: measure/index.html
+//! [`CellMeasure`]: measure/trait.CellMeasure.html
+//! [`QCellOwner::measure_graph`]: struct.QCellOwner.html#method.measure_graph
+//! [`soundness_claims`]: soundness_claims/index.html
+//! [`prelude`]: prelude/index.html
+//! [`marker!`]: macro.marker.html
+//! [`errors`]: errors/index.html
+//! [`lcell_brands`]: lcell_brands/index.html
 //! [`doctest_qcell`]: doctest_qcell/index.html
 //! [`doctest_qcell_noalloc`]: doctest_qcell_noalloc/index.html
 //! [`doctest_tcell`]: doctest_tcell/index.html
@@ -368,22 +658,188 @@
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+mod access_set;
+#[cfg(feature = "async-tokio")]
+mod async_owner;
+mod batch;
+mod bit_cells;
+mod capability;
+mod cell_assert;
+#[cfg(feature = "std")]
+mod cell_graph_builder;
+#[cfg(feature = "alloc")]
+mod compact;
+#[cfg(feature = "alloc")]
+mod context;
+#[cfg(feature = "alloc")]
+mod cell_arena;
+#[cfg(feature = "alloc")]
+mod cell_ptr;
+#[cfg(feature = "alloc")]
+mod clone_graph;
+#[cfg(feature = "alloc")]
+mod fn_cell;
+#[cfg(feature = "alloc")]
+mod graph_cells;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod collect;
+pub mod intrusive;
+pub mod iter;
 mod lcell;
+#[cfg(feature = "alloc")]
+mod observed;
+#[cfg(feature = "std")]
+mod owner_groups;
+#[cfg(feature = "std")]
+mod owner_transfer;
+#[cfg(feature = "rayon")]
+mod par_iter;
+#[cfg(feature = "std")]
+mod park;
+#[cfg(feature = "alloc")]
+mod phase_cell;
+mod pinned_owner;
+mod poison;
 mod qcell;
+mod qcell_any;
+#[cfg(feature = "alloc")]
+mod qcell_dyn;
+#[cfg(feature = "alloc")]
+mod qcell_gen;
+mod qcell_namespace;
+#[cfg(feature = "std")]
+mod qcell_partition;
+#[cfg(feature = "std")]
+mod qcell_split;
+#[cfg(feature = "alloc")]
+mod qcell_local;
+#[cfg(feature = "alloc")]
+mod qcell_quota;
+#[cfg(feature = "alloc")]
+mod qcell_noid;
+#[cfg(feature = "alloc")]
+mod qcell_pool;
+mod qcell_single;
+#[cfg(feature = "std")]
+mod rcu_cell;
+mod ro_slice;
+#[cfg(feature = "alloc")]
+mod rw_many;
+#[cfg(feature = "alloc")]
+mod shared;
+mod scoped_cell;
+mod sound;
+mod step_cell;
+#[cfg(feature = "alloc")]
+mod sub_cell;
 mod tcell;
+#[cfg(target_has_atomic = "64")]
+mod tcell_indexed;
+mod tcell_slot;
+mod tcell_witness;
+mod tuple;
+// Uses `std::thread::scope`, so needs Rust 1.63
+#[cfg(feature = "scoped-threads")]
+#[clippy::msrv = "1.63"]
+mod tcell_chunks;
+#[cfg(feature = "std")]
+mod tcell_lifecycle;
+#[cfg(feature = "std")]
+mod tcell_sharded;
 #[cfg(feature = "std")]
 mod tlcell;
+#[cfg(feature = "std")]
+mod tlcell_lend;
+#[cfg(feature = "std")]
+mod tlcell_migrate;
+mod versioned;
+mod view;
+mod xcell;
 
+pub mod doctest_access_set;
+#[cfg(feature = "async-tokio")]
+pub mod doctest_async_owner;
+#[cfg(feature = "alloc")]
+pub mod doctest_capability;
+#[cfg(feature = "alloc")]
+pub mod doctest_cell_arena;
+#[cfg(feature = "alloc")]
+pub mod doctest_context;
+pub mod doctest_intrusive;
 pub mod doctest_lcell;
+#[cfg(feature = "std")]
+pub mod doctest_lcell_erased;
 #[cfg(feature = "generativity")]
 pub mod doctest_lcell_generativity;
+#[cfg(feature = "rayon")]
+pub mod doctest_par_iter;
 #[cfg(feature = "alloc")]
 pub mod doctest_qcell;
 pub mod doctest_qcell_noalloc;
-#[cfg(feature = "std")]
+pub mod doctest_rw_distinct;
+#[cfg(feature = "scoped-threads")]
+pub mod doctest_scope_chunks;
+#[cfg(all(feature = "std", not(feature = "strict-markers")))]
 pub mod doctest_tcell;
+#[cfg(all(feature = "std", feature = "strict-markers"))]
+pub mod doctest_tcell_strict;
 #[cfg(feature = "std")]
+pub mod doctest_tcell_witness;
+#[cfg(all(feature = "std", not(feature = "strict-markers")))]
 pub mod doctest_tlcell;
+#[cfg(feature = "std")]
+pub mod doctest_tlcell_lend;
+
+#[cfg(feature = "compat-0-4")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compat-0-4")))]
+pub mod compat_0_4;
+#[cfg(feature = "diagnostics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+pub mod diagnostics;
+#[cfg(feature = "ffi-host")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ffi-host")))]
+pub mod ffi_host;
+#[cfg(feature = "ghost-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ghost-compat")))]
+pub mod ghost_compat;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod global;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod lock_order;
+#[cfg(feature = "measure")]
+#[cfg_attr(docsrs, doc(cfg(feature = "measure")))]
+pub mod measure;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod migration;
+
+pub mod errors;
+pub mod lcell_brands;
+pub mod panic_messages;
+pub mod prelude;
+pub mod q;
+#[cfg(feature = "std")]
+pub mod soundness_claims;
+#[cfg(feature = "record")]
+#[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+pub mod record;
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod teardown;
+
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "std", feature = "exclusion-set"))))]
+pub mod typeid_gate;
+pub mod util;
+// Uses `pointer::wrapping_byte_sub`, so needs Rust 1.75
+#[cfg(feature = "unsize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "unsize")))]
+#[clippy::msrv = "1.75"]
+pub mod unsize;
 
 #[cfg(feature = "generativity")]
 pub extern crate generativity;
@@ -407,22 +863,134 @@ pub extern crate generativity;
 //
 // Needs an abstraction as a struct, since otherwise we'll get errors
 // regarding "function pointers cannot appear in constant functions"
+#[allow(dead_code)]
 struct Invariant<T>(fn(T) -> T);
 
+pub use crate::access_set::{AccessSet, AssertCompatible};
+pub use crate::bit_cells::{BitCellArray, BitCellSet, BitSliceMut};
+pub use crate::capability::{Capability, ReadOnly, WriteOnly};
+pub use crate::lcell::BrandFamily;
+pub use crate::lcell::Branded;
+#[cfg(feature = "std")]
+pub use crate::lcell::ErasedLCell;
 pub use crate::lcell::LCell;
 pub use crate::lcell::LCellOwner;
+#[cfg(feature = "std")]
+pub use crate::lcell::LCellTokenOwner;
+pub use crate::pinned_owner::PinnedOwnerFuture;
+pub use crate::poison::{CellPoisoned, PoisonCell, PoisonState};
 pub use crate::qcell::QCell;
+pub use crate::qcell_any::{OwnerMut, OwnerRef};
+pub use crate::qcell::QCellBorrow;
 pub use crate::qcell::QCellOwnerID;
 pub use crate::qcell::QCellOwnerPinned;
 pub use crate::qcell::QCellOwnerSeq;
+pub use crate::qcell_namespace::QCellOwnerNamespace;
+pub use crate::qcell_single::QCellOwnerSingle;
+pub use crate::qcell_single::QCellSingle;
+pub use crate::ro_slice::{RoSlice, RoSliceIter};
+pub use crate::scoped_cell::{scoped_cell, scoped_cell_with};
+pub use crate::step_cell::{LStepCell, StepCell, StepOutcome, StepResult};
 pub use crate::tcell::TCell;
+pub use crate::tcell::TCellMarker;
 pub use crate::tcell::TCellOwner;
+#[cfg(target_has_atomic = "64")]
+pub use crate::tcell_indexed::{IndexedMarker, IndexedTCellOwner};
+pub use crate::tcell_slot::{SlotMarker, TCellSlot, TCellSlotOwner};
+pub use crate::tcell_witness::{Guarded, RoWitness, RwWitness};
+pub use crate::versioned::{VersionConflict, VersionedCell};
+pub use crate::view::{SliceView, SliceViewMut};
+pub use crate::xcell::{OwnerIdScheme, XCell, XCellOwner};
+#[doc(hidden)]
+#[cfg(feature = "alloc")]
+pub use alloc::rc::Rc as __ContextRc;
+#[doc(hidden)]
+#[cfg(feature = "alloc")]
+pub use crate::cell_assert::{cell_assert_failed as __cell_assert_failed, cell_debug as __cell_debug};
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use crate::cell_assert::debug_cell as __debug_cell;
+#[doc(hidden)]
+pub use crate::pinned_owner::pin_owner_unchecked as __pin_owner_unchecked;
+#[doc(hidden)]
+pub use crate::tuple::{
+    rw_tuple as __rw_tuple, rw_tuple_unchecked as __rw_tuple_unchecked,
+    RwTupleOwner as __RwTupleOwner,
+};
 
+#[cfg(feature = "alloc")]
+pub use crate::clone_graph::{
+    clone_graph, clone_graph_rewrite, clone_graph_rewrite_in, RewriteRefs,
+};
+#[cfg(feature = "alloc")]
+pub use crate::bit_cells::BitCells;
+#[cfg(feature = "alloc")]
+pub use crate::cell_arena::{ArenaRef, CellArena, LCellArena, TCellArena};
+#[cfg(feature = "alloc")]
+pub use crate::cell_ptr::{CellArc, CellPtr, WeakCellArc, WeakCellPtr};
+#[cfg(feature = "alloc")]
+pub use crate::compact::{CompactRegistry, Compactable, SweepStats};
+#[cfg(feature = "alloc")]
+pub use crate::fn_cell::FnCell;
+#[cfg(feature = "alloc")]
+pub use crate::sub_cell::{SubCell, SubCellError};
+#[cfg(feature = "alloc")]
+pub use crate::phase_cell::{PhaseCell, PhaseError, PhaseHandle};
+#[cfg(feature = "alloc")]
+pub use crate::graph_cells::{CellEdge, GraphCells, NodeId};
+#[cfg(feature = "alloc")]
+pub use crate::observed::{ObservedCell, ObservedGuard, SubscriptionId};
 #[cfg(feature = "alloc")]
 pub use crate::qcell::QCellOwner;
+#[cfg(feature = "alloc")]
+pub use crate::qcell_gen::{QCellOwnerGen, WeakCellRef};
+#[cfg(feature = "alloc")]
+pub use crate::qcell_local::{QCellOwnerLocal, QCellOwnerLocalRef};
+#[cfg(feature = "alloc")]
+pub use crate::qcell_noid::{OwnedCells, QCellNoId, QCellRef};
+#[cfg(feature = "alloc")]
+pub use crate::qcell_pool::{CellPool, PooledCell};
+#[cfg(feature = "alloc")]
+pub use crate::qcell_quota::{QuotaExceeded, QuotaHandle, QuotaOwnerID};
+#[cfg(feature = "alloc")]
+pub use crate::shared::{new_shared, new_shared_sync, Shared, SharedSync};
+#[cfg(feature = "alloc")]
+pub use crate::view::VecViewMut;
 
+#[cfg(feature = "async-tokio")]
+pub use crate::async_owner::AsyncOwner;
+#[cfg(feature = "scoped-threads")]
+pub use crate::tcell_chunks::{scope_chunks, try_scope_chunks};
+#[cfg(feature = "std")]
+pub use crate::cell_arena::SyncCellArena;
+#[cfg(feature = "std")]
+pub use crate::cell_graph_builder::{
+    CellGraphBuilder, CellLink, Declared, DeclaredCell, GraphBuildError, GraphHandles,
+};
+#[cfg(feature = "std")]
+pub use crate::lock_order::{lock_owners2, lock_owners3};
+#[cfg(feature = "std")]
+pub use crate::owner_groups::{partition_by_owner, sort_by_owner};
+#[cfg(feature = "std")]
+pub use crate::qcell_partition::{PartOwner, Partition, PartitionError};
+#[cfg(feature = "std")]
+pub use crate::qcell_split::{ReadHalf, ReadPhase, WriteHalf};
+#[cfg(feature = "std")]
+pub use crate::owner_transfer::{OwnerTransfer, TCellOwnerTransfer, TransferableOwner};
+#[cfg(feature = "std")]
+pub use crate::rcu_cell::RcuCell;
+#[cfg(feature = "std")]
+pub use crate::tcell_lifecycle::OwnerLifecycle;
+#[cfg(feature = "std")]
+pub use crate::tcell_slot::{TLCellSlot, TLCellSlotOwner};
+#[cfg(feature = "std")]
+pub use crate::tcell_sharded::{ShardedTCellOwner, TShardCell, TShardOwner};
+#[cfg(feature = "std")]
+pub use crate::teardown::teardown;
+#[cfg(feature = "std")]
+pub use crate::{tlcell::TLCell, tlcell::TLCellOwner, tlcell_lend::TLCellLend};
 #[cfg(feature = "std")]
-pub use crate::{tlcell::TLCell, tlcell::TLCellOwner};
+pub use crate::tlcell_migrate::{tl_migrate, MigrationPackage};
 
 // Static assertions on traits
 #[cfg(test)]
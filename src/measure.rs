@@ -0,0 +1,447 @@
+//! Memory usage accounting for graphs of [`QCell`] nodes.
+//!
+//! Generic code can't look inside a cell without the owner, so
+//! measuring a graph of `Rc<QCell<_>>` nodes needs the owner plumbed
+//! through.  [`QCellOwner::measure_graph`] does this: it walks the
+//! graph from a set of roots, and calls [`CellMeasure::size_of`] on
+//! the contents of each node.  The trait is modelled on the
+//! `MallocSizeOf` trait from the `malloc_size_of` crate, but without
+//! a hook into the allocator, so sizes are estimated from the types
+//! and capacities involved.
+//!
+//! The accounting rules are as follows:
+//!
+//! - Nodes are discovered breadth-first from the roots, and are
+//!   deduplicated by pointer identity, in the same way as for
+//!   [`teardown`].  So a node reachable by several paths is counted
+//!   only once.
+//!
+//! - For each node, the driver counts the size of the `Rc`
+//!   allocation that holds the cell, which is the two reference
+//!   counts plus `size_of::<QCell<T>>()`, rounded up to the alignment
+//!   of the cell.  This includes the inline part of the value.
+//!
+//! - [`CellMeasure::size_of`] returns only the heap memory owned by
+//!   the value, not its inline size, which has already been counted
+//!   by whatever contains it.  For example a `Vec<T>` counts its
+//!   capacity times `size_of::<T>()`, plus the heap memory owned by
+//!   each element.  Handles to other nodes of the graph should be
+//!   skipped, since those nodes are counted by the driver.
+//!
+//! - Shared data behind `Rc` or `Arc` is counted the first time that
+//!   it is seen, using [`MeasureOps::have_seen_ptr`].
+//!
+//! - The totals are grouped by [`CellMeasure::type_name`], which by
+//!   default is the name of the implementing type, so a graph of
+//!   `dyn Trait` nodes is split by concrete type.
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwner};
+//!# use qcell::measure::{CellMeasure, MeasureOps};
+//!# use std::rc::Rc;
+//! struct Node {
+//!     name: String,
+//!     peers: Vec<Rc<QCell<Node>>>,
+//! }
+//!
+//! impl CellMeasure for Node {
+//!     fn size_of(&self, ops: &mut MeasureOps) -> usize {
+//!         // Count the `peers` vector, but not the nodes it refers to
+//!         self.name.size_of(ops)
+//!             + self.peers.capacity() * std::mem::size_of::<Rc<QCell<Node>>>()
+//!     }
+//! }
+//!
+//! let owner = QCellOwner::new();
+//! let leaf = Rc::new(owner.cell(Node { name: "leaf".into(), peers: Vec::new() }));
+//! let root = Rc::new(owner.cell(Node {
+//!     name: String::with_capacity(10),
+//!     peers: vec![leaf.clone(), leaf],
+//! }));
+//! let report = owner.measure_graph(vec![root], |node, found| {
+//!     found.extend(node.peers.iter().cloned())
+//! });
+//! assert_eq!(report.cells, 2);
+//! let usage = &report.by_type[std::any::type_name::<Node>()];
+//! assert_eq!(usage.heap, 10 + 4 + 2 * std::mem::size_of::<usize>());
+//! assert_eq!(report.total, usage.total());
+//! ```
+//!
+//! [`QCell`]: ../struct.QCell.html
+//! [`QCellOwner::measure_graph`]: ../struct.QCellOwner.html#method.measure_graph
+//! [`teardown`]: ../teardown/index.html
+//! [`CellMeasure::size_of`]: trait.CellMeasure.html#tymethod.size_of
+//! [`CellMeasure::type_name`]: trait.CellMeasure.html#method.type_name
+//! [`MeasureOps::have_seen_ptr`]: struct.MeasureOps.html#method.have_seen_ptr
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::mem::{align_of, align_of_val, size_of, size_of_val};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::teardown::walk_graph;
+use crate::{QCell, QCellOwner};
+
+/// State passed to [`CellMeasure::size_of`] whilst measuring a graph
+///
+/// [`CellMeasure::size_of`]: trait.CellMeasure.html#tymethod.size_of
+#[derive(Debug, Default)]
+pub struct MeasureOps {
+    seen: HashSet<usize>,
+}
+
+impl MeasureOps {
+    /// Create a new instance, with no pointers seen yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the data at `ptr` has been seen, returning `true`
+    /// if it had already been seen before.  Use this to count shared
+    /// data only once.
+    pub fn have_seen_ptr<T: ?Sized>(&mut self, ptr: *const T) -> bool {
+        !self.seen.insert(ptr as *const () as usize)
+    }
+}
+
+/// Measurement of the heap memory owned by a value, for use with
+/// [`QCellOwner::measure_graph`].  See the [module
+/// documentation](index.html) for the accounting rules.
+///
+/// [`QCellOwner::measure_graph`]: ../struct.QCellOwner.html#method.measure_graph
+pub trait CellMeasure {
+    /// Get the number of bytes of heap memory owned by this value,
+    /// not including the inline size of the value itself
+    fn size_of(&self, ops: &mut MeasureOps) -> usize;
+
+    /// Get the name to group this value's totals under.  By default
+    /// this is the name of the implementing type.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// Memory used by all the nodes of one type, as part of a
+/// [`MemoryReport`]
+///
+/// [`MemoryReport`]: struct.MemoryReport.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TypeUsage {
+    /// Number of nodes of this type
+    pub count: usize,
+    /// Bytes in the `Rc` allocations holding the cells, including
+    /// the inline part of the values
+    pub overhead: usize,
+    /// Bytes of heap memory owned by the values, as returned by
+    /// [`CellMeasure::size_of`]
+    ///
+    /// [`CellMeasure::size_of`]: trait.CellMeasure.html#tymethod.size_of
+    pub heap: usize,
+}
+
+impl TypeUsage {
+    /// Get the total bytes used, i.e. `overhead + heap`
+    pub fn total(&self) -> usize {
+        self.overhead + self.heap
+    }
+}
+
+/// Memory used by a graph of cells, as returned by
+/// [`QCellOwner::measure_graph`]
+///
+/// [`QCellOwner::measure_graph`]: ../struct.QCellOwner.html#method.measure_graph
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MemoryReport {
+    /// Total bytes used by all the nodes
+    pub total: usize,
+    /// Number of distinct nodes found
+    pub cells: usize,
+    /// Usage grouped by [`CellMeasure::type_name`]
+    ///
+    /// [`CellMeasure::type_name`]: trait.CellMeasure.html#method.type_name
+    pub by_type: BTreeMap<&'static str, TypeUsage>,
+}
+
+// Size of the allocation for an `Rc` or `Arc` holding a value of the
+// given size and alignment: two counts of `usize`, then the value,
+// laid out as `repr(C)`
+fn rc_alloc_size(size: usize, align: usize) -> usize {
+    let align = align.max(align_of::<usize>());
+    let round_up = |n: usize| (n + align - 1) / align * align;
+    round_up(round_up(2 * size_of::<usize>()) + size)
+}
+
+impl QCellOwner {
+    /// Measure the memory used by the graph of nodes reachable from
+    /// `roots`, as described in the [`measure`] module
+    /// documentation.  `neighbours` is called once for each node
+    /// found, and should add the node's neighbours to the `Vec`
+    /// passed.  Panics if any node is not owned by this owner.
+    ///
+    /// [`measure`]: measure/index.html
+    pub fn measure_graph<T: CellMeasure + ?Sized>(
+        &self,
+        roots: impl IntoIterator<Item = Rc<QCell<T>>>,
+        mut neighbours: impl FnMut(&T, &mut Vec<Rc<QCell<T>>>),
+    ) -> MemoryReport {
+        let nodes = walk_graph(roots, |node, found| neighbours(self.ro(node), found));
+        let mut ops = MeasureOps::new();
+        let mut report = MemoryReport::default();
+        for node in &nodes {
+            let cell: &QCell<T> = node;
+            let value = self.ro(cell);
+            let overhead = rc_alloc_size(size_of_val(cell), align_of_val(cell));
+            let heap = value.size_of(&mut ops);
+            let usage = report.by_type.entry(value.type_name()).or_default();
+            usage.count += 1;
+            usage.overhead += overhead;
+            usage.heap += heap;
+            report.total += overhead + heap;
+        }
+        report.cells = nodes.len();
+        report
+    }
+}
+
+macro_rules! no_heap {
+    ($($t:ty),*) => {
+        $(
+            impl CellMeasure for $t {
+                #[inline]
+                fn size_of(&self, _: &mut MeasureOps) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+no_heap!(
+    (), bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64,
+    &'static str
+);
+
+// Sum of the heap memory owned by the items of a collection
+fn sum_items<'a, T: CellMeasure + 'a>(
+    items: impl IntoIterator<Item = &'a T>,
+    ops: &mut MeasureOps,
+) -> usize {
+    items.into_iter().map(|item| item.size_of(ops)).sum()
+}
+
+impl<T: CellMeasure, const N: usize> CellMeasure for [T; N] {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        sum_items(self, ops)
+    }
+}
+
+macro_rules! tuple_impl {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t: CellMeasure),*> CellMeasure for ($($t,)*) {
+            fn size_of(&self, ops: &mut MeasureOps) -> usize {
+                0 $(+ self.$i.size_of(ops))*
+            }
+        }
+    };
+}
+
+tuple_impl!(A 0, B 1);
+tuple_impl!(A 0, B 1, C 2);
+tuple_impl!(A 0, B 1, C 2, D 3);
+
+impl CellMeasure for String {
+    fn size_of(&self, _: &mut MeasureOps) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: CellMeasure + ?Sized> CellMeasure for Box<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        size_of_val(&**self) + (**self).size_of(ops)
+    }
+}
+
+impl<T: CellMeasure> CellMeasure for Option<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.as_ref().map_or(0, |v| v.size_of(ops))
+    }
+}
+
+impl<T: CellMeasure> CellMeasure for Vec<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.capacity() * size_of::<T>() + sum_items(self, ops)
+    }
+}
+
+impl<T: CellMeasure> CellMeasure for VecDeque<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.capacity() * size_of::<T>() + sum_items(self, ops)
+    }
+}
+
+// Hash tables are estimated as their capacity times the entry size,
+// which ignores the control bytes and any unused buckets
+impl<K: CellMeasure, V: CellMeasure, S> CellMeasure for HashMap<K, V, S> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.capacity() * size_of::<(K, V)>()
+            + sum_items(self.keys(), ops)
+            + sum_items(self.values(), ops)
+    }
+}
+
+impl<T: CellMeasure, S> CellMeasure for HashSet<T, S> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.capacity() * size_of::<T>() + sum_items(self, ops)
+    }
+}
+
+// B-trees are estimated as their length times the entry size, which
+// ignores the node headers and unused slots
+impl<K: CellMeasure, V: CellMeasure> CellMeasure for BTreeMap<K, V> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.len() * size_of::<(K, V)>() + sum_items(self.keys(), ops) + sum_items(self.values(), ops)
+    }
+}
+
+impl<T: CellMeasure> CellMeasure for BTreeSet<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        self.len() * size_of::<T>() + sum_items(self, ops)
+    }
+}
+
+impl<T: CellMeasure + ?Sized> CellMeasure for Rc<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        if ops.have_seen_ptr(Rc::as_ptr(self)) {
+            return 0;
+        }
+        rc_alloc_size(size_of_val(&**self), align_of_val(&**self)) + (**self).size_of(ops)
+    }
+}
+
+impl<T: CellMeasure + ?Sized> CellMeasure for Arc<T> {
+    fn size_of(&self, ops: &mut MeasureOps) -> usize {
+        if ops.have_seen_ptr(Arc::as_ptr(self)) {
+            return 0;
+        }
+        rc_alloc_size(size_of_val(&**self), align_of_val(&**self)) + (**self).size_of(ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rc_alloc_size, CellMeasure, MeasureOps};
+    use crate::{QCell, QCellOwner};
+    use std::any::type_name;
+    use std::mem::size_of;
+    use std::rc::Rc;
+
+    const W: usize = size_of::<usize>();
+
+    struct Node {
+        data: Vec<u32>,
+        label: Rc<String>,
+        peers: Vec<Rc<QCell<Node>>>,
+    }
+
+    impl CellMeasure for Node {
+        fn size_of(&self, ops: &mut MeasureOps) -> usize {
+            self.data.size_of(ops)
+                + self.label.size_of(ops)
+                + self.peers.capacity() * size_of::<Rc<QCell<Node>>>()
+        }
+    }
+
+    fn node(owner: &QCellOwner, data: Vec<u32>, label: &Rc<String>) -> Rc<QCell<Node>> {
+        Rc::new(owner.cell(Node {
+            data,
+            label: label.clone(),
+            peers: Vec::new(),
+        }))
+    }
+
+    fn peers(node: &Node, found: &mut Vec<Rc<QCell<Node>>>) {
+        found.extend(node.peers.iter().cloned());
+    }
+
+    #[test]
+    fn measure_known_sizes() {
+        let mut owner = QCellOwner::new();
+        let label = Rc::new(String::with_capacity(7));
+        let a = node(&owner, Vec::with_capacity(4), &label);
+        let b = node(&owner, Vec::with_capacity(2), &label);
+        let c = node(&owner, Vec::new(), &label);
+        // Diamond with a cycle back to the root: `c` is reachable
+        // twice and `a` three times
+        owner.rw(&a).peers = vec![b.clone(), c.clone()];
+        owner.rw(&b).peers = vec![c.clone()];
+        owner.rw(&c).peers = vec![a.clone()];
+
+        let report = owner.measure_graph(vec![a.clone(), a.clone()], peers);
+        assert_eq!(report.cells, 3);
+        let cell = rc_alloc_size(size_of::<QCell<Node>>(), W);
+        // The `Rc<String>` is counted once: 2 counts, the `String`
+        // and its 7 bytes
+        let label_size = 2 * W + 3 * W + 7;
+        let heap = (4 + 2) * 4 + label_size + (2 + 1 + 1) * W;
+        let usage = report.by_type[type_name::<Node>()];
+        assert_eq!(usage.count, 3);
+        assert_eq!(usage.overhead, 3 * cell);
+        assert_eq!(usage.heap, heap);
+        assert_eq!(report.total, 3 * cell + heap);
+
+        // Measuring from an inner node finds the same graph
+        assert_eq!(owner.measure_graph(vec![b], peers), report);
+        for n in [&a, &c] {
+            owner.rw(n).peers.clear();
+        }
+    }
+
+    trait Shape: CellMeasure {}
+
+    struct Circle;
+    struct Poly(Vec<(f64, f64)>);
+
+    impl CellMeasure for Circle {
+        fn size_of(&self, _: &mut MeasureOps) -> usize {
+            0
+        }
+    }
+
+    impl CellMeasure for Poly {
+        fn size_of(&self, ops: &mut MeasureOps) -> usize {
+            self.0.size_of(ops)
+        }
+    }
+
+    impl Shape for Circle {}
+    impl Shape for Poly {}
+
+    #[test]
+    fn measure_by_type() {
+        let owner = QCellOwner::new();
+        let shapes: Vec<Rc<QCell<dyn Shape>>> = vec![
+            Rc::new(owner.cell(Circle)),
+            Rc::new(owner.cell(Poly(vec![(0.0, 0.0); 3]))),
+            Rc::new(owner.cell(Circle)),
+        ];
+        let report = owner.measure_graph(shapes, |_, _| ());
+        assert_eq!(report.cells, 3);
+        assert_eq!(report.by_type.len(), 2);
+        let circle = report.by_type[type_name::<Circle>()];
+        let poly = report.by_type[type_name::<Poly>()];
+        assert_eq!((circle.count, circle.heap), (2, 0));
+        assert_eq!((poly.count, poly.heap), (1, 3 * 16));
+        assert_eq!(report.total, circle.total() + poly.total());
+    }
+
+    #[test]
+    #[should_panic]
+    fn measure_bad_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let c = Rc::new(owner1.cell(0_u32));
+        owner2.measure_graph(vec![c], |_, _| ());
+    }
+}
@@ -0,0 +1,478 @@
+//! Migration path from [`RefCell`] to [`QCell`].
+//!
+//! Converting a large [`RefCell`]-based codebase to [`QCell`] in one
+//! go is a big change, because every `borrow()` and `borrow_mut()`
+//! call must be changed to use an owner, and the owner must be passed
+//! down to every place that needs it.  [`QRefCell`] lets this be done
+//! in two steps:
+//!
+//! - First switch the types mechanically from `RefCell<T>` to
+//!   `QRefCell<T>`.  [`QRefCell`] has `borrow()` and `borrow_mut()`
+//!   calls which return guards just like [`RefCell`], and which check
+//!   borrows at runtime in the same way, so the code behaves just as
+//!   before.
+//!
+//! - Then convert code to use explicit owner threading, one area at a
+//!   time.  Every [`QRefCell`] contains an ordinary [`QCell`] owned
+//!   by a per-thread default owner, which is available via
+//!   [`QRefCell::inner`].  Converted code runs within
+//!   [`default_owner_scope`], which lends it `&mut` on the default
+//!   owner, so it can access those cells directly with
+//!   `owner.ro(cell.inner())` or `owner.rw(cell.inner())` with no
+//!   runtime borrow checks.  Converted and unconverted code can share
+//!   the same cells as the migration proceeds.
+//!
+//! Eventually, once all the `borrow()` and `borrow_mut()` calls are
+//! gone, the `QRefCell<T>` types can be replaced with `QCell<T>`, and
+//! the default owner with an owner of your own.
+//!
+//! The runtime checks work at two levels.  Each [`QRefCell`] tracks
+//! its own borrows just like a [`RefCell`].  In addition, the default
+//! owner cannot be lent out by [`default_owner_scope`] whilst any
+//! [`QRefCell`] guards are active, and no guards can be created
+//! whilst it is lent out.  All conflicts cause a panic which reports
+//! the source location of the earlier borrow.
+//!
+//! ```
+//!# use qcell::migration::{default_owner_scope, QRefCell};
+//!# use std::rc::Rc;
+//! // Unconverted code
+//! let item = Rc::new(QRefCell::new(Vec::<u8>::new()));
+//! item.borrow_mut().push(1);
+//!
+//! // Converted code
+//! default_owner_scope(|owner| {
+//!     owner.rw(item.inner()).push(2);
+//! });
+//!
+//! assert_eq!(*item.borrow(), [1, 2]);
+//! ```
+//!
+//! [`RefCell`]: https://doc.rust-lang.org/std/cell/struct.RefCell.html
+//! [`QCell`]: ../struct.QCell.html
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+
+use crate::{panic_messages, sound, QCell, QCellOwner, QCellOwnerID};
+
+type Site = Cell<Option<&'static Location<'static>>>;
+
+struct DefaultOwner {
+    id: QCellOwnerID,
+    owner: RefCell<QCellOwner>,
+    // Where `default_owner_scope` was called, if active
+    scope_site: Site,
+    // Number of active `QRefCell` guards, and where the most recent
+    // one was created
+    guards: Cell<usize>,
+    guard_site: Site,
+}
+
+std::thread_local! {
+    static DEFAULT_OWNER: DefaultOwner = DefaultOwner::new();
+}
+
+struct SiteDisplay(Option<&'static Location<'static>>);
+
+impl fmt::Display for SiteDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(loc) => write!(f, "{}", loc),
+            None => write!(f, "<unknown>"),
+        }
+    }
+}
+
+/// Get the ID of this thread's default owner.  This may be used to
+/// create [`QCell`] instances which are owned by the default owner
+/// without entering [`default_owner_scope`].
+///
+/// [`QCell`]: ../struct.QCell.html
+pub fn default_owner_id() -> QCellOwnerID {
+    DEFAULT_OWNER.with(|d| d.id)
+}
+
+/// Lend out `&mut` on this thread's default owner for the duration
+/// of the closure call.  This is used by converted code to access
+/// the cells within [`QRefCell`] instances directly, via
+/// [`QRefCell::inner`].
+///
+/// Panics if any [`QRefCell`] guards are active, or if called from
+/// within another [`default_owner_scope`] call.
+#[track_caller]
+pub fn default_owner_scope<R>(f: impl FnOnce(&mut QCellOwner) -> R) -> R {
+    let caller = Location::caller();
+    DEFAULT_OWNER.with(|d| {
+        if let Some(site) = d.scope_site.get() {
+            panic!(
+                "{}: called at {}, active from {}",
+                panic_messages::DEFAULT_OWNER_SCOPE_NESTED,
+                caller,
+                SiteDisplay(Some(site))
+            );
+        }
+        if d.guards.get() != 0 {
+            panic!(
+                "{}: called at {}, borrowed at {}",
+                panic_messages::DEFAULT_OWNER_SCOPE_WHILE_BORROWED,
+                caller,
+                SiteDisplay(d.guard_site.get())
+            );
+        }
+        let mut owner = d.owner.borrow_mut();
+        let _scope = ScopeReset::new(d, caller);
+        f(&mut owner)
+    })
+}
+
+impl DefaultOwner {
+    fn new() -> Self {
+        let owner = QCellOwner::new();
+        Self {
+            // Keep a copy of the ID, since the owner may be borrowed
+            // by `default_owner_scope`
+            id: owner.id(),
+            owner: RefCell::new(owner),
+            scope_site: Cell::new(None),
+            guards: Cell::new(0),
+            guard_site: Cell::new(None),
+        }
+    }
+
+    fn acquire(&self, caller: &'static Location<'static>) {
+        if let Some(site) = self.scope_site.get() {
+            panic!(
+                "{}: borrowed at {}, lent out at {}",
+                panic_messages::QREFCELL_OWNER_LENT_OUT,
+                caller,
+                SiteDisplay(Some(site))
+            );
+        }
+        self.guards.set(self.guards.get() + 1);
+        self.guard_site.set(Some(caller));
+    }
+
+    fn release(&self) {
+        self.guards.set(self.guards.get() - 1);
+    }
+}
+
+// Clears the scope marker even if the closure panics
+struct ScopeReset<'a> {
+    d: &'a DefaultOwner,
+}
+
+impl<'a> ScopeReset<'a> {
+    fn new(d: &'a DefaultOwner, site: &'static Location<'static>) -> Self {
+        d.scope_site.set(Some(site));
+        Self { d }
+    }
+}
+
+impl Drop for ScopeReset<'_> {
+    fn drop(&mut self) {
+        self.d.scope_site.set(None);
+    }
+}
+
+/// Drop-in replacement for `RefCell` which wraps a [`QCell`] owned by
+/// a per-thread default owner.
+///
+/// See the [module documentation](index.html) for the migration
+/// procedure.  Since the contained [`QCell`] belongs to the default
+/// owner of the thread that created it, this type is neither `Send`
+/// nor `Sync`.
+///
+/// [`QCell`]: ../struct.QCell.html
+pub struct QRefCell<T: ?Sized> {
+    // Positive: number of active `QRef` guards; -1: active `QRefMut`
+    borrow: Cell<isize>,
+    borrow_site: Site,
+    // The contained QCell belongs to this thread's default owner
+    _not_send_or_sync: PhantomData<*const ()>,
+    inner: QCell<T>,
+}
+
+impl<T> QRefCell<T> {
+    /// Create a new [`QRefCell`] owned by this thread's default owner
+    pub fn new(value: T) -> Self {
+        Self {
+            borrow: Cell::new(0),
+            borrow_site: Cell::new(None),
+            _not_send_or_sync: PhantomData,
+            inner: QCell::new(default_owner_id(), value),
+        }
+    }
+
+    /// Destroy the cell and return the contained value
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> QRefCell<T> {
+    /// Borrow the contents immutably, returning a guard.  Panics if
+    /// the cell is currently mutably borrowed, or if the default owner
+    /// is lent out by [`default_owner_scope`].
+    #[track_caller]
+    pub fn borrow(&self) -> QRef<'_, T> {
+        let caller = Location::caller();
+        if self.borrow.get() < 0 {
+            panic!(
+                "{}: borrowed at {}, mutably borrowed at {}",
+                panic_messages::QREFCELL_ALREADY_MUTABLY_BORROWED,
+                caller,
+                SiteDisplay(self.borrow_site.get())
+            );
+        }
+        let value = DEFAULT_OWNER.with(|d| {
+            d.acquire(caller);
+            d.owner.borrow().ro(&self.inner) as *const T
+        });
+        self.borrow.set(self.borrow.get() + 1);
+        self.borrow_site.set(Some(caller));
+        QRef {
+            src: self,
+            // The per-cell borrow count prevents a `QRefMut` being
+            // created, and the owner guard count prevents
+            // `default_owner_scope` from lending out the owner, for as
+            // long as this guard exists.
+            // Safety: Derived from the `ro` borrow, and no `&mut`, as above
+            target: unsafe { sound::deref_raw(value) },
+        }
+    }
+
+    /// Borrow the contents mutably, returning a guard.  Panics if the
+    /// cell is currently borrowed, or if the default owner is lent out
+    /// by [`default_owner_scope`].
+    #[track_caller]
+    pub fn borrow_mut(&self) -> QRefMut<'_, T> {
+        let caller = Location::caller();
+        if self.borrow.get() != 0 {
+            panic!(
+                "{}: mutably borrowed at {}, borrowed at {}",
+                panic_messages::QREFCELL_ALREADY_BORROWED,
+                caller,
+                SiteDisplay(self.borrow_site.get())
+            );
+        }
+        let value = DEFAULT_OWNER.with(|d| {
+            d.acquire(caller);
+            d.owner.borrow_mut().rw(&self.inner) as *mut T
+        });
+        self.borrow.set(-1);
+        self.borrow_site.set(Some(caller));
+        QRefMut {
+            src: self,
+            // The per-cell borrow flag prevents any other guard being
+            // created, and the owner guard count prevents
+            // `default_owner_scope` from lending out the owner, for as
+            // long as this guard exists.
+            // Safety: Derived from the `rw` borrow, and no other use, as above
+            target: unsafe { sound::deref_raw_mut(value) },
+        }
+    }
+
+    /// Get the [`QCell`] contained within this cell, which is owned by
+    /// this thread's default owner.  Use this from within
+    /// [`default_owner_scope`] to access the contents with explicit
+    /// owner threading.
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    pub fn inner(&self) -> &QCell<T> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying data.  See
+    /// [`QCell::get_mut`](../struct.QCell.html#method.get_mut).
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Default> Default for QRefCell<T> {
+    fn default() -> Self {
+        QRefCell::new(T::default())
+    }
+}
+
+/// Immutable borrow guard returned by [`QRefCell::borrow`]
+pub struct QRef<'b, T: ?Sized> {
+    src: &'b QRefCell<T>,
+    target: &'b T,
+}
+
+impl<T: ?Sized> Deref for QRef<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.target
+    }
+}
+
+impl<T: ?Sized> Drop for QRef<'_, T> {
+    fn drop(&mut self) {
+        self.src.borrow.set(self.src.borrow.get() - 1);
+        let _ = DEFAULT_OWNER.try_with(|d| d.release());
+    }
+}
+
+/// Mutable borrow guard returned by [`QRefCell::borrow_mut`]
+pub struct QRefMut<'b, T: ?Sized> {
+    src: &'b QRefCell<T>,
+    target: &'b mut T,
+}
+
+impl<T: ?Sized> Deref for QRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.target
+    }
+}
+
+impl<T: ?Sized> DerefMut for QRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.target
+    }
+}
+
+impl<T: ?Sized> Drop for QRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.src.borrow.set(0);
+        let _ = DEFAULT_OWNER.try_with(|d| d.release());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_owner_id, default_owner_scope, QRefCell};
+    use crate::QCell;
+    use std::rc::Rc;
+
+    struct Node {
+        value: u32,
+        next: Option<Rc<QRefCell<Node>>>,
+    }
+
+    // Unconverted code, still using runtime-checked borrows
+    fn old_sum(node: &Rc<QRefCell<Node>>) -> u32 {
+        let n = node.borrow();
+        n.value + n.next.as_ref().map_or(0, old_sum)
+    }
+
+    // Converted code, using explicit owner threading
+    fn new_increment(owner: &mut crate::QCellOwner, node: &Rc<QRefCell<Node>>) {
+        let n = owner.rw(node.inner());
+        n.value += 1;
+        if let Some(next) = n.next.clone() {
+            new_increment(owner, &next);
+        }
+    }
+
+    #[test]
+    fn migration_interleaved() {
+        let tail = Rc::new(QRefCell::new(Node {
+            value: 2,
+            next: None,
+        }));
+        let head = Rc::new(QRefCell::new(Node {
+            value: 1,
+            next: Some(tail.clone()),
+        }));
+        assert_eq!(old_sum(&head), 3);
+        default_owner_scope(|owner| new_increment(owner, &head));
+        assert_eq!(old_sum(&head), 5);
+        tail.borrow_mut().value = 10;
+        let total = default_owner_scope(|owner| {
+            owner.ro(head.inner()).value + owner.ro(tail.inner()).value
+        });
+        assert_eq!(total, 12);
+    }
+
+    #[test]
+    fn migration_plain_qcell() {
+        // Cells created directly by converted code can share the
+        // default owner
+        let cell = QCell::new(default_owner_id(), 100u32);
+        let old = QRefCell::new(1u32);
+        default_owner_scope(|owner| {
+            let (a, b) = owner.rw2(&cell, old.inner());
+            *a += *b;
+        });
+        assert_eq!(*old.borrow(), 1);
+        assert_eq!(default_owner_scope(|owner| *owner.ro(&cell)), 101);
+    }
+
+    #[test]
+    fn migration_multiple_guards() {
+        let c1 = QRefCell::new(1u32);
+        let c2 = QRefCell::new(2u32);
+        let r1 = c1.borrow();
+        let r1b = c1.borrow();
+        let mut w2 = c2.borrow_mut();
+        *w2 += *r1 + *r1b;
+        drop(w2);
+        drop(r1);
+        drop(r1b);
+        assert_eq!(*c2.borrow(), 4);
+        assert_eq!(c2.into_inner(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "whilst already borrowed: mutably borrowed at")]
+    fn migration_borrow_mut_twice() {
+        let c = QRefCell::new(1u32);
+        let _r1 = c.borrow();
+        let _r2 = c.borrow_mut();
+    }
+
+    #[test]
+    #[should_panic(expected = "whilst already mutably borrowed: borrowed at")]
+    fn migration_borrow_while_mut() {
+        let c = QRefCell::new(1u32);
+        let _r1 = c.borrow_mut();
+        let _r2 = c.borrow();
+    }
+
+    #[test]
+    #[should_panic(expected = "whilst QRefCell is borrowed: called at")]
+    fn migration_scope_while_borrowed() {
+        let c = QRefCell::new(1u32);
+        let _r = c.borrow();
+        default_owner_scope(|_| ());
+    }
+
+    #[test]
+    #[should_panic(expected = "whilst default owner is lent out")]
+    fn migration_borrow_in_scope() {
+        let c = QRefCell::new(1u32);
+        default_owner_scope(|_| {
+            let _r = c.borrow();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "whilst already active")]
+    fn migration_nested_scope() {
+        default_owner_scope(|_| default_owner_scope(|_| ()));
+    }
+
+    #[test]
+    fn migration_panic_site() {
+        let c = QRefCell::new(1u32);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _r1 = c.borrow_mut();
+            let _r2 = c.borrow_mut();
+        }));
+        let msg = result.unwrap_err();
+        let msg = msg.downcast_ref::<String>().unwrap();
+        // Both the new and the earlier call site are reported
+        assert_eq!(msg.matches("src/migration.rs:").count(), 2, "{}", msg);
+        // Borrows are released on unwind, and the scope is usable
+        default_owner_scope(|owner| *owner.rw(c.inner()) += 1);
+        assert_eq!(*c.borrow(), 2);
+    }
+}
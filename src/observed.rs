@@ -0,0 +1,371 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::ops::{Deref, DerefMut};
+
+use crate::{QCell, QCellOwner, QCellOwnerID, QCellOwnerSeq};
+
+/// Identifies a subscriber of an [`ObservedCell`], as returned by
+/// `subscribe` on the owner.
+///
+/// [`ObservedCell`]: struct.ObservedCell.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Callback<T> = Box<dyn FnMut(&T)>;
+
+struct Subscribers<T> {
+    next: u64,
+    list: Vec<(SubscriptionId, Callback<T>)>,
+}
+
+/// A [`QCell`] whose subscribers are notified after each write.
+///
+/// This is a minimal hook for code such as a UI which needs to know
+/// when a value may have changed, without a full reactive framework.
+/// Subscribers are added with `subscribe` on the owner, and each write
+/// made through `rw_observed` on the owner returns an
+/// [`ObservedGuard`] which calls all the subscribers with the final
+/// value when it is dropped, in the order they subscribed.  Reading
+/// with `ro_observed` notifies no-one.
+///
+/// The subscriber callbacks are kept in a second [`QCell`] with the
+/// same owner, so they are protected by the owner just like the value.
+/// The notification pass runs whilst the guard still holds the owner's
+/// exclusive borrow, so subscribers only get `&T` and can't reach the
+/// owner to make further changes.  For the same reason they can't call
+/// `unsubscribe` on the owner.  Instead, a subscriber which has access
+/// to the cell (for example through a `Weak`) can call
+/// [`ObservedCell::unsubscribe_deferred`], which takes effect
+/// straight away for notification but only removes the callback once
+/// the current pass is finished.
+///
+/// If a subscriber panics, the rest of that notification pass is
+/// skipped.  With the **std** feature, no notification is made if the
+/// guard is dropped whilst unwinding from a panic, since the value may
+/// have been left half-updated.
+///
+/// This is implemented for [`QCellOwner`] and [`QCellOwnerSeq`].
+///
+/// ```
+///# use qcell::{ObservedCell, QCellOwner};
+///# use std::rc::Rc;
+///# use std::cell::RefCell;
+/// let mut owner = QCellOwner::new();
+/// let counter = ObservedCell::new(&owner, 0);
+/// let log = Rc::new(RefCell::new(Vec::new()));
+/// let log2 = log.clone();
+/// owner.subscribe(&counter, move |n: &i32| log2.borrow_mut().push(*n));
+/// *owner.rw_observed(&counter) += 1;
+/// {
+///     let mut count = owner.rw_observed(&counter);
+///     *count += 1;
+///     *count *= 10;
+/// }
+/// assert_eq!(*owner.ro_observed(&counter), 20);
+/// assert_eq!(*log.borrow(), [1, 20]);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`ObservedGuard`]: struct.ObservedGuard.html
+/// [`ObservedCell::unsubscribe_deferred`]: #method.unsubscribe_deferred
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub struct ObservedCell<T> {
+    value: QCell<T>,
+    subscribers: QCell<Subscribers<T>>,
+    // Subscriptions to remove at the next opportunity.  Kept outside
+    // the owner's protection so that subscribers can add to it during
+    // a notification pass.
+    removed: Cell<Vec<SubscriptionId>>,
+}
+
+impl<T> ObservedCell<T> {
+    /// Create a new cell with no subscribers, owned by the given owner
+    /// ID, like [`QCell::new`].
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    pub fn new(id: impl Into<QCellOwnerID>, value: T) -> Self {
+        let id = id.into();
+        Self {
+            value: id.cell(value),
+            subscribers: id.cell(Subscribers {
+                next: 0,
+                list: Vec::new(),
+            }),
+            removed: Cell::new(Vec::new()),
+        }
+    }
+
+    /// Destroy the cell and return the contained value, dropping all
+    /// the subscribers
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Stop notifying a subscriber.  This needs no owner, so it may be
+    /// called from within a subscriber during a notification pass.
+    /// The subscriber is skipped from then on, and is dropped the next
+    /// time the owner accesses the subscribers.  Unknown IDs are
+    /// ignored.
+    pub fn unsubscribe_deferred(&self, id: SubscriptionId) {
+        let mut removed = self.removed.take();
+        removed.push(id);
+        self.removed.set(removed);
+    }
+
+    // Test whether a subscription is waiting to be removed
+    fn is_removed(&self, id: SubscriptionId) -> bool {
+        let removed = self.removed.take();
+        let found = removed.contains(&id);
+        self.removed.set(removed);
+        found
+    }
+
+    // Drop the callbacks of any subscriptions waiting to be removed
+    fn purge(&self, subscribers: &mut Subscribers<T>) {
+        let removed = self.removed.take();
+        if !removed.is_empty() {
+            subscribers.list.retain(|(id, _)| !removed.contains(id));
+        }
+    }
+}
+
+/// Guard for a write to an [`ObservedCell`], returned by
+/// `rw_observed` on the owner.  Derefs to the value.  The subscribers
+/// are notified when it is dropped.
+///
+/// [`ObservedCell`]: struct.ObservedCell.html
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[must_use = "dropping the ObservedGuard notifies the subscribers immediately"]
+pub struct ObservedGuard<'a, T> {
+    cell: &'a ObservedCell<T>,
+    value: &'a mut T,
+    subscribers: &'a mut Subscribers<T>,
+}
+
+impl<'a, T> Deref for ObservedGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for ObservedGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<'a, T> Drop for ObservedGuard<'a, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        if std::thread::panicking() {
+            return;
+        }
+        self.cell.purge(self.subscribers);
+        for (id, callback) in &mut self.subscribers.list {
+            if !self.cell.is_removed(*id) {
+                callback(self.value);
+            }
+        }
+        self.cell.purge(self.subscribers);
+    }
+}
+
+macro_rules! observed_access {
+    ($owner:ty, $name:literal) => {
+        impl $owner {
+            #[doc = concat!("Borrow the contents of an [`ObservedCell`] immutably.  No subscribers are notified.  Panics if the cell is not owned by this `", $name, "`.")]
+            ///
+            /// [`ObservedCell`]: struct.ObservedCell.html
+            #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+            #[inline]
+            pub fn ro_observed<'a, T>(&'a self, cell: &'a ObservedCell<T>) -> &'a T {
+                self.ro(&cell.value)
+            }
+
+            #[doc = concat!("Borrow the contents of an [`ObservedCell`] mutably, returning a guard which notifies the subscribers when dropped.  Panics if the cell is not owned by this `", $name, "`.")]
+            ///
+            /// [`ObservedCell`]: struct.ObservedCell.html
+            #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+            #[inline]
+            pub fn rw_observed<'a, T>(&'a mut self, cell: &'a ObservedCell<T>) -> ObservedGuard<'a, T> {
+                let (value, subscribers) = self.rw2(&cell.value, &cell.subscribers);
+                ObservedGuard {
+                    cell,
+                    value,
+                    subscribers,
+                }
+            }
+
+            /// Add a subscriber to an [`ObservedCell`], to be called
+            /// with the value after each write.  Subscribers are
+            /// called in the order they were added.  Panics if the
+            /// cell is not owned by this owner.
+            ///
+            /// [`ObservedCell`]: struct.ObservedCell.html
+            #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+            pub fn subscribe<T>(
+                &mut self,
+                cell: &ObservedCell<T>,
+                callback: impl FnMut(&T) + 'static,
+            ) -> SubscriptionId {
+                let subscribers = self.rw(&cell.subscribers);
+                cell.purge(subscribers);
+                let id = SubscriptionId(subscribers.next);
+                subscribers.next += 1;
+                subscribers.list.push((id, Box::new(callback)));
+                id
+            }
+
+            /// Remove a subscriber from an [`ObservedCell`].  Returns
+            /// `false` if it was not subscribed.  Panics if the cell is
+            /// not owned by this owner.
+            ///
+            /// [`ObservedCell`]: struct.ObservedCell.html
+            #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+            pub fn unsubscribe<T>(&mut self, cell: &ObservedCell<T>, id: SubscriptionId) -> bool {
+                let subscribers = self.rw(&cell.subscribers);
+                cell.purge(subscribers);
+                let len = subscribers.list.len();
+                subscribers.list.retain(|(i, _)| *i != id);
+                subscribers.list.len() != len
+            }
+        }
+    };
+}
+
+observed_access!(QCellOwner, "QCellOwner");
+observed_access!(QCellOwnerSeq, "QCellOwnerSeq");
+
+#[cfg(test)]
+mod tests {
+    use super::ObservedCell;
+    use crate::{QCellOwner, QCellOwnerSeq};
+    use std::cell::RefCell;
+    use std::rc::{Rc, Weak};
+
+    type Log = Rc<RefCell<Vec<(char, i32)>>>;
+
+    fn logger(log: &Log, name: char) -> impl FnMut(&i32) + 'static {
+        let log = log.clone();
+        move |v| log.borrow_mut().push((name, *v))
+    }
+
+    #[test]
+    fn observed_order() {
+        let mut owner = QCellOwner::new();
+        let cell = ObservedCell::new(&owner, 1);
+        let log = Log::default();
+        owner.subscribe(&cell, logger(&log, 'a'));
+        owner.subscribe(&cell, logger(&log, 'b'));
+        owner.subscribe(&cell, logger(&log, 'c'));
+        {
+            let mut guard = owner.rw_observed(&cell);
+            *guard += 1;
+            assert!(log.borrow().is_empty());
+            *guard *= 10;
+        }
+        assert_eq!(*log.borrow(), [('a', 20), ('b', 20), ('c', 20)]);
+        assert_eq!(cell.into_inner(), 20);
+    }
+
+    #[test]
+    fn observed_no_notify_on_ro() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = ObservedCell::new(&owner, 5);
+        let log = Log::default();
+        owner.subscribe(&cell, logger(&log, 'a'));
+        assert_eq!(*owner.ro_observed(&cell), 5);
+        assert_eq!(*owner.ro_observed(&cell) + 1, 6);
+        assert!(log.borrow().is_empty());
+        drop(owner.rw_observed(&cell));
+        assert_eq!(*log.borrow(), [('a', 5)]);
+    }
+
+    #[test]
+    fn observed_unsubscribe() {
+        let mut owner = QCellOwner::new();
+        let cell = ObservedCell::new(&owner, 0);
+        let log = Log::default();
+        let a = owner.subscribe(&cell, logger(&log, 'a'));
+        let b = owner.subscribe(&cell, logger(&log, 'b'));
+        assert_ne!(a, b);
+        assert!(owner.unsubscribe(&cell, a));
+        assert!(!owner.unsubscribe(&cell, a));
+        *owner.rw_observed(&cell) = 1;
+        assert_eq!(*log.borrow(), [('b', 1)]);
+        // Deferred removal outside a notification pass also works
+        cell.unsubscribe_deferred(b);
+        *owner.rw_observed(&cell) = 2;
+        assert_eq!(*log.borrow(), [('b', 1)]);
+        assert!(!owner.unsubscribe(&cell, b));
+    }
+
+    #[test]
+    fn observed_unsubscribe_during_notify() {
+        let mut owner = QCellOwner::new();
+        let cell = Rc::new(ObservedCell::new(&owner, 0));
+        let log = Log::default();
+        owner.subscribe(&cell, logger(&log, 'a'));
+
+        // 'b' unsubscribes itself and 'c' on its first call, so 'c'
+        // is skipped even in the pass that is already running
+        let weak: Weak<ObservedCell<i32>> = Rc::downgrade(&cell);
+        let ids = Rc::new(RefCell::new(Vec::new()));
+        let ids2 = ids.clone();
+        let mut inner = logger(&log, 'b');
+        let b = owner.subscribe(&cell, move |v| {
+            inner(v);
+            let cell = weak.upgrade().unwrap();
+            for id in ids2.borrow().iter() {
+                cell.unsubscribe_deferred(*id);
+            }
+        });
+        let c = owner.subscribe(&cell, logger(&log, 'c'));
+        ids.borrow_mut().extend([b, c]);
+        owner.subscribe(&cell, logger(&log, 'd'));
+
+        *owner.rw_observed(&cell) = 1;
+        *owner.rw_observed(&cell) = 2;
+        assert_eq!(
+            *log.borrow(),
+            [('a', 1), ('b', 1), ('d', 1), ('a', 2), ('d', 2)]
+        );
+        assert!(!owner.unsubscribe(&cell, b));
+        assert!(!owner.unsubscribe(&cell, c));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn observed_no_notify_on_panic() {
+        let mut owner = QCellOwner::new();
+        let cell = ObservedCell::new(&owner, 0);
+        let log = Log::default();
+        owner.subscribe(&cell, logger(&log, 'a'));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = owner.rw_observed(&cell);
+            *guard = 1;
+            panic!("half-way through");
+        }));
+        assert!(result.is_err());
+        assert!(log.borrow().is_empty());
+        *owner.rw_observed(&cell) += 1;
+        assert_eq!(*log.borrow(), [('a', 2)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn observed_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let cell = ObservedCell::new(&owner1, 0);
+        drop(owner2.rw_observed(&cell)); // Panic here
+    }
+}
@@ -0,0 +1,195 @@
+// Grouping cells from several owners by owner ID, so that each group
+// can be processed under its own owner without trying every owner on
+// every cell.  This only reads the owner ID stored in each cell, so
+// no access to the contents is involved.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::vec::Vec;
+
+use crate::{QCell, QCellOwner, QCellOwnerID, QCellOwnerSeq};
+
+/// Split a collection of cells into groups by owner ID.  Within each
+/// group, the cells keep the order they had in `cells`.  Each group
+/// may then be processed under the owner with that ID, which is
+/// guaranteed not to panic with a wrong-owner error.
+///
+/// ```
+///# use qcell::{partition_by_owner, QCell, QCellOwner};
+///# use std::sync::Arc;
+/// let mut owner1 = QCellOwner::new();
+/// let owner2 = QCellOwner::new();
+/// let cells = vec![
+///     Arc::new(QCell::new(&owner1, 1)),
+///     Arc::new(QCell::new(&owner2, 2)),
+///     Arc::new(QCell::new(&owner1, 3)),
+/// ];
+/// let groups = partition_by_owner(cells);
+/// for c in &groups[&owner1.id()] {
+///     *owner1.rw(c) *= 10;
+/// }
+/// let sum: i32 = groups[&owner1.id()].iter().map(|c| *owner1.ro(c)).sum();
+/// assert_eq!(sum, 40);
+/// assert_eq!(groups[&owner2.id()].len(), 1);
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn partition_by_owner<T: ?Sized>(
+    cells: Vec<Arc<QCell<T>>>,
+) -> HashMap<QCellOwnerID, Vec<Arc<QCell<T>>>> {
+    let mut groups: HashMap<QCellOwnerID, Vec<Arc<QCell<T>>>> = HashMap::new();
+    for cell in cells {
+        groups.entry(cell.owner_id()).or_default().push(cell);
+    }
+    groups
+}
+
+/// Reorder a slice of cells in place so that cells with the same
+/// owner ID are next to each other.  The sort is stable: groups
+/// appear in the order in which their owner was first seen in the
+/// slice, and within a group the cells keep their original order.
+/// The groups can then be walked by comparing [`QCell::owner_id`]
+/// of neighbouring cells.
+///
+/// [`QCell::owner_id`]: struct.QCell.html#method.owner_id
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn sort_by_owner<T: ?Sized>(cells: &mut [Arc<QCell<T>>]) {
+    // Registries typically have only a handful of owners, so a linear
+    // search for the rank is cheaper than hashing
+    let mut seen: Vec<QCellOwnerID> = Vec::new();
+    for cell in cells.iter() {
+        let id = cell.owner_id();
+        if !seen.contains(&id) {
+            seen.push(id);
+        }
+    }
+    cells.sort_by_cached_key(|cell| {
+        let id = cell.owner_id();
+        seen.iter().position(|&s| s == id)
+    });
+}
+
+macro_rules! owner_groups_methods {
+    ($owner:ty) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+        impl $owner {
+            /// Iterate over just those cells in `cells` which are
+            /// owned by this owner, in their original order.  All the
+            /// cells yielded may be accessed through this owner
+            /// without a wrong-owner panic.
+            pub fn filter_owned<'a, T: ?Sized>(
+                &self,
+                cells: &'a [Arc<QCell<T>>],
+            ) -> impl Iterator<Item = &'a Arc<QCell<T>>> + 'a {
+                let id = self.id();
+                cells.iter().filter(move |cell| cell.owner_id() == id)
+            }
+        }
+    };
+}
+
+owner_groups_methods!(QCellOwner);
+owner_groups_methods!(QCellOwnerSeq);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    // Three owners, with cells interleaved as 0, 1, 2, 0, 1, 2, ...
+    fn interleaved(owners: [QCellOwnerID; 3], n: usize) -> Vec<Arc<QCell<usize>>> {
+        (0..n).map(|i| Arc::new(QCell::new(owners[i % 3], i))).collect()
+    }
+
+    #[test]
+    fn owner_groups_partition() {
+        let mut o0 = QCellOwner::new();
+        let mut o1 = QCellOwner::new();
+        let mut o2 = unsafe { QCellOwnerSeq::new() };
+        let ids = [o0.id(), o1.id(), o2.id()];
+        let cells = interleaved(ids, 30);
+        let groups = partition_by_owner(cells.clone());
+        assert_eq!(groups.len(), 3);
+
+        for (k, id) in ids.iter().enumerate() {
+            let group = &groups[id];
+            assert_eq!(group.len(), 10);
+            assert!(group.iter().all(|c| c.owner_id() == *id));
+            let idx: Vec<usize> = (0..10).map(|j| j * 3 + k).collect();
+            assert!(group.iter().zip(&idx).all(|(c, &i)| Arc::ptr_eq(c, &cells[i])));
+        }
+
+        // Every group fully accessible under its own owner
+        for c in &groups[&ids[0]] {
+            *o0.rw(c) += 100;
+        }
+        for c in &groups[&ids[1]] {
+            *o1.rw(c) += 100;
+        }
+        for c in &groups[&ids[2]] {
+            *o2.rw(c) += 100;
+        }
+        for (i, c) in cells.iter().enumerate() {
+            let v = match i % 3 {
+                0 => *o0.ro(c),
+                1 => *o1.ro(c),
+                _ => *o2.ro(c),
+            };
+            assert_eq!(v, i + 100);
+        }
+    }
+
+    #[test]
+    fn owner_groups_filter_owned() {
+        let o0 = QCellOwner::new();
+        let o1 = QCellOwner::new();
+        let o2 = unsafe { QCellOwnerSeq::new() };
+        let ids = [o0.id(), o1.id(), o2.id()];
+        let cells = interleaved(ids, 20);
+        let groups = partition_by_owner(cells.clone());
+
+        let f0: Vec<_> = o0.filter_owned(&cells).collect();
+        let f1: Vec<_> = o1.filter_owned(&cells).collect();
+        let f2: Vec<_> = o2.filter_owned(&cells).collect();
+        for (filtered, id) in [f0, f1, f2].iter().zip(&ids) {
+            let group = &groups[id];
+            assert_eq!(filtered.len(), group.len());
+            assert!(filtered.iter().zip(group).all(|(a, b)| Arc::ptr_eq(a, b)));
+        }
+        assert_eq!(o1.filter_owned(&cells).map(|c| *o1.ro(c)).sum::<usize>(), 70);
+
+        let other = QCellOwner::new();
+        assert_eq!(other.filter_owned(&cells).count(), 0);
+    }
+
+    #[test]
+    fn owner_groups_sort_stable() {
+        let o0 = QCellOwner::new();
+        let o1 = QCellOwner::new();
+        let o2 = QCellOwner::new();
+        // First appearances are o1, o2, o0
+        let order = [1, 2, 1, 0, 2, 0, 1, 0, 2, 1];
+        let ids = [o0.id(), o1.id(), o2.id()];
+        let mut cells: Vec<_> = order
+            .iter()
+            .enumerate()
+            .map(|(i, &k)| Arc::new(QCell::new(ids[k], i)))
+            .collect();
+        sort_by_owner(&mut cells);
+
+        let owners: Vec<QCellOwnerID> = cells.iter().map(|c| c.owner_id()).collect();
+        let mut expect = Vec::new();
+        for k in [1, 2, 0] {
+            expect.extend(order.iter().filter(|&&o| o == k).map(|_| ids[k]));
+        }
+        assert!(owners == expect);
+
+        let contents: Vec<usize> = cells
+            .iter()
+            .map(|c| match ids.iter().position(|&id| id == c.owner_id()) {
+                Some(0) => *o0.ro(c),
+                Some(1) => *o1.ro(c),
+                _ => *o2.ro(c),
+            })
+            .collect();
+        assert_eq!(contents, [0, 2, 6, 9, 1, 4, 8, 3, 5, 7]);
+    }
+}
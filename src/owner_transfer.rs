@@ -0,0 +1,287 @@
+use std::fmt;
+use std::thread::{self, ThreadId};
+use std::time::SystemTime;
+
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{self, OwnerKind, TransferRecord};
+use crate::{QCellOwner, QCellOwnerSeq, TCellMarker, TCellOwner};
+
+mod sealed {
+    pub trait Sealed {
+        // The owner's registration number, if registered, and how to
+        // describe it in the transfer log
+        #[cfg(feature = "diagnostics")]
+        fn audit(&self) -> (Option<u64>, super::OwnerKind, Option<&'static str>);
+    }
+}
+
+/// Owner types which can be handed to another thread with an
+/// [`OwnerTransfer`].  This trait is sealed, so can't be implemented
+/// outside this crate.
+///
+/// [`OwnerTransfer`]: struct.OwnerTransfer.html
+pub trait TransferableOwner: sealed::Sealed + Send {}
+
+impl sealed::Sealed for QCellOwner {
+    #[cfg(feature = "diagnostics")]
+    fn audit(&self) -> (Option<u64>, OwnerKind, Option<&'static str>) {
+        (self.diag.id(), OwnerKind::QCellOwner, None)
+    }
+}
+impl TransferableOwner for QCellOwner {}
+
+impl sealed::Sealed for QCellOwnerSeq {
+    #[cfg(feature = "diagnostics")]
+    fn audit(&self) -> (Option<u64>, OwnerKind, Option<&'static str>) {
+        (self.diag.id(), OwnerKind::QCellOwnerSeq, None)
+    }
+}
+impl TransferableOwner for QCellOwnerSeq {}
+
+impl<Q: TCellMarker> sealed::Sealed for TCellOwner<Q> {
+    #[cfg(feature = "diagnostics")]
+    fn audit(&self) -> (Option<u64>, OwnerKind, Option<&'static str>) {
+        (self.diag.id(), OwnerKind::TCellOwner, Some(std::any::type_name::<Q>()))
+    }
+}
+impl<Q: TCellMarker> TransferableOwner for TCellOwner<Q> {}
+
+/// An owner on its way from one thread to another, as an explicit
+/// checkpoint in code which hands an owner over.
+///
+/// Moving an owner to another thread needs nothing more than `Send`.
+/// The owner is moved by value, so the borrow checker has already
+/// proved that no reference to cell contents obtained through it on
+/// the old thread is still alive.  `OwnerTransfer` gives that
+/// guarantee a name that an auditor can search for: `into_transfer`
+/// takes the owner by value on the source thread, and
+/// [`OwnerTransfer::redeem`] gives it back on the destination thread.
+/// Redeeming consumes the transfer, so it can only happen once, and
+/// the compiler rejects a second attempt, so there is no checked
+/// variant.
+///
+/// The transfer remembers the source thread and when it was made.
+/// With the **diagnostics** feature, redeeming a transfer of a
+/// registered owner also adds a [`TransferRecord`] to the log
+/// returned by [`diagnostics::transfers`].
+///
+/// ```
+///# use qcell::QCellOwner;
+///# use std::sync::Arc;
+/// let mut owner = QCellOwner::new();
+/// let total = Arc::new(owner.cell(0_u64));
+/// *owner.rw(&total) += 1;
+///
+/// let transfer = owner.into_transfer();
+/// let handle = {
+///     let total = total.clone();
+///     std::thread::spawn(move || {
+///         let mut owner = transfer.redeem();
+///         *owner.rw(&total) += 10;
+///         owner.into_transfer()
+///     })
+/// };
+/// let owner = handle.join().unwrap().redeem();
+/// assert_eq!(*owner.ro(&total), 11);
+/// ```
+///
+/// [`OwnerTransfer::redeem`]: struct.OwnerTransfer.html#method.redeem
+/// [`TransferRecord`]: diagnostics/struct.TransferRecord.html
+/// [`diagnostics::transfers`]: diagnostics/fn.transfers.html
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct OwnerTransfer<O: TransferableOwner = QCellOwner> {
+    owner: O,
+    from: ThreadId,
+    sent: SystemTime,
+}
+
+/// An [`OwnerTransfer`] of a [`TCellOwner`]
+///
+/// [`OwnerTransfer`]: struct.OwnerTransfer.html
+/// [`TCellOwner`]: struct.TCellOwner.html
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub type TCellOwnerTransfer<Q> = OwnerTransfer<TCellOwner<Q>>;
+
+impl<O: TransferableOwner> OwnerTransfer<O> {
+    fn new(owner: O) -> Self {
+        Self {
+            owner,
+            from: thread::current().id(),
+            sent: SystemTime::now(),
+        }
+    }
+
+    /// Get the ID of the thread which started the transfer
+    pub fn source_thread(&self) -> ThreadId {
+        self.from
+    }
+
+    /// Get the time at which the transfer was started
+    pub fn sent_at(&self) -> SystemTime {
+        self.sent
+    }
+
+    /// Take the owner out on the destination thread.  This may also
+    /// be the source thread, which the log then shows.
+    pub fn redeem(self) -> O {
+        #[cfg(feature = "diagnostics")]
+        {
+            let (registration, kind, marker) = sealed::Sealed::audit(&self.owner);
+            if let Some(owner) = registration {
+                diagnostics::record_transfer(TransferRecord {
+                    owner,
+                    kind,
+                    marker,
+                    from: self.from,
+                    to: thread::current().id(),
+                    sent: self.sent,
+                    redeemed: SystemTime::now(),
+                });
+            }
+        }
+        self.owner
+    }
+}
+
+impl<O: TransferableOwner> fmt::Debug for OwnerTransfer<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnerTransfer")
+            .field("from", &self.from)
+            .field("sent", &self.sent)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl QCellOwner {
+    /// Start handing the owner to another thread.  See
+    /// [`OwnerTransfer`].
+    ///
+    /// [`OwnerTransfer`]: struct.OwnerTransfer.html
+    pub fn into_transfer(self) -> OwnerTransfer<Self> {
+        OwnerTransfer::new(self)
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl QCellOwnerSeq {
+    /// Start handing the owner to another thread.  See
+    /// [`OwnerTransfer`].
+    ///
+    /// [`OwnerTransfer`]: struct.OwnerTransfer.html
+    pub fn into_transfer(self) -> OwnerTransfer<Self> {
+        OwnerTransfer::new(self)
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<Q: TCellMarker> TCellOwner<Q> {
+    /// Start handing the owner to another thread.  See
+    /// [`OwnerTransfer`].  The marker type stays claimed throughout.
+    ///
+    /// [`OwnerTransfer`]: struct.OwnerTransfer.html
+    pub fn into_transfer(self) -> TCellOwnerTransfer<Q> {
+        OwnerTransfer::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{QCellOwner, QCellOwnerSeq, TCell, TCellOwner};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn transfer_qcell_owner_round_trip() {
+        let mut owner = QCellOwner::new();
+        let id = owner.id();
+        let cells: Vec<_> = (0..3).map(|i| Arc::new(owner.cell(i))).collect();
+        *owner.rw(&cells[0]) += 100;
+
+        let transfer = owner.into_transfer();
+        assert_eq!(transfer.source_thread(), thread::current().id());
+        let sent = transfer.sent_at();
+        let (worker, transfer) = {
+            let cells = cells.clone();
+            thread::spawn(move || {
+                let mut owner = transfer.redeem();
+                for cell in &cells {
+                    *owner.rw(cell) *= 2;
+                }
+                (thread::current().id(), owner.into_transfer())
+            })
+            .join()
+            .unwrap()
+        };
+        assert_eq!(transfer.source_thread(), worker);
+        assert!(transfer.sent_at() >= sent);
+        assert!(format!("{:?}", transfer).starts_with("OwnerTransfer { from: ThreadId("));
+        let owner = transfer.redeem();
+        assert_eq!(owner.id(), id);
+        let values: Vec<i32> = cells.iter().map(|c| *owner.ro(c)).collect();
+        assert_eq!(values, [200, 2, 4]);
+    }
+
+    #[test]
+    fn transfer_seq_and_tcell_owners() {
+        crate::marker!(struct Marker;);
+        // Safety: No other owner is created with this ID
+        let mut seq = unsafe { QCellOwnerSeq::new() };
+        let qcell = Arc::new(seq.cell(String::from("a")));
+        let mut towner = TCellOwner::<Marker>::new();
+        let tcell = Arc::new(TCell::<Marker, _>::new(1));
+        seq.rw(&qcell).push('b');
+        *towner.rw(&tcell) += 1;
+
+        let (seq_transfer, tcell_transfer) = (seq.into_transfer(), towner.into_transfer());
+        let (seq, towner) = {
+            let (qcell, tcell) = (qcell.clone(), tcell.clone());
+            thread::spawn(move || {
+                let mut seq = seq_transfer.redeem();
+                let mut towner = tcell_transfer.redeem();
+                // Still claimed whilst in transfer
+                assert!(TCellOwner::<Marker>::try_new().is_none());
+                seq.rw(&qcell).push('c');
+                *towner.rw(&tcell) += 1;
+                (seq.into_transfer(), towner.into_transfer())
+            })
+            .join()
+            .unwrap()
+        };
+        let (seq, towner) = (seq.redeem(), towner.redeem());
+        assert_eq!(seq.ro(&qcell), "abc");
+        assert_eq!(*towner.ro(&tcell), 3);
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[test]
+    fn transfer_audit_log() {
+        crate::marker!(struct Marker;);
+        let owner = TCellOwner::<Marker>::new_registered("transfer-audit");
+        let registration = crate::diagnostics::snapshot()
+            .into_iter()
+            .find(|info| info.label.as_deref() == Some("transfer-audit"))
+            .unwrap()
+            .id;
+        let transfer = owner.into_transfer();
+        let owner = thread::spawn(move || transfer.redeem()).join().unwrap();
+        // Redeemed where it started, which is also logged
+        let _owner = owner.into_transfer().redeem();
+        // An unregistered owner isn't logged
+        let _plain = QCellOwner::new().into_transfer().redeem();
+
+        let records: Vec<_> = crate::diagnostics::transfers()
+            .into_iter()
+            .filter(|r| r.owner == registration)
+            .collect();
+        assert_eq!(records.len(), 2);
+        let here = thread::current().id();
+        assert_eq!(records[0].kind, crate::diagnostics::OwnerKind::TCellOwner);
+        assert!(records[0].marker.unwrap().ends_with("Marker"));
+        assert_eq!(records[0].from, here);
+        assert_ne!(records[0].to, here);
+        assert_eq!((records[1].from, records[1].to), (here, here));
+        assert!(records[0].sent <= records[0].redeemed);
+        assert!(records[0].redeemed <= records[1].sent);
+    }
+}
@@ -0,0 +1,923 @@
+//! Panic messages used by this crate.
+//!
+//! These are the canonical texts of the panics raised on misuse, for
+//! code that needs to recognise them, for example tests that check
+//! that a panic was caught.  Where a panic has no further details,
+//! the message is exactly the constant.  Otherwise the constant is
+//! followed by `": "` and then the details, for example the indices
+//! of the cells concerned or the source locations of conflicting
+//! borrows.  So to match a panic reliably, test whether the message
+//! starts with the constant:
+//!
+//! ```
+//!# use qcell::{panic_messages, QCellOwnerSeq};
+//!# use std::panic::{catch_unwind, AssertUnwindSafe};
+//! // Safety: No other owner is created with this ID
+//! let mut owner = unsafe { QCellOwnerSeq::new() };
+//! let cell = owner.cell(1);
+//! let payload = catch_unwind(AssertUnwindSafe(|| {
+//!     let _ = owner.rw2(&cell, &cell);
+//! }))
+//! .unwrap_err();
+//! let msg = payload
+//!     .downcast_ref::<String>()
+//!     .map(|s| s.as_str())
+//!     .or_else(|| payload.downcast_ref::<&str>().copied())
+//!     .unwrap();
+//! assert!(msg.starts_with(panic_messages::QCELL_BORROWED_TWICE));
+//! ```
+//!
+//! Panics from the standard library, such as slice index out of
+//! bounds, are not listed here.  The constants are all present
+//! whatever features are enabled, even if the code which raises the
+//! panic is not.
+
+/// A [`QCell`] was accessed with an owner other than the one it
+/// belongs to.
+///
+/// [`QCell`]: ../struct.QCell.html
+pub const QCELL_INCORRECT_OWNER: &str = "QCell accessed with incorrect owner";
+
+/// A [`QCell`] was created with a [`QCellOwnerID`] whose owner had
+/// been dropped, and accessed with a new owner that reused its ID.
+/// Only detected with the `debug-owner-tracking` feature.
+///
+/// [`QCell`]: ../struct.QCell.html
+/// [`QCellOwnerID`]: ../struct.QCellOwnerID.html
+pub const QCELL_STALE_OWNER_ID: &str = "stale QCellOwnerID used after original owner was dropped";
+
+/// The same [`QCell`] was passed more than once to `rw2()` or
+/// `rw3()`.
+///
+/// [`QCell`]: ../struct.QCell.html
+pub const QCELL_BORROWED_TWICE: &str = "Illegal to borrow same QCell twice with rw2() or rw3()";
+
+/// A [`PartOwner`] was given a cell which is not in its set.  The
+/// details give the index of the part.
+///
+/// [`PartOwner`]: ../struct.PartOwner.html
+pub const QCELL_PART_NOT_MEMBER: &str = "QCell accessed through a part that doesn't contain it";
+
+/// An [`XCell`] was accessed with an owner whose ID differs from the
+/// cell's.
+///
+/// [`XCell`]: ../struct.XCell.html
+pub const XCELL_INCORRECT_OWNER: &str = "XCell accessed with incorrect owner";
+
+/// The same [`XCell`] was passed more than once to `rw2()` or
+/// `rw3()`.
+///
+/// [`XCell`]: ../struct.XCell.html
+pub const XCELL_BORROWED_TWICE: &str = "Illegal to borrow same XCell twice with rw2() or rw3()";
+
+/// An [`OwnedCells`] container was accessed with an owner other than
+/// the one it belongs to.
+///
+/// [`OwnedCells`]: ../struct.OwnedCells.html
+pub const OWNED_CELLS_INCORRECT_OWNER: &str = "OwnedCells accessed with incorrect owner";
+
+/// The same [`OwnedCells`] index was passed twice to `rw2_at()`.
+///
+/// [`OwnedCells`]: ../struct.OwnedCells.html
+pub const OWNED_CELLS_BORROWED_TWICE: &str =
+    "Illegal to borrow same OwnedCells element twice with rw2_at()";
+
+/// A [`BitCells`] or [`BitCellArray`] was accessed with an owner
+/// other than the one it belongs to.
+///
+/// [`BitCells`]: ../struct.BitCells.html
+/// [`BitCellArray`]: ../struct.BitCellArray.html
+pub const BIT_CELLS_INCORRECT_OWNER: &str = "BitCells accessed with incorrect owner";
+
+/// A bit index or range was outside a [`BitCells`], [`BitCellArray`]
+/// or [`BitSliceMut`].  The details give the index or range and the
+/// length.
+///
+/// [`BitCells`]: ../struct.BitCells.html
+/// [`BitCellArray`]: ../struct.BitCellArray.html
+/// [`BitSliceMut`]: ../struct.BitSliceMut.html
+pub const BIT_CELLS_INDEX_OUT_OF_RANGE: &str = "BitCells index out of range";
+
+/// Two of the ranges passed to `split_disjoint()` overlap.  The
+/// details give the two ranges.
+pub const BIT_CELLS_RANGES_OVERLAP: &str = "Illegal to split BitCells into ranges that overlap";
+
+/// An owner ID was derived from an odd address, so an owner's
+/// internal allocation or pinned location wasn't aligned as required.
+/// This means the allocator or platform doesn't honour alignment,
+/// and IDs could clash with those of [`QCellOwnerSeq`].  The details
+/// give the address.
+///
+/// [`QCellOwnerSeq`]: ../struct.QCellOwnerSeq.html
+pub const QCELL_OWNER_ADDRESS_ODD: &str = "Owner ID address is not 2-byte aligned";
+
+/// [`QCellOwnerNamespace::owner`] was called after every ID in the
+/// namespace had been used.  The details give the range of the
+/// namespace.
+///
+/// [`QCellOwnerNamespace::owner`]: ../struct.QCellOwnerNamespace.html#method.owner
+pub const QCELL_NAMESPACE_EXHAUSTED: &str = "QCellOwnerNamespace has no owner IDs left";
+
+/// [`QCellOwnerNamespace::new`] found no room left in the owner ID
+/// space for another namespace.
+///
+/// [`QCellOwnerNamespace::new`]: ../struct.QCellOwnerNamespace.html#method.new
+pub const QCELL_NAMESPACE_SPACE_EXHAUSTED: &str =
+    "No owner ID space left to reserve another QCellOwnerNamespace";
+
+/// [`QCellOwnerSingle::new`] was called after the owner had already
+/// been created.
+///
+/// [`QCellOwnerSingle::new`]: ../struct.QCellOwnerSingle.html#method.new
+pub const QCELL_SINGLE_SECOND_OWNER: &str = "Illegal to create a second QCellOwnerSingle";
+
+/// A [`QCellSingle`] was accessed with a [`QCellOwnerSingle`] other
+/// than the one it was created with.  Only detected in debug builds.
+///
+/// [`QCellSingle`]: ../struct.QCellSingle.html
+/// [`QCellOwnerSingle`]: ../struct.QCellOwnerSingle.html
+pub const QCELL_SINGLE_WRONG_OWNER: &str = "QCellSingle accessed with wrong QCellOwnerSingle";
+
+/// The same [`QCellSingle`] was passed more than once to `rw2()` or
+/// `rw3()`.
+///
+/// [`QCellSingle`]: ../struct.QCellSingle.html
+pub const QCELL_SINGLE_BORROWED_TWICE: &str =
+    "Illegal to borrow same QCellSingle twice with rw2() or rw3()";
+
+/// [`TCellOwner::new`] was called whilst another owner with the same
+/// marker type exists.  The details give the marker type and, where
+/// recorded, the source location where the existing owner was
+/// created, e.g. `existing TCellOwner<my_crate::Marker> created at
+/// src/init.rs:42:17`.
+///
+/// [`TCellOwner::new`]: ../struct.TCellOwner.html#method.new
+pub const TCELL_SECOND_OWNER: &str =
+    "Illegal to create two TCellOwner instances with the same marker type parameter";
+
+/// The same [`TCell`] was passed more than once to `rw2()` or
+/// `rw3()`.
+///
+/// [`TCell`]: ../struct.TCell.html
+pub const TCELL_BORROWED_TWICE: &str = "Illegal to borrow same TCell twice with rw2() or rw3()";
+
+/// [`TCellOwner::wait_for_new`] was called from the thread which
+/// holds the owner, so it would never return.  The details give the
+/// marker type.
+///
+/// [`TCellOwner::wait_for_new`]: ../struct.TCellOwner.html#method.wait_for_new
+pub const TCELL_WAIT_FOR_NEW_DEADLOCK: &str =
+    "TCellOwner::wait_for_new would deadlock as the owner is held by the current thread";
+
+/// A [`TCellOwner`] was created from within one of the lifecycle
+/// hooks for its own marker type.  The details give the marker type.
+///
+/// [`TCellOwner`]: ../struct.TCellOwner.html
+pub const TCELL_OWNER_IN_LIFECYCLE_HOOK: &str =
+    "TCellOwner created from within one of its own lifecycle hooks";
+
+/// [`TypeGate::claim_blocking`] was called from the thread which
+/// holds the claim, so it would never return.  The details give the
+/// type.
+///
+/// [`TypeGate::claim_blocking`]: ../typeid_gate/struct.TypeGate.html#method.claim_blocking
+pub const TYPE_GATE_CLAIM_DEADLOCK: &str =
+    "Waiting to claim a type that the same thread has claimed would deadlock";
+
+/// A [`CellLink`] was used before [`CellGraphBuilder::finish`]
+/// resolved it.
+///
+/// [`CellLink`]: ../struct.CellLink.html
+/// [`CellGraphBuilder::finish`]: ../struct.CellGraphBuilder.html#method.finish
+pub const CELL_LINK_UNRESOLVED: &str = "CellLink used before CellGraphBuilder::finish resolved it";
+
+/// A [`Declared`] cell was passed to a [`CellGraphBuilder`] or
+/// [`GraphHandles`] other than the one it was declared with.  The
+/// details give the index of the cell.
+///
+/// [`Declared`]: ../struct.Declared.html
+/// [`CellGraphBuilder`]: ../struct.CellGraphBuilder.html
+/// [`GraphHandles`]: ../struct.GraphHandles.html
+pub const CELL_GRAPH_FOREIGN_DECLARED: &str =
+    "Declared cell used with a different CellGraphBuilder";
+
+/// [`TLCellOwner::new`] was called whilst another owner with the same
+/// marker type exists in the same thread.  The details give the
+/// marker type and the source location where the existing owner was
+/// created, as for [`TCELL_SECOND_OWNER`].
+///
+/// [`TCELL_SECOND_OWNER`]: constant.TCELL_SECOND_OWNER.html
+/// [`TLCellOwner::new`]: ../struct.TLCellOwner.html#method.new
+pub const TLCELL_SECOND_OWNER: &str =
+    "Illegal to create two TLCellOwner instances within the same thread with the same marker type parameter";
+
+/// The same [`TLCell`] was passed more than once to `rw2()` or
+/// `rw3()`.
+///
+/// [`TLCell`]: ../struct.TLCell.html
+pub const TLCELL_BORROWED_TWICE: &str = "Illegal to borrow same TLCell twice with rw2() or rw3()";
+
+/// A [`TLCellLend`] future was polled again after it completed.
+///
+/// [`TLCellLend`]: ../struct.TLCellLend.html
+pub const TLCELL_LEND_POLLED_AFTER_COMPLETION: &str = "TLCellLend polled after completion";
+
+/// The same [`LCell`] was passed more than once to `rw2()` or
+/// `rw3()`, or two cells which overlap, such as a cell for a whole
+/// slice and one from its `as_slice_of_cells()`.
+///
+/// [`LCell`]: ../struct.LCell.html
+pub const LCELL_BORROWED_TWICE: &str = "Illegal to borrow same LCell twice with rw2() or rw3()";
+
+/// [`LCellOwner::scope_with_token`] was passed a token which was not
+/// returned by [`LCellOwner::brand_token`].  The details give the
+/// token.
+///
+/// [`LCellOwner::scope_with_token`]: ../struct.LCellOwner.html#method.scope_with_token
+/// [`LCellOwner::brand_token`]: ../struct.LCellOwner.html#method.brand_token
+pub const LCELL_BRAND_TOKEN_INVALID: &str =
+    "LCellOwner brand token was not obtained from LCellOwner::brand_token";
+
+/// [`LCellOwner::scope_with_token`] was passed a token which is
+/// already in use by an active scope.  The details give the token.
+///
+/// [`LCellOwner::scope_with_token`]: ../struct.LCellOwner.html#method.scope_with_token
+pub const LCELL_BRAND_TOKEN_IN_USE: &str =
+    "LCellOwner brand token is already in use by another scope";
+
+/// [`LCellOwner::brand_token`] has handed out every possible token.
+///
+/// [`LCellOwner::brand_token`]: ../struct.LCellOwner.html#method.brand_token
+pub const LCELL_BRAND_TOKENS_EXHAUSTED: &str = "LCellOwner brand tokens exhausted";
+
+/// A [`TShardCell`] was accessed through the owner of a different
+/// shard.  The details give both shard indices.
+///
+/// [`TShardCell`]: ../struct.TShardCell.html
+pub const TSHARD_WRONG_SHARD: &str = "Illegal to access TShardCell using owner of another shard";
+
+/// The same [`TShardCell`] was passed twice to `rw2()`.
+///
+/// [`TShardCell`]: ../struct.TShardCell.html
+pub const TSHARD_BORROWED_TWICE: &str = "Illegal to borrow same TShardCell twice with rw2()";
+
+/// A [`TShardCell`] was created for a shard index which is out of
+/// range.
+///
+/// [`TShardCell`]: ../struct.TShardCell.html
+pub const TSHARD_INDEX_OUT_OF_RANGE: &str = "TShardCell shard index out of range";
+
+/// [`ShardedTCellOwner::new`] was called whilst another owner with
+/// the same marker type exists.
+///
+/// [`ShardedTCellOwner::new`]: ../struct.ShardedTCellOwner.html#method.new
+pub const SHARDED_TCELL_SECOND_OWNER: &str =
+    "Illegal to create two owner instances with the same marker type parameter";
+
+/// A [`ShardedTCellOwner`] was created with a shard count of zero or
+/// more than 65536.
+///
+/// [`ShardedTCellOwner`]: ../struct.ShardedTCellOwner.html
+pub const SHARDED_TCELL_SHARD_COUNT: &str =
+    "ShardedTCellOwner shard count must be in the range 1..=65536";
+
+/// [`TCellOwner::new_indexed`] was called for a marker whose
+/// [`IndexedMarker::INDEX`] is already used by another marker type.
+/// The details give the index and the names of both marker types.
+///
+/// [`TCellOwner::new_indexed`]: ../struct.TCellOwner.html#method.new_indexed
+/// [`IndexedMarker::INDEX`]: ../trait.IndexedMarker.html#associatedconstant.INDEX
+pub const TCELL_INDEX_IN_USE: &str = "IndexedMarker index already used by another marker type";
+
+/// A cell passed to `rw_from_slice()` or `rw_from_iter()` appears
+/// more than once, or two of the `LCell`s overlap.  The details give
+/// the indices of the duplicates.
+pub const RW_MANY_BORROWED_TWICE: &str =
+    "Illegal to borrow same cell twice with rw_from_slice() or rw_from_iter()";
+
+/// A cell passed to `rw!()` or `rw_distinct!()` appears more than
+/// once, or two of the `LCell`s overlap.  The details give the
+/// positions of the duplicates.
+pub const RW_TUPLE_BORROWED_TWICE: &str =
+    "Illegal to borrow same cell twice with rw!() or rw_distinct!()";
+
+/// [`assert_all_distinct`] was passed the same pointer twice.  The
+/// details give the indices of the first pair.
+///
+/// [`assert_all_distinct`]: ../util/fn.assert_all_distinct.html
+pub const UTIL_NOT_DISTINCT: &str = "Pointers passed to assert_all_distinct are not all distinct";
+
+/// The writer cell passed to `rw1_ro_slice()` also appears in the
+/// readers, or overlaps one of them, for `LCell`.  The details give
+/// its index in the readers.
+pub const RW1_RO_SLICE_ALIASED: &str =
+    "Illegal to borrow same cell mutably and immutably with rw1_ro_slice()";
+
+/// The cells and values passed to `read_into_slice()` or
+/// `write_from_slice()` have different lengths.  The details give
+/// both lengths.
+pub const BATCH_LENGTH_MISMATCH: &str =
+    "Cells and values passed to read_into_slice() or write_from_slice() differ in length";
+
+/// An [`FnCell`] was called from within its own closure.
+///
+/// [`FnCell`]: ../struct.FnCell.html
+pub const FN_CELL_REENTERED: &str = "FnCell called again whilst its closure is running";
+
+/// An [`FnCell`] was called after its closure panicked, without a
+/// new closure being stored with `replace()`.
+///
+/// [`FnCell`]: ../struct.FnCell.html
+pub const FN_CELL_POISONED: &str = "FnCell poisoned by a panic in its closure";
+
+/// A [`StepCell`] or [`LStepCell`] was stepped from within its own
+/// step.
+///
+/// [`StepCell`]: ../struct.StepCell.html
+/// [`LStepCell`]: ../struct.LStepCell.html
+pub const STEP_CELL_REENTERED: &str = "StepCell stepped again whilst already being stepped";
+
+/// A [`StepCell`] or [`LStepCell`] was stepped after a step panicked,
+/// without the poison being cleared.
+///
+/// [`StepCell`]: ../struct.StepCell.html
+/// [`LStepCell`]: ../struct.LStepCell.html
+pub const STEP_CELL_POISONED: &str = "StepCell poisoned by a panic whilst being stepped";
+
+/// [`scope_chunks`] or [`try_scope_chunks`] was called with a chunk
+/// size of zero.
+///
+/// [`scope_chunks`]: ../fn.scope_chunks.html
+/// [`try_scope_chunks`]: ../fn.try_scope_chunks.html
+pub const SCOPE_CHUNKS_ZERO_SIZE: &str = "scope_chunks chunk_size must be non-zero";
+
+/// [`teardown`] was called from within [`CellTeardown::teardown`].
+///
+/// [`teardown`]: ../fn.teardown.html
+/// [`CellTeardown::teardown`]: ../teardown/trait.CellTeardown.html#tymethod.teardown
+pub const TEARDOWN_RECURSIVE: &str =
+    "Illegal to call qcell teardown recursively from CellTeardown::teardown";
+
+/// [`CellTeardown::teardown`] tried to access the cell which is being
+/// torn down.
+///
+/// [`CellTeardown::teardown`]: ../teardown/trait.CellTeardown.html#tymethod.teardown
+pub const TEARDOWN_CELL_IN_USE: &str =
+    "Illegal to access the cell being torn down from CellTeardown::teardown";
+
+/// An intrusive list [`Node`] was pushed whilst already linked into
+/// the list.
+///
+/// [`Node`]: ../intrusive/struct.Node.html
+pub const INTRUSIVE_ALREADY_LINKED: &str = "Illegal to link a list Node that is already linked";
+
+/// [`default_owner_scope`] was called from within another
+/// [`default_owner_scope`].  The details give both call sites.
+///
+/// [`default_owner_scope`]: ../migration/fn.default_owner_scope.html
+pub const DEFAULT_OWNER_SCOPE_NESTED: &str = "default_owner_scope called whilst already active";
+
+/// A [`global`] cell or the global owner was accessed from within
+/// another access on the same thread.  The global owner is behind a
+/// lock which the thread already holds, so this would otherwise
+/// deadlock.  The details give both call sites.
+///
+/// [`global`]: ../global/index.html
+pub const GLOBAL_OWNER_REENTERED: &str =
+    "qcell::global accessed from within another access on the same thread, which would deadlock";
+
+/// [`default_owner_scope`] was called whilst a [`QRefCell`] guard is
+/// active.  The details give the call site and the borrow site.
+///
+/// [`default_owner_scope`]: ../migration/fn.default_owner_scope.html
+/// [`QRefCell`]: ../migration/struct.QRefCell.html
+pub const DEFAULT_OWNER_SCOPE_WHILE_BORROWED: &str =
+    "default_owner_scope called whilst QRefCell is borrowed";
+
+/// A [`QRefCell`] was borrowed from within [`default_owner_scope`].
+/// The details give the borrow site and the scope's call site.
+///
+/// [`default_owner_scope`]: ../migration/fn.default_owner_scope.html
+/// [`QRefCell`]: ../migration/struct.QRefCell.html
+pub const QREFCELL_OWNER_LENT_OUT: &str =
+    "QRefCell borrowed whilst default owner is lent out by default_owner_scope";
+
+/// A [`QRefCell`] was borrowed whilst mutably borrowed.  The details
+/// give both borrow sites.
+///
+/// [`QRefCell`]: ../migration/struct.QRefCell.html
+pub const QREFCELL_ALREADY_MUTABLY_BORROWED: &str =
+    "QRefCell borrowed whilst already mutably borrowed";
+
+/// A [`QRefCell`] was mutably borrowed whilst borrowed.  The details
+/// give both borrow sites.
+///
+/// [`QRefCell`]: ../migration/struct.QRefCell.html
+pub const QREFCELL_ALREADY_BORROWED: &str = "QRefCell mutably borrowed whilst already borrowed";
+
+/// The same lock was passed more than once to one of the
+/// [`lock_order`] functions, such as `lock_owners2()`.
+///
+/// [`lock_order`]: ../lock_order/index.html
+pub const LOCK_OWNERS_SAME_LOCK: &str = "Illegal to lock the same owner lock twice";
+
+/// An owner lock was taken whilst holding another, in the opposite
+/// order to one seen before.  Only detected with the
+/// `debug-lock-order` feature.  The details give the addresses of the
+/// two locks.  See [`lock_order`].
+///
+/// [`lock_order`]: ../lock_order/index.html
+pub const LOCK_ORDER_CYCLE: &str = "Owner locks taken in inconsistent order";
+
+/// An owner lock was taken again by the thread already holding it.
+/// Only detected with the `debug-lock-order` feature.  The details
+/// give the address of the lock.  See [`lock_order`].
+///
+/// [`lock_order`]: ../lock_order/index.html
+pub const LOCK_ORDER_REENTRANT: &str = "Owner lock taken again by the thread holding it";
+
+/// A recorded trace was replayed with [`Replay::run`] without mapping
+/// one of the cells it accesses.  Only with the `record` feature.
+/// The details give the cell's ID and the event's sequence number.
+///
+/// [`Replay::run`]: ../record/struct.Replay.html#method.run
+pub const REPLAY_UNMAPPED_CELL: &str = "Replayed trace accesses a cell which is not mapped";
+
+/// A cell assertion macro such as [`assert_cell_eq!`] failed.  The
+/// details give the cell expression as written and the source
+/// location, followed by the cell's contents and the expected value
+/// or pattern on separate lines.
+///
+/// [`assert_cell_eq!`]: ../macro.assert_cell_eq.html
+pub const CELL_ASSERTION_FAILED: &str = "cell assertion failed";
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::any::Any;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    // Run `f`, which must panic, and return the panic message.  Also
+    // used by the tests of other modules.
+    pub(crate) fn panic_message<R>(f: impl FnOnce() -> R) -> String {
+        let payload: Box<dyn Any + Send> = match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(_) => panic!("no panic"),
+            Err(payload) => payload,
+        };
+        match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => String::from(*payload.downcast::<&str>().unwrap()),
+        }
+    }
+
+    fn assert_message<R>(f: impl FnOnce() -> R, expected: &str) {
+        assert_eq!(panic_message(f), expected);
+    }
+
+    fn assert_prefix<R>(f: impl FnOnce() -> R, expected: &str) {
+        let msg = panic_message(f);
+        assert!(
+            msg.starts_with(expected) && msg[expected.len()..].starts_with(": "),
+            "{:?} does not start with {:?}",
+            msg,
+            expected
+        );
+    }
+
+    #[test]
+    fn panic_messages_qcell() {
+        use crate::{QCell, QCellOwnerSeq};
+        let mut owner1 = unsafe { QCellOwnerSeq::new() };
+        let owner2 = unsafe { QCellOwnerSeq::new() };
+        let c1: QCell<u32> = owner1.cell(1);
+        let c2 = owner1.cell(2);
+        assert_message(|| owner2.ro(&c1), QCELL_INCORRECT_OWNER);
+        assert_message(|| owner1.rw2(&c1, &c1), QCELL_BORROWED_TWICE);
+        assert_message(|| owner1.rw3(&c1, &c2, &c1), QCELL_BORROWED_TWICE);
+        assert_prefix(
+            || *owner1.rw1_ro_slice(&c1, &[&c2, &c1]).0,
+            RW1_RO_SLICE_ALIASED,
+        );
+        assert_prefix(
+            || owner1.read_into_slice(&[&c1, &c2], &mut [0; 1]),
+            BATCH_LENGTH_MISMATCH,
+        );
+        assert_prefix(
+            || {
+                crate::rw!(owner1 => &c1, &c2, &c1);
+            },
+            RW_TUPLE_BORROWED_TWICE,
+        );
+
+        assert_prefix(
+            || crate::qcell::address_id(0x1001 as *const crate::qcell::OwnerIDTarget),
+            QCELL_OWNER_ADDRESS_ODD,
+        );
+
+        let ns = crate::QCellOwnerNamespace::reserve(2);
+        let _owner = unsafe { ns.owner() };
+        assert_prefix(|| unsafe { ns.owner() }, QCELL_NAMESPACE_EXHAUSTED);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn panic_messages_qcell_alloc() {
+        use crate::{OwnedCells, QCell, QCellOwner};
+        use std::rc::Rc;
+        let mut owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let mut cells = OwnedCells::new(&owner1);
+        cells.extend(0..2u32);
+        assert_message(|| owner2.ro_at(&cells, 0), OWNED_CELLS_INCORRECT_OWNER);
+        assert_message(|| owner1.rw2_at(&cells, 1, 1), OWNED_CELLS_BORROWED_TWICE);
+
+        let flags = crate::BitCells::with_len(&owner1, 8);
+        assert_message(|| owner2.get_bit(&flags, 0), BIT_CELLS_INCORRECT_OWNER);
+        assert_prefix(|| owner1.get_bit(&flags, 8), BIT_CELLS_INDEX_OUT_OF_RANGE);
+        assert_prefix(
+            || owner1.set_range(&flags, 4..9, true),
+            BIT_CELLS_INDEX_OUT_OF_RANGE,
+        );
+        assert_prefix(
+            || owner1.split_disjoint(&flags, &[0..4, 3..5]).len(),
+            BIT_CELLS_RANGES_OVERLAP,
+        );
+
+        let cell: Rc<QCell<u32>> = Rc::new(owner1.cell(0));
+        assert_prefix(
+            || owner1.rw_from_slice(&[cell.clone(), cell.clone()]).len(),
+            RW_MANY_BORROWED_TWICE,
+        );
+
+        let fn_cell: crate::FnCell<()> = crate::FnCell::new(&owner1, |_, _| panic!());
+        let _ = catch_unwind(AssertUnwindSafe(|| owner1.call(&fn_cell, ())));
+        assert_message(|| owner1.call(&fn_cell, ()), FN_CELL_POISONED);
+        let fn_cell = Rc::new(fn_cell);
+        let weak = Rc::downgrade(&fn_cell);
+        fn_cell.replace(&mut owner1, move |mut owner, _| {
+            owner.call(&weak.upgrade().unwrap(), ())
+        });
+        assert_message(|| owner1.call(&fn_cell, ()), FN_CELL_REENTERED);
+
+        let step_cell = Rc::new(crate::StepCell::new(&owner1, 0));
+        let inner = step_cell.clone();
+        assert_message(
+            || {
+                owner1.step(&step_cell, |_, owner| {
+                    owner.step(&inner, |_, _| panic!());
+                    panic!()
+                })
+            },
+            STEP_CELL_REENTERED,
+        );
+        assert_message(
+            || owner1.step(&step_cell, |_, _| crate::StepResult::Pending),
+            STEP_CELL_POISONED,
+        );
+    }
+
+    #[cfg(feature = "debug-owner-tracking")]
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn panic_messages_qcell_stale() {
+        use crate::QCellOwner;
+        // Depends on the allocator reusing an owner's address
+        for _ in 0..1000 {
+            let stale_id = QCellOwner::new().id();
+            let owner = QCellOwner::new();
+            if owner.id().num == stale_id.num {
+                let cell = stale_id.cell(0u32);
+                assert_message(|| owner.ro(&cell), QCELL_STALE_OWNER_ID);
+                return;
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_xcell() {
+        use crate::xcell::tests::RandomTokens;
+        use crate::XCellOwner;
+        let mut owner1 = XCellOwner::<RandomTokens>::new();
+        let owner2 = XCellOwner::<RandomTokens>::new();
+        let c1 = owner1.cell(1u32);
+        let c2 = owner1.cell(2u32);
+        assert_message(|| owner2.ro(&c1), XCELL_INCORRECT_OWNER);
+        assert_message(|| owner1.rw2(&c1, &c1), XCELL_BORROWED_TWICE);
+        assert_message(|| owner1.rw3(&c1, &c2, &c2), XCELL_BORROWED_TWICE);
+        assert_prefix(
+            || *owner1.rw1_ro_slice(&c1, &[&c2, &c1]).0,
+            RW1_RO_SLICE_ALIASED,
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_qcell_single() {
+        use crate::QCellOwnerSingle;
+        let (_lock, mut owner) = crate::qcell_single::tests::fresh();
+        assert_message(QCellOwnerSingle::new, QCELL_SINGLE_SECOND_OWNER);
+        let c1 = owner.cell(1u32);
+        let c2 = owner.cell(2u32);
+        assert_message(|| owner.rw2(&c1, &c1), QCELL_SINGLE_BORROWED_TWICE);
+        assert_message(|| owner.rw3(&c1, &c2, &c2), QCELL_SINGLE_BORROWED_TWICE);
+        #[cfg(debug_assertions)]
+        {
+            unsafe { QCellOwnerSingle::reset_for_tests() };
+            let owner2 = QCellOwnerSingle::new();
+            assert_message(|| owner2.ro(&c1), QCELL_SINGLE_WRONG_OWNER);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_tcell() {
+        use crate::{TCell, TCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        assert_prefix(TCellOwner::<Marker>::new, TCELL_SECOND_OWNER);
+        let c1 = TCell::new(1u32);
+        let c2 = TCell::new(2u32);
+        assert_message(|| owner.rw2(&c1, &c1), TCELL_BORROWED_TWICE);
+        assert_message(|| owner.rw3(&c1, &c2, &c2), TCELL_BORROWED_TWICE);
+        #[cfg(not(feature = "exclusion-set"))]
+        assert_prefix(
+            TCellOwner::<Marker>::wait_for_new,
+            TCELL_WAIT_FOR_NEW_DEADLOCK,
+        );
+        #[cfg(not(feature = "exclusion-set"))]
+        assert_prefix(
+            crate::typeid_gate::TypeGate::claim_blocking::<Marker>,
+            TYPE_GATE_CLAIM_DEADLOCK,
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_tcell_indexed() {
+        use crate::TCellOwner;
+        crate::marker!(struct Marker = 60;);
+        crate::marker!(struct Other = 60;);
+        let _owner = TCellOwner::<Marker>::new_indexed();
+        assert_prefix(TCellOwner::<Marker>::new_indexed, TCELL_SECOND_OWNER);
+        assert_prefix(TCellOwner::<Other>::new_indexed, TCELL_INDEX_IN_USE);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_qcell_partition() {
+        use crate::QCellOwner;
+        use std::sync::Arc;
+        let mut owner = QCellOwner::new();
+        let c1 = Arc::new(owner.cell(1));
+        let c2 = Arc::new(owner.cell(2));
+        let mut parts = owner.partition(vec![vec![c1], vec![c2.clone()]]).unwrap().into_parts();
+        assert_prefix(|| *parts[0].rw(&c2), QCELL_PART_NOT_MEMBER);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_tcell_std() {
+        use crate::{OwnerLifecycle, ShardedTCellOwner, TCellOwner};
+        crate::marker!(struct Marker;);
+        crate::marker!(struct Hooked;);
+        let owner = ShardedTCellOwner::<Marker, 2>::new();
+        assert_message(
+            ShardedTCellOwner::<Marker, 2>::new,
+            SHARDED_TCELL_SECOND_OWNER,
+        );
+        assert_message(|| owner.cell(2, 0), TSHARD_INDEX_OUT_OF_RANGE);
+        let c1 = owner.cell(1, 0);
+        assert_prefix(|| owner.with_rw(0, |s| *s.rw(&c1)), TSHARD_WRONG_SHARD);
+        assert_message(
+            || owner.with_rw(1, |s| *s.rw2(&c1, &c1).0),
+            TSHARD_BORROWED_TWICE,
+        );
+        drop(owner);
+        crate::marker!(struct Empty;);
+        assert_message(
+            ShardedTCellOwner::<Empty, 0>::new,
+            SHARDED_TCELL_SHARD_COUNT,
+        );
+
+        TCellOwner::<Hooked>::register_lifecycle(
+            OwnerLifecycle::new().on_create(|| drop(TCellOwner::<Hooked>::try_new())),
+        );
+        assert_prefix(TCellOwner::<Hooked>::new, TCELL_OWNER_IN_LIFECYCLE_HOOK);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_tlcell() {
+        use crate::{TLCell, TLCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        assert_prefix(TLCellOwner::<Marker>::new, TLCELL_SECOND_OWNER);
+        let c1 = TLCell::new(1u32);
+        let c2 = TLCell::new(2u32);
+        assert_message(|| owner.rw2(&c1, &c1), TLCELL_BORROWED_TWICE);
+        assert_message(|| owner.rw3(&c1, &c2, &c2), TLCELL_BORROWED_TWICE);
+    }
+
+    #[cfg(all(feature = "std", feature = "async"))]
+    #[test]
+    fn panic_messages_tlcell_lend() {
+        use crate::{TLCell, TLCellOwner};
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::{Context, Wake, Waker};
+        use std::thread::{self, Thread};
+
+        struct ThreadWaker(Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::new(1u32);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut lend = Box::pin(owner.lend_to_thread(&cell, |v| *v));
+        while lend.as_mut().poll(&mut cx).is_pending() {
+            thread::park();
+        }
+        assert_message(
+            || lend.as_mut().poll(&mut cx),
+            TLCELL_LEND_POLLED_AFTER_COMPLETION,
+        );
+    }
+
+    #[test]
+    fn panic_messages_lcell() {
+        use crate::{LCell, LCellOwner};
+        LCellOwner::scope(|mut owner| {
+            let c1 = LCell::new(1u32);
+            let c2 = LCell::new(2u32);
+            assert_message(|| owner.rw2(&c1, &c1), LCELL_BORROWED_TWICE);
+            assert_message(|| owner.rw3(&c1, &c2, &c2), LCELL_BORROWED_TWICE);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_lcell_std() {
+        use crate::LCellOwner;
+        assert_prefix(
+            || LCellOwner::scope_with_token(u64::MAX, |_| ()),
+            LCELL_BRAND_TOKEN_INVALID,
+        );
+        let token = LCellOwner::brand_token();
+        assert_prefix(
+            || LCellOwner::scope_with_token(token, |_| LCellOwner::scope_with_token(token, |_| ())),
+            LCELL_BRAND_TOKEN_IN_USE,
+        );
+    }
+
+    #[cfg(feature = "scoped-threads")]
+    #[test]
+    fn panic_messages_scope_chunks() {
+        use crate::{scope_chunks, TCell, TCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cells = [TCell::new(0)];
+        assert_message(
+            || scope_chunks(&mut owner, &cells, 0, |_, _| ()),
+            SCOPE_CHUNKS_ZERO_SIZE,
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_teardown() {
+        use crate::teardown::{teardown_typed, CellTeardown, TeardownOwner};
+        use crate::{teardown, QCell, QCellOwner};
+        use std::rc::Rc;
+
+        struct Recurse;
+        impl CellTeardown for Recurse {
+            fn teardown(&mut self, _owner: &mut TeardownOwner<'_>) {
+                teardown(&mut QCellOwner::new(), Vec::new());
+            }
+        }
+
+        struct SelfAccess(Option<Rc<QCell<SelfAccess>>>);
+        impl CellTeardown for SelfAccess {
+            fn teardown(&mut self, owner: &mut TeardownOwner<'_>) {
+                if let Some(ref this) = self.0 {
+                    owner.ro(this);
+                }
+            }
+        }
+
+        let mut owner = QCellOwner::new();
+        let node = Rc::new(owner.cell(Recurse));
+        assert_message(
+            || teardown_typed(&mut owner, vec![node]),
+            TEARDOWN_RECURSIVE,
+        );
+        let node = Rc::new(owner.cell(SelfAccess(None)));
+        owner.rw(&node).0 = Some(node.clone());
+        assert_message(
+            || teardown_typed(&mut owner, vec![node]),
+            TEARDOWN_CELL_IN_USE,
+        );
+    }
+
+    // The node is declared before the list, so that it outlives it
+    #[allow(clippy::needless_late_init)]
+    #[test]
+    fn panic_messages_intrusive() {
+        use crate::intrusive::{Node, PinnedListHeader};
+        use pin_utils::pin_mut;
+        let a: Node<u32>;
+        let list = PinnedListHeader::new();
+        pin_mut!(list);
+        let list = list.as_ref();
+        a = list.node(1);
+        list.push_back(&a);
+        assert_message(|| list.push_back(&a), INTRUSIVE_ALREADY_LINKED);
+    }
+
+    #[test]
+    fn panic_messages_util() {
+        let ptrs = [1 as *const (), 2 as *const (), 1 as *const ()];
+        assert_prefix(|| crate::util::assert_all_distinct(&ptrs), UTIL_NOT_DISTINCT);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_global() {
+        use crate::global;
+        let c = global::cell(1u32);
+        assert_prefix(
+            || global::with_rw(&c, |_| global::with_ro(&c, |_| ())),
+            GLOBAL_OWNER_REENTERED,
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_migration() {
+        use crate::migration::{default_owner_scope, QRefCell};
+        let c = QRefCell::new(1u32);
+        assert_prefix(
+            || default_owner_scope(|_| default_owner_scope(|_| ())),
+            DEFAULT_OWNER_SCOPE_NESTED,
+        );
+        assert_prefix(
+            || default_owner_scope(|_| *c.borrow()),
+            QREFCELL_OWNER_LENT_OUT,
+        );
+        let r = c.borrow();
+        assert_prefix(
+            || default_owner_scope(|_| ()),
+            DEFAULT_OWNER_SCOPE_WHILE_BORROWED,
+        );
+        assert_prefix(|| c.borrow_mut(), QREFCELL_ALREADY_BORROWED);
+        drop(r);
+        let _w = c.borrow_mut();
+        assert_prefix(|| c.borrow(), QREFCELL_ALREADY_MUTABLY_BORROWED);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_lock_order() {
+        use crate::lock_order::{lock_owners2, write_owners3};
+        use crate::QCellOwner;
+        use std::sync::{Mutex, RwLock};
+        let m = Mutex::new(QCellOwner::new());
+        assert_message(|| drop(lock_owners2(&m, &m)), LOCK_OWNERS_SAME_LOCK);
+        let a = RwLock::new(QCellOwner::new());
+        let b = RwLock::new(QCellOwner::new());
+        assert_message(|| drop(write_owners3(&a, &b, &b)), LOCK_OWNERS_SAME_LOCK);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_messages_cell_assert() {
+        use crate::QCellOwner;
+        let owner = QCellOwner::new();
+        let cell = owner.cell(1);
+        assert_prefix(|| crate::assert_cell_eq!(owner, cell, 2), CELL_ASSERTION_FAILED);
+        assert_prefix(|| crate::assert_cell_matches!(owner, cell, 0), CELL_ASSERTION_FAILED);
+    }
+
+    #[cfg(all(feature = "std", feature = "record"))]
+    #[test]
+    fn panic_messages_record() {
+        use crate::record::{Recorder, Replay};
+        use crate::QCellOwner;
+        let mut owner = QCellOwner::new();
+        let cell = owner.cell(0);
+        owner.attach_recorder(Recorder::new(1));
+        owner.set(&cell, 1);
+        let trace = owner.dump_trace();
+        assert_prefix(
+            || Replay::new().run(&mut owner, &trace),
+            REPLAY_UNMAPPED_CELL,
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use core::hash::{BuildHasher, Hash};
+use core::pin::Pin;
+use std::collections::HashMap;
+
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{LCell, LCellOwner, QCell, QCellOwner, QCellOwnerPinned, QCellOwnerSeq, TCell};
+use crate::{TCellMarker, TCellOwner};
+
+// The contents are borrowed once through the owner on the calling
+// thread, and only that `&C` is shared with the `rayon` workers.  So
+// the owner itself never needs to be `Sync`, and the usual `rayon`
+// bounds on `&C` (i.e. `C: Sync`, or `V: Sync` for the values) are
+// all that is required.  The borrow of the owner lasts as long as the
+// returned iterator, so no `rw` call can be made until it is dropped.
+macro_rules! par_access {
+    ($([$($gen:tt)*] $owner:ty, $cell:ident[$($pre:tt)*], $name:literal;)*) => {
+        $(
+            impl<$($gen)*> $owner {
+                #[doc = concat!("Iterate in parallel over the contents of a cell holding a collection, such as a `Vec` or `HashMap`, using `rayon`.  The collection is borrowed once, and the `", $name, "` stays borrowed immutably for as long as the iterator exists, so the cells can't be modified meanwhile.  Panics if the cell is not owned by this `", $name, "`.")]
+                #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+                #[inline]
+                pub fn par_iter<'a, C>(
+                    &'a self,
+                    cell: &'a $cell<$($pre)* C>,
+                ) -> <&'a C as IntoParallelIterator>::Iter
+                where
+                    C: ?Sized + 'a,
+                    &'a C: IntoParallelIterator,
+                {
+                    self.ro(cell).into_par_iter()
+                }
+
+                #[doc = concat!("Iterate in parallel over the values of a cell holding a `HashMap`, using `rayon`.  As for `par_iter`, the `", $name, "` stays borrowed immutably for as long as the iterator exists.  Panics if the cell is not owned by this `", $name, "`.")]
+                #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+                #[inline]
+                pub fn par_values<'a, K, V, S>(
+                    &'a self,
+                    cell: &'a $cell<$($pre)* HashMap<K, V, S>>,
+                ) -> impl ParallelIterator<Item = &'a V> + 'a
+                where
+                    K: Eq + Hash + Sync + 'a,
+                    V: Sync + 'a,
+                    S: BuildHasher + Sync + 'a,
+                {
+                    self.ro(cell).par_iter().map(|(_, v)| v)
+                }
+            }
+        )*
+    };
+}
+
+par_access! {
+    [] QCellOwner, QCell[], "QCellOwner";
+    [] QCellOwnerSeq, QCell[], "QCellOwnerSeq";
+    [Q: TCellMarker] TCellOwner<Q>, TCell[Q,], "TCellOwner";
+    ['id] LCellOwner<'id>, LCell['id,], "LCellOwner";
+}
+
+impl QCellOwnerPinned {
+    /// Iterate in parallel over the contents of a cell holding a
+    /// collection, using `rayon`.  See [`QCellOwner::par_iter`].
+    ///
+    /// [`QCellOwner::par_iter`]: struct.QCellOwner.html#method.par_iter
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    #[inline]
+    pub fn par_iter<'a, C>(self: Pin<&'a Self>, cell: &'a QCell<C>) -> <&'a C as IntoParallelIterator>::Iter
+    where
+        C: ?Sized + 'a,
+        &'a C: IntoParallelIterator,
+    {
+        self.ro(cell).into_par_iter()
+    }
+
+    /// Iterate in parallel over the values of a cell holding a
+    /// `HashMap`, using `rayon`.  See [`QCellOwner::par_values`].
+    ///
+    /// [`QCellOwner::par_values`]: struct.QCellOwner.html#method.par_values
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    #[inline]
+    pub fn par_values<'a, K, V, S>(
+        self: Pin<&'a Self>,
+        cell: &'a QCell<HashMap<K, V, S>>,
+    ) -> impl ParallelIterator<Item = &'a V> + 'a
+    where
+        K: Eq + Hash + Sync + 'a,
+        V: Sync + 'a,
+        S: BuildHasher + Sync + 'a,
+    {
+        self.ro(cell).par_iter().map(|(_, v)| v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LCell, LCellOwner, QCell, QCellOwner, QCellOwnerPinned, TCell, TCellOwner};
+    use rayon::iter::ParallelIterator;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn par_values_sum() {
+        let owner = QCellOwner::new();
+        let map: HashMap<u32, u64> = (0..1_000_000).map(|i| (i, u64::from(i) * 3)).collect();
+        let cell = Arc::new(owner.cell(map));
+        let sequential: u64 = owner.ro(&cell).values().sum();
+        let parallel: u64 = owner.par_values(&cell).sum();
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel, 3 * 999_999 * 1_000_000 / 2);
+    }
+
+    #[test]
+    fn par_iter_vec_and_slice() {
+        let mut owner = QCellOwner::new();
+        let cell = owner.cell((1..=1000).collect::<Vec<u64>>());
+        assert_eq!(owner.par_iter(&cell).sum::<u64>(), 500_500);
+        let max = owner.par_iter(&cell).copied().max();
+        assert_eq!(max, Some(1000));
+
+        // Writes are possible again once the iterator is gone
+        owner.rw(&cell).push(1001);
+        assert_eq!(owner.par_iter(&cell).count(), 1001);
+
+        let slice: Box<QCell<[u64]>> = Box::new(owner.cell([1, 2, 3]));
+        assert_eq!(owner.par_iter(&*slice).sum::<u64>(), 6);
+    }
+
+    #[test]
+    fn par_iter_other_owners() {
+        crate::marker!(struct Marker;);
+        let owner = TCellOwner::<Marker>::new();
+        let cell = TCell::new(vec![1_u32; 100]);
+        assert_eq!(owner.par_iter(&cell).count(), 100);
+        let map = TCell::new((0..10).map(|i| (i, i)).collect::<HashMap<_, _>>());
+        assert_eq!(owner.par_values(&map).sum::<i32>(), 45);
+
+        LCellOwner::scope(|owner| {
+            let cell = LCell::new(vec![2_u32; 10]);
+            assert_eq!(owner.par_iter(&cell).sum::<u32>(), 20);
+        });
+
+        let owner = Box::pin(QCellOwnerPinned::new());
+        let cell = owner.as_ref().cell(vec![3_u32; 10]);
+        assert_eq!(owner.as_ref().par_iter(&cell).sum::<u32>(), 30);
+    }
+}
@@ -0,0 +1,161 @@
+// Blocking on a condition of data behind a `Mutex`, as used by the
+// claims registry in `typeid_gate`.  In the crate this is always a
+// `Condvar`.  The tests use `VirtualPark` instead, which has a clock
+// that only moves when a test advances it, and which counts the
+// times that threads have parked, so that a test can tell that a
+// waiter is blocked without sleeping for a while and hoping.  It is
+// only needed by the backend that blocks on a mutex.
+
+#![cfg(not(any(
+    feature = "exclusion-set",
+    all(target_arch = "wasm32", not(target_feature = "atomics"))
+)))]
+
+use std::sync::{Condvar, MutexGuard};
+use std::time::Duration;
+
+pub(crate) trait Park {
+    // Block until `condition` returns false.  The mutex is unlocked
+    // whilst blocked, and locked again to check the condition.
+    fn wait_while<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T>;
+
+    // Same as `wait_while`, but giving up once `timeout` has passed.
+    // Returns true if it gave up with `condition` still true.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn wait_timeout_while<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Duration,
+        condition: impl FnMut(&mut T) -> bool,
+    ) -> (MutexGuard<'a, T>, bool);
+
+    // Wake up all the threads that are blocked, so that they check
+    // their conditions again.  This must be called after the data
+    // has been changed through the mutex.
+    fn notify_all(&self);
+}
+
+impl Park for Condvar {
+    fn wait_while<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        Condvar::wait_while(self, guard, condition).unwrap()
+    }
+
+    fn wait_timeout_while<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Duration,
+        condition: impl FnMut(&mut T) -> bool,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let (guard, result) = Condvar::wait_timeout_while(self, guard, timeout, condition).unwrap();
+        (guard, result.timed_out())
+    }
+
+    fn notify_all(&self) {
+        Condvar::notify_all(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) use self::virtual_time::VirtualPark;
+
+#[cfg(test)]
+mod virtual_time {
+    use super::Park;
+    use std::sync::{Condvar, Mutex, MutexGuard};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Clock {
+        now: Duration,
+        // Total number of times that a thread has blocked
+        parks: usize,
+    }
+
+    // A `Park` whose time only moves on when `advance` is called.
+    // Threads still really block, on a `Condvar`, but only ever wake
+    // up to check their condition, so a timeout is taken if and only
+    // if the test has advanced the clock past the deadline.
+    #[derive(Default)]
+    pub(crate) struct VirtualPark {
+        wake: Condvar,
+        clock: Mutex<Clock>,
+        parked: Condvar,
+    }
+
+    impl VirtualPark {
+        pub(crate) fn now(&self) -> Duration {
+            self.clock.lock().unwrap().now
+        }
+
+        // Move the clock on and wake the blocked threads.  Waiters
+        // check the clock whilst holding the lock on their data, so
+        // that lock is taken here too, to make sure that no waiter
+        // is between checking the clock and blocking.
+        pub(crate) fn advance<T>(&self, data: &Mutex<T>, by: Duration) {
+            let _guard = data.lock().unwrap();
+            self.clock.lock().unwrap().now += by;
+            self.wake.notify_all();
+        }
+
+        // Block until threads have parked `count` times in total since
+        // this was created.  A thread counts as parked once it has
+        // released the lock on its data, so anything done through
+        // that lock afterwards happens whilst it is blocked.
+        pub(crate) fn wait_parks(&self, count: usize) {
+            let clock = self.clock.lock().unwrap();
+            drop(self.parked.wait_while(clock, |c| c.parks < count).unwrap());
+        }
+
+        fn park<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+            // The clock lock is released before blocking, but the data
+            // lock isn't released until `wait` blocks, and a test
+            // can't act on the waiter's data until then
+            self.clock.lock().unwrap().parks += 1;
+            self.parked.notify_all();
+            self.wake.wait(guard).unwrap()
+        }
+    }
+
+    impl Park for VirtualPark {
+        fn wait_while<'a, T>(
+            &self,
+            mut guard: MutexGuard<'a, T>,
+            mut condition: impl FnMut(&mut T) -> bool,
+        ) -> MutexGuard<'a, T> {
+            while condition(&mut guard) {
+                guard = self.park(guard);
+            }
+            guard
+        }
+
+        fn wait_timeout_while<'a, T>(
+            &self,
+            mut guard: MutexGuard<'a, T>,
+            timeout: Duration,
+            mut condition: impl FnMut(&mut T) -> bool,
+        ) -> (MutexGuard<'a, T>, bool) {
+            let deadline = self.now() + timeout;
+            loop {
+                if !condition(&mut guard) {
+                    return (guard, false);
+                }
+                if self.now() >= deadline {
+                    return (guard, true);
+                }
+                guard = self.park(guard);
+            }
+        }
+
+        fn notify_all(&self) {
+            self.wake.notify_all();
+        }
+    }
+}
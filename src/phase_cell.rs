@@ -0,0 +1,433 @@
+// Typestate-style handles onto a cell whose contents change type as a
+// protocol moves from phase to phase.
+//
+// The cell holds a `Box<dyn Any + Send>` plus the name of the current
+// phase type and a generation count.  A `PhaseHandle<P>` is an `Rc`
+// onto the cell together with the generation it was issued for, so
+// accessing through a handle checks both the type and the generation,
+// all with safe code via `Any`.  A transition swaps the box in place
+// and bumps the generation, which invalidates any remaining clones of
+// the old handle even if the protocol later returns to the same type.
+// If the transition function panics the cell is left holding a
+// placeholder and reports `Poisoned` from then on.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::any::{type_name, Any};
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::{QCell, QCellOwner, QCellOwnerID, QCellOwnerSeq};
+
+/// Error from accessing a [`PhaseCell`] through a [`PhaseHandle`]
+/// that no longer matches the current phase.
+///
+/// [`PhaseCell`]: struct.PhaseCell.html
+/// [`PhaseHandle`]: struct.PhaseHandle.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PhaseError {
+    /// The cell has moved on to a phase of a different type
+    TypeMismatch {
+        /// Type name of the phase expected by the handle
+        expected: &'static str,
+        /// Type name of the current phase of the cell
+        found: &'static str,
+    },
+    /// The cell has been through a transition since the handle was
+    /// issued, but has come back to a phase of the same type
+    Stale {
+        /// Generation the handle was issued for
+        handle: u64,
+        /// Current generation of the cell
+        current: u64,
+    },
+    /// A transition function panicked, leaving the cell without a
+    /// phase
+    Poisoned,
+}
+
+impl fmt::Display for PhaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhaseError::TypeMismatch { expected, found } => {
+                write!(f, "phase cell holds {}, not {}", found, expected)
+            }
+            PhaseError::Stale { handle, current } => write!(
+                f,
+                "phase handle is for generation {}, but cell is at generation {}",
+                handle, current
+            ),
+            PhaseError::Poisoned => write!(f, "phase cell poisoned by panic in transition"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PhaseError {}
+
+// Placeholder held while a transition function runs
+struct Vacant;
+
+struct PhaseState {
+    value: Box<dyn Any + Send>,
+    name: &'static str,
+    generation: u64,
+}
+
+impl PhaseState {
+    fn check<P: Any>(&self, generation: u64) -> Result<(), PhaseError> {
+        if self.value.is::<Vacant>() {
+            Err(PhaseError::Poisoned)
+        } else if !self.value.is::<P>() {
+            Err(PhaseError::TypeMismatch {
+                expected: type_name::<P>(),
+                found: self.name,
+            })
+        } else if self.generation != generation {
+            Err(PhaseError::Stale {
+                handle: generation,
+                current: self.generation,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A cell whose contents move through a sequence of phases of
+/// different types, accessed through typed [`PhaseHandle`]s.
+///
+/// Internally this is a [`QCell`] holding a `Box<dyn Any + Send>`
+/// along with the type name of the current phase, so the usual owner
+/// check applies to every access.  It is created and shared via
+/// [`PhaseHandle::new`], and moved from one phase to the next with
+/// `transition` on the owner.
+///
+/// [`PhaseHandle`]: struct.PhaseHandle.html
+/// [`PhaseHandle::new`]: struct.PhaseHandle.html#method.new
+/// [`QCell`]: struct.QCell.html
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub struct PhaseCell {
+    state: QCell<PhaseState>,
+}
+
+impl fmt::Debug for PhaseCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PhaseCell").finish_non_exhaustive()
+    }
+}
+
+/// Typed handle onto a [`PhaseCell`] which is currently in phase
+/// `P`.  Cloning a handle just clones the `Rc`.
+///
+/// When the cell moves to another phase via `transition` on the
+/// owner, the handle passed in is consumed and a handle for the new
+/// phase is returned, sharing the same `Rc`.  Any other clones of the
+/// old handle are invalidated, and accessing through them fails with
+/// a [`PhaseError`] rather than panicking.
+///
+/// ```
+///# use qcell::{PhaseError, PhaseHandle, QCellOwner};
+/// struct Connecting(u16);
+/// struct Open { port: u16, sent: usize }
+///
+/// let mut owner = QCellOwner::new();
+/// let conn = PhaseHandle::new(&owner, Connecting(80));
+/// let early = conn.clone();
+///
+/// let open = owner.transition(conn, |c| Open { port: c.0, sent: 0 }).unwrap();
+/// owner.rw_phase(&open).unwrap().sent += 5;
+/// assert_eq!(owner.ro_phase(&open).unwrap().port, 80);
+/// assert!(matches!(owner.ro_phase(&early), Err(PhaseError::TypeMismatch { .. })));
+/// ```
+///
+/// [`PhaseCell`]: struct.PhaseCell.html
+/// [`PhaseError`]: enum.PhaseError.html
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub struct PhaseHandle<P> {
+    cell: Rc<PhaseCell>,
+    generation: u64,
+    phantom: PhantomData<fn() -> P>,
+}
+
+impl<P: Any + Send> PhaseHandle<P> {
+    /// Create a new [`PhaseCell`] in phase `P`, owned for borrowing
+    /// purposes by the owner with the given [`QCellOwnerID`], or a
+    /// type that can be converted into one, such as `&owner`.
+    ///
+    /// [`PhaseCell`]: struct.PhaseCell.html
+    /// [`QCellOwnerID`]: struct.QCellOwnerID.html
+    pub fn new(id: impl Into<QCellOwnerID>, value: P) -> Self {
+        let state = PhaseState {
+            value: Box::new(value),
+            name: type_name::<P>(),
+            generation: 0,
+        };
+        Self {
+            cell: Rc::new(PhaseCell {
+                state: QCell::new(id, state),
+            }),
+            generation: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P> PhaseHandle<P> {
+    /// Get the shared [`PhaseCell`] behind this handle.  This is the
+    /// same `Rc` for all handles onto the cell, whatever their phase.
+    ///
+    /// [`PhaseCell`]: struct.PhaseCell.html
+    #[inline]
+    pub fn cell(&self) -> &Rc<PhaseCell> {
+        &self.cell
+    }
+
+    /// Get the generation of the cell that this handle was issued
+    /// for.  This starts at 0 and goes up by one on each transition.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl<P> Clone for PhaseHandle<P> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+            generation: self.generation,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P> fmt::Debug for PhaseHandle<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PhaseHandle")
+            .field("phase", &type_name::<P>())
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+macro_rules! phase_methods {
+    ($owner:ty) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+        impl $owner {
+            /// Borrow the contents of a [`PhaseCell`] immutably
+            /// through a handle for phase `P`.  Fails if the cell has
+            /// since moved to another phase.  Panics if the cell does
+            /// not belong to this owner.
+            ///
+            /// [`PhaseCell`]: struct.PhaseCell.html
+            pub fn ro_phase<'a, P: Any + Send>(
+                &'a self,
+                handle: &'a PhaseHandle<P>,
+            ) -> Result<&'a P, PhaseError> {
+                let state = self.ro(&handle.cell.state);
+                state.check::<P>(handle.generation)?;
+                state
+                    .value
+                    .downcast_ref::<P>()
+                    .ok_or(PhaseError::TypeMismatch {
+                        expected: type_name::<P>(),
+                        found: state.name,
+                    })
+            }
+
+            /// Borrow the contents of a [`PhaseCell`] mutably through
+            /// a handle for phase `P`.  Fails if the cell has since
+            /// moved to another phase.  Panics if the cell does not
+            /// belong to this owner.
+            ///
+            /// [`PhaseCell`]: struct.PhaseCell.html
+            pub fn rw_phase<'a, P: Any + Send>(
+                &'a mut self,
+                handle: &'a PhaseHandle<P>,
+            ) -> Result<&'a mut P, PhaseError> {
+                let state = self.rw(&handle.cell.state);
+                state.check::<P>(handle.generation)?;
+                let found = state.name;
+                state
+                    .value
+                    .downcast_mut::<P>()
+                    .ok_or(PhaseError::TypeMismatch {
+                        expected: type_name::<P>(),
+                        found,
+                    })
+            }
+
+            /// Move a [`PhaseCell`] from phase `From` to phase `To`,
+            /// passing the current contents by value to `f` and
+            /// storing the result in place of them.  The handle is
+            /// consumed and a handle for the new phase returned,
+            /// sharing the same `Rc`.  All other handles issued before
+            /// the transition are invalidated.
+            ///
+            /// Fails without calling `f` if the handle no longer
+            /// matches the current phase.  If `f` panics, the cell is
+            /// left poisoned and all further accesses fail.  Panics if
+            /// the cell does not belong to this owner.
+            ///
+            /// [`PhaseCell`]: struct.PhaseCell.html
+            pub fn transition<From: Any + Send, To: Any + Send>(
+                &mut self,
+                handle: PhaseHandle<From>,
+                f: impl FnOnce(From) -> To,
+            ) -> Result<PhaseHandle<To>, PhaseError> {
+                let state = self.rw(&handle.cell.state);
+                state.check::<From>(handle.generation)?;
+                let value = mem::replace(&mut state.value, Box::new(Vacant));
+                let from = match value.downcast::<From>() {
+                    Ok(from) => *from,
+                    Err(value) => {
+                        // Unreachable after the check above, but put
+                        // the contents back rather than panicking
+                        state.value = value;
+                        return Err(PhaseError::TypeMismatch {
+                            expected: type_name::<From>(),
+                            found: state.name,
+                        });
+                    }
+                };
+                state.value = Box::new(f(from));
+                state.name = type_name::<To>();
+                state.generation = state.generation.wrapping_add(1);
+                Ok(PhaseHandle {
+                    generation: state.generation,
+                    cell: handle.cell,
+                    phantom: PhantomData,
+                })
+            }
+        }
+    };
+}
+
+phase_methods!(QCellOwner);
+phase_methods!(QCellOwnerSeq);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::string::String;
+    use std::vec::Vec;
+
+    #[derive(Debug)]
+    struct Idle {
+        name: String,
+    }
+    #[derive(Debug)]
+    struct Handshake {
+        name: String,
+        nonce: u32,
+    }
+    #[derive(Debug)]
+    struct Established {
+        name: String,
+        nonce: u32,
+        log: Vec<&'static str>,
+    }
+
+    #[test]
+    fn phase_three_phase_protocol() {
+        let mut owner = QCellOwner::new();
+        let idle = PhaseHandle::new(&owner, Idle { name: "peer".into() });
+        owner.rw_phase(&idle).unwrap().name.push_str("-1");
+
+        let hs = owner
+            .transition(idle, |i| Handshake { name: i.name, nonce: 7 })
+            .unwrap();
+        owner.rw_phase(&hs).unwrap().nonce += 1;
+        assert_eq!(hs.generation(), 1);
+
+        let est = owner
+            .transition(hs, |h| Established {
+                name: h.name,
+                nonce: h.nonce,
+                log: vec!["up"],
+            })
+            .unwrap();
+        owner.rw_phase(&est).unwrap().log.push("ping");
+        let e = owner.ro_phase(&est).unwrap();
+        assert_eq!(e.name, "peer-1");
+        assert_eq!(e.nonce, 8);
+        assert_eq!(e.log, ["up", "ping"]);
+        assert_eq!(est.generation(), 2);
+    }
+
+    #[test]
+    fn phase_stale_handle() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let idle = PhaseHandle::new(&owner, Idle { name: "a".into() });
+        let stale = idle.clone();
+        let hs = owner
+            .transition(idle, |i| Handshake { name: i.name, nonce: 1 })
+            .unwrap();
+
+        let err = owner.ro_phase(&stale).unwrap_err();
+        assert_eq!(
+            err,
+            PhaseError::TypeMismatch {
+                expected: type_name::<Idle>(),
+                found: type_name::<Handshake>(),
+            }
+        );
+        assert!(err.to_string().contains("Handshake"));
+        assert!(owner.rw_phase(&stale).is_err());
+        assert!(owner.transition(stale.clone(), |i| i).is_err());
+
+        // Back to the same type, but the old handle stays invalid
+        let idle2 = owner.transition(hs, |h| Idle { name: h.name }).unwrap();
+        assert_eq!(
+            owner.ro_phase(&stale).unwrap_err(),
+            PhaseError::Stale {
+                handle: 0,
+                current: 2
+            }
+        );
+        assert_eq!(owner.ro_phase(&idle2).unwrap().name, "a");
+    }
+
+    #[test]
+    fn phase_no_reallocation() {
+        let mut owner = QCellOwner::new();
+        let idle = PhaseHandle::new(&owner, Idle { name: "x".into() });
+        let ptr = Rc::as_ptr(idle.cell());
+        let hs = owner
+            .transition(idle, |i| Handshake { name: i.name, nonce: 0 })
+            .unwrap();
+        assert_eq!(Rc::as_ptr(hs.cell()), ptr);
+        let est = owner
+            .transition(hs, |h| Established {
+                name: h.name,
+                nonce: h.nonce,
+                log: Vec::new(),
+            })
+            .unwrap();
+        assert!(core::ptr::eq(Rc::as_ptr(est.cell()), ptr));
+    }
+
+    #[test]
+    fn phase_poisoned() {
+        let mut owner = QCellOwner::new();
+        let idle = PhaseHandle::new(&owner, Idle { name: "p".into() });
+        let other = idle.clone();
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            let _ = owner.transition(idle, |_| -> Handshake { panic!("boom") });
+        }));
+        assert!(r.is_err());
+        assert_eq!(owner.ro_phase(&other).unwrap_err(), PhaseError::Poisoned);
+    }
+
+    #[test]
+    #[should_panic]
+    fn phase_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let h = PhaseHandle::new(&owner1, 1_u32);
+        let _ = owner2.ro_phase(&h);
+    }
+}
@@ -0,0 +1,297 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::{sound, QCellOwnerPinned};
+
+/// Create a [`QCellOwnerPinned`] pinned on the stack, and bind a
+/// `Pin<&mut QCellOwnerPinned>` to it under the given name.
+///
+/// This is the same as creating the owner and then using
+/// `pin_utils::pin_mut!` or `std::pin::pin!`, but needs no extra
+/// dependency, and works with Rust versions older than 1.68 where
+/// `std::pin::pin!` isn't available.  The owner itself is held in a
+/// variable hidden by macro hygiene, so it can't be moved or
+/// forgotten, and it is dropped at the end of the enclosing scope.
+///
+/// ```
+///# use qcell::{pin_owner, QCell, QCellOwnerPinned};
+///# use std::pin::Pin;
+/// pin_owner!(owner);
+/// let cell = owner.as_ref().cell(1);
+/// *owner.as_mut().rw(&cell) += 1;
+/// bump(owner.as_mut(), &cell);
+/// assert_eq!(*owner.as_ref().ro(&cell), 3);
+///
+/// fn bump(owner: Pin<&mut QCellOwnerPinned>, cell: &QCell<i32>) {
+///     *owner.rw(cell) += 1;
+/// }
+/// ```
+///
+/// # Use within `async` code
+///
+/// Used within an `async fn` or `async` block, the hidden owner
+/// becomes part of the state of the future, and since a future is
+/// pinned before it is polled, the owner stays pinned across `.await`
+/// points.  So this is all that is needed to keep an owner across
+/// awaits:
+///
+/// ```
+///# use qcell::pin_owner;
+/// async fn count(ticks: impl Fn() -> std::future::Ready<()>) -> u32 {
+///     pin_owner!(owner);
+///     let cell = owner.as_ref().cell(0);
+///     for _ in 0..3 {
+///         ticks().await;
+///         *owner.as_mut().rw(&cell) += 1;
+///     }
+///     *owner.as_ref().ro(&cell)
+/// }
+/// ```
+///
+/// If the future is dropped before it completes, the owner and any
+/// cells in the future's state are dropped along with it, as with any
+/// other local variables.  A borrow of a cell can't be held across an
+/// `.await` unless the owner remains borrowed across it, which the
+/// compiler checks as usual, so dropping the future mid-borrow
+/// just ends the borrow.  Nothing special is needed.
+///
+/// For a future written by hand, which can't have pinned local
+/// variables, see [`PinnedOwnerFuture`].
+///
+/// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+/// [`PinnedOwnerFuture`]: struct.PinnedOwnerFuture.html
+#[macro_export]
+macro_rules! pin_owner {
+    ($name:ident) => {
+        let mut owner = $crate::QCellOwnerPinned::new();
+        // Safety: `owner` is hidden by hygiene, so is never moved again
+        #[allow(unused_mut)]
+        let mut $name = unsafe { $crate::__pin_owner_unchecked(&mut owner) };
+    };
+}
+
+/// # Safety
+///
+/// The owner must never be moved again, and its memory must not be
+/// reused before it is dropped.
+#[doc(hidden)]
+#[inline]
+pub unsafe fn pin_owner_unchecked(owner: &mut QCellOwnerPinned) -> Pin<&mut QCellOwnerPinned> {
+    // Safety: Passed on to the caller, as above
+    sound::pin_unchecked(owner)
+}
+
+/// A hand-written future which owns a [`QCellOwnerPinned`].
+///
+/// A future that is written by hand has to project its pin onto its
+/// fields to get a `Pin<&mut QCellOwnerPinned>`, which normally means
+/// using `pin-project` or unsafe code.  This does the projection
+/// instead.  It holds the owner, some state of type `S`, and a poll
+/// function, which is called on each poll with the pinned owner,
+/// mutable access to the state, and the task context.  The state is
+/// not pinned, so cells created during polling can be kept there.
+///
+/// The owner's ID is only known once the future has been pinned, so
+/// cells owned by it have to be created during the first poll, not
+/// before.
+///
+/// ```
+///# use qcell::{PinnedOwnerFuture, QCell, QCellOwnerPinned};
+///# use std::future::Future;
+///# use std::pin::Pin;
+///# use std::sync::Arc;
+///# use std::task::{Context, Poll, Wake, Waker};
+/// let fut = PinnedOwnerFuture::new(
+///     None,
+///     |owner: Pin<&mut QCellOwnerPinned>, cell: &mut Option<QCell<u32>>, cx: &mut Context<'_>| {
+///         let cell = cell.get_or_insert_with(|| owner.as_ref().cell(0));
+///         let count = owner.rw(cell);
+///         *count += 1;
+///         if *count < 3 {
+///             cx.waker().wake_by_ref();
+///             Poll::Pending
+///         } else {
+///             Poll::Ready(*count)
+///         }
+///     },
+/// );
+///# struct NoopWaker;
+///# impl Wake for NoopWaker { fn wake(self: Arc<Self>) {} }
+///# let waker = Waker::from(Arc::new(NoopWaker));
+///# let mut cx = Context::from_waker(&waker);
+///# let mut fut = Box::pin(fut);
+///# let count = loop {
+///#     if let Poll::Ready(v) = fut.as_mut().poll(&mut cx) { break v; }
+///# };
+///# assert_eq!(count, 3);
+/// ```
+///
+/// Dropping the future drops the owner and the state, like any other
+/// future.  Borrows made through the owner during a poll can't
+/// outlive the call to the poll function, so there is never a borrow
+/// outstanding when this happens.
+///
+/// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+pub struct PinnedOwnerFuture<S, F> {
+    owner: QCellOwnerPinned,
+    state: S,
+    poll: F,
+}
+
+impl<S, F> PinnedOwnerFuture<S, F> {
+    /// Create the future from its initial state and poll function
+    #[inline]
+    pub const fn new(state: S, poll: F) -> Self {
+        Self {
+            owner: QCellOwnerPinned::new(),
+            state,
+            poll,
+        }
+    }
+
+    // The owner is structurally pinned.  The state and the poll
+    // function are not.
+    #[inline]
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut QCellOwnerPinned>, &mut S, &mut F) {
+        // Safety: Only `owner` is structurally pinned, and this type has no `Drop`
+        let this = unsafe { sound::pin_get_unchecked_mut(self) };
+        // Safety: A field of a pinned value, never moved, as above
+        let owner = unsafe { sound::pin_unchecked(&mut this.owner) };
+        (owner, &mut this.state, &mut this.poll)
+    }
+}
+
+impl<S, F, R> Future for PinnedOwnerFuture<S, F>
+where
+    F: FnMut(Pin<&mut QCellOwnerPinned>, &mut S, &mut Context<'_>) -> Poll<R>,
+{
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let (owner, state, poll) = self.project();
+        poll(owner, state, cx)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::PinnedOwnerFuture;
+    use crate::{QCell, QCellOwnerID, QCellOwnerPinned};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    // Poll the future to completion, returning the result and the
+    // number of polls it took
+    fn block_on<F: Future>(fut: F) -> (F::Output, usize) {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        let mut polls = 0;
+        loop {
+            polls += 1;
+            if let Poll::Ready(v) = fut.as_mut().poll(&mut cx) {
+                return (v, polls);
+            }
+        }
+    }
+
+    // Returns `Pending` once before completing
+    struct YieldNow(bool);
+    impl Future for YieldNow {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn pin_owner_across_awaits() {
+        let ((sum, id_stable), polls) = block_on(async {
+            crate::pin_owner!(owner);
+            let id = owner.as_ref().id();
+            let a = Rc::new(owner.as_ref().cell(1));
+            YieldNow(false).await;
+            let b = owner.as_ref().cell(10);
+            for _ in 0..3 {
+                YieldNow(false).await;
+                let (a, b) = owner.as_mut().rw2(&a, &b);
+                *b += *a;
+                *a += 1;
+            }
+            let sum = *owner.as_ref().ro(&b);
+            (sum, owner.as_ref().id() == id)
+        });
+        assert_eq!((sum, id_stable, polls), (10 + 1 + 2 + 3, true, 5));
+    }
+
+    #[derive(Default)]
+    struct State {
+        id: Option<QCellOwnerID>,
+        cells: Vec<QCell<u32>>,
+    }
+
+    fn step(owner: Pin<&mut QCellOwnerPinned>, state: &mut State, _: &mut Context<'_>) -> Poll<u32> {
+        // The ID stays the same from poll to poll
+        let id = owner.as_ref().id();
+        assert_eq!(*state.id.get_or_insert(id), id);
+        state.cells.push(owner.as_ref().cell(state.cells.len() as u32));
+        let n = state.cells.len();
+        if n < 2 {
+            return Poll::Pending;
+        }
+        let (last, prev) = {
+            let (head, tail) = state.cells.split_at(n - 1);
+            (&tail[0], &head[n - 2])
+        };
+        let (last, prev) = owner.rw2(last, prev);
+        *last += *prev;
+        if n < 4 {
+            Poll::Pending
+        } else {
+            Poll::Ready(*last)
+        }
+    }
+
+    #[test]
+    fn pinned_owner_future() {
+        let fut = PinnedOwnerFuture::new(State::default(), step);
+        // Cells are 0, 1, 2, 3, with each added to by the one before
+        // it: 0, 0+1, 1+2, 3+3
+        assert_eq!(block_on(fut), (6, 4));
+    }
+
+    #[test]
+    fn pinned_owner_future_dropped_mid_way() {
+        let dropped = Rc::new(());
+        let tracker = dropped.clone();
+        let mut fut = Box::pin(PinnedOwnerFuture::new(
+            (tracker, Vec::new()),
+            |owner: Pin<&mut QCellOwnerPinned>, state: &mut (Rc<()>, Vec<QCell<u8>>), _: &mut Context<'_>| {
+                state.1.push(owner.as_ref().cell(1));
+                Poll::<()>::Pending
+            },
+        ));
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..3 {
+            assert!(fut.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(Rc::strong_count(&dropped), 2);
+        drop(fut);
+        assert_eq!(Rc::strong_count(&dropped), 1);
+    }
+}
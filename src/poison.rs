@@ -0,0 +1,287 @@
+use core::fmt;
+
+use crate::{QCell, QCellBorrow, QCellOwnerID};
+
+/// Error returned when accessing a [`PoisonCell`] whose last
+/// [`PoisonCell::with_mut`] call panicked.
+///
+/// [`PoisonCell`]: struct.PoisonCell.html
+/// [`PoisonCell::with_mut`]: struct.PoisonCell.html#method.with_mut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellPoisoned;
+
+impl fmt::Display for CellPoisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cell poisoned by a panic during a previous update")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for CellPoisoned {}
+
+/// Contents of the [`QCell`] inside a [`PoisonCell`]: the value plus
+/// the poison flag.
+///
+/// [`PoisonCell`]: struct.PoisonCell.html
+pub struct PoisonState<T: ?Sized> {
+    poisoned: bool,
+    value: T,
+}
+
+impl<T: ?Sized> PoisonState<T> {
+    /// Test whether the cell is poisoned
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Get the value, ignoring the poison flag
+    #[inline]
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Get the value mutably, ignoring the poison flag
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// [`QCell`] that is poisoned if a panic unwinds out of an update,
+/// like a `Mutex` or `RwLock`.
+///
+/// Updates made through [`PoisonCell::with_mut`] mark the cell as
+/// being in progress before calling the closure, and clear the mark
+/// once it returns.  If the closure panics, the mark is left set, and
+/// from then on [`PoisonCell::try_ro_poison`],
+/// [`PoisonCell::try_rw_poison`] and [`PoisonCell::with_mut`] return
+/// `Err(CellPoisoned)`, since the value may have been left with its
+/// invariants broken.  Once the value has been checked or repaired,
+/// [`PoisonCell::clear_poison`] makes it accessible again.
+///
+/// This is opt-in: plain [`QCell`] instances are unaffected.  Direct
+/// access to the inner [`QCell`] through [`PoisonCell::inner`]
+/// bypasses the poison checks by design.
+///
+/// The owner may be any [`QCellBorrow`] type: a [`QCellOwner`], a
+/// [`QCellOwnerSeq`] or a `Pin<&mut QCellOwnerPinned>`.
+///
+/// ```
+///# use qcell::{CellPoisoned, PoisonCell, QCellOwnerSeq};
+///# use std::panic::{catch_unwind, AssertUnwindSafe};
+/// // Safety: No other owner is created with this ID
+/// let mut owner = unsafe { QCellOwnerSeq::new() };
+/// let cell = PoisonCell::new(&owner, vec![1, 2, 3]);
+/// let result = catch_unwind(AssertUnwindSafe(|| {
+///     cell.with_mut(&mut owner, |v| {
+///         v.clear();
+///         panic!("half-way through");
+///     })
+/// }));
+/// assert!(result.is_err());
+/// assert_eq!(cell.try_ro_poison(&owner), Err(CellPoisoned));
+/// cell.clear_poison(&mut owner);
+/// assert!(cell.try_ro_poison(&owner).unwrap().is_empty());
+/// ```
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`QCellBorrow`]: trait.QCellBorrow.html
+/// [`PoisonCell::with_mut`]: #method.with_mut
+/// [`PoisonCell::try_ro_poison`]: #method.try_ro_poison
+/// [`PoisonCell::try_rw_poison`]: #method.try_rw_poison
+/// [`PoisonCell::clear_poison`]: #method.clear_poison
+/// [`PoisonCell::inner`]: #method.inner
+pub struct PoisonCell<T: ?Sized> {
+    cell: QCell<PoisonState<T>>,
+}
+
+impl<T> PoisonCell<T> {
+    /// Create a new cell owned by the given owner ID, like
+    /// [`QCell::new`].
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn new(id: impl Into<QCellOwnerID>, value: T) -> Self {
+        Self {
+            cell: QCell::new(
+                id,
+                PoisonState {
+                    poisoned: false,
+                    value,
+                },
+            ),
+        }
+    }
+
+    /// Destroy the cell and return the contained value, or
+    /// `Err(CellPoisoned)` with the value if it is poisoned.  Safe
+    /// because it consumes the cell.
+    #[inline]
+    pub fn into_inner(self) -> Result<T, (CellPoisoned, T)> {
+        let state = self.cell.into_inner();
+        if state.poisoned {
+            Err((CellPoisoned, state.value))
+        } else {
+            Ok(state.value)
+        }
+    }
+}
+
+impl<T: ?Sized> PoisonCell<T> {
+    /// Borrow the contents immutably, or return `Err(CellPoisoned)` if
+    /// the cell is poisoned.  Panics if the cell is not owned by this
+    /// owner.
+    #[inline]
+    pub fn try_ro_poison<'a, O: QCellBorrow>(
+        &'a self,
+        owner: &'a O,
+    ) -> Result<&'a T, CellPoisoned> {
+        let state = owner.qcell_ro(&self.cell);
+        if state.poisoned {
+            Err(CellPoisoned)
+        } else {
+            Ok(&state.value)
+        }
+    }
+
+    /// Borrow the contents mutably, or return `Err(CellPoisoned)` if
+    /// the cell is poisoned.  Since this hands out a plain reference,
+    /// a panic while it is held does not poison the cell.  Use
+    /// [`PoisonCell::with_mut`] for updates that need that.  Panics
+    /// if the cell is not owned by this owner.
+    ///
+    /// [`PoisonCell::with_mut`]: #method.with_mut
+    #[inline]
+    pub fn try_rw_poison<'a, O: QCellBorrow>(
+        &'a self,
+        owner: &'a mut O,
+    ) -> Result<&'a mut T, CellPoisoned> {
+        let state = owner.qcell_rw(&self.cell);
+        if state.poisoned {
+            Err(CellPoisoned)
+        } else {
+            Ok(&mut state.value)
+        }
+    }
+
+    /// Call `f` on the contents mutably, returning its result, or
+    /// return `Err(CellPoisoned)` without calling it if the cell is
+    /// poisoned.  If `f` panics, the cell is left poisoned.  Panics if
+    /// the cell is not owned by this owner.
+    #[inline]
+    pub fn with_mut<O: QCellBorrow, R>(
+        &self,
+        owner: &mut O,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, CellPoisoned> {
+        let state = owner.qcell_rw(&self.cell);
+        if state.poisoned {
+            return Err(CellPoisoned);
+        }
+        state.poisoned = true;
+        let result = f(&mut state.value);
+        state.poisoned = false;
+        Ok(result)
+    }
+
+    /// Test whether the cell is poisoned.  Panics if the cell is not
+    /// owned by this owner.
+    #[inline]
+    pub fn is_poisoned<O: QCellBorrow>(&self, owner: &O) -> bool {
+        owner.qcell_ro(&self.cell).poisoned
+    }
+
+    /// Clear the poison flag, making the contents accessible again.
+    /// Panics if the cell is not owned by this owner.
+    #[inline]
+    pub fn clear_poison<O: QCellBorrow>(&self, owner: &mut O) {
+        owner.qcell_rw(&self.cell).poisoned = false;
+    }
+
+    /// Get the inner [`QCell`].  Access through this ignores the
+    /// poison flag.
+    #[inline]
+    pub fn inner(&self) -> &QCell<PoisonState<T>> {
+        &self.cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CellPoisoned, PoisonCell};
+    use crate::{QCellOwnerPinned, QCellOwnerSeq};
+    use pin_utils::pin_mut;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn poison_with_mut_panic() {
+        // Safety: Only one `QCellOwnerSeq` is used in this test
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = PoisonCell::new(&owner, (1, 1));
+        assert_eq!(cell.with_mut(&mut owner, |v| v.0 += 1), Ok(()));
+        assert!(!cell.is_poisoned(&owner));
+
+        // Break the invariant that both halves match, then panic
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            cell.with_mut(&mut owner, |v| {
+                v.1 = 100;
+                panic!("oops");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(cell.is_poisoned(&owner));
+        assert_eq!(cell.try_ro_poison(&owner), Err(CellPoisoned));
+        assert_eq!(cell.try_rw_poison(&mut owner), Err(CellPoisoned));
+        assert_eq!(cell.with_mut(&mut owner, |_| ()), Err(CellPoisoned));
+
+        // The inner cell is still accessible, so it can be repaired
+        assert_eq!(*owner.ro(cell.inner()).get(), (2, 100));
+        owner.rw(cell.inner()).get_mut().1 = 2;
+        cell.clear_poison(&mut owner);
+        assert_eq!(cell.try_ro_poison(&owner), Ok(&(2, 2)));
+        *cell.try_rw_poison(&mut owner).unwrap() = (3, 3);
+        assert_eq!(cell.into_inner(), Ok((3, 3)));
+    }
+
+    #[test]
+    fn poison_pinned_owner() {
+        let owner = QCellOwnerPinned::new();
+        pin_mut!(owner);
+        let cell = PoisonCell::new(owner.as_ref(), String::from("a"));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            cell.with_mut(&mut owner, |s| {
+                s.push('b');
+                panic!("oops");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(cell.is_poisoned(&owner));
+        assert_eq!(cell.into_inner(), Err((CellPoisoned, String::from("ab"))));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn poison_bad_owner() {
+        use crate::QCellOwner;
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cell = PoisonCell::new(&owner1, 0);
+        let _ = cell.try_ro_poison(&owner2);
+    }
+
+    #[test]
+    fn poison_qcell_untouched() {
+        use crate::QCell;
+        use core::mem::size_of;
+        // Poisoning lives entirely in the wrapper
+        assert_eq!(
+            size_of::<QCell<u64>>(),
+            size_of::<crate::QCellOwnerID>() + 8
+        );
+        assert!(size_of::<PoisonCell<u64>>() > size_of::<QCell<u64>>());
+    }
+}
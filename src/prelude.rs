@@ -0,0 +1,52 @@
+//! Imports for common setups, with `use qcell::prelude::*;`.
+//!
+//! This brings in the four cell types and their owners, the
+//! `QCell` owner ID and other `QCell` owners, the traits needed to
+//! call the cell methods generically, and the [`marker!`] macro.
+//! With **alloc**, it also brings in the [`Shared`] and
+//! [`SharedSync`] aliases with their constructors, and
+//! [`CollectCells`].  With **generativity**, it brings in
+//! `make_guard!` for [`LCellOwner::new`].  Items which need a feature
+//! that isn't enabled are left out.
+//!
+#![cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ use qcell::prelude::*;
+ let mut owner = QCellOwner::new();
+ let item: Shared<Vec<u8>> = new_shared(&owner, Vec::new());
+ owner.rw(&item).push(1);
+
+ LCellOwner::scope(|mut owner| {
+     let cells: Vec<LCell<'_, u8>> = (1..=3).collect_cells(&owner);
+     *owner.rw(&cells[0]) += owner.get(&cells[2]);
+     assert_eq!(owner.get(&cells[0]), 4);
+ });
+ ```
+"
+)]
+//!
+//! [`marker!`]: ../macro.marker.html
+//! [`Shared`]: ../type.Shared.html
+//! [`SharedSync`]: ../type.SharedSync.html
+//! [`CollectCells`]: ../collect/trait.CollectCells.html
+//! [`LCellOwner::new`]: ../struct.LCellOwner.html#method.new
+
+pub use crate::marker;
+pub use crate::{LCell, LCellOwner};
+pub use crate::{QCell, QCellBorrow, QCellOwnerID, QCellOwnerPinned, QCellOwnerSeq};
+pub use crate::{TCell, TCellMarker, TCellOwner};
+
+#[cfg(feature = "alloc")]
+pub use crate::collect::CollectCells;
+#[cfg(feature = "alloc")]
+pub use crate::QCellOwner;
+#[cfg(feature = "alloc")]
+pub use crate::{new_shared, new_shared_sync, Shared, SharedSync};
+
+#[cfg(feature = "std")]
+pub use crate::{TLCell, TLCellOwner};
+
+#[cfg(feature = "generativity")]
+pub use crate::generativity::make_guard;
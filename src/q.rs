@@ -0,0 +1,106 @@
+//! Free functions for short, script-like code using [`QCell`].
+//!
+//! These are just shorter spellings of the usual constructors, for
+//! examples, tests and small programs where the types get in the way
+//! of reading the code.
+//!
+#![cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ use qcell::q;
+ let mut owner = q::owner();
+ let count = q::cell(&owner, 0);
+ let log = q::shared(&owner, Vec::new());
+ for i in 1..=3 {
+     *owner.rw(&count) += i;
+     owner.rw(&log).push(i);
+ }
+ assert_eq!(owner.get(&count), 6);
+ assert_eq!(*owner.ro(&log), [1, 2, 3]);
+ ```
+"
+)]
+//!
+//! [`QCell`]: ../struct.QCell.html
+
+use crate::{QCell, QCellOwnerID};
+#[cfg(feature = "alloc")]
+use crate::{QCellOwner, Shared};
+
+/// Create a new owner.  Same as [`QCellOwner::new`].
+///
+/// ```
+///# use qcell::q;
+/// let owner = q::owner();
+/// let cell = q::cell(&owner, 1);
+/// assert_eq!(owner.get(&cell), 1);
+/// ```
+///
+/// [`QCellOwner::new`]: ../struct.QCellOwner.html#method.new
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[inline]
+pub fn owner() -> QCellOwner {
+    QCellOwner::new()
+}
+
+/// Create a new cell owned by the given owner.  Same as
+/// [`QCell::new`].
+///
+/// ```
+///# use qcell::{q, QCellOwnerSeq};
+/// // Safety: No ID collisions are being exploited
+/// let owner = unsafe { QCellOwnerSeq::new() };
+/// let cell = q::cell(&owner, "text");
+/// assert_eq!(*owner.ro(&cell), "text");
+/// ```
+///
+/// [`QCell::new`]: ../struct.QCell.html#method.new
+#[inline]
+pub fn cell<T>(owner: impl Into<QCellOwnerID>, value: T) -> QCell<T> {
+    QCell::new(owner, value)
+}
+
+/// Create a new cell owned by the given owner, inside an `Rc`.
+/// Same as [`new_shared`].
+///
+/// ```
+///# use qcell::q;
+/// let mut owner = q::owner();
+/// let a = q::shared(&owner, 1);
+/// let b = a.clone();
+/// *owner.rw(&a) += 1;
+/// assert_eq!(owner.get(&b), 2);
+/// ```
+///
+/// [`new_shared`]: ../fn.new_shared.html
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[inline]
+pub fn shared<T>(owner: impl Into<QCellOwnerID>, value: T) -> Shared<T> {
+    crate::new_shared(owner, value)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use crate::q;
+
+    #[test]
+    fn q_functions() {
+        let mut owner = q::owner();
+        let a = q::cell(&owner, 1);
+        let b = q::shared(&owner, 2);
+        let c = b.clone();
+        let (a, b) = owner.rw2(&a, &b);
+        std::mem::swap(a, b);
+        assert_eq!(owner.get(&c), 1);
+    }
+
+    #[test]
+    fn q_owners_are_distinct() {
+        let owner1 = q::owner();
+        let owner2 = q::owner();
+        assert_ne!(owner1.id(), owner2.id());
+    }
+}
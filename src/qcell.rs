@@ -1,31 +1,191 @@
+#[cfg(feature = "alloc")]
+use core::borrow::Borrow;
 use core::cell::UnsafeCell;
+use core::fmt;
 use core::marker::PhantomPinned;
+use core::mem;
 use core::pin::Pin;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::batch;
+use crate::capability::Capability;
+use crate::panic_messages;
+use crate::ro_slice::{writer_check, RoSlice, ValuePtr};
+#[cfg(feature = "alloc")]
+use crate::rw_many::distinct_check;
+use crate::sound;
+#[cfg(feature = "alloc")]
+use crate::view::VecViewMut;
+use crate::view::{SliceView, SliceViewMut};
+
 #[cfg(feature = "alloc")]
-use alloc::boxed::Box;
+use crate::collect::CollectCells;
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+#[cfg(feature = "record")]
+use crate::record::{AccessKind, CellId, Recorder};
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{OwnerKind, Registration};
 
 // Ensure the alignment is 2 so we can use odd-numbered IDs for those
 // created via `QCellOwnerSeq`.
 #[repr(align(2))]
 #[derive(Clone, Copy)]
-struct OwnerIDTarget {
+pub(crate) struct OwnerIDTarget {
     _data: u16,
 }
 
-const MAGIC_OWNER_ID_TARGET: OwnerIDTarget = OwnerIDTarget { _data: 0xCE11 };
+pub(crate) const MAGIC_OWNER_ID_TARGET: OwnerIDTarget = OwnerIDTarget { _data: 0xCE11 };
+
+// The owner ID scheme makes two assumptions about the target:
+//
+// - The addresses of `OwnerIDTarget` instances, whether on the heap
+//   (`QCellOwner`) or elsewhere (`QCellOwnerPinned`), are even.  The
+//   compiler guarantees this for a correctly aligned reference, so
+//   this only fails with a broken allocator that ignores the
+//   requested alignment.  It is checked whenever an ID is derived
+//   from an address, in release builds too, since the check is
+//   cheap compared to an allocation and an ID clash would be unsound.
+//
+// - `usize` is at least 32 bits, so that the `QCellOwnerSeq` counter
+//   can't wrap by accident.  With a 16-bit `usize` it would wrap
+//   after 32768 owners, which a long-running program could easily
+//   reach, so 16-bit targets are rejected outright.
+#[cfg(target_pointer_width = "16")]
+compile_error!(
+    "qcell does not support targets with a 16-bit usize, since the \
+     QCellOwnerSeq ID counter could wrap by accident"
+);
+
+const _: () = assert!(mem::align_of::<OwnerIDTarget>() >= 2);
+
+// Without **debug-owner-tracking**, the `QCell` layout documented for
+// C is the same whatever the build profile: a pointer-sized owner ID,
+// then the value.  `offset_of!` can't be used in a `const` at the
+// MSRV, but where the value leaves no trailing padding, its offset is
+// the size of the cell less the size of the value.
+#[cfg(not(feature = "debug-owner-tracking"))]
+const _: () = {
+    use mem::{align_of, size_of};
+    assert!(size_of::<QCellOwnerID>() == size_of::<usize>());
+    assert!(align_of::<QCellOwnerID>() == align_of::<usize>());
+    assert!(size_of::<QCell<usize>>() - size_of::<usize>() == size_of::<usize>());
+    assert!(size_of::<QCell<[usize; 3]>>() - size_of::<[usize; 3]>() == size_of::<usize>());
+    assert!(size_of::<QCell<u8>>() == 2 * size_of::<usize>());
+    assert!(size_of::<QCell<()>>() == size_of::<usize>());
+};
+
+#[cold]
+#[inline(never)]
+fn odd_address_panic(num: usize) -> ! {
+    panic!("{}: {:#x}", panic_messages::QCELL_OWNER_ADDRESS_ODD, num);
+}
+
+// Convert the address of an `OwnerIDTarget` into an owner ID number,
+// checking that it is even, so that it can't clash with a
+// `QCellOwnerSeq` ID.  The pointer is never dereferenced.
+#[inline]
+pub(crate) fn address_id(ptr: *const OwnerIDTarget) -> usize {
+    let num = ptr as usize;
+    if num & 1 != 0 {
+        odd_address_panic(num);
+    }
+    num
+}
+
+// Take `count` consecutive odd ID numbers from `counter`, returning
+// the first.  The counter starts odd and steps by 2 per ID, so the
+// numbers stay odd even when it wraps around past `usize::MAX`.
+#[inline]
+pub(crate) fn take_seq_ids(counter: &AtomicUsize, count: usize) -> usize {
+    // Use `Relaxed` ordering because we don't care who gets which
+    // ID, just that they are different.
+    counter.fetch_add(count.wrapping_mul(2), Ordering::Relaxed)
+}
+
+#[cold]
+#[inline(never)]
+pub(crate) fn bad_owner_panic() -> ! {
+    panic!("{}", panic_messages::QCELL_INCORRECT_OWNER);
+}
 
+#[cfg(feature = "debug-owner-tracking")]
 #[cold]
 #[inline(never)]
-fn bad_owner_panic() -> ! {
-    panic!("QCell accessed with incorrect owner");
+fn stale_owner_panic() -> ! {
+    panic!("{}", panic_messages::QCELL_STALE_OWNER_ID);
+}
+
+// Used to generate a nonce for each owner when owner tracking is
+// enabled.  This is only a debugging aid, so it doesn't matter if it
+// wraps on 32-bit platforms.
+#[cfg(feature = "debug-owner-tracking")]
+static NEXT_OWNER_NONCE: AtomicUsize = AtomicUsize::new(1);
+
+// Nonce allocated to each owner at creation, to detect a stale
+// `QCellOwnerID` being used after its owner has been dropped and its
+// ID reused by another owner.  Zero-sized unless owner tracking is
+// enabled, so release layout is unchanged.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub(crate) struct OwnerNonce {
+    #[cfg(feature = "debug-owner-tracking")]
+    value: u64,
+}
+
+impl OwnerNonce {
+    #[inline]
+    pub(crate) fn next() -> Self {
+        Self {
+            #[cfg(feature = "debug-owner-tracking")]
+            value: NEXT_OWNER_NONCE.fetch_add(1, Ordering::Relaxed) as u64,
+        }
+    }
+}
+
+// Nonce which is only allocated when first needed, so that owners
+// holding it can be created in a `const` context.  Zero means not yet
+// allocated.  Zero-sized unless owner tracking is enabled.
+pub(crate) struct LazyOwnerNonce {
+    #[cfg(feature = "debug-owner-tracking")]
+    value: AtomicUsize,
+}
+
+impl LazyOwnerNonce {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self {
+            #[cfg(feature = "debug-owner-tracking")]
+            value: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> OwnerNonce {
+        #[cfg(feature = "debug-owner-tracking")]
+        {
+            let mut value = self.value.load(Ordering::Relaxed);
+            while value == 0 {
+                // Skip zero if the global counter has wrapped
+                let next = NEXT_OWNER_NONCE.fetch_add(1, Ordering::Relaxed);
+                let result =
+                    self.value
+                        .compare_exchange(0, next, Ordering::Relaxed, Ordering::Relaxed);
+                value = result.map_or_else(|current| current, |_| next);
+            }
+            OwnerNonce {
+                value: value as u64,
+            }
+        }
+        #[cfg(not(feature = "debug-owner-tracking"))]
+        OwnerNonce {}
+    }
 }
 
 macro_rules! owner_check {
     ($owner:expr $(, $qcell:expr)+) => {
         $(
-            if $qcell.owner.0 != $owner.id().0 {
+            if !$qcell.owner.matches($owner.id()) {
                 bad_owner_panic();
             }
         )+
@@ -34,8 +194,8 @@ macro_rules! owner_check {
 
 #[cold]
 #[inline(never)]
-fn not_distinct_panic() -> ! {
-    panic!("Illegal to borrow same QCell twice with rw2() or rw3()");
+pub(crate) fn not_distinct_panic() -> ! {
+    panic!("{}", panic_messages::QCELL_BORROWED_TWICE);
 }
 
 macro_rules! distinct_check {
@@ -72,20 +232,110 @@ macro_rules! distinct_check {
 /// instances to be created after the owner has gone.  But [`QCell`]
 /// instances can outlive the owner in any case, so this makes no
 /// difference to safety.
+///
+/// # Stale IDs
+///
+/// Once an owner is dropped, its ID may be reused by a new owner.  A
+/// stale ID kept by buggy code would then create cells that the new
+/// owner can access, which isn't unsafe, but is a logic bug that is
+/// hard to trace.  So with the **debug-owner-tracking** feature,
+/// each owner also gets a nonce at creation, which is carried in the
+/// ID.  Accessing a cell with an owner that has the same ID but a
+/// different nonce then panics with "stale QCellOwnerID used after
+/// original owner was dropped".  Without the feature, the nonce is
+/// zero-sized, so the layout and behaviour are unchanged.  The
+/// feature is the only thing that changes the size of
+/// `QCellOwnerID`, and so the layout of [`QCell`], which is the same
+/// in debug and release builds.
+///
+/// [`QCell`]: struct.QCell.html
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct QCellOwnerID(usize);
+#[repr(C)]
+pub struct QCellOwnerID {
+    pub(crate) num: usize,
+    nonce: OwnerNonce,
+}
+
+impl fmt::Debug for QCellOwnerID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut t = f.debug_tuple("QCellOwnerID");
+        t.field(&format_args!("{:#x}", self.num));
+        #[cfg(feature = "debug-owner-tracking")]
+        t.field(&self.nonce.value);
+        t.finish()
+    }
+}
 
 impl QCellOwnerID {
+    #[inline]
+    pub(crate) const fn new(num: usize, nonce: OwnerNonce) -> Self {
+        Self { num, nonce }
+    }
+
+    // Check whether a cell with this owner ID may be accessed by the
+    // owner with ID `owner`.  With owner tracking enabled, panics if
+    // the IDs match but the nonces don't.
+    #[inline]
+    pub(crate) fn matches(self, owner: QCellOwnerID) -> bool {
+        if self.num != owner.num {
+            return false;
+        }
+        #[cfg(feature = "debug-owner-tracking")]
+        if self.nonce != owner.nonce {
+            stale_owner_panic();
+        }
+        true
+    }
+
     /// Create a new cell owned by this owner-ID.  See also
-    /// [`QCell::new`].
+    /// [`QCell::new`].  This is a `const fn`.
     ///
     /// [`QCell::new`]: struct.QCell.html
-    pub fn cell<T>(self, value: T) -> QCell<T> {
+    pub const fn cell<T>(self, value: T) -> QCell<T> {
         QCell {
             value: UnsafeCell::new(value),
             owner: self,
         }
     }
+
+    /// Create a new cell owned by this owner-ID and leak it, keeping
+    /// it for the rest of the program.  This is for cells that are
+    /// referenced from statics.  See [`QCellOwner::leak`].
+    ///
+    /// [`QCellOwner::leak`]: struct.QCellOwner.html#method.leak
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn leak_cell<T: 'static>(self, value: T) -> &'static QCell<T> {
+        Box::leak(Box::new(self.cell(value)))
+    }
+
+    /// Create a cell owned by this owner-ID for each `Ok` value
+    /// produced by the iterator.  Stops at the first `Err`, and
+    /// returns it along with its index in the iteration.  See also
+    /// [`CollectCells::collect_cells`].
+    ///
+    /// [`CollectCells::collect_cells`]: collect/trait.CollectCells.html#method.collect_cells
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn try_cells_from_iter<T, E>(
+        self,
+        iter: impl IntoIterator<Item = Result<T, E>>,
+    ) -> Result<Vec<QCell<T>>, (usize, E)> {
+        iter.into_iter().collect_cells(&self)
+    }
+
+    /// Same as [`QCellOwnerID::try_cells_from_iter`], but wraps each
+    /// cell in an `Arc`.
+    ///
+    /// [`QCellOwnerID::try_cells_from_iter`]: struct.QCellOwnerID.html#method.try_cells_from_iter
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn try_arc_cells_from_iter<T, E>(
+        self,
+        iter: impl IntoIterator<Item = Result<T, E>>,
+    ) -> Result<Vec<Arc<QCell<T>>>, (usize, E)> {
+        iter.into_iter().collect_cells(&self)
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -115,12 +365,47 @@ impl From<Pin<&QCellOwnerPinned>> for QCellOwnerID {
 /// are also convenience methods [`QCell::ro`] and [`QCell::rw`].  See
 /// also [crate documentation](index.html).
 ///
+/// # Layout
+///
+/// [`QCell`] is `#[repr(C)]`, so it may be embedded in structs that
+/// are shared with C code, with stable field offsets.  The C side
+/// must treat the owner ID as opaque, and must not access the value
+/// while Rust code may hold a borrow of it.  Without the
+/// **debug-owner-tracking** feature, in both debug and release
+/// builds, the layout is the owner ID as a pointer-sized integer,
+/// followed by the value at the next offset that suits its
+/// alignment:
+///
+/// ```c
+/// typedef struct {
+///     uintptr_t owner_id;
+///     T value;
+/// } QCell_T;
+/// ```
+///
+/// With the **debug-owner-tracking** feature, the owner ID also
+/// carries a 64-bit nonce (see [`QCellOwnerID`]), so the layout
+/// becomes:
+///
+/// ```c
+/// typedef struct {
+///     uintptr_t owner_id;
+///     uint64_t owner_nonce;
+///     T value;
+/// } QCell_T;
+/// ```
+///
+/// Since a feature is shared by the whole build, C code which depends
+/// on the layout only has to match whether **debug-owner-tracking** is
+/// enabled, not the build profile.
+///
 /// [`QCellOwner`]: struct.QCellOwner.html
 /// [`QCell::ro`]: struct.QCell.html#method.ro
 /// [`QCell::rw`]: struct.QCell.html#method.rw
+#[repr(C)]
 pub struct QCell<T: ?Sized> {
-    owner: QCellOwnerID,
-    value: UnsafeCell<T>,
+    pub(crate) owner: QCellOwnerID,
+    pub(crate) value: UnsafeCell<T>,
 }
 
 // QCell already automatically implements Send, but not Sync.
@@ -141,6 +426,13 @@ pub struct QCell<T: ?Sized> {
 // even though the locking mechanisms are different.
 unsafe impl<T: Send + Sync + ?Sized> Sync for QCell<T> {}
 
+impl<T: ?Sized> ValuePtr for QCell<T> {
+    type Value = T;
+    fn value_ptr(&self) -> *const T {
+        self.value.get()
+    }
+}
+
 impl<T> QCell<T> {
     /// Create a new [`QCell`] owned for borrowing purposes by the
     /// owner with the given [`QCellOwnerID`], or a type that can be
@@ -155,6 +447,17 @@ impl<T> QCell<T> {
         }
     }
 
+    /// Create a new [`QCell`] owned for borrowing purposes by the
+    /// owner with the given [`QCellOwnerID`].  Unlike
+    /// [`QCell::new`], this is a `const fn`, since it doesn't need to
+    /// call `Into::into`, which can't be called in a `const` context.
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub const fn new_with_id(id: QCellOwnerID, value: T) -> QCell<T> {
+        id.cell(value)
+    }
+
     /// Destroy the cell and return the contained value
     ///
     /// Safety: Since this consumes the cell, there can be no other
@@ -165,6 +468,34 @@ impl<T> QCell<T> {
     }
 }
 
+impl<T: ?Sized> QCell<T> {
+    /// Returns a mutable reference to the underlying data
+    ///
+    /// Note that this is only useful at the beginning-of-life or
+    /// end-of-life of the cell when you have exclusive access to it.
+    /// Normally you'd use [`QCell::rw`] or [`QCellOwner::rw`] to get
+    /// a mutable reference to the contents of the cell.
+    ///
+    /// Safety: This call borrows `QCell` mutably which guarantees
+    /// that we possess the only reference.  This means that there can
+    /// be no active borrows of other forms, even ones obtained using
+    /// an immutable reference.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Get the ID of the owner of this cell.  This allows cells from
+    /// several owners to be grouped by owner before processing, for
+    /// example with [`partition_by_owner`].
+    ///
+    /// [`partition_by_owner`]: fn.partition_by_owner.html
+    #[inline]
+    pub fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+}
+
 #[cfg(feature = "alloc")]
 impl<T: ?Sized> QCell<T> {
     /// Convenience method to borrow a cell immutably when the owner
@@ -181,25 +512,10 @@ impl<T: ?Sized> QCell<T> {
     /// [`QCellOwnerSeq::rw`] or [`QCellOwnerPinned::rw`] to borrow
     /// for other owner types.
     #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
     pub fn rw<'a>(&'a self, owner: &'a mut QCellOwner) -> &'a mut T {
         owner.rw(self)
     }
-
-    /// Returns a mutable reference to the underlying data
-    ///
-    /// Note that this is only useful at the beginning-of-life or
-    /// end-of-life of the cell when you have exclusive access to it.
-    /// Normally you'd use [`QCell::rw`] or [`QCellOwner::rw`] to get
-    /// a mutable reference to the contents of the cell.
-    ///
-    /// Safety: This call borrows `QCell` mutably which guarantees
-    /// that we possess the only reference.  This means that there can
-    /// be no active borrows of other forms, even ones obtained using
-    /// an immutable reference.
-    #[inline]
-    pub fn get_mut(&mut self) -> &mut T {
-        self.value.get_mut()
-    }
 }
 
 /// Borrowing-owner of zero or more [`QCell`] instances.
@@ -211,7 +527,9 @@ impl<T: ?Sized> QCell<T> {
 /// owner ID from the address of an internal memory allocation which
 /// this owner holds until it is dropped, which ensures that the ID is
 /// temporally unique.  The allocation is aligned to ensure that its
-/// ID cannot collide with those created using [`QCellOwnerSeq`].
+/// ID cannot collide with those created using [`QCellOwnerSeq`].  If
+/// the allocator doesn't honour that alignment, which would be a bug
+/// in the allocator, `new` panics rather than risk a collision.
 ///
 /// In a `no_std` environment this requires the `alloc` feature
 /// because it allocates memory.  For a `no_std` environment without
@@ -242,6 +560,13 @@ pub struct QCellOwner {
     // stable, but add `Pin` as a safeguard against any future
     // optimisation of `Box`.
     handle: Pin<Box<OwnerIDTarget>>,
+    // Kept here rather than in the target allocation, so that `id()`
+    // still never reads the target memory
+    nonce: OwnerNonce,
+    #[cfg(feature = "record")]
+    pub(crate) recorder: Option<Box<Recorder>>,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) diag: Registration,
 }
 
 #[cfg(feature = "alloc")]
@@ -256,11 +581,21 @@ impl Default for QCellOwner {
 #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
 impl QCellOwner {
     /// Create an owner that can be used for creating many [`QCell`]
-    /// instances.
+    /// instances.  This can't be a `const fn`, since it allocates.
     #[inline]
     pub fn new() -> Self {
         let handle = Box::pin(MAGIC_OWNER_ID_TARGET);
-        Self { handle }
+        // Check once here that the allocator honoured the alignment,
+        // rather than on every call to `id`
+        address_id(&*handle);
+        Self {
+            handle,
+            nonce: OwnerNonce::next(),
+            #[cfg(feature = "record")]
+            recorder: None,
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::auto(OwnerKind::QCellOwner, None),
+        }
     }
 
     /// Get the internal owner ID.  This may be used to create [`QCell`]
@@ -268,8 +603,14 @@ impl QCellOwner {
     /// useful if this structure is already borrowed.
     #[inline]
     pub fn id(&self) -> QCellOwnerID {
+        // This only converts the pointer to an integer, and never
+        // reads the target memory.  So we expect that `ro` and `rw`
+        // don't suffer from false sharing on the target allocation.
+        // Whether to pad it out to a cache line is still open until
+        // that is measured on a multi-core machine, see
+        // `benches/ro_contention.rs`, so for now it is left as it was.
         let raw_ptr: *const OwnerIDTarget = &*self.handle;
-        QCellOwnerID(raw_ptr as usize)
+        QCellOwnerID::new(raw_ptr as usize, self.nonce)
     }
 
     /// Create a new cell owned by this owner instance.  See also
@@ -280,6 +621,27 @@ impl QCellOwner {
         id.cell(value)
     }
 
+    /// Leak this owner, keeping it for the rest of the program.  This
+    /// suits a graph of cells that is built at startup and never
+    /// freed, since the owner and the cells can then be referenced
+    /// from statics.  The owner's ID is never released, so no later
+    /// owner can reuse it.  The `&'static mut` can be turned into a
+    /// `&'static` to share the owner between threads.  See also
+    /// [`QCellOwnerID::leak_cell`].
+    ///
+    /// ```
+    ///# use qcell::{QCell, QCellOwner};
+    /// let owner: &'static mut QCellOwner = QCellOwner::new().leak();
+    /// let cell: &'static QCell<u32> = owner.id().leak_cell(1);
+    /// *owner.rw(cell) += 1;
+    /// assert_eq!(owner.get(cell), 2);
+    /// ```
+    ///
+    /// [`QCellOwnerID::leak_cell`]: struct.QCellOwnerID.html#method.leak_cell
+    pub fn leak(self) -> &'static mut QCellOwner {
+        Box::leak(Box::new(self))
+    }
+
     /// Borrow contents of a [`QCell`] immutably (read-only).  Many
     /// [`QCell`] instances can be borrowed immutably at the same time
     /// from the same owner.  Panics if the [`QCell`] is not owned by
@@ -287,7 +649,8 @@ impl QCellOwner {
     #[inline]
     pub fn ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
         owner_check!(self, qc);
-        unsafe { &*qc.value.get() }
+        // Safety: Owned (owner ID checked above), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&qc.value) }
     }
 
     /// Borrow contents of a [`QCell`] mutably (read-write).  Only one
@@ -296,15 +659,20 @@ impl QCellOwner {
     /// another can be borrowed.  Panics if the [`QCell`] is not owned
     /// by this [`QCellOwner`].
     #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
     pub fn rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
         owner_check!(self, qc);
-        unsafe { &mut *qc.value.get() }
+        #[cfg(feature = "record")]
+        self.record(AccessKind::Rw, &[CellId::of(qc)]);
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&qc.value) }
     }
 
     /// Borrow contents of two [`QCell`] instances mutably.  Panics if
     /// the two [`QCell`] instances point to the same memory.  Panics
     /// if either [`QCell`] is not owned by this [`QCellOwner`].
     #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
     pub fn rw2<'a, T: ?Sized, U: ?Sized>(
         &'a mut self,
         qc1: &'a QCell<T>,
@@ -312,13 +680,17 @@ impl QCellOwner {
     ) -> (&'a mut T, &'a mut U) {
         owner_check!(self, qc1, qc2);
         distinct_check!(qc1, qc2);
-        unsafe { (&mut *qc1.value.get(), &mut *qc2.value.get()) }
+        #[cfg(feature = "record")]
+        self.record(AccessKind::Rw2, &[CellId::of(qc1), CellId::of(qc2)]);
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&qc1.value, &qc2.value) }
     }
 
     /// Borrow contents of three [`QCell`] instances mutably.  Panics
     /// if any pair of [`QCell`] instances point to the same memory.
     /// Panics if any [`QCell`] is not owned by this [`QCellOwner`].
     #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
     pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
         &'a mut self,
         qc1: &'a QCell<T>,
@@ -327,21 +699,380 @@ impl QCellOwner {
     ) -> (&'a mut T, &'a mut U, &'a mut V) {
         owner_check!(self, qc1, qc2, qc3);
         distinct_check!(qc1, qc2, qc3);
-        unsafe {
-            (
-                &mut *qc1.value.get(),
-                &mut *qc2.value.get(),
-                &mut *qc3.value.get(),
-            )
+        #[cfg(feature = "record")]
+        self.record(
+            AccessKind::Rw3,
+            &[CellId::of(qc1), CellId::of(qc2), CellId::of(qc3)],
+        );
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&qc1.value, &qc2.value, &qc3.value) }
+    }
+
+    /// Borrow contents of one [`QCell`] mutably, and the contents of
+    /// a slice of [`QCell`] instances immutably.  The readers may
+    /// repeat, but none may be the same cell as the writer.  Panics
+    /// with the position of the first reader that is the same as the
+    /// writer, or if any [`QCell`] is not owned by this
+    /// [`QCellOwner`].  The contents of the readers are fetched lazily
+    /// through the returned [`RoSlice`].
+    ///
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        w: &'a QCell<T>,
+        readers: &'a [&'a QCell<U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        owner_check!(self, w);
+        for r in readers {
+            owner_check!(self, r);
+        }
+        writer_check("QCell", w, readers);
+        // Safety: Owned (owner ID checked above), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
+    }
+
+    /// Copy the contents of a [`QCell`] out, like `Cell::get`.  No
+    /// borrow is held after this returns.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwner`].
+    #[inline]
+    pub fn get<T: Copy>(&self, qc: &QCell<T>) -> T {
+        *self.ro(qc)
+    }
+
+    /// Clone the contents of a [`QCell`] out.  This is a separate
+    /// call from [`QCellOwner::get`] so that `get` stays a plain copy.
+    /// Panics if the [`QCell`] is not owned by this [`QCellOwner`].
+    #[inline]
+    pub fn get_cloned<T: Clone>(&self, qc: &QCell<T>) -> T {
+        self.ro(qc).clone()
+    }
+
+    /// Replace the contents of a [`QCell`] with `value`, like
+    /// `Cell::set`, dropping the old value.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn set<T>(&mut self, qc: &QCell<T>, value: T) {
+        owner_check!(self, qc);
+        #[cfg(feature = "record")]
+        self.record(AccessKind::Set, &[CellId::of(qc)]);
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed
+        let slot = unsafe { sound::deref_unique(&qc.value) };
+        *slot = value;
+    }
+
+    /// Replace the contents of a [`QCell`] with `value`, returning
+    /// the old value, like `Cell::replace`.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn replace<T>(&mut self, qc: &QCell<T>, value: T) -> T {
+        owner_check!(self, qc);
+        #[cfg(feature = "record")]
+        self.record(AccessKind::Replace, &[CellId::of(qc)]);
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed
+        let slot = unsafe { sound::deref_unique(&qc.value) };
+        mem::replace(slot, value)
+    }
+
+    /// Replace the contents of a [`QCell`] with the result of
+    /// calling `f` on a copy of the old contents, and return the new
+    /// value.  Panics if the [`QCell`] is not owned by this
+    /// [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn update<T: Copy>(&mut self, qc: &QCell<T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(qc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Move the contents of `from` into `to`, leaving `T::default()`
+    /// in `from`, for reusing an expensive value such as a `Vec` with
+    /// its capacity in a fresh cell.  The value that was in `to` is
+    /// dropped.  No reallocation or copy of the value's heap data takes
+    /// place.  Panics if the two [`QCell`] instances are the same,
+    /// like `rw2`.  Panics if either [`QCell`] is not owned by this
+    /// [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn recycle_into<T: Default>(&mut self, from: &QCell<T>, to: &QCell<T>) {
+        let (from, to) = self.rw2(from, to);
+        *to = mem::take(from);
+    }
+
+    /// Copy the contents of many [`QCell`]s into `out` without
+    /// allocating, for example to take a snapshot of a set of
+    /// parameters.  Each cell's owner is checked as for
+    /// [`QCellOwner::get`], which is one compare per cell.  Panics if
+    /// any [`QCell`] is not owned by this [`QCellOwner`].
+    #[inline]
+    pub fn read_into<T: Copy, const N: usize>(&self, cells: &[&QCell<T>; N], out: &mut [T; N]) {
+        self.read_into_slice(cells, out);
+    }
+
+    /// Copy the contents of many [`QCell`]s into `out`, like
+    /// [`QCellOwner::read_into`], but with the lengths checked at
+    /// runtime.  Panics if `cells` and `out` have different
+    /// lengths, or if any [`QCell`] is not owned by this [`QCellOwner`].
+    #[inline]
+    pub fn read_into_slice<T: Copy>(&self, cells: &[&QCell<T>], out: &mut [T]) {
+        batch::length_check(cells.len(), out.len());
+        for (qc, value) in cells.iter().zip(out) {
+            *value = *self.ro(qc);
+        }
+    }
+
+    /// Copy the values in `src` into many [`QCell`]s without
+    /// allocating.  The cells are written in order, and no check
+    /// is made for repeated cells, so if the same [`QCell`] appears
+    /// more than once then the last write wins.  Panics if any
+    /// [`QCell`] is not owned by this [`QCellOwner`], in which case the cells
+    /// before it have already been written.
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn write_from<T: Copy, const N: usize>(&mut self, cells: &[&QCell<T>; N], src: &[T; N]) {
+        self.write_from_slice(cells, src);
+    }
+
+    /// Copy the values in `src` into many [`QCell`]s, like
+    /// [`QCellOwner::write_from`], but with the lengths checked at
+    /// runtime.  Panics before writing anything if `cells` and
+    /// `src` have different lengths.  Also panics if any [`QCell`] is
+    /// not owned by this [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn write_from_slice<T: Copy>(&mut self, cells: &[&QCell<T>], src: &[T]) {
+        batch::length_check(cells.len(), src.len());
+        for (qc, value) in cells.iter().zip(src) {
+            *self.rw(qc) = *value;
+        }
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a slice, `Vec`, boxed
+    /// slice or array as a read-only [`SliceView`], which supports
+    /// indexing by position or range  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwner`].
+    #[inline]
+    pub fn view<'a, T, S: AsRef<[T]> + ?Sized>(&'a self, qc: &'a QCell<S>) -> SliceView<'a, T> {
+        SliceView::new(self.ro(qc).as_ref())
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a `Vec` as a
+    /// [`VecViewMut`], which supports indexing for reading and
+    /// writing.  This owner stays mutably borrowed while the view
+    /// exists, so no other cell can be borrowed through it  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwner`].
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn view_mut<'a, T>(&'a mut self, qc: &'a QCell<Vec<T>>) -> VecViewMut<'a, T> {
+        VecViewMut::new(self.rw(qc))
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a slice, `Vec`, boxed
+    /// slice or array as a [`SliceViewMut`], which supports indexing
+    /// for reading and writing.  This owner stays mutably borrowed
+    /// while the view exists.  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn slice_view_mut<'a, T, S: AsMut<[T]> + ?Sized>(
+        &'a mut self,
+        qc: &'a QCell<S>,
+    ) -> SliceViewMut<'a, T> {
+        SliceViewMut::new(self.rw(qc).as_mut())
+    }
+
+    /// Wrap a mutable borrow of this owner in a [`Capability`] which
+    /// can only read [`QCell`] instances if `READ` is `true`, and only
+    /// write them if `WRITE` is `true`, to hand to code which should
+    /// be restricted to one or the other.  See [`ReadOnly`] and
+    /// [`WriteOnly`].
+    ///
+    /// [`Capability`]: struct.Capability.html
+    /// [`ReadOnly`]: type.ReadOnly.html
+    /// [`WriteOnly`]: type.WriteOnly.html
+    #[inline]
+    pub fn capability<const READ: bool, const WRITE: bool>(
+        &mut self,
+    ) -> Capability<'_, QCellOwner, READ, WRITE> {
+        Capability::new(self)
+    }
+
+    /// Borrow the contents of a [`QCell`] mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  No runtime check is needed to keep the
+    /// two apart: the cell's contents are reached through the owner,
+    /// and `other` through a separate `&mut`, which safe code can't
+    /// point into the cell's contents.  Since the owner is borrowed
+    /// for the call, `f` can't use it.  Panics if the [`QCell`] is not owned by
+    /// this [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        &'a mut self,
+        qc: &'a QCell<T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(qc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two [`QCell`] instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.  Also panics if either is not owned by
+    /// this [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        &'a mut self,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(qc1, qc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of a [`QCell`] immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwner`].
+    ///
+    /// ```
+    ///# use qcell::{QCell, QCellOwner};
+    /// enum Conn {
+    ///     Idle,
+    ///     Retrying(u32),
+    /// }
+    /// enum Next {
+    ///     Connect,
+    ///     GiveUp(u32),
+    ///     Wait,
+    /// }
+    ///
+    /// let mut owner = QCellOwner::new();
+    /// let conn = owner.cell(Conn::Retrying(3));
+    /// let log = owner.cell(Vec::new());
+    /// let next = owner.inspect(&conn, |conn| match conn {
+    ///     Conn::Idle => Next::Connect,
+    ///     Conn::Retrying(n) if *n >= 3 => Next::GiveUp(*n),
+    ///     Conn::Retrying(_) => Next::Wait,
+    /// });
+    /// match next {
+    ///     Next::Connect => *owner.rw(&conn) = Conn::Retrying(0),
+    ///     Next::GiveUp(n) => {
+    ///         owner.rw(&log).push(format!("gave up after {}", n));
+    ///         *owner.rw(&conn) = Conn::Idle;
+    ///     }
+    ///     Next::Wait => (),
+    /// }
+    /// assert_eq!(owner.ro(&log).len(), 1);
+    /// ```
+    ///
+    /// See [`QCELL-E011`] for the error from writing this as a
+    /// single `match`.
+    ///
+    /// [`QCELL-E011`]: errors/e011/index.html
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn inspect<T: ?Sized, D>(
+        &self,
+        qc: &QCell<T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(qc))
+    }
+
+    /// Same as `inspect`, but borrows two [`QCell`] instances, which
+    /// may be the same cell.  Panics if either is not owned by this
+    /// [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        &self,
+        qc1: &QCell<T>,
+        qc2: &QCell<U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(qc1), self.ro(qc2))
+    }
+
+    /// Read the contents of a [`QCell`] with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.  Panics if the [`QCell`] is not owned by this
+    /// [`QCellOwner`].
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    pub fn modify_if<T: ?Sized, D>(
+        &mut self,
+        qc: &QCell<T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.ro(qc)) {
+            Some(decision) => {
+                apply(self.rw(qc), decision);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Borrow the contents of a slice of [`QCell`] instances mutably,
+    /// returning a `Vec` of references in the same order.  The slice
+    /// may hold the cells directly, or anything that borrows as a
+    /// [`QCell`], such as `Rc<QCell<T>>`, `Arc<QCell<T>>` or
+    /// `&QCell<T>`.  Panics if any two entries are the same cell,
+    /// which includes two `Rc` or `Arc` clones of the same cell, or if
+    /// any [`QCell`] is not owned by this [`QCellOwner`].
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_slice<'a, T: ?Sized, C: Borrow<QCell<T>>>(
+        &'a mut self,
+        cells: &'a [C],
+    ) -> Vec<&'a mut T> {
+        self.rw_from_iter(cells)
+    }
+
+    /// Same as [`QCellOwner::rw_from_slice`], but takes the cells from
+    /// an iterator of references.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_iter<'a, T: ?Sized + 'a, C: Borrow<QCell<T>> + 'a>(
+        &'a mut self,
+        cells: impl IntoIterator<Item = &'a C>,
+    ) -> Vec<&'a mut T> {
+        let cells: Vec<&'a QCell<T>> = cells.into_iter().map(Borrow::borrow).collect();
+        for qc in &cells {
+            owner_check!(self, qc);
         }
+        distinct_check("QCell", &cells);
+        cells
+            .into_iter()
+            // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+            .map(|qc| unsafe { sound::deref_unique(&qc.value) })
+            .collect()
     }
 }
 
 // Used to generate a unique QCellOwnerID number for each
 // QCellOwnerSeq.  Start at index 1 and increment by 2 each time so
 // the number is always odd to ensure it will never conflict with the
-// address of a OwnerIDTarget.
-static FAST_QCELLOWNER_ID: AtomicUsize = AtomicUsize::new(1);
+// address of a OwnerIDTarget.  `QCellOwnerNamespace` also reserves
+// blocks of numbers from here.
+pub(crate) static FAST_QCELLOWNER_ID: AtomicUsize = AtomicUsize::new(1);
 
 /// Borrowing-owner of zero or more [`QCell`] instances, using an ID
 /// sequence.
@@ -362,18 +1093,30 @@ static FAST_QCELLOWNER_ID: AtomicUsize = AtomicUsize::new(1);
 /// Because of that the [`QCellOwnerSeq::new`] method is marked as
 /// `unsafe`.  However it is not possible to use it unsafely by
 /// accident, only through making an intentional, determined and
-/// CPU-intensive effort to exploit it.
+/// CPU-intensive effort to exploit it.  That depends on `usize` being
+/// at least 32 bits, so the crate doesn't compile for targets with a
+/// 16-bit `usize`, where the counter could wrap in normal use.
 ///
 /// See [crate documentation](index.html).
+//
+// `Send` and `Sync` come from the auto traits, since the owner is
+// just a number.  Sending an owner to another thread is fine because
+// the cells it gives access to must themselves be `Send` or `Sync` to
+// be reached from there.  The assertions module checks these, and
+// also that the owner can't be cloned, so adding a field can't change
+// them unnoticed.
 pub struct QCellOwnerSeq {
-    id: QCellOwnerID,
+    pub(crate) id: QCellOwnerID,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) diag: Registration,
 }
 
 // Default implementation not possible, due to `unsafe`
 
 impl QCellOwnerSeq {
     /// Create an owner that can be used for creating many [`QCell`]
-    /// instances.
+    /// instances.  This can't be a `const fn`, since each call takes
+    /// the next ID from a global counter.
     ///
     /// # Safety
     ///
@@ -401,14 +1144,55 @@ impl QCellOwnerSeq {
     #[inline]
     pub unsafe fn new() -> Self {
         // Must increment by 2 to ensure we never collide with an ID
-        // derived from the address of an `OwnerIDTarget`.  Use
-        // `Relaxed` ordering because we don't care who gets which ID,
-        // just that they are different.
+        // derived from the address of an `OwnerIDTarget`
         Self {
-            id: QCellOwnerID(FAST_QCELLOWNER_ID.fetch_add(2, Ordering::Relaxed)),
+            id: QCellOwnerID::new(take_seq_ids(&FAST_QCELLOWNER_ID, 1), OwnerNonce::next()),
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::auto(OwnerKind::QCellOwnerSeq, None),
         }
     }
 
+    /// Create `N` owners with consecutive IDs, reserving them from the
+    /// global counter with a single atomic operation.  This is cheaper
+    /// than calling [`QCellOwnerSeq::new`] `N` times when setting up
+    /// many owners at once, for example one for each subsystem or
+    /// entity archetype, and keeps their IDs together, which makes
+    /// them easier to recognise when debugging.
+    ///
+    /// ```
+    ///# use qcell::QCellOwnerSeq;
+    /// // Safety: No other owners are created with these IDs
+    /// let [mut physics, mut audio, input, ai] = unsafe { QCellOwnerSeq::new_batch() };
+    /// let velocity = physics.cell(0.0_f32);
+    /// let volume = audio.cell(0.5_f32);
+    /// let pressed = input.cell(false);
+    /// let target = ai.cell(None::<u32>);
+    ///
+    /// *physics.rw(&velocity) += 9.8;
+    /// *audio.rw(&volume) *= 2.0;
+    /// assert_eq!((physics.get(&velocity), audio.get(&volume)), (9.8, 1.0));
+    /// assert!(!input.get(&pressed) && ai.get(&target).is_none());
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The same contract applies as for [`QCellOwnerSeq::new`].
+    #[inline]
+    pub unsafe fn new_batch<const N: usize>() -> [Self; N] {
+        // Step by 2 per owner, as for `new`, so that all the IDs are
+        // odd
+        let mut num = take_seq_ids(&FAST_QCELLOWNER_ID, N);
+        [(); N].map(|()| {
+            let id = QCellOwnerID::new(num, OwnerNonce::next());
+            num = num.wrapping_add(2);
+            Self {
+                id,
+                #[cfg(feature = "diagnostics")]
+                diag: Registration::auto(OwnerKind::QCellOwnerSeq, None),
+            }
+        })
+    }
+
     /// Get the internal owner ID.  This may be used to create
     /// [`QCell`] instances without needing a borrow on this
     /// structure, which is useful if this structure is already
@@ -434,7 +1218,8 @@ impl QCellOwnerSeq {
     #[inline]
     pub fn ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
         owner_check!(self, qc);
-        unsafe { &*qc.value.get() }
+        // Safety: Owned (owner ID checked above), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&qc.value) }
     }
 
     /// Borrow contents of a [`QCell`] mutably (read-write).  Only one
@@ -445,7 +1230,8 @@ impl QCellOwnerSeq {
     #[inline]
     pub fn rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
         owner_check!(self, qc);
-        unsafe { &mut *qc.value.get() }
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&qc.value) }
     }
 
     /// Borrow contents of two [`QCell`] instances mutably.  Panics if
@@ -459,7 +1245,8 @@ impl QCellOwnerSeq {
     ) -> (&'a mut T, &'a mut U) {
         owner_check!(self, qc1, qc2);
         distinct_check!(qc1, qc2);
-        unsafe { (&mut *qc1.value.get(), &mut *qc2.value.get()) }
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&qc1.value, &qc2.value) }
     }
 
     /// Borrow contents of three [`QCell`] instances mutably.  Panics
@@ -475,38 +1262,335 @@ impl QCellOwnerSeq {
     ) -> (&'a mut T, &'a mut U, &'a mut V) {
         owner_check!(self, qc1, qc2, qc3);
         distinct_check!(qc1, qc2, qc3);
-        unsafe {
-            (
-                &mut *qc1.value.get(),
-                &mut *qc2.value.get(),
-                &mut *qc3.value.get(),
-            )
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&qc1.value, &qc2.value, &qc3.value) }
+    }
+
+    /// Borrow contents of one [`QCell`] mutably, and the contents of
+    /// a slice of [`QCell`] instances immutably.  The readers may
+    /// repeat, but none may be the same cell as the writer.  Panics
+    /// with the position of the first reader that is the same as the
+    /// writer, or if any [`QCell`] is not owned by this
+    /// [`QCellOwnerSeq`].  The contents of the readers are fetched lazily
+    /// through the returned [`RoSlice`].
+    ///
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        w: &'a QCell<T>,
+        readers: &'a [&'a QCell<U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        owner_check!(self, w);
+        for r in readers {
+            owner_check!(self, r);
         }
+        writer_check("QCell", w, readers);
+        // Safety: Owned (owner ID checked above), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
     }
-}
 
-/// Borrowing-owner of zero or more [`QCell`] instances, based on a
-/// pinned struct
-///
-/// This type uses its own memory address to provide a unique owner
-/// ID, which requires no allocations and only 2 bytes of storage.  So
-/// this is suitable for a `no_std` environment without an allocator.
-/// The owner can be created on the stack, or on the heap, as
-/// required.  To ensure its memory address cannot change while cells
-/// exist that are owned by it, it requires itself to be pinned before
-/// any operation interacting with the ID is attempted.
-///
-/// There are many ways to safely pin a value, such as
-/// [`Box::pin`](https://doc.rust-lang.org/std/boxed/struct.Box.html#method.pin),
-/// [`pin-utils::pin_mut!`](https://docs.rs/pin-utils/latest/pin_utils/macro.pin_mut.html),
-/// [`tokio::pin!`](https://docs.rs/tokio/latest/tokio/macro.pin.html),
-/// or the [`pin-project`](https://github.com/taiki-e/pin-project)
-/// crate.
-///
-/// The following example uses the `pin_mut!` macro from the
-/// `pin-utils` crate:
-///
-/// ```
+    /// Copy the contents of a [`QCell`] out, like `Cell::get`.  No
+    /// borrow is held after this returns.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn get<T: Copy>(&self, qc: &QCell<T>) -> T {
+        *self.ro(qc)
+    }
+
+    /// Clone the contents of a [`QCell`] out.  This is a separate
+    /// call from [`QCellOwnerSeq::get`] so that `get` stays a plain copy.
+    /// Panics if the [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn get_cloned<T: Clone>(&self, qc: &QCell<T>) -> T {
+        self.ro(qc).clone()
+    }
+
+    /// Replace the contents of a [`QCell`] with `value`, like
+    /// `Cell::set`, dropping the old value.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn set<T>(&mut self, qc: &QCell<T>, value: T) {
+        *self.rw(qc) = value;
+    }
+
+    /// Replace the contents of a [`QCell`] with `value`, returning
+    /// the old value, like `Cell::replace`.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn replace<T>(&mut self, qc: &QCell<T>, value: T) -> T {
+        mem::replace(self.rw(qc), value)
+    }
+
+    /// Replace the contents of a [`QCell`] with the result of
+    /// calling `f` on a copy of the old contents, and return the new
+    /// value.  Panics if the [`QCell`] is not owned by this
+    /// [`QCellOwnerSeq`].
+    #[inline]
+    pub fn update<T: Copy>(&mut self, qc: &QCell<T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(qc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Move the contents of `from` into `to`, leaving `T::default()`
+    /// in `from`, for reusing an expensive value such as a `Vec` with
+    /// its capacity in a fresh cell.  The value that was in `to` is
+    /// dropped.  No reallocation or copy of the value's heap data takes
+    /// place.  Panics if the two [`QCell`] instances are the same,
+    /// like `rw2`.  Panics if either [`QCell`] is not owned by this
+    /// [`QCellOwnerSeq`].
+    #[inline]
+    pub fn recycle_into<T: Default>(&mut self, from: &QCell<T>, to: &QCell<T>) {
+        let (from, to) = self.rw2(from, to);
+        *to = mem::take(from);
+    }
+
+    /// Copy the contents of many [`QCell`]s into `out` without
+    /// allocating, for example to take a snapshot of a set of
+    /// parameters.  Each cell's owner is checked as for
+    /// [`QCellOwnerSeq::get`], which is one compare per cell.  Panics if
+    /// any [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn read_into<T: Copy, const N: usize>(&self, cells: &[&QCell<T>; N], out: &mut [T; N]) {
+        self.read_into_slice(cells, out);
+    }
+
+    /// Copy the contents of many [`QCell`]s into `out`, like
+    /// [`QCellOwnerSeq::read_into`], but with the lengths checked at
+    /// runtime.  Panics if `cells` and `out` have different
+    /// lengths, or if any [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn read_into_slice<T: Copy>(&self, cells: &[&QCell<T>], out: &mut [T]) {
+        batch::length_check(cells.len(), out.len());
+        for (qc, value) in cells.iter().zip(out) {
+            *value = *self.ro(qc);
+        }
+    }
+
+    /// Copy the values in `src` into many [`QCell`]s without
+    /// allocating.  The cells are written in order, and no check
+    /// is made for repeated cells, so if the same [`QCell`] appears
+    /// more than once then the last write wins.  Panics if any
+    /// [`QCell`] is not owned by this [`QCellOwnerSeq`], in which case the cells
+    /// before it have already been written.
+    #[inline]
+    pub fn write_from<T: Copy, const N: usize>(&mut self, cells: &[&QCell<T>; N], src: &[T; N]) {
+        self.write_from_slice(cells, src);
+    }
+
+    /// Copy the values in `src` into many [`QCell`]s, like
+    /// [`QCellOwnerSeq::write_from`], but with the lengths checked at
+    /// runtime.  Panics before writing anything if `cells` and
+    /// `src` have different lengths.  Also panics if any [`QCell`] is
+    /// not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn write_from_slice<T: Copy>(&mut self, cells: &[&QCell<T>], src: &[T]) {
+        batch::length_check(cells.len(), src.len());
+        for (qc, value) in cells.iter().zip(src) {
+            *self.rw(qc) = *value;
+        }
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a slice, `Vec`, boxed
+    /// slice or array as a read-only [`SliceView`], which supports
+    /// indexing by position or range  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn view<'a, T, S: AsRef<[T]> + ?Sized>(&'a self, qc: &'a QCell<S>) -> SliceView<'a, T> {
+        SliceView::new(self.ro(qc).as_ref())
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a `Vec` as a
+    /// [`VecViewMut`], which supports indexing for reading and
+    /// writing.  This owner stays mutably borrowed while the view
+    /// exists, so no other cell can be borrowed through it  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[inline]
+    pub fn view_mut<'a, T>(&'a mut self, qc: &'a QCell<Vec<T>>) -> VecViewMut<'a, T> {
+        VecViewMut::new(self.rw(qc))
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a slice, `Vec`, boxed
+    /// slice or array as a [`SliceViewMut`], which supports indexing
+    /// for reading and writing.  This owner stays mutably borrowed
+    /// while the view exists.  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn slice_view_mut<'a, T, S: AsMut<[T]> + ?Sized>(
+        &'a mut self,
+        qc: &'a QCell<S>,
+    ) -> SliceViewMut<'a, T> {
+        SliceViewMut::new(self.rw(qc).as_mut())
+    }
+
+    /// Wrap a mutable borrow of this owner in a [`Capability`] which
+    /// can only read [`QCell`] instances if `READ` is `true`, and only
+    /// write them if `WRITE` is `true`, to hand to code which should
+    /// be restricted to one or the other.  See [`ReadOnly`] and
+    /// [`WriteOnly`].
+    ///
+    /// [`Capability`]: struct.Capability.html
+    /// [`ReadOnly`]: type.ReadOnly.html
+    /// [`WriteOnly`]: type.WriteOnly.html
+    #[inline]
+    pub fn capability<const READ: bool, const WRITE: bool>(
+        &mut self,
+    ) -> Capability<'_, QCellOwnerSeq, READ, WRITE> {
+        Capability::new(self)
+    }
+
+    /// Borrow the contents of a [`QCell`] mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  No runtime check is needed to keep the
+    /// two apart: the cell's contents are reached through the owner,
+    /// and `other` through a separate `&mut`, which safe code can't
+    /// point into the cell's contents.  Since the owner is borrowed
+    /// for the call, `f` can't use it.  Panics if the [`QCell`] is not owned by
+    /// this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        &'a mut self,
+        qc: &'a QCell<T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(qc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two [`QCell`] instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.  Also panics if either is not owned by
+    /// this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        &'a mut self,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(qc1, qc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of a [`QCell`] immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[inline]
+    pub fn inspect<T: ?Sized, D>(
+        &self,
+        qc: &QCell<T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(qc))
+    }
+
+    /// Same as `inspect`, but borrows two [`QCell`] instances, which
+    /// may be the same cell.  Panics if either is not owned by this
+    /// [`QCellOwnerSeq`].
+    #[inline]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        &self,
+        qc1: &QCell<T>,
+        qc2: &QCell<U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(qc1), self.ro(qc2))
+    }
+
+    /// Read the contents of a [`QCell`] with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.  Panics if the [`QCell`] is not owned by this
+    /// [`QCellOwnerSeq`].
+    #[inline]
+    pub fn modify_if<T: ?Sized, D>(
+        &mut self,
+        qc: &QCell<T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.ro(qc)) {
+            Some(decision) => {
+                apply(self.rw(qc), decision);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Borrow the contents of a slice of [`QCell`] instances mutably,
+    /// returning a `Vec` of references in the same order.  The slice
+    /// may hold the cells directly, or anything that borrows as a
+    /// [`QCell`], such as `Rc<QCell<T>>`, `Arc<QCell<T>>` or
+    /// `&QCell<T>`.  Panics if any two entries are the same cell,
+    /// which includes two `Rc` or `Arc` clones of the same cell, or if
+    /// any [`QCell`] is not owned by this [`QCellOwnerSeq`].
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_slice<'a, T: ?Sized, C: Borrow<QCell<T>>>(
+        &'a mut self,
+        cells: &'a [C],
+    ) -> Vec<&'a mut T> {
+        self.rw_from_iter(cells)
+    }
+
+    /// Same as [`QCellOwnerSeq::rw_from_slice`], but takes the cells from
+    /// an iterator of references.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_iter<'a, T: ?Sized + 'a, C: Borrow<QCell<T>> + 'a>(
+        &'a mut self,
+        cells: impl IntoIterator<Item = &'a C>,
+    ) -> Vec<&'a mut T> {
+        let cells: Vec<&'a QCell<T>> = cells.into_iter().map(Borrow::borrow).collect();
+        for qc in &cells {
+            owner_check!(self, qc);
+        }
+        distinct_check("QCell", &cells);
+        cells
+            .into_iter()
+            // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+            .map(|qc| unsafe { sound::deref_unique(&qc.value) })
+            .collect()
+    }
+}
+
+/// Borrowing-owner of zero or more [`QCell`] instances, based on a
+/// pinned struct
+///
+/// This type uses its own memory address to provide a unique owner
+/// ID, which requires no allocations and only 2 bytes of storage.  So
+/// this is suitable for a `no_std` environment without an allocator.
+/// The owner can be created on the stack, or on the heap, as
+/// required.  To ensure its memory address cannot change while cells
+/// exist that are owned by it, it requires itself to be pinned before
+/// any operation interacting with the ID is attempted.
+///
+/// There are many ways to safely pin a value, such as
+/// [`Box::pin`](https://doc.rust-lang.org/std/boxed/struct.Box.html#method.pin),
+/// [`pin-utils::pin_mut!`](https://docs.rs/pin-utils/latest/pin_utils/macro.pin_mut.html),
+/// [`tokio::pin!`](https://docs.rs/tokio/latest/tokio/macro.pin.html),
+/// or the [`pin-project`](https://github.com/taiki-e/pin-project)
+/// crate.  This crate's own [`pin_owner!`] macro creates an owner
+/// pinned on the stack without any extra dependency, including
+/// within `async` code, and [`PinnedOwnerFuture`] holds one within a
+/// hand-written future.
+///
+/// The following example uses the `pin_mut!` macro from the
+/// `pin-utils` crate:
+///
+/// ```
 /// use pin_utils::pin_mut;
 /// use qcell::{QCell, QCellOwnerPinned};
 ///# use std::rc::Rc;
@@ -522,6 +1606,32 @@ impl QCellOwnerSeq {
 /// }
 /// ```
 ///
+/// The methods take the `Pin` by value, so calling them more than
+/// once needs `as_mut()` or `as_ref()` each time.  Helper functions
+/// can instead take `&mut Pin<&mut QCellOwnerPinned>`, and use
+/// [`rw_via`], [`ro_via`] and the other `_via` functions, which do
+/// that for them.  The helpers can then be called repeatedly, and
+/// can pass the owner on to further helpers:
+///
+/// ```
+///# use pin_utils::pin_mut;
+///# use qcell::{QCell, QCellOwnerPinned};
+///# use std::pin::Pin;
+/// type Owner<'a> = Pin<&'a mut QCellOwnerPinned>;
+///
+/// fn push_twice(owner: &mut Owner<'_>, list: &QCell<Vec<u8>>, v: u8) {
+///     QCellOwnerPinned::rw_via(owner, list).push(v);
+///     QCellOwnerPinned::rw_via(owner, list).push(v);
+/// }
+///
+/// let owner = QCellOwnerPinned::new();
+/// pin_mut!(owner);
+/// let list = QCellOwnerPinned::cell_via(&owner, Vec::new());
+/// push_twice(&mut owner, &list, 1);
+/// push_twice(&mut owner, &list, 2);
+/// assert_eq!(QCellOwnerPinned::ro_via(&owner, &list), &[1, 1, 2, 2]);
+/// ```
+///
 /// This example incorporates the [`QCellOwnerPinned`] into a larger
 /// structure kept on the stack, and accesses it using the
 /// [`pin-project`](https://github.com/taiki-e/pin-project) crate:
@@ -575,7 +1685,10 @@ impl QCellOwnerSeq {
 /// [`QCellOwner`] (which is also based on the addresses of occupied
 /// memory, but always on the heap), or [`QCellOwnerSeq`] (which only
 /// allocates odd IDs, which cannot clash with addresses from this
-/// type which always have an alignment of 2).  So this should
+/// type which always have an alignment of 2).  The alignment is
+/// checked whenever the ID is derived, and [`id`] panics if the
+/// address is odd, which can only happen if the platform doesn't
+/// honour the alignment of a pinned value.  So this should
 /// successfully defend against all malicious and unsafe use.  If not,
 /// please raise an issue.
 ///
@@ -593,10 +1706,19 @@ impl QCellOwnerSeq {
 /// longer of any use without the owner ID.
 ///
 /// [`QCellOwner`]: struct.QCellOwner.html
+/// [`id`]: #method.id
+/// [`rw_via`]: #method.rw_via
+/// [`ro_via`]: #method.ro_via
+/// [`pin_owner!`]: macro.pin_owner.html
+/// [`PinnedOwnerFuture`]: struct.PinnedOwnerFuture.html
 pub struct QCellOwnerPinned {
     target: OwnerIDTarget,
+    // Allocated on first use, so that `new` can be `const`
+    nonce: LazyOwnerNonce,
     // ensure this type is !Unpin
     _marker: PhantomPinned,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) diag: Registration,
 }
 
 impl Default for QCellOwnerPinned {
@@ -607,12 +1729,30 @@ impl Default for QCellOwnerPinned {
 
 impl QCellOwnerPinned {
     /// Create an owner that can be used for creating many [`QCell`]
-    /// instances.
+    /// instances.  This is a `const fn`, so the owner may be
+    /// created in a `static`.  The nonce used to detect stale IDs
+    /// with **debug-owner-tracking** is allocated on first use
+    /// instead.
+    ///
+    /// ```
+    ///# use qcell::QCellOwnerPinned;
+    ///# use std::pin::Pin;
+    /// static OWNER: QCellOwnerPinned = QCellOwnerPinned::new();
+    ///
+    /// // Safety: A static never moves
+    /// let owner = unsafe { Pin::new_unchecked(&OWNER) };
+    /// let cell = owner.cell(100);
+    /// assert_eq!(*owner.ro(&cell), 100);
+    /// ```
     #[inline]
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             target: MAGIC_OWNER_ID_TARGET,
+            nonce: LazyOwnerNonce::new(),
             _marker: PhantomPinned,
+            // Can't register in a `const fn`
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::NONE,
         }
     }
 
@@ -624,9 +1764,11 @@ impl QCellOwnerPinned {
     /// Requires this owner to be pinned before use.
     pub fn id(self: Pin<&Self>) -> QCellOwnerID {
         // Pin guarantees that our address will not change until we
-        // are dropped, so we can use it as a unique ID.
+        // are dropped, so we can use it as a unique ID.  The address
+        // is checked each time, since there is no one place where
+        // the owner is known to have been pinned.
         let raw_ptr: *const OwnerIDTarget = &self.target;
-        QCellOwnerID(raw_ptr as usize)
+        QCellOwnerID::new(address_id(raw_ptr), self.nonce.get())
     }
 
     /// Create a new cell owned by this owner instance.
@@ -647,7 +1789,8 @@ impl QCellOwnerPinned {
     #[inline]
     pub fn ro<'a, T: ?Sized>(self: Pin<&'a Self>, qc: &'a QCell<T>) -> &'a T {
         owner_check!(self, qc);
-        unsafe { &*qc.value.get() }
+        // Safety: Owned (owner ID checked above), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&qc.value) }
     }
 
     /// Borrow contents of a [`QCell`] mutably (read-write).  Only one
@@ -661,7 +1804,8 @@ impl QCellOwnerPinned {
     #[allow(clippy::mut_from_ref)]
     pub fn rw<'a, T: ?Sized>(self: Pin<&'a mut Self>, qc: &'a QCell<T>) -> &'a mut T {
         owner_check!(self.as_ref(), qc);
-        unsafe { &mut *qc.value.get() }
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&qc.value) }
     }
 
     /// Borrow contents of two [`QCell`] instances mutably.  Panics if
@@ -677,7 +1821,8 @@ impl QCellOwnerPinned {
     ) -> (&'a mut T, &'a mut U) {
         owner_check!(self.as_ref(), qc1, qc2);
         distinct_check!(qc1, qc2);
-        unsafe { (&mut *qc1.value.get(), &mut *qc2.value.get()) }
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&qc1.value, &qc2.value) }
     }
 
     /// Borrow contents of three [`QCell`] instances mutably.  Panics
@@ -695,14 +1840,470 @@ impl QCellOwnerPinned {
     ) -> (&'a mut T, &'a mut U, &'a mut V) {
         owner_check!(self.as_ref(), qc1, qc2, qc3);
         distinct_check!(qc1, qc2, qc3);
-        unsafe {
-            (
-                &mut *qc1.value.get(),
-                &mut *qc2.value.get(),
-                &mut *qc3.value.get(),
-            )
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&qc1.value, &qc2.value, &qc3.value) }
+    }
+
+    /// Same as [`cell`], but taking the pinned owner by reference,
+    /// for use in helper functions which are passed `&Pin<&mut
+    /// QCellOwnerPinned>` or `&mut Pin<&mut QCellOwnerPinned>`.
+    ///
+    /// [`cell`]: #method.cell
+    #[inline]
+    pub fn cell_via<T>(owner: &Pin<&mut Self>, value: T) -> QCell<T> {
+        owner.as_ref().cell(value)
+    }
+
+    /// Same as [`ro`], but taking the pinned owner by reference, so
+    /// that it can be called repeatedly without `as_ref()`.
+    ///
+    /// [`ro`]: #method.ro
+    #[inline]
+    pub fn ro_via<'a, T: ?Sized>(owner: &'a Pin<&mut Self>, qc: &'a QCell<T>) -> &'a T {
+        owner.as_ref().ro(qc)
+    }
+
+    /// Same as [`rw`], but taking the pinned owner by mutable
+    /// reference, so that it can be called repeatedly without
+    /// `as_mut()`.
+    ///
+    /// [`rw`]: #method.rw
+    #[inline]
+    pub fn rw_via<'a, T: ?Sized>(owner: &'a mut Pin<&mut Self>, qc: &'a QCell<T>) -> &'a mut T {
+        owner.as_mut().rw(qc)
+    }
+
+    /// Same as [`rw2`], but taking the pinned owner by mutable
+    /// reference.
+    ///
+    /// [`rw2`]: #method.rw2
+    #[inline]
+    pub fn rw2_via<'a, T: ?Sized, U: ?Sized>(
+        owner: &'a mut Pin<&mut Self>,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+    ) -> (&'a mut T, &'a mut U) {
+        owner.as_mut().rw2(qc1, qc2)
+    }
+
+    /// Same as [`rw3`], but taking the pinned owner by mutable
+    /// reference.
+    ///
+    /// [`rw3`]: #method.rw3
+    #[inline]
+    pub fn rw3_via<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
+        owner: &'a mut Pin<&mut Self>,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+        qc3: &'a QCell<V>,
+    ) -> (&'a mut T, &'a mut U, &'a mut V) {
+        owner.as_mut().rw3(qc1, qc2, qc3)
+    }
+
+    /// Borrow contents of one [`QCell`] mutably, and the contents of
+    /// a slice of [`QCell`] instances immutably.  The readers may
+    /// repeat, but none may be the same cell as the writer.  Panics
+    /// with the position of the first reader that is the same as the
+    /// writer, or if any [`QCell`] is not owned by this
+    /// [`QCellOwnerPinned`].  The contents of the readers are fetched lazily
+    /// through the returned [`RoSlice`].
+    ///
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        self: Pin<&'a mut Self>,
+        w: &'a QCell<T>,
+        readers: &'a [&'a QCell<U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        owner_check!(self.as_ref(), w);
+        for r in readers {
+            owner_check!(self.as_ref(), r);
+        }
+        writer_check("QCell", w, readers);
+        // Safety: Owned (owner ID checked above), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
+    }
+
+    /// Copy the contents of a [`QCell`] out, like `Cell::get`.  No
+    /// borrow is held after this returns.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn get<T: Copy>(self: Pin<&Self>, qc: &QCell<T>) -> T {
+        *self.ro(qc)
+    }
+
+    /// Clone the contents of a [`QCell`] out.  This is a separate
+    /// call from [`QCellOwnerPinned::get`] so that `get` stays a plain copy.
+    /// Panics if the [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn get_cloned<T: Clone>(self: Pin<&Self>, qc: &QCell<T>) -> T {
+        self.ro(qc).clone()
+    }
+
+    /// Replace the contents of a [`QCell`] with `value`, like
+    /// `Cell::set`, dropping the old value.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn set<T>(self: Pin<&mut Self>, qc: &QCell<T>, value: T) {
+        *self.rw(qc) = value;
+    }
+
+    /// Replace the contents of a [`QCell`] with `value`, returning
+    /// the old value, like `Cell::replace`.  Panics if the [`QCell`]
+    /// is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn replace<T>(self: Pin<&mut Self>, qc: &QCell<T>, value: T) -> T {
+        mem::replace(self.rw(qc), value)
+    }
+
+    /// Replace the contents of a [`QCell`] with the result of
+    /// calling `f` on a copy of the old contents, and return the new
+    /// value.  Panics if the [`QCell`] is not owned by this
+    /// [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn update<T: Copy>(self: Pin<&mut Self>, qc: &QCell<T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(qc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Move the contents of `from` into `to`, leaving `T::default()`
+    /// in `from`, for reusing an expensive value such as a `Vec` with
+    /// its capacity in a fresh cell.  The value that was in `to` is
+    /// dropped.  No reallocation or copy of the value's heap data takes
+    /// place.  Panics if the two [`QCell`] instances are the same,
+    /// like `rw2`.  Panics if either [`QCell`] is not owned by this
+    /// [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn recycle_into<T: Default>(self: Pin<&mut Self>, from: &QCell<T>, to: &QCell<T>) {
+        let (from, to) = self.rw2(from, to);
+        *to = mem::take(from);
+    }
+
+    /// Copy the contents of many [`QCell`]s into `out` without
+    /// allocating, for example to take a snapshot of a set of
+    /// parameters.  Each cell's owner is checked as for
+    /// [`QCellOwnerPinned::get`], which is one compare per cell.  Panics if
+    /// any [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn read_into<T: Copy, const N: usize>(
+        self: Pin<&Self>,
+        cells: &[&QCell<T>; N],
+        out: &mut [T; N],
+    ) {
+        self.read_into_slice(cells, out);
+    }
+
+    /// Copy the contents of many [`QCell`]s into `out`, like
+    /// [`QCellOwnerPinned::read_into`], but with the lengths checked at
+    /// runtime.  Panics if `cells` and `out` have different
+    /// lengths, or if any [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn read_into_slice<T: Copy>(self: Pin<&Self>, cells: &[&QCell<T>], out: &mut [T]) {
+        batch::length_check(cells.len(), out.len());
+        for (qc, value) in cells.iter().zip(out) {
+            *value = *self.ro(qc);
+        }
+    }
+
+    /// Copy the values in `src` into many [`QCell`]s without
+    /// allocating.  The cells are written in order, and no check
+    /// is made for repeated cells, so if the same [`QCell`] appears
+    /// more than once then the last write wins.  Panics if any
+    /// [`QCell`] is not owned by this [`QCellOwnerPinned`], in which case the cells
+    /// before it have already been written.
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn write_from<T: Copy, const N: usize>(
+        self: Pin<&mut Self>,
+        cells: &[&QCell<T>; N],
+        src: &[T; N],
+    ) {
+        self.write_from_slice(cells, src);
+    }
+
+    /// Copy the values in `src` into many [`QCell`]s, like
+    /// [`QCellOwnerPinned::write_from`], but with the lengths checked at
+    /// runtime.  Panics before writing anything if `cells` and
+    /// `src` have different lengths.  Also panics if any [`QCell`] is
+    /// not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn write_from_slice<T: Copy>(mut self: Pin<&mut Self>, cells: &[&QCell<T>], src: &[T]) {
+        batch::length_check(cells.len(), src.len());
+        for (qc, value) in cells.iter().zip(src) {
+            *self.as_mut().rw(qc) = *value;
+        }
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a slice, `Vec`, boxed
+    /// slice or array as a read-only [`SliceView`], which supports
+    /// indexing by position or range  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn view<'a, T, S: AsRef<[T]> + ?Sized>(
+        self: Pin<&'a Self>,
+        qc: &'a QCell<S>,
+    ) -> SliceView<'a, T> {
+        SliceView::new(self.ro(qc).as_ref())
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a `Vec` as a
+    /// [`VecViewMut`], which supports indexing for reading and
+    /// writing.  This owner stays mutably borrowed while the view
+    /// exists, so no other cell can be borrowed through it  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[inline]
+    pub fn view_mut<'a, T>(self: Pin<&'a mut Self>, qc: &'a QCell<Vec<T>>) -> VecViewMut<'a, T> {
+        VecViewMut::new(self.rw(qc))
+    }
+
+    /// Borrow the contents of a [`QCell`] holding a slice, `Vec`, boxed
+    /// slice or array as a [`SliceViewMut`], which supports indexing
+    /// for reading and writing.  This owner stays mutably borrowed
+    /// while the view exists.  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn slice_view_mut<'a, T, S: AsMut<[T]> + ?Sized>(
+        self: Pin<&'a mut Self>,
+        qc: &'a QCell<S>,
+    ) -> SliceViewMut<'a, T> {
+        SliceViewMut::new(self.rw(qc).as_mut())
+    }
+
+    /// Borrow the contents of a [`QCell`] mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  No runtime check is needed to keep the
+    /// two apart: the cell's contents are reached through the owner,
+    /// and `other` through a separate `&mut`, which safe code can't
+    /// point into the cell's contents.  Since the owner is borrowed
+    /// for the call, `f` can't use it.  Panics if the [`QCell`] is not owned by
+    /// this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        self: Pin<&'a mut Self>,
+        qc: &'a QCell<T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(qc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two [`QCell`] instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.  Also panics if either is not owned by
+    /// this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        self: Pin<&'a mut Self>,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(qc1, qc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of a [`QCell`] immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.  Panics if the
+    /// [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn inspect<T: ?Sized, D>(
+        self: Pin<&Self>,
+        qc: &QCell<T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(qc))
+    }
+
+    /// Same as `inspect`, but borrows two [`QCell`] instances, which
+    /// may be the same cell.  Panics if either is not owned by this
+    /// [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        self: Pin<&Self>,
+        qc1: &QCell<T>,
+        qc2: &QCell<U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(qc1), self.ro(qc2))
+    }
+
+    /// Read the contents of a [`QCell`] with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.  Panics if the [`QCell`] is not owned by this
+    /// [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[inline]
+    pub fn modify_if<T: ?Sized, D>(
+        self: Pin<&mut Self>,
+        qc: &QCell<T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.as_ref().ro(qc)) {
+            Some(decision) => {
+                apply(self.rw(qc), decision);
+                true
+            }
+            None => false,
         }
     }
+
+    /// Borrow the contents of a slice of [`QCell`] instances mutably,
+    /// returning a `Vec` of references in the same order.  The slice
+    /// may hold the cells directly, or anything that borrows as a
+    /// [`QCell`], such as `Rc<QCell<T>>`, `Arc<QCell<T>>` or
+    /// `&QCell<T>`.  Panics if any two entries are the same cell,
+    /// which includes two `Rc` or `Arc` clones of the same cell, or if
+    /// any [`QCell`] is not owned by this [`QCellOwnerPinned`].
+    ///
+    /// Requires this owner to be pinned before use.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_slice<'a, T: ?Sized, C: Borrow<QCell<T>>>(
+        self: Pin<&'a mut Self>,
+        cells: &'a [C],
+    ) -> Vec<&'a mut T> {
+        self.rw_from_iter(cells)
+    }
+
+    /// Same as [`QCellOwnerPinned::rw_from_slice`], but takes the cells from
+    /// an iterator of references.
+    ///
+    /// Requires this owner to be pinned before use.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_iter<'a, T: ?Sized + 'a, C: Borrow<QCell<T>> + 'a>(
+        self: Pin<&'a mut Self>,
+        cells: impl IntoIterator<Item = &'a C>,
+    ) -> Vec<&'a mut T> {
+        let cells: Vec<&'a QCell<T>> = cells.into_iter().map(Borrow::borrow).collect();
+        for qc in &cells {
+            owner_check!(self.as_ref(), qc);
+        }
+        distinct_check("QCell", &cells);
+        cells
+            .into_iter()
+            // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+            .map(|qc| unsafe { sound::deref_unique(&qc.value) })
+            .collect()
+    }
+}
+
+pub(crate) mod sealed {
+    pub trait Sealed {}
+}
+
+/// Owners of [`QCell`] instances, to allow code to be generic over
+/// the owner type.
+///
+/// This is implemented for [`QCellOwner`], [`QCellOwnerSeq`],
+/// [`QCellOwnerGen`], [`QCellOwnerLocal`], [`OwnerMut`] and
+/// `Pin<&mut QCellOwnerPinned>`, and can't be implemented outside
+/// this crate.  The calls behave exactly like `ro` and `rw` on the
+/// owner, including the panic if the cell is not owned by it.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerGen`]: struct.QCellOwnerGen.html
+/// [`QCellOwnerLocal`]: struct.QCellOwnerLocal.html
+/// [`OwnerMut`]: enum.OwnerMut.html
+pub trait QCellBorrow: sealed::Sealed {
+    /// Borrow contents of a [`QCell`] immutably, like `ro`
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T;
+
+    /// Borrow contents of a [`QCell`] mutably, like `rw`
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T;
+}
+
+#[cfg(feature = "alloc")]
+impl sealed::Sealed for QCellOwner {}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+impl QCellBorrow for QCellOwner {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.ro(qc)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "record", track_caller)]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.rw(qc)
+    }
+}
+
+impl sealed::Sealed for QCellOwnerSeq {}
+
+impl QCellBorrow for QCellOwnerSeq {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.ro(qc)
+    }
+
+    #[inline]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.rw(qc)
+    }
+}
+
+impl sealed::Sealed for Pin<&mut QCellOwnerPinned> {}
+
+impl QCellBorrow for Pin<&mut QCellOwnerPinned> {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.as_ref().ro(qc)
+    }
+
+    #[inline]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.as_mut().rw(qc)
+    }
 }
 
 #[cfg(test)]
@@ -711,8 +2312,67 @@ mod tests {
 
     use pin_utils::pin_mut;
 
+    #[cfg(not(feature = "debug-owner-tracking"))]
+    use super::QCellOwnerID;
     use super::{QCell, QCellOwnerPinned, QCellOwnerSeq};
 
+    // The owner is passed down through three levels of helpers,
+    // each of which uses it more than once
+    #[test]
+    fn qcell_pinned_via_helpers() {
+        type Owner<'a> = Pin<&'a mut QCellOwnerPinned>;
+
+        fn level3(owner: &mut Owner<'_>, a: &QCell<u32>, b: &QCell<u32>, c: &QCell<u32>) {
+            let (a, b, c) = QCellOwnerPinned::rw3_via(owner, a, b, c);
+            *c = *a + *b;
+        }
+
+        fn level2(owner: &mut Owner<'_>, a: &QCell<u32>, b: &QCell<u32>, c: &QCell<u32>) {
+            let (x, y) = QCellOwnerPinned::rw2_via(owner, a, b);
+            core::mem::swap(x, y);
+            level3(owner, a, b, c);
+        }
+
+        fn level1(owner: &mut Owner<'_>, a: &QCell<u32>, b: &QCell<u32>, c: &QCell<u32>) -> u32 {
+            *QCellOwnerPinned::rw_via(owner, a) += 1;
+            level2(owner, a, b, c);
+            level2(owner, a, b, c);
+            *QCellOwnerPinned::ro_via(owner, c)
+        }
+
+        let owner = QCellOwnerPinned::new();
+        pin_mut!(owner);
+        let a = QCellOwnerPinned::cell_via(&owner, 1);
+        let b = QCellOwnerPinned::cell_via(&owner, 10);
+        let c = QCellOwnerPinned::cell_via(&owner, 0);
+        assert_eq!(level1(&mut owner, &a, &b, &c), 12);
+        assert_eq!(level1(&mut owner, &a, &b, &c), 13);
+        assert_eq!(*QCellOwnerPinned::ro_via(&owner, &a), 3);
+
+        // The ordinary methods still work on the same `Pin` afterwards
+        *owner.as_mut().rw(&b) = 0;
+        assert_eq!(*owner.as_ref().ro(&b), 0);
+    }
+
+    #[test]
+    fn qcell_inspect_seq_pinned() {
+        // Safety: No other owner is created with this ID
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let (a, b) = (owner.cell(1), owner.cell(2));
+        assert_eq!(owner.inspect(&a, |a| *a + 1), 2);
+        assert_eq!(owner.inspect2(&a, &b, |a, b| a + b), 3);
+        assert!(owner.modify_if(&b, |b| Some(*b), |b, v| *b += v));
+        assert_eq!(*owner.ro(&b), 4);
+
+        let owner = QCellOwnerPinned::new();
+        pin_mut!(owner);
+        let (a, b) = (owner.as_ref().cell(1), owner.as_ref().cell(2));
+        assert_eq!(owner.as_ref().inspect2(&a, &b, |a, b| a * b), 2);
+        assert!(!owner.as_mut().modify_if(&a, |_| None::<i32>, |a, v| *a = v));
+        assert!(owner.as_mut().modify_if(&a, |a| Some(*a + 10), |a, v| *a = v));
+        assert_eq!(owner.as_ref().inspect(&a, |a| *a), 11);
+    }
+
     #[test]
     fn qcell_pinned() {
         let owner = QCellOwnerPinned::new();
@@ -728,24 +2388,203 @@ mod tests {
     }
 
     #[test]
+    fn qcell_const_constructors() {
+        use super::QCellOwnerID;
+        static OWNER: QCellOwnerPinned = QCellOwnerPinned::new();
+        const fn cells(id: QCellOwnerID) -> (QCell<u32>, QCell<&'static str>) {
+            (QCell::new_with_id(id, 1), id.cell("a"))
+        }
+        // Safety: A static never moves
+        let owner = unsafe { Pin::new_unchecked(&OWNER) };
+        let (c1, c2) = cells(owner.id());
+        assert_eq!((*owner.ro(&c1), *owner.ro(&c2)), (1, "a"));
+        // The lazily-allocated nonce doesn't change once allocated
+        assert_eq!(owner.id(), owner.id());
+    }
+
+    #[cfg(feature = "debug-owner-tracking")]
+    #[test]
+    #[should_panic(expected = "stale QCellOwnerID used after original owner was dropped")]
+    fn qcell_pinned_stale_id() {
+        // `Pin::set` replaces the owner in place, so the new owner has
+        // the same address, but gets its own nonce on first use
+        let mut owner = Box::pin(QCellOwnerPinned::new());
+        let cell = owner.as_ref().cell(1);
+        owner.set(QCellOwnerPinned::new());
+        let _ = owner.as_ref().ro(&cell);
+    }
+
+    #[test]
+    fn qcell_pinned_get_set() {
+        let owner = QCellOwnerPinned::new();
+        pin_mut!(owner);
+        let count = owner.as_ref().cell(0u64);
+        for _ in 0..10 {
+            owner.as_mut().update(&count, |n| n + 3);
+        }
+        assert_eq!(owner.as_ref().get(&count), 30);
+        owner.as_mut().set(&count, 7);
+        assert_eq!(owner.as_ref().get(&count), 7);
+        let name = owner.as_ref().cell("abc");
+        assert_eq!(owner.as_ref().get_cloned(&name), "abc");
+    }
+
+    #[test]
+    fn qcell_seq_get_set() {
+        // Safety: Only one `QCellOwnerSeq` is used in this test
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let count = owner.cell(1u32);
+        assert_eq!(owner.update(&count, |n| n * 10), 10);
+        owner.set(&count, owner.get(&count) + 5);
+        assert_eq!(owner.get(&count), 15);
+        assert_eq!(owner.get_cloned(&count), 15);
+    }
+
+    #[test]
+    fn qcell_seq_new_batch() {
+        // Safety: No ID collisions are being exploited
+        let batch: [QCellOwnerSeq; 4] = unsafe { QCellOwnerSeq::new_batch() };
+        let first = batch[0].id.num;
+        for (i, owner) in batch.iter().enumerate() {
+            assert_eq!(owner.id.num, first.wrapping_add(2 * i));
+            assert_eq!(owner.id.num & 1, 1);
+        }
+        let cell = batch[1].cell(1);
+        assert_eq!(batch[1].get(&cell), 1);
+        let empty: [QCellOwnerSeq; 0] = unsafe { QCellOwnerSeq::new_batch() };
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn qcell_seq_new_batch_concurrent() {
+        const THREADS: usize = 4;
+        const ROUNDS: usize = 100;
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    let mut nums = Vec::new();
+                    for _ in 0..ROUNDS {
+                        // Safety: No ID collisions are being exploited
+                        let batch: [QCellOwnerSeq; 8] = unsafe { QCellOwnerSeq::new_batch() };
+                        let single = unsafe { QCellOwnerSeq::new() };
+                        nums.extend(batch.iter().map(|o| o.id.num));
+                        nums.push(single.id.num);
+                    }
+                    nums
+                })
+            })
+            .collect();
+        let mut nums: Vec<usize> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        assert!(nums.iter().all(|n| n & 1 == 1));
+        let count = nums.len();
+        nums.sort_unstable();
+        nums.dedup();
+        assert_eq!(nums.len(), count, "Duplicate owner IDs");
+    }
+
+    #[test]
+    #[allow(clippy::drop_non_drop)]
     fn qcell_fast_ids_pinned() {
         let owner1 = QCellOwnerPinned::new();
         pin_mut!(owner1);
         let id1 = owner1.as_ref().id();
         let owner2 = unsafe { QCellOwnerSeq::new() };
         let id2 = owner2.id;
-        assert_ne!(id1.0, id2.0, "Expected ID 1/2 to be different");
+        assert_ne!(id1.num, id2.num, "Expected ID 1/2 to be different");
         let owner3 = unsafe { QCellOwnerSeq::new() };
         let id3 = owner3.id;
-        assert_ne!(id2.0, id3.0, "Expected ID 2/3 to be different");
+        assert_ne!(id2.num, id3.num, "Expected ID 2/3 to be different");
         drop(owner2);
         drop(owner3);
         let owner4 = QCellOwnerPinned::new();
         pin_mut!(owner4);
         let id4 = owner4.as_ref().id();
-        assert_ne!(id1.0, id4.0, "Expected ID 1/4 to be different");
-        assert_ne!(id2.0, id4.0, "Expected ID 2/4 to be different");
-        assert_ne!(id3.0, id4.0, "Expected ID 3/4 to be different");
+        assert_ne!(id1.num, id4.num, "Expected ID 1/4 to be different");
+        assert_ne!(id2.num, id4.num, "Expected ID 2/4 to be different");
+        assert_ne!(id3.num, id4.num, "Expected ID 3/4 to be different");
+    }
+
+    #[cfg(not(feature = "debug-owner-tracking"))]
+    #[test]
+    fn qcell_id_size() {
+        use core::mem::size_of;
+        assert_eq!(size_of::<QCellOwnerID>(), size_of::<usize>());
+    }
+
+    #[test]
+    fn qcell_layout() {
+        use super::QCellOwnerID as ID;
+        use core::mem::{align_of, size_of};
+        use memoffset::offset_of;
+        fn round_up(offset: usize, align: usize) -> usize {
+            (offset + align - 1) / align * align
+        }
+        #[repr(align(32))]
+        struct Aligned32(#[allow(dead_code)] u8);
+
+        assert_eq!(offset_of!(ID, num), 0);
+        #[cfg(not(feature = "debug-owner-tracking"))]
+        assert_eq!(size_of::<ID>(), size_of::<usize>());
+        #[cfg(feature = "debug-owner-tracking")]
+        assert_eq!(
+            offset_of!(ID, nonce),
+            round_up(size_of::<usize>(), align_of::<u64>())
+        );
+
+        assert_eq!(offset_of!(QCell<u8>, owner), 0);
+        assert_eq!(offset_of!(QCell<u8>, value), size_of::<ID>());
+        assert_eq!(offset_of!(QCell<u16>, value), round_up(size_of::<ID>(), 2));
+        assert_eq!(offset_of!(QCell<u64>, value), round_up(size_of::<ID>(), align_of::<u64>()));
+        assert_eq!(offset_of!(QCell<Aligned32>, value), 32);
+        assert_eq!(offset_of!(QCell<[u8; 3]>, value), size_of::<ID>());
+
+        // The owner addresses used as IDs are still even
+        assert_eq!(align_of::<super::OwnerIDTarget>(), 2);
+        assert_eq!(align_of::<QCellOwnerPinned>() % 2, 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn qcell_seq_ids_wrap_odd() {
+        use super::take_seq_ids;
+        use core::sync::atomic::AtomicUsize;
+        // A counter seeded just below the top of the ID space wraps
+        // around to the bottom, and stays odd throughout
+        let counter = AtomicUsize::new(usize::MAX - 2);
+        assert_eq!(take_seq_ids(&counter, 1), usize::MAX - 2);
+        assert_eq!(take_seq_ids(&counter, 2), usize::MAX);
+        assert_eq!(take_seq_ids(&counter, 1), 3);
+        let counter = AtomicUsize::new(usize::MAX);
+        let first = take_seq_ids(&counter, 3);
+        let ids: Vec<usize> = (0..3).map(|i| first.wrapping_add(2 * i)).collect();
+        assert_eq!(ids, [usize::MAX, 1, 3]);
+        assert_eq!(counter.into_inner(), 5);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn qcell_address_ids() {
+        use super::{address_id, OwnerIDTarget, QCellOwner};
+        // Even addresses at the boundaries are accepted unchanged.
+        // The pointers are never dereferenced.
+        for num in [2, 0x8000, usize::MAX - 1] {
+            assert_eq!(address_id(num as *const OwnerIDTarget), num);
+        }
+        for num in [1, usize::MAX] {
+            let result = std::panic::catch_unwind(|| address_id(num as *const OwnerIDTarget));
+            assert!(result.is_err());
+        }
+        // Real owners have even IDs, and so can't clash with
+        // `QCellOwnerSeq`
+        let owner = QCellOwner::new();
+        assert_eq!(owner.id().num & 1, 0);
+        let pinned = Box::pin(QCellOwnerPinned::new());
+        assert_eq!(pinned.as_ref().id().num & 1, 0);
+        let seq = unsafe { QCellOwnerSeq::new() };
+        assert_eq!(seq.id().num & 1, 1);
     }
 
     #[test]
@@ -829,6 +2668,21 @@ mod tests {
         assert_eq!(owner.as_ref().ro(&cell1).value(), 7);
         assert_eq!(owner.as_ref().ro(&cell2).value(), 100);
     }
+
+    #[test]
+    fn qcell_recycle_into_seq_and_pinned() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let (from, to) = (owner.cell(Some(5)), owner.cell(Some(6)));
+        owner.recycle_into(&from, &to);
+        assert_eq!((owner.get(&from), owner.get(&to)), (None, Some(5)));
+
+        let owner = QCellOwnerPinned::new();
+        pin_mut!(owner);
+        let (from, to) = (owner.as_ref().cell(7), owner.as_ref().cell(8));
+        owner.as_mut().recycle_into(&from, &to);
+        assert_eq!((owner.as_ref().get(&from), owner.as_ref().get(&to)), (0, 7));
+    }
 }
 
 #[cfg(all(test, feature = "alloc"))]
@@ -854,37 +2708,83 @@ mod tests_with_alloc {
         let id1 = owner1.id();
         let owner2 = QCellOwner::new();
         let id2 = owner2.id();
-        assert_ne!(id1.0, id2.0, "Expected ID 1/2 to be different");
+        assert_ne!(id1.num, id2.num, "Expected ID 1/2 to be different");
         drop(owner2);
         let owner3 = QCellOwner::new();
         let id3 = owner3.id();
-        assert_ne!(id1.0, id3.0, "Expected ID 1/3 to be different");
+        assert_ne!(id1.num, id3.num, "Expected ID 1/3 to be different");
         drop(owner3);
         drop(owner1);
         let owner4 = QCellOwner::new();
         let id4 = owner4.id();
         let owner5 = QCellOwner::new();
         let id5 = owner5.id();
-        assert_ne!(id4.0, id5.0, "Expected ID 4/5 to be different");
+        assert_ne!(id4.num, id5.num, "Expected ID 4/5 to be different");
     }
 
     #[test]
+    #[allow(clippy::drop_non_drop)]
     fn qcell_fast_ids() {
         let owner1 = QCellOwner::new();
         let id1 = owner1.id();
         let owner2 = unsafe { QCellOwnerSeq::new() };
         let id2 = owner2.id();
-        assert_ne!(id1.0, id2.0, "Expected ID 1/2 to be different");
+        assert_ne!(id1.num, id2.num, "Expected ID 1/2 to be different");
         let owner3 = unsafe { QCellOwnerSeq::new() };
         let id3 = owner3.id();
-        assert_ne!(id2.0, id3.0, "Expected ID 2/3 to be different");
+        assert_ne!(id2.num, id3.num, "Expected ID 2/3 to be different");
         drop(owner2);
         drop(owner3);
         let owner4 = QCellOwner::new();
         let id4 = owner4.id();
-        assert_ne!(id1.0, id4.0, "Expected ID 1/4 to be different");
-        assert_ne!(id2.0, id4.0, "Expected ID 2/4 to be different");
-        assert_ne!(id3.0, id4.0, "Expected ID 3/4 to be different");
+        assert_ne!(id1.num, id4.num, "Expected ID 1/4 to be different");
+        assert_ne!(id2.num, id4.num, "Expected ID 2/4 to be different");
+        assert_ne!(id3.num, id4.num, "Expected ID 3/4 to be different");
+    }
+
+    #[cfg(feature = "debug-owner-tracking")]
+    #[test]
+    fn qcell_stale_id() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        // Create owners until one reuses the address of an owner
+        // that has just been dropped, which the allocator will
+        // normally do straight away
+        let mut reused = None;
+        for _ in 0..1000 {
+            let stale_id = QCellOwner::new().id();
+            let owner = QCellOwner::new();
+            if owner.id().num == stale_id.num {
+                reused = Some((stale_id, owner));
+                break;
+            }
+        }
+        // Fail rather than pass without checking anything, so that an
+        // allocator which doesn't reuse addresses can't hide a gap in
+        // coverage
+        let (stale_id, owner) = reused.expect(
+            "allocator never reused an owner's address, so stale ID detection wasn't tested",
+        );
+        assert!(stale_id != owner.id());
+        let cell = stale_id.cell(100u32);
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| *owner.ro(&cell))).unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().map(String::as_str),
+            Some(crate::panic_messages::QCELL_STALE_OWNER_ID)
+        );
+
+        // A cell from the current owner is still fine
+        let cell = owner.cell(200u32);
+        assert_eq!(*owner.ro(&cell), 200);
+    }
+
+    #[test]
+    fn qcell_id_debug() {
+        let owner = QCellOwner::new();
+        let id = owner.id();
+        let text = format!("{:?}", id);
+        assert!(text.starts_with(&format!("QCellOwnerID({:#x}", id.num)));
+        assert_eq!(text, format!("{:?}", owner.id()));
     }
 
     #[test]
@@ -903,6 +2803,60 @@ mod tests_with_alloc {
         );
     }
 
+    #[test]
+    fn qcell_get_set() {
+        let mut owner = QCellOwner::new();
+        let hits = owner.cell(0u64);
+        let name = owner.cell(String::from("x"));
+        fn record(owner: &mut QCellOwner, hits: &QCell<u64>) {
+            owner.update(hits, |n| n + 1);
+        }
+        for _ in 0..5 {
+            let before = owner.get(&hits);
+            record(&mut owner, &hits);
+            assert_eq!(owner.get(&hits), before + 1);
+        }
+        owner.set(&name, owner.get_cloned(&name) + "yz");
+        assert_eq!(owner.get_cloned(&name), "xyz");
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn qcell_get_bad_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let cell = owner1.cell(0u32);
+        owner2.set(&cell, 1);
+    }
+
+    #[test]
+    fn qcell_recycle_into() {
+        use std::rc::Rc;
+        let mut owner = QCellOwner::new();
+        let marker = Rc::new(());
+        let old = owner.cell(Vec::with_capacity(100));
+        let new = owner.cell(vec![marker.clone()]);
+        owner.rw(&old).push(marker.clone());
+        let ptr = owner.ro(&old).as_ptr();
+        assert_eq!(Rc::strong_count(&marker), 3);
+
+        // The value previously in `to` is dropped, and the buffer is
+        // moved across without reallocating
+        owner.recycle_into(&old, &new);
+        assert_eq!(Rc::strong_count(&marker), 2);
+        assert!(owner.ro(&old).is_empty() && owner.ro(&old).capacity() == 0);
+        assert_eq!(owner.ro(&new).as_ptr(), ptr);
+        assert_eq!(owner.ro(&new).capacity(), 100);
+    }
+
+    #[test]
+    #[should_panic]
+    fn qcell_recycle_into_same_cell() {
+        let mut owner = QCellOwner::new();
+        let cell = owner.cell(vec![1]);
+        owner.recycle_into(&cell, &cell);
+    }
+
     #[test]
     fn qcell_get_mut() {
         let owner = QCellOwner::new();
@@ -976,4 +2930,147 @@ mod tests_with_alloc {
         assert_eq!(cell1.ro(own).value(), 7);
         assert_eq!(cell2.ro(own).value(), 100);
     }
+
+    #[test]
+    fn qcell_leak_static_graph() {
+        use once_cell::sync::OnceCell;
+
+        struct Node {
+            value: u32,
+            next: Option<&'static QCell<Node>>,
+        }
+
+        struct Graph {
+            owner: &'static QCellOwner,
+            head: &'static QCell<Node>,
+        }
+
+        static GRAPH: OnceCell<Graph> = OnceCell::new();
+
+        fn sum(graph: &Graph) -> u32 {
+            let mut total = 0;
+            let mut node = Some(graph.head);
+            while let Some(n) = node {
+                let n = graph.owner.ro(n);
+                total += n.value;
+                node = n.next;
+            }
+            total
+        }
+
+        GRAPH.get_or_init(|| {
+            let owner = QCellOwner::new().leak();
+            let id = owner.id();
+            let mut head = None;
+            for value in (1..=4).rev() {
+                head = Some(id.leak_cell(Node { value, next: head }));
+            }
+            let head = head.unwrap();
+
+            // Finish building the graph using the owner mutably, then
+            // share it
+            let mut node = Some(head);
+            while let Some(n) = node {
+                owner.rw(n).value *= 10;
+                node = owner.ro(n).next;
+            }
+            Graph { owner, head }
+        });
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| sum(GRAPH.get().unwrap())))
+            .collect();
+        for t in threads {
+            assert_eq!(t.join().unwrap(), 100);
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Door {
+        Closed { knocks: u32 },
+        Open,
+        Locked,
+    }
+
+    enum Step {
+        Knock,
+        Open(u32),
+        Lock,
+        Stay,
+    }
+
+    fn decide(door: &Door) -> Step {
+        match door {
+            Door::Closed { knocks } if *knocks >= 2 => Step::Open(*knocks),
+            Door::Closed { .. } => Step::Knock,
+            Door::Open => Step::Lock,
+            Door::Locked => Step::Stay,
+        }
+    }
+
+    // The transition written by binding what is needed out of the
+    // borrow by hand first, and with `inspect`, give the same states
+    #[test]
+    fn qcell_inspect_state_machine() {
+        fn by_hand(owner: &mut QCellOwner, door: &QCell<Door>, log: &QCell<Vec<u32>>) {
+            let knocks = match owner.ro(door) {
+                Door::Closed { knocks } => Some(*knocks),
+                _ => None,
+            };
+            let locked = *owner.ro(door) == Door::Locked;
+            match knocks {
+                Some(n) if n >= 2 => {
+                    owner.rw(log).push(n);
+                    *owner.rw(door) = Door::Open;
+                }
+                Some(n) => *owner.rw(door) = Door::Closed { knocks: n + 1 },
+                None if !locked => *owner.rw(door) = Door::Locked,
+                None => (),
+            }
+        }
+
+        fn with_inspect(owner: &mut QCellOwner, door: &QCell<Door>, log: &QCell<Vec<u32>>) {
+            match owner.inspect(door, decide) {
+                Step::Open(n) => {
+                    owner.rw(log).push(n);
+                    *owner.rw(door) = Door::Open;
+                }
+                Step::Knock => {
+                    owner.modify_if(
+                        door,
+                        |d| match d {
+                            Door::Closed { knocks } => Some(knocks + 1),
+                            _ => None,
+                        },
+                        |d, knocks| *d = Door::Closed { knocks },
+                    );
+                }
+                Step::Lock => *owner.rw(door) = Door::Locked,
+                Step::Stay => (),
+            }
+        }
+
+        let mut owner = QCellOwner::new();
+        let (door1, log1) = (owner.cell(Door::Closed { knocks: 0 }), owner.cell(Vec::new()));
+        let (door2, log2) = (owner.cell(Door::Closed { knocks: 0 }), owner.cell(Vec::new()));
+        for _ in 0..6 {
+            by_hand(&mut owner, &door1, &log1);
+            with_inspect(&mut owner, &door2, &log2);
+            assert_eq!(owner.ro(&door1), owner.ro(&door2));
+        }
+        assert_eq!(*owner.ro(&door2), Door::Locked);
+        assert_eq!(*owner.ro(&log2), [2]);
+        assert_eq!(owner.ro(&log1), owner.ro(&log2));
+    }
+
+    #[test]
+    fn qcell_inspect2_modify_if() {
+        let mut owner = QCellOwner::new();
+        let (a, b) = (owner.cell(3), owner.cell(String::from("x")));
+        assert_eq!(owner.inspect2(&a, &b, |a, b| b.repeat(*a)), "xxx");
+        assert_eq!(owner.inspect2(&a, &a, |a1, a2| a1 + a2), 6);
+        assert!(!owner.modify_if(&a, |a| (*a > 5).then(|| 0), |a, v| *a = v));
+        assert!(owner.modify_if(&a, |a| Some(*a * 2), |a, v| *a += v));
+        assert_eq!(*owner.ro(&a), 9);
+    }
 }
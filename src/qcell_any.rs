@@ -0,0 +1,385 @@
+use core::pin::Pin;
+
+use crate::qcell::sealed;
+#[cfg(feature = "alloc")]
+use crate::QCellOwner;
+use crate::{QCell, QCellBorrow, QCellOwnerID, QCellOwnerPinned, QCellOwnerSeq};
+
+/// Mutable borrow of a [`QCellOwner`], [`QCellOwnerSeq`] or
+/// [`QCellOwnerPinned`], chosen at runtime.
+///
+/// This allows library code to accept whichever owner the caller
+/// has, without making its signatures generic over [`QCellBorrow`].
+/// Each call is one branch on the variant, then a static call to the
+/// owner's own method, so the cost is small.  Callers convert with
+/// `.into()`:
+///
+/// ```
+///# use qcell::{OwnerMut, QCell, QCellOwnerPinned, QCellOwnerSeq};
+///# use std::sync::Arc;
+/// fn process(mut owner: OwnerMut<'_>, cells: &[Arc<QCell<u32>>]) {
+///     for cell in cells {
+///         *owner.rw(cell) += 1;
+///     }
+/// }
+///
+/// // Safety: No other owner is created with this ID
+/// let mut owner = unsafe { QCellOwnerSeq::new() };
+/// let cells = vec![Arc::new(owner.cell(1)), Arc::new(owner.cell(2))];
+/// process((&mut owner).into(), &cells);
+/// assert_eq!(*owner.ro(&cells[1]), 3);
+///
+/// let mut pinned = Box::pin(QCellOwnerPinned::new());
+/// let cells = vec![Arc::new(pinned.as_ref().cell(10))];
+/// process(pinned.as_mut().into(), &cells);
+/// assert_eq!(*pinned.as_ref().ro(&cells[0]), 11);
+/// ```
+///
+/// To pass an `OwnerMut` on to another call and keep using it
+/// afterwards, use [`OwnerMut::reborrow`].
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+/// [`QCellBorrow`]: trait.QCellBorrow.html
+/// [`OwnerMut::reborrow`]: enum.OwnerMut.html#method.reborrow
+pub enum OwnerMut<'a> {
+    /// A [`QCellOwner`]
+    ///
+    /// [`QCellOwner`]: struct.QCellOwner.html
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    Alloc(&'a mut QCellOwner),
+    /// A [`QCellOwnerSeq`]
+    ///
+    /// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+    Seq(&'a mut QCellOwnerSeq),
+    /// A pinned [`QCellOwnerPinned`]
+    ///
+    /// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+    Pinned(Pin<&'a mut QCellOwnerPinned>),
+}
+
+impl<'a> OwnerMut<'a> {
+    /// Reborrow this owner for a shorter lifetime, to pass it to a
+    /// call that takes an `OwnerMut` by value and still be able to
+    /// use it afterwards
+    #[inline]
+    pub fn reborrow(&mut self) -> OwnerMut<'_> {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerMut::Alloc(owner) => OwnerMut::Alloc(owner),
+            OwnerMut::Seq(owner) => OwnerMut::Seq(owner),
+            OwnerMut::Pinned(owner) => OwnerMut::Pinned(owner.as_mut()),
+        }
+    }
+
+    /// Get a shared borrow of this owner, as an [`OwnerRef`]
+    ///
+    /// [`OwnerRef`]: enum.OwnerRef.html
+    #[inline]
+    pub fn shared(&self) -> OwnerRef<'_> {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerMut::Alloc(owner) => OwnerRef::Alloc(owner),
+            OwnerMut::Seq(owner) => OwnerRef::Seq(owner),
+            OwnerMut::Pinned(owner) => OwnerRef::Pinned(owner.as_ref()),
+        }
+    }
+
+    /// Get the internal owner ID
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        self.shared().id()
+    }
+
+    /// Create a new cell owned by this owner instance
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> QCell<T> {
+        self.shared().cell(value)
+    }
+
+    /// Borrow contents of a [`QCell`] immutably (read-only).  Many
+    /// [`QCell`] instances can be borrowed immutably at the same
+    /// time from the same owner.  Panics if the [`QCell`] is not
+    /// owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn ro<'b, T: ?Sized>(&'b self, qc: &'b QCell<T>) -> &'b T {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerMut::Alloc(owner) => owner.ro(qc),
+            OwnerMut::Seq(owner) => owner.ro(qc),
+            OwnerMut::Pinned(owner) => owner.as_ref().ro(qc),
+        }
+    }
+
+    /// Borrow contents of a [`QCell`] mutably (read-write).  Only
+    /// one [`QCell`] at a time can be borrowed from the owner using
+    /// this call.  The returned reference must go out of scope before
+    /// another can be borrowed.  Panics if the [`QCell`] is not
+    /// owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw<'b, T: ?Sized>(&'b mut self, qc: &'b QCell<T>) -> &'b mut T {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerMut::Alloc(owner) => owner.rw(qc),
+            OwnerMut::Seq(owner) => owner.rw(qc),
+            OwnerMut::Pinned(owner) => owner.as_mut().rw(qc),
+        }
+    }
+
+    /// Borrow contents of two [`QCell`] instances mutably.  Panics if
+    /// the two [`QCell`] instances point to the same memory, or if
+    /// either is not owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw2<'b, T: ?Sized, U: ?Sized>(
+        &'b mut self,
+        qc1: &'b QCell<T>,
+        qc2: &'b QCell<U>,
+    ) -> (&'b mut T, &'b mut U) {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerMut::Alloc(owner) => owner.rw2(qc1, qc2),
+            OwnerMut::Seq(owner) => owner.rw2(qc1, qc2),
+            OwnerMut::Pinned(owner) => owner.as_mut().rw2(qc1, qc2),
+        }
+    }
+
+    /// Borrow contents of three [`QCell`] instances mutably.  Panics
+    /// if any pair of [`QCell`] instances point to the same memory,
+    /// or if any is not owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw3<'b, T: ?Sized, U: ?Sized, V: ?Sized>(
+        &'b mut self,
+        qc1: &'b QCell<T>,
+        qc2: &'b QCell<U>,
+        qc3: &'b QCell<V>,
+    ) -> (&'b mut T, &'b mut U, &'b mut V) {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerMut::Alloc(owner) => owner.rw3(qc1, qc2, qc3),
+            OwnerMut::Seq(owner) => owner.rw3(qc1, qc2, qc3),
+            OwnerMut::Pinned(owner) => owner.as_mut().rw3(qc1, qc2, qc3),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<&'a mut QCellOwner> for OwnerMut<'a> {
+    fn from(owner: &'a mut QCellOwner) -> Self {
+        OwnerMut::Alloc(owner)
+    }
+}
+
+impl<'a> From<&'a mut QCellOwnerSeq> for OwnerMut<'a> {
+    fn from(owner: &'a mut QCellOwnerSeq) -> Self {
+        OwnerMut::Seq(owner)
+    }
+}
+
+impl<'a> From<Pin<&'a mut QCellOwnerPinned>> for OwnerMut<'a> {
+    fn from(owner: Pin<&'a mut QCellOwnerPinned>) -> Self {
+        OwnerMut::Pinned(owner)
+    }
+}
+
+impl sealed::Sealed for OwnerMut<'_> {}
+
+impl QCellBorrow for OwnerMut<'_> {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.ro(qc)
+    }
+
+    #[inline]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.rw(qc)
+    }
+}
+
+/// Shared borrow of a [`QCellOwner`], [`QCellOwnerSeq`] or
+/// [`QCellOwnerPinned`], chosen at runtime.
+///
+/// This is the read-only counterpart of [`OwnerMut`].  It is `Copy`,
+/// so it can be passed around freely.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+/// [`OwnerMut`]: enum.OwnerMut.html
+#[derive(Clone, Copy)]
+pub enum OwnerRef<'a> {
+    /// A [`QCellOwner`]
+    ///
+    /// [`QCellOwner`]: struct.QCellOwner.html
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    Alloc(&'a QCellOwner),
+    /// A [`QCellOwnerSeq`]
+    ///
+    /// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+    Seq(&'a QCellOwnerSeq),
+    /// A pinned [`QCellOwnerPinned`]
+    ///
+    /// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+    Pinned(Pin<&'a QCellOwnerPinned>),
+}
+
+impl<'a> OwnerRef<'a> {
+    /// Get the internal owner ID
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerRef::Alloc(owner) => owner.id(),
+            OwnerRef::Seq(owner) => owner.id(),
+            OwnerRef::Pinned(owner) => owner.id(),
+        }
+    }
+
+    /// Create a new cell owned by this owner instance
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> QCell<T> {
+        self.id().cell(value)
+    }
+
+    /// Borrow contents of a [`QCell`] immutably (read-only).  Many
+    /// [`QCell`] instances can be borrowed immutably at the same
+    /// time from the same owner.  Panics if the [`QCell`] is not
+    /// owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn ro<'b, T: ?Sized>(&'b self, qc: &'b QCell<T>) -> &'b T {
+        match self {
+            #[cfg(feature = "alloc")]
+            OwnerRef::Alloc(owner) => owner.ro(qc),
+            OwnerRef::Seq(owner) => owner.ro(qc),
+            OwnerRef::Pinned(owner) => owner.ro(qc),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<&'a QCellOwner> for OwnerRef<'a> {
+    fn from(owner: &'a QCellOwner) -> Self {
+        OwnerRef::Alloc(owner)
+    }
+}
+
+impl<'a> From<&'a QCellOwnerSeq> for OwnerRef<'a> {
+    fn from(owner: &'a QCellOwnerSeq) -> Self {
+        OwnerRef::Seq(owner)
+    }
+}
+
+impl<'a> From<Pin<&'a QCellOwnerPinned>> for OwnerRef<'a> {
+    fn from(owner: Pin<&'a QCellOwnerPinned>) -> Self {
+        OwnerRef::Pinned(owner)
+    }
+}
+
+impl From<OwnerRef<'_>> for QCellOwnerID {
+    fn from(owner: OwnerRef<'_>) -> Self {
+        owner.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnerMut, OwnerRef};
+    use crate::{QCell, QCellBorrow, QCellOwnerID, QCellOwnerPinned, QCellOwnerSeq};
+    use core::pin::Pin;
+
+    // Exercise every method through the enum
+    fn exercise(mut owner: OwnerMut<'_>) {
+        let id = owner.id();
+        let c1 = owner.cell(1);
+        let c2 = id.cell(2);
+        let c3 = owner.shared().cell(3);
+        assert_eq!(*owner.ro(&c1), 1);
+        *owner.rw(&c1) += 10;
+        let (a, b) = owner.rw2(&c1, &c2);
+        std::mem::swap(a, b);
+        let (a, b, c) = owner.rw3(&c1, &c2, &c3);
+        *a += *b + *c;
+        assert_eq!(*owner.qcell_ro(&c1), 16);
+        *owner.qcell_rw(&c3) = 0;
+
+        // Reborrowing leaves the owner usable afterwards
+        take(owner.reborrow(), &c2);
+        take(owner.reborrow(), &c2);
+        let shared = owner.shared();
+        assert_eq!(
+            (*shared.ro(&c1), *shared.ro(&c2), *shared.ro(&c3)),
+            (16, 13, 0)
+        );
+        assert_eq!(QCellOwnerID::from(shared), id);
+    }
+
+    fn take(mut owner: OwnerMut<'_>, cell: &QCell<i32>) {
+        *owner.rw(cell) += 1;
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn owner_mut_alloc() {
+        let mut owner = crate::QCellOwner::new();
+        exercise((&mut owner).into());
+        let cell = owner.cell(5);
+        let shared: OwnerRef<'_> = (&owner).into();
+        assert_eq!(shared.id(), owner.id());
+        assert_eq!(*shared.ro(&cell), 5);
+    }
+
+    #[test]
+    fn owner_mut_seq() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        exercise((&mut owner).into());
+        let cell = owner.cell(5);
+        let shared = OwnerRef::from(&owner);
+        assert_eq!(*shared.ro(&cell), 5);
+    }
+
+    #[test]
+    fn owner_mut_pinned() {
+        let mut owner = Box::pin(QCellOwnerPinned::new());
+        exercise(owner.as_mut().into());
+
+        // A `Pin<&mut>` held in the enum is reborrowed internally, so
+        // can be used for several calls in a row
+        let cell = owner.as_ref().cell(0);
+        let other = owner.as_ref().cell(0);
+        let mut any = OwnerMut::from(owner.as_mut());
+        for _ in 0..3 {
+            *any.rw(&cell) += 1;
+            let (a, b) = any.rw2(&cell, &other);
+            *a += 1;
+            *b += 1;
+        }
+        let shared = OwnerRef::from(Pin::as_ref(&owner));
+        assert_eq!((*shared.ro(&cell), *shared.ro(&other)), (6, 3));
+        assert_eq!(shared.id(), owner.as_ref().id());
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn owner_mut_wrong_owner() {
+        // Safety: No ID collisions are being exploited
+        let mut owner1 = unsafe { QCellOwnerSeq::new() };
+        let owner2 = unsafe { QCellOwnerSeq::new() };
+        let cell = owner2.cell(1);
+        let any = OwnerMut::from(&mut owner1);
+        any.ro(&cell);
+    }
+}
@@ -0,0 +1,174 @@
+use alloc::sync::Arc;
+use core::any::Any;
+
+use crate::sound;
+use crate::{OwnerMut, QCell, QCellBorrow, QCellOwner, QCellOwnerSeq};
+
+impl<T: Any + Send + Sync> QCell<T> {
+    /// Erase the type of a shared cell, so that cells of different
+    /// types can be kept together, for example in a plugin registry.
+    /// This is the same as the unsized coercion, but saves spelling
+    /// out the target type.  Use the owner's `ro_downcast` and
+    /// `rw_downcast` to access the contents, or
+    /// [`QCell::try_typed_arc`] to get the typed cell back.
+    ///
+    /// ```
+    ///# use qcell::{QCell, QCellOwner};
+    ///# use std::sync::Arc;
+    /// let mut owner = QCellOwner::new();
+    /// let plugins = vec![
+    ///     QCell::erase_arc(Arc::new(owner.cell(10_u32))),
+    ///     QCell::erase_arc(Arc::new(owner.cell(String::from("log")))),
+    /// ];
+    /// *owner.rw_downcast::<u32>(&plugins[0]).unwrap() += 1;
+    /// assert_eq!(owner.ro_downcast::<u32>(&plugins[0]), Some(&11));
+    /// assert_eq!(owner.ro_downcast::<u32>(&plugins[1]), None);
+    ///
+    /// let name = QCell::try_typed_arc::<String, _>(plugins[1].clone(), &owner).ok().unwrap();
+    /// assert_eq!(owner.ro(&name), "log");
+    /// ```
+    ///
+    /// [`QCell::try_typed_arc`]: struct.QCell.html#method.try_typed_arc
+    #[inline]
+    pub fn erase_arc(this: Arc<Self>) -> Arc<QCell<dyn Any + Send + Sync>> {
+        this
+    }
+}
+
+impl QCell<dyn Any + Send + Sync> {
+    /// Get back the typed cell from a cell erased with
+    /// [`QCell::erase_arc`], if its contents are a `T`, or else return
+    /// the erased cell unchanged.  The result shares the same
+    /// allocation, so other handles to the cell see the same value.
+    ///
+    /// The owner is needed to check the type of the contents, since
+    /// that means borrowing them.  Panics if the cell is not owned by
+    /// this owner.
+    ///
+    /// [`QCell::erase_arc`]: struct.QCell.html#method.erase_arc
+    pub fn try_typed_arc<T: Any + Send + Sync, O: QCellBorrow>(
+        this: Arc<Self>,
+        owner: &O,
+    ) -> Result<Arc<QCell<T>>, Arc<Self>> {
+        if owner.qcell_ro(&this).is::<T>() {
+            // Safety: The contents are a `T`, so the cell was coerced
+            // from a `QCell<T>`
+            Ok(unsafe { sound::arc_downcast(this) })
+        } else {
+            Err(this)
+        }
+    }
+}
+
+macro_rules! downcast_methods {
+    ($owner:ty) => {
+        impl $owner {
+            /// Borrow the contents of a type-erased [`QCell`]
+            /// immutably, if they are a `T`.  Panics if the cell is
+            /// not owned by this owner.
+            ///
+            /// [`QCell`]: struct.QCell.html
+            #[inline]
+            pub fn ro_downcast<'a, T: Any>(
+                &'a self,
+                qc: &'a QCell<dyn Any + Send + Sync>,
+            ) -> Option<&'a T> {
+                self.ro(qc).downcast_ref()
+            }
+
+            /// Borrow the contents of a type-erased [`QCell`] mutably,
+            /// if they are a `T`.  Panics if the cell is not owned by
+            /// this owner.
+            ///
+            /// [`QCell`]: struct.QCell.html
+            #[inline]
+            pub fn rw_downcast<'a, T: Any>(
+                &'a mut self,
+                qc: &'a QCell<dyn Any + Send + Sync>,
+            ) -> Option<&'a mut T> {
+                self.rw(qc).downcast_mut()
+            }
+        }
+    };
+}
+
+downcast_methods!(QCellOwner);
+downcast_methods!(QCellOwnerSeq);
+downcast_methods!(OwnerMut<'_>);
+
+#[cfg(test)]
+mod tests {
+    use crate::{OwnerMut, QCell, QCellOwner, QCellOwnerSeq};
+    use std::any::Any;
+    use std::sync::Arc;
+
+    type Erased = Arc<QCell<dyn Any + Send + Sync>>;
+
+    #[test]
+    fn qcell_dyn_registry() {
+        let mut owner = QCellOwner::new();
+        let registry: Vec<Erased> = vec![
+            QCell::erase_arc(Arc::new(owner.cell(1_u32))),
+            QCell::erase_arc(Arc::new(owner.cell(String::from("a")))),
+            QCell::erase_arc(Arc::new(owner.cell(vec![1.5_f64]))),
+            QCell::erase_arc(Arc::new(owner.cell(2_u32))),
+        ];
+        for cell in &registry {
+            if let Some(n) = owner.rw_downcast::<u32>(cell) {
+                *n *= 10;
+            } else if let Some(s) = owner.rw_downcast::<String>(cell) {
+                s.push('!');
+            }
+        }
+        let numbers: Vec<u32> = registry
+            .iter()
+            .filter_map(|c| owner.ro_downcast::<u32>(c).copied())
+            .collect();
+        assert_eq!(numbers, [10, 20]);
+        assert_eq!(owner.ro_downcast::<String>(&registry[1]).unwrap(), "a!");
+        assert_eq!(owner.ro_downcast::<Vec<f64>>(&registry[2]).unwrap(), &[1.5]);
+    }
+
+    #[test]
+    fn qcell_dyn_wrong_type() {
+        // Safety: No other owner is created with this ID
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = QCell::erase_arc(Arc::new(owner.cell(1_u8)));
+        assert!(owner.ro_downcast::<u16>(&cell).is_none());
+        assert!(owner.rw_downcast::<i8>(&cell).is_none());
+        assert!(OwnerMut::from(&mut owner).rw_downcast::<()>(&cell).is_none());
+        let cell = QCell::try_typed_arc::<u32, _>(cell, &owner).err().unwrap();
+        assert_eq!(owner.ro_downcast::<u8>(&cell), Some(&1));
+    }
+
+    #[test]
+    fn qcell_dyn_typed_round_trip() {
+        let mut owner = QCellOwner::new();
+        let typed = Arc::new(owner.cell(String::from("x")));
+        let erased = QCell::erase_arc(typed.clone());
+        let back = QCell::try_typed_arc::<String, _>(erased, &owner).ok().unwrap();
+        assert!(Arc::ptr_eq(&back, &typed));
+        assert_eq!(Arc::strong_count(&typed), 2);
+        owner.rw(&back).push('y');
+        assert_eq!(owner.ro(&typed), "xy");
+        drop(back);
+        assert_eq!(Arc::strong_count(&typed), 1);
+
+        // Over-aligned contents stay at the same offset
+        #[derive(Debug, PartialEq)]
+        #[repr(align(64))]
+        struct Aligned(u8);
+        let erased = QCell::erase_arc(Arc::new(owner.cell(Aligned(7))));
+        let back = QCell::try_typed_arc::<Aligned, _>(erased, &owner).ok().unwrap();
+        assert_eq!(*owner.ro(&back), Aligned(7));
+    }
+
+    #[test]
+    #[should_panic]
+    fn qcell_dyn_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cell = QCell::erase_arc(Arc::new(owner1.cell(0_u32)));
+        let _ = QCell::try_typed_arc::<u32, _>(cell, &owner2);
+    }
+}
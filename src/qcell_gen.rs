@@ -0,0 +1,410 @@
+use alloc::boxed::Box;
+use alloc::rc::{Rc, Weak};
+use core::pin::Pin;
+
+use crate::qcell::{bad_owner_panic, sealed, OwnerNonce};
+use crate::{sound, QCell, QCellBorrow, QCellOwnerID};
+
+// Number of low bits of the ID used for the generation.  The ID is
+// the target address plus twice the low bits of the generation, so
+// the target is aligned (and sized) to cover all of those addresses.
+// Bit 0 is left clear so as never to collide with the odd IDs of
+// `QCellOwnerSeq`.
+const GEN_BITS: u32 = 8;
+const GEN_MASK: usize = (1 << GEN_BITS) - 1;
+const TARGET_SIZE: usize = 2 << GEN_BITS;
+
+// Every ID this owner can hand out is an address inside this
+// allocation, so no other live owner can have the same ID
+#[repr(C, align(512))]
+struct GenTarget {
+    _data: [u8; TARGET_SIZE],
+}
+
+// Pack the low bits of a generation into the ID for a target at
+// address `base`
+#[inline]
+fn pack(base: usize, generation: u64) -> usize {
+    base + ((generation as usize & GEN_MASK) << 1)
+}
+
+// Split an ID packed by `pack` into the target address and the low
+// bits of the generation.  Only needed to check the packing scheme.
+#[cfg(test)]
+fn unpack(num: usize) -> (usize, usize) {
+    (num & !(TARGET_SIZE - 1), (num & (TARGET_SIZE - 1)) >> 1)
+}
+
+/// Borrowing-owner of zero or more [`QCell`] instances, whose ID
+/// changes with each generation.
+///
+/// This is like a [`QCellOwner`], but calling
+/// [`QCellOwnerGen::next_generation`] gives it a new ID, which
+/// disowns all the cells created so far.  This suits a subsystem that
+/// gets torn down and rebuilt, where long-lived observers may still
+/// hold references to cells of the old generation.  Observers keep a
+/// [`WeakCellRef`] obtained from [`QCellOwnerGen::subscribe`], and
+/// access the cell through [`QCellOwnerGen::ro_if_current`] or
+/// [`QCellOwnerGen::rw_if_current`], which return `None` if the cell
+/// has been dropped or belongs to a dead generation, instead of
+/// panicking.
+///
+/// The low 8 bits of the generation are packed into the ID, which is
+/// the address of a 512-byte aligned allocation plus twice those
+/// bits.  So every ID is an address inside an allocation that only
+/// this owner holds, and can't clash with any other live owner.
+/// Every 256 generations the owner moves to a fresh allocation
+/// (allocated before the old one is freed) to get new IDs.  A
+/// subscription kept across that many rebuilds could in theory find
+/// its old ID reused, which would make it current again.  That is a
+/// logic error rather than anything unsound, and with the
+/// **debug-owner-tracking** feature, the per-generation nonce catches
+/// it.
+///
+/// ```
+///# use qcell::QCellOwnerGen;
+///# use std::rc::Rc;
+/// let mut owner = QCellOwnerGen::new();
+/// let cell = Rc::new(owner.cell(1));
+/// let sub = owner.subscribe(&cell);
+/// assert_eq!(owner.ro_if_current(&sub, |v| *v), Some(1));
+///
+/// // Rebuild the subsystem: the old cell is no longer current
+/// owner.next_generation();
+/// assert_eq!(owner.ro_if_current(&sub, |v| *v), None);
+///
+/// let cell = Rc::new(owner.cell(2));
+/// let sub = owner.subscribe(&cell);
+/// assert_eq!(owner.rw_if_current(&sub, |v| { *v += 1; *v }), Some(3));
+/// ```
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`WeakCellRef`]: struct.WeakCellRef.html
+/// [`QCellOwnerGen::next_generation`]: struct.QCellOwnerGen.html#method.next_generation
+/// [`QCellOwnerGen::subscribe`]: struct.QCellOwnerGen.html#method.subscribe
+/// [`QCellOwnerGen::ro_if_current`]: struct.QCellOwnerGen.html#method.ro_if_current
+/// [`QCellOwnerGen::rw_if_current`]: struct.QCellOwnerGen.html#method.rw_if_current
+pub struct QCellOwnerGen {
+    // Pinned for the same reason as in `QCellOwner`
+    handle: Pin<Box<GenTarget>>,
+    generation: u64,
+    nonce: OwnerNonce,
+}
+
+impl Default for QCellOwnerGen {
+    fn default() -> Self {
+        QCellOwnerGen::new()
+    }
+}
+
+impl QCellOwnerGen {
+    /// Create an owner at generation 0.  This can't be a `const fn`,
+    /// since it allocates.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            handle: Self::target(),
+            generation: 0,
+            nonce: OwnerNonce::next(),
+        }
+    }
+
+    #[inline]
+    fn target() -> Pin<Box<GenTarget>> {
+        Box::pin(GenTarget {
+            _data: [0; TARGET_SIZE],
+        })
+    }
+
+    /// Get the internal owner ID for the current generation.  This
+    /// may be used to create [`QCell`] instances without needing a
+    /// borrow on this structure.
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        let raw_ptr: *const GenTarget = &*self.handle;
+        QCellOwnerID::new(pack(raw_ptr as usize, self.generation), self.nonce)
+    }
+
+    /// Get the current generation, which starts at 0
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Move on to the next generation, giving this owner a new ID.
+    /// All cells created so far are disowned: accessing them through
+    /// `ro` or `rw` panics, and through `ro_if_current` or
+    /// `rw_if_current` returns `None`.
+    pub fn next_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation as usize & GEN_MASK == 0 {
+            // Out of IDs in this target.  Assigning drops the old
+            // target only after the new one is allocated, so the new
+            // one can't be at the same address.
+            self.handle = Self::target();
+        }
+        self.nonce = OwnerNonce::next();
+    }
+
+    /// Create a new cell owned by the current generation of this
+    /// owner.  See also [`QCell::new`].
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> QCell<T> {
+        self.id().cell(value)
+    }
+
+    /// Borrow contents of a [`QCell`] immutably (read-only).  Panics
+    /// if the [`QCell`] is not owned by the current generation of this
+    /// owner.
+    #[inline]
+    pub fn ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        if !qc.owner.matches(self.id()) {
+            bad_owner_panic();
+        }
+        // Safety: Owned (owner ID checked above), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&qc.value) }
+    }
+
+    /// Borrow contents of a [`QCell`] mutably (read-write).  Panics if
+    /// the [`QCell`] is not owned by the current generation of this
+    /// owner.
+    #[inline]
+    pub fn rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        if !qc.owner.matches(self.id()) {
+            bad_owner_panic();
+        }
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&qc.value) }
+    }
+
+    /// Create a weak reference to a cell, recording the current
+    /// generation.  Panics if the cell is not owned by the current
+    /// generation of this owner.
+    pub fn subscribe<T: ?Sized>(&self, cell: &Rc<QCell<T>>) -> WeakCellRef<T> {
+        let id = self.id();
+        if !cell.owner.matches(id) {
+            bad_owner_panic();
+        }
+        WeakCellRef {
+            id,
+            weak: Rc::downgrade(cell),
+        }
+    }
+
+    /// Call `f` with the contents of the referenced cell borrowed
+    /// immutably, returning its result.  Returns `None` without
+    /// calling `f` if the cell has been dropped or the reference was
+    /// made in an earlier generation.
+    #[inline]
+    pub fn ro_if_current<T: ?Sized, R>(
+        &self,
+        weak: &WeakCellRef<T>,
+        f: impl FnOnce(&T) -> R,
+    ) -> Option<R> {
+        let cell = weak.upgrade_if_current(self)?;
+        // Safety: Owned (`upgrade_if_current` checked the ID), and owner borrowed
+        Some(f(unsafe { sound::deref_shared(&cell.value) }))
+    }
+
+    /// Call `f` with the contents of the referenced cell borrowed
+    /// mutably, returning its result.  Returns `None` without calling
+    /// `f` if the cell has been dropped or the reference was made in
+    /// an earlier generation.
+    #[inline]
+    pub fn rw_if_current<T: ?Sized, R>(
+        &mut self,
+        weak: &WeakCellRef<T>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        let cell = weak.upgrade_if_current(self)?;
+        // Safety: Owned (`upgrade_if_current` checked the ID), and owner mutably borrowed
+        Some(f(unsafe { sound::deref_unique(&cell.value) }))
+    }
+}
+
+impl From<&QCellOwnerGen> for QCellOwnerID {
+    fn from(owner: &QCellOwnerGen) -> Self {
+        owner.id()
+    }
+}
+
+impl sealed::Sealed for QCellOwnerGen {}
+
+impl QCellBorrow for QCellOwnerGen {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.ro(qc)
+    }
+
+    #[inline]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.rw(qc)
+    }
+}
+
+/// Weak reference to an `Rc<QCell<T>>`, tagged with the
+/// [`QCellOwnerGen`] generation it was made in.
+///
+/// Created by [`QCellOwnerGen::subscribe`].  See [`QCellOwnerGen`].
+///
+/// [`QCellOwnerGen`]: struct.QCellOwnerGen.html
+/// [`QCellOwnerGen::subscribe`]: struct.QCellOwnerGen.html#method.subscribe
+pub struct WeakCellRef<T: ?Sized> {
+    id: QCellOwnerID,
+    weak: Weak<QCell<T>>,
+}
+
+impl<T: ?Sized> Clone for WeakCellRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            weak: self.weak.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized> WeakCellRef<T> {
+    /// Get the owner ID that was current when this reference was made
+    #[inline]
+    pub fn subscribed_id(&self) -> QCellOwnerID {
+        self.id
+    }
+
+    /// Test whether the cell still exists and belongs to the current
+    /// generation of `owner`
+    #[inline]
+    pub fn is_current(&self, owner: &QCellOwnerGen) -> bool {
+        self.upgrade_if_current(owner).is_some()
+    }
+
+    /// Get the cell, if it still exists and belongs to the current
+    /// generation of `owner`.  Otherwise returns `None`.
+    pub fn upgrade_if_current(&self, owner: &QCellOwnerGen) -> Option<Rc<QCell<T>>> {
+        // Both the ID recorded at subscription and the ID stored in
+        // the cell are compared against the owner's current ID, never
+        // against each other, so a reference from a dead generation
+        // can't pass.  Plain equality is used rather than
+        // `QCellOwnerID::matches`, so that a stale nonce gives `None`
+        // instead of a panic.
+        let current = owner.id();
+        if self.id != current {
+            return None;
+        }
+        let cell = self.weak.upgrade()?;
+        if cell.owner != current {
+            return None;
+        }
+        Some(cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack, unpack, QCellOwnerGen, GEN_MASK, TARGET_SIZE};
+    use crate::QCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn qcell_gen_rebuild() {
+        let mut owner = QCellOwnerGen::new();
+        let old: Vec<_> = (0..3).map(|i| Rc::new(owner.cell(i))).collect();
+        let old_subs: Vec<_> = old.iter().map(|c| owner.subscribe(c)).collect();
+        assert_eq!(owner.rw_if_current(&old_subs[1], |v| *v += 10), Some(()));
+        assert_eq!(*owner.ro(&old[1]), 11);
+
+        let old_id = owner.id();
+        owner.next_generation();
+        assert_eq!(owner.generation(), 1);
+        assert_ne!(owner.id(), old_id);
+        for sub in &old_subs {
+            assert!(!sub.is_current(&owner));
+            assert_eq!(owner.ro_if_current(sub, |v| *v), None);
+            assert_eq!(owner.rw_if_current(sub, |v| *v = 0), None);
+        }
+
+        let new = Rc::new(owner.cell(100));
+        let new_sub = owner.subscribe(&new);
+        assert!(new_sub.is_current(&owner));
+        assert_eq!(owner.ro_if_current(&new_sub, |v| *v), Some(100));
+        drop(new);
+        assert_eq!(owner.ro_if_current(&new_sub, |v| *v), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn qcell_gen_old_cell_panics() {
+        let mut owner = QCellOwnerGen::new();
+        let cell = owner.cell(0);
+        owner.next_generation();
+        owner.ro(&cell);
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn qcell_gen_subscribe_other_owner() {
+        let owner1 = QCellOwnerGen::new();
+        let owner2 = QCellOwnerGen::new();
+        let cell = Rc::new(QCell::new(&owner1, 0));
+        owner2.subscribe(&cell);
+    }
+
+    #[test]
+    fn qcell_gen_ids_unique() {
+        // Run through a few targets, checking no ID repeats while
+        // the subscriptions hold their cells alive
+        let mut owner = QCellOwnerGen::new();
+        let mut subs = Vec::new();
+        let mut cells = Vec::new();
+        for _ in 0..(3 * (GEN_MASK + 1) + 5) {
+            let cell = Rc::new(owner.cell(owner.generation()));
+            subs.push(owner.subscribe(&cell));
+            cells.push(cell);
+            owner.next_generation();
+        }
+        let id = owner.id();
+        assert!(subs.iter().all(|s| s.subscribed_id() != id));
+        assert!(subs.iter().all(|s| !s.is_current(&owner)));
+        assert_eq!(unpack(id.num).1, 5);
+    }
+
+    #[test]
+    fn qcell_gen_pack_round_trip() {
+        let base = TARGET_SIZE * 3;
+        for gen in [0u64, 1, 2, 127, 255, 256, 257, u64::MAX] {
+            let num = pack(base, gen);
+            assert_eq!(num & 1, 0);
+            assert_eq!(unpack(num), (base, gen as usize & GEN_MASK));
+        }
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn qcell_gen_pack_64() {
+        let base = 0xFFFF_FFFF_FFFF_FE00;
+        assert_eq!(pack(base, 0xFF), 0xFFFF_FFFF_FFFF_FFFE);
+        assert_eq!(unpack(0xFFFF_FFFF_FFFF_FFFE), (base, 0xFF));
+        assert_eq!(unpack(0x7FFF_1234_5678_9A02), (0x7FFF_1234_5678_9A00, 1));
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn qcell_gen_pack_32() {
+        let base = 0xFFFF_FE00;
+        assert_eq!(pack(base, 0xFF), 0xFFFF_FFFE);
+        assert_eq!(unpack(0xFFFF_FFFE), (base, 0xFF));
+        assert_eq!(unpack(0x1234_5A02), (0x1234_5A00, 1));
+    }
+
+    #[test]
+    fn qcell_gen_target_covers_ids() {
+        use core::mem::{align_of, size_of};
+        assert_eq!(align_of::<super::GenTarget>(), TARGET_SIZE);
+        assert_eq!(size_of::<super::GenTarget>(), TARGET_SIZE);
+        let owner = QCellOwnerGen::new();
+        let (base, gen) = unpack(owner.id().num);
+        assert_eq!(gen, 0);
+        assert_eq!(base % TARGET_SIZE, 0);
+    }
+}
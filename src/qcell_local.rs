@@ -0,0 +1,339 @@
+use alloc::rc::Rc;
+
+use crate::qcell::{
+    bad_owner_panic, not_distinct_panic, sealed, OwnerIDTarget, OwnerNonce, MAGIC_OWNER_ID_TARGET,
+};
+use crate::{sound, QCell, QCellBorrow, QCellOwnerID};
+
+/// Single-threaded borrowing-owner of zero or more [`QCell`]
+/// instances, which can hand out handles for creating cells.
+///
+/// Like [`QCellOwner`], the ID is the address of an allocation held
+/// by the owner, but here the allocation is an `Rc`, so the owner is
+/// neither `Send` nor `Sync`.  That is all that is needed on a
+/// single-threaded `no_std` target with `alloc`, and unlike
+/// [`QCellOwnerSeq`], creating one is safe.
+///
+/// What sets it apart is [`QCellOwnerLocal::creator`], which returns
+/// a [`QCellOwnerLocalRef`] holding a clone of the `Rc`.  Like a
+/// [`QCellOwnerID`], a creator handle can create cells without
+/// borrowing the owner, but it also keeps the allocation alive.  So
+/// even if the owner is dropped, its ID can't be given to any new
+/// owner whilst a handle exists, and the stale ID problem described
+/// for [`QCellOwnerID`] can't arise.  Only the owner itself can
+/// borrow the cells.
+///
+/// The cells are ordinary [`QCell`] instances, so they work with
+/// anything generic over [`QCellBorrow`].
+///
+/// In short, the owner types for [`QCell`] compare as follows:
+///
+/// - [`QCellOwner`]: `Send` and `Sync`, needs `alloc`
+/// - [`QCellOwnerSeq`]: no allocation, but `new` is unsafe
+/// - [`QCellOwnerPinned`]: no allocation, but must be pinned
+/// - [`QCellOwnerGen`]: changes ID on each generation, needs `alloc`
+/// - `QCellOwnerLocal`: single-threaded, needs `alloc`, with creator
+///   handles that keep its ID reserved
+///
+/// ```
+///# use qcell::QCellOwnerLocal;
+/// let mut owner = QCellOwnerLocal::new();
+/// let creator = owner.creator();
+///
+/// // Code holding only the creator handle can make cells
+/// let cells: Vec<_> = (1..=3).map(|i| creator.cell(i)).collect();
+///
+/// // But only the owner can get at their contents
+/// *owner.rw(&cells[0]) += *owner.ro(&cells[2]);
+/// assert_eq!(*owner.ro(&cells[0]), 4);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`QCellOwnerPinned`]: struct.QCellOwnerPinned.html
+/// [`QCellOwnerGen`]: struct.QCellOwnerGen.html
+/// [`QCellOwnerID`]: struct.QCellOwnerID.html
+/// [`QCellOwnerLocalRef`]: struct.QCellOwnerLocalRef.html
+/// [`QCellOwnerLocal::creator`]: struct.QCellOwnerLocal.html#method.creator
+/// [`QCellBorrow`]: trait.QCellBorrow.html
+pub struct QCellOwnerLocal {
+    // The allocation is aligned to 2, so the ID can't collide with the
+    // odd IDs of `QCellOwnerSeq`
+    target: Rc<OwnerIDTarget>,
+    nonce: OwnerNonce,
+}
+
+impl Default for QCellOwnerLocal {
+    fn default() -> Self {
+        QCellOwnerLocal::new()
+    }
+}
+
+impl QCellOwnerLocal {
+    /// Create an owner that can be used for creating many [`QCell`]
+    /// instances.  This can't be a `const fn`, since it allocates.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            target: Rc::new(MAGIC_OWNER_ID_TARGET),
+            nonce: OwnerNonce::next(),
+        }
+    }
+
+    /// Get the internal owner ID.  See [`QCellOwner::id`].
+    ///
+    /// [`QCellOwner::id`]: struct.QCellOwner.html#method.id
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        QCellOwnerID::new(Rc::as_ptr(&self.target) as usize, self.nonce)
+    }
+
+    /// Get a handle which can create cells owned by this owner, and
+    /// which keeps this owner's ID reserved for as long as it exists
+    #[inline]
+    pub fn creator(&self) -> QCellOwnerLocalRef {
+        QCellOwnerLocalRef {
+            target: self.target.clone(),
+            nonce: self.nonce,
+        }
+    }
+
+    /// Create a new cell owned by this owner instance.  See also
+    /// [`QCell::new`].
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> QCell<T> {
+        self.id().cell(value)
+    }
+
+    #[inline]
+    fn owner_check<T: ?Sized>(&self, qc: &QCell<T>) {
+        if !qc.owner.matches(self.id()) {
+            bad_owner_panic();
+        }
+    }
+
+    /// Borrow contents of a [`QCell`] immutably (read-only).  Panics
+    /// if the [`QCell`] is not owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.owner_check(qc);
+        // Safety: Owned (owner ID checked above), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&qc.value) }
+    }
+
+    /// Borrow contents of a [`QCell`] mutably (read-write).  Panics if
+    /// the [`QCell`] is not owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.owner_check(qc);
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&qc.value) }
+    }
+
+    /// Borrow contents of two [`QCell`] instances mutably.  Panics if
+    /// the two [`QCell`] instances point to the same memory, or if
+    /// either is not owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw2<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+    ) -> (&'a mut T, &'a mut U) {
+        self.owner_check(qc1);
+        self.owner_check(qc2);
+        if qc1 as *const _ as *const () == qc2 as *const _ as *const () {
+            not_distinct_panic();
+        }
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&qc1.value, &qc2.value) }
+    }
+
+    /// Borrow contents of three [`QCell`] instances mutably.  Panics
+    /// if any pair of [`QCell`] instances point to the same memory, or
+    /// if any is not owned by this owner.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
+        &'a mut self,
+        qc1: &'a QCell<T>,
+        qc2: &'a QCell<U>,
+        qc3: &'a QCell<V>,
+    ) -> (&'a mut T, &'a mut U, &'a mut V) {
+        self.owner_check(qc1);
+        self.owner_check(qc2);
+        self.owner_check(qc3);
+        let p1 = qc1 as *const _ as *const ();
+        let p2 = qc2 as *const _ as *const ();
+        let p3 = qc3 as *const _ as *const ();
+        if p1 == p2 || p2 == p3 || p3 == p1 {
+            not_distinct_panic();
+        }
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&qc1.value, &qc2.value, &qc3.value) }
+    }
+}
+
+impl From<&QCellOwnerLocal> for QCellOwnerID {
+    fn from(owner: &QCellOwnerLocal) -> Self {
+        owner.id()
+    }
+}
+
+impl sealed::Sealed for QCellOwnerLocal {}
+
+impl QCellBorrow for QCellOwnerLocal {
+    #[inline]
+    fn qcell_ro<'a, T: ?Sized>(&'a self, qc: &'a QCell<T>) -> &'a T {
+        self.ro(qc)
+    }
+
+    #[inline]
+    fn qcell_rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCell<T>) -> &'a mut T {
+        self.rw(qc)
+    }
+}
+
+/// Handle which creates cells for a [`QCellOwnerLocal`], and keeps
+/// its ID reserved.
+///
+/// Created by [`QCellOwnerLocal::creator`].  This can't borrow the
+/// cells it creates.  See [`QCellOwnerLocal`].
+///
+/// [`QCellOwnerLocal`]: struct.QCellOwnerLocal.html
+/// [`QCellOwnerLocal::creator`]: struct.QCellOwnerLocal.html#method.creator
+#[derive(Clone)]
+pub struct QCellOwnerLocalRef {
+    target: Rc<OwnerIDTarget>,
+    nonce: OwnerNonce,
+}
+
+impl QCellOwnerLocalRef {
+    /// Get the owner ID of the [`QCellOwnerLocal`] this handle came
+    /// from
+    ///
+    /// [`QCellOwnerLocal`]: struct.QCellOwnerLocal.html
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        QCellOwnerID::new(Rc::as_ptr(&self.target) as usize, self.nonce)
+    }
+
+    /// Create a new cell owned by the [`QCellOwnerLocal`] this
+    /// handle came from
+    ///
+    /// [`QCellOwnerLocal`]: struct.QCellOwnerLocal.html
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> QCell<T> {
+        self.id().cell(value)
+    }
+}
+
+impl From<&QCellOwnerLocalRef> for QCellOwnerID {
+    fn from(creator: &QCellOwnerLocalRef) -> Self {
+        creator.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QCellOwnerLocal;
+    use crate::{PoisonCell, QCell, QCellBorrow, QCellOwner, QCellOwnerID};
+    use std::rc::Rc;
+
+    #[test]
+    fn qcell_local() {
+        let mut owner = QCellOwnerLocal::new();
+        let c1 = owner.cell(1);
+        let c2 = QCell::new(&owner, 2);
+        let c3 = owner.creator().cell(3);
+        *owner.rw(&c1) += 10;
+        let (a, b, c) = owner.rw3(&c1, &c2, &c3);
+        std::mem::swap(a, c);
+        *b += 1;
+        assert_eq!((owner.ro(&c1), owner.ro(&c2), owner.ro(&c3)), (&3, &3, &11));
+        let (a, b) = owner.rw2(&c1, &c3);
+        *a += *b;
+        assert_eq!(c1.into_inner(), 14);
+    }
+
+    #[test]
+    fn qcell_local_interop() {
+        // Cells are plain `QCell` instances, usable with the generic
+        // owner trait and the wrappers built on it
+        fn sum<O: QCellBorrow>(owner: &O, cells: &[Rc<QCell<i32>>]) -> i32 {
+            cells.iter().map(|c| *owner.qcell_ro(c)).sum()
+        }
+        let mut owner = QCellOwnerLocal::new();
+        let creator = owner.creator();
+        let cells: Vec<_> = (1..=4).map(|i| Rc::new(creator.cell(i))).collect();
+        assert_eq!(sum(&owner, &cells), 10);
+        *owner.qcell_rw(&cells[0]) = 5;
+        assert_eq!(sum(&owner, &cells), 14);
+
+        let poison = PoisonCell::new(&owner, 1);
+        assert_eq!(poison.with_mut(&mut owner, |v| *v + 1), Ok(2));
+        let id: QCellOwnerID = (&creator).into();
+        assert_eq!(id, owner.id());
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn qcell_local_bad_owner() {
+        let owner1 = QCellOwnerLocal::new();
+        let owner2 = QCellOwnerLocal::new();
+        let c = owner1.creator().cell(1);
+        let _ = owner2.ro(&c);
+    }
+
+    #[test]
+    #[should_panic(expected = "Illegal to borrow same QCell twice")]
+    fn qcell_local_rw2_same() {
+        let mut owner = QCellOwnerLocal::new();
+        let c = owner.cell(1);
+        let _ = owner.rw2(&c, &c);
+    }
+
+    #[test]
+    fn qcell_local_creator_keeps_id() {
+        let owner = QCellOwnerLocal::new();
+        let creator = owner.creator();
+        let id = owner.id();
+        drop(owner);
+
+        // The allocation is still held, so no new owner can get the
+        // same ID, whatever order the allocator hands out addresses
+        let mut owners = Vec::new();
+        let mut locals = Vec::new();
+        for _ in 0..1000 {
+            let o = QCellOwner::new();
+            assert_ne!(o.id(), id);
+            owners.push(o);
+            let l = QCellOwnerLocal::new();
+            assert_ne!(l.id(), id);
+            locals.push(l);
+        }
+        drop(owners);
+        drop(locals);
+        for _ in 0..1000 {
+            assert_ne!(QCellOwner::new().id(), id);
+            assert_ne!(QCellOwnerLocal::new().id(), id);
+        }
+
+        // Cells made through the handle belong to nobody alive
+        let c = creator.cell(1);
+        let o = QCellOwner::new();
+        assert!(!c.owner.matches(o.id()));
+        assert_eq!(creator.id(), id);
+    }
+}
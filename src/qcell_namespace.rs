@@ -0,0 +1,295 @@
+use core::fmt;
+use core::ops::Range;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::panic_messages;
+use crate::qcell::{OwnerNonce, FAST_QCELLOWNER_ID};
+use crate::{QCellOwnerID, QCellOwnerSeq};
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{OwnerKind, Registration};
+
+// Size of the block of ID numbers reserved for each namespace.  This
+// gives 2^32 owners per namespace on 64-bit, or 2^16 on 32-bit, since
+// only the odd numbers are used.
+const NAMESPACE_SPAN: usize = 1 << (usize::BITS / 2 + 1);
+
+#[cold]
+#[inline(never)]
+fn exhausted_panic(range: Range<usize>) -> ! {
+    panic!(
+        "{}: range {:#x}..{:#x}",
+        panic_messages::QCELL_NAMESPACE_EXHAUSTED,
+        range.start,
+        range.end
+    );
+}
+
+// Find the first block of `span` ID numbers at or after `next` which
+// starts just after a multiple of `span`, or `None` if there is no
+// room left.  `span` must be a power of two.
+fn block_after(next: usize, span: usize) -> Option<Range<usize>> {
+    let start = (next - 1).checked_add(span - 1)? & !(span - 1);
+    let start = start + 1;
+    Some(start..start.checked_add(span)?)
+}
+
+/// Source of [`QCellOwnerSeq`] owners with IDs from a private range.
+///
+/// [`QCellOwnerSeq::new`] takes its IDs from a single global counter,
+/// so where several subsystems each create many owners, their IDs
+/// are interleaved and all the threads contend on the same atomic.
+/// A namespace reserves a large block of the ID space from the global
+/// counter once, when it is created, and then hands out owners from
+/// that block using its own counter.  So the owners of one subsystem
+/// have IDs in a recognisable range, which can be checked with
+/// [`QCellOwnerNamespace::contains`], and creating owners in one
+/// namespace does not contend with creating them in another.
+///
+/// Each namespace has room for 2^32 owners on a 64-bit platform, or
+/// 2^16 on a 32-bit platform.  Blocks are aligned, so with the
+/// `Debug` output of [`QCellOwnerID`], the IDs of a namespace all
+/// share the same leading digits.  Creating an owner once the
+/// namespace is used up panics, rather than wrapping.
+///
+/// The owners are ordinary [`QCellOwnerSeq`] instances, and work with
+/// all [`QCell`] code just the same.  A namespace is `Send` and
+/// `Sync`, so it may be shared between threads.
+///
+/// ```
+///# use qcell::{QCellOwnerNamespace, QCellOwnerSeq};
+/// let plugin_a = QCellOwnerNamespace::new();
+/// let plugin_b = QCellOwnerNamespace::new();
+/// // Safety: No ID collisions are being exploited
+/// let mut owner = unsafe { plugin_a.owner() };
+/// let other = unsafe { plugin_b.owner() };
+/// let cell = owner.cell(1);
+/// *owner.rw(&cell) += 1;
+/// assert_eq!(*owner.ro(&cell), 2);
+/// assert!(plugin_a.contains(&owner) && !plugin_a.contains(&other));
+/// assert!(plugin_b.contains(&other) && !plugin_b.contains(owner.id()));
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwnerID`]: struct.QCellOwnerID.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`QCellOwnerSeq::new`]: struct.QCellOwnerSeq.html#method.new
+/// [`QCellOwnerNamespace::contains`]: #method.contains
+// Aligned so that the counters of namespaces next to each other in
+// memory don't share a cache line, which would bring the contention
+// back.  128 covers CPUs that prefetch cache lines in pairs.
+#[repr(align(128))]
+pub struct QCellOwnerNamespace {
+    next: AtomicUsize,
+    range: Range<usize>,
+}
+
+impl QCellOwnerNamespace {
+    /// Reserve a new namespace from the global owner ID sequence.
+    /// Panics if there is no room left in the ID space for another
+    /// namespace.  This can't be a `const fn`, since it updates the
+    /// global counter.
+    pub fn new() -> Self {
+        Self::reserve(NAMESPACE_SPAN)
+    }
+
+    pub(crate) fn reserve(span: usize) -> Self {
+        // Move the global counter past the block, so that
+        // `QCellOwnerSeq::new` and other namespaces never hand out
+        // any of its IDs
+        let mut next = FAST_QCELLOWNER_ID.load(Ordering::Relaxed);
+        loop {
+            let range = match block_after(next, span) {
+                Some(range) => range,
+                None => panic!("{}", panic_messages::QCELL_NAMESPACE_SPACE_EXHAUSTED),
+            };
+            match FAST_QCELLOWNER_ID.compare_exchange_weak(
+                next,
+                range.end,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    return Self {
+                        next: AtomicUsize::new(range.start),
+                        range,
+                    }
+                }
+                Err(current) => next = current,
+            }
+        }
+    }
+
+    /// Create an owner with the next ID from this namespace.  Panics
+    /// if the namespace has no IDs left.
+    ///
+    /// # Safety
+    ///
+    /// The same contract applies as for [`QCellOwnerSeq::new`].  IDs
+    /// within a namespace never wrap, but the global sequence that
+    /// [`QCellOwnerSeq::new`] uses can still wrap into the range of a
+    /// namespace after 2^63 owners on a 64-bit build.
+    ///
+    /// [`QCellOwnerSeq::new`]: struct.QCellOwnerSeq.html#method.new
+    #[inline]
+    pub unsafe fn owner(&self) -> QCellOwnerSeq {
+        // Numbers past the end are never handed out, so it doesn't
+        // matter that failed calls still advance the counter
+        let num = self.next.fetch_add(2, Ordering::Relaxed);
+        if num >= self.range.end {
+            exhausted_panic(self.range.clone());
+        }
+        QCellOwnerSeq {
+            id: QCellOwnerID::new(num, OwnerNonce::next()),
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::auto(OwnerKind::QCellOwnerSeq, None),
+        }
+    }
+
+    /// Test whether an owner ID was allocated from this namespace
+    #[inline]
+    pub fn contains(&self, id: impl Into<QCellOwnerID>) -> bool {
+        self.range.contains(&id.into().num)
+    }
+
+    /// Get the range of ID numbers reserved for this namespace, as
+    /// they appear in the `Debug` output of [`QCellOwnerID`].
+    ///
+    /// [`QCellOwnerID`]: struct.QCellOwnerID.html
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Default for QCellOwnerNamespace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for QCellOwnerNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("QCellOwnerNamespace")
+            .field(&format_args!(
+                "{:#x}..{:#x}",
+                self.range.start, self.range.end
+            ))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_after, QCellOwnerNamespace, NAMESPACE_SPAN};
+    use crate::{QCellOwnerID, QCellOwnerSeq};
+    use std::panic::AssertUnwindSafe;
+    use std::sync::Arc;
+
+    #[test]
+    fn namespace_block_after() {
+        assert_eq!(block_after(1, 16), Some(1..17));
+        assert_eq!(block_after(3, 16), Some(17..33));
+        assert_eq!(block_after(17, 16), Some(17..33));
+        assert_eq!(block_after(19, 16), Some(33..49));
+        let top = usize::MAX - 15;
+        assert_eq!(block_after(top - 16, 16), Some(top - 15..top + 1));
+        assert_eq!(block_after(top - 14, 16), None);
+        assert_eq!(block_after(usize::MAX, 16), None);
+    }
+
+    #[test]
+    fn namespace_distinct() {
+        let seq1 = unsafe { QCellOwnerSeq::new() };
+        let ns1 = QCellOwnerNamespace::new();
+        let ns2 = QCellOwnerNamespace::new();
+        let seq2 = unsafe { QCellOwnerSeq::new() };
+        for ns in &[&ns1, &ns2] {
+            let range = ns.range();
+            assert_eq!(range.len(), NAMESPACE_SPAN);
+            assert_eq!((range.start - 1) % NAMESPACE_SPAN, 0);
+            assert!(!ns.contains(&seq1) && !ns.contains(&seq2));
+        }
+        assert!(ns1.range().end <= ns2.range().start);
+        assert!(seq2.id().num >= ns2.range().end);
+
+        let owners1: Vec<_> = (0..100).map(|_| unsafe { ns1.owner() }).collect();
+        let owners2: Vec<_> = (0..100).map(|_| unsafe { ns2.owner() }).collect();
+        let mut ids: Vec<QCellOwnerID> = owners1.iter().chain(&owners2).map(|o| o.id()).collect();
+        assert!(owners1.iter().all(|o| ns1.contains(o) && !ns2.contains(o)));
+        assert!(owners2.iter().all(|o| ns2.contains(o) && !ns1.contains(o)));
+        ids.push(seq1.id());
+        ids.push(seq2.id());
+        ids.sort_by_key(|id| id.num);
+        ids.dedup_by_key(|id| id.num);
+        assert_eq!(ids.len(), 202);
+        assert!(ids.iter().all(|id| id.num % 2 == 1));
+    }
+
+    #[test]
+    fn namespace_cells() {
+        let ns = QCellOwnerNamespace::new();
+        let mut owner1 = unsafe { ns.owner() };
+        let owner2 = unsafe { ns.owner() };
+        let c1 = owner1.cell(1u32);
+        let c2 = owner1.id().cell(2u32);
+        let (a, b) = owner1.rw2(&c1, &c2);
+        std::mem::swap(a, b);
+        assert_eq!((*owner1.ro(&c1), *owner1.ro(&c2)), (2, 1));
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| *owner2.ro(&c1)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn namespace_exhausted() {
+        let ns = QCellOwnerNamespace::reserve(8);
+        let owners: Vec<_> = (0..4).map(|_| unsafe { ns.owner() }).collect();
+        assert!(owners.iter().all(|o| ns.contains(o)));
+        for _ in 0..2 {
+            let result = std::panic::catch_unwind(|| unsafe { ns.owner() });
+            let msg = *result.err().unwrap().downcast::<String>().unwrap();
+            assert!(msg.starts_with(crate::panic_messages::QCELL_NAMESPACE_EXHAUSTED));
+        }
+        // Other namespaces are unaffected
+        let ns2 = QCellOwnerNamespace::reserve(8);
+        let owner = unsafe { ns2.owner() };
+        assert!(ns2.contains(&owner) && !ns.contains(&owner));
+    }
+
+    #[test]
+    fn namespace_threads() {
+        let ns = Arc::new(QCellOwnerNamespace::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let ns = ns.clone();
+                std::thread::spawn(move || {
+                    (0..1000)
+                        .map(|_| unsafe { ns.owner() }.id().num)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut nums: Vec<usize> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        nums.sort_unstable();
+        nums.dedup();
+        assert_eq!(nums.len(), 4000);
+        assert_eq!(nums[0], ns.range().start);
+        assert_eq!(nums[3999], ns.range().start + 2 * 3999);
+    }
+
+    #[test]
+    fn namespace_debug() {
+        let ns = QCellOwnerNamespace::reserve(16);
+        let text = format!("{:?}", ns);
+        assert_eq!(
+            text,
+            format!(
+                "QCellOwnerNamespace({:#x}..{:#x})",
+                ns.range().start,
+                ns.range().end
+            )
+        );
+    }
+}
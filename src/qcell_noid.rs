@@ -0,0 +1,406 @@
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+use crate::{panic_messages, sound, QCellOwner, QCellOwnerID, QCellOwnerSeq};
+
+#[cold]
+#[inline(never)]
+fn bad_owner_panic() -> ! {
+    panic!("{}", panic_messages::OWNED_CELLS_INCORRECT_OWNER);
+}
+
+#[cold]
+#[inline(never)]
+fn not_distinct_panic() -> ! {
+    panic!("{}", panic_messages::OWNED_CELLS_BORROWED_TWICE);
+}
+
+/// Cell with no owner ID, for use within an [`OwnedCells`]
+/// container, which stores the owner ID once for all its cells.
+///
+/// This has no space overhead over the contained value.  It can only
+/// be accessed through the owner via an [`OwnedCells`] container.
+///
+/// [`OwnedCells`]: struct.OwnedCells.html
+pub struct QCellNoId<T: ?Sized> {
+    value: UnsafeCell<T>,
+}
+
+// Same reasoning as for `QCell`
+unsafe impl<T: Send + Sync + ?Sized> Sync for QCellNoId<T> {}
+
+impl<T> QCellNoId<T> {
+    /// Create a new cell.  It will be owned by whichever owner owns
+    /// the [`OwnedCells`] container it is placed in.
+    ///
+    /// [`OwnedCells`]: struct.OwnedCells.html
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destroy the cell and return the contained value
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> QCellNoId<T> {
+    /// Returns a mutable reference to the underlying data
+    ///
+    /// Safety: This call borrows the cell mutably which guarantees
+    /// that we possess the only reference.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Container of [`QCellNoId`] cells all owned by the same owner,
+/// which stores the owner ID only once.
+///
+/// A `Vec<QCell<T>>` stores a copy of the owner ID in every element.
+/// For small `T` that may double the memory required.  This container
+/// stores the ID once, and the elements are accessed through the
+/// owner by index, using calls such as [`QCellOwner::ro_at`] and
+/// [`QCellOwner::rw_at`].  The ID is checked on each access, as for
+/// [`QCell`].  Elements can only be added when the container is
+/// borrowed mutably, so that there can be no active borrows.
+///
+/// ```
+///# use qcell::{OwnedCells, QCellOwner};
+/// let mut owner = QCellOwner::new();
+/// let mut cells = OwnedCells::new(&owner);
+/// cells.extend(0..10);
+/// *owner.rw_at(&cells, 3) += 100;
+/// let (a, b) = owner.rw2_at(&cells, 0, 9);
+/// std::mem::swap(a, b);
+/// for v in owner.rw_iter(&cells) {
+///     *v += 1;
+/// }
+/// let total: i32 = owner.ro_iter(&cells).sum();
+/// assert_eq!(total, 155);
+/// assert_eq!(*owner.ro_at(&cells, 0), 10);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellNoId`]: struct.QCellNoId.html
+/// [`QCellOwner::ro_at`]: struct.QCellOwner.html#method.ro_at
+/// [`QCellOwner::rw_at`]: struct.QCellOwner.html#method.rw_at
+pub struct OwnedCells<T> {
+    owner: QCellOwnerID,
+    cells: Vec<QCellNoId<T>>,
+}
+
+impl<T> OwnedCells<T> {
+    /// Create an empty container owned by the given owner, or
+    /// owner-ID.  See [`QCell::new`] for the forms accepted.
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn new(id: impl Into<QCellOwnerID>) -> Self {
+        Self::with_capacity(id, 0)
+    }
+
+    /// Create an empty container with space for `capacity` cells
+    #[inline]
+    pub fn with_capacity(id: impl Into<QCellOwnerID>, capacity: usize) -> Self {
+        Self {
+            owner: id.into(),
+            cells: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Get the ID of the owner of the cells in this container
+    #[inline]
+    pub fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    /// Get the number of cells in the container
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Test whether the container is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Add a new cell to the end of the container
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.cells.push(QCellNoId::new(value));
+    }
+
+    /// Get a reference to a cell which also carries the owner ID of
+    /// the container.  Panics if `index` is out of range.
+    #[inline]
+    pub fn cell_ref(&self, index: usize) -> QCellRef<'_, T> {
+        QCellRef {
+            owner: self.owner,
+            cell: &self.cells[index],
+        }
+    }
+
+    /// Returns a mutable reference to the contents of a cell.  Panics
+    /// if `index` is out of range.
+    ///
+    /// Safety: This call borrows the container mutably which
+    /// guarantees that we possess the only reference.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> &mut T {
+        self.cells[index].get_mut()
+    }
+
+    /// Destroy the container and return the contents of the cells
+    pub fn into_vec(self) -> Vec<T> {
+        self.cells.into_iter().map(QCellNoId::into_inner).collect()
+    }
+
+    #[inline]
+    fn get(&self, id: QCellOwnerID, index: usize) -> &QCellNoId<T> {
+        if !self.owner.matches(id) {
+            bad_owner_panic();
+        }
+        &self.cells[index]
+    }
+}
+
+impl<T> Extend<T> for OwnedCells<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.cells.extend(iter.into_iter().map(QCellNoId::new));
+    }
+}
+
+/// Reference to a cell within an [`OwnedCells`] container, which
+/// carries the container's owner ID so that it can be accessed
+/// independently of the container with the usual owner check.
+/// Created with [`OwnedCells::cell_ref`].
+///
+/// [`OwnedCells`]: struct.OwnedCells.html
+/// [`OwnedCells::cell_ref`]: struct.OwnedCells.html#method.cell_ref
+pub struct QCellRef<'a, T> {
+    owner: QCellOwnerID,
+    cell: &'a QCellNoId<T>,
+}
+
+impl<'a, T> Clone for QCellRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for QCellRef<'a, T> {}
+
+impl<'a, T> QCellRef<'a, T> {
+    /// Get the ID of the owner of the referenced cell
+    #[inline]
+    pub fn owner_id(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    /// Borrow the cell immutably.  Panics if `owner` is not the
+    /// owner of the cell.
+    #[inline]
+    pub fn ro<'b>(self, owner: &'b QCellOwner) -> &'b T
+    where
+        'a: 'b,
+    {
+        if !self.owner.matches(owner.id()) {
+            bad_owner_panic();
+        }
+        // Safety: Owned (owner ID checked above), and owner borrowed for 'b
+        unsafe { sound::deref_shared(&self.cell.value) }
+    }
+
+    /// Borrow the cell mutably.  Panics if `owner` is not the owner
+    /// of the cell.
+    #[inline]
+    pub fn rw<'b>(self, owner: &'b mut QCellOwner) -> &'b mut T
+    where
+        'a: 'b,
+    {
+        if !self.owner.matches(owner.id()) {
+            bad_owner_panic();
+        }
+        // Safety: Owned (owner ID checked above), and owner mutably borrowed for 'b
+        unsafe { sound::deref_unique(&self.cell.value) }
+    }
+}
+
+macro_rules! owned_cells_access {
+    ($owner:ty, $name:literal) => {
+        impl $owner {
+            #[doc = concat!("Borrow the contents of a cell of an [`OwnedCells`] container immutably.  Panics if the container is not owned by this `", $name, "`, or if `index` is out of range.")]
+            ///
+            /// [`OwnedCells`]: struct.OwnedCells.html
+            #[inline]
+            pub fn ro_at<'a, T>(&'a self, cells: &'a OwnedCells<T>, index: usize) -> &'a T {
+                let cell = cells.get(self.id(), index);
+                // Safety: Owned (owner ID checked by `get`), and owner borrowed for 'a
+                unsafe { sound::deref_shared(&cell.value) }
+            }
+
+            #[doc = concat!("Borrow the contents of a cell of an [`OwnedCells`] container mutably.  Panics if the container is not owned by this `", $name, "`, or if `index` is out of range.")]
+            ///
+            /// [`OwnedCells`]: struct.OwnedCells.html
+            #[inline]
+            pub fn rw_at<'a, T>(&'a mut self, cells: &'a OwnedCells<T>, index: usize) -> &'a mut T {
+                let cell = cells.get(self.id(), index);
+                // Safety: Owned (owner ID checked by `get`), and owner mutably borrowed for 'a
+                unsafe { sound::deref_unique(&cell.value) }
+            }
+
+            /// Borrow the contents of two cells of an [`OwnedCells`]
+            /// container mutably.  Panics if the two indices are the
+            /// same, or for the same reasons as `rw_at`.
+            ///
+            /// [`OwnedCells`]: struct.OwnedCells.html
+            #[inline]
+            pub fn rw2_at<'a, T>(
+                &'a mut self,
+                cells: &'a OwnedCells<T>,
+                index1: usize,
+                index2: usize,
+            ) -> (&'a mut T, &'a mut T) {
+                if index1 == index2 {
+                    not_distinct_panic();
+                }
+                let id = self.id();
+                let (c1, c2) = (cells.get(id, index1), cells.get(id, index2));
+                // Safety: Owned (owner ID checked by `get`), Distinct, and owner mutably borrowed
+                unsafe { sound::deref_unique2(&c1.value, &c2.value) }
+            }
+
+            /// Iterate over the contents of all the cells of an
+            /// [`OwnedCells`] container immutably.  Panics if the
+            /// container is not owned by this owner.
+            ///
+            /// [`OwnedCells`]: struct.OwnedCells.html
+            pub fn ro_iter<'a, T>(
+                &'a self,
+                cells: &'a OwnedCells<T>,
+            ) -> impl Iterator<Item = &'a T> + 'a {
+                if !cells.owner.matches(self.id()) {
+                    bad_owner_panic();
+                }
+                // Safety: Owned (owner ID checked above), and owner borrowed for 'a
+                cells.cells.iter().map(|c| unsafe { sound::deref_shared(&c.value) })
+            }
+
+            /// Iterate over the contents of all the cells of an
+            /// [`OwnedCells`] container mutably.  Panics if the
+            /// container is not owned by this owner.
+            ///
+            /// [`OwnedCells`]: struct.OwnedCells.html
+            pub fn rw_iter<'a, T>(
+                &'a mut self,
+                cells: &'a OwnedCells<T>,
+            ) -> impl Iterator<Item = &'a mut T> + 'a {
+                if !cells.owner.matches(self.id()) {
+                    bad_owner_panic();
+                }
+                // Safety: Owned (owner ID checked above), Distinct since each
+                // element is yielded only once, and owner mutably borrowed
+                cells.cells.iter().map(|c| unsafe { sound::deref_unique(&c.value) })
+            }
+        }
+    };
+}
+
+owned_cells_access!(QCellOwner, "QCellOwner");
+owned_cells_access!(QCellOwnerSeq, "QCellOwnerSeq");
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnedCells, QCellNoId};
+    use crate::{QCellOwner, QCellOwnerSeq};
+
+    #[test]
+    fn qcell_noid_const_new() {
+        static CELL: QCellNoId<u32> = QCellNoId::new(1);
+        const fn pair() -> (QCellNoId<u32>, QCellNoId<u32>) {
+            (QCellNoId::new(2), QCellNoId::new(3))
+        }
+        let (a, b) = pair();
+        assert_eq!(a.into_inner() + b.into_inner(), 5);
+        assert_eq!(std::mem::size_of_val(&CELL), 4);
+    }
+
+    #[test]
+    fn owned_cells() {
+        let mut owner = QCellOwner::new();
+        let mut cells = OwnedCells::with_capacity(&owner, 4);
+        assert!(cells.is_empty());
+        cells.push(1u32);
+        cells.extend(vec![2, 3, 4]);
+        assert_eq!(cells.len(), 4);
+        assert!(cells.owner_id() == owner.id());
+        *owner.rw_at(&cells, 1) += 10;
+        let (a, b) = owner.rw2_at(&cells, 3, 0);
+        *a += 100;
+        *b += 1000;
+        assert_eq!(
+            owner.ro_iter(&cells).copied().collect::<Vec<_>>(),
+            [1001, 12, 3, 104]
+        );
+        owner.rw_iter(&cells).for_each(|v| *v *= 2);
+        let r = cells.cell_ref(2);
+        *r.rw(&mut owner) += 1;
+        assert_eq!(*r.ro(&owner), 7);
+        *cells.get_mut(2) = 0;
+        assert_eq!(cells.into_vec(), [2002, 24, 0, 208]);
+    }
+
+    #[test]
+    fn owned_cells_seq() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let mut cells = OwnedCells::new(owner.id());
+        cells.extend(0..3u8);
+        *owner.rw_at(&cells, 2) = 9;
+        assert_eq!(*owner.ro_at(&cells, 2), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "incorrect owner")]
+    fn owned_cells_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let mut cells = OwnedCells::new(&owner1);
+        cells.push(1);
+        *owner2.rw_at(&cells, 0) += 1; // Panic here
+    }
+
+    #[test]
+    #[should_panic(expected = "incorrect owner")]
+    fn owned_cells_wrong_owner_ref() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let mut cells = OwnedCells::new(&owner1);
+        cells.push(1);
+        let _ = cells.cell_ref(0).ro(&owner2); // Panic here
+    }
+
+    #[test]
+    #[should_panic(expected = "twice")]
+    fn owned_cells_rw2_same() {
+        let mut owner = QCellOwner::new();
+        let mut cells = OwnedCells::new(&owner);
+        cells.extend(0..3);
+        let _ = owner.rw2_at(&cells, 1, 1); // Panic here
+    }
+
+    #[test]
+    fn owned_cells_size() {
+        use core::mem::size_of;
+        assert_eq!(size_of::<QCellNoId<u8>>(), 1);
+        assert_eq!(size_of::<QCellNoId<u32>>(), size_of::<u32>());
+        assert_eq!(size_of::<[QCellNoId<u16>; 100]>(), size_of::<[u16; 100]>());
+    }
+}
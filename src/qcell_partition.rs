@@ -0,0 +1,392 @@
+// Splitting a `QCellOwner` into sub-owners for disjoint sets of cells,
+// so that fork-join code can mutate the sets from different threads.
+//
+// Soundness depends on three things together:
+//
+// - The `&mut QCellOwner` is borrowed for `'a`, the lifetime of the
+//   `Partition` and of every `PartOwner`.  So whilst any part exists,
+//   no other access can be made through the owner, and no other owner
+//   has the ID of these cells.
+//
+// - Every cell was checked to have this owner's ID when partitioning,
+//   and no cell appears in two different sets.  The check is by
+//   address, and the addresses stay valid since each part holds an
+//   `Arc` for every cell in its set.  Two live `QCell` instances can't
+//   share an address, since a `QCell` is never zero-sized.
+//
+// - Each access through a `PartOwner` checks that the cell is in its
+//   own set.  `ro` and `rw` then give the same guarantees for that set
+//   as `QCellOwner::ro` and `QCellOwner::rw` give for all cells, since
+//   the part is borrowed in the same way.
+//
+// So no two parts can reach the same cell, and nothing else can reach
+// any of them until `'a` ends, which means that parts can be sent to
+// different threads and used at the same time.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::{panic_messages, sound, QCell, QCellOwner};
+
+/// Error from [`QCellOwner::partition`] and
+/// [`QCellOwner::with_partition`].
+///
+/// [`QCellOwner::partition`]: struct.QCellOwner.html#method.partition
+/// [`QCellOwner::with_partition`]: struct.QCellOwner.html#method.with_partition
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PartitionError {
+    /// The same cell appears in two different sets, given by index
+    Overlap {
+        /// Index of the lower of the two sets
+        first: usize,
+        /// Index of the higher of the two sets
+        second: usize,
+    },
+    /// A cell does not belong to the owner being partitioned
+    ForeignCell {
+        /// Index of the set containing the cell
+        set: usize,
+        /// Position of the cell within that set
+        position: usize,
+    },
+}
+
+impl fmt::Display for PartitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionError::Overlap { first, second } => {
+                write!(f, "cell sets #{} and #{} overlap", first, second)
+            }
+            PartitionError::ForeignCell { set, position } => write!(
+                f,
+                "cell #{} of set #{} belongs to another owner",
+                position, set
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PartitionError {}
+
+/// A [`QCellOwner`] split into [`PartOwner`] handles for disjoint sets
+/// of cells.  Created by [`QCellOwner::partition`].  The owner stays
+/// mutably borrowed for as long as the partition or any of its parts
+/// exist.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`PartOwner`]: struct.PartOwner.html
+/// [`QCellOwner::partition`]: struct.QCellOwner.html#method.partition
+pub struct Partition<'a, T> {
+    parts: Vec<PartOwner<'a, T>>,
+}
+
+impl<'a, T> Partition<'a, T> {
+    /// Get the number of parts, which is the number of cell sets
+    /// passed to [`QCellOwner::partition`]
+    ///
+    /// [`QCellOwner::partition`]: struct.QCellOwner.html#method.partition
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Test whether there are no parts
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Get the parts, in the same order as the cell sets.  Each part
+    /// is `Send` (for `T: Send + Sync`), so can be moved to its own
+    /// thread.
+    #[inline]
+    pub fn into_parts(self) -> Vec<PartOwner<'a, T>> {
+        self.parts
+    }
+}
+
+/// Borrowing-owner of one set of cells from a [`Partition`].  It can
+/// only access the cells in its own set, and panics if given any other
+/// cell.
+///
+/// [`Partition`]: struct.Partition.html
+pub struct PartOwner<'a, T> {
+    index: usize,
+    // Keeps the cells alive, so that no other cell can take one of
+    // their addresses whilst this part exists
+    cells: Vec<Arc<QCell<T>>>,
+    members: HashSet<usize>,
+    owner: PhantomData<&'a mut QCellOwner>,
+}
+
+#[cold]
+#[inline(never)]
+fn not_member_panic(part: usize) -> ! {
+    panic!("{}: part #{}", panic_messages::QCELL_PART_NOT_MEMBER, part);
+}
+
+#[inline]
+fn address<T>(qc: &QCell<T>) -> usize {
+    qc as *const QCell<T> as usize
+}
+
+impl<'a, T> PartOwner<'a, T> {
+    /// Get the index of this part's cell set
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the cells of this part's set, in the order given, and
+    /// without any duplicates removed
+    #[inline]
+    pub fn cells(&self) -> &[Arc<QCell<T>>] {
+        &self.cells
+    }
+
+    /// Test whether a cell is in this part's set
+    #[inline]
+    pub fn contains(&self, qc: &QCell<T>) -> bool {
+        self.members.contains(&address(qc))
+    }
+
+    #[inline]
+    fn check(&self, qc: &QCell<T>) {
+        if !self.contains(qc) {
+            not_member_panic(self.index);
+        }
+    }
+
+    /// Borrow contents of a [`QCell`] immutably (read-only).  Panics
+    /// if the cell is not in this part's set.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn ro<'b>(&'b self, qc: &'b QCell<T>) -> &'b T {
+        self.check(qc);
+        // Safety: Owned (membership checked above, owner ID checked by
+        // `partition`), and part borrowed for 'b
+        unsafe { sound::deref_shared(&qc.value) }
+    }
+
+    /// Borrow contents of a [`QCell`] mutably (read-write).  Panics if
+    /// the cell is not in this part's set.
+    ///
+    /// [`QCell`]: struct.QCell.html
+    #[inline]
+    pub fn rw<'b>(&'b mut self, qc: &'b QCell<T>) -> &'b mut T {
+        self.check(qc);
+        // Safety: Owned (membership checked above, owner ID checked by
+        // `partition`), and part mutably borrowed for 'b
+        unsafe { sound::deref_unique(&qc.value) }
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl QCellOwner {
+    /// Split this owner into parts which each own one of the given
+    /// sets of cells, for example to let worker threads mutate
+    /// disjoint sets at the same time.
+    ///
+    /// Every cell must belong to this owner, and no cell may appear
+    /// in more than one set, otherwise an error is returned.  A cell
+    /// may appear more than once in the same set.  The owner is
+    /// mutably borrowed until the [`Partition`] and all its parts are
+    /// dropped.  See also [`QCellOwner::with_partition`].
+    ///
+    /// ```
+    ///# use qcell::QCellOwner;
+    ///# use std::sync::Arc;
+    /// let mut owner = QCellOwner::new();
+    /// let cells: Vec<_> = (0..4).map(|i| Arc::new(owner.cell(i))).collect();
+    /// let sets = vec![cells[..2].to_vec(), cells[2..].to_vec()];
+    /// let mut parts = owner.partition(sets).unwrap().into_parts();
+    /// let (left, right) = parts.split_at_mut(1);
+    /// std::mem::swap(left[0].rw(&cells[0]), right[0].rw(&cells[3]));
+    /// drop(parts);
+    /// assert_eq!(owner.get(&cells[0]), 3);
+    /// ```
+    ///
+    /// [`Partition`]: struct.Partition.html
+    /// [`QCellOwner::with_partition`]: struct.QCellOwner.html#method.with_partition
+    pub fn partition<T>(
+        &mut self,
+        cell_sets: Vec<Vec<Arc<QCell<T>>>>,
+    ) -> Result<Partition<'_, T>, PartitionError> {
+        let id = self.id();
+        let mut all = Vec::new();
+        for (set, cells) in cell_sets.iter().enumerate() {
+            for (position, cell) in cells.iter().enumerate() {
+                if !cell.owner.matches(id) {
+                    return Err(PartitionError::ForeignCell { set, position });
+                }
+                all.push((address(cell), set));
+            }
+        }
+        // After sorting, any cell in two sets has adjacent entries
+        all.sort_unstable();
+        for pair in all.windows(2) {
+            if pair[0].0 == pair[1].0 && pair[0].1 != pair[1].1 {
+                return Err(PartitionError::Overlap {
+                    first: pair[0].1,
+                    second: pair[1].1,
+                });
+            }
+        }
+        let parts = cell_sets
+            .into_iter()
+            .enumerate()
+            .map(|(index, cells)| PartOwner {
+                index,
+                members: cells.iter().map(|cell| address(cell)).collect(),
+                cells,
+                owner: PhantomData,
+            })
+            .collect();
+        Ok(Partition { parts })
+    }
+
+    /// Split this owner into parts as for [`QCellOwner::partition`],
+    /// and pass the parts to `f`.  The owner is usable again once `f`
+    /// returns.
+    ///
+    #[cfg_attr(
+        feature = "scoped-threads",
+        doc = "
+ ```
+ # use qcell::QCellOwner;
+ # use std::sync::Arc;
+ let mut owner = QCellOwner::new();
+ let cells: Vec<_> = (0..8).map(|i| Arc::new(owner.cell(i))).collect();
+ let sets = cells.chunks(2).map(|c| c.to_vec()).collect();
+ owner
+     .with_partition(sets, |parts| {
+         std::thread::scope(|s| {
+             for mut part in parts {
+                 s.spawn(move || {
+                     for cell in part.cells().to_vec() {
+                         *part.rw(&cell) *= 10;
+                     }
+                 });
+             }
+         })
+     })
+     .unwrap();
+ assert_eq!(owner.get(&cells[7]), 70);
+ ```
+"
+    )]
+    ///
+    /// [`QCellOwner::partition`]: struct.QCellOwner.html#method.partition
+    pub fn with_partition<T, R>(
+        &mut self,
+        cell_sets: Vec<Vec<Arc<QCell<T>>>>,
+        f: impl FnOnce(Vec<PartOwner<'_, T>>) -> R,
+    ) -> Result<R, PartitionError> {
+        Ok(f(self.partition(cell_sets)?.into_parts()))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::PartitionError;
+    use crate::{QCell, QCellOwner};
+    use std::sync::Arc;
+
+    fn cells(owner: &QCellOwner, count: usize) -> Vec<Arc<QCell<usize>>> {
+        (0..count).map(|i| Arc::new(owner.cell(i))).collect()
+    }
+
+    // Uses `std::thread::scope`, so needs Rust 1.63
+    #[cfg(feature = "scoped-threads")]
+    #[test]
+    fn partition_threads_mutate_disjoint_sets() {
+        let mut owner = QCellOwner::new();
+        let all = cells(&owner, 40);
+        // Interleave the sets, so that neighbouring cells are in
+        // different parts
+        let sets = (0..4)
+            .map(|p| all.iter().skip(p).step_by(4).cloned().collect())
+            .collect();
+        let sums = owner
+            .with_partition(sets, |parts| {
+                std::thread::scope(|s| {
+                    let handles: Vec<_> = parts
+                        .into_iter()
+                        .map(|mut part| {
+                            s.spawn(move || {
+                                let mut sum = 0;
+                                for _ in 0..100 {
+                                    for cell in part.cells().to_vec() {
+                                        *part.rw(&cell) += 1;
+                                    }
+                                }
+                                for cell in part.cells() {
+                                    sum += *part.ro(cell);
+                                }
+                                (part.index(), sum)
+                            })
+                        })
+                        .collect();
+                    handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+                })
+            })
+            .unwrap();
+        for (index, sum) in sums {
+            let expected: usize = (index..40).step_by(4).map(|i| i + 100).sum();
+            assert_eq!(sum, expected);
+        }
+        for (i, cell) in all.iter().enumerate() {
+            assert_eq!(owner.get(cell), i + 100);
+        }
+    }
+
+    #[test]
+    fn partition_rejects_overlap() {
+        let mut owner = QCellOwner::new();
+        let all = cells(&owner, 4);
+        let sets = vec![
+            vec![all[0].clone(), all[1].clone()],
+            vec![all[2].clone()],
+            vec![all[3].clone(), all[1].clone()],
+        ];
+        let err = owner.partition(sets).err().unwrap();
+        assert_eq!(err, PartitionError::Overlap { first: 0, second: 2 });
+        assert_eq!(err.to_string(), "cell sets #0 and #2 overlap");
+
+        // Repeating a cell within one set is fine
+        let sets = vec![vec![all[0].clone(), all[0].clone()], vec![all[1].clone()]];
+        let mut parts = owner.partition(sets).unwrap().into_parts();
+        *parts[0].rw(&all[0]) += 10;
+        drop(parts);
+        assert_eq!(owner.get(&all[0]), 10);
+    }
+
+    #[test]
+    fn partition_rejects_foreign_cell() {
+        let mut owner = QCellOwner::new();
+        let other = QCellOwner::new();
+        let mine = cells(&owner, 2);
+        let theirs = cells(&other, 1);
+        let sets = vec![vec![mine[0].clone()], vec![mine[1].clone(), theirs[0].clone()]];
+        let err = owner.partition(sets).err().unwrap();
+        assert_eq!(err, PartitionError::ForeignCell { set: 1, position: 1 });
+        let result = owner.with_partition(vec![theirs], |_| unreachable!());
+        assert_eq!(result, Err(PartitionError::ForeignCell { set: 0, position: 0 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed through a part that doesn't contain it")]
+    fn partition_panics_outside_set() {
+        let mut owner = QCellOwner::new();
+        let all = cells(&owner, 2);
+        let sets = vec![vec![all[0].clone()], vec![all[1].clone()]];
+        let mut parts = owner.partition(sets).unwrap().into_parts();
+        assert!(parts[0].contains(&all[0]));
+        assert!(!parts[0].contains(&all[1]));
+        *parts[0].rw(&all[1]) += 1;
+    }
+}
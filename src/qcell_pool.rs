@@ -0,0 +1,236 @@
+use alloc::rc::{Rc, Weak};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::{QCell, QCellBorrow, QCellOwnerID};
+
+/// Pool of spare [`QCell`] instances all owned by one owner, for
+/// reusing values which are expensive to allocate, such as buffers.
+///
+/// [`CellPool::acquire`] hands out a [`PooledCell`], which derefs to
+/// the [`QCell`], and the cell goes back on the pool's free list when
+/// the handle is dropped.  The value is not touched on the way back,
+/// so the next caller to acquire the cell gets whatever it was left
+/// holding, for example a `Vec` with its capacity.  Only the handle
+/// can get at its cell to move it, so all the cells on the free list
+/// stay owned by the pool's owner.  The contents can only be reached
+/// through that owner as usual, so returning a cell from `Drop`
+/// without access to the owner is no problem.
+///
+/// The free list is shared between the pool and the handles through
+/// an `Rc`, so the pool and its handles are neither `Send` nor
+/// `Sync`.  If the pool is dropped first, cells returned afterwards
+/// are dropped instead.
+///
+/// To move a value out of a cell that is being retired into a pooled
+/// cell, see [`QCellOwner::recycle_into`].
+///
+/// ```
+///# use qcell::{CellPool, QCellOwner};
+/// let mut owner = QCellOwner::new();
+/// let pool = CellPool::<Vec<u8>>::new(&owner);
+///
+/// let buf = pool.acquire(&mut owner);
+/// owner.rw(&buf).extend_from_slice(b"first message");
+/// let capacity = owner.ro(&buf).capacity();
+/// drop(buf);
+///
+/// // The buffer comes back with its contents, so clear it for reuse
+/// let buf = pool.acquire(&mut owner);
+/// owner.rw(&buf).clear();
+/// assert_eq!(owner.ro(&buf).capacity(), capacity);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`PooledCell`]: struct.PooledCell.html
+/// [`CellPool::acquire`]: struct.CellPool.html#method.acquire
+/// [`QCellOwner::recycle_into`]: struct.QCellOwner.html#method.recycle_into
+pub struct CellPool<T> {
+    id: QCellOwnerID,
+    free: Rc<RefCell<Vec<QCell<T>>>>,
+}
+
+impl<T> CellPool<T> {
+    /// Create an empty pool of cells owned by the given owner
+    #[inline]
+    pub fn new(owner: impl Into<QCellOwnerID>) -> Self {
+        Self {
+            id: owner.into(),
+            free: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Get the ID of the owner of the cells in the pool
+    #[inline]
+    pub fn id(&self) -> QCellOwnerID {
+        self.id
+    }
+
+    /// Return the number of spare cells waiting to be reused
+    #[inline]
+    pub fn spare(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Take a spare cell from the pool, or create a new one holding
+    /// `T::default()` if there are none.  A reused cell holds the
+    /// value it was returned with.  The owner is checked against the
+    /// pool, so this panics if the cells are not owned by `owner`,
+    /// rather than at the first access to the cell.
+    pub fn acquire(&self, owner: &mut impl QCellBorrow) -> PooledCell<T>
+    where
+        T: Default,
+    {
+        let spare = self.free.borrow_mut().pop();
+        let cell = spare.unwrap_or_else(|| self.id.cell(T::default()));
+        owner.qcell_ro(&cell);
+        PooledCell {
+            cell: Some(cell),
+            free: Rc::downgrade(&self.free),
+        }
+    }
+}
+
+impl<T> fmt::Debug for CellPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CellPool")
+            .field("id", &self.id)
+            .field("spare", &self.spare())
+            .finish()
+    }
+}
+
+/// Handle to a [`QCell`] taken from a [`CellPool`], which returns
+/// the cell to the pool when dropped.
+///
+/// This derefs to the [`QCell`], so it can be passed to the owner's
+/// `ro` and `rw` calls like any other cell.
+///
+/// [`QCell`]: struct.QCell.html
+/// [`CellPool`]: struct.CellPool.html
+pub struct PooledCell<T> {
+    // Only `None` whilst being dropped or detached
+    cell: Option<QCell<T>>,
+    free: Weak<RefCell<Vec<QCell<T>>>>,
+}
+
+impl<T> PooledCell<T> {
+    /// Take the cell out of the pool for good, so that it won't be
+    /// returned
+    #[inline]
+    pub fn detach(mut self) -> QCell<T> {
+        // Leaves `None` for `Drop` to skip
+        self.cell.take().unwrap()
+    }
+}
+
+impl<T> Deref for PooledCell<T> {
+    type Target = QCell<T>;
+
+    #[inline]
+    fn deref(&self) -> &QCell<T> {
+        self.cell.as_ref().unwrap()
+    }
+}
+
+impl<T> Drop for PooledCell<T> {
+    fn drop(&mut self) {
+        // No other code runs whilst the free list is borrowed, so the
+        // `borrow_mut` can't clash with another one
+        if let (Some(cell), Some(free)) = (self.cell.take(), self.free.upgrade()) {
+            free.borrow_mut().push(cell);
+        }
+    }
+}
+
+impl<T> fmt::Debug for PooledCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledCell").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CellPool;
+    use crate::{QCellOwner, QCellOwnerLocal};
+
+    #[test]
+    fn pool_reuses_capacity() {
+        let mut owner = QCellOwner::new();
+        let pool = CellPool::<Vec<u8>>::new(&owner);
+        let buf = pool.acquire(&mut owner);
+        owner.rw(&buf).reserve_exact(4096);
+        let (ptr, capacity) = (owner.ro(&buf).as_ptr(), owner.ro(&buf).capacity());
+        drop(buf);
+        assert_eq!(pool.spare(), 1);
+
+        for round in 0..100 {
+            let buf = pool.acquire(&mut owner);
+            assert_eq!(pool.spare(), 0);
+            let data = owner.rw(&buf);
+            data.clear();
+            data.extend((0..4096).map(|i| (i + round) as u8));
+            assert_eq!((data.as_ptr(), data.capacity()), (ptr, capacity));
+        }
+        assert_eq!(pool.spare(), 1);
+    }
+
+    #[test]
+    fn pool_grows_when_empty() {
+        let mut owner = QCellOwnerLocal::new();
+        let pool = CellPool::<String>::new(&owner);
+        let a = pool.acquire(&mut owner);
+        let b = pool.acquire(&mut owner);
+        owner.rw(&a).push('a');
+        owner.rw(&b).push('b');
+        drop((a, b));
+        assert_eq!(pool.spare(), 2);
+
+        // Last in, first out
+        let b = pool.acquire(&mut owner);
+        assert_eq!(owner.ro(&b), "b");
+        let a = pool.acquire(&mut owner);
+        assert_eq!(owner.ro(&a), "a");
+        let fresh = pool.acquire(&mut owner);
+        assert_eq!(owner.ro(&fresh), "");
+    }
+
+    #[test]
+    fn pool_recycle_into() {
+        let mut owner = QCellOwner::new();
+        let pool = CellPool::new(&owner);
+        let retiring = owner.cell(Vec::<u32>::with_capacity(512));
+        let pooled = pool.acquire(&mut owner);
+        owner.recycle_into(&retiring, &pooled);
+        assert_eq!(owner.ro(&retiring).capacity(), 0);
+        assert_eq!(owner.ro(&pooled).capacity(), 512);
+        drop(pooled);
+        let reused = pool.acquire(&mut owner);
+        assert_eq!(owner.ro(&reused).capacity(), 512);
+    }
+
+    #[test]
+    fn pool_detach_and_pool_dropped() {
+        let mut owner = QCellOwner::new();
+        let pool = CellPool::new(&owner);
+        let kept = pool.acquire(&mut owner).detach();
+        owner.set(&kept, 5);
+        let orphan = pool.acquire(&mut owner);
+        assert_eq!(pool.spare(), 0);
+        drop(pool);
+        owner.set(&orphan, 6);
+        drop(orphan);
+        assert_eq!(owner.get(&kept), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pool_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let pool = CellPool::<u32>::new(&owner1);
+        let _cell = pool.acquire(&mut owner2);
+    }
+}
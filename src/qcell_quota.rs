@@ -0,0 +1,341 @@
+use alloc::sync::Arc;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{QCell, QCellOwnerID};
+
+/// Error returned when a [`QuotaOwnerID`] has already created as many
+/// cells as its quota allows.
+///
+/// [`QuotaOwnerID`]: struct.QuotaOwnerID.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaExceeded;
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cell creation quota exceeded")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for QuotaExceeded {}
+
+// State shared between the ID and the host's handles
+struct Quota {
+    count: AtomicUsize,
+    limit: AtomicUsize,
+}
+
+impl Quota {
+    // Count one more cell, if that stays within the limit.  The check
+    // and the increment are one atomic step, so concurrent creators
+    // can never take the count past the limit.
+    #[inline]
+    fn take(&self) -> Result<(), QuotaExceeded> {
+        self.count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                if count < self.limit.load(Ordering::Relaxed) {
+                    Some(count + 1)
+                } else {
+                    None
+                }
+            })
+            .map(|_| ())
+            .map_err(|_| QuotaExceeded)
+    }
+
+    #[inline]
+    fn remaining(&self) -> usize {
+        let count = self.count.load(Ordering::Relaxed);
+        self.limit.load(Ordering::Relaxed).saturating_sub(count)
+    }
+}
+
+/// A [`QCellOwnerID`] which can only create a limited number of
+/// cells.
+///
+/// This is for handing to code that shouldn't be trusted to create
+/// cells without bound, for example a third-party plugin creating
+/// cells in the host's arena.  It is created with
+/// [`QCellOwnerID::with_quota`].  The plugin gets the
+/// `QuotaOwnerID`, and the host keeps a [`QuotaHandle`] from
+/// [`QuotaOwnerID::handle`], which it uses to watch the count and to
+/// raise or lower the limit at runtime.  The underlying
+/// [`QCellOwnerID`] can't be obtained from a `QuotaOwnerID`, so the
+/// quota can't be bypassed.
+///
+/// The quota limits the number of cells created, not the number
+/// alive: dropping a cell does not give back its place in the quota.
+/// Only creation is checked.  The cells created are ordinary
+/// [`QCell`] instances, owned by the ID the quota was created from,
+/// and accessing them costs no more than any other [`QCell`].
+///
+/// The count is an atomic shared by all clones of the ID and all
+/// handles, so cells may be created from several threads at once.
+/// The quota is exact: however many threads race, no more cells are
+/// created than the limit allows.  If the host lowers the limit below
+/// the number of cells already created, all further creations fail,
+/// but the existing cells are unaffected.
+///
+/// ```
+///# use qcell::{QCellOwner, QuotaExceeded};
+/// let mut owner = QCellOwner::new();
+/// let plugin_id = owner.id().with_quota(2);
+/// let handle = plugin_id.handle();
+///
+/// // In the plugin
+/// let c1 = plugin_id.cell(1).unwrap();
+/// let c2 = plugin_id.cell(2).unwrap();
+/// assert_eq!(plugin_id.cell(3).err(), Some(QuotaExceeded));
+///
+/// // Back in the host, the cells work with the normal owner
+/// *owner.rw(&c1) += *owner.ro(&c2);
+/// assert_eq!(*owner.ro(&c1), 3);
+/// assert_eq!((handle.count(), handle.remaining()), (2, 0));
+/// handle.set_limit(3);
+/// assert!(plugin_id.cell(3).is_ok());
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwnerID`]: struct.QCellOwnerID.html
+/// [`QCellOwnerID::with_quota`]: struct.QCellOwnerID.html#method.with_quota
+/// [`QuotaHandle`]: struct.QuotaHandle.html
+/// [`QuotaOwnerID::handle`]: #method.handle
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Clone)]
+pub struct QuotaOwnerID {
+    id: QCellOwnerID,
+    quota: Arc<Quota>,
+}
+
+impl QuotaOwnerID {
+    /// Create a new cell owned by the underlying owner ID, or return
+    /// [`QuotaExceeded`] if the quota is used up, in which case
+    /// `value` is dropped.
+    ///
+    /// [`QuotaExceeded`]: struct.QuotaExceeded.html
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> Result<QCell<T>, QuotaExceeded> {
+        self.quota.take()?;
+        Ok(self.id.cell(value))
+    }
+
+    /// Create a new cell owned by the underlying owner ID, or give
+    /// `value` back if the quota is used up
+    #[inline]
+    pub fn try_cell<T>(&self, value: T) -> Result<QCell<T>, T> {
+        match self.quota.take() {
+            Ok(()) => Ok(self.id.cell(value)),
+            Err(QuotaExceeded) => Err(value),
+        }
+    }
+
+    /// Get the number of cells that may still be created.  Other
+    /// threads may use up the quota in the meantime, so this is only
+    /// a hint.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.quota.remaining()
+    }
+
+    /// Get a handle to watch and adjust the quota of this ID and all
+    /// its clones
+    pub fn handle(&self) -> QuotaHandle {
+        QuotaHandle {
+            quota: self.quota.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for QuotaOwnerID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaOwnerID")
+            .field("id", &self.id)
+            .field("count", &self.quota.count.load(Ordering::Relaxed))
+            .field("limit", &self.quota.limit.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Host-side handle to the quota of a [`QuotaOwnerID`].
+///
+/// This allows watching how many cells have been created, and
+/// changing the limit at runtime.  It can't create cells itself.
+///
+/// [`QuotaOwnerID`]: struct.QuotaOwnerID.html
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Clone)]
+pub struct QuotaHandle {
+    quota: Arc<Quota>,
+}
+
+impl QuotaHandle {
+    /// Get the number of cells created so far
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.quota.count.load(Ordering::Relaxed)
+    }
+
+    /// Get the current limit
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.quota.limit.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of cells that may still be created, which is
+    /// zero if the limit has been lowered below the count
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.quota.remaining()
+    }
+
+    /// Change the limit.  This takes effect for all creations that
+    /// start after this call.  Lowering the limit below the count
+    /// makes all further creations fail, but does not affect the
+    /// cells already created.
+    #[inline]
+    pub fn set_limit(&self, max: usize) {
+        self.quota.limit.store(max, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for QuotaHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotaHandle")
+            .field("count", &self.count())
+            .field("limit", &self.limit())
+            .finish()
+    }
+}
+
+impl QCellOwnerID {
+    /// Wrap this owner ID so that it can only create `max` cells.
+    /// See [`QuotaOwnerID`].
+    ///
+    /// [`QuotaOwnerID`]: struct.QuotaOwnerID.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn with_quota(self, max: usize) -> QuotaOwnerID {
+        QuotaOwnerID {
+            id: self,
+            quota: Arc::new(Quota {
+                count: AtomicUsize::new(0),
+                limit: AtomicUsize::new(max),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuotaExceeded;
+    use crate::{QCellOwner, QCellOwnerSeq};
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn quota_basic() {
+        let owner = QCellOwner::new();
+        let id = owner.id().with_quota(3);
+        let handle = id.handle();
+        assert_eq!((handle.count(), handle.limit(), id.remaining()), (0, 3, 3));
+        let cells: Vec<_> = (0..3).map(|i| id.cell(i).unwrap()).collect();
+        assert_eq!(id.cell(3).err(), Some(QuotaExceeded));
+        assert_eq!(id.try_cell(String::from("x")).err().unwrap(), "x");
+        assert_eq!((handle.count(), id.remaining()), (3, 0));
+        // Dropping cells doesn't give back quota
+        drop(cells);
+        assert!(id.cell(4).is_err());
+        assert_eq!(QuotaExceeded.to_string(), "cell creation quota exceeded");
+    }
+
+    #[test]
+    fn quota_set_limit() {
+        let mut owner = QCellOwner::new();
+        let id = owner.id().with_quota(2);
+        let handle = id.handle();
+        let c1 = id.cell(1).unwrap();
+        assert_eq!(handle.remaining(), 1);
+
+        // Raising the limit allows more creations
+        handle.set_limit(4);
+        let c2 = id.cell(2).unwrap();
+        let c3 = id.try_cell(3).unwrap();
+        assert_eq!((handle.count(), handle.remaining()), (3, 1));
+
+        // Lowering it below the count saturates, and existing cells
+        // still work
+        handle.set_limit(1);
+        assert_eq!((handle.count(), handle.limit()), (3, 1));
+        assert_eq!((handle.remaining(), id.remaining()), (0, 0));
+        assert!(id.cell(4).is_err());
+        *owner.rw(&c1) += *owner.ro(&c2) + *owner.ro(&c3);
+        assert_eq!(*owner.ro(&c1), 6);
+
+        // Raising it again only allows creations above the count
+        handle.set_limit(4);
+        assert!(id.clone().cell(4).is_ok());
+        assert!(id.cell(5).is_err());
+        assert_eq!(handle.count(), 4);
+    }
+
+    #[test]
+    fn quota_threads() {
+        const THREADS: usize = 8;
+        const LIMIT: usize = 2500;
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let id = owner.id().with_quota(LIMIT);
+        let handle = id.handle();
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let threads: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let id = id.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    (0..1000)
+                        .filter_map(|i| id.cell(t * 1000 + i).ok())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut cells: Vec<_> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        assert_eq!(cells.len(), LIMIT);
+        assert_eq!((handle.count(), handle.remaining()), (LIMIT, 0));
+        for c in &cells {
+            *owner.rw(c) += 1;
+        }
+        let mut values: Vec<_> = cells.iter_mut().map(|c| *c.get_mut()).collect();
+        values.sort_unstable();
+        values.dedup();
+        assert_eq!(values.len(), LIMIT);
+    }
+
+    #[test]
+    fn quota_threads_changing_limit() {
+        let owner = QCellOwner::new();
+        let id = owner.id().with_quota(0);
+        let handle = id.handle();
+        let creator = std::thread::spawn(move || {
+            let mut created = 0;
+            while created < 1000 {
+                match id.cell(()) {
+                    Ok(_) => created += 1,
+                    Err(_) => std::thread::yield_now(),
+                }
+            }
+            created
+        });
+        for limit in (0..=1000).step_by(10) {
+            handle.set_limit(limit);
+            while handle.count() < limit {
+                std::thread::yield_now();
+            }
+            assert_eq!(handle.count(), limit);
+        }
+        assert_eq!(creator.join().unwrap(), 1000);
+        assert_eq!(handle.count(), 1000);
+    }
+}
@@ -0,0 +1,418 @@
+use core::cell::UnsafeCell;
+use core::{mem, ptr};
+#[cfg(debug_assertions)]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::panic_messages;
+use crate::sound;
+
+// Set once the one and only `QCellOwnerSingle` has been created
+static SINGLE_OWNER_CREATED: AtomicBool = AtomicBool::new(false);
+
+// In debug builds, each owner gets an ID which is stored in its
+// cells, to catch misuse after `reset_for_tests`
+#[cfg(debug_assertions)]
+static SINGLE_OWNER_DEBUG_ID: AtomicUsize = AtomicUsize::new(1);
+
+// Check that two cells passed to `rw2` or `rw3` are different.  In
+// release builds a cell of a zero-sized type is itself zero-sized, and
+// two different such cells may have the same address.  Borrowing no
+// bytes can't alias anything, so such cells always count as distinct.
+#[inline(always)]
+fn distinct<T: ?Sized, U: ?Sized>(qc1: &QCellSingle<T>, qc2: &QCellSingle<U>) -> bool {
+    mem::size_of_val(qc1) == 0
+        || mem::size_of_val(qc2) == 0
+        || !ptr::eq(qc1 as *const _ as *const (), qc2 as *const _ as *const ())
+}
+
+/// Cell whose contents is owned (for borrowing purposes) by the one
+/// and only [`QCellOwnerSingle`].
+///
+/// This is like a [`QCell`], but stores no owner ID in release builds,
+/// so it has no space overhead over the contained value, and
+/// borrowing through the owner requires no runtime check.  In debug
+/// builds an owner ID is stored and checked with `debug_assert!` to
+/// catch misuse during development.  See also [crate
+/// documentation](index.html).
+///
+/// Because of that debug-only owner ID, the size and layout of this
+/// type differ between debug and release builds.  In release builds
+/// it has the same size as `T`, and so is zero-sized if `T` is.
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwnerSingle`]: struct.QCellOwnerSingle.html
+pub struct QCellSingle<T: ?Sized> {
+    #[cfg(debug_assertions)]
+    owner: usize,
+    value: UnsafeCell<T>,
+}
+
+// Same reasoning as for `QCell`: Sync requires T: Send + Sync because
+// a &QCellSingle<T> plus the owner gives a &mut T on another thread
+unsafe impl<T: Send + Sync + ?Sized> Sync for QCellSingle<T> {}
+
+impl<T> QCellSingle<T> {
+    /// Create a new [`QCellSingle`] owned for borrowing purposes by
+    /// the given [`QCellOwnerSingle`]
+    ///
+    /// [`QCellSingle`]: struct.QCellSingle.html
+    /// [`QCellOwnerSingle`]: struct.QCellOwnerSingle.html
+    #[inline]
+    pub fn new(owner: &QCellOwnerSingle, value: T) -> QCellSingle<T> {
+        owner.cell(value)
+    }
+
+    /// Destroy the cell and return the contained value
+    ///
+    /// Safety: Since this consumes the cell, there can be no other
+    /// references to the cell or the data at this point.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> QCellSingle<T> {
+    /// Convenience method to borrow a cell immutably.  Equivalent to
+    /// [`QCellOwnerSingle::ro`].
+    ///
+    /// [`QCellOwnerSingle::ro`]: struct.QCellOwnerSingle.html#method.ro
+    #[inline]
+    pub fn ro<'a>(&'a self, owner: &'a QCellOwnerSingle) -> &'a T {
+        owner.ro(self)
+    }
+
+    /// Convenience method to borrow a cell mutably.  Equivalent to
+    /// [`QCellOwnerSingle::rw`].
+    ///
+    /// [`QCellOwnerSingle::rw`]: struct.QCellOwnerSingle.html#method.rw
+    #[inline]
+    pub fn rw<'a>(&'a self, owner: &'a mut QCellOwnerSingle) -> &'a mut T {
+        owner.rw(self)
+    }
+
+    /// Returns a mutable reference to the underlying data
+    ///
+    /// Note that this is only useful at the beginning-of-life or
+    /// end-of-life of the cell when you have exclusive access to it.
+    /// Normally you'd use [`QCellSingle::rw`] or
+    /// [`QCellOwnerSingle::rw`] to get a mutable reference to the
+    /// contents of the cell.
+    ///
+    /// Safety: This call borrows `QCellSingle` mutably which
+    /// guarantees that we possess the only reference.  This means
+    /// that there can be no active borrows of other forms, even ones
+    /// obtained using an immutable reference.
+    ///
+    /// [`QCellSingle::rw`]: struct.QCellSingle.html#method.rw
+    /// [`QCellOwnerSingle::rw`]: struct.QCellOwnerSingle.html#method.rw
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Borrowing-owner of zero or more [`QCellSingle`] instances, of
+/// which only one may ever be created in the process.
+///
+/// This has the same API as [`QCellOwner`], but since there is only
+/// ever one owner, there's no need to check at runtime that a cell
+/// belongs to it.
+///
+/// There is one owner for the whole process, not one per crate, so
+/// only one user in the process can have it.  If two crates in the
+/// same program both create a `QCellOwnerSingle`, whichever comes
+/// second gets a panic from [`QCellOwnerSingle::new`], or `None` from
+/// [`QCellOwnerSingle::try_new`].  So this suits an application, or a
+/// library which documents that it takes the process's one owner, and
+/// so can't be used alongside any other such library.  A library
+/// which has to coexist with other users should use a [`QCellOwner`],
+/// or a [`TCell`] with a marker type private to the library.
+///
+/// # Safety
+///
+/// Soundness rests on [`QCellOwnerSingle::new`] refusing to create a
+/// second owner.  This is checked using a process-wide flag, and
+/// stays that way even after the owner is dropped.  The only way
+/// around this is the unsafe [`QCellOwnerSingle::reset_for_tests`]
+/// call, which is intended only for test code.  In debug builds,
+/// using a cell with the wrong owner after a reset is detected and
+/// causes a panic, but in release builds it is not checked.
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellSingle`]: struct.QCellSingle.html
+/// [`TCell`]: struct.TCell.html
+/// [`QCellOwnerSingle::new`]: struct.QCellOwnerSingle.html#method.new
+/// [`QCellOwnerSingle::try_new`]: struct.QCellOwnerSingle.html#method.try_new
+/// [`QCellOwnerSingle::reset_for_tests`]: struct.QCellOwnerSingle.html#method.reset_for_tests
+pub struct QCellOwnerSingle {
+    #[cfg(debug_assertions)]
+    id: usize,
+}
+
+impl Default for QCellOwnerSingle {
+    fn default() -> Self {
+        QCellOwnerSingle::new()
+    }
+}
+
+impl QCellOwnerSingle {
+    /// Create the one and only [`QCellOwnerSingle`].  Panics if one
+    /// has already been created in this process, by any crate, even
+    /// if it has since been dropped.  This can't be a `const fn`, since it
+    /// records the creation in a global flag.
+    ///
+    /// [`QCellOwnerSingle`]: struct.QCellOwnerSingle.html
+    #[inline]
+    pub fn new() -> Self {
+        Self::try_new().expect(panic_messages::QCELL_SINGLE_SECOND_OWNER)
+    }
+
+    /// Same as [`QCellOwnerSingle::new`], except that it returns
+    /// `None` if the owner has already been created.
+    ///
+    /// [`QCellOwnerSingle::new`]: struct.QCellOwnerSingle.html#method.new
+    #[inline]
+    pub fn try_new() -> Option<Self> {
+        if SINGLE_OWNER_CREATED.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some(Self {
+            #[cfg(debug_assertions)]
+            id: SINGLE_OWNER_DEBUG_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Allow another [`QCellOwnerSingle`] to be created.  This is
+    /// intended for test code which needs a fresh owner for each
+    /// test.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that no cell created by a previous
+    /// owner is ever accessed through the new owner, and that no two
+    /// owners are used at the same time to access the same cell.  In
+    /// debug builds such misuse causes a panic, but in release builds
+    /// it is undefined behaviour.
+    ///
+    /// [`QCellOwnerSingle`]: struct.QCellOwnerSingle.html
+    pub unsafe fn reset_for_tests() {
+        SINGLE_OWNER_CREATED.store(false, Ordering::Release);
+    }
+
+    /// Create a new cell owned by this owner instance.  See also
+    /// [`QCellSingle::new`].
+    ///
+    /// [`QCellSingle::new`]: struct.QCellSingle.html#method.new
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> QCellSingle<T> {
+        QCellSingle {
+            #[cfg(debug_assertions)]
+            owner: self.id,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    #[inline(always)]
+    fn debug_check<T: ?Sized>(&self, _qc: &QCellSingle<T>) {
+        #[cfg(debug_assertions)]
+        debug_assert!(
+            _qc.owner == self.id,
+            "{}",
+            panic_messages::QCELL_SINGLE_WRONG_OWNER
+        );
+    }
+
+    /// Borrow contents of a [`QCellSingle`] immutably (read-only).
+    /// Many [`QCellSingle`] instances can be borrowed immutably at
+    /// the same time from the same owner.
+    ///
+    /// [`QCellSingle`]: struct.QCellSingle.html
+    #[inline]
+    pub fn ro<'a, T: ?Sized>(&'a self, qc: &'a QCellSingle<T>) -> &'a T {
+        self.debug_check(qc);
+        // Safety: Owned (singleton owner), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&qc.value) }
+    }
+
+    /// Borrow contents of a [`QCellSingle`] mutably (read-write).
+    /// Only one [`QCellSingle`] at a time can be borrowed from the
+    /// owner using this call.  The returned reference must go out of
+    /// scope before another can be borrowed.
+    ///
+    /// [`QCellSingle`]: struct.QCellSingle.html
+    #[inline]
+    pub fn rw<'a, T: ?Sized>(&'a mut self, qc: &'a QCellSingle<T>) -> &'a mut T {
+        self.debug_check(qc);
+        // Safety: Owned (singleton owner), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&qc.value) }
+    }
+
+    /// Borrow contents of two [`QCellSingle`] instances mutably.
+    /// Panics if the two [`QCellSingle`] instances point to the same
+    /// memory.  Cells which are zero-sized, as a cell of a zero-sized
+    /// type is in release builds, are never treated as the same.
+    ///
+    /// [`QCellSingle`]: struct.QCellSingle.html
+    #[inline]
+    pub fn rw2<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        qc1: &'a QCellSingle<T>,
+        qc2: &'a QCellSingle<U>,
+    ) -> (&'a mut T, &'a mut U) {
+        self.debug_check(qc1);
+        self.debug_check(qc2);
+        assert!(
+            distinct(qc1, qc2),
+            "{}",
+            panic_messages::QCELL_SINGLE_BORROWED_TWICE
+        );
+        // Safety: Owned (singleton owner), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&qc1.value, &qc2.value) }
+    }
+
+    /// Borrow contents of three [`QCellSingle`] instances mutably.
+    /// Panics if any pair of [`QCellSingle`] instances point to the
+    /// same memory.
+    ///
+    /// [`QCellSingle`]: struct.QCellSingle.html
+    #[inline]
+    pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
+        &'a mut self,
+        qc1: &'a QCellSingle<T>,
+        qc2: &'a QCellSingle<U>,
+        qc3: &'a QCellSingle<V>,
+    ) -> (&'a mut T, &'a mut U, &'a mut V) {
+        self.debug_check(qc1);
+        self.debug_check(qc2);
+        self.debug_check(qc3);
+        assert!(
+            distinct(qc1, qc2) && distinct(qc2, qc3) && distinct(qc3, qc1),
+            "{}",
+            panic_messages::QCELL_SINGLE_BORROWED_TWICE
+        );
+        // Safety: Owned (singleton owner), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&qc1.value, &qc2.value, &qc3.value) }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use super::{QCellOwnerSingle, QCellSingle};
+    use once_cell::sync::Lazy;
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    // The owner is process-wide, so tests must not run in parallel
+    static TEST_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+    pub(crate) fn fresh() -> (MutexGuard<'static, ()>, QCellOwnerSingle) {
+        let guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe { QCellOwnerSingle::reset_for_tests() };
+        (guard, QCellOwnerSingle::new())
+    }
+
+    #[test]
+    fn qcell_single() {
+        let (_lock, mut owner) = fresh();
+        let c1 = QCellSingle::new(&owner, 100u32);
+        let c2 = owner.cell(200u32);
+        let c3 = owner.cell(300u32);
+        *owner.rw(&c1) += 1;
+        *c2.rw(&mut owner) += 2;
+        let (a, b, c) = owner.rw3(&c1, &c2, &c3);
+        std::mem::swap(a, c);
+        *b += 1;
+        assert_eq!(*owner.ro(&c1), 300);
+        assert_eq!(*c2.ro(&owner), 203);
+        assert_eq!(c3.into_inner(), 101);
+    }
+
+    #[test]
+    fn qcell_single_singleton() {
+        let (_lock, owner) = fresh();
+        assert!(QCellOwnerSingle::try_new().is_none());
+        {
+            let _dropped = owner;
+        }
+        assert!(QCellOwnerSingle::try_new().is_none());
+        let result = std::panic::catch_unwind(QCellOwnerSingle::new);
+        assert!(result.is_err());
+    }
+
+    // Two libraries in one process, each wanting its own owner: the
+    // second one is refused, on any thread
+    #[test]
+    fn qcell_single_second_user() {
+        use crate::panic_messages::tests::panic_message;
+        use crate::panic_messages::QCELL_SINGLE_SECOND_OWNER;
+        let (_lock, owner) = fresh();
+        let lib_a = owner.cell(String::from("a"));
+        let lib_b = std::thread::spawn(QCellOwnerSingle::try_new).join().unwrap();
+        assert!(lib_b.is_none());
+        assert_eq!(panic_message(QCellOwnerSingle::new), QCELL_SINGLE_SECOND_OWNER);
+        assert_eq!(owner.ro(&lib_a), "a");
+    }
+
+    #[test]
+    #[should_panic]
+    fn qcell_single_rw2_same() {
+        let (_lock, mut owner) = fresh();
+        let c1 = owner.cell(1u32);
+        let _ = owner.rw2(&c1, &c1);
+    }
+
+    #[test]
+    fn qcell_single_rw2_zero_sized() {
+        // In release builds these cells are zero-sized and both sit at
+        // the start of the struct, so they have the same address
+        struct Pair(QCellSingle<()>, QCellSingle<()>, QCellSingle<()>);
+        let (_lock, mut owner) = fresh();
+        let pair = Pair(owner.cell(()), owner.cell(()), owner.cell(()));
+        let _ = owner.rw2(&pair.0, &pair.1);
+        let _ = owner.rw3(&pair.0, &pair.1, &pair.2);
+    }
+
+    #[test]
+    fn qcell_single_threads() {
+        let (_lock, mut owner) = fresh();
+        let shared = Arc::new(owner.cell(vec![1u32]));
+        let shared2 = shared.clone();
+        let mut owner = std::thread::spawn(move || {
+            owner.rw(&shared2).push(2);
+            owner
+        })
+        .join()
+        .unwrap();
+        let cell = owner.cell(vec![3u32]);
+        let cell = std::thread::spawn(move || {
+            owner.rw(&cell).push(4);
+            assert_eq!(*owner.ro(&shared), vec![1, 2]);
+            cell
+        })
+        .join()
+        .unwrap();
+        assert_eq!(cell.into_inner(), vec![3, 4]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "wrong QCellOwnerSingle")]
+    fn qcell_single_debug_misuse() {
+        let (_lock, owner1) = fresh();
+        let cell = owner1.cell(1u32);
+        unsafe { QCellOwnerSingle::reset_for_tests() };
+        let owner2 = QCellOwnerSingle::new();
+        let _ = owner2.ro(&cell); // Panic here in debug builds
+    }
+
+    #[test]
+    fn qcell_single_size() {
+        use std::mem::size_of;
+        #[cfg(not(debug_assertions))]
+        assert_eq!(size_of::<QCellSingle<u8>>(), 1);
+        #[cfg(debug_assertions)]
+        assert_eq!(size_of::<QCellSingle<u8>>(), 2 * size_of::<usize>());
+    }
+}
@@ -0,0 +1,465 @@
+// The owner is kept in an `UnsafeCell`, shared between one
+// `WriteHalf` and any number of `ReadHalf` clones.  `state` counts
+// the read phases in progress, plus `WRITING` whilst the writer wants
+// or holds the owner.  A reader only gets `&QCellOwner` inside a read
+// phase, which it enters by incrementing the count when `WRITING` is
+// clear.  The writer only gets `&mut QCellOwner` after setting
+// `WRITING` and seeing the count fall to zero.  Since a reader which
+// finds `WRITING` set backs out before touching the owner, the two
+// kinds of access never overlap.
+//
+// Blocking is only on the slow paths, on a `Condvar` with an empty
+// `Mutex`.  Each side checks its condition with the mutex locked, and
+// the other side takes the mutex before notifying, so a wake-up can't
+// fall between the check and the wait.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use crate::sound;
+use crate::QCellOwner;
+
+// High bit of `Phases::state`.  The rest counts the read phases.
+const WRITING: usize = !(usize::MAX >> 1);
+
+struct Phases {
+    owner: UnsafeCell<QCellOwner>,
+    state: AtomicUsize,
+    lock: Mutex<()>,
+    // The writer waits here for the read phases to end
+    readers_done: Condvar,
+    // Readers wait here for the write phase to end
+    write_done: Condvar,
+}
+
+// Safety: The owner is only accessed within read phases (shared) or
+// a write phase (unique), which `state` keeps apart, just as a lock
+// would.  `QCellOwner` is `Send + Sync`.
+unsafe impl Send for Phases {}
+unsafe impl Sync for Phases {}
+
+impl Phases {
+    // The mutex protects no data, so poisoning means nothing
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn try_enter_read(&self) -> bool {
+        let state = self.state.fetch_add(1, Ordering::Acquire);
+        if state & WRITING == 0 {
+            return true;
+        }
+        self.exit_read();
+        false
+    }
+
+    fn enter_read(&self) {
+        while !self.try_enter_read() {
+            let guard = self.lock();
+            let _guard = self
+                .write_done
+                .wait_while(guard, |_| self.state.load(Ordering::Acquire) & WRITING != 0)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn exit_read(&self) {
+        let state = self.state.fetch_sub(1, Ordering::Release);
+        if state == WRITING + 1 {
+            // Last one out whilst the writer is waiting
+            let _guard = self.lock();
+            self.readers_done.notify_one();
+        }
+    }
+
+    fn enter_write(&self) {
+        let state = self.state.fetch_or(WRITING, Ordering::Acquire);
+        debug_assert_eq!(state & WRITING, 0);
+        if state != 0 {
+            let guard = self.lock();
+            let _guard = self
+                .readers_done
+                .wait_while(guard, |_| self.state.load(Ordering::Acquire) != WRITING)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn exit_write(&self) {
+        self.state.fetch_and(!WRITING, Ordering::Release);
+        let _guard = self.lock();
+        self.write_done.notify_all();
+    }
+}
+
+// Ends the write phase even if the closure panics
+struct WriteGuard<'a>(&'a Phases);
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        self.0.exit_write();
+    }
+}
+
+/// The writing half of a [`QCellOwner`] split by
+/// [`QCellOwner::split_rw_sched`], for a design with one thread that
+/// modifies cells and many threads that read them.
+///
+/// [`WriteHalf::write_phase`] waits for the read phases in progress
+/// to end, and stops new ones from starting, so the writer is never
+/// starved, however busy the readers are.  Readers between write
+/// phases pay only two atomic operations per read phase, and never
+/// block each other.
+///
+/// # Compared with a lock around the owner
+///
+/// - `Mutex<QCellOwner>` lets only one thread read at a time.
+///
+/// - `RwLock<QCellOwner>` lets readers in together, but whether a
+///   waiting writer holds back new readers depends on the platform,
+///   so under heavy read load the writer may wait indefinitely.
+///
+/// - The split owner always gives the writer priority.  The cost is
+///   that readers stall for the whole of each write phase, including
+///   the wait for the last read phase to end, and that there is only
+///   ever one writer.  So keep both kinds of phase short.
+///
+/// Read phases don't nest safely: a thread which starts a read phase
+/// whilst already in one will deadlock if the writer starts waiting
+/// in between.  The same applies to a thread which holds a read phase
+/// and then calls [`WriteHalf::write_phase`].
+///
+/// ```
+///# use qcell::QCellOwner;
+///# use std::sync::Arc;
+///# use std::thread;
+/// let owner = QCellOwner::new();
+/// let position = Arc::new(owner.cell((0_i64, 0_i64)));
+/// let (mut writer, reader) = owner.split_rw_sched();
+///
+/// let readers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let reader = reader.clone();
+///         let position = position.clone();
+///         thread::spawn(move || {
+///             for _ in 0..1000 {
+///                 let phase = reader.read();
+///                 let (x, y) = *phase.ro(&position);
+///                 // Never part-way through a move
+///                 assert_eq!(x, -y);
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for _ in 0..100 {
+///     writer.write_phase(|owner| {
+///         let position = owner.rw(&position);
+///         position.0 += 1;
+///         position.1 -= 1;
+///     });
+/// }
+/// for handle in readers {
+///     handle.join().unwrap();
+/// }
+/// assert_eq!(*reader.read().ro(&position), (100, -100));
+/// ```
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwner::split_rw_sched`]: struct.QCellOwner.html#method.split_rw_sched
+/// [`WriteHalf::write_phase`]: struct.WriteHalf.html#method.write_phase
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct WriteHalf {
+    phases: Arc<Phases>,
+}
+
+impl WriteHalf {
+    /// Wait for the read phases in progress to end, then call `f`
+    /// with mutable access to the owner.  Readers which try to start
+    /// a read phase in the meantime block until `f` returns.
+    ///
+    /// If `f` panics, the write phase still ends, and readers see
+    /// whatever `f` had written so far.  Use [`PoisonCell`] for
+    /// cells where that matters.
+    ///
+    /// [`PoisonCell`]: struct.PoisonCell.html
+    pub fn write_phase<R>(&mut self, f: impl FnOnce(&mut QCellOwner) -> R) -> R {
+        self.phases.enter_write();
+        let _guard = WriteGuard(&self.phases);
+        // Safety: In a write phase, so no read phase is in progress,
+        // and `&mut self` excludes any other write phase
+        let owner = unsafe { sound::deref_unique(&self.phases.owner) };
+        f(owner)
+    }
+
+    /// Get a new [`ReadHalf`] for the same owner
+    ///
+    /// [`ReadHalf`]: struct.ReadHalf.html
+    pub fn reader(&self) -> ReadHalf {
+        ReadHalf {
+            phases: self.phases.clone(),
+        }
+    }
+
+    /// Put the owner back together, which is only possible once all
+    /// the [`ReadHalf`] clones have been dropped.  Otherwise the
+    /// `WriteHalf` is returned.
+    ///
+    /// [`ReadHalf`]: struct.ReadHalf.html
+    pub fn into_owner(self) -> Result<QCellOwner, Self> {
+        match Arc::try_unwrap(self.phases) {
+            Ok(phases) => Ok(phases.owner.into_inner()),
+            Err(phases) => Err(Self { phases }),
+        }
+    }
+}
+
+impl fmt::Debug for WriteHalf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteHalf").finish_non_exhaustive()
+    }
+}
+
+/// The reading half of a [`QCellOwner`] split by
+/// [`QCellOwner::split_rw_sched`].  Clone it to give each reader
+/// thread its own, or share one between threads.  See [`WriteHalf`]
+/// for the scheduling and trade-offs.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwner::split_rw_sched`]: struct.QCellOwner.html#method.split_rw_sched
+/// [`WriteHalf`]: struct.WriteHalf.html
+#[derive(Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct ReadHalf {
+    phases: Arc<Phases>,
+}
+
+impl ReadHalf {
+    /// Start a read phase, blocking whilst a write phase is waiting
+    /// or in progress.  The phase gives shared access to the owner
+    /// until it is dropped.
+    pub fn read(&self) -> ReadPhase<'_> {
+        self.phases.enter_read();
+        ReadPhase {
+            phases: &self.phases,
+        }
+    }
+
+    /// Start a read phase only if no write phase is waiting or in
+    /// progress, without blocking
+    pub fn try_read(&self) -> Option<ReadPhase<'_>> {
+        if self.phases.try_enter_read() {
+            Some(ReadPhase {
+                phases: &self.phases,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for ReadHalf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadHalf").finish_non_exhaustive()
+    }
+}
+
+/// Shared access to the owner of a [`ReadHalf`], for the duration of
+/// a read phase.  This dereferences to [`QCellOwner`], so cells are
+/// read with `phase.ro(&cell)` as usual.  The writer can't start its
+/// next write phase until this is dropped.
+///
+/// [`ReadHalf`]: struct.ReadHalf.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct ReadPhase<'a> {
+    phases: &'a Phases,
+}
+
+impl Deref for ReadPhase<'_> {
+    type Target = QCellOwner;
+
+    #[inline]
+    fn deref(&self) -> &QCellOwner {
+        // Safety: In a read phase, so no write phase is in progress
+        unsafe { sound::deref_shared(&self.phases.owner) }
+    }
+}
+
+impl Drop for ReadPhase<'_> {
+    fn drop(&mut self) {
+        self.phases.exit_read();
+    }
+}
+
+impl fmt::Debug for ReadPhase<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadPhase").finish_non_exhaustive()
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl QCellOwner {
+    /// Split the owner into a [`WriteHalf`] for one writer thread and
+    /// a [`ReadHalf`] for any number of reader threads, scheduled in
+    /// phases with priority for the writer.  See [`WriteHalf`] for an
+    /// example and the trade-offs against a lock around the owner.
+    ///
+    /// [`WriteHalf`]: struct.WriteHalf.html
+    /// [`ReadHalf`]: struct.ReadHalf.html
+    pub fn split_rw_sched(self) -> (WriteHalf, ReadHalf) {
+        let phases = Arc::new(Phases {
+            owner: UnsafeCell::new(self),
+            state: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            readers_done: Condvar::new(),
+            write_done: Condvar::new(),
+        });
+        let reader = ReadHalf {
+            phases: phases.clone(),
+        };
+        (WriteHalf { phases }, reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::QCellOwner;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // The writer leaves the pair unequal part-way through each write
+    // phase, and yields there to give readers every chance to see it
+    #[test]
+    fn split_no_torn_reads() {
+        let owner = QCellOwner::new();
+        let pair = Arc::new(owner.cell((0_u64, 0_u64)));
+        let (mut writer, reader) = owner.split_rw_sched();
+        let done = Arc::new(AtomicBool::new(false));
+        let reads = Arc::new(AtomicUsize::new(0));
+        let readers: Vec<_> = (0..6)
+            .map(|_| {
+                let (reader, pair, done, reads) =
+                    (reader.clone(), pair.clone(), done.clone(), reads.clone());
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        let phase = reader.read();
+                        let (a, b) = *phase.ro(&pair);
+                        assert_eq!(a, b);
+                        reads.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..200 {
+            writer.write_phase(|owner| {
+                owner.rw(&pair).0 += 1;
+                thread::yield_now();
+                owner.rw(&pair).1 += 1;
+            });
+        }
+        done.store(true, Ordering::Relaxed);
+        for handle in readers {
+            handle.join().unwrap();
+        }
+        assert!(reads.load(Ordering::Relaxed) > 0);
+        assert_eq!(*reader.read().ro(&pair), (200, 200));
+    }
+
+    // Many readers which each stay in a read phase for a while, so
+    // that between them there is almost never a moment with none in
+    // progress, which would starve a writer that had to wait for a
+    // gap
+    #[test]
+    fn split_writer_not_starved() {
+        let owner = QCellOwner::new();
+        let counter = Arc::new(owner.cell(0_u32));
+        let (mut writer, reader) = owner.split_rw_sched();
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let (reader, counter, done) = (reader.clone(), counter.clone(), done.clone());
+                thread::spawn(move || {
+                    // The largest count seen, so that the reads have
+                    // a use
+                    let mut max = 0;
+                    while !done.load(Ordering::Relaxed) {
+                        let phase = reader.read();
+                        for _ in 0..100 {
+                            max = max.max(*phase.ro(&counter));
+                        }
+                    }
+                    max
+                })
+            })
+            .collect();
+        let start = Instant::now();
+        for _ in 0..200 {
+            writer.write_phase(|owner| *owner.rw(&counter) += 1);
+        }
+        let elapsed = start.elapsed();
+        done.store(true, Ordering::Relaxed);
+        for handle in readers {
+            assert!(handle.join().unwrap() <= 200);
+        }
+        assert_eq!(*reader.read().ro(&counter), 200);
+        assert!(elapsed < Duration::from_secs(30), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn split_readers_wait_for_writer() {
+        let owner = QCellOwner::new();
+        let cell = Arc::new(owner.cell(0_u32));
+        let (mut writer, reader) = owner.split_rw_sched();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let handle = {
+            let cell = cell.clone();
+            thread::spawn(move || {
+                writer.write_phase(|owner| {
+                    *owner.rw(&cell) = 1;
+                    started_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                    *owner.rw(&cell) = 2;
+                });
+                writer
+            })
+        };
+        started_rx.recv().unwrap();
+        assert!(reader.try_read().is_none());
+        release_tx.send(()).unwrap();
+        // Blocks until the write phase has finished
+        assert_eq!(*reader.read().ro(&cell), 2);
+        let writer = handle.join().unwrap();
+        assert_eq!(*reader.try_read().unwrap().ro(&cell), 2);
+
+        let writer = match writer.into_owner() {
+            Ok(_) => panic!("reader still exists"),
+            Err(writer) => writer,
+        };
+        drop(reader);
+        let owner = writer.into_owner().unwrap();
+        assert_eq!(*owner.ro(&cell), 2);
+    }
+
+    #[test]
+    fn split_write_phase_panic() {
+        let owner = QCellOwner::new();
+        let cell = owner.cell(0_u32);
+        let (mut writer, reader) = owner.split_rw_sched();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            writer.write_phase(|owner| {
+                *owner.rw(&cell) = 1;
+                panic!("part-way");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(*reader.try_read().unwrap().ro(&cell), 1);
+        writer.write_phase(|owner| *owner.rw(&cell) = 2);
+        assert_eq!(*reader.read().ro(&cell), 2);
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+use crate::sound::{self, ArcMirror};
+use crate::{QCell, QCellBorrow, QCellOwnerID};
+
+/// [`QCell`] holding an `Arc<T>` snapshot, which any thread can load
+/// without the owner, in the style of read-copy-update.
+///
+/// Configuration and similar read-mostly data is often kept as
+/// `Arc<QCell<Arc<T>>>`, with readers cloning the inner `Arc` under a
+/// brief `ro` and then working on their own snapshot.  But with the
+/// owner shared between threads behind a `Mutex`, every reader then
+/// has to take the lock.  `RcuCell` keeps a second copy of the `Arc`
+/// in an atomic pointer, so [`RcuCell::load`] clones the current
+/// snapshot without needing the owner, and is wait-free.  Writers
+/// still go through the owner, with [`RcuCell::store`] or
+/// [`RcuCell::update`], which excludes other writers and keeps both
+/// copies the same.
+///
+/// # Consistency
+///
+/// A snapshot is never modified, so a reader always sees a complete
+/// value, either from before or after any given store.  A reader
+/// which loads whilst a store is in progress may get the previous
+/// snapshot, and it keeps whichever snapshot it loaded for as long as
+/// it holds the `Arc`, so readers may briefly be working on different
+/// versions.  Readers loading after a store has returned always get
+/// the new value or a later one.
+///
+/// A store waits for any readers which are part-way through loading
+/// the old snapshot to finish cloning it, which takes just a few
+/// instructions each.  It never waits for readers to drop their
+/// snapshots.
+///
+/// ```
+///# use qcell::{QCellOwner, RcuCell};
+///# use std::sync::{Arc, Mutex};
+///# use std::thread;
+/// struct Config {
+///     name: String,
+///     limit: u32,
+/// }
+///
+/// let owner = Arc::new(Mutex::new(QCellOwner::new()));
+/// let config = Arc::new(RcuCell::new(&*owner.lock().unwrap(), Config {
+///     name: "default".into(),
+///     limit: 10,
+/// }));
+///
+/// let reader = {
+///     let config = config.clone();
+///     thread::spawn(move || {
+///         // No owner needed to take a snapshot
+///         let snapshot = config.load();
+///         assert!(snapshot.limit == 10 || snapshot.limit == 20);
+///     })
+/// };
+/// config.update(&mut *owner.lock().unwrap(), |old| Config {
+///     name: old.name.clone(),
+///     limit: 20,
+/// });
+/// reader.join().unwrap();
+/// assert_eq!(config.load().limit, 20);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`RcuCell::load`]: struct.RcuCell.html#method.load
+/// [`RcuCell::store`]: struct.RcuCell.html#method.store
+/// [`RcuCell::update`]: struct.RcuCell.html#method.update
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct RcuCell<T> {
+    cell: QCell<Arc<T>>,
+    mirror: ArcMirror<T>,
+}
+
+impl<T> RcuCell<T> {
+    /// Create a new cell owned by the given owner ID, holding `value`
+    /// as its first snapshot
+    pub fn new(id: impl Into<QCellOwnerID>, value: impl Into<Arc<T>>) -> Self {
+        let value = value.into();
+        Self {
+            mirror: ArcMirror::new(value.clone()),
+            cell: QCell::new(id, value),
+        }
+    }
+
+    /// Get the current snapshot, without needing the owner.  This is
+    /// wait-free.  A store in progress on another thread may or may
+    /// not be seen.
+    #[inline]
+    pub fn load(&self) -> Arc<T> {
+        self.mirror.load()
+    }
+
+    /// Borrow the current snapshot using the owner, which avoids the
+    /// atomic operations of [`RcuCell::load`].  Since writers need
+    /// the owner mutably, this is always the latest snapshot.  Panics
+    /// if the cell is not owned by this owner.
+    ///
+    /// [`RcuCell::load`]: struct.RcuCell.html#method.load
+    #[inline]
+    pub fn get<'a, O: QCellBorrow>(&'a self, owner: &'a O) -> &'a Arc<T> {
+        owner.qcell_ro(&self.cell)
+    }
+
+    /// Replace the snapshot with `value`, returning the old one.
+    /// Readers which load after this returns see the new value.
+    /// Panics if the cell is not owned by this owner.
+    pub fn store<O: QCellBorrow>(&self, owner: &mut O, value: impl Into<Arc<T>>) -> Arc<T> {
+        let value = value.into();
+        let slot = owner.qcell_rw(&self.cell);
+        // Safety: Owner mutably borrowed, so no other store can overlap
+        unsafe { sound::arc_mirror_store(&self.mirror, value.clone()) };
+        mem::replace(slot, value)
+    }
+
+    /// Replace the snapshot with the value returned by `f`, which is
+    /// passed the current one.  Panics if the cell is not owned by
+    /// this owner, in which case `f` is not called.
+    pub fn update<O: QCellBorrow>(&self, owner: &mut O, f: impl FnOnce(&T) -> T) {
+        let current: &Arc<T> = owner.qcell_ro(&self.cell);
+        let value = f(current);
+        self.store(owner, value);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RcuCell")
+            .field("value", &self.load())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{QCellOwner, QCellOwnerSeq, RcuCell};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn rcu_cell_store_update_get() {
+        let mut owner = QCellOwner::new();
+        let cell = RcuCell::new(&owner, 1);
+        assert_eq!(*cell.load(), 1);
+        let old = cell.store(&mut owner, 2);
+        assert_eq!((*old, *cell.load(), **cell.get(&owner)), (1, 2, 2));
+        cell.update(&mut owner, |v| v * 10);
+        assert_eq!(*cell.load(), 20);
+        assert_eq!(format!("{:?}", cell), "RcuCell { value: 20 }");
+    }
+
+    #[test]
+    #[should_panic]
+    fn rcu_cell_wrong_owner() {
+        let mut owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cell = RcuCell::new(&owner2, 0);
+        cell.store(&mut owner1, 1);
+    }
+
+    #[test]
+    fn rcu_cell_refcounts() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let first = Arc::new(String::from("first"));
+        let cell = RcuCell::new(&owner, first.clone());
+        // Held by `first`, the cell, and the atomic copy
+        assert_eq!(Arc::strong_count(&first), 3);
+        let loaded: Vec<_> = (0..10).map(|_| cell.load()).collect();
+        assert_eq!(Arc::strong_count(&first), 13);
+        drop(loaded);
+        assert_eq!(Arc::strong_count(&first), 3);
+
+        let second = Arc::new(String::from("second"));
+        let old = cell.store(&mut owner, second.clone());
+        assert!(Arc::ptr_eq(&old, &first));
+        drop(old);
+        assert_eq!(Arc::strong_count(&first), 1);
+        assert_eq!(Arc::strong_count(&second), 3);
+        drop(cell);
+        assert_eq!(Arc::strong_count(&second), 1);
+    }
+
+    #[test]
+    fn rcu_cell_concurrent_readers() {
+        const READERS: usize = 4;
+        const UPDATES: u64 = 2000;
+
+        // Each snapshot is complete if all the values are equal, and
+        // drops are counted to check that none are leaked
+        struct Snapshot {
+            values: [u64; 8],
+            drops: Arc<Mutex<u64>>,
+        }
+        impl Drop for Snapshot {
+            fn drop(&mut self) {
+                *self.drops.lock().unwrap() += 1;
+            }
+        }
+
+        let drops = Arc::new(Mutex::new(0));
+        let snapshot = |n| Snapshot {
+            values: [n; 8],
+            drops: drops.clone(),
+        };
+        let mut owner = QCellOwner::new();
+        let cell = Arc::new(RcuCell::new(&owner, snapshot(0)));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                let (cell, done) = (cell.clone(), done.clone());
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while !done.load(Ordering::Relaxed) {
+                        let snap = cell.load();
+                        let n = snap.values[0];
+                        assert!(snap.values.iter().all(|v| *v == n));
+                        // Snapshots never go backwards for one reader
+                        assert!(n >= last);
+                        last = n;
+                    }
+                })
+            })
+            .collect();
+        for n in 1..=UPDATES {
+            cell.store(&mut owner, snapshot(n));
+        }
+        done.store(true, Ordering::Relaxed);
+        for r in readers {
+            r.join().unwrap();
+        }
+        assert_eq!(Arc::strong_count(&cell), 1);
+        assert_eq!(cell.load().values, [UPDATES; 8]);
+        // All but the current snapshot have been dropped
+        assert_eq!(*drops.lock().unwrap(), UPDATES);
+        drop(cell);
+        assert_eq!(*drops.lock().unwrap(), UPDATES + 1);
+    }
+}
@@ -0,0 +1,517 @@
+//! Recording of the mutations made through a [`QCellOwner`], for
+//! reproducing bugs in tests.
+//!
+//! When a wrong value turns up deep in a graph of cells, the hard
+//! part of tracking it down is usually working out which cells were
+//! changed, in which order, and from where.  With the **record**
+//! feature enabled, a [`Recorder`] attached to a [`QCellOwner`] with
+//! [`QCellOwner::attach_recorder`] logs an [`Event`] for each call to
+//! `rw`, `rw2`, `rw3`, `set` or `replace` on that owner.  Each event
+//! gives a sequence number, the owner's ID, the [`CellId`] of each
+//! cell borrowed, and the source location of the call.  Other
+//! methods which borrow mutably through these, such as `update` or
+//! `rw_with`, are logged as the call they make, with the location of
+//! their own caller.
+//!
+//! Values are not recorded, since cell contents don't have to be
+//! `Clone` or `Debug`.  The trace gives the sequence of accesses, and
+//! [`Replay`] can apply it again to another set of cells, with the
+//! test supplying the change to make for each event.
+//!
+//! The recorder keeps only the most recent events, up to its
+//! capacity.  Recording costs a check per access even with no
+//! recorder attached, so the feature is intended for debugging
+//! builds.  Without the feature, nothing here exists and the owner
+//! is unchanged.
+//!
+//! ```
+//!# use qcell::QCellOwner;
+//!# use qcell::record::{AccessKind, CellId, Recorder, Replay};
+//! let mut owner = QCellOwner::new();
+//! let total = owner.cell(0);
+//! let count = owner.cell(0);
+//! owner.attach_recorder(Recorder::new(16));
+//! for n in 1..=3 {
+//!     let (total, count) = owner.rw2(&total, &count);
+//!     *total += n;
+//!     *count += 1;
+//! }
+//! owner.set(&count, 10);
+//!
+//! let trace = owner.dump_trace();
+//! assert_eq!(trace.len(), 4);
+//! assert_eq!(trace[0].kind(), AccessKind::Rw2);
+//! assert_eq!(trace[0].cells(), [CellId::of(&total), CellId::of(&count)]);
+//! assert_eq!(trace[3].cells(), [CellId::of(&count)]);
+//!
+//! // Reproduce the final state from the trace in a fresh owner
+//! let mut owner2 = QCellOwner::new();
+//! let total2 = owner2.cell(0);
+//! let count2 = owner2.cell(0);
+//! Replay::new()
+//!     .cell(CellId::of(&total), &total2, |v, ev| *v += ev.seq() + 1)
+//!     .cell(CellId::of(&count), &count2, |v, ev| match ev.kind() {
+//!         AccessKind::Set => *v = 10,
+//!         _ => *v += 1,
+//!     })
+//!     .run(&mut owner2, &trace);
+//! assert_eq!((owner2.get(&total2), owner2.get(&count2)), (6, 10));
+//! ```
+//!
+//! [`QCellOwner`]: ../struct.QCellOwner.html
+//! [`QCellOwner::attach_recorder`]: ../struct.QCellOwner.html#method.attach_recorder
+//! [`Recorder`]: struct.Recorder.html
+//! [`Event`]: struct.Event.html
+//! [`CellId`]: struct.CellId.html
+//! [`Replay`]: struct.Replay.html
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use core::panic::Location;
+
+use crate::panic_messages;
+use crate::{QCell, QCellOwner, QCellOwnerID};
+
+/// Identity of a [`QCell`] in a trace, taken from its address.
+///
+/// Addresses are reused once a cell is dropped, so a trace which
+/// spans the dropping and creation of cells may give the same ID to
+/// two different cells.
+///
+/// [`QCell`]: ../struct.QCell.html
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CellId(usize);
+
+impl CellId {
+    /// Get the ID of a cell
+    #[inline]
+    pub fn of<T: ?Sized>(qc: &QCell<T>) -> Self {
+        Self(qc as *const QCell<T> as *const () as usize)
+    }
+}
+
+/// The owner method which made a recorded access
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AccessKind {
+    /// `rw`, borrowing one cell
+    Rw,
+    /// `rw2`, borrowing two cells
+    Rw2,
+    /// `rw3`, borrowing three cells
+    Rw3,
+    /// `set`, overwriting the contents of one cell
+    Set,
+    /// `replace`, swapping in new contents for one cell
+    Replace,
+}
+
+impl AccessKind {
+    fn cell_count(self) -> usize {
+        match self {
+            AccessKind::Rw | AccessKind::Set | AccessKind::Replace => 1,
+            AccessKind::Rw2 => 2,
+            AccessKind::Rw3 => 3,
+        }
+    }
+}
+
+/// One recorded access to the cells of an owner
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Event {
+    seq: u64,
+    owner: QCellOwnerID,
+    kind: AccessKind,
+    cells: [CellId; 3],
+    location: &'static Location<'static>,
+}
+
+impl Event {
+    /// Position of this event in the sequence of all the events
+    /// seen by the [`Recorder`], counting from zero.  This keeps
+    /// counting when old events are discarded, so the first event in
+    /// a trace which has wrapped doesn't have sequence number zero.
+    ///
+    /// [`Recorder`]: struct.Recorder.html
+    #[inline]
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// ID of the owner which made the access
+    #[inline]
+    pub fn owner(&self) -> QCellOwnerID {
+        self.owner
+    }
+
+    /// Which method made the access
+    #[inline]
+    pub fn kind(&self) -> AccessKind {
+        self.kind
+    }
+
+    /// The cells borrowed, in argument order
+    #[inline]
+    pub fn cells(&self) -> &[CellId] {
+        &self.cells[..self.kind.cell_count()]
+    }
+
+    /// Source location of the call
+    #[inline]
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+/// Ring buffer of the most recent [`Event`]s of an owner, attached
+/// with [`QCellOwner::attach_recorder`].  Once the buffer is full,
+/// each new event discards the oldest one.
+///
+/// [`Event`]: struct.Event.html
+/// [`QCellOwner::attach_recorder`]: ../struct.QCellOwner.html#method.attach_recorder
+pub struct Recorder {
+    events: VecDeque<Event>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl Recorder {
+    /// Create a recorder which keeps the last `capacity` events.
+    /// With a capacity of zero, events are only counted.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            next_seq: 0,
+        }
+    }
+
+    /// Get the maximum number of events kept
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of events which have been discarded to make
+    /// room for newer ones
+    #[inline]
+    pub fn discarded(&self) -> u64 {
+        self.next_seq - self.events.len() as u64
+    }
+
+    /// Get a copy of the events kept, oldest first
+    pub fn trace(&self) -> Vec<Event> {
+        self.events.iter().copied().collect()
+    }
+
+    fn push(&mut self, event: Event) {
+        self.next_seq += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("capacity", &self.capacity)
+            .field("len", &self.events.len())
+            .field("discarded", &self.discarded())
+            .finish()
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+impl QCellOwner {
+    /// Attach a [`Recorder`], to log the mutable accesses made
+    /// through this owner from now on.  Returns the recorder which
+    /// was attached before, if any.  See the [`record`] module.
+    ///
+    /// [`Recorder`]: record/struct.Recorder.html
+    /// [`record`]: record/index.html
+    pub fn attach_recorder(&mut self, recorder: Recorder) -> Option<Recorder> {
+        self.recorder.replace(Box::new(recorder)).map(|r| *r)
+    }
+
+    /// Stop recording, and return the recorder if one was attached
+    pub fn detach_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take().map(|r| *r)
+    }
+
+    /// Get a copy of the events held by the attached [`Recorder`],
+    /// oldest first, or an empty `Vec` if there is none
+    ///
+    /// [`Recorder`]: record/struct.Recorder.html
+    pub fn dump_trace(&self) -> Vec<Event> {
+        self.recorder.as_ref().map_or_else(Vec::new, |r| r.trace())
+    }
+
+    #[inline]
+    #[track_caller]
+    pub(crate) fn record(&mut self, kind: AccessKind, cells: &[CellId]) {
+        let owner = self.id();
+        if let Some(recorder) = self.recorder.as_deref_mut() {
+            let mut ids = [CellId(0); 3];
+            ids[..cells.len()].copy_from_slice(cells);
+            recorder.push(Event {
+                seq: recorder.next_seq,
+                owner,
+                kind,
+                cells: ids,
+                location: Location::caller(),
+            });
+        }
+    }
+}
+
+type Apply<'a> = Box<dyn FnMut(&mut QCellOwner, &Event) + 'a>;
+
+/// Test helper to apply a recorded trace again, to a different set of
+/// cells.
+///
+/// Each [`CellId`] in the trace is mapped with [`Replay::cell`] to a
+/// current cell, along with a function to make the change for an
+/// event, which is passed the contents and the [`Event`].  Then
+/// [`Replay::run`] goes through the trace in order, and calls the
+/// function for each cell borrowed by each event.  For `rw2` and
+/// `rw3` events, the functions are called one cell at a time, in
+/// argument order.  Since no values are recorded, the functions
+/// decide what to change, for example based on the event's sequence
+/// number or kind, or on a script kept by the test.
+///
+/// The changes are made with `rw` through the owner passed to
+/// [`Replay::run`], so they are recorded if it has a recorder
+/// attached.
+///
+/// [`CellId`]: struct.CellId.html
+/// [`Event`]: struct.Event.html
+/// [`Replay::cell`]: struct.Replay.html#method.cell
+/// [`Replay::run`]: struct.Replay.html#method.run
+#[derive(Default)]
+pub struct Replay<'a> {
+    cells: Vec<(CellId, Apply<'a>)>,
+}
+
+impl<'a> Replay<'a> {
+    /// Create a replay with no cells mapped
+    pub fn new() -> Self {
+        Self { cells: Vec::new() }
+    }
+
+    /// Map the cell with ID `recorded` in the trace to `cell`, with
+    /// `apply` making the change for each event that borrows it.  A
+    /// later mapping for the same ID replaces an earlier one.
+    pub fn cell<T: ?Sized>(
+        mut self,
+        recorded: CellId,
+        cell: &'a QCell<T>,
+        mut apply: impl FnMut(&mut T, &Event) + 'a,
+    ) -> Self {
+        let apply: Apply<'a> = Box::new(move |owner, event| apply(owner.rw(cell), event));
+        match self.cells.iter_mut().find(|(id, _)| *id == recorded) {
+            Some(entry) => entry.1 = apply,
+            None => self.cells.push((recorded, apply)),
+        }
+        self
+    }
+
+    /// Apply the events of `trace` in order, using `owner` to borrow
+    /// the mapped cells.  Panics if an event borrows a cell which has
+    /// not been mapped, or if a mapped cell is not owned by `owner`.
+    pub fn run(&mut self, owner: &mut QCellOwner, trace: &[Event]) {
+        for event in trace {
+            for id in event.cells() {
+                match self.cells.iter_mut().find(|(mapped, _)| mapped == id) {
+                    Some((_, apply)) => apply(owner, event),
+                    None => panic!(
+                        "{}: {:?} in event {}",
+                        panic_messages::REPLAY_UNMAPPED_CELL,
+                        id,
+                        event.seq
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Replay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.cells.iter().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{AccessKind, CellId, Event, Recorder, Replay};
+    use crate::QCellOwner;
+
+    fn summary(trace: &[Event]) -> Vec<(u64, AccessKind, Vec<CellId>)> {
+        trace
+            .iter()
+            .map(|e| (e.seq(), e.kind(), e.cells().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn record_scripted_sequence() {
+        let mut owner = QCellOwner::new();
+        let (a, b, c) = (owner.cell(1), owner.cell(2), owner.cell(3));
+        let (ia, ib, ic) = (CellId::of(&a), CellId::of(&b), CellId::of(&c));
+        // Nothing is recorded before a recorder is attached
+        *owner.rw(&a) += 1;
+        assert!(owner.dump_trace().is_empty());
+        assert!(owner.attach_recorder(Recorder::new(10)).is_none());
+
+        let line = line!() + 1;
+        *owner.rw(&a) += 1;
+        let (vb, vc) = owner.rw2(&b, &c);
+        *vb += *vc;
+        let (vc, va, vb) = owner.rw3(&c, &a, &b);
+        *vc = *va + *vb;
+        owner.set(&b, 20);
+        assert_eq!(owner.replace(&c, 30), 8);
+        // Reads and wrappers
+        let _ = owner.ro(&a);
+        owner.update(&a, |v| v * 2);
+        a.rw(&mut owner);
+        let mut other = 0;
+        owner.rw2_with(&a, &b, &mut other, |_, _, _| ());
+
+        let trace = owner.dump_trace();
+        assert_eq!(
+            summary(&trace),
+            vec![
+                (0, AccessKind::Rw, vec![ia]),
+                (1, AccessKind::Rw2, vec![ib, ic]),
+                (2, AccessKind::Rw3, vec![ic, ia, ib]),
+                (3, AccessKind::Set, vec![ib]),
+                (4, AccessKind::Replace, vec![ic]),
+                (5, AccessKind::Rw, vec![ia]),
+                (6, AccessKind::Rw, vec![ia]),
+                (7, AccessKind::Rw2, vec![ia, ib]),
+            ]
+        );
+        for (i, event) in trace.iter().enumerate() {
+            assert_eq!(event.owner(), owner.id());
+            assert_eq!(event.location().file(), file!());
+            let expected = match i {
+                5 => line + 9,
+                6 => line + 10,
+                7 => line + 12,
+                _ => line + [0, 1, 3, 5, 6][i],
+            };
+            assert_eq!(event.location().line(), expected, "event {}", i);
+        }
+        assert_eq!((owner.get(&a), owner.get(&b), owner.get(&c)), (6, 20, 30));
+
+        let recorder = owner.detach_recorder().unwrap();
+        assert_eq!((recorder.capacity(), recorder.discarded()), (10, 0));
+        assert_eq!(recorder.trace(), trace);
+        owner.set(&a, 0);
+        assert!(owner.dump_trace().is_empty());
+    }
+
+    #[test]
+    fn record_ring_wraps() {
+        let mut owner = QCellOwner::new();
+        let cells: Vec<_> = (0..5).map(|n| owner.cell(n)).collect();
+        owner.attach_recorder(Recorder::new(3));
+        for (n, cell) in cells.iter().enumerate() {
+            owner.set(cell, n * 10);
+        }
+        let trace = owner.dump_trace();
+        let seqs: Vec<u64> = trace.iter().map(|e| e.seq()).collect();
+        assert_eq!(seqs, [2, 3, 4]);
+        let ids: Vec<CellId> = trace.iter().map(|e| e.cells()[0]).collect();
+        let expected: Vec<CellId> = cells[2..].iter().map(CellId::of).collect();
+        assert_eq!(ids, expected);
+
+        // Replacing the recorder returns the old one
+        let old = owner.attach_recorder(Recorder::new(0)).unwrap();
+        assert_eq!(old.discarded(), 2);
+        owner.rw(&cells[0]);
+        owner.rw(&cells[1]);
+        let counting = owner.detach_recorder().unwrap();
+        assert!(counting.trace().is_empty());
+        assert_eq!(counting.discarded(), 2);
+        assert_eq!(
+            format!("{:?}", counting),
+            "Recorder { capacity: 0, len: 0, discarded: 2 }"
+        );
+    }
+
+    #[test]
+    fn record_replay_toy_bank() {
+        // Transfers between accounts, with a fee account taking a cut
+        // of each transfer
+        let script = [(0, 1, 50), (1, 2, 20), (2, 0, 5), (0, 2, 100)];
+        let run = |owner: &mut QCellOwner, accounts: &[crate::QCell<i64>; 3]| {
+            let fee = owner.cell(0_i64);
+            for (from, to, amount) in script.iter().copied() {
+                let (f, t) = owner.rw2(&accounts[from], &accounts[to]);
+                *f -= amount;
+                *t += amount - 1;
+                *owner.rw(&fee) += 1;
+            }
+            owner.replace(&accounts[1], 0)
+        };
+
+        let mut owner = QCellOwner::new();
+        let accounts = [owner.cell(200), owner.cell(0), owner.cell(0)];
+        owner.attach_recorder(Recorder::new(64));
+        let taken = run(&mut owner, &accounts);
+        let trace = owner.dump_trace();
+        let ids: Vec<CellId> = accounts.iter().map(CellId::of).collect();
+        let fee = trace[1].cells()[0];
+        assert!(!ids.contains(&fee));
+        let finals: Vec<i64> = accounts.iter().map(|a| owner.get(a)).collect();
+
+        // Replay with a script lookup per event, against new cells
+        let mut owner2 = QCellOwner::new();
+        let accounts2 = [owner2.cell(200), owner2.cell(0), owner2.cell(0)];
+        let fee2 = owner2.cell(0_i64);
+        let mut replay = Replay::new().cell(fee, &fee2, |v, _| *v += 1);
+        for (i, account) in accounts2.iter().enumerate() {
+            let id = ids[i];
+            replay = replay.cell(id, account, move |v, ev| {
+                if ev.kind() == AccessKind::Replace {
+                    *v = 0;
+                    return;
+                }
+                let (from, to, amount) = script[(ev.seq() / 2) as usize];
+                let cells = ev.cells();
+                if cells[0] == id && from == i {
+                    *v -= amount;
+                } else if cells[1] == id && to == i {
+                    *v += amount - 1;
+                }
+            });
+        }
+        replay.run(&mut owner2, &trace);
+        let finals2: Vec<i64> = accounts2.iter().map(|a| owner2.get(a)).collect();
+        assert_eq!(finals2, finals);
+        assert_eq!(owner2.get(&fee2), 4);
+        assert_eq!(taken, 29);
+        assert!(format!("{:?}", replay).starts_with("[CellId("));
+    }
+
+    #[test]
+    #[should_panic]
+    fn record_replay_unmapped_cell() {
+        let mut owner = QCellOwner::new();
+        let cell = owner.cell(0);
+        owner.attach_recorder(Recorder::new(4));
+        owner.set(&cell, 1);
+        let trace = owner.dump_trace();
+        Replay::new().run(&mut owner, &trace);
+    }
+}
@@ -0,0 +1,329 @@
+use core::fmt;
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::Index;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::panic_messages;
+use crate::sound::ErasedReaders;
+use crate::util::clash;
+
+// Implemented by each cell type to give `RoSlice` access to the
+// contents of a cell
+pub(crate) trait ValuePtr {
+    type Value: ?Sized;
+    fn value_ptr(&self) -> *const Self::Value;
+}
+
+#[cold]
+#[inline(never)]
+fn aliased_panic(cell: &str, index: usize, same: bool) -> ! {
+    if same {
+        panic!(
+            "{}: readers[{}] is the same {} as the writer",
+            panic_messages::RW1_RO_SLICE_ALIASED,
+            index,
+            cell
+        );
+    }
+    panic!(
+        "{}: readers[{}] overlaps the {} of the writer",
+        panic_messages::RW1_RO_SLICE_ALIASED,
+        index,
+        cell
+    );
+}
+
+// Panic if the writer cell is also in the readers slice, or overlaps
+// one of them, as an `LCell` for a whole slice overlaps the cells for
+// its elements
+#[inline]
+pub(crate) fn writer_check<W: ?Sized, R: ?Sized>(cell: &str, writer: &W, readers: &[&R]) {
+    let writer = (writer as *const W as *const () as usize, mem::size_of_val(writer));
+    for (index, reader) in readers.iter().enumerate() {
+        let reader = (*reader as *const R as *const () as usize, mem::size_of_val(*reader));
+        if clash(writer, reader) {
+            aliased_panic(cell, index, writer.0 == reader.0);
+        }
+    }
+}
+
+/// Read-only view onto the contents of a slice of cells, as returned
+/// by the `rw1_ro_slice` call on any of the owners.
+///
+/// The contents of each cell are only fetched when accessed, either
+/// through indexing, [`RoSlice::get`] or by iterating.  With the
+/// "alloc" feature, [`RoSlice::to_vec`] collects them all eagerly.
+///
+/// ```
+///# use qcell::{QCell, QCellOwnerSeq};
+/// // Safety: No other owner is created with this ID
+/// let mut owner = unsafe { QCellOwnerSeq::new() };
+/// let total = QCell::new(&owner, 0);
+/// let inputs: Vec<_> = (1..=4).map(|i| QCell::new(&owner, i)).collect();
+/// let refs: Vec<_> = inputs.iter().collect();
+/// let (total, inputs) = owner.rw1_ro_slice(&total, &refs);
+/// *total = inputs.iter().sum::<i32>() + inputs[0];
+/// assert_eq!(*total, 11);
+/// ```
+///
+/// [`RoSlice::get`]: struct.RoSlice.html#method.get
+/// [`RoSlice::to_vec`]: struct.RoSlice.html#method.to_vec
+pub struct RoSlice<'a, U: ?Sized> {
+    readers: ErasedReaders<'a, U>,
+}
+
+impl<'a, U: ?Sized> RoSlice<'a, U> {
+    #[inline]
+    pub(crate) fn new(readers: ErasedReaders<'a, U>) -> Self {
+        Self { readers }
+    }
+
+    /// Number of cells in the slice
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.readers.len()
+    }
+
+    /// Test whether the slice is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.readers.len() == 0
+    }
+
+    /// Borrow the contents of the cell at `index`, or return `None`
+    /// if it is out of range
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&'a U> {
+        self.readers.get(index)
+    }
+
+    /// Iterate over the contents of the cells, in order
+    #[inline]
+    pub fn iter(&self) -> RoSliceIter<'a, U> {
+        RoSliceIter {
+            slice: *self,
+            front: 0,
+            back: self.readers.len(),
+        }
+    }
+
+    /// Collect references to the contents of all the cells into a
+    /// `Vec`
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn to_vec(&self) -> Vec<&'a U> {
+        self.iter().collect()
+    }
+}
+
+impl<'a, U: ?Sized> Clone for RoSlice<'a, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, U: ?Sized> Copy for RoSlice<'a, U> {}
+
+impl<'a, U: ?Sized> Index<usize> for RoSlice<'a, U> {
+    type Output = U;
+
+    fn index(&self, index: usize) -> &U {
+        match self.get(index) {
+            Some(value) => value,
+            None => panic!(
+                "index out of bounds: the len is {} but the index is {}",
+                self.len(),
+                index
+            ),
+        }
+    }
+}
+
+impl<'a, U: ?Sized + fmt::Debug> fmt::Debug for RoSlice<'a, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, U: ?Sized> IntoIterator for RoSlice<'a, U> {
+    type Item = &'a U;
+    type IntoIter = RoSliceIter<'a, U>;
+
+    fn into_iter(self) -> RoSliceIter<'a, U> {
+        self.iter()
+    }
+}
+
+impl<'a, U: ?Sized> IntoIterator for &RoSlice<'a, U> {
+    type Item = &'a U;
+    type IntoIter = RoSliceIter<'a, U>;
+
+    fn into_iter(self) -> RoSliceIter<'a, U> {
+        self.iter()
+    }
+}
+
+/// Iterator over the contents of the cells in a [`RoSlice`]
+///
+/// [`RoSlice`]: struct.RoSlice.html
+pub struct RoSliceIter<'a, U: ?Sized> {
+    slice: RoSlice<'a, U>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, U: ?Sized> Iterator for RoSliceIter<'a, U> {
+    type Item = &'a U;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a U> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.slice.get(self.front - 1)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, U: ?Sized> DoubleEndedIterator for RoSliceIter<'a, U> {
+    #[inline]
+    fn next_back(&mut self) -> Option<&'a U> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.slice.get(self.back)
+    }
+}
+
+impl<'a, U: ?Sized> ExactSizeIterator for RoSliceIter<'a, U> {}
+impl<'a, U: ?Sized> FusedIterator for RoSliceIter<'a, U> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LCellOwner, QCell, QCellOwnerSeq};
+    #[cfg(feature = "std")]
+    use crate::{TCell, TCellOwner};
+
+    // One step of Jacobi relaxation over a ring of nodes, each
+    // connected to its two neighbours and to node 0
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn ro_slice_relaxation() {
+        use crate::QCellOwner;
+        const N: usize = 1000;
+        let mut owner = QCellOwner::new();
+        let prev: Vec<_> = (0..N).map(|i| owner.cell(i as f64)).collect();
+        let next: Vec<_> = (0..N).map(|_| owner.cell(0.0)).collect();
+        let edges: Vec<Vec<&QCell<f64>>> = (0..N)
+            .map(|i| {
+                let mut adj = vec![&prev[(i + N - 1) % N], &prev[(i + 1) % N]];
+                if i != 0 {
+                    adj.push(&prev[0]);
+                }
+                adj
+            })
+            .collect();
+        for (i, adj) in edges.iter().enumerate() {
+            let (out, inputs) = owner.rw1_ro_slice(&next[i], adj);
+            assert_eq!(inputs.len(), adj.len());
+            *out = inputs.iter().sum::<f64>() / inputs.len() as f64;
+        }
+        assert_eq!(*owner.ro(&next[0]), (999.0 + 1.0) / 2.0);
+        assert_eq!(*owner.ro(&next[1]), 2.0 / 3.0);
+        assert_eq!(*owner.ro(&next[500]), (499.0 + 501.0) / 3.0);
+
+        // In-place step, reading from the neighbours of each node
+        // while writing the node itself
+        let edges: Vec<Vec<&QCell<f64>>> = (0..N)
+            .map(|i| vec![&next[(i + N - 1) % N], &next[(i + 1) % N]])
+            .collect();
+        for (i, adj) in edges.iter().enumerate() {
+            let (out, inputs) = owner.rw1_ro_slice(&next[i], adj);
+            *out = (inputs[0] + inputs[1] + *out) / 3.0;
+        }
+        let expect = [*owner.ro(&next[1]), *owner.ro(&next[3])];
+        let (_, inputs) = owner.rw1_ro_slice(&prev[0], &edges[2]);
+        assert_eq!(inputs.to_vec(), [&expect[0], &expect[1]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "readers[2] is the same QCell as the writer")]
+    fn ro_slice_aliased_qcell() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cells: [QCell<u32>; 3] = [owner.cell(0), owner.cell(1), owner.cell(2)];
+        let readers = [&cells[1], &cells[1], &cells[0]];
+        owner.rw1_ro_slice(&cells[0], &readers);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[should_panic(expected = "readers[0] is the same TCell as the writer")]
+    fn ro_slice_aliased_tcell() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cell = TCell::new(0);
+        owner.rw1_ro_slice(&cell, &[&cell]);
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn ro_slice_bad_owner() {
+        let mut owner1 = unsafe { QCellOwnerSeq::new() };
+        let owner2 = unsafe { QCellOwnerSeq::new() };
+        let c1 = owner1.cell(0);
+        let c2 = owner2.cell(0);
+        owner1.rw1_ro_slice(&c1, &[&c2]);
+    }
+
+    #[test]
+    fn ro_slice_empty() {
+        LCellOwner::scope(|mut owner| {
+            let cell = owner.cell(5);
+            let (value, readers) = owner.rw1_ro_slice(&cell, &[]);
+            *value += readers.iter().sum::<i32>();
+            assert!(readers.is_empty());
+            assert_eq!(readers.get(0), None);
+            assert_eq!(readers.iter().next(), None);
+            assert_eq!(format!("{:?}", readers), "[]");
+            assert_eq!(*owner.ro(&cell), 5);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ro_slice_iter() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let w = TCell::new(String::new());
+        let cells: Vec<_> = ["a", "b", "c"].iter().map(|s| TCell::new(*s)).collect();
+        let readers: Vec<_> = cells.iter().chain(Some(&cells[0])).collect();
+        let (w, readers) = owner.rw1_ro_slice(&w, &readers);
+        for s in readers.iter().rev() {
+            w.push_str(s);
+        }
+        assert_eq!(w, "acba");
+        assert_eq!(readers.iter().len(), 4);
+        assert_eq!(format!("{:?}", readers), r#"["a", "b", "c", "a"]"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn ro_slice_index_out_of_bounds() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let w = owner.cell(0);
+        let r = owner.cell(1);
+        let refs = [&r];
+        let (_, readers) = owner.rw1_ro_slice(&w, &refs);
+        let _ = readers[1];
+    }
+}
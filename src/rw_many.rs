@@ -0,0 +1,141 @@
+use core::mem;
+
+use crate::panic_messages;
+use crate::util::first_clash;
+
+#[cold]
+#[inline(never)]
+fn duplicate_panic(cell: &str, index1: usize, index2: usize, same: bool) -> ! {
+    if same {
+        panic!(
+            "{}: cells[{}] and cells[{}] are the same {}",
+            panic_messages::RW_MANY_BORROWED_TWICE,
+            index1,
+            index2,
+            cell
+        );
+    }
+    panic!(
+        "{}: cells[{}] and cells[{}] are overlapping {}s",
+        panic_messages::RW_MANY_BORROWED_TWICE,
+        index1,
+        index2,
+        cell
+    );
+}
+
+// Panic if any two of the cells are the same cell, or overlap.  Cells
+// are compared by address, so two `Rc` or `Arc` clones of the same
+// cell count as the same cell, and by size too, so that an `LCell`
+// for a whole slice clashes with the cells for its elements.
+pub(crate) fn distinct_check<C: ?Sized>(cell: &str, cells: &[&C]) {
+    let range = |i: usize| {
+        let cell = cells[i];
+        (cell as *const C as *const () as usize, mem::size_of_val(cell))
+    };
+    if let Some((i, j)) = first_clash(cells.len(), range) {
+        duplicate_panic(cell, i, j, range(i).0 == range(j).0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LCellOwner, QCell, QCellOwner, QCellOwnerSeq};
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[test]
+    fn rw_from_slice_rc() {
+        let mut owner = QCellOwner::new();
+        let cells: Vec<Rc<QCell<u32>>> = (0..5).map(|i| Rc::new(owner.cell(i))).collect();
+        for (i, v) in owner.rw_from_slice(&cells).into_iter().enumerate() {
+            *v += 10 * i as u32;
+        }
+        let values: Vec<u32> = cells.iter().map(|c| owner.get(c)).collect();
+        assert_eq!(values, [0, 11, 22, 33, 44]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cells[1] and cells[3] are the same QCell")]
+    fn rw_from_slice_rc_clone() {
+        let mut owner = QCellOwner::new();
+        let mut cells: Vec<Rc<QCell<u32>>> = (0..3).map(|i| Rc::new(owner.cell(i))).collect();
+        cells.push(cells[1].clone());
+        owner.rw_from_slice(&cells);
+    }
+
+    #[test]
+    #[should_panic(expected = "cells[37] and cells[81] are the same QCell")]
+    fn rw_from_slice_rc_clone_large() {
+        let mut owner = QCellOwner::new();
+        let mut cells: Vec<Rc<QCell<u32>>> = (0..100).map(|i| Rc::new(owner.cell(i))).collect();
+        cells.insert(37, cells[80].clone());
+        owner.rw_from_slice(&cells);
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn rw_from_slice_bad_owner() {
+        let mut owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cells = [Rc::new(owner1.cell(0)), Rc::new(owner2.cell(1))];
+        owner1.rw_from_slice(&cells);
+    }
+
+    #[test]
+    fn rw_from_slice_empty() {
+        let mut owner = QCellOwner::new();
+        let cells: [Rc<QCell<u32>>; 0] = [];
+        assert!(owner.rw_from_slice(&cells).is_empty());
+        assert!(owner.rw_from_iter::<u32, QCell<u32>>(None).is_empty());
+    }
+
+    #[test]
+    fn rw_from_iter_arc() {
+        // Safety: Only one `QCellOwnerSeq` is used in this test
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cells: Vec<Arc<QCell<String>>> = (0..4)
+            .map(|i| Arc::new(owner.cell(i.to_string())))
+            .collect();
+        for s in owner.rw_from_iter(cells.iter().step_by(2)) {
+            s.push('!');
+        }
+        let values: Vec<String> = cells.iter().map(|c| owner.get_cloned(c)).collect();
+        assert_eq!(values, ["0!", "1", "2!", "3"]);
+    }
+
+    #[test]
+    fn rw_from_iter_lcell() {
+        LCellOwner::scope(|mut owner| {
+            let cells: Vec<_> = (0..3).map(|i| Rc::new(owner.cell(i))).collect();
+            let mut refs = owner.rw_from_iter(cells.iter().rev());
+            *refs[0] += 100;
+            assert_eq!(owner.get(&cells[2]), 102);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[should_panic(expected = "cells[0] and cells[2] are the same TCell")]
+    fn rw_from_slice_tcell_arc_clone() {
+        use crate::{TCell, TCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cell = Arc::new(TCell::new(0));
+        owner.rw_from_slice(&[cell.clone(), Arc::new(TCell::new(1)), cell]);
+    }
+
+    #[test]
+    fn rw_from_slice_10k() {
+        let mut owner = QCellOwner::new();
+        let cells: Vec<Rc<QCell<u64>>> = (0..10_000).map(|i| Rc::new(owner.cell(i))).collect();
+        let start = std::time::Instant::now();
+        for v in owner.rw_from_slice(&cells) {
+            *v *= 2;
+        }
+        // Generous bound, to catch an accidental quadratic check
+        // without being flaky on slow machines
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(owner.get(&cells[9999]), 19998);
+    }
+}
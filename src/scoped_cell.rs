@@ -0,0 +1,234 @@
+use core::mem;
+
+#[cfg(feature = "std")]
+use crate::TLCell;
+use crate::{LCell, QCell, QCellOwnerID, TCell};
+
+// Moves the value back out of the temporary cell into the caller's
+// variable when dropped, including on unwind
+struct Restore<'a, T> {
+    value: &'a mut T,
+    cell: Option<QCell<T>>,
+}
+
+impl<'a, T> Drop for Restore<'a, T> {
+    fn drop(&mut self) {
+        if let Some(cell) = self.cell.take() {
+            *self.value = cell.into_inner();
+        }
+    }
+}
+
+/// Call `f` with a temporary [`QCell`] owned by `owner` which holds
+/// the value from `*value`, and then put the value back.
+///
+/// This allows code written against `&QCell<T>` and an owner to be
+/// called with data which isn't in a cell.  A `QCell` has an owner ID
+/// in front of the value, so a `&mut T` can't be viewed as a
+/// `&QCell<T>` in place.  Instead the value is moved into a new cell,
+/// leaving `T::default()` behind, and moved back out when `f`
+/// returns.  Any changes made through the owner during `f` are kept.
+/// If `f` panics, the value is still moved back whilst unwinding, so
+/// the caller's variable holds the value as it was at the point of
+/// the panic.  The placeholder is never visible to the caller, since
+/// `*value` is borrowed throughout.  To avoid the `Default` bound, use
+/// [`scoped_cell_with`].
+///
+/// For the cell types with no header, [`TCell::scoped_cell`],
+/// [`TLCell::scoped_cell`] and [`LCell::scoped_cell`] do the same
+/// thing in place, without moving the value.
+///
+#[cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ # use qcell::{scoped_cell, QCell, QCellOwner};
+ // A library function written against cells
+ fn push_twice(owner: &mut QCellOwner, cell: &QCell<Vec<u32>>, v: u32) {
+     owner.rw(cell).extend([v, v]);
+ }
+
+ let mut owner = QCellOwner::new();
+ let mut list = vec![1];
+ let len = scoped_cell(owner.id(), &mut list, |cell| {
+     push_twice(&mut owner, cell, 2);
+     owner.ro(cell).len()
+ });
+ assert_eq!(len, 3);
+ assert_eq!(list, [1, 2, 2]);
+ ```
+"
+)]
+///
+/// [`QCell`]: struct.QCell.html
+/// [`scoped_cell_with`]: fn.scoped_cell_with.html
+/// [`TCell::scoped_cell`]: struct.TCell.html#method.scoped_cell
+/// [`TLCell::scoped_cell`]: struct.TLCell.html#method.scoped_cell
+/// [`LCell::scoped_cell`]: struct.LCell.html#method.scoped_cell
+#[inline]
+pub fn scoped_cell<T: Default, R>(
+    owner: impl Into<QCellOwnerID>,
+    value: &mut T,
+    f: impl FnOnce(&QCell<T>) -> R,
+) -> R {
+    scoped_cell_with(owner, value, T::default(), f)
+}
+
+/// Same as [`scoped_cell`], but leaving `placeholder` behind in
+/// `*value` whilst the value is in the cell, instead of
+/// `T::default()`.  The placeholder is dropped when the value is moved
+/// back.
+///
+/// [`scoped_cell`]: fn.scoped_cell.html
+pub fn scoped_cell_with<T, R>(
+    owner: impl Into<QCellOwnerID>,
+    value: &mut T,
+    placeholder: T,
+    f: impl FnOnce(&QCell<T>) -> R,
+) -> R {
+    let cell = owner.into().cell(mem::replace(value, placeholder));
+    let restore = Restore {
+        value,
+        cell: Some(cell),
+    };
+    f(restore.cell.as_ref().unwrap())
+}
+
+impl<Q, T: ?Sized> TCell<Q, T> {
+    /// Call `f` with `value` viewed in place as a `TCell`, for
+    /// passing to code written against cells.  This is the same as
+    /// calling `f` with [`TCell::from_mut`], and is named to match
+    /// [`scoped_cell`], which has to move the value into a `QCell`
+    /// instead.
+    ///
+    #[cfg_attr(
+        any(feature = "std", feature = "exclusion-set"),
+        doc = "
+ ```
+ # use qcell::{TCell, TCellOwner};
+ # qcell::marker!(struct Marker;);
+ fn bump(owner: &mut TCellOwner<Marker>, cell: &TCell<Marker, u32>) {
+     *owner.rw(cell) += 1;
+ }
+
+ let mut owner = TCellOwner::<Marker>::new();
+ let mut count = 1;
+ TCell::scoped_cell(&mut count, |cell| bump(&mut owner, cell));
+ assert_eq!(count, 2);
+ ```
+"
+    )]
+    ///
+    /// [`TCell::from_mut`]: struct.TCell.html#method.from_mut
+    /// [`scoped_cell`]: fn.scoped_cell.html
+    #[inline]
+    pub fn scoped_cell<R>(value: &mut T, f: impl FnOnce(&Self) -> R) -> R {
+        f(Self::from_mut(value))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Q, T: ?Sized> TLCell<Q, T> {
+    /// Call `f` with `value` viewed in place as a `TLCell`, for
+    /// passing to code written against cells.  This is the same as
+    /// calling `f` with [`TLCell::from_mut`], and is named to match
+    /// [`scoped_cell`], which has to move the value into a `QCell`
+    /// instead.
+    ///
+    /// [`TLCell::from_mut`]: struct.TLCell.html#method.from_mut
+    /// [`scoped_cell`]: fn.scoped_cell.html
+    #[inline]
+    pub fn scoped_cell<R>(value: &mut T, f: impl FnOnce(&Self) -> R) -> R {
+        f(Self::from_mut(value))
+    }
+}
+
+impl<'id, T: ?Sized> LCell<'id, T> {
+    /// Call `f` with `value` viewed in place as an `LCell`, for
+    /// passing to code written against cells.  This is the same as
+    /// calling `f` with [`LCell::from_mut`], and is named to match
+    /// [`scoped_cell`], which has to move the value into a `QCell`
+    /// instead.
+    ///
+    /// [`LCell::from_mut`]: struct.LCell.html#method.from_mut
+    /// [`scoped_cell`]: fn.scoped_cell.html
+    #[inline]
+    pub fn scoped_cell<R>(value: &mut T, f: impl FnOnce(&Self) -> R) -> R {
+        f(Self::from_mut(value))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{scoped_cell, scoped_cell_with};
+    use crate::{LCell, LCellOwner, QCell, QCellOwner, TCell, TCellOwner, TLCell, TLCellOwner};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    // Library functions written against cells
+    fn lib_q(owner: &mut QCellOwner, cell: &QCell<Vec<i32>>) -> usize {
+        owner.rw(cell).push(10);
+        owner.ro(cell).len()
+    }
+
+    crate::marker!(struct Marker;);
+
+    fn lib_t(owner: &mut TCellOwner<Marker>, cell: &TCell<Marker, Vec<i32>>) -> usize {
+        owner.rw(cell).push(20);
+        owner.ro(cell).len()
+    }
+
+    #[test]
+    fn scoped_qcell() {
+        let mut owner = QCellOwner::new();
+        let mut data = vec![1, 2];
+        assert_eq!(scoped_cell(owner.id(), &mut data, |c| lib_q(&mut owner, c)), 3);
+        assert_eq!(data, [1, 2, 10]);
+
+        // Without `Default`
+        struct NoDefault(u32);
+        let mut value = NoDefault(5);
+        scoped_cell_with(owner.id(), &mut value, NoDefault(0), |c| {
+            assert_eq!(owner.ro(c).0, 5);
+            owner.rw(c).0 += 1;
+        });
+        assert_eq!(value.0, 6);
+    }
+
+    #[test]
+    fn scoped_qcell_unwind_restores() {
+        let mut owner = QCellOwner::new();
+        let mut data = vec![1];
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            scoped_cell(owner.id(), &mut data, |c| {
+                owner.rw(c).push(2);
+                panic!("in scoped_cell");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(data, [1, 2]);
+    }
+
+    #[test]
+    fn scoped_transparent_cells() {
+        let mut owner = TCellOwner::<Marker>::new();
+        let mut data = vec![1, 2, 3];
+        assert_eq!(TCell::scoped_cell(&mut data, |c| lib_t(&mut owner, c)), 4);
+        assert_eq!(data, [1, 2, 3, 20]);
+        let ptr = data.as_ptr();
+        // In place, so the cell saw the very same `Vec`
+        TCell::<Marker, _>::scoped_cell(&mut data, |c: &TCell<Marker, Vec<i32>>| {
+            assert_eq!(owner.ro(c).as_ptr(), ptr);
+        });
+
+        crate::marker!(struct TlMarker;);
+        let mut tl_owner = TLCellOwner::<TlMarker>::new();
+        let mut count = 1;
+        TLCell::scoped_cell(&mut count, |c| *tl_owner.rw(c) += 1);
+        assert_eq!(count, 2);
+
+        LCellOwner::scope(|mut owner| {
+            LCell::scoped_cell(&mut count, |c| *owner.rw(c) *= 10);
+        });
+        assert_eq!(count, 20);
+    }
+}
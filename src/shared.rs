@@ -0,0 +1,127 @@
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+
+use crate::{QCell, QCellOwnerID};
+
+/// A [`QCell`] behind an `Rc`, which is the usual way to share a
+/// cell between several parts of a data structure on one thread.
+///
+/// ```
+///# use qcell::{new_shared, QCellOwner, Shared};
+/// struct Node {
+///     next: Option<Shared<Node>>,
+///     value: u32,
+/// }
+/// let mut owner = QCellOwner::new();
+/// let tail: Shared<Node> = new_shared(&owner, Node { next: None, value: 2 });
+/// let head = new_shared(&owner, Node { next: Some(tail.clone()), value: 1 });
+/// owner.rw(&tail).value += owner.ro(&head).value;
+/// assert_eq!(owner.ro(owner.ro(&head).next.as_ref().unwrap()).value, 3);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+pub type Shared<T> = Rc<QCell<T>>;
+
+/// A [`QCell`] behind an `Arc`, to share a cell between threads.
+/// The owner still has to be passed between the threads, or shared
+/// behind a lock, to get at the contents.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "
+ ```
+ # use qcell::{new_shared_sync, QCellOwner, SharedSync};
+ let owner = QCellOwner::new();
+ let total: SharedSync<u64> = new_shared_sync(&owner, 0);
+ let transfer = owner.into_transfer();
+ let handle = {
+     let total = total.clone();
+     std::thread::spawn(move || {
+         let mut owner = transfer.redeem();
+         *owner.rw(&total) += 10;
+         owner.into_transfer()
+     })
+ };
+ let owner = handle.join().unwrap().redeem();
+ assert_eq!(*owner.ro(&total), 10);
+ ```
+"
+)]
+///
+/// [`QCell`]: struct.QCell.html
+pub type SharedSync<T> = Arc<QCell<T>>;
+
+/// Create a new [`QCell`] owned by the given owner, inside an `Rc`.
+/// See [`Shared`].
+///
+/// ```
+///# use qcell::{new_shared, QCellOwner};
+/// let mut owner = QCellOwner::new();
+/// let cell = new_shared(&owner, String::from("a"));
+/// owner.rw(&cell).push('b');
+/// assert_eq!(owner.ro(&cell), "ab");
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`Shared`]: type.Shared.html
+#[inline]
+pub fn new_shared<T>(owner: impl Into<QCellOwnerID>, value: T) -> Shared<T> {
+    Rc::new(QCell::new(owner, value))
+}
+
+/// Create a new [`QCell`] owned by the given owner, inside an `Arc`.
+/// See [`SharedSync`].
+///
+/// ```
+///# use qcell::{new_shared_sync, QCellOwner};
+/// let owner = QCellOwner::new();
+/// let cell = new_shared_sync(owner.id(), 5_u32);
+/// assert_eq!(owner.get(&cell), 5);
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`SharedSync`]: type.SharedSync.html
+#[inline]
+pub fn new_shared_sync<T>(owner: impl Into<QCellOwnerID>, value: T) -> SharedSync<T> {
+    Arc::new(QCell::new(owner, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_shared, new_shared_sync, Shared, SharedSync};
+    use crate::{QCellOwner, QCellOwnerSeq};
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[test]
+    fn shared_clones_share_contents() {
+        let mut owner = QCellOwner::new();
+        let a: Shared<Vec<u8>> = new_shared(&owner, vec![1]);
+        let b = a.clone();
+        owner.rw(&b).push(2);
+        assert_eq!(*owner.ro(&a), [1, 2]);
+        assert_eq!(Rc::strong_count(&a), 2);
+    }
+
+    #[test]
+    fn shared_with_id_only() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let id = owner.id();
+        let cells: Vec<SharedSync<u32>> = (0..4).map(|i| new_shared_sync(id, i)).collect();
+        for c in &cells {
+            *owner.rw(c) *= 2;
+        }
+        assert_eq!(cells.iter().map(|c| owner.get(c)).sum::<u32>(), 12);
+        assert_eq!(Arc::strong_count(&cells[0]), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn shared_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cell = new_shared(&owner1, 0);
+        owner2.ro(&cell);
+    }
+}
@@ -0,0 +1,674 @@
+// All the unsafe operations of the crate, apart from the `Send` and
+// `Sync` impls, go through the functions in this module.  Each states
+// exactly which facts its caller has to establish, and each call site
+// has a one-line `Safety:` comment saying where those facts come
+// from.  So auditing the crate means checking the handful of
+// functions here, and then checking that each call site establishes
+// the contract it cites.  The `unsafe_only_in_sound` test checks that
+// no other unsafe blocks have crept in elsewhere.
+//
+// The facts which the cell accessors establish are:
+//
+// - **Owned**: the cell belongs to the owner in hand.  For `QCell`
+//   this is the owner ID check; for `TCell` and `TLCell` it is
+//   implied by the marker type, since only one owner per marker can
+//   exist; for `LCell` it is implied by the brand lifetime; for
+//   `TShardCell` it is the shard check; for a `PartOwner` it is the
+//   membership check, backed by the owner ID and overlap checks made
+//   when partitioning.
+//
+// - **Owner borrow**: the owner is borrowed for the lifetime of the
+//   returned references, immutably for shared access and mutably for
+//   unique access.  Since all access to cell contents goes through an
+//   owner borrow, this is what excludes conflicting access.  For an
+//   owner split into a `WriteHalf` and `ReadHalf`, the borrow is
+//   made by a write or read phase, which the phase protocol keeps
+//   apart.
+//
+// - **Distinct**: where several references are returned for unique
+//   access, the cells were checked to be different cells.
+
+#![deny(unsafe_op_in_unsafe_fn)]
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::pin::Pin;
+
+#[cfg(feature = "ghost-compat")]
+use crate::ghost_compat::GhostCell;
+use crate::ro_slice::ValuePtr;
+use crate::{LCell, TCell};
+#[cfg(feature = "std")]
+use crate::TLCell;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering::SeqCst};
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+#[cfg(feature = "unsize")]
+use alloc::{boxed::Box, rc::Rc};
+#[cfg(feature = "unsize")]
+use unsize::{CoerceUnsize, Coercion};
+
+/// Borrow the contents of a cell immutably, for as long as the cell is
+/// borrowed.
+///
+/// # Safety
+///
+/// No mutable reference to the contents may exist whilst the returned
+/// reference is live.  For a cell, the caller establishes this with
+/// **Owned** for the cell and an immutable (or mutable) **Owner
+/// borrow** for the same lifetime as the cell borrow.
+#[inline(always)]
+pub(crate) unsafe fn deref_shared<T: ?Sized>(value: &UnsafeCell<T>) -> &T {
+    // Safety: No `&mut T` can exist, as above
+    unsafe { &*value.get() }
+}
+
+/// Borrow the contents of a cell mutably, for as long as the cell is
+/// borrowed.
+///
+/// # Safety
+///
+/// No other reference to the contents may exist whilst the returned
+/// reference is live.  For a cell, the caller establishes this with
+/// **Owned** for the cell and a mutable **Owner borrow** for the same
+/// lifetime as the cell borrow.  If the caller calls this more than
+/// once under the same owner borrow, it must also have established
+/// **Distinct** for all the cells.
+#[inline(always)]
+#[allow(clippy::mut_from_ref)]
+pub(crate) unsafe fn deref_unique<T: ?Sized>(value: &UnsafeCell<T>) -> &mut T {
+    // Safety: No other reference can exist, as above
+    unsafe { &mut *value.get() }
+}
+
+/// Borrow the contents of two cells mutably for `'a`.
+///
+/// # Safety
+///
+/// As for [`deref_unique`] on each cell, plus **Distinct** for the
+/// two cells.
+#[inline(always)]
+#[allow(clippy::mut_from_ref)]
+pub(crate) unsafe fn deref_unique2<'a, T: ?Sized, U: ?Sized>(
+    v1: &'a UnsafeCell<T>,
+    v2: &'a UnsafeCell<U>,
+) -> (&'a mut T, &'a mut U) {
+    // Safety: The cells are distinct, so the references don't alias
+    unsafe { (deref_unique(v1), deref_unique(v2)) }
+}
+
+/// Borrow the contents of three cells mutably for `'a`.
+///
+/// # Safety
+///
+/// As for [`deref_unique`] on each cell, plus **Distinct** for all
+/// three cells.
+#[inline(always)]
+#[allow(clippy::mut_from_ref)]
+pub(crate) unsafe fn deref_unique3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
+    v1: &'a UnsafeCell<T>,
+    v2: &'a UnsafeCell<U>,
+    v3: &'a UnsafeCell<V>,
+) -> (&'a mut T, &'a mut U, &'a mut V) {
+    // Safety: The cells are distinct, so the references don't alias
+    unsafe { (deref_unique(v1), deref_unique(v2), deref_unique(v3)) }
+}
+
+/// Borrow the contents of a cell mutably for `'a`, given the pointer
+/// to its contents from `UnsafeCell::get`.
+///
+/// # Safety
+///
+/// The pointer must have come from `UnsafeCell::get` on a cell which
+/// is borrowed for `'a`.  Otherwise as for [`deref_unique`] on that
+/// cell, including **Distinct** if called more than once under the
+/// same owner borrow.
+#[inline(always)]
+pub(crate) unsafe fn deref_unique_ptr<'a, T: ?Sized>(ptr: *const T) -> &'a mut T {
+    // Safety: The pointer is into an `UnsafeCell`, and no other
+    // reference can exist, as above
+    unsafe { &mut *(ptr as *mut T) }
+}
+
+/// Borrow the contents of a cell mutably, and prepare lazy immutable
+/// access to the contents of a slice of cells, all for `'a`.
+///
+/// # Safety
+///
+/// The caller must have established **Owned** for the writer and for
+/// every reader, hold a mutable **Owner borrow** for `'a`, and have
+/// checked that the writer is not among the readers.  The readers may
+/// repeat, since they are only borrowed immutably.
+#[inline(always)]
+#[allow(clippy::mut_from_ref)]
+pub(crate) unsafe fn deref_unique_with_readers<'a, T: ?Sized, C: ValuePtr + ?Sized>(
+    writer: &'a UnsafeCell<T>,
+    readers: &'a [&'a C],
+) -> (&'a mut T, ErasedReaders<'a, C::Value>) {
+    let readers = ErasedReaders {
+        base: readers.as_ptr() as *const (),
+        len: readers.len(),
+        get: value_at::<C>,
+        phantom: PhantomData,
+    };
+    // Safety: The writer is not among the readers, so the mutable
+    // reference doesn't alias any of the immutable ones
+    (unsafe { deref_unique(writer) }, readers)
+}
+
+// Fetch the contents pointer of entry `index` of a `&[&C]` slice
+// starting at `base`.  Safety: `base` must have come from a `&[&C]`
+// slice that is still live, and `index` must be in range.
+unsafe fn value_at<C: ValuePtr + ?Sized>(base: *const (), index: usize) -> *const C::Value {
+    // Safety: As above
+    unsafe { (*(base as *const &C).add(index)).value_ptr() }
+}
+
+/// Immutable access to the contents of a slice of cells of any type
+/// whose contents are `U`, with the cell type erased.  This can only
+/// be created by [`deref_unique_with_readers`], which is what makes
+/// the accessors safe.
+pub(crate) struct ErasedReaders<'a, U: ?Sized> {
+    base: *const (),
+    len: usize,
+    get: unsafe fn(*const (), usize) -> *const U,
+    phantom: PhantomData<&'a [&'a U]>,
+}
+
+// Safety: This only gives out `&'a U`, so it can be shared or sent
+// wherever `&'a [&'a U]` could be
+unsafe impl<'a, U: ?Sized + Sync> Send for ErasedReaders<'a, U> {}
+unsafe impl<'a, U: ?Sized + Sync> Sync for ErasedReaders<'a, U> {}
+
+impl<'a, U: ?Sized> Clone for ErasedReaders<'a, U> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, U: ?Sized> Copy for ErasedReaders<'a, U> {}
+
+impl<'a, U: ?Sized> ErasedReaders<'a, U> {
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, index: usize) -> Option<&'a U> {
+        if index < self.len {
+            // Safety: The slice is borrowed for `'a` and `index` is in
+            // range.  The contract of `deref_unique_with_readers`
+            // makes the contents safe to borrow immutably for `'a`.
+            Some(unsafe { &*(self.get)(self.base, index) })
+        } else {
+            None
+        }
+    }
+}
+
+/// Turn a raw pointer back into a reference for `'a`.
+///
+/// # Safety
+///
+/// The pointer must have been derived from a reference which is
+/// valid for `'a`, and no mutable reference to the target may exist
+/// during `'a`.
+#[inline(always)]
+pub(crate) unsafe fn deref_raw<'a, T: ?Sized>(ptr: *const T) -> &'a T {
+    // Safety: As above
+    unsafe { &*ptr }
+}
+
+/// Turn a raw pointer back into a mutable reference for `'a`.
+///
+/// # Safety
+///
+/// The pointer must have been derived from a mutable reference which
+/// is valid for `'a`, and no other reference to the target may be
+/// used during `'a`.
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) unsafe fn deref_raw_mut<'a, T: ?Sized>(ptr: *mut T) -> &'a mut T {
+    // Safety: As above
+    unsafe { &mut *ptr }
+}
+
+/// Pin a reference to a value which is known to be pinned.
+///
+/// # Safety
+///
+/// The target must never be moved again before it is dropped, and
+/// the target's memory must not be reused before it is dropped.  This
+/// holds for a field that is structurally pinned within a value that
+/// has already been pinned.
+#[inline(always)]
+pub(crate) unsafe fn pin_unchecked<P: Deref>(ptr: P) -> Pin<P> {
+    // Safety: As above
+    unsafe { Pin::new_unchecked(ptr) }
+}
+
+/// Get mutable access to the contents of a pinned value, in order to
+/// project the pin onto its fields.
+///
+/// # Safety
+///
+/// Nothing may be moved out of the returned reference.  Fields that
+/// are structurally pinned must only be handed out pinned, and the
+/// value's type must not implement `Drop` or `Unpin` in a way that
+/// moves them.
+#[inline(always)]
+pub(crate) unsafe fn pin_get_unchecked_mut<T>(pin: Pin<&mut T>) -> &mut T {
+    // Safety: As above
+    unsafe { Pin::get_unchecked_mut(pin) }
+}
+
+/// Borrow the contents of a slice of cells mutably as one slice of
+/// values.
+///
+/// # Safety
+///
+/// `C` must be `repr(transparent)` over `UnsafeCell<T>`, so that the
+/// two slices have the same layout.  The caller must have established
+/// **Owned** for all the cells, and hold a mutable **Owner borrow**
+/// for `'a`.  Elements of a slice are always **Distinct**.
+#[cfg(feature = "scoped-threads")]
+#[inline(always)]
+#[allow(clippy::mut_from_ref)]
+pub(crate) unsafe fn transparent_slice_mut<C, T>(cells: &[C]) -> &mut [T] {
+    // Safety: Same layout, and all the bytes are within `UnsafeCell`s,
+    // so mutation through a shared reference is allowed
+    unsafe { core::slice::from_raw_parts_mut(cells.as_ptr() as *mut T, cells.len()) }
+}
+
+/// Reinterpret an erased `LCell` value as an `LCell` of brand `'id`.
+///
+/// # Safety
+///
+/// The caller must have established that the owner of brand `'id` has
+/// exclusive rights to the value, just as if the cell had been
+/// created in its scope.  `LCell` is `repr(transparent)` over the
+/// `UnsafeCell`, so the layout is the same.
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) unsafe fn lcell_from_erased<'a, 'id, T: ?Sized>(
+    value: &'a UnsafeCell<T>,
+) -> &'a LCell<'id, T> {
+    // Safety: Same layout, and the brand has exclusive rights
+    unsafe { &*(value as *const UnsafeCell<T> as *const LCell<'id, T>) }
+}
+
+/// View a mutable reference to a value as a mutable reference to an
+/// `LCell` of any brand containing it.  This needs nothing from the
+/// caller: the value is borrowed mutably for `'a`, so no owner can
+/// reach it by any other route, and `LCell` is `repr(transparent)`
+/// over `UnsafeCell<T>`, which has the same layout as `T`.
+#[inline(always)]
+pub(crate) fn lcell_from_mut<'a, 'id, T: ?Sized>(value: &'a mut T) -> &'a mut LCell<'id, T> {
+    // Safety: Same layout, and the borrow is unique
+    unsafe { &mut *(value as *mut T as *mut LCell<'id, T>) }
+}
+
+/// View a mutable reference to a value as a mutable reference to a
+/// `TCell` containing it.  This needs nothing from the caller, for
+/// the same reasons as `lcell_from_mut`: the borrow is unique, and
+/// `TCell` is `repr(transparent)` over `UnsafeCell<T>`.
+#[inline(always)]
+pub(crate) fn tcell_from_mut<Q, T: ?Sized>(value: &mut T) -> &mut TCell<Q, T> {
+    // Safety: Same layout, and the borrow is unique
+    unsafe { &mut *(value as *mut T as *mut TCell<Q, T>) }
+}
+
+/// View a mutable reference to a value as a mutable reference to a
+/// `TLCell` containing it.  This needs nothing from the caller, for
+/// the same reasons as `lcell_from_mut`: the borrow is unique, and
+/// `TLCell` is `repr(transparent)` over `UnsafeCell<T>`.
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn tlcell_from_mut<Q, T: ?Sized>(value: &mut T) -> &mut TLCell<Q, T> {
+    // Safety: Same layout, and the borrow is unique
+    unsafe { &mut *(value as *mut T as *mut TLCell<Q, T>) }
+}
+
+/// View an `LCell` containing a slice as a slice of `LCell`s with the
+/// same brand.  This needs nothing from the caller, for the same
+/// reasons as `Cell::as_slice_of_cells`: `UnsafeCell<[T]>` has the
+/// same layout as `[UnsafeCell<T>]`, and the owner which controls
+/// access to the whole slice also controls each element.  The cell
+/// for the whole slice overlaps the cells for the elements, so the
+/// owner's checks for borrowing several cells at once compare byte
+/// ranges, see `util::clash`.
+#[inline(always)]
+pub(crate) fn lcell_slice_of_cells<'a, 'id, T>(cells: &'a LCell<'id, [T]>) -> &'a [LCell<'id, T>] {
+    // Safety: Same layout, and the same owner
+    unsafe { &*(cells as *const LCell<'id, [T]> as *const [LCell<'id, T>]) }
+}
+
+/// View a mutable reference to an `LCell` as a mutable reference to a
+/// `GhostCell` wrapping it.  This needs nothing from the caller,
+/// since `GhostCell` is `repr(transparent)` over `LCell`.
+#[cfg(feature = "ghost-compat")]
+#[inline(always)]
+pub(crate) fn ghost_cell_from_lcell<'a, 'id, T: ?Sized>(
+    cell: &'a mut LCell<'id, T>,
+) -> &'a mut GhostCell<'id, T> {
+    // Safety: Same layout
+    unsafe { &mut *(cell as *mut LCell<'id, T> as *mut GhostCell<'id, T>) }
+}
+
+/// View a slice of `LCell`s as a slice of the `GhostCell`s wrapping
+/// them.  This needs nothing from the caller, since `GhostCell` is
+/// `repr(transparent)` over `LCell`.
+#[cfg(feature = "ghost-compat")]
+#[inline(always)]
+pub(crate) fn ghost_cells_from_lcells<'a, 'id, T>(
+    cells: &'a [LCell<'id, T>],
+) -> &'a [GhostCell<'id, T>] {
+    // Safety: Same layout
+    unsafe { &*(cells as *const [LCell<'id, T>] as *const [GhostCell<'id, T>]) }
+}
+
+/// Remove the entry for `key` from an exclusion set.
+///
+/// # Safety
+///
+/// The caller must not call this concurrently with another removal
+/// of the same key, which is the precondition of
+/// `exclusion_set::Set::remove`.
+#[cfg(feature = "exclusion-set")]
+#[inline(always)]
+pub(crate) unsafe fn exclusion_set_remove<T: Eq>(set: &exclusion_set::Set<T>, key: &T) {
+    // Safety: As above
+    unsafe {
+        set.remove(key);
+    }
+}
+
+/// Copy of an `Arc` held as an atomic pointer, for `RcuCell`, which
+/// any thread may clone without a lock.
+///
+/// The pointer holds one strong count.  Readers announce themselves
+/// in one of two counters, chosen by the low bit of `epoch`, before
+/// loading the pointer and incrementing its strong count, and leave
+/// afterwards.  A writer swaps in the new pointer, and then waits for
+/// each counter in turn to drain, flipping the epoch before each wait
+/// so that readers arriving later use the other counter and can't
+/// hold it up.  Any reader which loaded the old pointer announced
+/// itself before the swap, so once both counters have been seen at
+/// zero since the swap, that reader has finished incrementing the
+/// strong count, and the writer may release the old pointer's count.
+#[cfg(feature = "std")]
+pub(crate) struct ArcMirror<T> {
+    ptr: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    // Sharing this shares the `Arc`, so needs the same bounds
+    _arc: PhantomData<Arc<T>>,
+}
+
+#[cfg(feature = "std")]
+impl<T> ArcMirror<T> {
+    pub(crate) fn new(value: Arc<T>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Arc::into_raw(value) as *mut T),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            _arc: PhantomData,
+        }
+    }
+
+    /// Clone the current `Arc`.  This is wait-free.
+    pub(crate) fn load(&self) -> Arc<T> {
+        let readers = &self.readers[self.epoch.load(SeqCst) & 1];
+        readers.fetch_add(1, SeqCst);
+        let ptr = self.ptr.load(SeqCst);
+        // Safety: `ptr` came from `Arc::into_raw`, and its strong
+        // count can't be released by `arc_mirror_store` until this
+        // reader has left
+        let value = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        readers.fetch_sub(1, SeqCst);
+        value
+    }
+}
+
+/// Replace the `Arc` held by `mirror` with `value`, returning the old
+/// one once no reader can still be cloning it.  Blocks until readers
+/// which started before the swap have finished, which takes just a
+/// few instructions each.
+///
+/// # Safety
+///
+/// Calls for the same `mirror` must not overlap, since the epoch and
+/// counters only track one writer.  `RcuCell` establishes this with
+/// a mutable **Owner borrow** of the cell holding its own copy of the
+/// `Arc`.
+#[cfg(feature = "std")]
+pub(crate) unsafe fn arc_mirror_store<T>(mirror: &ArcMirror<T>, value: Arc<T>) -> Arc<T> {
+    let old = mirror.ptr.swap(Arc::into_raw(value) as *mut T, SeqCst);
+    for _ in 0..2 {
+        let epoch = mirror.epoch.fetch_add(1, SeqCst);
+        while mirror.readers[epoch & 1].load(SeqCst) != 0 {
+            std::thread::yield_now();
+        }
+    }
+    // Safety: `old` came from `Arc::into_raw`, and all readers which
+    // could have loaded it have finished, as above
+    unsafe { Arc::from_raw(old) }
+}
+
+#[cfg(feature = "std")]
+impl<T> Drop for ArcMirror<T> {
+    fn drop(&mut self) {
+        // Safety: The pointer came from `Arc::into_raw`, and no reader
+        // can be active, since `self` is borrowed mutably
+        drop(unsafe { Arc::from_raw(*self.ptr.get_mut()) });
+    }
+}
+
+/// Convert an `Arc` of an unsized type back into an `Arc` of the sized
+/// type that it was coerced from.
+///
+/// # Safety
+///
+/// The value behind `arc` must have been created as a `T`, in an
+/// `Arc<T>` which was then coerced to `Arc<U>`.  For a `QCell`, the
+/// caller establishes this by checking the type of the contents,
+/// since a `QCell<dyn Any>` can only be made by coercing a
+/// `QCell<T>`.  `QCell` is `repr(C)`, so the contents are at the same
+/// offset either way.
+#[cfg(feature = "alloc")]
+#[inline(always)]
+pub(crate) unsafe fn arc_downcast<T, U: ?Sized>(arc: Arc<U>) -> Arc<T> {
+    // Safety: The pointer came from an `Arc<T>`, as above
+    unsafe { Arc::from_raw(Arc::into_raw(arc).cast::<T>()) }
+}
+
+/// Borrow the last value of an arena chunk for `'a`, which may be
+/// longer than the borrow of the chunk.
+///
+/// # Safety
+///
+/// The chunk must not be empty.  For the whole of `'a`, the chunk's
+/// buffer must not be reallocated or freed, and the value must not be
+/// moved, dropped or mutably borrowed.  `CellArena` establishes this
+/// by only pushing onto a chunk within its capacity, never removing
+/// values, and tying `'a` to a borrow of the arena, which owns the
+/// chunks.
+#[cfg(feature = "alloc")]
+#[inline(always)]
+pub(crate) unsafe fn arena_last<'a, T>(chunk: &[T]) -> &'a T {
+    let last: *const T = &chunk[chunk.len() - 1];
+    // Safety: As above
+    unsafe { &*last }
+}
+
+/// Borrow the values of an arena chunk from `start` onwards for `'a`,
+/// which may be longer than the borrow of the chunk.
+///
+/// # Safety
+///
+/// As for [`arena_last`], for each of the values.  `LCellArena` and
+/// `TCellArena` establish this in the same way as `CellArena`.
+#[cfg(feature = "alloc")]
+#[inline(always)]
+pub(crate) unsafe fn arena_tail<'a, T>(chunk: &[T], start: usize) -> &'a [T] {
+    let tail: *const [T] = &chunk[start..];
+    // Safety: As above
+    unsafe { &*tail }
+}
+
+/// Turn a pointer stored in the indexed marker table back into the
+/// function it was made from.
+///
+/// # Safety
+///
+/// The pointer must have been made by casting a `MarkerInfo` function
+/// pointer.  The indexed marker table establishes this by only ever
+/// storing null or such a pointer in its slots, and checking for null
+/// before calling this.
+#[cfg(target_has_atomic = "64")]
+#[inline(always)]
+pub(crate) unsafe fn marker_info_from_ptr(ptr: *mut ()) -> crate::tcell_indexed::MarkerInfo {
+    // Safety: The pointer came from a `MarkerInfo`, as above
+    unsafe { core::mem::transmute::<*mut (), crate::tcell_indexed::MarkerInfo>(ptr) }
+}
+
+/// Make a coercion from a function which converts a pointer to one
+/// cell type into a pointer to the unsized form of the same cell.
+///
+/// # Safety
+///
+/// `coerce` must return a pointer to the same address as its
+/// argument, with the pointer metadata for the unsized form, and do
+/// nothing else.  The `unsize` module establishes this by passing the
+/// contents pointer through a `Coercion` for the contents, which
+/// carries the same contract, and then moving the address back to the
+/// start of the cell.  For `TCell`, `TLCell` and `LCell`, which are
+/// `repr(transparent)`, the contents are at the start of the cell.
+/// For `QCell`, which is `repr(C)`, the contents are at the same
+/// offset in the sized and unsized forms, since the offset depends
+/// only on the alignment of the contents, which is unchanged.
+#[cfg(feature = "unsize")]
+#[inline(always)]
+pub(crate) unsafe fn cell_coercion<T, U: ?Sized, F>(coerce: F) -> Coercion<T, U, F>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    // Safety: `coerce` only unsizes the pointer, as above
+    unsafe { Coercion::new(coerce) }
+}
+
+/// Unsize the value in a `Box` using a `Coercion`.
+#[cfg(feature = "unsize")]
+#[inline]
+pub(crate) fn unsize_box<T, U: ?Sized, F>(b: Box<T>, with: Coercion<T, U, F>) -> Box<U>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let ptr: *mut U = Box::into_raw(b).unsize(with);
+    // Safety: A `Coercion` only unsizes the pointer, so this is the
+    // same allocation, holding a value with the same layout
+    unsafe { Box::from_raw(ptr) }
+}
+
+/// Unsize the value in an `Rc` using a `Coercion`.
+#[cfg(feature = "unsize")]
+#[inline]
+pub(crate) fn unsize_rc<T, U: ?Sized, F>(rc: Rc<T>, with: Coercion<T, U, F>) -> Rc<U>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let ptr: *const U = Rc::into_raw(rc).unsize(with);
+    // Safety: As for `unsize_box`.  `U` is an unsized form of `T`,
+    // which `Rc::from_raw` allows.
+    unsafe { Rc::from_raw(ptr) }
+}
+
+/// Unsize the value in an `Arc` using a `Coercion`.
+#[cfg(feature = "unsize")]
+#[inline]
+pub(crate) fn unsize_arc<T, U: ?Sized, F>(arc: Arc<T>, with: Coercion<T, U, F>) -> Arc<U>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let ptr: *const U = Arc::into_raw(arc).unsize(with);
+    // Safety: As for `unsize_rc`
+    unsafe { Arc::from_raw(ptr) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    // Check that `line` at position `i` of `lines` is allowed to
+    // mention `unsafe` outside of this module
+    fn allowed(lines: &[&str], i: usize) -> Result<(), &'static str> {
+        let line = lines[i].trim();
+        if line.starts_with("unsafe impl") {
+            return if line.contains(" Send for ") || line.contains(" Sync for ") {
+                Ok(())
+            } else {
+                Err("unsafe impl of a trait other than Send or Sync")
+            };
+        }
+        if line.starts_with("pub unsafe fn ") || line.starts_with("pub unsafe trait ") {
+            // Public API with a documented contract.  The body is
+            // checked line by line like any other code.
+            return Ok(());
+        }
+        if line.starts_with("(unsafe $") {
+            // Macro arm requiring `unsafe` from the caller, who takes
+            // on the contract of the public unsafe function called
+            return Ok(());
+        }
+        let rest = line.split_once("unsafe").map_or("", |x| x.1);
+        if !rest.starts_with(" { sound::") && !rest.starts_with(" { $crate::__") {
+            return Err("unsafe block not calling straight into `sound`");
+        }
+        let start = i.saturating_sub(3);
+        if !lines[start..i].iter().any(|l| l.contains("// Safety: ")) {
+            return Err("unsafe block without a `// Safety:` comment");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unsafe_only_in_sound() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut failures = Vec::new();
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            if !name.ends_with(".rs")
+                || name == "sound.rs"
+                || name == "assertions.rs"
+                || name.starts_with("doctest_")
+            {
+                continue;
+            }
+            let text = fs::read_to_string(&path).unwrap();
+            // Test code only uses the public API, so stop there
+            let lines: Vec<&str> = text
+                .lines()
+                .take_while(|l| !l.ends_with("mod tests {"))
+                .collect();
+            for i in 0..lines.len() {
+                let code = lines[i].split("//").next().unwrap();
+                if !code.contains("unsafe") {
+                    continue;
+                }
+                if let Err(e) = allowed(&lines, i) {
+                    failures.push(format!("{}:{}: {}", name, i + 1, e));
+                }
+            }
+        }
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+}
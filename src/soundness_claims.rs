@@ -0,0 +1,655 @@
+//! The claims on which the soundness of the crate rests, each with
+//! the tests that check it.
+//!
+//! The [crate documentation](../index.html#why-this-is-safe) gives
+//! the reasoning in brief.  Here it is split into separate claims,
+//! each with an identifier, so that a soundness review can take them
+//! one at a time, and so that each can be traced to evidence.  Every
+//! claim has at least one `#[test]` in this module showing the
+//! allowed pattern, which is run under Miri by `./run-test-miri` to
+//! check that it involves no undefined behaviour.  Where a pattern is
+//! prevented, a `compile_fail` example below, a `should_panic` test,
+//! or both, show that it is rejected.  The `compile_fail` examples
+//! are also checked with `trybuild` in `trybuild-qcell`, so that they
+//! fail for the expected reason.  A test in this module checks that
+//! every test named in the table exists.
+//!
+//! The `doctest_*` modules, such as [`doctest_qcell`], have many more
+//! `compile_fail` examples for each cell type.
+//!
+//! | ID | Claim | Tests |
+//! |----|-------|-------|
+//! | [G1](#g1-exclusive-gatekeeping) | Cell contents are only reachable through the owner, or through exclusive access to the cell itself | `g1_access_through_owner`, `g1_wrong_owner_panics` |
+//! | [G2](#g2-borrow-mirroring) | A shared borrow of the contents needs a shared borrow of the owner, and a mutable one needs a mutable borrow | `g2_shared_borrows_coexist`, `g2_mutable_borrow_ends_before_next` |
+//! | [G3](#g3-drop-non-interference) | A cell can't be dropped or moved whilst its contents are borrowed | `g3_drop_without_owner`, `g3_drop_other_cell_whilst_borrowed` |
+//! | [G4](#g4-distinct-mutable-borrows) | Several mutable borrows at once are only given for distinct cells | `g4_rw2_rw3_distinct_cells`, `g4_qcell_rw2_same_cell_panics`, `g4_tcell_rw3_same_cell_panics` |
+//! | [Q1](#q1-qcellowner-id-temporal-uniqueness) | No two live `QCellOwner` instances have the same ID | `q1_live_owner_ids_distinct`, `q1_other_owner_panics` |
+//! | [S1](#s1-qcellownerseq-counter-monotonicity) | `QCellOwnerSeq` IDs come from a counter which only increases, and never match an address-based ID | `s1_ids_increase`, `s1_ids_distinct_across_threads` |
+//! | [P1](#p1-qcellownerpinned-address-stability) | A `QCellOwnerPinned` ID is only taken once the owner is pinned, so its address can't change whilst the ID is in use | `p1_moved_before_pin`, `p1_pinned_owners_distinct`, `p1_other_pinned_owner_panics` |
+//! | [T1](#t1-tcell-marker-singleton-and-invariance) | Only one `TCellOwner` exists per marker type, and the marker type can't be changed by subtyping | `t1_one_owner_per_marker`, `t1_subtype_markers_are_distinct` |
+//! | [TL1](#tl1-tlcell-per-thread-exclusivity) | Only one `TLCellOwner` exists per marker type per thread, and neither it nor a `TLCell` can be used from two threads at once | `tl1_one_owner_per_thread`, `tl1_cell_moves_between_threads` |
+//! | [L1](#l1-lcell-brand-invariance) | Each `LCellOwner` has a brand which no other owner shares, and a cell can't change its brand | `l1_nested_scopes`, `l1_brand_fixed_by_first_use` |
+//!
+//! # G1: Exclusive gatekeeping
+//!
+//! Between the creation and destruction of a cell, the only ways to
+//! reach its contents are through the owner, or through exclusive
+//! access to the cell itself (`get_mut` on a `&mut` cell, or
+//! `into_inner`).  So the owner is the gatekeeper of the contents.
+//! The cell types don't implement `Deref`:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 100);
+//! let value: &u32 = &*cell; // Compile fail
+//! ```
+//!
+//! Using a `QCell` with an owner other than its own is a runtime
+//! error.  For the other cell types, the owner is part of the type,
+//! so it is a compile error.
+//!
+//! # G2: Borrow mirroring
+//!
+//! The borrowing calls need a `&` owner reference to return a `&`
+//! reference to the contents, or a `&mut` owner reference to return a
+//! `&mut` reference.  So the borrow of the contents is the same kind
+//! as the borrow of the owner, and lasts no longer.  Whilst that
+//! borrow is active, Rust blocks incompatible borrows of the owner,
+//! which blocks incompatible borrows of all its cells.  The contents
+//! act as if they were owned by the owner, like the elements of a
+//! `Vec`.
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let c1 = QCell::new(&owner, 100);
+//! let c2 = QCell::new(&owner, 200);
+//! let r1 = owner.ro(&c1);
+//! let r2 = owner.rw(&c2); // Compile fail
+//! assert_eq!(*r1, 100);
+//! ```
+//!
+//! # G3: Drop non-interference
+//!
+//! The owner has no control over when a cell is dropped, so it can't
+//! act as gatekeeper at that point.  However a borrow of the contents
+//! also borrows the cell, so the cell can't be dropped or moved
+//! whilst the borrow is active.  If the cell is behind an `Rc`, the
+//! last strong reference can't be released until the borrow ends.
+//! The contents are dropped without the owner, which is fine since no
+//! borrow can be active.  This holds for each cell type:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 100);
+//! let r = owner.ro(&cell);
+//! drop(cell); // Compile fail
+//! assert_eq!(*r, 100);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//!# struct Marker;
+//! let owner = TCellOwner::<Marker>::new();
+//! let cell = TCell::<Marker, _>::new(100);
+//! let r = owner.ro(&cell);
+//! drop(cell); // Compile fail
+//! assert_eq!(*r, 100);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{TLCell, TLCellOwner};
+//!# struct Marker;
+//! let owner = TLCellOwner::<Marker>::new();
+//! let cell = TLCell::<Marker, _>::new(100);
+//! let r = owner.ro(&cell);
+//! drop(cell); // Compile fail
+//! assert_eq!(*r, 100);
+//! ```
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|owner| {
+//!     let cell = LCell::new(100);
+//!     let r = owner.ro(&cell);
+//!     drop(cell); // Compile fail
+//!     assert_eq!(*r, 100);
+//! });
+//! ```
+//!
+//! Moving the cell is blocked in the same way:
+//!
+//! ```compile_fail
+//!# use qcell::{QCell, QCellOwner};
+//! let mut owner = QCellOwner::new();
+//! let cell = QCell::new(&owner, 100);
+//! let r = owner.rw(&cell);
+//! let moved = cell; // Compile fail
+//! *r += 1;
+//! ```
+//!
+//! # G4: Distinct mutable borrows
+//!
+//! `rw2`, `rw3` and the other calls which return several `&mut`
+//! references at once check at runtime that the cells are all
+//! different, and panic if not.
+//!
+//! # Q1: `QCellOwner` ID temporal uniqueness
+//!
+//! The ID of a [`QCellOwner`] is the address of a heap allocation
+//! which the owner holds until it is dropped, so no two live owners
+//! can have the same ID.  The ID may be given to another owner once
+//! the first is dropped, but then there is still only one owner with
+//! that ID, and that owner can only reach the old cells by being
+//! given references to them.  Debug builds also detect such reuse,
+//! using a nonce.
+//!
+//! # S1: `QCellOwnerSeq` counter monotonicity
+//!
+//! [`QCellOwnerSeq`] IDs come from a global counter which is only
+//! ever increased, by 2, starting from an odd number.  So each call
+//! gives a new ID until the counter wraps, which is why
+//! `QCellOwnerSeq::new` is `unsafe`.  The IDs are always odd, and
+//! address-based IDs are always even, so they never match.
+//!
+//! # P1: `QCellOwnerPinned` address stability
+//!
+//! A [`QCellOwnerPinned`] only gives out its ID through `Pin<&Self>`,
+//! and is `!Unpin`, so once the ID has been taken the owner can't be
+//! moved until it is dropped.  Moving the owner before it is pinned
+//! is fine, since no ID has been taken from it yet.  The owner can't
+//! be used unpinned:
+//!
+//! ```compile_fail
+//!# use qcell::QCellOwnerPinned;
+//! let owner = QCellOwnerPinned::new();
+//! let cell = owner.cell(100); // Compile fail
+//! ```
+//!
+//! Nor moved out of its pin:
+//!
+//! ```compile_fail
+//!# use qcell::QCellOwnerPinned;
+//!# use std::pin::Pin;
+//! let pinned = Box::pin(QCellOwnerPinned::new());
+//! let cell = pinned.as_ref().cell(100);
+//! let owner = *Pin::into_inner(pinned); // Compile fail
+//! ```
+//!
+//! # T1: `TCell` marker singleton and invariance
+//!
+//! Only one [`TCellOwner`] may exist at a time for each marker type,
+//! which is checked at runtime when the owner is created.  Since the
+//! marker type identifies the owner, the marker type of a
+//! `TCellOwner` or `TCell` must not be changeable.  Before version
+//! 0.4.3, the marker type parameter was covariant, so an owner for
+//! `fn(&())` could be coerced to an owner for `fn(&'static ())`, a
+//! different type with its own owner, giving two owners for the same
+//! cells.  Now the marker type parameter is invariant, so this fails
+//! to compile:
+//!
+//! ```compile_fail
+//!# use qcell::{TCell, TCellOwner};
+//! type MarkerA = fn(&());
+//! type MarkerB = fn(&'static ());
+//! let owner1 = TCellOwner::<MarkerA>::new() as TCellOwner<MarkerB>; // Compile fail
+//! ```
+//!
+//! # TL1: `TLCell` per-thread exclusivity
+//!
+//! Only one [`TLCellOwner`] may exist at a time for each marker type
+//! on each thread.  The owner is not `Send`, so it stays on that
+//! thread.  A [`TLCell`] may be sent to another thread and used with
+//! that thread's owner, but it is not `Sync`, so it can't be used
+//! from two threads at once:
+//!
+//! ```compile_fail
+//!# use qcell::{TLCell, TLCellOwner};
+//!# use std::sync::Arc;
+//!# struct Marker;
+//! let cell = Arc::new(TLCell::<Marker, u32>::new(100));
+//! let cell2 = cell.clone();
+//! std::thread::spawn(move || { // Compile fail
+//!     let owner = TLCellOwner::<Marker>::new();
+//!     assert_eq!(*owner.ro(&cell2), 100);
+//! });
+//! ```
+//!
+//! # L1: `LCell` brand invariance
+//!
+//! Each [`LCellOwner`] has a brand, which is its `'id` lifetime.  The
+//! brand is invariant, and each call to `LCellOwner::scope` creates a
+//! new one which can't escape the closure, so no two owners have the
+//! same brand.  A cell takes the brand of the first owner it is used
+//! with, and using it with any other owner fails to compile.  See
+//! [`lcell_brands`] for the errors this gives.
+//!
+//! ```compile_fail
+//!# use qcell::{LCell, LCellOwner};
+//! LCellOwner::scope(|owner1| {
+//!     let cell = LCell::new(100);
+//!     assert_eq!(*owner1.ro(&cell), 100);
+//!     LCellOwner::scope(|owner2| {
+//!         let _ = owner2.ro(&cell); // Compile fail
+//!     });
+//! });
+//! ```
+//!
+//! [`doctest_qcell`]: ../doctest_qcell/index.html
+//! [`lcell_brands`]: ../lcell_brands/index.html
+//! [`QCellOwner`]: ../struct.QCellOwner.html
+//! [`QCellOwnerSeq`]: ../struct.QCellOwnerSeq.html
+//! [`QCellOwnerPinned`]: ../struct.QCellOwnerPinned.html
+//! [`TCellOwner`]: ../struct.TCellOwner.html
+//! [`TLCellOwner`]: ../struct.TLCellOwner.html
+//! [`TLCell`]: ../struct.TLCell.html
+//! [`LCellOwner`]: ../struct.LCellOwner.html
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        marker, LCell, LCellOwner, QCell, QCellOwner, QCellOwnerPinned, QCellOwnerSeq, TCell,
+        TCellOwner, TLCell, TLCellOwner,
+    };
+    use pin_utils::pin_mut;
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::panic::catch_unwind;
+    use std::rc::Rc;
+    use std::thread;
+
+    // Counts how many times it is dropped
+    struct DropCount<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCount<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    // Check that each test named in the claims table exists, and that
+    // each test here belongs to a claim in the table
+    #[test]
+    fn claims_traceability() {
+        let source = include_str!("soundness_claims.rs");
+        let tests: HashSet<&str> = source
+            .split("\n    #[test]\n")
+            .skip(1)
+            .filter_map(|rest| {
+                let line = rest.lines().find(|l| !l.trim_start().starts_with("#["))?;
+                let name = line.trim_start().strip_prefix("fn ")?;
+                Some(&name[..name.find('(')?])
+            })
+            .collect();
+        let mut claims = Vec::new();
+        let mut listed = HashSet::new();
+        for row in source.lines().filter(|l| l.starts_with("//! | [")) {
+            let id = &row[7..row.find(']').unwrap()];
+            let cols: Vec<&str> = row.split(" | ").collect();
+            let names: Vec<&str> = cols[cols.len() - 1]
+                .split('`')
+                .skip(1)
+                .step_by(2)
+                .collect();
+            assert!(!names.is_empty(), "claim {} has no tests", id);
+            let prefix = format!("{}_", id.to_lowercase());
+            for name in names {
+                assert!(name.starts_with(&prefix), "{} listed under {}", name, id);
+                assert!(tests.contains(name), "{} for claim {} is missing", name, id);
+                listed.insert(name);
+            }
+            claims.push(prefix);
+        }
+        assert_eq!(claims.len(), 10);
+        for test in tests {
+            if test != "claims_traceability" {
+                assert!(listed.contains(test), "{} is not in the claims table", test);
+            }
+        }
+    }
+
+    #[test]
+    fn g1_access_through_owner() {
+        let mut owner = QCellOwner::new();
+        let mut cell = QCell::new(&owner, vec![1]);
+        owner.rw(&cell).push(2);
+        cell.get_mut().push(3);
+        assert_eq!(owner.ro(&cell), &[1, 2, 3]);
+        assert_eq!(cell.into_inner(), [1, 2, 3]);
+
+        marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let mut cell = TCell::<Marker, _>::new(1);
+        *owner.rw(&cell) += 1;
+        *cell.get_mut() += 1;
+        assert_eq!(cell.into_inner(), 3);
+
+        let mut owner = TLCellOwner::<Marker>::new();
+        let mut cell = TLCell::<Marker, _>::new(1);
+        *owner.rw(&cell) += 1;
+        *cell.get_mut() += 1;
+        assert_eq!(cell.into_inner(), 3);
+
+        LCellOwner::scope(|mut owner| {
+            let mut cell = LCell::new(1);
+            *owner.rw(&cell) += 1;
+            *cell.get_mut() += 1;
+            assert_eq!(cell.into_inner(), 3);
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn g1_wrong_owner_panics() {
+        let owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let cell = QCell::new(&owner1, 100);
+        let _ = owner2.ro(&cell);
+    }
+
+    #[test]
+    fn g2_shared_borrows_coexist() {
+        let owner = QCellOwner::new();
+        let c1 = QCell::new(&owner, 1);
+        let c2 = QCell::new(&owner, 2);
+        let (r1, r2, r3) = (owner.ro(&c1), owner.ro(&c2), owner.ro(&c1));
+        assert_eq!(*r1 + *r2 + *r3, 4);
+
+        marker!(struct Marker;);
+        let owner = TCellOwner::<Marker>::new();
+        let c1 = TCell::<Marker, _>::new(1);
+        let (r1, r2) = (owner.ro(&c1), owner.ro(&c1));
+        assert_eq!(*r1 + *r2, 2);
+    }
+
+    #[test]
+    fn g2_mutable_borrow_ends_before_next() {
+        let mut owner = QCellOwner::new();
+        let cell = QCell::new(&owner, 1);
+        let r = owner.rw(&cell);
+        *r += 1;
+        let r2 = owner.rw(&cell);
+        *r2 += 1;
+        assert_eq!(*owner.ro(&cell), 3);
+    }
+
+    #[test]
+    fn g3_drop_without_owner() {
+        let drops = Cell::new(0);
+        let owner = QCellOwner::new();
+        let cell = Rc::new(QCell::new(&owner, DropCount(&drops)));
+        let cell2 = cell.clone();
+        let _ = owner.ro(&cell);
+        drop(cell);
+        assert_eq!(drops.get(), 0);
+        // The contents are dropped after the owner, without it
+        drop(owner);
+        drop(cell2);
+        assert_eq!(drops.get(), 1);
+
+        marker!(struct Marker;);
+        let owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::<Marker, _>::new(DropCount(&drops));
+        let _ = owner.ro(&cell);
+        drop(owner);
+        drop(cell);
+        assert_eq!(drops.get(), 2);
+
+        LCellOwner::scope(|owner| {
+            let cell = LCell::new(DropCount(&drops));
+            let _ = owner.ro(&cell);
+        });
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn g3_drop_other_cell_whilst_borrowed() {
+        let drops = Cell::new(0);
+        let mut owner = QCellOwner::new();
+        let c1 = QCell::new(&owner, 1);
+        let c2 = QCell::new(&owner, DropCount(&drops));
+        let r = owner.rw(&c1);
+        drop(c2);
+        *r += 1;
+        assert_eq!(drops.get(), 1);
+        assert_eq!(*owner.ro(&c1), 2);
+    }
+
+    #[test]
+    fn g4_rw2_rw3_distinct_cells() {
+        let mut owner = QCellOwner::new();
+        let c1 = QCell::new(&owner, 1);
+        let c2 = QCell::new(&owner, 2);
+        let c3 = QCell::new(&owner, 3);
+        let (r1, r2, r3) = owner.rw3(&c1, &c2, &c3);
+        std::mem::swap(r1, r3);
+        *r2 += 10;
+        assert_eq!((*owner.ro(&c1), *owner.ro(&c2), *owner.ro(&c3)), (3, 12, 1));
+
+        marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let c1 = TLCell::<Marker, _>::new(1);
+        let c2 = TLCell::<Marker, _>::new(2);
+        let (r1, r2) = owner.rw2(&c1, &c2);
+        std::mem::swap(r1, r2);
+        assert_eq!(*owner.ro(&c1), 2);
+
+        LCellOwner::scope(|mut owner| {
+            let c1 = LCell::new(1);
+            let c2 = LCell::new(2);
+            let (r1, r2) = owner.rw2(&c1, &c2);
+            std::mem::swap(r1, r2);
+            assert_eq!(*owner.ro(&c1), 2);
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn g4_qcell_rw2_same_cell_panics() {
+        let mut owner = QCellOwner::new();
+        let cell = QCell::new(&owner, 1);
+        let _ = owner.rw2(&cell, &cell);
+    }
+
+    #[test]
+    #[should_panic]
+    fn g4_tcell_rw3_same_cell_panics() {
+        marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let c1 = TCell::<Marker, _>::new(1);
+        let c2 = TCell::<Marker, _>::new(2);
+        let _ = owner.rw3(&c1, &c2, &c1);
+    }
+
+    #[test]
+    fn q1_live_owner_ids_distinct() {
+        let owners: Vec<QCellOwner> = (0..32).map(|_| QCellOwner::new()).collect();
+        let ids: HashSet<usize> = owners.iter().map(|o| o.id().num).collect();
+        assert_eq!(ids.len(), owners.len());
+        assert!(ids.iter().all(|id| id % 2 == 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn q1_other_owner_panics() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let cell = owner1.cell(1);
+        *owner2.rw(&cell) += 1;
+    }
+
+    #[test]
+    fn s1_ids_increase() {
+        let mut last = 0;
+        for _ in 0..16 {
+            // Safety: No other owner is created with this ID
+            let owner = unsafe { QCellOwnerSeq::new() };
+            let id = owner.id().num;
+            assert_eq!(id % 2, 1);
+            assert!(id > last);
+            last = id;
+        }
+        // Safety: No other owners are created with these IDs
+        let batch: [QCellOwnerSeq; 4] = unsafe { QCellOwnerSeq::new_batch() };
+        for owner in &batch {
+            assert!(owner.id().num > last);
+            last = owner.id().num;
+        }
+    }
+
+    #[test]
+    fn s1_ids_distinct_across_threads() {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    (0..8)
+                        // Safety: No other owner is created with this ID
+                        .map(|_| unsafe { QCellOwnerSeq::new() }.id().num)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut ids = HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(ids.insert(id));
+            }
+        }
+        let owner = QCellOwner::new();
+        assert!(!ids.contains(&owner.id().num));
+    }
+
+    #[test]
+    fn p1_moved_before_pin() {
+        let owner = QCellOwnerPinned::new();
+        let moved = [owner];
+        let [owner] = moved;
+        let mut owner = Box::pin(owner);
+        let id = owner.as_ref().id();
+        let cell = owner.as_ref().cell(1);
+        *owner.as_mut().rw(&cell) += 1;
+        // Moving the `Box` doesn't move the owner
+        let owner = std::convert::identity(owner);
+        assert_eq!(owner.as_ref().id(), id);
+        assert_eq!(*owner.as_ref().ro(&cell), 2);
+    }
+
+    #[test]
+    fn p1_pinned_owners_distinct() {
+        let owner1 = QCellOwnerPinned::new();
+        pin_mut!(owner1);
+        let owner2 = Box::pin(QCellOwnerPinned::new());
+        let id1 = owner1.as_ref().id();
+        let id2 = owner2.as_ref().id();
+        assert_ne!(id1, id2);
+        assert_eq!(id1.num % 2, 0);
+        let cell = owner1.as_ref().cell(1);
+        *owner1.as_mut().rw(&cell) += 1;
+        assert_eq!(*owner1.as_ref().ro(&cell), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn p1_other_pinned_owner_panics() {
+        let owner1 = QCellOwnerPinned::new();
+        let owner2 = QCellOwnerPinned::new();
+        pin_mut!(owner1);
+        pin_mut!(owner2);
+        let cell = owner1.as_ref().cell(1);
+        let _ = owner2.as_ref().ro(&cell);
+    }
+
+    #[test]
+    fn t1_one_owner_per_marker() {
+        marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+        let cell = TCell::<Marker, _>::new(1);
+        *owner.rw(&cell) += 1;
+        drop(owner);
+        let owner = TCellOwner::<Marker>::try_new().unwrap();
+        assert_eq!(*owner.ro(&cell), 2);
+    }
+
+    #[cfg(not(feature = "strict-markers"))]
+    #[test]
+    fn t1_subtype_markers_are_distinct() {
+        // The types from the 0.4.3 fix: these are separate markers,
+        // each with its own owner and cells
+        type MarkerA = fn(&());
+        type MarkerB = fn(&'static ());
+        let mut owner_a = TCellOwner::<MarkerA>::new();
+        let mut owner_b = TCellOwner::<MarkerB>::new();
+        let cell_a = TCell::<MarkerA, _>::new(1);
+        let cell_b = TCell::<MarkerB, _>::new(2);
+        let ra = owner_a.rw(&cell_a);
+        let rb = owner_b.rw(&cell_b);
+        std::mem::swap(ra, rb);
+        assert_eq!((*owner_a.ro(&cell_a), *owner_b.ro(&cell_b)), (2, 1));
+    }
+
+    #[test]
+    fn tl1_one_owner_per_thread() {
+        marker!(struct Marker;);
+        let owner = TLCellOwner::<Marker>::new();
+        assert!(catch_unwind(TLCellOwner::<Marker>::new).is_err());
+        let other = thread::spawn(|| {
+            let owner = TLCellOwner::<Marker>::new();
+            assert!(catch_unwind(TLCellOwner::<Marker>::new).is_err());
+            let cell = TLCell::<Marker, _>::new(1);
+            *owner.ro(&cell)
+        });
+        assert_eq!(other.join().unwrap(), 1);
+        drop(owner);
+        let _ = TLCellOwner::<Marker>::new();
+    }
+
+    #[test]
+    fn tl1_cell_moves_between_threads() {
+        marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::<Marker, _>::new(vec![1]);
+        owner.rw(&cell).push(2);
+        let cell = thread::spawn(move || {
+            let mut owner = TLCellOwner::<Marker>::new();
+            owner.rw(&cell).push(3);
+            cell
+        })
+        .join()
+        .unwrap();
+        assert_eq!(owner.ro(&cell), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn l1_nested_scopes() {
+        LCellOwner::scope(|mut outer| {
+            let c1 = LCell::new(1);
+            *outer.rw(&c1) += 1;
+            LCellOwner::scope(|mut inner| {
+                let c2 = LCell::new(10);
+                *inner.rw(&c2) += *outer.ro(&c1);
+                assert_eq!(*inner.ro(&c2), 12);
+            });
+            assert_eq!(*outer.ro(&c1), 2);
+        });
+    }
+
+    #[test]
+    fn l1_brand_fixed_by_first_use() {
+        // The cells have no brand until first used with an owner
+        fn swap<'id>(owner: &mut LCellOwner<'id>, a: &LCell<'id, u32>, b: &LCell<'id, u32>) {
+            let (a, b) = owner.rw2(a, b);
+            std::mem::swap(a, b);
+        }
+        LCellOwner::scope(|mut owner| {
+            let cells = vec![LCell::new(1), LCell::new(2)];
+            for cell in &cells {
+                *owner.rw(cell) *= 10;
+            }
+            swap(&mut owner, &cells[0], &cells[1]);
+            assert_eq!((*owner.ro(&cells[0]), *owner.ro(&cells[1])), (20, 10));
+        });
+    }
+}
@@ -0,0 +1,541 @@
+use core::cell::Cell;
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use crate::QCellOwner;
+use crate::{panic_messages, LCell, LCellOwner, QCell, QCellBorrow, QCellOwnerID, QCellOwnerSeq};
+
+/// Returned by the closure passed to `step`, to say whether the
+/// value in a [`StepCell`] or [`LStepCell`] has more steps to run.
+///
+/// [`StepCell`]: struct.StepCell.html
+/// [`LStepCell`]: struct.LStepCell.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// Put the value back in the cell, to be stepped again later
+    Pending,
+    /// The value is finished with, so take it out of the cell and
+    /// return it in [`StepOutcome::Complete`]
+    ///
+    /// [`StepOutcome::Complete`]: enum.StepOutcome.html#variant.Complete
+    Complete,
+}
+
+/// Result of `step` on a [`StepCell`] or [`LStepCell`]
+///
+/// [`StepCell`]: struct.StepCell.html
+/// [`LStepCell`]: struct.LStepCell.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome<T> {
+    /// The closure returned [`StepResult::Pending`], and the value is
+    /// back in the cell
+    ///
+    /// [`StepResult::Pending`]: enum.StepResult.html#variant.Pending
+    Pending,
+    /// The closure returned [`StepResult::Complete`], and this is the
+    /// value taken out of the cell, which is left empty
+    ///
+    /// [`StepResult::Complete`]: enum.StepResult.html#variant.Complete
+    Complete(T),
+    /// The cell was empty, so the closure was not called
+    Empty,
+}
+
+// State kept outside the owner's protection, so that it can be
+// updated whilst the owner is lent to the closure
+#[derive(Default)]
+struct Flags {
+    stepping: Cell<bool>,
+    poisoned: Cell<bool>,
+}
+
+// Clears the stepping flag when the step ends, and poisons the cell
+// if it ends by unwinding
+struct Stepping<'a> {
+    flags: &'a Flags,
+    returned: bool,
+}
+
+impl Drop for Stepping<'_> {
+    fn drop(&mut self) {
+        self.flags.stepping.set(false);
+        if !self.returned {
+            self.flags.poisoned.set(true);
+        }
+    }
+}
+
+// Take the value out of the cell, call `f` with it and the owner, and
+// put it back again.  `slot` borrows the cell's contents through the
+// owner.
+fn step_with<O, C, T>(
+    flags: &Flags,
+    owner: &mut O,
+    cell: &C,
+    slot: impl for<'b> Fn(&'b mut O, &'b C) -> &'b mut Option<T>,
+    f: impl FnOnce(&mut T, &mut O) -> StepResult,
+) -> StepOutcome<T> {
+    if flags.stepping.get() {
+        panic!("{}", panic_messages::STEP_CELL_REENTERED);
+    }
+    if flags.poisoned.get() {
+        panic!("{}", panic_messages::STEP_CELL_POISONED);
+    }
+    let mut value = match slot(owner, cell).take() {
+        Some(value) => value,
+        None => return StepOutcome::Empty,
+    };
+    flags.stepping.set(true);
+    let mut stepping = Stepping {
+        flags,
+        returned: false,
+    };
+    let result = f(&mut value, owner);
+    stepping.returned = true;
+    drop(stepping);
+    match result {
+        StepResult::Complete => StepOutcome::Complete(value),
+        StepResult::Pending => {
+            // Unless a new value was stored during the step, in which
+            // case that one is kept and this one is dropped
+            let slot = slot(owner, cell);
+            if slot.is_none() {
+                *slot = Some(value);
+            }
+            StepOutcome::Pending
+        }
+    }
+}
+
+/// [`QCell`] holding a state machine or similar value which is
+/// stepped with the owner still available, so that each step may
+/// access other cells.
+///
+/// When a value in a `QCell` needs both `&mut self` and the owner to
+/// make progress, `owner.rw(&cell).step(owner)` doesn't compile,
+/// because the owner is already borrowed to get at the value.
+/// `StepCell` handles this by taking the value out of the cell for the
+/// duration of each step, so that the closure passed to `step` on the
+/// owner gets both the value and the owner, and then putting it back
+/// again afterwards.  The closure returns [`StepResult::Pending`] to
+/// put the value back, or [`StepResult::Complete`] to leave the cell
+/// empty and get the value back from `step`.
+///
+/// Since the value isn't in the cell during a step, stepping the same
+/// cell again from within its own step panics, and [`StepCell::ro`]
+/// returns `None`.  If the closure panics, the value is dropped
+/// during the unwind and the cell is poisoned: [`is_poisoned`]
+/// returns `true` and `step` panics, until [`clear_poison`] is
+/// called.  [`StepCell::replace`] may be used at any time to store a
+/// new value, even during a step, in which case the value being
+/// stepped is dropped at the end of the step if it is `Pending`.
+///
+#[cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ # use qcell::{QCellOwner, StepCell, StepOutcome, StepResult};
+ struct Countdown(u32);
+
+ let mut owner = QCellOwner::new();
+ let ticks = owner.cell(0);
+ let cell = StepCell::new(&owner, Countdown(2));
+ let step = |c: &mut Countdown, owner: &mut QCellOwner| {
+     // Other cells are accessible whilst stepping
+     *owner.rw(&ticks) += 1;
+     c.0 -= 1;
+     if c.0 == 0 { StepResult::Complete } else { StepResult::Pending }
+ };
+ assert!(matches!(owner.step(&cell, step), StepOutcome::Pending));
+ assert!(matches!(owner.step(&cell, step), StepOutcome::Complete(Countdown(0))));
+ assert!(matches!(owner.step(&cell, step), StepOutcome::Empty));
+ assert_eq!(*owner.ro(&ticks), 2);
+ ```
+"
+)]
+///
+/// # Values which are not `Unpin`
+///
+/// `T` must be `Unpin`, because the value is moved out of the cell
+/// and back in again on each step.  A `!Unpin` value such as a future
+/// may have been pinned during an earlier step, and moving it after
+/// that would break the pinning guarantee.  To step one, store it as
+/// `Pin<Box<F>>`, which is `Unpin` itself, and poll it in the closure
+/// with `value.as_mut().poll(cx)`.  Only the box is moved, and the
+/// future stays where it was pinned.
+///
+/// [`QCell`]: struct.QCell.html
+/// [`StepResult::Pending`]: enum.StepResult.html#variant.Pending
+/// [`StepResult::Complete`]: enum.StepResult.html#variant.Complete
+/// [`StepCell::ro`]: #method.ro
+/// [`StepCell::replace`]: #method.replace
+/// [`is_poisoned`]: #method.is_poisoned
+/// [`clear_poison`]: #method.clear_poison
+pub struct StepCell<T: Unpin> {
+    // `None` when empty, stepping or poisoned
+    cell: QCell<Option<T>>,
+    flags: Flags,
+}
+
+impl<T: Unpin> StepCell<T> {
+    /// Create a new cell holding `value`, owned by the given owner
+    /// ID, like [`QCell::new`]
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn new(id: impl Into<QCellOwnerID>, value: T) -> Self {
+        Self {
+            cell: QCell::new(id, Some(value)),
+            flags: Flags::default(),
+        }
+    }
+
+    /// Create a new empty cell owned by the given owner ID
+    #[inline]
+    pub fn empty(id: impl Into<QCellOwnerID>) -> Self {
+        Self {
+            cell: QCell::new(id, None),
+            flags: Flags::default(),
+        }
+    }
+
+    /// Destroy the cell and return the value, if there is one
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        self.cell.into_inner()
+    }
+
+    /// Borrow the value immutably, or get `None` if the cell is
+    /// empty, poisoned or being stepped.  Panics if the cell is not
+    /// owned by this owner.
+    #[inline]
+    pub fn ro<'a, O: QCellBorrow>(&'a self, owner: &'a O) -> Option<&'a T> {
+        owner.qcell_ro(&self.cell).as_ref()
+    }
+
+    /// Store a new value in the cell, returning the old one if there
+    /// was one.  This also clears the poison flag.  Panics if the
+    /// cell is not owned by this owner.
+    pub fn replace<O: QCellBorrow>(&self, owner: &mut O, value: T) -> Option<T> {
+        self.flags.poisoned.set(false);
+        owner.qcell_rw(&self.cell).replace(value)
+    }
+
+    /// Test whether the cell is currently being stepped
+    #[inline]
+    pub fn is_stepping(&self) -> bool {
+        self.flags.stepping.get()
+    }
+
+    /// Test whether the cell is poisoned, because a step panicked
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.flags.poisoned.get()
+    }
+
+    /// Clear the poison flag, leaving the cell empty unless a value
+    /// has been stored with `replace` since
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.flags.poisoned.set(false);
+    }
+}
+
+impl<T: Unpin> fmt::Debug for StepCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StepCell")
+            .field("stepping", &self.is_stepping())
+            .field("poisoned", &self.is_poisoned())
+            .finish_non_exhaustive()
+    }
+}
+
+macro_rules! step_cell_access {
+    ($owner:ty) => {
+        impl $owner {
+            /// Step the value in a [`StepCell`], passing the closure
+            /// both the value and this owner.  See [`StepCell`] for
+            /// the details.  Panics if the cell is being stepped
+            /// already, or is poisoned, or is not owned by this
+            /// owner.  If the closure panics, the cell is left
+            /// poisoned.
+            ///
+            /// [`StepCell`]: struct.StepCell.html
+            pub fn step<T: Unpin>(
+                &mut self,
+                cell: &StepCell<T>,
+                f: impl FnOnce(&mut T, &mut Self) -> StepResult,
+            ) -> StepOutcome<T> {
+                step_with(&cell.flags, self, &cell.cell, |owner, c| owner.rw(c), f)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+step_cell_access!(QCellOwner);
+step_cell_access!(QCellOwnerSeq);
+
+/// [`LCell`] holding a value which is stepped with the owner still
+/// available.  This is the same as [`StepCell`] but for
+/// [`LCellOwner`].
+///
+/// [`LCell`]: struct.LCell.html
+/// [`LCellOwner`]: struct.LCellOwner.html
+/// [`StepCell`]: struct.StepCell.html
+pub struct LStepCell<'id, T: Unpin> {
+    cell: LCell<'id, Option<T>>,
+    flags: Flags,
+}
+
+impl<'id, T: Unpin> LStepCell<'id, T> {
+    /// Create a new cell holding `value`
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self {
+            cell: LCell::new(Some(value)),
+            flags: Flags::default(),
+        }
+    }
+
+    /// Create a new empty cell
+    #[inline]
+    pub fn empty() -> Self {
+        Self {
+            cell: LCell::new(None),
+            flags: Flags::default(),
+        }
+    }
+
+    /// Destroy the cell and return the value, if there is one
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        self.cell.into_inner()
+    }
+
+    /// Borrow the value immutably, or get `None` if the cell is
+    /// empty, poisoned or being stepped
+    #[inline]
+    pub fn ro<'a>(&'a self, owner: &'a LCellOwner<'id>) -> Option<&'a T> {
+        owner.ro(&self.cell).as_ref()
+    }
+
+    /// Store a new value in the cell, returning the old one if there
+    /// was one.  This also clears the poison flag.
+    pub fn replace(&self, owner: &mut LCellOwner<'id>, value: T) -> Option<T> {
+        self.flags.poisoned.set(false);
+        owner.rw(&self.cell).replace(value)
+    }
+
+    /// Test whether the cell is currently being stepped
+    #[inline]
+    pub fn is_stepping(&self) -> bool {
+        self.flags.stepping.get()
+    }
+
+    /// Test whether the cell is poisoned, because a step panicked
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.flags.poisoned.get()
+    }
+
+    /// Clear the poison flag, leaving the cell empty unless a value
+    /// has been stored with `replace` since
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.flags.poisoned.set(false);
+    }
+}
+
+impl<T: Unpin> fmt::Debug for LStepCell<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LStepCell")
+            .field("stepping", &self.is_stepping())
+            .field("poisoned", &self.is_poisoned())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'id> LCellOwner<'id> {
+    /// Step the value in an [`LStepCell`], passing the closure both
+    /// the value and this owner.  See [`StepCell`] for the details.
+    /// Panics if the cell is being stepped already, or is poisoned.
+    /// If the closure panics, the cell is left poisoned.
+    ///
+    /// [`LStepCell`]: struct.LStepCell.html
+    /// [`StepCell`]: struct.StepCell.html
+    pub fn step<T: Unpin>(
+        &mut self,
+        cell: &LStepCell<'id, T>,
+        f: impl FnOnce(&mut T, &mut Self) -> StepResult,
+    ) -> StepOutcome<T> {
+        step_with(&cell.flags, self, &cell.cell, |owner, c| owner.rw(c), f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{LStepCell, StepCell, StepOutcome, StepResult};
+    use crate::panic_messages::tests::panic_message;
+    use crate::panic_messages::{STEP_CELL_POISONED, STEP_CELL_REENTERED};
+    use crate::{LCell, LCellOwner, QCell, QCellOwner};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    // Each machine counts up, copying the other's count when the
+    // other is ahead, and finishes on reaching `target`
+    struct Machine {
+        count: u32,
+        target: u32,
+        other: Option<Rc<StepCell<Machine>>>,
+        log: Rc<QCell<Vec<(char, u32)>>>,
+        name: char,
+    }
+
+    fn step_machine(m: &mut Machine, owner: &mut QCellOwner) -> StepResult {
+        let other = m.other.as_ref().unwrap();
+        if let Some(o) = other.ro(owner) {
+            m.count = m.count.max(o.count);
+        }
+        m.count += 1;
+        owner.rw(&m.log).push((m.name, m.count));
+        if m.count >= m.target {
+            StepResult::Complete
+        } else {
+            StepResult::Pending
+        }
+    }
+
+    #[test]
+    fn step_cell_two_machines() {
+        let mut owner = QCellOwner::new();
+        let log = Rc::new(owner.cell(Vec::new()));
+        let machine = |name, count, target| Machine {
+            count,
+            target,
+            other: None,
+            log: log.clone(),
+            name,
+        };
+        let a = Rc::new(StepCell::new(&owner, machine('a', 0, 6)));
+        let b = Rc::new(StepCell::new(&owner, machine('b', 3, 5)));
+        a.replace(&mut owner, Machine { other: Some(b.clone()), ..machine('a', 0, 6) });
+        b.replace(&mut owner, Machine { other: Some(a.clone()), ..machine('b', 3, 5) });
+
+        let mut done = Vec::new();
+        while done.len() < 2 {
+            for cell in [&a, &b] {
+                if let StepOutcome::Complete(m) = owner.step(cell, step_machine) {
+                    done.push((m.name, m.count));
+                }
+            }
+        }
+        // Once `b` is complete, `a` no longer sees it
+        assert_eq!(
+            *owner.ro(&log),
+            [('a', 4), ('b', 5), ('a', 5), ('a', 6)]
+        );
+        assert_eq!(done, [('b', 5), ('a', 6)]);
+        assert!(matches!(owner.step(&a, step_machine), StepOutcome::Empty));
+        assert!(a.ro(&owner).is_none());
+
+        // Break the `Rc` cycle
+        a.replace(&mut owner, machine('a', 0, 0));
+        b.replace(&mut owner, machine('b', 0, 0));
+    }
+
+    #[test]
+    fn step_cell_reentrant_step_panics() {
+        let mut owner = QCellOwner::new();
+        let cell = Rc::new(StepCell::new(&owner, 0_u32));
+        let cell2 = cell.clone();
+        let msg = panic_message(|| {
+            owner.step(&cell, |_, owner| {
+                assert!(cell2.is_stepping());
+                assert!(cell2.ro(owner).is_none());
+                owner.step(&cell2, |_, _| StepResult::Pending);
+                StepResult::Pending
+            })
+        });
+        assert_eq!(msg, STEP_CELL_REENTERED);
+        // The panic unwound through the outer step too
+        assert!(cell.is_poisoned());
+        assert!(!cell.is_stepping());
+    }
+
+    #[test]
+    fn step_cell_panic_poisons() {
+        let mut owner = QCellOwner::new();
+        let cell = StepCell::new(&owner, vec![1]);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            owner.step(&cell, |v, _| {
+                v.push(2);
+                panic!("step failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(cell.is_poisoned());
+        assert_eq!(cell.ro(&owner), None);
+        let msg = panic_message(|| owner.step(&cell, |_, _| StepResult::Pending));
+        assert_eq!(msg, STEP_CELL_POISONED);
+
+        // Clearing the poison leaves the cell empty
+        cell.clear_poison();
+        assert_eq!(owner.step(&cell, |_, _| StepResult::Pending), StepOutcome::Empty);
+        assert_eq!(cell.replace(&mut owner, vec![3]), None);
+        assert_eq!(owner.step(&cell, |_, _| StepResult::Complete), StepOutcome::Complete(vec![3]));
+
+        // `replace` also clears the poison
+        let _ = catch_unwind(AssertUnwindSafe(|| owner.step(&cell, |_, _| panic!())));
+        cell.replace(&mut owner, vec![4]);
+        assert!(!cell.is_poisoned());
+        assert_eq!(cell.into_inner(), Some(vec![4]));
+    }
+
+    #[test]
+    fn step_cell_replace_during_step() {
+        let mut owner = QCellOwner::new();
+        let cell = Rc::new(StepCell::new(&owner, 1));
+        let cell2 = cell.clone();
+        let outcome = owner.step(&cell, |v, owner| {
+            *v += 1;
+            assert_eq!(cell2.replace(owner, 10), None);
+            StepResult::Pending
+        });
+        assert_eq!(outcome, StepOutcome::Pending);
+        assert_eq!(cell.ro(&owner), Some(&10));
+    }
+
+    fn countdown<'a, 'id>(
+        total: &'a LCell<'id, u32>,
+    ) -> impl Fn(&mut u32, &mut LCellOwner<'id>) -> StepResult + 'a {
+        move |n, owner| {
+            *owner.rw(total) += *n;
+            *n -= 1;
+            if *n == 0 {
+                StepResult::Complete
+            } else {
+                StepResult::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn lstep_cell() {
+        LCellOwner::scope(|mut owner| {
+            let total = owner.cell(0);
+            let a = LStepCell::new(3_u32);
+            let b = LStepCell::empty();
+            let step = countdown(&total);
+            while owner.step(&a, &step) == StepOutcome::Pending {}
+            assert_eq!(*owner.ro(&total), 6);
+            assert_eq!(owner.step(&b, &step), StepOutcome::Empty);
+            b.replace(&mut owner, 1);
+            assert_eq!(b.ro(&owner), Some(&1));
+            let _ = catch_unwind(AssertUnwindSafe(|| owner.step(&b, |_, _| panic!())));
+            assert!(b.is_poisoned());
+            b.clear_poison();
+            assert_eq!(b.into_inner(), None);
+        });
+    }
+}
@@ -0,0 +1,467 @@
+// Range-restricted handles onto the contents of a `QCell` holding a
+// `Vec`, boxed slice or array.
+//
+// A `SubCell` is just a reference to the parent cell plus a range,
+// and every access goes through the owner's `ro` or `rw` on the
+// parent, so the usual owner check applies and no unsafe code is
+// needed.  Disjointness of the ranges is checked when carving, and
+// checked again whenever several handles are borrowed together, since
+// nothing stops the same buffer being carved twice.  The contents may
+// be resized through the parent cell between accesses, so each access
+// also checks the range against the current length.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Range;
+use core::ptr;
+
+use crate::{QCell, QCellOwner, QCellOwnerSeq};
+
+/// Error from carving a cell into [`SubCell`] handles, or from
+/// accessing them.
+///
+/// [`SubCell`]: struct.SubCell.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SubCellError {
+    /// A range given to `carve` does not fit within the contents of
+    /// the cell
+    OutOfBounds {
+        /// Index of the range
+        index: usize,
+        /// Length of the contents of the cell
+        len: usize,
+    },
+    /// Two ranges overlap.  For `carve` these are indices into the
+    /// ranges given, and for `rw_sub2` and `rw_sub3` they are the
+    /// positions of the handles in the call, starting from 0.
+    Overlap {
+        /// Index of the first of the two ranges
+        first: usize,
+        /// Index of the second of the two ranges
+        second: usize,
+    },
+    /// The contents of the cell have shrunk since the handle was
+    /// carved, so that its range no longer fits
+    Stale {
+        /// Range of the handle
+        range: Range<usize>,
+        /// Current length of the contents of the cell
+        len: usize,
+    },
+    /// Handles passed to `rw_sub2` or `rw_sub3` were carved from
+    /// different cells
+    DifferentCells,
+}
+
+impl fmt::Display for SubCellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubCellError::OutOfBounds { index, len } => {
+                write!(f, "range #{} is out of bounds for length {}", index, len)
+            }
+            SubCellError::Overlap { first, second } => {
+                write!(f, "ranges #{} and #{} overlap", first, second)
+            }
+            SubCellError::Stale { range, len } => write!(
+                f,
+                "range {}..{} no longer fits contents of length {}",
+                range.start, range.end, len
+            ),
+            SubCellError::DifferentCells => write!(f, "sub-cells belong to different cells"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SubCellError {}
+
+/// Handle to a range of the elements in a [`QCell`] holding a `Vec`,
+/// boxed slice or array, as returned by `carve` on [`QCellOwner`] or
+/// [`QCellOwnerSeq`].
+///
+/// A buffer shared between several stages of processing can be
+/// carved into disjoint ranges, and each stage given the handle for
+/// its own range.  A stage can then only reach the elements in its
+/// range, through `ro_sub` and `rw_sub` on the owner, and several
+/// ranges of the same buffer can be borrowed mutably at once through
+/// `rw_sub2` and `rw_sub3`.
+///
+/// The handle borrows the parent cell, and accesses still need the
+/// cell's owner, which checks the cell's owner ID as usual.  The
+/// contents of the cell may still be replaced or resized through the
+/// owner, in which case an access with a range that no longer fits
+/// gives [`SubCellError::Stale`].
+///
+/// ```
+///# use qcell::{QCell, QCellOwner};
+/// let mut owner = QCellOwner::new();
+/// let buffer = QCell::new(&owner, vec![0_u8; 16]);
+/// let stages = owner.carve(&buffer, &[0..4, 4..12, 12..16]).unwrap();
+/// let (header, body, trailer) = (&stages[0], &stages[1], &stages[2]);
+///
+/// owner.rw_sub(header).unwrap().copy_from_slice(b"HDR1");
+/// let (body, trailer) = owner.rw_sub2(body, trailer).unwrap();
+/// body.fill(b'.');
+/// trailer.copy_from_slice(&(body.len() as u32).to_le_bytes());
+///
+/// assert_eq!(&owner.ro(&buffer)[..6], b"HDR1..");
+/// assert_eq!(owner.ro_sub(header).unwrap(), b"HDR1");
+/// ```
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`SubCellError::Stale`]: enum.SubCellError.html#variant.Stale
+pub struct SubCell<'a, T, S: ?Sized = Vec<T>> {
+    cell: &'a QCell<S>,
+    range: Range<usize>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, S: ?Sized> SubCell<'a, T, S> {
+    /// Get the range of elements covered by this handle
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Get the number of elements covered by this handle
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    /// Test whether the range is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// Get the cell that this handle was carved from
+    #[inline]
+    pub fn cell(&self) -> &'a QCell<S> {
+        self.cell
+    }
+}
+
+impl<'a, T, S: ?Sized> fmt::Debug for SubCell<'a, T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubCell")
+            .field("range", &self.range)
+            .finish_non_exhaustive()
+    }
+}
+
+// An empty range lying strictly inside another one counts as
+// overlapping it, which keeps the splitting in `split_disjoint` simple
+#[inline]
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+// Check that every range fits within `len` and that no two overlap
+fn check_carve(ranges: &[Range<usize>], len: usize) -> Result<(), SubCellError> {
+    for (index, range) in ranges.iter().enumerate() {
+        if range.start > range.end || range.end > len {
+            return Err(SubCellError::OutOfBounds { index, len });
+        }
+    }
+    let mut order: Vec<usize> = (0..ranges.len()).collect();
+    order.sort_unstable_by_key(|&i| (ranges[i].start, ranges[i].end));
+    // In order of start, each range only has to be compared with the
+    // earlier range which reaches furthest
+    let mut furthest: Option<usize> = None;
+    for &i in &order {
+        if let Some(f) = furthest {
+            if overlaps(&ranges[f], &ranges[i]) {
+                return Err(SubCellError::Overlap {
+                    first: f.min(i),
+                    second: f.max(i),
+                });
+            }
+        }
+        if furthest.map_or(true, |f| ranges[i].end > ranges[f].end) {
+            furthest = Some(i);
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn check_fits(range: &Range<usize>, len: usize) -> Result<(), SubCellError> {
+    if range.end > len {
+        return Err(SubCellError::Stale {
+            range: range.clone(),
+            len,
+        });
+    }
+    Ok(())
+}
+
+// Check that handles to be borrowed together are of the same cell,
+// still fit, and don't overlap
+fn check_together<T, S: ?Sized>(
+    subs: &[&SubCell<'_, T, S>],
+    len: usize,
+) -> Result<(), SubCellError> {
+    for sub in subs {
+        if !ptr::eq(sub.cell, subs[0].cell) {
+            return Err(SubCellError::DifferentCells);
+        }
+        check_fits(&sub.range, len)?;
+    }
+    for second in 1..subs.len() {
+        for first in 0..second {
+            if overlaps(&subs[first].range, &subs[second].range) {
+                return Err(SubCellError::Overlap { first, second });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Split a slice into the given ranges, which must fit within it and
+// not overlap according to `overlaps`
+fn split_disjoint<'a, T, const N: usize>(
+    mut slice: &'a mut [T],
+    ranges: [&Range<usize>; N],
+) -> [&'a mut [T]; N] {
+    let mut order = [0; N];
+    for (i, o) in order.iter_mut().enumerate() {
+        *o = i;
+    }
+    order.sort_unstable_by_key(|&i| (ranges[i].start, ranges[i].end));
+    let mut parts: [Option<&'a mut [T]>; N] = [(); N].map(|_| None);
+    let mut offset = 0;
+    for &i in &order {
+        let range = ranges[i];
+        let rest = mem::take(&mut slice);
+        let (_, rest) = rest.split_at_mut(range.start - offset);
+        let (part, rest) = rest.split_at_mut(range.len());
+        parts[i] = Some(part);
+        slice = rest;
+        offset = range.end;
+    }
+    parts.map(Option::unwrap)
+}
+
+macro_rules! sub_cell_methods {
+    ($owner:ty) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+        impl $owner {
+            /// Carve the contents of a cell holding a `Vec`, boxed
+            /// slice or array into [`SubCell`] handles for the given
+            /// ranges.  Fails if any range is out of bounds of the
+            /// current contents, or if any two ranges overlap.  Empty
+            /// ranges are allowed, but not strictly inside another
+            /// range.  Panics if the cell does not belong to this owner.
+            ///
+            /// [`SubCell`]: struct.SubCell.html
+            pub fn carve<'a, T, S: AsRef<[T]> + ?Sized>(
+                &self,
+                qc: &'a QCell<S>,
+                ranges: &[Range<usize>],
+            ) -> Result<Vec<SubCell<'a, T, S>>, SubCellError> {
+                check_carve(ranges, self.ro(qc).as_ref().len())?;
+                Ok(ranges
+                    .iter()
+                    .map(|range| SubCell {
+                        cell: qc,
+                        range: range.clone(),
+                        phantom: PhantomData,
+                    })
+                    .collect())
+            }
+
+            /// Borrow the elements in the range of a [`SubCell`]
+            /// immutably.  Fails with [`SubCellError::Stale`] if the
+            /// contents have shrunk so that the range no longer fits.
+            /// Panics if the cell does not belong to this owner.
+            ///
+            /// [`SubCell`]: struct.SubCell.html
+            /// [`SubCellError::Stale`]: enum.SubCellError.html#variant.Stale
+            pub fn ro_sub<'a, T, S: AsRef<[T]> + ?Sized>(
+                &'a self,
+                sub: &'a SubCell<'_, T, S>,
+            ) -> Result<&'a [T], SubCellError> {
+                let slice = self.ro(sub.cell).as_ref();
+                check_fits(&sub.range, slice.len())?;
+                Ok(&slice[sub.range.clone()])
+            }
+
+            /// Borrow the elements in the range of a [`SubCell`]
+            /// mutably.  Fails with [`SubCellError::Stale`] if the
+            /// contents have shrunk so that the range no longer fits.
+            /// Panics if the cell does not belong to this owner.
+            ///
+            /// [`SubCell`]: struct.SubCell.html
+            /// [`SubCellError::Stale`]: enum.SubCellError.html#variant.Stale
+            pub fn rw_sub<'a, T, S: AsMut<[T]> + ?Sized>(
+                &'a mut self,
+                sub: &'a SubCell<'_, T, S>,
+            ) -> Result<&'a mut [T], SubCellError> {
+                let slice = self.rw(sub.cell).as_mut();
+                check_fits(&sub.range, slice.len())?;
+                Ok(&mut slice[sub.range.clone()])
+            }
+
+            /// Borrow the elements in the ranges of two [`SubCell`]
+            /// handles of the same cell mutably at the same time.
+            /// Fails if the handles are of different cells, if either
+            /// range no longer fits, or if the ranges overlap, which
+            /// is possible if the cell was carved more than once.
+            /// Panics if the cell does not belong to this owner.
+            ///
+            /// [`SubCell`]: struct.SubCell.html
+            pub fn rw_sub2<'a, T, S: AsMut<[T]> + ?Sized>(
+                &'a mut self,
+                sub1: &'a SubCell<'_, T, S>,
+                sub2: &'a SubCell<'_, T, S>,
+            ) -> Result<(&'a mut [T], &'a mut [T]), SubCellError> {
+                let slice = self.rw(sub1.cell).as_mut();
+                check_together(&[sub1, sub2], slice.len())?;
+                let [a, b] = split_disjoint(slice, [&sub1.range, &sub2.range]);
+                Ok((a, b))
+            }
+
+            /// Borrow the elements in the ranges of three [`SubCell`]
+            /// handles of the same cell mutably at the same time, as
+            /// for `rw_sub2`.
+            ///
+            /// [`SubCell`]: struct.SubCell.html
+            #[allow(clippy::type_complexity)]
+            pub fn rw_sub3<'a, T, S: AsMut<[T]> + ?Sized>(
+                &'a mut self,
+                sub1: &'a SubCell<'_, T, S>,
+                sub2: &'a SubCell<'_, T, S>,
+                sub3: &'a SubCell<'_, T, S>,
+            ) -> Result<(&'a mut [T], &'a mut [T], &'a mut [T]), SubCellError> {
+                let slice = self.rw(sub1.cell).as_mut();
+                check_together(&[sub1, sub2, sub3], slice.len())?;
+                let [a, b, c] =
+                    split_disjoint(slice, [&sub1.range, &sub2.range, &sub3.range]);
+                Ok((a, b, c))
+            }
+        }
+    };
+}
+
+sub_cell_methods!(QCellOwner);
+sub_cell_methods!(QCellOwnerSeq);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    // Carving a single range is meant here
+    #![allow(clippy::single_range_in_vec_init)]
+
+    use super::{check_carve, SubCellError};
+    use crate::{QCell, QCellOwner, QCellOwnerSeq};
+
+    #[test]
+    fn sub_cell_stage_writes() {
+        let mut owner = QCellOwner::new();
+        let buffer = QCell::new(&owner, vec![0_u8; 10]);
+        let subs = owner.carve(&buffer, &[0..3, 3..3, 3..7, 7..10]).unwrap();
+        for (stage, sub) in subs.iter().enumerate() {
+            for byte in owner.rw_sub(sub).unwrap() {
+                *byte = stage as u8 + 1;
+            }
+        }
+        assert_eq!(*owner.ro(&buffer), [1, 1, 1, 3, 3, 3, 3, 4, 4, 4]);
+        assert_eq!(owner.ro_sub(&subs[3]).unwrap(), [4, 4, 4]);
+        assert_eq!((subs[2].range(), subs[2].len()), (3..7, 4));
+        assert!(subs[1].is_empty());
+        assert!(std::ptr::eq(subs[0].cell(), &buffer));
+        assert_eq!(format!("{:?}", subs[0]), "SubCell { range: 0..3, .. }");
+
+        // Boxed slice, with a sequential owner
+        // Safety: No other owner is created with this ID
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let boxed = QCell::new(&owner, vec![0_u32; 6].into_boxed_slice());
+        let subs = owner.carve(&boxed, &[4..6, 0..2, 2..4]).unwrap();
+        let (a, b, c) = owner.rw_sub3(&subs[0], &subs[1], &subs[2]).unwrap();
+        a.fill(3);
+        b.fill(1);
+        c.fill(2);
+        assert_eq!(owner.ro(&boxed)[..], [1, 1, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn sub_cell_carve_rejected() {
+        let owner = QCellOwner::new();
+        let buffer = QCell::new(&owner, vec![0_u8; 10]);
+        let err = owner.carve(&buffer, &[0..4, 8..11]).unwrap_err();
+        assert_eq!(err, SubCellError::OutOfBounds { index: 1, len: 10 });
+        assert_eq!(err.to_string(), "range #1 is out of bounds for length 10");
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = [5..4];
+        assert!(owner.carve(&buffer, &reversed).is_err());
+        let err = owner.carve(&buffer, &[6..9, 0..2, 2..7]).unwrap_err();
+        assert_eq!(err, SubCellError::Overlap { first: 0, second: 2 });
+        assert_eq!(err.to_string(), "ranges #0 and #2 overlap");
+
+        // Overlap with an earlier range other than the one just before
+        let overlap = |ranges: &[std::ops::Range<usize>]| match check_carve(ranges, 20) {
+            Err(SubCellError::Overlap { first, second }) => Some((first, second)),
+            _ => None,
+        };
+        assert_eq!(overlap(&[0..10, 2..3, 5..6]), Some((0, 1)));
+        assert_eq!(overlap(&[0..10, 10..12, 10..10, 4..4]), Some((0, 3)));
+        assert_eq!(overlap(&[0..0, 0..5, 5..5, 5..5, 5..8, 8..8]), None);
+    }
+
+    #[test]
+    fn sub_cell_stale_after_shrink() {
+        let mut owner = QCellOwner::new();
+        let buffer = QCell::new(&owner, vec![1_u8; 8]);
+        let subs = owner.carve(&buffer, &[0..4, 4..8]).unwrap();
+        owner.rw(&buffer).truncate(6);
+        assert_eq!(owner.rw_sub(&subs[0]).unwrap(), [1; 4]);
+        let stale = SubCellError::Stale { range: 4..8, len: 6 };
+        assert_eq!(owner.ro_sub(&subs[1]).unwrap_err(), stale);
+        assert_eq!(owner.rw_sub(&subs[1]).unwrap_err(), stale);
+        assert_eq!(
+            owner.rw_sub2(&subs[0], &subs[1]).unwrap_err().to_string(),
+            "range 4..8 no longer fits contents of length 6"
+        );
+        // Usable again once the buffer has grown back
+        owner.rw(&buffer).resize(8, 2);
+        assert_eq!(owner.ro_sub(&subs[1]).unwrap(), [1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn sub_cell_rw_sub2_adjacent() {
+        let mut owner = QCellOwner::new();
+        let buffer = QCell::new(&owner, (0..8).collect::<Vec<u8>>());
+        let other = QCell::new(&owner, vec![0_u8; 8]);
+        let subs = owner.carve(&buffer, &[0..4, 4..8]).unwrap();
+        {
+            let (low, high) = owner.rw_sub2(&subs[1], &subs[0]).unwrap();
+            low.swap_with_slice(high);
+        }
+        assert_eq!(*owner.ro(&buffer), [4, 5, 6, 7, 0, 1, 2, 3]);
+
+        // Carving twice can't give overlapping mutable borrows
+        let again = owner.carve(&buffer, &[2..6]).unwrap();
+        let err = owner.rw_sub2(&subs[0], &again[0]).unwrap_err();
+        assert_eq!(err, SubCellError::Overlap { first: 0, second: 1 });
+        let err = owner.rw_sub3(&subs[0], &subs[1], &again[0]).unwrap_err();
+        assert_eq!(err, SubCellError::Overlap { first: 0, second: 2 });
+        let foreign = owner.carve(&other, &[0..4]).unwrap();
+        let err = owner.rw_sub2(&subs[0], &foreign[0]).unwrap_err();
+        assert_eq!(err, SubCellError::DifferentCells);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sub_cell_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let buffer = QCell::new(&owner1, vec![0_u8; 4]);
+        let subs = owner1.carve(&buffer, &[0..4]).unwrap();
+        let _ = owner2.rw_sub(&subs[0]);
+    }
+}
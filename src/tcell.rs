@@ -1,125 +1,294 @@
-#[cfg(any(feature = "std", feature = "exclusion-set"))]
-use core::any::TypeId;
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::borrow::Borrow;
 use core::cell::UnsafeCell;
 use core::marker::PhantomData;
-#[cfg(all(feature = "std", not(feature = "exclusion-set")))]
-use once_cell::sync::Lazy;
-#[cfg(all(feature = "std", not(feature = "exclusion-set")))]
-use std::{
-    collections::HashSet,
-    sync::{Condvar, Mutex},
-};
+#[cfg(target_has_atomic = "64")]
+use core::mem::ManuallyDrop;
+#[cfg(feature = "diagnostics")]
+use core::any::type_name;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{OwnerKind, Registration};
 
 use super::Invariant;
+use crate::batch;
+use crate::capability::Capability;
+use crate::panic_messages;
+use crate::ro_slice::{writer_check, RoSlice, ValuePtr};
+#[cfg(feature = "alloc")]
+use crate::rw_many::distinct_check;
+use crate::sound;
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+use crate::typeid_gate::{second_owner_panic, ClaimGuard, TypeGate};
+#[cfg(feature = "alloc")]
+use crate::view::VecViewMut;
+use crate::view::{SliceView, SliceViewMut};
+
+/// Marker types acceptable for [`TCellOwner`] and
+/// [`TLCellOwner`](struct.TLCellOwner.html).
+///
+/// Normally this is implemented for all `'static` types, so any type
+/// may be used as a marker.  However the singleton check means that
+/// if the marker type is public and constructible from other crates,
+/// then unrelated code can create an owner for "your" marker.  At
+/// best this causes a panic at a distance when your own code tries
+/// to create its owner.  To guard against this, the
+/// **strict-markers** feature removes the blanket implementation, so
+/// that only types declared through the [`marker!`] macro are
+/// accepted.  That macro only produces unit structs that are private
+/// (or at most `pub(crate)`), which gives a lint-like guarantee that
+/// every marker in a codebase went through the same path.
+///
+/// Implementing this trait by hand is not unsafe, but it defeats the
+/// purpose of the **strict-markers** feature, so use [`marker!`]
+/// instead.
+///
+/// Since Cargo unifies features across the whole build, enabling
+/// **strict-markers** affects every crate which uses **qcell**, and
+/// breaks any of them that uses a marker not declared with
+/// [`marker!`].  So it must only be enabled by a final binary crate,
+/// not by a library.
+///
+/// [`marker!`]: macro.marker.html
+pub trait TCellMarker: 'static {}
+
+#[cfg(not(feature = "strict-markers"))]
+impl<Q: 'static> TCellMarker for Q {}
+
+/// Declare a marker type for use with [`TCell`] or
+/// [`TLCell`](struct.TLCell.html).
+///
+/// The marker is always a unit struct, and may only be private or
+/// `pub(crate)`, which means that no other crate can construct an
+/// owner for it.  Attributes (including doc comments) are passed
+/// through.
+///
+/// ```
+/// use qcell::{marker, TCell};
+/// marker!(struct MarkerA;);
+/// marker!(
+///     /// Marker for the B cells
+///     pub(crate) struct MarkerB;
+/// );
+/// type ACell<T> = TCell<MarkerA, T>;
+/// type BCell<T> = TCell<MarkerB, T>;
+/// ```
+///
+/// With the **strict-markers** feature enabled, this also implements
+/// [`TCellMarker`] for the new type, and only types declared this way
+/// may be used as markers for [`TCellOwner`] and
+/// [`TLCellOwner`](struct.TLCellOwner.html).  Without the feature,
+/// this is just a plain struct declaration.
+///
+/// Adding `= index` after the name also implements
+/// [`IndexedMarker`] with that index, so that owners can be created
+/// with [`TCellOwner::new_indexed`]:
+///
+/// ```
+/// use qcell::{marker, TCellOwner};
+/// marker!(pub(crate) struct PluginMarker = 7;);
+/// let owner = TCellOwner::<PluginMarker>::new_indexed();
+/// ```
+///
+/// [`IndexedMarker`]: trait.IndexedMarker.html
+/// [`TCellOwner::new_indexed`]: struct.TCellOwner.html#method.new_indexed
+#[macro_export]
+macro_rules! marker {
+    ($(#[$meta:meta])* struct $name:ident;) => {
+        $(#[$meta])* struct $name;
+        $crate::__tcell_marker_impl!($name);
+    };
+    ($(#[$meta:meta])* pub(crate) struct $name:ident;) => {
+        $(#[$meta])* pub(crate) struct $name;
+        $crate::__tcell_marker_impl!($name);
+    };
+    ($(#[$meta:meta])* struct $name:ident = $index:expr;) => {
+        $(#[$meta])* struct $name;
+        $crate::__tcell_marker_impl!($name);
+        impl $crate::IndexedMarker for $name {
+            const INDEX: usize = $index;
+        }
+        const _: () = assert!($index < 64, "marker index must be less than 64");
+    };
+    ($(#[$meta:meta])* pub(crate) struct $name:ident = $index:expr;) => {
+        $(#[$meta])* pub(crate) struct $name;
+        $crate::__tcell_marker_impl!($name);
+        impl $crate::IndexedMarker for $name {
+            const INDEX: usize = $index;
+        }
+        const _: () = assert!($index < 64, "marker index must be less than 64");
+    };
+}
 
-#[cfg(all(feature = "std", not(feature = "exclusion-set")))]
-static SINGLETON_CHECK: Lazy<Mutex<HashSet<TypeId>>> = Lazy::new(|| Mutex::new(HashSet::new()));
-#[cfg(all(feature = "std", not(feature = "exclusion-set")))]
-static SINGLETON_CHECK_CONDVAR: Lazy<Condvar> = Lazy::new(Condvar::new);
-#[cfg(feature = "exclusion-set")]
-static SINGLETON_CHECK_SET: exclusion_set::Set<TypeId> = exclusion_set::Set::new();
+#[cfg(not(feature = "strict-markers"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __tcell_marker_impl {
+    ($name:ident) => {};
+}
+
+#[cfg(feature = "strict-markers")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __tcell_marker_impl {
+    ($name:ident) => {
+        impl $crate::TCellMarker for $name {}
+    };
+}
 
 /// Borrowing-owner of zero or more [`TCell`](struct.TCell.html)
 /// instances.
 ///
 /// See [crate documentation](index.html).
-pub struct TCellOwner<Q: 'static> {
+pub struct TCellOwner<Q: TCellMarker> {
     // Allow Send and Sync, and Q is invariant
     typ: PhantomData<Invariant<Q>>,
+    // The claim on Q in the `TypeGate`, released when the owner is
+    // dropped
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    _claim: ClaimGuard<Q>,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) diag: Registration,
 }
 
-impl<Q: 'static> Drop for TCellOwner<Q> {
-    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
+#[cfg(feature = "std")]
+impl<Q: TCellMarker> Drop for TCellOwner<Q> {
     fn drop(&mut self) {
-        // Remove the TypeId of Q from the HashSet, indicating that
-        // no more instances of TCellOwner<Q> exist.
-        SINGLETON_CHECK.lock().unwrap().remove(&TypeId::of::<Q>());
+        // The claim is released after this, when the fields are
+        // dropped
+        crate::tcell_lifecycle::run_drop::<Q>();
+    }
+}
 
-        // Wake up all threads waiting in TCellOwner::wait_for_new()
-        // to check if their Q was removed from the HashSet.
-        SINGLETON_CHECK_CONDVAR.notify_all();
+impl<Q: TCellMarker> TCellOwner<Q> {
+    // Called by the constructors before trying to claim Q
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    #[inline]
+    fn pre_create() {
+        #[cfg(feature = "std")]
+        crate::tcell_lifecycle::check_reentry::<Q>();
     }
 
-    #[cfg(feature = "exclusion-set")]
-    fn drop(&mut self) {
-        // Remove the TypeId of Q from the Set, indicating that
-        // no more instances of TCellOwner<Q> exist.
-        // SAFETY: the precondition of remove is satisfied since
-        // this can be the only TCellOwner for a given Q.
-        unsafe {
-            SINGLETON_CHECK_SET.remove(&TypeId::of::<Q>());
+    // Called by the constructors once Q is claimed.  If an
+    // `on_create` hook panics, the claim is dropped whilst unwinding,
+    // which releases it again without running the `on_drop` hooks.
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    fn created(claim: ClaimGuard<Q>) -> Self {
+        #[cfg(feature = "std")]
+        crate::tcell_lifecycle::run_create::<Q>();
+        Self {
+            typ: PhantomData,
+            _claim: claim,
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::auto(OwnerKind::TCellOwner, Some(type_name::<Q>())),
         }
     }
 
-    #[cfg(not(any(feature = "std", feature = "exclusion-set")))]
-    fn drop(&mut self) {
-        // constructors should be unavailable with this feature set, so the
-        // destructor should be unreachable
-        unreachable!()
+    // Create an owner without claiming Q.  Dropping it would release
+    // whichever claim on Q is held, so it is returned wrapped in
+    // `ManuallyDrop`.  The caller must have ensured by other means
+    // that no other owner for Q exists.
+    #[cfg(target_has_atomic = "64")]
+    pub(crate) fn unregistered() -> ManuallyDrop<Self> {
+        ManuallyDrop::new(Self {
+            typ: PhantomData,
+            #[cfg(any(feature = "std", feature = "exclusion-set"))]
+            _claim: ClaimGuard::assume_claimed(),
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::NONE,
+        })
     }
 }
 
 #[cfg(any(feature = "std", feature = "exclusion-set"))]
 #[cfg_attr(docsrs, doc(cfg(any(feature = "std", feature = "exclusion-set"))))]
-impl<Q: 'static> Default for TCellOwner<Q> {
+impl<Q: TCellMarker> Default for TCellOwner<Q> {
+    #[track_caller]
     fn default() -> Self {
         TCellOwner::new()
     }
 }
 
-impl<Q: 'static> TCellOwner<Q> {
+impl<Q: TCellMarker> TCellOwner<Q> {
     /// Create the singleton owner instance.  Each owner may be used
     /// to create many `TCell` instances.  There may be only one
     /// instance of this type per process at any given time for each
     /// different marker type `Q`.  This call panics if a second
-    /// simultaneous instance is created.
+    /// simultaneous instance is created.  The panic message gives the
+    /// source location where the existing owner was created, as
+    /// recorded in the [`TypeGate`], and with the **owner-backtrace**
+    /// feature, a backtrace of its creation.
+    ///
+    /// This can't be a `const fn`, since it registers the owner in a
+    /// global set.
     ///
     /// Keep in mind that in Rust, tests are run in parallel unless
     /// specified otherwise (using e.g. `RUST_TEST_THREADS`), so
     /// this panic may be more easy to trigger than you might think.
     /// To avoid this panic, consider using the methods
-    #[cfg_attr(feature = "std", doc = "[`TCellOwner::wait_for_new`]")]
-    #[cfg_attr(not(feature = "std"), doc = "`TCellOwner::wait_for_new`")]
+    #[cfg_attr(
+        all(
+            feature = "std",
+            not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+        ),
+        doc = "[`TCellOwner::wait_for_new`]"
+    )]
+    #[cfg_attr(
+        not(all(
+            feature = "std",
+            not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+        )),
+        doc = "`TCellOwner::wait_for_new`"
+    )]
     /// or [`TCellOwner::try_new`] instead.
+    ///
+    /// [`TypeGate`]: typeid_gate/struct.TypeGate.html
     #[cfg(any(feature = "std", feature = "exclusion-set"))]
     #[cfg_attr(docsrs, doc(cfg(any(feature = "std", feature = "exclusion-set"))))]
+    #[track_caller]
     pub fn new() -> Self {
         if let Some(owner) = TCellOwner::try_new() {
             owner
         } else {
-            panic!("Illegal to create two TCellOwner instances with the same marker type parameter")
-        }
-    }
-
-    /// Same as [`TCellOwner::new`], except if another `TCellOwner`
-    /// of this type `Q` already exists, this returns `None` instead
-    /// of panicking.
-    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
-    pub fn try_new() -> Option<Self> {
-        if SINGLETON_CHECK.lock().unwrap().insert(TypeId::of::<Q>()) {
-            Some(Self { typ: PhantomData })
-        } else {
-            None
+            second_owner_panic(
+                panic_messages::TCELL_SECOND_OWNER,
+                "TCellOwner",
+                core::any::type_name::<Q>(),
+                TypeGate::claimant::<Q>(),
+            )
         }
     }
 
     /// Same as [`TCellOwner::new`], except if another `TCellOwner`
     /// of this type `Q` already exists, this returns `None` instead
     /// of panicking.
-    #[cfg(feature = "exclusion-set")]
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
     #[cfg_attr(docsrs, doc(cfg(any(feature = "std", feature = "exclusion-set"))))]
+    #[track_caller]
     pub fn try_new() -> Option<Self> {
-        if SINGLETON_CHECK_SET.try_insert(TypeId::of::<Q>()) {
-            Some(Self { typ: PhantomData })
-        } else {
-            None
-        }
+        Self::pre_create();
+        // The registry is not locked whilst running any hooks
+        TypeGate::try_claim::<Q>().map(Self::created)
     }
 
     /// Same as [`TCellOwner::new`], except if another `TCellOwner`
     /// of this type `Q` already exists, this function blocks the thread
-    /// until that other instance is dropped.  This will of course deadlock
-    /// if that other instance is owned by the same thread.
+    /// until that other instance is dropped.
+    ///
+    /// Waiting for an owner that was created by the current thread
+    /// would usually deadlock, so in that case this panics instead of
+    /// blocking.  The check is best-effort: the thread recorded is
+    /// the one that created the existing owner, not the one that
+    /// holds it now.  So if that owner was sent to another thread,
+    /// this still panics on the creating thread, and a wait on the
+    /// new holding thread is not detected and will deadlock.  (With
+    /// the **exclusion-set** feature there is no detection at all.)
+    ///
+    /// Owners for different marker types are registered in separate
+    /// shards by a hash of the marker's `TypeId`, so they rarely
+    /// contend with each other, and dropping an owner only wakes up
+    /// the waiting threads whose marker shares its shard.
     ///
     /// Note that owners are expected to be relatively long-lived.  If
     /// you need to access cells associated with a given marker type
@@ -128,32 +297,48 @@ impl<Q: 'static> TCellOwner<Q> {
     /// a `Mutex` or `RwLock` to control access.  This call is
     /// intended to help when several independent tests need to run
     /// which use the same marker type internally.
-    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
+    ///
+    /// Not available on WASM without the "atomics" target feature,
+    /// since there is only one thread, so waiting would never end.
+    #[cfg(all(
+        feature = "std",
+        not(feature = "exclusion-set"),
+        not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(
+            feature = "std",
+            not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+        )))
+    )]
+    #[track_caller]
     pub fn wait_for_new() -> Self {
-        // Lock the HashSet mutex.
-        let hashset_guard = SINGLETON_CHECK.lock().unwrap();
-
-        // If the HashSet already contains the TypeId of Q, there is
-        // another TCellOwner. Block the thread until it gets dropped.
-        // (the HashSet mutex is unlocked while waiting)
-        let mut hashset_guard = SINGLETON_CHECK_CONDVAR
-            .wait_while(hashset_guard, |hashset| {
-                hashset.contains(&TypeId::of::<Q>())
-            })
-            .unwrap();
-
-        // If we get here, no other TCellOwner of this type exists.
-        // Return a new TCellOwner.  When dropped, it will remove the
-        // TypeId of Q from the HashSet, and notify all waiting threads.
-        let inserted = hashset_guard.insert(TypeId::of::<Q>());
-        assert!(inserted);
-        Self { typ: PhantomData }
+        Self::pre_create();
+        // If the existing owner was created by this thread, the wait
+        // would most likely never end
+        match TypeGate::try_claim_blocking::<Q>() {
+            Some(claim) => Self::created(claim),
+            None => panic!(
+                "{}: {}",
+                panic_messages::TCELL_WAIT_FOR_NEW_DEADLOCK,
+                core::any::type_name::<Q>()
+            ),
+        }
     }
 
     /// Same as [`TCellOwner::new`], except if another `TCellOwner`
     /// of this type `Q` already exists, this function blocks the thread
     /// until that other instance is dropped.  This will of course deadlock
-    /// if that other instance is owned by the same thread.
+    /// if that other instance is owned by the same thread.  Unlike
+    /// the default backend, the **exclusion-set** backend has no way
+    /// to record which thread created the owner, so this case is not
+    /// detected.
+    ///
+    /// The **exclusion-set** backend keeps a lock-free queue of waiting
+    /// threads for each marker type, and dropping an owner wakes just
+    /// one waiting thread for that marker.  So owners for different
+    /// marker types never contend on a shared lock.
     ///
     /// Note that owners are expected to be relatively long-lived.  If
     /// you need to access cells associated with a given marker type
@@ -162,14 +347,39 @@ impl<Q: 'static> TCellOwner<Q> {
     /// a `Mutex` or `RwLock` to control access.  This call is
     /// intended to help when several independent tests need to run
     /// which use the same marker type internally.
-    #[cfg(all(feature = "std", feature = "exclusion-set"))]
+    ///
+    /// Not available on WASM without the "atomics" target feature,
+    /// since there is only one thread, so waiting would never end.
+    #[cfg(all(
+        feature = "std",
+        feature = "exclusion-set",
+        not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+    ))]
     #[cfg_attr(
         docsrs,
-        doc(cfg(any(feature = "std", all(feature = "exclusion-set", feature = "std"))))
+        doc(cfg(all(
+            feature = "std",
+            not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+        )))
     )]
+    #[track_caller]
     pub fn wait_for_new() -> Self {
-        SINGLETON_CHECK_SET.wait_to_insert(TypeId::of::<Q>());
-        Self { typ: PhantomData }
+        Self::pre_create();
+        Self::created(TypeGate::claim_blocking::<Q>())
+    }
+
+    /// Leak this owner, keeping it for the rest of the program, for
+    /// use with cells that are referenced from statics.  The
+    /// registration of the singleton for marker type `Q` is
+    /// permanent: no other owner for `Q` can be created afterwards,
+    /// so [`TCellOwner::new`] will panic and [`TCellOwner::try_new`]
+    /// will return `None`.  Any drop hooks registered for `Q` are
+    /// never run.  The `&'static mut` can be turned into a `&'static`
+    /// to share the owner between threads.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn leak(self) -> &'static mut TCellOwner<Q> {
+        Box::leak(Box::new(self))
     }
 
     /// Create a new cell owned by this owner instance.  See also
@@ -185,7 +395,8 @@ impl<Q: 'static> TCellOwner<Q> {
     /// from the same owner.
     #[inline]
     pub fn ro<'a, T: ?Sized>(&'a self, tc: &'a TCell<Q, T>) -> &'a T {
-        unsafe { &*tc.value.get() }
+        // Safety: Owned (by marker type), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&tc.value) }
     }
 
     /// Borrow contents of a `TCell` mutably (read-write).  Only one
@@ -194,12 +405,14 @@ impl<Q: 'static> TCellOwner<Q> {
     /// another can be borrowed.
     #[inline]
     pub fn rw<'a, T: ?Sized>(&'a mut self, tc: &'a TCell<Q, T>) -> &'a mut T {
-        unsafe { &mut *tc.value.get() }
+        // Safety: Owned (by marker type), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&tc.value) }
     }
 
     /// Borrow contents of two `TCell` instances mutably.  Panics if
     /// the two `TCell` instances point to the same memory.
     #[inline]
+    #[allow(clippy::ptr_eq)]
     pub fn rw2<'a, T: ?Sized, U: ?Sized>(
         &'a mut self,
         tc1: &'a TCell<Q, T>,
@@ -207,14 +420,17 @@ impl<Q: 'static> TCellOwner<Q> {
     ) -> (&'a mut T, &'a mut U) {
         assert!(
             tc1 as *const _ as *const () as usize != tc2 as *const _ as *const () as usize,
-            "Illegal to borrow same TCell twice with rw2()"
+            "{}",
+            panic_messages::TCELL_BORROWED_TWICE
         );
-        unsafe { (&mut *tc1.value.get(), &mut *tc2.value.get()) }
+        // Safety: Owned (by marker type), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&tc1.value, &tc2.value) }
     }
 
     /// Borrow contents of three `TCell` instances mutably.  Panics if
     /// any pair of `TCell` instances point to the same memory.
     #[inline]
+    #[allow(clippy::ptr_eq)]
     pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
         &'a mut self,
         tc1: &'a TCell<Q, T>,
@@ -225,16 +441,280 @@ impl<Q: 'static> TCellOwner<Q> {
             (tc1 as *const _ as *const () as usize != tc2 as *const _ as *const () as usize)
                 && (tc2 as *const _ as *const () as usize != tc3 as *const _ as *const () as usize)
                 && (tc3 as *const _ as *const () as usize != tc1 as *const _ as *const () as usize),
-            "Illegal to borrow same TCell twice with rw3()"
+            "{}",
+            panic_messages::TCELL_BORROWED_TWICE
         );
-        unsafe {
-            (
-                &mut *tc1.value.get(),
-                &mut *tc2.value.get(),
-                &mut *tc3.value.get(),
-            )
+        // Safety: Owned (by marker type), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&tc1.value, &tc2.value, &tc3.value) }
+    }
+
+    /// Borrow contents of one `TCell` mutably, and the contents of a
+    /// slice of `TCell` instances immutably.  The readers may repeat,
+    /// but none may be the same cell as the writer.  Panics with the
+    /// position of the first reader that is the same as the writer.
+    /// The contents of the readers are fetched lazily through the
+    /// returned [`RoSlice`].
+    ///
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        w: &'a TCell<Q, T>,
+        readers: &'a [&'a TCell<Q, U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        writer_check("TCell", w, readers);
+        // Safety: Owned (by marker type), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
+    }
+
+    /// Copy the contents of a `TCell` out, like `Cell::get`.  No
+    /// borrow is held after this returns.
+    #[inline]
+    pub fn get<T: Copy>(&self, tc: &TCell<Q, T>) -> T {
+        *self.ro(tc)
+    }
+
+    /// Clone the contents of a `TCell` out.  This is a separate call
+    /// from [`TCellOwner::get`] so that `get` stays a plain copy.
+    ///
+    /// [`TCellOwner::get`]: struct.TCellOwner.html#method.get
+    #[inline]
+    pub fn get_cloned<T: Clone>(&self, tc: &TCell<Q, T>) -> T {
+        self.ro(tc).clone()
+    }
+
+    /// Replace the contents of a `TCell` with `value`, like
+    /// `Cell::set`, dropping the old value.
+    #[inline]
+    pub fn set<T>(&mut self, tc: &TCell<Q, T>, value: T) {
+        *self.rw(tc) = value;
+    }
+
+    /// Replace the contents of a `TCell` with the result of calling
+    /// `f` on a copy of the old contents, and return the new value.
+    #[inline]
+    pub fn update<T: Copy>(&mut self, tc: &TCell<Q, T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(tc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Copy the contents of many `TCell`s into `out` without
+    /// allocating, for example to take a snapshot of a set of
+    /// parameters.  This is the same as calling [`TCellOwner::get`]
+    /// for each cell.
+    ///
+    /// [`TCellOwner::get`]: struct.TCellOwner.html#method.get
+    #[inline]
+    pub fn read_into<T: Copy, const N: usize>(&self, cells: &[&TCell<Q, T>; N], out: &mut [T; N]) {
+        self.read_into_slice(cells, out);
+    }
+
+    /// Copy the contents of many `TCell`s into `out`, like
+    /// [`TCellOwner::read_into`], but with the lengths checked at
+    /// runtime.  Panics if `cells` and `out` have different
+    /// lengths.
+    ///
+    /// [`TCellOwner::read_into`]: struct.TCellOwner.html#method.read_into
+    #[inline]
+    pub fn read_into_slice<T: Copy>(&self, cells: &[&TCell<Q, T>], out: &mut [T]) {
+        batch::length_check(cells.len(), out.len());
+        for (tc, value) in cells.iter().zip(out) {
+            *value = *self.ro(tc);
+        }
+    }
+
+    /// Copy the values in `src` into many `TCell`s without
+    /// allocating.  The cells are written in order, and no check
+    /// is made for repeated cells, so if the same `TCell` appears
+    /// more than once then the last write wins.
+    #[inline]
+    pub fn write_from<T: Copy, const N: usize>(&mut self, cells: &[&TCell<Q, T>; N], src: &[T; N]) {
+        self.write_from_slice(cells, src);
+    }
+
+    /// Copy the values in `src` into many `TCell`s, like
+    /// [`TCellOwner::write_from`], but with the lengths checked at
+    /// runtime.  Panics before writing anything if `cells` and
+    /// `src` have different lengths.
+    ///
+    /// [`TCellOwner::write_from`]: struct.TCellOwner.html#method.write_from
+    #[inline]
+    pub fn write_from_slice<T: Copy>(&mut self, cells: &[&TCell<Q, T>], src: &[T]) {
+        batch::length_check(cells.len(), src.len());
+        for (tc, value) in cells.iter().zip(src) {
+            *self.rw(tc) = *value;
+        }
+    }
+
+    /// Borrow the contents of a `TCell` holding a slice, `Vec`, boxed
+    /// slice or array as a read-only [`SliceView`], which supports
+    /// indexing by position or range.
+    ///
+    /// [`SliceView`]: struct.SliceView.html
+    #[inline]
+    pub fn view<'a, T, S: AsRef<[T]> + ?Sized>(&'a self, tc: &'a TCell<Q, S>) -> SliceView<'a, T> {
+        SliceView::new(self.ro(tc).as_ref())
+    }
+
+    /// Borrow the contents of a `TCell` holding a `Vec` as a
+    /// [`VecViewMut`], which supports indexing for reading and
+    /// writing.  This owner stays mutably borrowed while the view
+    /// exists, so no other cell can be borrowed through it.
+    ///
+    /// [`VecViewMut`]: struct.VecViewMut.html
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[inline]
+    pub fn view_mut<'a, T>(&'a mut self, tc: &'a TCell<Q, Vec<T>>) -> VecViewMut<'a, T> {
+        VecViewMut::new(self.rw(tc))
+    }
+
+    /// Borrow the contents of a `TCell` holding a slice, `Vec`, boxed
+    /// slice or array as a [`SliceViewMut`], which supports indexing
+    /// for reading and writing.  This owner stays mutably borrowed
+    /// while the view exists.
+    ///
+    /// [`SliceViewMut`]: struct.SliceViewMut.html
+    #[inline]
+    pub fn slice_view_mut<'a, T, S: AsMut<[T]> + ?Sized>(
+        &'a mut self,
+        tc: &'a TCell<Q, S>,
+    ) -> SliceViewMut<'a, T> {
+        SliceViewMut::new(self.rw(tc).as_mut())
+    }
+
+    /// Wrap a mutable borrow of this owner in a [`Capability`] which
+    /// can only read [`TCell`] instances if `READ` is `true`, and only
+    /// write them if `WRITE` is `true`, to hand to code which should
+    /// be restricted to one or the other.  See [`ReadOnly`] and
+    /// [`WriteOnly`].
+    ///
+    /// [`Capability`]: struct.Capability.html
+    /// [`ReadOnly`]: type.ReadOnly.html
+    /// [`WriteOnly`]: type.WriteOnly.html
+    #[inline]
+    pub fn capability<const READ: bool, const WRITE: bool>(
+        &mut self,
+    ) -> Capability<'_, TCellOwner<Q>, READ, WRITE> {
+        Capability::new(self)
+    }
+
+    /// Borrow the contents of a `TCell` mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  No runtime check is needed to keep the
+    /// two apart: the cell's contents are reached through the owner,
+    /// and `other` through a separate `&mut`, which safe code can't
+    /// point into the cell's contents.  Since the owner is borrowed
+    /// for the call, `f` can't use it.
+    #[inline]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        &'a mut self,
+        tc: &'a TCell<Q, T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(tc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two `TCell` instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.
+    #[inline]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        &'a mut self,
+        tc1: &'a TCell<Q, T>,
+        tc2: &'a TCell<Q, U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(tc1, tc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of a `TCell` immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.
+    #[inline]
+    pub fn inspect<T: ?Sized, D>(
+        &self,
+        tc: &TCell<Q, T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(tc))
+    }
+
+    /// Same as `inspect`, but borrows two `TCell` instances, which may
+    /// be the same cell.
+    #[inline]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        &self,
+        tc1: &TCell<Q, T>,
+        tc2: &TCell<Q, U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(tc1), self.ro(tc2))
+    }
+
+    /// Read the contents of a `TCell` with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.
+    #[inline]
+    pub fn modify_if<T: ?Sized, D>(
+        &mut self,
+        tc: &TCell<Q, T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.ro(tc)) {
+            Some(decision) => {
+                apply(self.rw(tc), decision);
+                true
+            }
+            None => false,
         }
     }
+
+    /// Borrow the contents of a slice of `TCell` instances mutably,
+    /// returning a `Vec` of references in the same order.  The slice
+    /// may hold the cells directly, or anything that borrows as a
+    /// `TCell`, such as `Rc<TCell<..>>`, `Arc<TCell<..>>` or
+    /// `&TCell<..>`.  Panics if any two entries are the same cell,
+    /// which includes two `Rc` or `Arc` clones of the same cell.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_slice<'a, T: ?Sized, C: Borrow<TCell<Q, T>>>(
+        &'a mut self,
+        cells: &'a [C],
+    ) -> Vec<&'a mut T> {
+        self.rw_from_iter(cells)
+    }
+
+    /// Same as [`TCellOwner::rw_from_slice`], but takes the cells from
+    /// an iterator of references.
+    ///
+    /// [`TCellOwner::rw_from_slice`]: struct.TCellOwner.html#method.rw_from_slice
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rw_from_iter<'a, T: ?Sized + 'a, C: Borrow<TCell<Q, T>> + 'a>(
+        &'a mut self,
+        cells: impl IntoIterator<Item = &'a C>,
+    ) -> Vec<&'a mut T> {
+        let cells: Vec<&'a TCell<Q, T>> = cells.into_iter().map(Borrow::borrow).collect();
+        distinct_check("TCell", &cells);
+        cells
+            .into_iter()
+            // Safety: Owned (by marker type), Distinct, and owner mutably borrowed
+            .map(|tc| unsafe { sound::deref_unique(&tc.value) })
+            .collect()
+    }
 }
 
 /// Cell whose contents is owned (for borrowing purposes) by a
@@ -260,6 +740,13 @@ pub struct TCell<Q, T: ?Sized> {
     value: UnsafeCell<T>,
 }
 
+impl<Q, T: ?Sized> ValuePtr for TCell<Q, T> {
+    type Value = T;
+    fn value_ptr(&self) -> *const T {
+        self.value.get()
+    }
+}
+
 impl<Q, T> TCell<Q, T> {
     /// Create a new `TCell` owned for borrowing purposes by the
     /// `TCellOwner` derived from the same marker type `Q`.
@@ -286,7 +773,10 @@ impl<Q, T: ?Sized> TCell<Q, T> {
     /// `TCell` instances can be borrowed immutably at the same time
     /// from the same owner.
     #[inline]
-    pub fn ro<'a>(&'a self, owner: &'a TCellOwner<Q>) -> &'a T {
+    pub fn ro<'a>(&'a self, owner: &'a TCellOwner<Q>) -> &'a T
+    where
+        Q: TCellMarker,
+    {
         owner.ro(self)
     }
 
@@ -297,7 +787,10 @@ impl<Q, T: ?Sized> TCell<Q, T> {
     /// cells at the same time, see [`TCellOwner::rw2`] or
     /// [`TCellOwner::rw3`].
     #[inline]
-    pub fn rw<'a>(&'a self, owner: &'a mut TCellOwner<Q>) -> &'a mut T {
+    pub fn rw<'a>(&'a self, owner: &'a mut TCellOwner<Q>) -> &'a mut T
+    where
+        Q: TCellMarker,
+    {
         owner.rw(self)
     }
 
@@ -316,8 +809,20 @@ impl<Q, T: ?Sized> TCell<Q, T> {
     pub fn get_mut(&mut self) -> &mut T {
         self.value.get_mut()
     }
+
+    /// Convert a mutable reference to a value into a mutable
+    /// reference to a `TCell` containing it.  Since the value is
+    /// borrowed mutably, the owner can't reach it any other way until
+    /// the borrow ends.  See also [`TCell::scoped_cell`].
+    ///
+    /// [`TCell::scoped_cell`]: struct.TCell.html#method.scoped_cell
+    #[inline]
+    pub fn from_mut(value: &mut T) -> &mut Self {
+        sound::tcell_from_mut(value)
+    }
 }
 
+#[allow(clippy::needless_maybe_sized)]
 impl<Q: 'static, T: Default + ?Sized> Default for TCell<Q, T> {
     fn default() -> Self {
         TCell::new(T::default())
@@ -347,14 +852,48 @@ mod tests {
     #[test]
     #[should_panic]
     fn tcell_singleton_1() {
-        struct Marker;
+        marker!(struct Marker;);
         let _owner1 = TCellOwner::<Marker>::new();
         let _owner2 = TCellOwner::<Marker>::new(); // Panic here
     }
 
+    #[test]
+    fn tcell_inspect_modify_if() {
+        marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let (queue, done) = (TCell::new([3, 0]), TCell::new(0));
+        while let Some(n) = owner.inspect(&queue, |q| q.iter().copied().find(|n| *n > 0)) {
+            *owner.rw(&done) += n;
+            owner.modify_if(&queue, |q| q.iter().position(|v| *v == n), |q, i| q[i] = 0);
+        }
+        assert_eq!(owner.inspect2(&queue, &done, |q, d| (*q, *d)), ([0, 0], 3));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn tcell_leak() {
+        marker!(struct Marker;);
+        let owner = TCellOwner::<Marker>::new().leak();
+        let cell: &'static TCell<Marker, u32> = Box::leak(Box::new(TCell::new(1)));
+        *owner.rw(cell) += 1;
+        let owner: &'static TCellOwner<Marker> = owner;
+        let handle = std::thread::spawn(move || *owner.ro(cell));
+        assert_eq!(handle.join().unwrap(), 2);
+        // The marker type stays claimed
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    #[should_panic]
+    fn tcell_leak_second_owner() {
+        marker!(struct Marker;);
+        TCellOwner::<Marker>::new().leak();
+        let _owner = TCellOwner::<Marker>::new(); // Panic here
+    }
     #[test]
     fn tcell_singleton_2() {
-        struct Marker;
+        marker!(struct Marker;);
         let owner1 = TCellOwner::<Marker>::new();
         drop(owner1);
         let _owner2 = TCellOwner::<Marker>::new();
@@ -362,24 +901,56 @@ mod tests {
 
     #[test]
     fn tcell_singleton_3() {
-        struct Marker1;
-        struct Marker2;
+        marker!(struct Marker1;);
+        marker!(struct Marker2;);
         let _owner1 = TCellOwner::<Marker1>::new();
         let _owner2 = TCellOwner::<Marker2>::new();
     }
 
     #[test]
     fn tcell_singleton_try_new() {
-        struct Marker;
+        marker!(struct Marker;);
         let owner1 = TCellOwner::<Marker>::try_new();
         assert!(owner1.is_some());
         let owner2 = TCellOwner::<Marker>::try_new();
         assert!(owner2.is_none());
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn tcell_second_owner_location() {
+        use crate::typeid_gate::TypeGate;
+        use std::any::type_name;
+        marker!(struct Marker;);
+        let (line, owner1) = (line!(), TCellOwner::<Marker>::new());
+        let payload = match std::panic::catch_unwind(TCellOwner::<Marker>::new) {
+            Ok(_) => panic!("no panic"),
+            Err(payload) => payload,
+        };
+        let msg = payload.downcast_ref::<String>().unwrap();
+        let expected = format!(
+            "existing TCellOwner<{}> created at {}:{}:",
+            type_name::<Marker>(),
+            file!(),
+            line
+        );
+        assert!(msg.contains(&expected), "{}", msg);
+        #[cfg(feature = "owner-backtrace")]
+        assert!(msg.contains("\n\nbacktrace of its creation:\n"), "{}", msg);
+        drop(owner1);
+
+        // The location of the caller of `default` and `try_new` too
+        let (line, owner) = (line!(), TCellOwner::<Marker>::default());
+        assert_eq!(TypeGate::claimed_at::<Marker>().unwrap().line(), line);
+        drop(owner);
+        let (line, _owner) = (line!(), TCellOwner::<Marker>::try_new());
+        let location = TypeGate::claimed_at::<Marker>().unwrap();
+        assert_eq!((location.file(), location.line()), (file!(), line));
+    }
+
     #[test]
     fn tcell() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TCellOwner<Marker>;
         type ACell<T> = TCell<Marker, T>;
         let mut owner = ACellOwner::new();
@@ -396,7 +967,7 @@ mod tests {
     #[test]
     #[should_panic]
     fn tcell_threads() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TCellOwner<Marker>;
         // Do it this way around to make the panic appear in the main
         // thread, to avoid spurious messages in the test output.
@@ -413,12 +984,18 @@ mod tests {
         let _ = rx.recv();
     }
 
+    // Real threads and real sleeps, as a smoke test of the whole
+    // thing.  It takes a while, so only runs with `--ignored`.  The
+    // order of events is checked deterministically by
+    // `tcell_wait_for_new_waits_for_drop` below and by the `Shard`
+    // tests in `typeid_gate`.
     #[cfg(feature = "std")]
     #[test]
+    #[ignore]
     fn tcell_wait_for_new_in_100_threads() {
         use rand::Rng;
         use std::sync::Arc;
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TCellOwner<Marker>;
         type ACell = TCell<Marker, i32>;
         let cell_arc = Arc::new(ACell::new(0));
@@ -449,39 +1026,214 @@ mod tests {
         assert_eq!(*owner.ro(&*cell_arc), 100);
     }
 
+    // Owners for 16 markers are created and dropped by 4 threads each
+    // at once, so most markers share a shard with another, and every
+    // drop wakes waiters for other markers as well as its own
     #[cfg(feature = "std")]
     #[test]
-    fn tcell_wait_for_new_timeout() {
-        fn assert_time_out<F>(d: std::time::Duration, f: F)
-        where
-            F: FnOnce(),
-            F: Send + 'static,
-        {
-            let (done_tx, done_rx) = std::sync::mpsc::channel();
-            let _handle = std::thread::spawn(move || {
-                let val = f();
-                done_tx.send(()).unwrap();
-                val
-            });
+    fn tcell_wait_for_new_16_markers_64_threads() {
+        use super::TCellMarker;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::thread::{self, JoinHandle};
+        const THREADS_PER_MARKER: usize = 4;
+        const CYCLES: usize = 200;
 
-            assert!(
-                done_rx.recv_timeout(d).is_err(),
-                "ACellOwner::wait_for_new completed (but it shouldn't have)"
-            );
+        fn stress<Q: TCellMarker>() -> JoinHandle<()> {
+            thread::spawn(|| {
+                // The flag catches two owners existing at once, even
+                // if the non-atomic update of the count happens to
+                // come out right
+                let shared = Arc::new((TCell::<Q, usize>::new(0), AtomicBool::new(false)));
+                let workers: Vec<_> = (0..THREADS_PER_MARKER)
+                    .map(|_| {
+                        let shared = shared.clone();
+                        thread::spawn(move || {
+                            let (cell, held) = &*shared;
+                            for i in 0..CYCLES {
+                                let mut owner = match i % 4 {
+                                    0 => TCellOwner::<Q>::try_new()
+                                        .unwrap_or_else(TCellOwner::wait_for_new),
+                                    _ => TCellOwner::<Q>::wait_for_new(),
+                                };
+                                assert!(!held.swap(true, Ordering::SeqCst));
+                                let count = *owner.ro(cell);
+                                thread::yield_now();
+                                *owner.rw(cell) = count + 1;
+                                held.store(false, Ordering::SeqCst);
+                            }
+                        })
+                    })
+                    .collect();
+                for worker in workers {
+                    worker.join().unwrap();
+                }
+                let owner = TCellOwner::<Q>::new();
+                assert_eq!(*owner.ro(&shared.0), THREADS_PER_MARKER * CYCLES);
+            })
         }
 
-        assert_time_out(std::time::Duration::from_millis(1000), || {
-            struct Marker;
-            type ACellOwner = TCellOwner<Marker>;
+        marker!(struct M0;);
+        marker!(struct M1;);
+        marker!(struct M2;);
+        marker!(struct M3;);
+        marker!(struct M4;);
+        marker!(struct M5;);
+        marker!(struct M6;);
+        marker!(struct M7;);
+        marker!(struct M8;);
+        marker!(struct M9;);
+        marker!(struct M10;);
+        marker!(struct M11;);
+        marker!(struct M12;);
+        marker!(struct M13;);
+        marker!(struct M14;);
+        marker!(struct M15;);
+        let handles = vec![
+            stress::<M0>(),
+            stress::<M1>(),
+            stress::<M2>(),
+            stress::<M3>(),
+            stress::<M4>(),
+            stress::<M5>(),
+            stress::<M6>(),
+            stress::<M7>(),
+            stress::<M8>(),
+            stress::<M9>(),
+            stress::<M10>(),
+            stress::<M11>(),
+            stress::<M12>(),
+            stress::<M13>(),
+            stress::<M14>(),
+            stress::<M15>(),
+        ];
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    // Whichever backend is used, a waiter only gets the owner once
+    // the existing owner is dropped
+    #[cfg(feature = "std")]
+    #[test]
+    fn tcell_wait_for_new_waits_for_drop() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        marker!(struct Marker;);
+        let owner = TCellOwner::<Marker>::new();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let dropped = dropped.clone();
+                std::thread::spawn(move || {
+                    let _owner = TCellOwner::<Marker>::wait_for_new();
+                    assert!(dropped.load(Ordering::SeqCst));
+                })
+            })
+            .collect();
+        std::thread::yield_now();
+        dropped.store(true, Ordering::SeqCst);
+        drop(owner);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        let _owner = TCellOwner::<Marker>::new();
+    }
 
-            let _owner1 = ACellOwner::new();
-            let _owner2 = ACellOwner::wait_for_new();
+    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
+    fn wait_for_new_panic_message<Q: super::TCellMarker>() -> Option<String> {
+        std::panic::catch_unwind(|| TCellOwner::<Q>::wait_for_new())
+            .err()
+            .map(|e| *e.downcast::<String>().unwrap())
+    }
+
+    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
+    #[test]
+    fn tcell_wait_for_new_same_thread() {
+        marker!(struct Marker;);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let _owner = TCellOwner::<Marker>::new();
+            tx.send(wait_for_new_panic_message::<Marker>()).unwrap();
         });
+        let msg = rx
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("wait_for_new blocked instead of panicking");
+        assert_eq!(
+            msg.unwrap(),
+            format!(
+                "{}: {}",
+                crate::panic_messages::TCELL_WAIT_FOR_NEW_DEADLOCK,
+                std::any::type_name::<Marker>()
+            )
+        );
+
+        // The panic didn't leave anything behind once the thread's
+        // owner is dropped
+        handle.join().unwrap();
+        let _owner = TCellOwner::<Marker>::new();
+    }
+
+    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
+    #[test]
+    fn tcell_wait_for_new_other_thread() {
+        use std::sync::mpsc::RecvTimeoutError;
+        use std::time::Duration;
+        marker!(struct Marker;);
+        let owner = TCellOwner::<Marker>::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let owner = TCellOwner::<Marker>::wait_for_new();
+            tx.send(()).unwrap();
+            owner
+        });
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(200)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        drop(owner);
+        rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        drop(handle.join().unwrap());
+        let _owner = TCellOwner::<Marker>::new();
+    }
+
+    #[cfg(all(feature = "std", not(feature = "exclusion-set")))]
+    #[test]
+    fn tcell_wait_for_new_moved_owner() {
+        marker!(struct Marker;);
+        // The recorded thread is the creating thread, and sending the
+        // owner elsewhere doesn't update it.  So this thread still
+        // gets the panic, even though it no longer holds the owner.
+        let owner = TCellOwner::<Marker>::new();
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            let _ = rx.recv();
+            drop(owner);
+        });
+        assert!(wait_for_new_panic_message::<Marker>().is_some());
+
+        // Once the other thread has dropped it, waiting works again
+        tx.send(()).unwrap();
+        handle.join().unwrap();
+        let _owner = TCellOwner::<Marker>::wait_for_new();
+    }
+
+    #[test]
+    fn tcell_get_set() {
+        marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let count = TCell::new(2i32);
+        let label = TCell::new(String::from("n"));
+        assert_eq!(owner.update(&count, |n| n * n), 4);
+        owner.set(&count, owner.get(&count) - 1);
+        assert_eq!(owner.get(&count), 3);
+        owner.set(&label, format!("{}={}", owner.get_cloned(&label), owner.get(&count)));
+        assert_eq!(owner.get_cloned(&label), "n=3");
     }
 
     #[test]
     fn tcell_get_mut() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TCellOwner<Marker>;
         type ACell<T> = TCell<Marker, T>;
         let owner = ACellOwner::new();
@@ -494,7 +1246,7 @@ mod tests {
 
     #[test]
     fn tcell_into_inner() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACell<T> = TCell<Marker, T>;
         let cell = ACell::new(100u32);
         assert_eq!(cell.into_inner(), 100);
@@ -502,7 +1254,7 @@ mod tests {
 
     #[test]
     fn tcell_unsized() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TCellOwner<Marker>;
         type ACell<T> = TCell<Marker, T>;
         let mut owner = ACellOwner::new();
@@ -0,0 +1,202 @@
+use std::convert::Infallible;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{panic_messages, sound, TCell, TCellMarker, TCellOwner};
+
+// Borrow the contents of all the cells in a slice mutably, as a
+// single slice.
+//
+// `TCell<Q, T>` is `repr(transparent)` over `UnsafeCell<T>` (the
+// `PhantomData` is zero-sized), so `[TCell<Q, T>]` has the same
+// layout as `[T]` of the same length.  Holding `&mut TCellOwner<Q>`
+// for `'a` guarantees that no other borrow of any `TCell<Q, _>` can
+// be active for `'a`, just like `TCellOwner::rw`, so the returned
+// slice is the only way to reach those values.
+fn rw_slice<'a, Q: TCellMarker, T>(
+    _owner: &'a mut TCellOwner<Q>,
+    cells: &'a [TCell<Q, T>],
+) -> &'a mut [T] {
+    // Safety: Transparent, Owned (by marker type), and owner mutably borrowed for 'a
+    unsafe { sound::transparent_slice_mut(cells) }
+}
+
+// Run `f` on each chunk from a pool of scoped threads, returning the
+// results in chunk order
+fn run_chunks<T: Send, R: Send>(
+    values: &mut [T],
+    chunk_size: usize,
+    f: impl Fn(usize, &mut [T]) -> R + Sync,
+) -> Vec<R> {
+    assert!(
+        chunk_size != 0,
+        "{}",
+        panic_messages::SCOPE_CHUNKS_ZERO_SIZE
+    );
+    let count = (values.len() + chunk_size - 1) / chunk_size;
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(count);
+    if workers <= 1 {
+        // No point in spawning a thread.  This also covers targets
+        // that can't spawn threads at all, such as WASM without the
+        // "atomics" target feature, where the parallelism query fails.
+        return values
+            .chunks_mut(chunk_size)
+            .enumerate()
+            .map(|(index, chunk)| f(index, chunk))
+            .collect();
+    }
+    let queue = Mutex::new(values.chunks_mut(chunk_size).enumerate());
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+                let (index, chunk) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let result = f(index, chunk);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("chunk result missing"))
+        .collect()
+}
+
+/// Mutate the contents of a slice of [`TCell`] instances in parallel,
+/// split into chunks of `chunk_size` cells, using a pool of scoped
+/// threads.
+///
+/// `f` is called once for each chunk, with the chunk index and a
+/// mutable slice of the contents of the cells in that chunk.  The
+/// chunks are disjoint, so they can be mutated in parallel.  This
+/// returns once all chunks have been processed, ending the borrow on
+/// the owner.  If `f` panics, the panic is propagated once all the
+/// threads have finished.  Panics if `chunk_size` is zero.
+///
+/// `T` must be `Send`, since the contents are accessed from other
+/// threads:
+///
+/// ```
+///# use qcell::{marker, scope_chunks, TCell, TCellOwner};
+/// marker!(struct Marker;);
+/// let mut owner = TCellOwner::<Marker>::new();
+/// let cells: Vec<_> = (0..1000).map(|i| TCell::new(i)).collect();
+/// scope_chunks(&mut owner, &cells, 100, |_, chunk| {
+///     for v in chunk {
+///         *v *= 2;
+///     }
+/// });
+/// assert_eq!(*owner.ro(&cells[999]), 1998);
+/// ```
+///
+/// [`TCell`]: struct.TCell.html
+#[cfg_attr(docsrs, doc(cfg(feature = "scoped-threads")))]
+pub fn scope_chunks<Q: TCellMarker, T: Send>(
+    owner: &mut TCellOwner<Q>,
+    cells: &[TCell<Q, T>],
+    chunk_size: usize,
+    f: impl Fn(usize, &mut [T]) + Sync,
+) {
+    let _ = try_scope_chunks(owner, cells, chunk_size, |index, chunk| {
+        f(index, chunk);
+        Ok::<(), Infallible>(())
+    });
+}
+
+/// Same as [`scope_chunks`], but `f` may fail.  The results for all
+/// the chunks are returned in chunk order.  A failure in one chunk
+/// doesn't stop the other chunks from being processed.
+///
+/// [`scope_chunks`]: fn.scope_chunks.html
+#[cfg_attr(docsrs, doc(cfg(feature = "scoped-threads")))]
+pub fn try_scope_chunks<Q: TCellMarker, T: Send, R: Send, E: Send>(
+    owner: &mut TCellOwner<Q>,
+    cells: &[TCell<Q, T>],
+    chunk_size: usize,
+    f: impl Fn(usize, &mut [T]) -> Result<R, E> + Sync,
+) -> Vec<Result<R, E>> {
+    run_chunks(rw_slice(owner, cells), chunk_size, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scope_chunks, try_scope_chunks};
+    use crate::{TCell, TCellOwner};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn chunks_visit_once() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cells: Vec<_> = (0..10_000).map(|_| TCell::new(0u32)).collect();
+        let calls = AtomicUsize::new(0);
+        scope_chunks(&mut owner, &cells, 2500, |index, chunk| {
+            assert_eq!(chunk.len(), 2500);
+            calls.fetch_add(1, Ordering::Relaxed);
+            for v in chunk {
+                *v += 1 + index as u32;
+            }
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+        for (i, cell) in cells.iter().enumerate() {
+            assert_eq!(*owner.ro(cell), 1 + (i / 2500) as u32);
+        }
+    }
+
+    #[test]
+    fn chunks_edge_cases() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let calls = AtomicUsize::new(0);
+        scope_chunks(&mut owner, &[] as &[TCell<Marker, u32>], 10, |_, _| {
+            calls.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+        let cells: Vec<_> = (0..5).map(TCell::new).collect();
+        scope_chunks(&mut owner, &cells, 100, |index, chunk| {
+            assert_eq!(index, 0);
+            assert_eq!(chunk.len(), 5);
+            calls.fetch_add(1, Ordering::Relaxed);
+            chunk.reverse();
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(*owner.ro(&cells[0]), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk_size")]
+    fn chunks_zero_size() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cells = [TCell::new(0)];
+        scope_chunks(&mut owner, &cells, 0, |_, _| ());
+    }
+
+    #[test]
+    fn chunks_fallible() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cells: Vec<_> = (0..10).map(TCell::new).collect();
+        let results = try_scope_chunks(&mut owner, &cells, 3, |index, chunk| {
+            chunk[0] = 100;
+            if index == 1 {
+                Err(index)
+            } else {
+                Ok(chunk.len())
+            }
+        });
+        assert_eq!(results, [Ok(3), Err(1), Ok(3), Ok(1)]);
+        assert_eq!(*owner.ro(&cells[9]), 100);
+        assert_eq!(*owner.ro(&cells[3]), 100);
+    }
+}
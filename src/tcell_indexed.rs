@@ -0,0 +1,422 @@
+use core::any::{type_name, TypeId};
+use core::marker::PhantomData;
+#[cfg(any(feature = "diagnostics", feature = "std", feature = "exclusion-set"))]
+use core::mem;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+use core::sync::atomic::AtomicU8;
+use core::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{OwnerKind, Registration};
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+use crate::typeid_gate::TypeGate;
+use crate::{panic_messages, sound, TCellMarker, TCellOwner};
+
+// Number of indices available to `IndexedMarker` types
+const INDEXED_MARKERS: usize = 64;
+
+// Returns the identity and name of a marker type.  Comparing the
+// `TypeId` values tells whether two slots hold the same marker,
+// without depending on whether function pointers are unique.
+pub(crate) type MarkerInfo = fn() -> (TypeId, &'static str);
+
+fn marker_info<Q: 'static>() -> (TypeId, &'static str) {
+    (TypeId::of::<Q>(), type_name::<Q>())
+}
+
+// Reservation states of a slot.  See `Slot::reserve`.
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+const UNRESERVED: u8 = 0;
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+const RESERVING: u8 = 1;
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+const RESERVED: u8 = 2;
+
+// One entry per index.  The first marker to create an owner with an
+// index claims the slot for the rest of the run, by storing its
+// `MarkerInfo` function there.  Any other marker type with the same
+// index then fails the check.  This is the only point at which two
+// markers declared in unrelated parts of a program meet, so this is
+// where duplicate indices are caught.
+struct Slot {
+    marker: AtomicPtr<()>,
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    reserved: AtomicU8,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    marker: AtomicPtr::new(ptr::null_mut()),
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    reserved: AtomicU8::new(UNRESERVED),
+};
+
+static SLOTS: [Slot; INDEXED_MARKERS] = [EMPTY_SLOT; INDEXED_MARKERS];
+
+// One bit per index, set whilst an owner for that index exists
+static OWNERS: AtomicU64 = AtomicU64::new(0);
+
+#[cold]
+#[inline(never)]
+fn index_in_use_panic(index: usize, existing: &str, new: &str) -> ! {
+    panic!(
+        "{}: index {} is used by both {} and {}",
+        panic_messages::TCELL_INDEX_IN_USE,
+        index,
+        existing,
+        new
+    );
+}
+
+impl Slot {
+    // Claim the slot at `index` for Q, or check that Q already has it
+    fn claim<Q: 'static>(&self, index: usize) {
+        let info: MarkerInfo = marker_info::<Q>;
+        let mut current = self.marker.load(Ordering::Acquire);
+        if current.is_null() {
+            match self.marker.compare_exchange(
+                ptr::null_mut(),
+                info as *mut (),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(other) => current = other,
+            }
+        }
+        // Safety: Slots only ever hold null or a `MarkerInfo`, and
+        // `current` is not null
+        let existing = unsafe { sound::marker_info_from_ptr(current) };
+        let (existing_id, existing_name) = existing();
+        if existing_id != TypeId::of::<Q>() {
+            index_in_use_panic(index, existing_name, type_name::<Q>());
+        }
+    }
+
+    // Take the marker out of the default registry for good, so that
+    // `TCellOwner::new` and friends can never create an owner for it
+    // alongside an indexed owner.  This is done once per marker, on
+    // first use.  Returns false if an owner created by the default
+    // registry exists, in which case the reservation is tried again
+    // next time.
+    #[cfg(any(feature = "std", feature = "exclusion-set"))]
+    fn reserve<Q: TCellMarker>(&self) -> bool {
+        loop {
+            match self.reserved.compare_exchange(
+                UNRESERVED,
+                RESERVING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let registered = TypeGate::try_claim::<Q>().map(mem::forget).is_some();
+                    let state = if registered { RESERVED } else { UNRESERVED };
+                    self.reserved.store(state, Ordering::Release);
+                    return registered;
+                }
+                Err(RESERVED) => return true,
+                // Another thread is reserving this marker right now,
+                // which only takes one insertion into the registry
+                Err(_) => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+/// Marker type with a fixed index, for use with
+/// [`TCellOwner::new_indexed`].
+///
+/// The singleton check for owners created this way is a single bit
+/// in a global `AtomicU64`, which is set when the owner is created and
+/// cleared when it is dropped.  There is no mutex, no hashing and no
+/// allocation, so this works without **std**, and is cheap enough to
+/// create and drop owners often.  The cost is that each marker needs
+/// an index in the range `0..64` which no other marker in the program
+/// uses.  Usually the index is given when declaring the marker with
+/// [`marker!`]:
+///
+/// ```
+///# use qcell::{marker, TCell, TCellOwner};
+/// marker!(struct PluginA = 0;);
+/// marker!(struct PluginB = 1;);
+///
+/// let mut owner_a = TCellOwner::<PluginA>::new_indexed();
+/// let owner_b = TCellOwner::<PluginB>::new_indexed();
+/// let cell = TCell::<PluginA, _>::new(1);
+/// *owner_a.rw(&cell) += 1;
+/// assert_eq!(*owner_a.ro(&cell), 2);
+/// assert!(TCellOwner::<PluginA>::try_new_indexed().is_none());
+/// drop(owner_a);
+/// assert!(TCellOwner::<PluginA>::try_new_indexed().is_some());
+///# drop(owner_b);
+/// ```
+///
+/// Markers may be declared in unrelated parts of a program, so two of
+/// them might be given the same index by mistake.  This is detected at
+/// runtime: the first marker to create an owner claims its index for
+/// the rest of the run, and creating an owner for any other marker
+/// with the same index panics, naming both types.  An index outside
+/// `0..64` is a compile error, which for a hand-written
+/// implementation is only reported once `new_indexed` is
+/// instantiated for that marker.
+///
+/// Once an indexed owner has been created for a marker, the marker
+/// is taken out of the registry used by [`TCellOwner::new`] for the
+/// rest of the run, so that the two kinds of owner can't exist
+/// together.  After that, `TCellOwner::new` for that marker always
+/// panics and `TCellOwner::try_new` always returns `None`.
+///
+/// [`TCellOwner::new_indexed`]: struct.TCellOwner.html#method.new_indexed
+/// [`TCellOwner::new`]: struct.TCellOwner.html#method.new
+/// [`marker!`]: macro.marker.html
+pub trait IndexedMarker: TCellMarker {
+    /// Index of this marker, in the range `0..64`, which must not be
+    /// used by any other marker type in the program
+    const INDEX: usize;
+}
+
+// Fails to compile when instantiated for a marker whose index is out
+// of range
+struct IndexCheck<Q>(PhantomData<Q>);
+
+impl<Q: IndexedMarker> IndexCheck<Q> {
+    const IN_RANGE: () = assert!(
+        Q::INDEX < INDEXED_MARKERS,
+        "IndexedMarker::INDEX must be less than 64"
+    );
+}
+
+impl<Q: IndexedMarker> TCellOwner<Q> {
+    /// Create the singleton owner instance for a marker with a fixed
+    /// index.  This call panics if an owner for `Q` already exists,
+    /// or if another marker type uses the same index.  See
+    /// [`IndexedMarker`].
+    ///
+    /// The owner is returned in an [`IndexedTCellOwner`], which
+    /// dereferences to the `TCellOwner`, and releases the index when
+    /// dropped.  Hooks registered with `OwnerLifecycle` are not run
+    /// for indexed owners.
+    ///
+    /// [`IndexedMarker`]: trait.IndexedMarker.html
+    /// [`IndexedTCellOwner`]: struct.IndexedTCellOwner.html
+    pub fn new_indexed() -> IndexedTCellOwner<Q> {
+        if let Some(owner) = TCellOwner::try_new_indexed() {
+            owner
+        } else {
+            // The index records no location, so there is none to give
+            panic!(
+                "{}: existing TCellOwner<{}>",
+                panic_messages::TCELL_SECOND_OWNER,
+                type_name::<Q>()
+            )
+        }
+    }
+
+    /// Same as [`TCellOwner::new_indexed`], except if another owner
+    /// for `Q` already exists, this returns `None` instead of
+    /// panicking.  This still panics if another marker type uses the
+    /// same index, since that is a mistake in the program rather than
+    /// a temporary condition.
+    ///
+    /// [`TCellOwner::new_indexed`]: struct.TCellOwner.html#method.new_indexed
+    pub fn try_new_indexed() -> Option<IndexedTCellOwner<Q>> {
+        #[allow(clippy::let_unit_value)]
+        let () = IndexCheck::<Q>::IN_RANGE;
+        let slot = &SLOTS[Q::INDEX];
+        slot.claim::<Q>(Q::INDEX);
+        // Without a default registry there are no other owners to
+        // exclude
+        #[cfg(any(feature = "std", feature = "exclusion-set"))]
+        if !slot.reserve::<Q>() {
+            return None;
+        }
+        let bit = 1 << Q::INDEX;
+        if OWNERS.fetch_or(bit, Ordering::Acquire) & bit != 0 {
+            return None;
+        }
+        let owner = TCellOwner::unregistered();
+        #[cfg(feature = "diagnostics")]
+        let owner = {
+            let mut owner = owner;
+            owner.diag = Registration::auto(OwnerKind::TCellOwner, Some(type_name::<Q>()));
+            owner
+        };
+        Some(IndexedTCellOwner { owner })
+    }
+}
+
+/// Owner created by [`TCellOwner::new_indexed`].
+///
+/// This dereferences to the [`TCellOwner`], so it is used in the same
+/// way as any other owner.  Dropping it releases the marker's index,
+/// so that another owner may be created.
+///
+/// [`TCellOwner::new_indexed`]: struct.TCellOwner.html#method.new_indexed
+/// [`TCellOwner`]: struct.TCellOwner.html
+pub struct IndexedTCellOwner<Q: IndexedMarker> {
+    // Not registered in the default registry, so must not be dropped
+    owner: ManuallyDrop<TCellOwner<Q>>,
+}
+
+impl<Q: IndexedMarker> Drop for IndexedTCellOwner<Q> {
+    fn drop(&mut self) {
+        // The owner itself is never dropped, so release its entry in
+        // the diagnostics registry here
+        #[cfg(feature = "diagnostics")]
+        drop(mem::replace(&mut self.owner.diag, Registration::NONE));
+        OWNERS.fetch_and(!(1 << Q::INDEX), Ordering::Release);
+    }
+}
+
+impl<Q: IndexedMarker> Deref for IndexedTCellOwner<Q> {
+    type Target = TCellOwner<Q>;
+
+    #[inline]
+    fn deref(&self) -> &TCellOwner<Q> {
+        &self.owner
+    }
+}
+
+impl<Q: IndexedMarker> DerefMut for IndexedTCellOwner<Q> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut TCellOwner<Q> {
+        &mut self.owner
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    // All the tests in a test binary share the one index table, so
+    // each test uses its own indices: 10..=13 and 40..=43 here, and
+    // 60 in `panic_messages`.
+    use super::IndexedMarker;
+    use crate::panic_messages::tests::panic_message;
+    use crate::{marker, panic_messages, TCell, TCellOwner};
+    use std::any::type_name;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn tcell_indexed() {
+        marker!(struct Marker = 10;);
+        assert_eq!(Marker::INDEX, 10);
+        let cell = TCell::<Marker, _>::new(vec![1]);
+        for i in 2..5 {
+            let mut owner = TCellOwner::<Marker>::new_indexed();
+            assert!(TCellOwner::<Marker>::try_new_indexed().is_none());
+            owner.rw(&cell).push(i);
+        }
+        let owner = TCellOwner::<Marker>::new_indexed();
+        assert_eq!(owner.ro(&cell), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn tcell_indexed_second_owner_panic() {
+        // The same panic as for the default registry
+        marker!(struct Default;);
+        marker!(struct Indexed = 11;);
+        let _owner1 = TCellOwner::<Default>::new();
+        let _owner2 = TCellOwner::<Indexed>::new_indexed();
+        let default_msg = panic_message(TCellOwner::<Default>::new);
+        let indexed_msg = panic_message(TCellOwner::<Indexed>::new_indexed);
+        // The same apart from the location, which only the default
+        // registry records
+        let prefix = |name: &str| {
+            format!(
+                "{}: existing TCellOwner<{}>",
+                panic_messages::TCELL_SECOND_OWNER,
+                name
+            )
+        };
+        assert_eq!(indexed_msg, prefix(type_name::<Indexed>()));
+        assert!(default_msg.starts_with(&(prefix(type_name::<Default>()) + " created at ")));
+    }
+
+    #[test]
+    fn tcell_indexed_excludes_default_registry() {
+        marker!(struct Marker = 12;);
+        let owner = TCellOwner::<Marker>::new();
+        assert!(TCellOwner::<Marker>::try_new_indexed().is_none());
+        drop(owner);
+        let owner = TCellOwner::<Marker>::new_indexed();
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+        drop(owner);
+        // The marker stays reserved for indexed owners
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+        assert!(TCellOwner::<Marker>::try_new_indexed().is_some());
+    }
+
+    #[test]
+    fn tcell_indexed_duplicate_index() {
+        marker!(struct First = 13;);
+        marker!(struct Second = 13;);
+        let owner = TCellOwner::<First>::new_indexed();
+        let msg = panic_message(TCellOwner::<Second>::try_new_indexed);
+        assert!(msg.starts_with(panic_messages::TCELL_INDEX_IN_USE));
+        assert!(msg.contains("index 13"));
+        assert!(msg.contains("First") && msg.contains("Second"));
+        // The first marker keeps the index, even with no owner
+        drop(owner);
+        let msg = panic_message(TCellOwner::<Second>::new_indexed);
+        assert!(msg.starts_with(panic_messages::TCELL_INDEX_IN_USE));
+        assert!(TCellOwner::<First>::try_new_indexed().is_some());
+    }
+
+    #[test]
+    fn tcell_indexed_64_threads() {
+        marker!(struct M0 = 40;);
+        marker!(struct M1 = 41;);
+        marker!(struct M2 = 42;);
+        marker!(struct M3 = 43;);
+        const CYCLES: usize = 100;
+
+        // Each thread repeatedly creates an owner, increments a
+        // counter through it without any other synchronisation, and
+        // drops it.  A lost update would mean two owners at once.
+        fn run<Q: IndexedMarker>(counter: Arc<TCell<Q, usize>>) -> thread::JoinHandle<()> {
+            thread::spawn(move || {
+                for _ in 0..CYCLES {
+                    let mut owner = loop {
+                        if let Some(owner) = TCellOwner::<Q>::try_new_indexed() {
+                            break owner;
+                        }
+                        thread::yield_now();
+                    };
+                    let count = owner.rw(&counter);
+                    let before = *count;
+                    thread::yield_now();
+                    *count = before + 1;
+                }
+            })
+        }
+
+        let c0 = Arc::new(TCell::<M0, usize>::new(0));
+        let c1 = Arc::new(TCell::<M1, usize>::new(0));
+        let c2 = Arc::new(TCell::<M2, usize>::new(0));
+        let c3 = Arc::new(TCell::<M3, usize>::new(0));
+        let mut handles = Vec::new();
+        for _ in 0..16 {
+            handles.push(run(c0.clone()));
+            handles.push(run(c1.clone()));
+            handles.push(run(c2.clone()));
+            handles.push(run(c3.clone()));
+        }
+        assert_eq!(handles.len(), 64);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let owners = (
+            TCellOwner::<M0>::new_indexed(),
+            TCellOwner::<M1>::new_indexed(),
+            TCellOwner::<M2>::new_indexed(),
+            TCellOwner::<M3>::new_indexed(),
+        );
+        assert_eq!(*owners.0.ro(&c0), 16 * CYCLES);
+        assert_eq!(*owners.1.ro(&c1), 16 * CYCLES);
+        assert_eq!(*owners.2.ro(&c2), 16 * CYCLES);
+        assert_eq!(*owners.3.ro(&c3), 16 * CYCLES);
+    }
+}
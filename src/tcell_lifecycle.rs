@@ -0,0 +1,339 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+
+use crate::{panic_messages, TCellMarker, TCellOwner};
+
+type Hook = Box<dyn Fn() + Send + Sync>;
+type PanicHandler = Box<dyn Fn(&str) + Send + Sync>;
+
+// Set once any hooks have been registered, so that until then the
+// owners don't need to touch the registry at all
+static HOOKS_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+static REGISTRY: Lazy<Mutex<HashMap<TypeId, Vec<Arc<OwnerLifecycle>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+std::thread_local! {
+    // Marker types whose hooks are running on this thread right now
+    static IN_HOOK: RefCell<Vec<TypeId>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+std::thread_local! {
+    static LOOKUPS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Hooks to run when the [`TCellOwner`] for a marker type is created
+/// or dropped, registered with [`TCellOwner::register_lifecycle`].
+///
+/// This is intended for framework crates that hand out owners to
+/// user code, and need to set something up when the owner appears,
+/// or tear it down when it goes away.
+///
+/// ```
+///# use qcell::{marker, OwnerLifecycle, TCellOwner};
+///# use std::sync::atomic::{AtomicUsize, Ordering};
+/// marker!(struct Marker;);
+/// static LIVE: AtomicUsize = AtomicUsize::new(0);
+/// TCellOwner::<Marker>::register_lifecycle(
+///     OwnerLifecycle::new()
+///         .on_create(|| {
+///             LIVE.fetch_add(1, Ordering::SeqCst);
+///         })
+///         .on_drop(|| {
+///             LIVE.fetch_sub(1, Ordering::SeqCst);
+///         }),
+/// );
+/// let owner = TCellOwner::<Marker>::new();
+/// assert_eq!(LIVE.load(Ordering::SeqCst), 1);
+/// drop(owner);
+/// assert_eq!(LIVE.load(Ordering::SeqCst), 0);
+/// ```
+///
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`TCellOwner::register_lifecycle`]: struct.TCellOwner.html#method.register_lifecycle
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Default)]
+pub struct OwnerLifecycle {
+    on_create: Option<Hook>,
+    on_drop: Option<Hook>,
+    on_drop_panic: Option<PanicHandler>,
+}
+
+impl OwnerLifecycle {
+    /// Create an empty set of hooks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the hook to run once the owner has been created, before
+    /// it is returned from `new`, `try_new` or `wait_for_new`.  If
+    /// this panics, the owner is released again without running any
+    /// `on_drop` hooks, and the panic propagates to the caller.
+    pub fn on_create(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_create = Some(Box::new(f));
+        self
+    }
+
+    /// Set the hook to run when the owner is dropped, before it is
+    /// released.  A panic in this hook is caught, since it would be
+    /// raised from within `Drop`, and is passed to the
+    /// [`OwnerLifecycle::on_drop_panic`] handler, if any.
+    ///
+    /// [`OwnerLifecycle::on_drop_panic`]: struct.OwnerLifecycle.html#method.on_drop_panic
+    pub fn on_drop(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_drop = Some(Box::new(f));
+        self
+    }
+
+    /// Set the handler to call with the panic message if the
+    /// `on_drop` hook panics.  The panic is swallowed either way, but
+    /// note that the standard panic hook will still have reported it.
+    pub fn on_drop_panic(mut self, f: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_drop_panic = Some(Box::new(f));
+        self
+    }
+}
+
+impl<Q: TCellMarker> TCellOwner<Q> {
+    /// Register hooks to run whenever the owner for marker type `Q` is
+    /// created or dropped.  Further registrations for the same marker
+    /// are appended: the `on_create` hooks run in registration order,
+    /// and the `on_drop` hooks in reverse order, so that teardown
+    /// mirrors setup.
+    ///
+    /// Hooks registered while an owner exists apply from that point
+    /// on, so the `on_drop` hook will run for the current owner even
+    /// though `on_create` didn't.  A hook must not create an owner for
+    /// the same marker type `Q`, since that owner already exists or
+    /// is about to.  This is detected, and causes a panic.
+    ///
+    /// Cell access never looks at the hooks, and until the first
+    /// registration, creating and dropping owners doesn't either.
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn register_lifecycle(hooks: OwnerLifecycle) {
+        REGISTRY
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<Q>())
+            .or_default()
+            .push(Arc::new(hooks));
+        HOOKS_REGISTERED.store(true, Ordering::Release);
+    }
+}
+
+fn hooks_for<Q: TCellMarker>() -> Vec<Arc<OwnerLifecycle>> {
+    if !HOOKS_REGISTERED.load(Ordering::Acquire) {
+        return Vec::new();
+    }
+    #[cfg(test)]
+    LOOKUPS.with(|n| n.set(n.get() + 1));
+    // Clone the list so that the mutex isn't held while the hooks run
+    let registry = REGISTRY.lock().unwrap();
+    registry.get(&TypeId::of::<Q>()).cloned().unwrap_or_default()
+}
+
+// Marks the hooks for a marker type as running on this thread
+struct InHook;
+
+impl InHook {
+    fn enter(id: TypeId) -> Self {
+        IN_HOOK.with(|ids| ids.borrow_mut().push(id));
+        Self
+    }
+}
+
+impl Drop for InHook {
+    fn drop(&mut self) {
+        let _ = IN_HOOK.try_with(|ids| ids.borrow_mut().pop());
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+// Panic if called from within one of the hooks for `Q`
+pub(crate) fn check_reentry<Q: TCellMarker>() {
+    if HOOKS_REGISTERED.load(Ordering::Acquire)
+        && IN_HOOK
+            .try_with(|ids| ids.borrow().contains(&TypeId::of::<Q>()))
+            .unwrap_or(false)
+    {
+        panic!(
+            "{}: {}",
+            panic_messages::TCELL_OWNER_IN_LIFECYCLE_HOOK,
+            type_name::<Q>()
+        );
+    }
+}
+
+// Run the `on_create` hooks for `Q`.  If one panics, the panic is
+// passed on, and the caller's claim on `Q` is released whilst
+// unwinding.
+pub(crate) fn run_create<Q: TCellMarker>() {
+    let hooks = hooks_for::<Q>();
+    if hooks.is_empty() {
+        return;
+    }
+    let _in_hook = InHook::enter(TypeId::of::<Q>());
+    for hook in &hooks {
+        if let Some(ref f) = hook.on_create {
+            f();
+        }
+    }
+}
+
+// Run the `on_drop` hooks for `Q`, swallowing any panics
+pub(crate) fn run_drop<Q: TCellMarker>() {
+    let hooks = hooks_for::<Q>();
+    if hooks.is_empty() {
+        return;
+    }
+    let _in_hook = InHook::enter(TypeId::of::<Q>());
+    for hook in hooks.iter().rev() {
+        if let Some(ref f) = hook.on_drop {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                if let Some(ref handler) = hook.on_drop_panic {
+                    let msg = panic_message(&*payload);
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(msg)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnerLifecycle, LOOKUPS};
+    use crate::panic_messages::tests::panic_message;
+    use crate::{TCell, TCellOwner};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn logger(log: &Arc<Mutex<Vec<String>>>, name: &str) -> OwnerLifecycle {
+        let (log1, log2) = (log.clone(), log.clone());
+        let (name1, name2) = (format!("+{}", name), format!("-{}", name));
+        OwnerLifecycle::new()
+            .on_create(move || log1.lock().unwrap().push(name1.clone()))
+            .on_drop(move || log2.lock().unwrap().push(name2.clone()))
+    }
+
+    #[test]
+    fn lifecycle_cycles() {
+        crate::marker!(struct Marker;);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        TCellOwner::<Marker>::register_lifecycle(logger(&log, "a"));
+        TCellOwner::<Marker>::register_lifecycle(logger(&log, "b"));
+        for _ in 0..2 {
+            let owner = TCellOwner::<Marker>::new();
+            log.lock().unwrap().push("live".into());
+            drop(owner);
+        }
+        let owner = TCellOwner::<Marker>::try_new().unwrap();
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+        drop(owner);
+        let expect = ["+a", "+b", "live", "-b", "-a"].repeat(2);
+        let mut expect: Vec<_> = expect.iter().map(|s| s.to_string()).collect();
+        expect.extend(["+a", "+b", "-b", "-a"].iter().map(|s| s.to_string()));
+        assert_eq!(*log.lock().unwrap(), expect);
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", not(target_feature = "atomics"))))]
+    #[test]
+    fn lifecycle_wait_for_new() {
+        crate::marker!(struct Marker;);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        TCellOwner::<Marker>::register_lifecycle(logger(&log, "a"));
+        let owner = TCellOwner::<Marker>::new();
+        let handle = std::thread::spawn(|| {
+            let owner = TCellOwner::<Marker>::wait_for_new();
+            drop(owner);
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        drop(owner);
+        handle.join().unwrap();
+        assert_eq!(*log.lock().unwrap(), ["+a", "-a", "+a", "-a"]);
+    }
+
+    #[test]
+    fn lifecycle_create_panic() {
+        crate::marker!(struct Marker;);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let fail = Arc::new(AtomicBool::new(true));
+        TCellOwner::<Marker>::register_lifecycle(logger(&log, "a"));
+        let fail2 = fail.clone();
+        TCellOwner::<Marker>::register_lifecycle(OwnerLifecycle::new().on_create(move || {
+            if fail2.load(Ordering::SeqCst) {
+                panic!("setup failed");
+            }
+        }));
+        let msg = panic_message(TCellOwner::<Marker>::new);
+        assert_eq!(msg, "setup failed");
+        assert_eq!(*log.lock().unwrap(), ["+a"]);
+
+        // The owner was released, without running the drop hooks
+        fail.store(false, Ordering::SeqCst);
+        drop(TCellOwner::<Marker>::new());
+        assert_eq!(*log.lock().unwrap(), ["+a", "+a", "-a"]);
+    }
+
+    #[test]
+    fn lifecycle_drop_panic() {
+        crate::marker!(struct Marker;);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        TCellOwner::<Marker>::register_lifecycle(logger(&log, "a"));
+        let log2 = log.clone();
+        TCellOwner::<Marker>::register_lifecycle(
+            OwnerLifecycle::new()
+                .on_drop(|| panic!("flush failed: {}", 42))
+                .on_drop_panic(move |msg| log2.lock().unwrap().push(format!("caught {}", msg))),
+        );
+        drop(TCellOwner::<Marker>::new());
+        assert_eq!(*log.lock().unwrap(), ["+a", "caught flush failed: 42", "-a"]);
+
+        // Still released
+        let _owner = TCellOwner::<Marker>::try_new().unwrap();
+    }
+
+    #[test]
+    fn lifecycle_reentry() {
+        crate::marker!(struct Marker;);
+        crate::marker!(struct Other;);
+        TCellOwner::<Marker>::register_lifecycle(OwnerLifecycle::new().on_create(|| {
+            // Owners for other markers are fine
+            drop(TCellOwner::<Other>::new());
+            let _ = TCellOwner::<Marker>::try_new();
+        }));
+        let msg = panic_message(TCellOwner::<Marker>::new);
+        assert!(msg.starts_with(crate::panic_messages::TCELL_OWNER_IN_LIFECYCLE_HOOK));
+        let _other = TCellOwner::<Other>::new();
+    }
+
+    #[test]
+    fn lifecycle_no_lookup_on_access() {
+        crate::marker!(struct Marker;);
+        TCellOwner::<Marker>::register_lifecycle(OwnerLifecycle::new());
+        let start = LOOKUPS.with(|n| n.get());
+        let mut owner = TCellOwner::<Marker>::new();
+        let cell = TCell::new(0u64);
+        for _ in 0..100 {
+            owner.update(&cell, |n| n + 1);
+            assert!(*owner.ro(&cell) > 0);
+        }
+        drop(owner);
+        assert_eq!(LOOKUPS.with(|n| n.get()) - start, 2);
+    }
+}
@@ -0,0 +1,371 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::Invariant;
+use crate::{panic_messages, sound, TCellMarker, TCellOwner};
+
+/// Cell whose contents is owned (for borrowing purposes) by one shard
+/// of a [`ShardedTCellOwner`].
+///
+/// The cell records the index of the shard it was created through,
+/// and can only be accessed through the [`TShardOwner`] for that
+/// shard.  Using the wrong shard causes a panic.
+///
+/// [`ShardedTCellOwner`]: struct.ShardedTCellOwner.html
+/// [`TShardOwner`]: struct.TShardOwner.html
+pub struct TShardCell<Q, T: ?Sized> {
+    // Use Invariant<Q> for invariant parameter
+    owner: PhantomData<Invariant<Q>>,
+    shard: u16,
+    value: UnsafeCell<T>,
+}
+
+// Same reasoning as for `TCell`
+unsafe impl<Q, T: Send + Sync + ?Sized> Sync for TShardCell<Q, T> {}
+
+impl<Q, T> TShardCell<Q, T> {
+    /// Destroy the cell and return the contained value
+    ///
+    /// Safety: Since this consumes the cell, there can be no other
+    /// references to the cell or the data at this point.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<Q, T: ?Sized> TShardCell<Q, T> {
+    /// Get the index of the shard that owns this cell
+    #[inline]
+    pub fn shard(&self) -> usize {
+        self.shard as usize
+    }
+
+    /// Returns a mutable reference to the underlying data
+    ///
+    /// Safety: This call borrows `TShardCell` mutably which
+    /// guarantees that we possess the only reference.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Borrowing-owner of the [`TShardCell`] instances belonging to one
+/// shard of a [`ShardedTCellOwner`].  Obtained only by locking a
+/// shard with [`ShardedTCellOwner::with_rw`] or
+/// [`ShardedTCellOwner::with_all_rw`].
+///
+/// [`TShardCell`]: struct.TShardCell.html
+/// [`ShardedTCellOwner`]: struct.ShardedTCellOwner.html
+/// [`ShardedTCellOwner::with_rw`]: struct.ShardedTCellOwner.html#method.with_rw
+/// [`ShardedTCellOwner::with_all_rw`]: struct.ShardedTCellOwner.html#method.with_all_rw
+pub struct TShardOwner<Q: TCellMarker> {
+    typ: PhantomData<Invariant<Q>>,
+    shard: u16,
+}
+
+#[cold]
+#[inline(never)]
+fn wrong_shard_panic(cell: u16, owner: u16) -> ! {
+    panic!(
+        "{}: cell of shard {}, owner of shard {}",
+        panic_messages::TSHARD_WRONG_SHARD,
+        cell,
+        owner
+    );
+}
+
+impl<Q: TCellMarker> TShardOwner<Q> {
+    /// Get the index of this shard
+    #[inline]
+    pub fn shard(&self) -> usize {
+        self.shard as usize
+    }
+
+    #[inline]
+    fn check<T: ?Sized>(&self, tc: &TShardCell<Q, T>) {
+        if tc.shard != self.shard {
+            wrong_shard_panic(tc.shard, self.shard);
+        }
+    }
+
+    /// Borrow contents of a [`TShardCell`] immutably (read-only).
+    /// Panics if the cell belongs to a different shard.
+    ///
+    /// [`TShardCell`]: struct.TShardCell.html
+    #[inline]
+    pub fn ro<'a, T: ?Sized>(&'a self, tc: &'a TShardCell<Q, T>) -> &'a T {
+        self.check(tc);
+        // Safety: Owned (shard checked above), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&tc.value) }
+    }
+
+    /// Borrow contents of a [`TShardCell`] mutably (read-write).
+    /// Panics if the cell belongs to a different shard.
+    ///
+    /// [`TShardCell`]: struct.TShardCell.html
+    #[inline]
+    pub fn rw<'a, T: ?Sized>(&'a mut self, tc: &'a TShardCell<Q, T>) -> &'a mut T {
+        self.check(tc);
+        // Safety: Owned (shard checked above), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&tc.value) }
+    }
+
+    /// Borrow contents of two [`TShardCell`] instances mutably.
+    /// Panics if the two cells point to the same memory, or if
+    /// either belongs to a different shard.
+    ///
+    /// [`TShardCell`]: struct.TShardCell.html
+    #[inline]
+    pub fn rw2<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        tc1: &'a TShardCell<Q, T>,
+        tc2: &'a TShardCell<Q, U>,
+    ) -> (&'a mut T, &'a mut U) {
+        self.check(tc1);
+        self.check(tc2);
+        assert!(
+            !ptr::eq(tc1 as *const _ as *const (), tc2 as *const _ as *const ()),
+            "{}",
+            panic_messages::TSHARD_BORROWED_TWICE
+        );
+        // Safety: Owned (shard checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&tc1.value, &tc2.value) }
+    }
+}
+
+/// Owner for a marker type `Q` whose cells are split into `N`
+/// independently lockable shards.
+///
+/// A single `Mutex<TCellOwner<Q>>` serializes all access to the
+/// cells, even when threads touch disjoint cells.  This owner instead
+/// holds `N` shard owners, each behind its own `Mutex`.  Each cell is
+/// created through a particular shard with
+/// [`ShardedTCellOwner::cell`], and records the shard index, so
+/// [`ShardedTCellOwner::with_rw`] only needs to lock that one shard.
+/// Attempting to access a cell through the wrong shard causes a panic.
+///
+/// Creating a [`ShardedTCellOwner`] claims the marker type `Q` in the
+/// same way as [`TCellOwner::new`], so only one may exist at a time
+/// for each marker type, and no [`TCellOwner`] for `Q` may exist at
+/// the same time.
+///
+/// ```
+///# use qcell::{marker, ShardedTCellOwner};
+/// marker!(struct Marker;);
+/// let owner = ShardedTCellOwner::<Marker, 4>::new();
+/// let c0 = owner.cell(0, 100);
+/// let c1 = owner.cell(1, 200);
+/// owner.with_rw(0, |shard| *shard.rw(&c0) += 1);
+/// owner.with_all_rw(|shards| {
+///     let (s0, s1) = shards.split_at_mut(1);
+///     std::mem::swap(s0[0].rw(&c0), s1[0].rw(&c1));
+/// });
+/// assert_eq!(owner.with_rw(0, |shard| *shard.ro(&c0)), 200);
+/// assert_eq!(owner.with_rw(1, |shard| *shard.ro(&c1)), 101);
+/// ```
+///
+/// [`ShardedTCellOwner`]: struct.ShardedTCellOwner.html
+/// [`ShardedTCellOwner::cell`]: struct.ShardedTCellOwner.html#method.cell
+/// [`ShardedTCellOwner::with_rw`]: struct.ShardedTCellOwner.html#method.with_rw
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`TCellOwner::new`]: struct.TCellOwner.html#method.new
+pub struct ShardedTCellOwner<Q: TCellMarker, const N: usize> {
+    // Held only to claim the marker type
+    _owner: TCellOwner<Q>,
+    shards: Box<[Mutex<TShardOwner<Q>>]>,
+}
+
+impl<Q: TCellMarker, const N: usize> ShardedTCellOwner<Q, N> {
+    /// Create the sharded owner for marker type `Q`.  Panics if
+    /// another owner for `Q` already exists, or if `N` is zero or
+    /// greater than 65536.
+    pub fn new() -> Self {
+        if let Some(owner) = Self::try_new() {
+            owner
+        } else {
+            panic!("{}", panic_messages::SHARDED_TCELL_SECOND_OWNER)
+        }
+    }
+
+    /// Same as [`ShardedTCellOwner::new`], except if another owner
+    /// for `Q` already exists, this returns `None` instead of
+    /// panicking.
+    ///
+    /// [`ShardedTCellOwner::new`]: struct.ShardedTCellOwner.html#method.new
+    pub fn try_new() -> Option<Self> {
+        assert!(
+            N > 0 && N <= 1 << 16,
+            "{}",
+            panic_messages::SHARDED_TCELL_SHARD_COUNT
+        );
+        let owner = TCellOwner::try_new()?;
+        let shards = (0..N)
+            .map(|shard| {
+                Mutex::new(TShardOwner {
+                    typ: PhantomData,
+                    shard: shard as u16,
+                })
+            })
+            .collect();
+        Some(Self {
+            _owner: owner,
+            shards,
+        })
+    }
+
+    /// Create a new cell owned by the given shard.  Panics if `shard`
+    /// is out of range.
+    pub fn cell<T>(&self, shard: usize, value: T) -> TShardCell<Q, T> {
+        assert!(shard < N, "{}", panic_messages::TSHARD_INDEX_OUT_OF_RANGE);
+        TShardCell {
+            owner: PhantomData,
+            shard: shard as u16,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Lock the given shard and call `f` with its owner.  Other
+    /// threads may access other shards at the same time.  Panics if
+    /// `shard` is out of range.
+    pub fn with_rw<R>(&self, shard: usize, f: impl FnOnce(&mut TShardOwner<Q>) -> R) -> R {
+        f(&mut lock(&self.shards[shard]))
+    }
+
+    /// Lock all the shards in index order and call `f` with all the
+    /// shard owners, indexed by shard.  This allows operations which
+    /// span shards.  Since the shards are always locked in the same
+    /// order, this can't deadlock with other calls to `with_all_rw`,
+    /// but it will deadlock if called from within
+    /// [`ShardedTCellOwner::with_rw`] on the same thread.
+    ///
+    /// [`ShardedTCellOwner::with_rw`]: struct.ShardedTCellOwner.html#method.with_rw
+    pub fn with_all_rw<R>(&self, f: impl FnOnce(&mut [&mut TShardOwner<Q>]) -> R) -> R {
+        let mut guards: Vec<_> = self.shards.iter().map(lock).collect();
+        let mut owners: Vec<_> = guards.iter_mut().map(|g| &mut **g).collect();
+        f(&mut owners)
+    }
+}
+
+impl<Q: TCellMarker, const N: usize> Default for ShardedTCellOwner<Q, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A shard owner has no state that a panic could leave inconsistent,
+// so ignore poisoning
+fn lock<Q: TCellMarker>(shard: &Mutex<TShardOwner<Q>>) -> MutexGuard<'_, TShardOwner<Q>> {
+    shard.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedTCellOwner;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn sharded_basic() {
+        crate::marker!(struct Marker;);
+        let owner = ShardedTCellOwner::<Marker, 2>::new();
+        assert!(ShardedTCellOwner::<Marker, 3>::try_new().is_none());
+        assert!(crate::TCellOwner::<Marker>::try_new().is_none());
+        let c1 = owner.cell(1, 10);
+        let c2 = owner.cell(1, 20);
+        assert_eq!(c1.shard(), 1);
+        owner.with_rw(1, |s| {
+            let (a, b) = s.rw2(&c1, &c2);
+            std::mem::swap(a, b);
+        });
+        assert_eq!(owner.with_rw(1, |s| (*s.ro(&c1), *s.ro(&c2))), (20, 10));
+        drop(owner);
+        let _owner = crate::TCellOwner::<Marker>::new();
+    }
+
+    #[test]
+    #[should_panic(expected = "cell of shard 0, owner of shard 1")]
+    fn sharded_cross_shard() {
+        crate::marker!(struct Marker;);
+        let owner = ShardedTCellOwner::<Marker, 2>::new();
+        let c0 = owner.cell(0, 10);
+        owner.with_rw(1, |s| *s.rw(&c0) += 1); // Panic here
+    }
+
+    #[test]
+    #[should_panic]
+    fn sharded_cell_out_of_range() {
+        crate::marker!(struct Marker;);
+        let owner = ShardedTCellOwner::<Marker, 2>::new();
+        let _ = owner.cell(2, 10); // Panic here
+    }
+
+    #[test]
+    fn sharded_concurrent() {
+        // Both threads hold their shard lock whilst waiting on the
+        // barrier, so this would deadlock if the shards excluded each
+        // other
+        crate::marker!(struct Marker;);
+        let owner = Arc::new(ShardedTCellOwner::<Marker, 2>::new());
+        let cells = Arc::new([owner.cell(0, 0), owner.cell(1, 0)]);
+        let barrier = Arc::new(Barrier::new(2));
+        let handles: Vec<_> = (0..2)
+            .map(|shard| {
+                let (owner, cells, barrier) = (owner.clone(), cells.clone(), barrier.clone());
+                thread::spawn(move || {
+                    owner.with_rw(shard, |s| {
+                        barrier.wait();
+                        *s.rw(&cells[shard]) += 1;
+                    })
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        owner.with_all_rw(|s| {
+            assert_eq!(*s[0].ro(&cells[0]), 1);
+            assert_eq!(*s[1].ro(&cells[1]), 1);
+        });
+    }
+
+    #[test]
+    fn sharded_stress() {
+        crate::marker!(struct Marker;);
+        const N: usize = 4;
+        let owner = Arc::new(ShardedTCellOwner::<Marker, N>::new());
+        let cells: Arc<Vec<_>> = Arc::new((0..N).map(|i| owner.cell(i, 0i64)).collect());
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let (owner, cells) = (owner.clone(), cells.clone());
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        if (i + t) % 3 == 0 {
+                            // Move one unit from each shard to the next
+                            owner.with_all_rw(|s| {
+                                for j in 0..N {
+                                    *s[j].rw(&cells[j]) -= 1;
+                                    *s[(j + 1) % N].rw(&cells[(j + 1) % N]) += 1;
+                                }
+                            });
+                        } else {
+                            let shard = (i * 7 + t) % N;
+                            owner.with_rw(shard, |s| *s.rw(&cells[shard]) += 1);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let total = owner.with_all_rw(|s| (0..N).map(|j| *s[j].ro(&cells[j])).sum::<i64>());
+        let expected = (0..8)
+            .map(|t| (0..200).filter(|i| (i + t) % 3 != 0).count() as i64)
+            .sum::<i64>();
+        assert_eq!(total, expected);
+    }
+}
@@ -0,0 +1,251 @@
+use core::marker::PhantomData;
+
+use crate::{TCell, TCellMarker, TCellOwner};
+#[cfg(feature = "std")]
+use crate::{TLCell, TLCellOwner};
+
+/// Marker type for slot `SLOT` of marker type `Q`.
+///
+/// This is never constructed.  It only exists so that each slot gets
+/// its own `TypeId`, which makes it a separate key in the singleton
+/// registry.  So `TCellSlotOwner<Q, 0>` and `TCellSlotOwner<Q, 1>`
+/// can exist at the same time, and since `SLOT` is part of the type,
+/// the cells of one slot can't be accessed with the owner of another.
+/// See [`TCellSlotOwner`].  Slots are also independent of `Q` itself, so a plain
+/// [`TCellOwner<Q>`] can exist alongside them.
+///
+/// Creation and drop hooks registered with [`OwnerLifecycle`] for
+/// `Q` are not run for slot owners, since those are owners of
+/// `SlotMarker<Q, SLOT>`.
+///
+/// [`TCellSlotOwner`]: type.TCellSlotOwner.html
+/// [`TCellOwner<Q>`]: struct.TCellOwner.html
+/// [`OwnerLifecycle`]: struct.OwnerLifecycle.html
+pub struct SlotMarker<Q, const SLOT: usize> {
+    _q: PhantomData<Q>,
+    _never: core::convert::Infallible,
+}
+
+// With the **strict-markers** feature there is no blanket
+// implementation, but a slot of a strict marker is still private to
+// whoever can name `Q`
+#[cfg(feature = "strict-markers")]
+impl<Q: TCellMarker, const SLOT: usize> TCellMarker for SlotMarker<Q, SLOT> {}
+
+/// Owner of slot `SLOT` of marker type `Q`.
+///
+/// Sometimes a fixed number of owners is wanted for one marker type,
+/// for example double-buffered state where slot 0 is "current" and
+/// slot 1 is "next".  Inventing distinct marker types for these
+/// breaks code written generically over one marker.  Instead each
+/// slot has its own singleton owner, which owns only the
+/// [`TCellSlot`] cells of its slot.  This is an ordinary
+/// [`TCellOwner`] for [`SlotMarker<Q, SLOT>`], so all the owner
+/// methods are available.
+///
+#[cfg_attr(
+    any(feature = "std", feature = "exclusion-set"),
+    doc = "
+ ```
+ # use qcell::{marker, TCellSlot, TCellSlotOwner};
+ marker!(struct Buffers;);
+ let mut current = TCellSlotOwner::<Buffers, 0>::new();
+ let mut next = TCellSlotOwner::<Buffers, 1>::new();
+ let c0 = TCellSlot::<Buffers, 0, _>::new(vec![1]);
+ let c1 = next.cell(vec![]);
+
+ // Build the next state from the current one, then flip
+ next.rw(&c1).extend(current.ro(&c0).iter().map(|v| v * 10));
+ current.swap_slots(&mut next, &c0, &c1);
+ assert_eq!(*current.ro(&c0), [10]);
+ ```
+"
+)]
+///
+/// [`TCellSlot`]: type.TCellSlot.html
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`SlotMarker<Q, SLOT>`]: struct.SlotMarker.html
+pub type TCellSlotOwner<Q, const SLOT: usize> = TCellOwner<SlotMarker<Q, SLOT>>;
+
+/// Cell owned by slot `SLOT` of marker type `Q`.  See
+/// [`TCellSlotOwner`].
+///
+/// [`TCellSlotOwner`]: type.TCellSlotOwner.html
+pub type TCellSlot<Q, const SLOT: usize, T> = TCell<SlotMarker<Q, SLOT>, T>;
+
+impl<Q, T> TCell<SlotMarker<Q, 0>, T> {
+    /// Convert a plain [`TCell`] into a slot-0 cell, to help migrate
+    /// code to slots.  This consumes the cell, so there can be no
+    /// borrows of it.
+    ///
+    /// [`TCell`]: struct.TCell.html
+    #[inline]
+    pub fn from_tcell(cell: TCell<Q, T>) -> Self {
+        TCell::new(cell.into_inner())
+    }
+
+    /// Convert a slot-0 cell back into a plain [`TCell`]
+    ///
+    /// [`TCell`]: struct.TCell.html
+    #[inline]
+    pub fn into_tcell(self) -> TCell<Q, T> {
+        TCell::new(self.into_inner())
+    }
+}
+
+impl<Q: TCellMarker> TCellOwner<SlotMarker<Q, 0>> {
+    /// Exchange the values of a slot-0 cell and a slot-1 cell, given
+    /// both owners mutably.  This is the flip of a double buffer.
+    #[inline]
+    pub fn swap_slots<T>(
+        &mut self,
+        other: &mut TCellSlotOwner<Q, 1>,
+        c0: &TCellSlot<Q, 0, T>,
+        c1: &TCellSlot<Q, 1, T>,
+    ) {
+        core::mem::swap(self.rw(c0), other.rw(c1));
+    }
+}
+
+/// Owner of slot `SLOT` of marker type `Q` for the current thread.
+/// This is the [`TLCellOwner`] equivalent of [`TCellSlotOwner`].
+///
+/// [`TLCellOwner`]: struct.TLCellOwner.html
+/// [`TCellSlotOwner`]: type.TCellSlotOwner.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub type TLCellSlotOwner<Q, const SLOT: usize> = TLCellOwner<SlotMarker<Q, SLOT>>;
+
+/// Cell owned by slot `SLOT` of marker type `Q` in the current
+/// thread.  See [`TLCellSlotOwner`].
+///
+/// [`TLCellSlotOwner`]: type.TLCellSlotOwner.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub type TLCellSlot<Q, const SLOT: usize, T> = TLCell<SlotMarker<Q, SLOT>, T>;
+
+#[cfg(feature = "std")]
+impl<Q, T> TLCell<SlotMarker<Q, 0>, T> {
+    /// Convert a plain [`TLCell`] into a slot-0 cell.  See
+    /// [`TCell::from_tcell`].
+    ///
+    /// [`TLCell`]: struct.TLCell.html
+    /// [`TCell::from_tcell`]: struct.TCell.html#method.from_tcell
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[inline]
+    pub fn from_tlcell(cell: TLCell<Q, T>) -> Self {
+        TLCell::new(cell.into_inner())
+    }
+
+    /// Convert a slot-0 cell back into a plain [`TLCell`]
+    ///
+    /// [`TLCell`]: struct.TLCell.html
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[inline]
+    pub fn into_tlcell(self) -> TLCell<Q, T> {
+        TLCell::new(self.into_inner())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Q: TCellMarker> TLCellOwner<SlotMarker<Q, 0>> {
+    /// Exchange the values of a slot-0 cell and a slot-1 cell, given
+    /// both owners mutably.  See [`TCellOwner::swap_slots`].
+    ///
+    /// [`TCellOwner::swap_slots`]: struct.TCellOwner.html#method.swap_slots
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[inline]
+    pub fn swap_slots<T>(
+        &mut self,
+        other: &mut TLCellSlotOwner<Q, 1>,
+        c0: &TLCellSlot<Q, 0, T>,
+        c1: &TLCellSlot<Q, 1, T>,
+    ) {
+        core::mem::swap(self.rw(c0), other.rw(c1));
+    }
+}
+
+#[cfg(all(test, any(feature = "std", feature = "exclusion-set")))]
+mod tests {
+    use super::{TCellSlot, TCellSlotOwner};
+    use crate::{TCell, TCellOwner};
+
+    #[test]
+    fn tcell_slot_owners_coexist() {
+        crate::marker!(struct Marker;);
+        let mut plain = TCellOwner::<Marker>::new();
+        let mut s0 = TCellSlotOwner::<Marker, 0>::new();
+        let s1 = TCellSlotOwner::<Marker, 1>::new();
+        assert!(TCellSlotOwner::<Marker, 0>::try_new().is_none());
+        assert!(TCellSlotOwner::<Marker, 1>::try_new().is_none());
+        assert!(TCellSlotOwner::<Marker, 2>::try_new().is_some());
+
+        // Migrate a plain cell into slot 0 and back
+        let c = TCell::<Marker, _>::new(1);
+        *plain.rw(&c) += 1;
+        let c = TCellSlot::from_tcell(c);
+        *s0.rw(&c) += 1;
+        let c1 = s1.cell(10);
+        assert_eq!(*s0.ro(&c) + *s1.ro(&c1), 13);
+        assert_eq!(*plain.ro(&c.into_tcell()), 3);
+
+        // Dropping one slot's owner frees only that slot
+        drop(s1);
+        assert!(TCellSlotOwner::<Marker, 1>::try_new().is_some());
+        assert!(TCellSlotOwner::<Marker, 0>::try_new().is_none());
+    }
+
+    #[test]
+    fn tcell_slot_double_buffer() {
+        crate::marker!(struct Life;);
+        let mut current = TCellSlotOwner::<Life, 0>::new();
+        let mut next = TCellSlotOwner::<Life, 1>::new();
+        let cur = current.cell(vec![false, true, true, false, true]);
+        let nxt = next.cell(Vec::new());
+
+        // Each cell becomes the XOR of its neighbours, reading only
+        // the current buffer and writing only the next
+        let mut history = Vec::new();
+        for _ in 0..4 {
+            let src = current.ro(&cur);
+            let dst = next.rw(&nxt);
+            dst.clear();
+            dst.extend((0..src.len()).map(|i| {
+                let left = i > 0 && src[i - 1];
+                let right = i + 1 < src.len() && src[i + 1];
+                left ^ right
+            }));
+            current.swap_slots(&mut next, &cur, &nxt);
+            history.push(current.ro(&cur).clone());
+        }
+        assert_eq!(
+            history,
+            [
+                [true, true, true, false, false],
+                [true, false, true, true, false],
+                [false, false, true, true, true],
+                [false, true, true, false, true],
+            ]
+        );
+        // The next buffer holds the previous generation
+        assert_eq!(*next.ro(&nxt), history[2]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn tlcell_slot() {
+        use super::{TLCellSlot, TLCellSlotOwner};
+        use crate::TLCell;
+        crate::marker!(struct Marker;);
+        let mut s0 = TLCellSlotOwner::<Marker, 0>::new();
+        let mut s1 = TLCellSlotOwner::<Marker, 1>::new();
+        let c0 = TLCellSlot::from_tlcell(TLCell::<Marker, _>::new("a"));
+        let c1 = s1.cell("b");
+        for _ in 0..3 {
+            s0.swap_slots(&mut s1, &c0, &c1);
+        }
+        assert_eq!((*s0.ro(&c0), *s1.ro(&c1)), ("b", "a"));
+        let c0: TLCell<Marker, _> = c0.into_tlcell();
+        assert_eq!(c0.into_inner(), "b");
+    }
+}
@@ -0,0 +1,255 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+
+use super::Invariant;
+use crate::{sound, TCellMarker, TCellOwner};
+
+/// Zero-sized token proving that the [`TCellOwner`] for marker `Q` is
+/// borrowed immutably for `'a`.  Created with
+/// [`TCellOwner::witness`].
+///
+/// This allows [`Guarded`] data to be read wherever the token is
+/// passed, without passing the owner reference itself.  It is `Copy`,
+/// like the shared reference it stands for.
+///
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`TCellOwner::witness`]: struct.TCellOwner.html#method.witness
+/// [`Guarded`]: struct.Guarded.html
+pub struct RoWitness<'a, Q: TCellMarker> {
+    // Has the same `Send` and `Sync` as `&'a TCellOwner<Q>`
+    owner: PhantomData<&'a TCellOwner<Q>>,
+}
+
+impl<'a, Q: TCellMarker> Clone for RoWitness<'a, Q> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, Q: TCellMarker> Copy for RoWitness<'a, Q> {}
+
+/// Zero-sized token proving that the [`TCellOwner`] for marker `Q` is
+/// borrowed mutably for `'a`.  Created with
+/// [`TCellOwner::witness_mut`].
+///
+/// This allows [`Guarded`] data to be modified wherever the token is
+/// passed, without passing the owner reference itself.  Like the
+/// `&mut` reference it stands for, it can't be copied, but it can be
+/// reborrowed to pass it on and use it again afterwards.
+///
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`TCellOwner::witness_mut`]: struct.TCellOwner.html#method.witness_mut
+/// [`Guarded`]: struct.Guarded.html
+pub struct RwWitness<'a, Q: TCellMarker> {
+    // Has the same `Send` and `Sync` as `&'a mut TCellOwner<Q>`
+    owner: PhantomData<&'a mut TCellOwner<Q>>,
+}
+
+impl<'a, Q: TCellMarker> RwWitness<'a, Q> {
+    /// Reborrow the witness for a shorter lifetime, for example to
+    /// pass it to a function and still use it afterwards
+    #[inline]
+    pub fn reborrow(&mut self) -> RwWitness<'_, Q> {
+        RwWitness { owner: PhantomData }
+    }
+
+    /// Get an immutable witness for as long as this one is borrowed
+    #[inline]
+    pub fn as_ro(&self) -> RoWitness<'_, Q> {
+        RoWitness { owner: PhantomData }
+    }
+}
+
+impl<Q: TCellMarker> TCellOwner<Q> {
+    /// Get a zero-sized witness that this owner is borrowed
+    /// immutably, to read [`Guarded`] data with.
+    ///
+    /// [`Guarded`]: struct.Guarded.html
+    #[inline]
+    pub fn witness(&self) -> RoWitness<'_, Q> {
+        RoWitness { owner: PhantomData }
+    }
+
+    /// Get a zero-sized witness that this owner is borrowed mutably,
+    /// to modify [`Guarded`] data with.  The owner can't be used
+    /// again until the witness and anything borrowed with it have
+    /// gone.
+    ///
+    /// [`Guarded`]: struct.Guarded.html
+    #[inline]
+    pub fn witness_mut(&mut self) -> RwWitness<'_, Q> {
+        RwWitness { owner: PhantomData }
+    }
+}
+
+/// Data of any kind protected by the [`TCellOwner`] for marker `Q`,
+/// which is accessed with a witness from the owner instead of the
+/// owner itself.
+///
+/// Holding `&mut TCellOwner<Q>` proves exclusive access to everything
+/// owned by marker `Q`, and that may be useful for protecting things
+/// that aren't cells, such as an FFI handle or a file.  This works
+/// exactly like a [`TCell`], apart from taking an [`RoWitness`] or
+/// [`RwWitness`] in place of the owner reference.  The witnesses are
+/// zero-sized, so can be passed deep into a call tree at no cost,
+/// without giving the callee the owner itself.
+///
+#[cfg_attr(
+    any(feature = "std", feature = "exclusion-set"),
+    doc = "
+ ```
+ # use qcell::{Guarded, RwWitness, TCellOwner};
+ qcell::marker!(struct Device;);
+
+ // Stands for a handle from a C library which isn't thread-safe
+ struct Handle { writes: u32 }
+
+ fn write(handle: &Guarded<Device, Handle>, witness: RwWitness<'_, Device>) {
+     handle.get_mut(witness).writes += 1;
+ }
+
+ let mut owner = TCellOwner::<Device>::new();
+ let handle = Guarded::new(Handle { writes: 0 });
+ let mut witness = owner.witness_mut();
+ write(&handle, witness.reborrow());
+ write(&handle, witness);
+ assert_eq!(handle.get(owner.witness()).writes, 2);
+ ```
+"
+)]
+///
+/// # Safety
+///
+/// The same reasoning as for [`TCell`] applies.  A witness can only
+/// be obtained by borrowing the owner, and it holds that borrow for
+/// its lifetime, so an [`RwWitness`] proves the same exclusive access
+/// as `&mut TCellOwner<Q>` would, and an [`RoWitness`] the same
+/// shared access as `&TCellOwner<Q>`.  The witnesses are `Send` and
+/// `Sync` exactly when those references are.  `Guarded<Q, T>` is
+/// `Sync` only if `T` is `Send` and `Sync`, like a `TCell`, since
+/// data may be moved out through `&mut T` on another thread.
+///
+/// See the [`doctest_tcell_witness`] module for uses which fail to
+/// compile.
+///
+/// [`TCell`]: struct.TCell.html
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`RoWitness`]: struct.RoWitness.html
+/// [`RwWitness`]: struct.RwWitness.html
+/// [`doctest_tcell_witness`]: doctest_tcell_witness/index.html
+#[repr(transparent)]
+pub struct Guarded<Q, T: ?Sized> {
+    // Invariant in Q, for the same reason as `TCell`
+    owner: PhantomData<Invariant<Q>>,
+    value: UnsafeCell<T>,
+}
+
+// Same reasoning as for `TCell`
+unsafe impl<Q, T: Send + Sync + ?Sized> Sync for Guarded<Q, T> {}
+
+impl<Q, T> Guarded<Q, T> {
+    /// Wrap `value`, to be accessed with a witness from the owner for
+    /// marker `Q`
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Self {
+            owner: PhantomData,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destroy the wrapper and return the data
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<Q: TCellMarker, T: ?Sized> Guarded<Q, T> {
+    /// Borrow the data immutably, for as long as the witness lasts
+    #[inline]
+    pub fn get<'a>(&'a self, witness: RoWitness<'a, Q>) -> &'a T {
+        let _ = witness;
+        // Safety: Owned (by marker type), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&self.value) }
+    }
+
+    /// Borrow the data mutably, for as long as the witness lasts.
+    /// This consumes the witness, so use [`RwWitness::reborrow`] to
+    /// keep it for later.
+    ///
+    /// [`RwWitness::reborrow`]: struct.RwWitness.html#method.reborrow
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub fn get_mut<'a>(&'a self, witness: RwWitness<'a, Q>) -> &'a mut T {
+        let _ = witness;
+        // Safety: Owned (by marker type), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&self.value) }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Guarded, RoWitness, RwWitness};
+    use crate::{TCell, TCellOwner};
+    use std::sync::Arc;
+
+    crate::marker!(struct Ffi;);
+
+    // Stands for a C library handle: a counter that must only be
+    // touched by one caller at a time
+    struct RawHandle {
+        calls: u32,
+        last: Vec<u8>,
+    }
+
+    fn ffi_write(handle: &Guarded<Ffi, RawHandle>, w: RwWitness<'_, Ffi>, data: &[u8]) {
+        let h = handle.get_mut(w);
+        h.calls += 1;
+        h.last = data.to_vec();
+    }
+
+    fn ffi_calls(handle: &Guarded<Ffi, RawHandle>, w: RoWitness<'_, Ffi>) -> u32 {
+        handle.get(w).calls
+    }
+
+    // Layers of calls that only pass the witness on
+    fn outer(handle: &Guarded<Ffi, RawHandle>, mut w: RwWitness<'_, Ffi>) -> u32 {
+        for chunk in [&b"ab"[..], b"cd"] {
+            inner(handle, w.reborrow(), chunk);
+        }
+        ffi_calls(handle, w.as_ro())
+    }
+
+    fn inner(handle: &Guarded<Ffi, RawHandle>, w: RwWitness<'_, Ffi>, data: &[u8]) {
+        ffi_write(handle, w, data);
+    }
+
+    #[test]
+    fn guarded_ffi_handle() {
+        let mut owner = TCellOwner::<Ffi>::new();
+        let cell = TCell::<Ffi, u32>::new(5);
+        let handle = Arc::new(Guarded::new(RawHandle {
+            calls: 0,
+            last: Vec::new(),
+        }));
+        assert_eq!(outer(&handle, owner.witness_mut()), 2);
+        // Cells and guarded data may be read with the owner at once
+        let w = owner.witness();
+        assert_eq!(handle.get(w).last, b"cd");
+        assert_eq!(*owner.ro(&cell) + ffi_calls(&handle, w), 7);
+
+        // Moving the owner to another thread takes the access with it
+        let handle2 = handle.clone();
+        let calls = std::thread::spawn(move || {
+            let mut owner = owner;
+            ffi_write(&handle2, owner.witness_mut(), b"x");
+            ffi_calls(&handle2, owner.witness())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(calls, 3);
+        assert_eq!(Arc::try_unwrap(handle).ok().unwrap().into_inner().last, b"x");
+    }
+}
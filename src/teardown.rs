@@ -0,0 +1,371 @@
+//! Explicit teardown of graphs of [`QCell`] nodes.
+//!
+//! A `Drop` implementation has no access to the owner, so it can't
+//! touch neighbouring cells.  Instead [`teardown`] walks a graph of
+//! `Rc<QCell<dyn CellTeardownAny>>` nodes from a set of roots, calls
+//! [`CellTeardown::teardown`] on each node exactly once with access
+//! to the owner, and then drops all the handles.
+//!
+//! Nodes are discovered breadth-first: first the roots in the order
+//! given, then the neighbours of each discovered node in the order
+//! reported by [`CellTeardown::neighbours`].  Nodes are deduplicated
+//! by pointer identity, so cycles are handled.  `teardown` is then
+//! called in reverse discovery order, so nodes furthest from the
+//! roots are torn down first.  The handles are dropped only after
+//! all `teardown` calls have completed.
+//!
+//! During `teardown` a node may read and modify any other cell owned
+//! by the owner via the [`TeardownOwner`] it is passed, but not its
+//! own cell, since that is already borrowed mutably as `self`.  It
+//! must not call [`teardown`] or [`teardown_typed`] recursively,
+//! which is detected and causes a panic.
+//!
+//! ```
+//!# use qcell::{QCell, QCellOwner};
+//!# use qcell::teardown::{teardown, CellTeardown, CellTeardownAny, TeardownOwner};
+//!# use std::rc::Rc;
+//! struct Node {
+//!     peers: Vec<Rc<QCell<Node>>>,
+//!     notified: Rc<QCell<u32>>,
+//! }
+//!
+//! impl CellTeardown for Node {
+//!     fn neighbours(&self, found: &mut Vec<Rc<QCell<dyn CellTeardownAny>>>) {
+//!         found.extend(self.peers.iter().map(|p| p.clone() as _));
+//!     }
+//!     fn teardown(&mut self, owner: &mut TeardownOwner<'_>) {
+//!         *owner.rw(&self.notified) += 1;
+//!         self.peers.clear(); // Break cycles
+//!     }
+//! }
+//!
+//! let mut owner = QCellOwner::new();
+//! let notified = Rc::new(owner.cell(0));
+//! let a = Rc::new(owner.cell(Node { peers: Vec::new(), notified: notified.clone() }));
+//! let b = Rc::new(owner.cell(Node { peers: vec![a.clone()], notified: notified.clone() }));
+//! owner.rw(&a).peers.push(b.clone());
+//! teardown(&mut owner, vec![a as _]);
+//! assert_eq!(*owner.ro(&notified), 2);
+//! assert_eq!(Rc::strong_count(&b), 1);
+//! ```
+//!
+//! [`QCell`]: ../struct.QCell.html
+//! [`teardown`]: fn.teardown.html
+//! [`teardown_typed`]: fn.teardown_typed.html
+//! [`CellTeardown::teardown`]: trait.CellTeardown.html#tymethod.teardown
+//! [`CellTeardown::neighbours`]: trait.CellTeardown.html#method.neighbours
+//! [`TeardownOwner`]: struct.TeardownOwner.html
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::{panic_messages, sound, QCell, QCellOwner};
+
+std::thread_local! {
+    static IN_TEARDOWN: Cell<bool> = const { Cell::new(false) };
+}
+
+// Clears the reentrancy flag on exit, even on panic
+struct ActiveGuard;
+
+impl ActiveGuard {
+    fn new() -> Self {
+        if IN_TEARDOWN.with(|f| f.replace(true)) {
+            panic!("{}", panic_messages::TEARDOWN_RECURSIVE);
+        }
+        ActiveGuard
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        IN_TEARDOWN.with(|f| f.set(false));
+    }
+}
+
+/// A node in a graph of cells that can be torn down by [`teardown`].
+///
+/// [`teardown`]: fn.teardown.html
+pub trait CellTeardown {
+    /// Add handles to the neighbouring nodes which should also be
+    /// torn down.  By default a node has no neighbours.
+    fn neighbours(&self, found: &mut Vec<Rc<QCell<dyn CellTeardownAny>>>) {
+        let _ = found;
+    }
+
+    /// Tear down this node.  Called exactly once per node.  The
+    /// owner may be used to access any other cell, but not the cell
+    /// containing this node.  Typically this would notify neighbours
+    /// and then clear any handles to them, to break cycles.
+    fn teardown(&mut self, owner: &mut TeardownOwner<'_>);
+}
+
+/// Object-safe version of [`CellTeardown`] which also allows the node
+/// to be downcast to its concrete type.  This is implemented
+/// automatically for all `'static` types which implement
+/// [`CellTeardown`].
+///
+/// [`CellTeardown`]: trait.CellTeardown.html
+pub trait CellTeardownAny: CellTeardown {
+    /// Get the node as `&dyn Any`, for downcasting
+    fn as_any(&self) -> &dyn Any;
+
+    /// Get the node as `&mut dyn Any`, for downcasting
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: CellTeardown + Any> CellTeardownAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Restricted access to a [`QCellOwner`], passed to
+/// [`CellTeardown::teardown`].  This allows access to any cell except
+/// the one currently being torn down.
+///
+/// [`QCellOwner`]: ../struct.QCellOwner.html
+/// [`CellTeardown::teardown`]: trait.CellTeardown.html#tymethod.teardown
+pub struct TeardownOwner<'a> {
+    owner: &'a mut QCellOwner,
+    current: *const (),
+}
+
+impl<'a> TeardownOwner<'a> {
+    #[inline]
+    fn check<T: ?Sized>(&self, qc: &QCell<T>) {
+        if qc as *const QCell<T> as *const () == self.current {
+            panic!("{}", panic_messages::TEARDOWN_CELL_IN_USE);
+        }
+    }
+
+    /// Borrow contents of a [`QCell`] immutably.  Panics if the cell
+    /// is the one being torn down, or if it is not owned by this
+    /// owner.
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    #[inline]
+    pub fn ro<'b, T: ?Sized>(&'b self, qc: &'b QCell<T>) -> &'b T {
+        self.check(qc);
+        self.owner.ro(qc)
+    }
+
+    /// Borrow contents of a [`QCell`] mutably.  Panics if the cell is
+    /// the one being torn down, or if it is not owned by this owner.
+    ///
+    /// [`QCell`]: ../struct.QCell.html
+    #[inline]
+    pub fn rw<'b, T: ?Sized>(&'b mut self, qc: &'b QCell<T>) -> &'b mut T {
+        self.check(qc);
+        self.owner.rw(qc)
+    }
+}
+
+// Discover the nodes reachable from `roots` breadth-first, as
+// described in the module documentation, deduplicating them by
+// pointer identity.  `neighbours` adds the neighbours of a node to
+// the `Vec` passed.
+pub(crate) fn walk_graph<T: ?Sized>(
+    roots: impl IntoIterator<Item = Rc<T>>,
+    mut neighbours: impl FnMut(&Rc<T>, &mut Vec<Rc<T>>),
+) -> Vec<Rc<T>> {
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    for root in roots {
+        if seen.insert(Rc::as_ptr(&root) as *const () as usize) {
+            nodes.push(root);
+        }
+    }
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < nodes.len() {
+        neighbours(&nodes[i], &mut found);
+        for node in found.drain(..) {
+            if seen.insert(Rc::as_ptr(&node) as *const () as usize) {
+                nodes.push(node);
+            }
+        }
+        i += 1;
+    }
+    nodes
+}
+
+/// Tear down the graph of nodes reachable from `roots`, as described
+/// in the [module documentation](index.html).  Panics if called from
+/// within a [`CellTeardown::teardown`] call.
+///
+/// [`CellTeardown::teardown`]: trait.CellTeardown.html#tymethod.teardown
+pub fn teardown(owner: &mut QCellOwner, roots: Vec<Rc<QCell<dyn CellTeardownAny>>>) {
+    let _guard = ActiveGuard::new();
+
+    let nodes = walk_graph(roots, |node, found| owner.ro(node).neighbours(found));
+
+    for node in nodes.iter().rev() {
+        let this: *mut dyn CellTeardownAny = owner.rw(node);
+        let mut access = TeardownOwner {
+            owner: &mut *owner,
+            current: Rc::as_ptr(node) as *const (),
+        };
+        // `access` refuses access to this node's cell, so this is the
+        // only reference to its contents until `teardown` returns.
+        // Safety: Derived from the `rw` borrow, and no other use, as above
+        let this = unsafe { sound::deref_raw_mut(this) };
+        this.teardown(&mut access);
+    }
+    drop(nodes);
+}
+
+/// Same as [`teardown`], but for a homogeneous graph whose roots are
+/// all of the same type.
+///
+/// [`teardown`]: fn.teardown.html
+pub fn teardown_typed<T: CellTeardown + 'static>(owner: &mut QCellOwner, roots: Vec<Rc<QCell<T>>>) {
+    teardown(owner, roots.into_iter().map(|r| r as _).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{teardown, teardown_typed, CellTeardown, CellTeardownAny, TeardownOwner};
+    use crate::{QCell, QCellOwner};
+    use std::rc::Rc;
+
+    struct Node {
+        name: u32,
+        peers: Vec<Rc<QCell<Node>>>,
+        log: Rc<QCell<Vec<(u32, u32)>>>,
+        count: u32,
+    }
+
+    impl CellTeardown for Node {
+        fn neighbours(&self, found: &mut Vec<Rc<QCell<dyn CellTeardownAny>>>) {
+            found.extend(self.peers.iter().map(|p| p.clone() as _));
+        }
+
+        fn teardown(&mut self, owner: &mut TeardownOwner<'_>) {
+            self.count += 1;
+            assert_eq!(self.count, 1);
+            for peer in &self.peers {
+                let peer_name = owner.ro(peer).name;
+                owner.rw(&self.log).push((self.name, peer_name));
+            }
+            owner.rw(&self.log).push((self.name, self.name));
+            self.peers.clear();
+        }
+    }
+
+    fn node(owner: &QCellOwner, name: u32, log: &Rc<QCell<Vec<(u32, u32)>>>) -> Rc<QCell<Node>> {
+        Rc::new(owner.cell(Node {
+            name,
+            peers: Vec::new(),
+            log: log.clone(),
+            count: 0,
+        }))
+    }
+
+    #[test]
+    fn teardown_order() {
+        let mut owner = QCellOwner::new();
+        let log = Rc::new(owner.cell(Vec::new()));
+        let n: Vec<_> = (0..4).map(|i| node(&owner, i, &log)).collect();
+        // 0 -> 1, 2; 1 -> 3; 2 -> 3
+        owner.rw(&n[0]).peers = vec![n[1].clone(), n[2].clone()];
+        owner.rw(&n[1]).peers = vec![n[3].clone()];
+        owner.rw(&n[2]).peers = vec![n[3].clone()];
+        teardown_typed(&mut owner, vec![n[0].clone()]);
+        // Discovery order is 0, 1, 2, 3, so teardown is 3, 2, 1, 0
+        assert_eq!(
+            *owner.ro(&log),
+            [
+                (3, 3),
+                (2, 3),
+                (2, 2),
+                (1, 3),
+                (1, 1),
+                (0, 1),
+                (0, 2),
+                (0, 0)
+            ]
+        );
+        for node in &n {
+            assert_eq!(owner.ro(node).count, 1);
+            assert_eq!(Rc::strong_count(node), 1);
+        }
+    }
+
+    #[test]
+    fn teardown_cycle() {
+        let mut owner = QCellOwner::new();
+        let log = Rc::new(owner.cell(Vec::new()));
+        let a = node(&owner, 1, &log);
+        let b = node(&owner, 2, &log);
+        owner.rw(&a).peers = vec![b.clone()];
+        owner.rw(&b).peers = vec![a.clone()];
+        let weak = Rc::downgrade(&a);
+        teardown(&mut owner, vec![a.clone() as _, b.clone() as _, a as _]);
+        assert_eq!(*owner.ro(&log), [(2, 1), (2, 2), (1, 2), (1, 1)]);
+        assert_eq!(owner.ro(&b).count, 1);
+        assert_eq!(Rc::strong_count(&b), 1);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn teardown_downcast() {
+        let mut owner = QCellOwner::new();
+        let log = Rc::new(owner.cell(Vec::new()));
+        let a: Rc<QCell<dyn CellTeardownAny>> = node(&owner, 7, &log);
+        let name = owner.ro(&a).as_any().downcast_ref::<Node>().map(|n| n.name);
+        assert_eq!(name, Some(7));
+        teardown(&mut owner, vec![a]);
+        assert_eq!(*owner.ro(&log), [(7, 7)]);
+    }
+
+    struct Reentrant;
+
+    impl CellTeardown for Reentrant {
+        fn teardown(&mut self, _: &mut TeardownOwner<'_>) {
+            let mut owner2 = QCellOwner::new();
+            teardown(&mut owner2, Vec::new()); // Panic here
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "recursively")]
+    fn teardown_reentrant() {
+        let mut owner = QCellOwner::new();
+        let node = Rc::new(owner.cell(Reentrant));
+        teardown_typed(&mut owner, vec![node]);
+    }
+
+    #[test]
+    fn teardown_reentrant_flag_reset() {
+        let result = std::panic::catch_unwind(teardown_reentrant);
+        assert!(result.is_err());
+        let mut owner = QCellOwner::new();
+        teardown(&mut owner, Vec::new());
+    }
+
+    struct SelfAccess(Option<Rc<QCell<SelfAccess>>>);
+
+    impl CellTeardown for SelfAccess {
+        fn teardown(&mut self, owner: &mut TeardownOwner<'_>) {
+            if let Some(ref this) = self.0 {
+                owner.ro(this); // Panic here
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "being torn down")]
+    fn teardown_self_access() {
+        let mut owner = QCellOwner::new();
+        let node = Rc::new(owner.cell(SelfAccess(None)));
+        owner.rw(&node).0 = Some(node.clone());
+        teardown_typed(&mut owner, vec![node]);
+    }
+}
@@ -1,14 +1,21 @@
-use std::any::TypeId;
+use std::borrow::Borrow;
 use std::cell::UnsafeCell;
-use std::collections::HashSet;
 use std::marker::PhantomData;
 
 use super::Invariant;
+use crate::batch;
+use crate::capability::Capability;
+use crate::panic_messages;
+use crate::ro_slice::{writer_check, RoSlice, ValuePtr};
+use crate::rw_many::distinct_check;
+use crate::sound;
+use crate::typeid_gate::{second_owner_panic, LocalClaimGuard, LocalTypeGate};
+use crate::view::{SliceView, SliceViewMut, VecViewMut};
+use crate::TCellMarker;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{OwnerKind, Registration};
 
-std::thread_local! {
-    static SINGLETON_CHECK: std::cell::RefCell<HashSet<TypeId>> = std::cell::RefCell::new(HashSet::new());
-}
-
+#[allow(dead_code)]
 struct NotSendOrSync(*const ());
 
 /// Borrowing-owner of zero or more [`TLCell`](struct.TLCell.html)
@@ -16,41 +23,53 @@ struct NotSendOrSync(*const ());
 ///
 /// See [crate documentation](index.html).
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
-pub struct TLCellOwner<Q: 'static> {
+pub struct TLCellOwner<Q: TCellMarker> {
     // Use NotSendOrSync to disable Send and Sync,
     not_send_or_sync: PhantomData<NotSendOrSync>,
     // Use Invariant<Q> for invariant parameter
     typ: PhantomData<Invariant<Q>>,
+    // The claim on Q in this thread's `LocalTypeGate`, released when
+    // the owner is dropped
+    _claim: LocalClaimGuard<Q>,
+    #[cfg(feature = "diagnostics")]
+    pub(crate) diag: Registration,
 }
 
-impl<Q: 'static> Drop for TLCellOwner<Q> {
-    fn drop(&mut self) {
-        SINGLETON_CHECK.with(|set| set.borrow_mut().remove(&TypeId::of::<Q>()));
-    }
-}
-
-impl<Q: 'static> Default for TLCellOwner<Q> {
+impl<Q: TCellMarker> Default for TLCellOwner<Q> {
+    #[track_caller]
     fn default() -> Self {
         TLCellOwner::new()
     }
 }
 
-impl<Q: 'static> TLCellOwner<Q> {
+impl<Q: TCellMarker> TLCellOwner<Q> {
     /// Create the singleton owner instance.  Each owner may be used
     /// to create many `TLCell` instances.  There may be only one
     /// instance of this type per thread at any given time for each
     /// different marker type `Q`.  This call panics if a second
-    /// simultaneous instance is created.  Since the owner is only
-    /// valid to use in the thread it is created in, it does not
-    /// support `Send` or `Sync`.
+    /// simultaneous instance is created, and the panic message gives
+    /// the source location where the existing owner was created.
+    /// Since the owner is only valid to use in the thread it is
+    /// created in, it does not support `Send` or `Sync`.  This can't
+    /// be a `const fn`, since it registers the owner in a
+    /// thread-local set.
+    #[track_caller]
     pub fn new() -> Self {
-        SINGLETON_CHECK.with(|set| {
-            assert!(set.borrow_mut().insert(TypeId::of::<Q>()),
-                    "Illegal to create two TLCellOwner instances within the same thread with the same marker type parameter");
-        });
+        let claim = match LocalTypeGate::try_claim::<Q>() {
+            Some(claim) => claim,
+            None => second_owner_panic(
+                panic_messages::TLCELL_SECOND_OWNER,
+                "TLCellOwner",
+                std::any::type_name::<Q>(),
+                LocalTypeGate::claimant::<Q>(),
+            ),
+        };
         Self {
             not_send_or_sync: PhantomData,
             typ: PhantomData,
+            _claim: claim,
+            #[cfg(feature = "diagnostics")]
+            diag: Registration::auto(OwnerKind::TLCellOwner, Some(std::any::type_name::<Q>())),
         }
     }
 
@@ -67,7 +86,8 @@ impl<Q: 'static> TLCellOwner<Q> {
     /// from the same owner.
     #[inline]
     pub fn ro<'a, T: ?Sized>(&'a self, tc: &'a TLCell<Q, T>) -> &'a T {
-        unsafe { &*tc.value.get() }
+        // Safety: Owned (by marker type), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&tc.value) }
     }
 
     /// Borrow contents of a `TLCell` mutably (read-write).  Only one
@@ -76,12 +96,14 @@ impl<Q: 'static> TLCellOwner<Q> {
     /// another can be borrowed.
     #[inline]
     pub fn rw<'a, T: ?Sized>(&'a mut self, tc: &'a TLCell<Q, T>) -> &'a mut T {
-        unsafe { &mut *tc.value.get() }
+        // Safety: Owned (by marker type), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&tc.value) }
     }
 
     /// Borrow contents of two `TLCell` instances mutably.  Panics if
     /// the two `TLCell` instances point to the same memory.
     #[inline]
+    #[allow(clippy::ptr_eq)]
     pub fn rw2<'a, T: ?Sized, U: ?Sized>(
         &'a mut self,
         tc1: &'a TLCell<Q, T>,
@@ -89,14 +111,17 @@ impl<Q: 'static> TLCellOwner<Q> {
     ) -> (&'a mut T, &'a mut U) {
         assert!(
             tc1 as *const _ as *const () as usize != tc2 as *const _ as *const () as usize,
-            "Illegal to borrow same TLCell twice with rw2()"
+            "{}",
+            panic_messages::TLCELL_BORROWED_TWICE
         );
-        unsafe { (&mut *tc1.value.get(), &mut *tc2.value.get()) }
+        // Safety: Owned (by marker type), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&tc1.value, &tc2.value) }
     }
 
     /// Borrow contents of three `TLCell` instances mutably.  Panics if
     /// any pair of `TLCell` instances point to the same memory.
     #[inline]
+    #[allow(clippy::ptr_eq)]
     pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
         &'a mut self,
         tc1: &'a TLCell<Q, T>,
@@ -107,16 +132,280 @@ impl<Q: 'static> TLCellOwner<Q> {
             (tc1 as *const _ as *const () as usize != tc2 as *const _ as *const () as usize)
                 && (tc2 as *const _ as *const () as usize != tc3 as *const _ as *const () as usize)
                 && (tc3 as *const _ as *const () as usize != tc1 as *const _ as *const () as usize),
-            "Illegal to borrow same TLCell twice with rw3()"
+            "{}",
+            panic_messages::TLCELL_BORROWED_TWICE
         );
-        unsafe {
-            (
-                &mut *tc1.value.get(),
-                &mut *tc2.value.get(),
-                &mut *tc3.value.get(),
-            )
+        // Safety: Owned (by marker type), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&tc1.value, &tc2.value, &tc3.value) }
+    }
+
+    /// Borrow contents of one `TLCell` mutably, and the contents of a
+    /// slice of `TLCell` instances immutably.  The readers may repeat,
+    /// but none may be the same cell as the writer.  Panics with the
+    /// position of the first reader that is the same as the writer.
+    /// The contents of the readers are fetched lazily through the
+    /// returned [`RoSlice`].
+    ///
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        w: &'a TLCell<Q, T>,
+        readers: &'a [&'a TLCell<Q, U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        writer_check("TLCell", w, readers);
+        // Safety: Owned (by marker type), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
+    }
+
+    /// Copy the contents of a `TLCell` out, like `Cell::get`.  No
+    /// borrow is held after this returns.
+    #[inline]
+    pub fn get<T: Copy>(&self, tc: &TLCell<Q, T>) -> T {
+        *self.ro(tc)
+    }
+
+    /// Clone the contents of a `TLCell` out.  This is a separate call
+    /// from [`TLCellOwner::get`] so that `get` stays a plain copy.
+    ///
+    /// [`TLCellOwner::get`]: struct.TLCellOwner.html#method.get
+    #[inline]
+    pub fn get_cloned<T: Clone>(&self, tc: &TLCell<Q, T>) -> T {
+        self.ro(tc).clone()
+    }
+
+    /// Replace the contents of a `TLCell` with `value`, like
+    /// `Cell::set`, dropping the old value.
+    #[inline]
+    pub fn set<T>(&mut self, tc: &TLCell<Q, T>, value: T) {
+        *self.rw(tc) = value;
+    }
+
+    /// Replace the contents of a `TLCell` with the result of calling
+    /// `f` on a copy of the old contents, and return the new value.
+    #[inline]
+    pub fn update<T: Copy>(&mut self, tc: &TLCell<Q, T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(tc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Copy the contents of many `TLCell`s into `out` without
+    /// allocating, for example to take a snapshot of a set of
+    /// parameters.  This is the same as calling [`TLCellOwner::get`]
+    /// for each cell.
+    ///
+    /// [`TLCellOwner::get`]: struct.TLCellOwner.html#method.get
+    #[inline]
+    pub fn read_into<T: Copy, const N: usize>(&self, cells: &[&TLCell<Q, T>; N], out: &mut [T; N]) {
+        self.read_into_slice(cells, out);
+    }
+
+    /// Copy the contents of many `TLCell`s into `out`, like
+    /// [`TLCellOwner::read_into`], but with the lengths checked at
+    /// runtime.  Panics if `cells` and `out` have different
+    /// lengths.
+    ///
+    /// [`TLCellOwner::read_into`]: struct.TLCellOwner.html#method.read_into
+    #[inline]
+    pub fn read_into_slice<T: Copy>(&self, cells: &[&TLCell<Q, T>], out: &mut [T]) {
+        batch::length_check(cells.len(), out.len());
+        for (tc, value) in cells.iter().zip(out) {
+            *value = *self.ro(tc);
+        }
+    }
+
+    /// Copy the values in `src` into many `TLCell`s without
+    /// allocating.  The cells are written in order, and no check
+    /// is made for repeated cells, so if the same `TLCell` appears
+    /// more than once then the last write wins.
+    #[inline]
+    pub fn write_from<T: Copy, const N: usize>(
+        &mut self,
+        cells: &[&TLCell<Q, T>; N],
+        src: &[T; N],
+    ) {
+        self.write_from_slice(cells, src);
+    }
+
+    /// Copy the values in `src` into many `TLCell`s, like
+    /// [`TLCellOwner::write_from`], but with the lengths checked at
+    /// runtime.  Panics before writing anything if `cells` and
+    /// `src` have different lengths.
+    ///
+    /// [`TLCellOwner::write_from`]: struct.TLCellOwner.html#method.write_from
+    #[inline]
+    pub fn write_from_slice<T: Copy>(&mut self, cells: &[&TLCell<Q, T>], src: &[T]) {
+        batch::length_check(cells.len(), src.len());
+        for (tc, value) in cells.iter().zip(src) {
+            *self.rw(tc) = *value;
+        }
+    }
+
+    /// Borrow the contents of a `TLCell` holding a slice, `Vec`, boxed
+    /// slice or array as a read-only [`SliceView`], which supports
+    /// indexing by position or range.
+    ///
+    /// [`SliceView`]: struct.SliceView.html
+    #[inline]
+    pub fn view<'a, T, S: AsRef<[T]> + ?Sized>(&'a self, tc: &'a TLCell<Q, S>) -> SliceView<'a, T> {
+        SliceView::new(self.ro(tc).as_ref())
+    }
+
+    /// Borrow the contents of a `TLCell` holding a `Vec` as a
+    /// [`VecViewMut`], which supports indexing for reading and
+    /// writing.  This owner stays mutably borrowed while the view
+    /// exists, so no other cell can be borrowed through it.
+    ///
+    /// [`VecViewMut`]: struct.VecViewMut.html
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[inline]
+    pub fn view_mut<'a, T>(&'a mut self, tc: &'a TLCell<Q, Vec<T>>) -> VecViewMut<'a, T> {
+        VecViewMut::new(self.rw(tc))
+    }
+
+    /// Borrow the contents of a `TLCell` holding a slice, `Vec`, boxed
+    /// slice or array as a [`SliceViewMut`], which supports indexing
+    /// for reading and writing.  This owner stays mutably borrowed
+    /// while the view exists.
+    ///
+    /// [`SliceViewMut`]: struct.SliceViewMut.html
+    #[inline]
+    pub fn slice_view_mut<'a, T, S: AsMut<[T]> + ?Sized>(
+        &'a mut self,
+        tc: &'a TLCell<Q, S>,
+    ) -> SliceViewMut<'a, T> {
+        SliceViewMut::new(self.rw(tc).as_mut())
+    }
+
+    /// Wrap a mutable borrow of this owner in a [`Capability`] which
+    /// can only read [`TLCell`] instances if `READ` is `true`, and only
+    /// write them if `WRITE` is `true`, to hand to code which should
+    /// be restricted to one or the other.  See [`ReadOnly`] and
+    /// [`WriteOnly`].
+    ///
+    /// [`Capability`]: struct.Capability.html
+    /// [`ReadOnly`]: type.ReadOnly.html
+    /// [`WriteOnly`]: type.WriteOnly.html
+    #[inline]
+    pub fn capability<const READ: bool, const WRITE: bool>(
+        &mut self,
+    ) -> Capability<'_, TLCellOwner<Q>, READ, WRITE> {
+        Capability::new(self)
+    }
+
+    /// Borrow the contents of a `TLCell` mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  No runtime check is needed to keep the
+    /// two apart: the cell's contents are reached through the owner,
+    /// and `other` through a separate `&mut`, which safe code can't
+    /// point into the cell's contents.  Since the owner is borrowed
+    /// for the call, `f` can't use it.
+    #[inline]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        &'a mut self,
+        tc: &'a TLCell<Q, T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(tc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two `TLCell` instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.
+    #[inline]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        &'a mut self,
+        tc1: &'a TLCell<Q, T>,
+        tc2: &'a TLCell<Q, U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(tc1, tc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of a `TLCell` immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.
+    #[inline]
+    pub fn inspect<T: ?Sized, D>(
+        &self,
+        tc: &TLCell<Q, T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(tc))
+    }
+
+    /// Same as `inspect`, but borrows two `TLCell` instances, which may
+    /// be the same cell.
+    #[inline]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        &self,
+        tc1: &TLCell<Q, T>,
+        tc2: &TLCell<Q, U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(tc1), self.ro(tc2))
+    }
+
+    /// Read the contents of a `TLCell` with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.
+    #[inline]
+    pub fn modify_if<T: ?Sized, D>(
+        &mut self,
+        tc: &TLCell<Q, T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.ro(tc)) {
+            Some(decision) => {
+                apply(self.rw(tc), decision);
+                true
+            }
+            None => false,
         }
     }
+
+    /// Borrow the contents of a slice of `TLCell` instances mutably,
+    /// returning a `Vec` of references in the same order.  The slice
+    /// may hold the cells directly, or anything that borrows as a
+    /// `TLCell`, such as `Rc<TLCell<..>>`, `Arc<TLCell<..>>` or
+    /// `&TLCell<..>`.  Panics if any two entries are the same cell,
+    /// which includes two `Rc` or `Arc` clones of the same cell.
+    pub fn rw_from_slice<'a, T: ?Sized, C: Borrow<TLCell<Q, T>>>(
+        &'a mut self,
+        cells: &'a [C],
+    ) -> Vec<&'a mut T> {
+        self.rw_from_iter(cells)
+    }
+
+    /// Same as [`TLCellOwner::rw_from_slice`], but takes the cells from
+    /// an iterator of references.
+    ///
+    /// [`TLCellOwner::rw_from_slice`]: struct.TLCellOwner.html#method.rw_from_slice
+    pub fn rw_from_iter<'a, T: ?Sized + 'a, C: Borrow<TLCell<Q, T>> + 'a>(
+        &'a mut self,
+        cells: impl IntoIterator<Item = &'a C>,
+    ) -> Vec<&'a mut T> {
+        let cells: Vec<&'a TLCell<Q, T>> = cells.into_iter().map(Borrow::borrow).collect();
+        distinct_check("TLCell", &cells);
+        cells
+            .into_iter()
+            // Safety: Owned (by marker type), Distinct, and owner mutably borrowed
+            .map(|tc| unsafe { sound::deref_unique(&tc.value) })
+            .collect()
+    }
 }
 
 /// Cell whose contents is owned (for borrowing purposes) by a
@@ -154,6 +443,13 @@ pub struct TLCell<Q, T: ?Sized> {
     value: UnsafeCell<T>,
 }
 
+impl<Q, T: ?Sized> ValuePtr for TLCell<Q, T> {
+    type Value = T;
+    fn value_ptr(&self) -> *const T {
+        self.value.get()
+    }
+}
+
 impl<Q, T> TLCell<Q, T> {
     /// Create a new `TLCell` owned for borrowing purposes by the
     /// `TLCellOwner` derived from the same marker type `Q`.
@@ -180,7 +476,10 @@ impl<Q, T: ?Sized> TLCell<Q, T> {
     /// `TLCell` instances can be borrowed immutably at the same time
     /// from the same owner.
     #[inline]
-    pub fn ro<'a>(&'a self, owner: &'a TLCellOwner<Q>) -> &'a T {
+    pub fn ro<'a>(&'a self, owner: &'a TLCellOwner<Q>) -> &'a T
+    where
+        Q: TCellMarker,
+    {
         owner.ro(self)
     }
 
@@ -191,7 +490,10 @@ impl<Q, T: ?Sized> TLCell<Q, T> {
     /// cells at the same time, see [`TLCellOwner::rw2`] or
     /// [`TLCellOwner::rw3`].
     #[inline]
-    pub fn rw<'a>(&'a self, owner: &'a mut TLCellOwner<Q>) -> &'a mut T {
+    pub fn rw<'a>(&'a self, owner: &'a mut TLCellOwner<Q>) -> &'a mut T
+    where
+        Q: TCellMarker,
+    {
         owner.rw(self)
     }
 
@@ -210,8 +512,20 @@ impl<Q, T: ?Sized> TLCell<Q, T> {
     pub fn get_mut(&mut self) -> &mut T {
         self.value.get_mut()
     }
+
+    /// Convert a mutable reference to a value into a mutable
+    /// reference to a `TLCell` containing it.  Since the value is
+    /// borrowed mutably, the owner can't reach it any other way until
+    /// the borrow ends.  See also [`TLCell::scoped_cell`].
+    ///
+    /// [`TLCell::scoped_cell`]: struct.TLCell.html#method.scoped_cell
+    #[inline]
+    pub fn from_mut(value: &mut T) -> &mut Self {
+        sound::tlcell_from_mut(value)
+    }
 }
 
+#[allow(clippy::needless_maybe_sized)]
 impl<Q: 'static, T: Default + ?Sized> Default for TLCell<Q, T> {
     fn default() -> Self {
         TLCell::new(T::default())
@@ -221,18 +535,49 @@ impl<Q: 'static, T: Default + ?Sized> Default for TLCell<Q, T> {
 #[cfg(test)]
 mod tests {
     use super::{TLCell, TLCellOwner};
+    use crate::marker;
 
     #[test]
     #[should_panic]
     fn tlcell_singleton_1() {
-        struct Marker;
+        marker!(struct Marker;);
         let _owner1 = TLCellOwner::<Marker>::new();
         let _owner2 = TLCellOwner::<Marker>::new(); // Panic here
     }
 
+    #[test]
+    fn tlcell_second_owner_location() {
+        use crate::typeid_gate::LocalTypeGate;
+        use std::any::type_name;
+        marker!(struct Marker;);
+        let (line, owner1) = (line!(), TLCellOwner::<Marker>::new());
+        let payload = match std::panic::catch_unwind(TLCellOwner::<Marker>::new) {
+            Ok(_) => panic!("no panic"),
+            Err(payload) => payload,
+        };
+        let msg = payload.downcast_ref::<String>().unwrap();
+        let expected = format!(
+            "existing TLCellOwner<{}> created at {}:{}:",
+            type_name::<Marker>(),
+            file!(),
+            line
+        );
+        assert!(msg.contains(&expected), "{}", msg);
+        drop(owner1);
+
+        // Another thread's owner doesn't count, and `default` records
+        // its caller
+        std::thread::spawn(|| drop(TLCellOwner::<Marker>::new()))
+            .join()
+            .unwrap();
+        let (line, _owner) = (line!(), TLCellOwner::<Marker>::default());
+        let location = LocalTypeGate::claimed_at::<Marker>().unwrap();
+        assert_eq!((location.file(), location.line()), (file!(), line));
+    }
+
     #[test]
     fn tlcell_singleton_2() {
-        struct Marker;
+        marker!(struct Marker;);
         let owner1 = TLCellOwner::<Marker>::new();
         drop(owner1);
         let _owner2 = TLCellOwner::<Marker>::new();
@@ -240,15 +585,15 @@ mod tests {
 
     #[test]
     fn tlcell_singleton_3() {
-        struct Marker1;
-        struct Marker2;
+        marker!(struct Marker1;);
+        marker!(struct Marker2;);
         let _owner1 = TLCellOwner::<Marker1>::new();
         let _owner2 = TLCellOwner::<Marker2>::new();
     }
 
     #[test]
     fn tlcell() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TLCellOwner<Marker>;
         type ACell<T> = TLCell<Marker, T>;
         let mut owner = ACellOwner::new();
@@ -264,7 +609,7 @@ mod tests {
 
     #[test]
     fn tlcell_threads() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TLCellOwner<Marker>;
         let mut _owner1 = ACellOwner::new();
         std::thread::spawn(|| {
@@ -274,9 +619,22 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn tlcell_get_set() {
+        marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let count = TLCell::new(2i32);
+        let label = TLCell::new(String::from("n"));
+        assert_eq!(owner.update(&count, |n| n * n), 4);
+        owner.set(&count, owner.get(&count) - 1);
+        assert_eq!(owner.get(&count), 3);
+        owner.set(&label, format!("{}={}", owner.get_cloned(&label), owner.get(&count)));
+        assert_eq!(owner.get_cloned(&label), "n=3");
+    }
+
     #[test]
     fn tlcell_get_mut() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TLCellOwner<Marker>;
         type ACell<T> = TLCell<Marker, T>;
         let owner = ACellOwner::new();
@@ -289,7 +647,7 @@ mod tests {
 
     #[test]
     fn tlcell_into_inner() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACell<T> = TLCell<Marker, T>;
         let cell = ACell::new(100u32);
         assert_eq!(cell.into_inner(), 100);
@@ -297,7 +655,7 @@ mod tests {
 
     #[test]
     fn tlcell_unsized() {
-        struct Marker;
+        marker!(struct Marker;);
         type ACellOwner = TLCellOwner<Marker>;
         type ACell<T> = TLCell<Marker, T>;
         let mut owner = ACellOwner::new();
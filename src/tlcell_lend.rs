@@ -0,0 +1,379 @@
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::task::Waker;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::thread;
+
+#[cfg(feature = "async")]
+use crate::panic_messages;
+use crate::{TCellMarker, TLCell, TLCellOwner};
+
+struct LendState<T, R> {
+    done: Option<(T, thread::Result<R>)>,
+    waker: Option<Waker>,
+}
+
+struct LendShared<T, R> {
+    state: Mutex<LendState<T, R>>,
+    cond: Condvar,
+}
+
+impl<T, R> LendShared<T, R> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, LendState<T, R>> {
+        // The lock is never held while running user code, so
+        // poisoning can't leave the state inconsistent
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait(&self) -> (T, thread::Result<R>) {
+        let mut state = self.lock();
+        loop {
+            if let Some(done) = state.done.take() {
+                return done;
+            }
+            state = self
+                .cond
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl<Q: TCellMarker> TLCellOwner<Q> {
+    /// Lend the contents of a [`TLCell`] to a new thread, which calls
+    /// `f` on it.
+    ///
+    /// The value is moved out of the cell, leaving `T::default()` as
+    /// a placeholder, and moved back into the cell when the returned
+    /// [`TLCellLend`] handle is joined or dropped.  The cell itself
+    /// stays where it is, so any `Rc` references to it on this
+    /// thread remain valid, and see the updated value afterwards.
+    /// The handle borrows the owner mutably, so the placeholder can't
+    /// be observed through it whilst the value is lent out.
+    ///
+    /// If `f` panics, the value as `f` left it is still moved back
+    /// into the cell, and the panic is resumed when the handle is
+    /// joined.
+    ///
+    /// ```
+    ///# use qcell::{marker, TLCell, TLCellOwner};
+    ///# use std::rc::Rc;
+    /// marker!(struct Marker;);
+    /// let mut owner = TLCellOwner::<Marker>::new();
+    /// let cell = Rc::new(TLCell::new(vec![1, 2, 3]));
+    /// let holder = cell.clone();
+    /// let lend = owner.lend_to_thread(&cell, |v| {
+    ///     v.push(4);
+    ///     v.len()
+    /// });
+    /// assert_eq!(lend.join(), 4);
+    /// assert_eq!(owner.ro(&holder), &[1, 2, 3, 4]);
+    /// ```
+    ///
+    /// [`TLCell`]: struct.TLCell.html
+    /// [`TLCellLend`]: struct.TLCellLend.html
+    pub fn lend_to_thread<'a, T, R, F>(
+        &'a mut self,
+        cell: &'a TLCell<Q, T>,
+        f: F,
+    ) -> TLCellLend<'a, Q, T, R>
+    where
+        T: Default + Send + 'static,
+        R: Send + 'static,
+        F: FnOnce(&mut T) -> R + Send + 'static,
+    {
+        let mut value = mem::take(self.rw(cell));
+        let shared = Arc::new(LendShared {
+            state: Mutex::new(LendState {
+                done: None,
+                waker: None,
+            }),
+            cond: Condvar::new(),
+        });
+        let worker_shared = shared.clone();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut value)));
+            let waker = {
+                let mut state = worker_shared.lock();
+                state.done = Some((value, result));
+                state.waker.take()
+            };
+            worker_shared.cond.notify_all();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+        TLCellLend {
+            owner: self,
+            cell,
+            shared,
+            finished: false,
+        }
+    }
+
+    /// Lend the contents of a [`TLCell`] to a scoped thread, which
+    /// calls `f` on it, and wait for it to finish.
+    ///
+    /// Since this doesn't return until the thread has finished, the
+    /// value doesn't need to be moved out of the cell, so `T` doesn't
+    /// need to implement `Default`, and `f` may borrow from the
+    /// caller.  If `f` panics, the cell keeps the value as `f` left
+    /// it, and the panic is resumed in the calling thread.
+    ///
+    /// ```
+    ///# use qcell::{marker, TLCell, TLCellOwner};
+    /// marker!(struct Marker;);
+    /// let mut owner = TLCellOwner::<Marker>::new();
+    /// let cell = TLCell::new(String::from("abc"));
+    /// let suffix = String::from("def");
+    /// let len = owner.lend_to_scope(&cell, |s| {
+    ///     s.push_str(&suffix);
+    ///     s.len()
+    /// });
+    /// assert_eq!(len, 6);
+    /// assert_eq!(owner.ro(&cell), "abcdef");
+    /// ```
+    ///
+    /// [`TLCell`]: struct.TLCell.html
+    #[cfg(feature = "scoped-threads")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "scoped-threads")))]
+    #[clippy::msrv = "1.63"]
+    pub fn lend_to_scope<T, R, F>(&mut self, cell: &TLCell<Q, T>, f: F) -> R
+    where
+        T: Send + ?Sized,
+        R: Send,
+        F: FnOnce(&mut T) -> R + Send,
+    {
+        // `&mut T` is `Send` when `T` is `Send`, and the owner is
+        // borrowed mutably until the thread has finished
+        let value = self.rw(cell);
+        thread::scope(|s| s.spawn(move || f(value)).join())
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+}
+
+/// Handle for the contents of a [`TLCell`] lent to another thread by
+/// [`TLCellOwner::lend_to_thread`].
+///
+/// Call [`TLCellLend::join`] to wait for the thread to finish, which
+/// moves the value back into the cell and returns the result.  If the
+/// handle is dropped without being joined, the drop waits for the
+/// thread to finish and moves the value back, discarding the result,
+/// including any panic.
+///
+/// With the **async** feature, the handle is also a `Future` which
+/// moves the value back and resolves to the result when the thread
+/// finishes.  Since it borrows the `TLCellOwner`, it is not `Send`,
+/// so it needs a local executor.
+///
+/// [`TLCell`]: struct.TLCell.html
+/// [`TLCellOwner::lend_to_thread`]: struct.TLCellOwner.html#method.lend_to_thread
+/// [`TLCellLend::join`]: struct.TLCellLend.html#method.join
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct TLCellLend<'a, Q: TCellMarker, T, R> {
+    owner: &'a mut TLCellOwner<Q>,
+    cell: &'a TLCell<Q, T>,
+    shared: Arc<LendShared<T, R>>,
+    finished: bool,
+}
+
+impl<'a, Q: TCellMarker, T, R> TLCellLend<'a, Q, T, R> {
+    /// Wait for the thread to finish, move the value back into the
+    /// cell, and return the result of the closure.  If the closure
+    /// panicked, the panic is resumed here, after the value has been
+    /// moved back.
+    pub fn join(mut self) -> R {
+        let done = self.shared.wait();
+        match self.restore(done) {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn restore(&mut self, (value, result): (T, thread::Result<R>)) -> thread::Result<R> {
+        *self.owner.rw(self.cell) = value;
+        self.finished = true;
+        result
+    }
+}
+
+impl<'a, Q: TCellMarker, T, R> Drop for TLCellLend<'a, Q, T, R> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let done = self.shared.wait();
+            let _ = self.restore(done);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+impl<'a, Q: TCellMarker, T, R> Future for TLCellLend<'a, Q, T, R> {
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        assert!(
+            !self.finished,
+            "{}",
+            panic_messages::TLCELL_LEND_POLLED_AFTER_COMPLETION
+        );
+        let done = {
+            let mut state = self.shared.lock();
+            match state.done.take() {
+                Some(done) => done,
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+        match self.restore(done) {
+            Ok(result) => Poll::Ready(result),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TLCell, TLCellOwner};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    #[test]
+    fn lend_thread_graph() {
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = Rc::new(TLCell::new(vec![1u32]));
+        let holders = vec![cell.clone(), cell.clone()];
+        let lend = owner.lend_to_thread(&cell, |v| {
+            v.push(2);
+            v.iter().sum::<u32>()
+        });
+        assert_eq!(lend.join(), 3);
+        for holder in &holders {
+            assert!(Rc::ptr_eq(holder, &cell));
+            assert_eq!(owner.ro(holder), &[1, 2]);
+        }
+    }
+
+    #[test]
+    fn lend_thread_placeholder() {
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::new(vec![1u32, 2, 3]);
+        let (tx, rx) = mpsc::channel();
+        let lend = owner.lend_to_thread(&cell, move |v| {
+            rx.recv().unwrap();
+            v.len()
+        });
+        // Whilst the worker holds the value, the cell holds the
+        // `Default` placeholder
+        assert!(lend.owner.ro(lend.cell).is_empty());
+        tx.send(()).unwrap();
+        assert_eq!(lend.join(), 3);
+        assert_eq!(owner.ro(&cell), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn lend_thread_drop_restores() {
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::new(String::from("a"));
+        {
+            let _lend = owner.lend_to_thread(&cell, |s| s.push('b'));
+        }
+        assert_eq!(owner.ro(&cell), "ab");
+    }
+
+    #[test]
+    fn lend_thread_panic_restores() {
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::new(vec![1u32]);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            owner
+                .lend_to_thread(&cell, |v| {
+                    v.push(2);
+                    panic!("worker failed");
+                })
+                .join()
+        }));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"worker failed"));
+        assert_eq!(owner.ro(&cell), &[1, 2]);
+    }
+
+    #[cfg(feature = "scoped-threads")]
+    #[test]
+    #[clippy::msrv = "1.63"]
+    fn lend_scope() {
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = Rc::new(TLCell::new(vec![1u32]));
+        let holder = cell.clone();
+        let extra = [2, 3];
+        let sum = owner.lend_to_scope(&cell, |v| {
+            v.extend_from_slice(&extra);
+            v.iter().sum::<u32>()
+        });
+        assert_eq!(sum, 6);
+        assert_eq!(owner.ro(&holder), &[1, 2, 3]);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            owner.lend_to_scope(&cell, |v| {
+                v.push(4);
+                panic!("worker failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(owner.ro(&holder), &[1, 2, 3, 4]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn lend_async() {
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread::{self, Thread};
+
+        struct ThreadWaker(Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn block_on<F: Future>(fut: F) -> F::Output {
+            let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            let mut fut = Box::pin(fut);
+            loop {
+                match fut.as_mut().poll(&mut cx) {
+                    Poll::Ready(v) => return v,
+                    Poll::Pending => thread::park(),
+                }
+            }
+        }
+
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = Rc::new(TLCell::new(10u64));
+        let holder = cell.clone();
+        let result = block_on(async {
+            let doubled = owner.lend_to_thread(&cell, |v| *v * 2).await;
+            *owner.rw(&cell) += doubled;
+            owner.lend_to_thread(&cell, |v| *v + 1).await
+        });
+        assert_eq!(result, 31);
+        assert_eq!(*owner.ro(&holder), 30);
+    }
+}
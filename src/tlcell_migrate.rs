@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+use crate::{TCellMarker, TLCell, TLCellOwner};
+
+/// A graph of [`TLCell`] values taken apart by [`tl_migrate`], ready
+/// to send to another thread and put back together there with
+/// [`MigrationPackage::rebuild`].
+///
+/// This holds the plain part of each node's value, plus a table of
+/// the edges between nodes as indices, so it is `Send` whenever the
+/// plain values are.
+///
+/// [`TLCell`]: struct.TLCell.html
+/// [`tl_migrate`]: fn.tl_migrate.html
+/// [`MigrationPackage::rebuild`]: struct.MigrationPackage.html#method.rebuild
+pub struct MigrationPackage<P> {
+    values: Vec<P>,
+    edges: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+/// Move a graph of `Rc<TLCell>` nodes out of this thread's cells,
+/// to be rebuilt on another thread.
+///
+/// An `Rc` can't be sent to another thread, so a graph of nodes
+/// linked by `Rc<TLCell<Q, T>>` edges has to be taken apart and put
+/// back together again.  This does the taking apart: starting from
+/// `roots`, it visits every node reachable through the edges,
+/// matching nodes by pointer identity so that shared nodes and
+/// cycles are handled.  Each node's value is moved out of its cell
+/// with `mem::take`, leaving `T::default()` behind, and passed to
+/// `split`, which returns the plain part of the value to send, and
+/// the edges that it held, in order.  The edges are recorded as
+/// indices into the package.
+///
+/// On the destination thread, [`MigrationPackage::rebuild`] creates
+/// a new cell for each node and rewrites the edges to point at them,
+/// which gives a graph of the same shape.
+///
+/// The original graph is left with default values and no edges, so
+/// it is freed when the caller drops its own references to the
+/// nodes, even if it had cycles.  If `split` panics, the nodes
+/// already visited are left with their default values.  Panics if
+/// called on a thread other than the one owning `owner`, like any
+/// access to a [`TLCell`].
+///
+/// ```
+///# use qcell::{marker, tl_migrate, TLCell, TLCellOwner};
+///# use std::rc::Rc;
+/// marker!(struct Marker;);
+///
+/// #[derive(Default)]
+/// struct Node {
+///     name: String,
+///     next: Option<Rc<TLCell<Marker, Node>>>,
+/// }
+///
+/// let mut owner = TLCellOwner::<Marker>::new();
+/// let a = Rc::new(TLCell::new(Node { name: "a".into(), next: None }));
+/// let b = Rc::new(TLCell::new(Node { name: "b".into(), next: Some(a.clone()) }));
+/// owner.rw(&a).next = Some(b.clone());  // Cycle a -> b -> a
+///
+/// let package = tl_migrate(vec![a], &mut owner, |node| (node.name, node.next));
+/// let handle = std::thread::spawn(move || {
+///     let mut owner = TLCellOwner::<Marker>::new();
+///     let roots = package.rebuild(&mut owner, |name, mut next| Node {
+///         name,
+///         next: next.pop(),
+///     });
+///     let b = owner.ro(&roots[0]).next.clone().unwrap();
+///     let a = owner.ro(&b).next.clone().unwrap();
+///     assert!(Rc::ptr_eq(&a, &roots[0]));
+///     assert_eq!(owner.ro(&b).name, "b");
+///     owner.rw(&a).next = None;  // Break the cycle
+/// });
+/// handle.join().unwrap();
+/// ```
+///
+/// [`TLCell`]: struct.TLCell.html
+/// [`MigrationPackage::rebuild`]: struct.MigrationPackage.html#method.rebuild
+pub fn tl_migrate<Q, T, P, E>(
+    roots: Vec<Rc<TLCell<Q, T>>>,
+    owner: &mut TLCellOwner<Q>,
+    mut split: impl FnMut(T) -> (P, E),
+) -> MigrationPackage<P>
+where
+    Q: TCellMarker,
+    T: Default,
+    E: IntoIterator<Item = Rc<TLCell<Q, T>>>,
+{
+    let mut index = HashMap::new();
+    let mut nodes = Vec::new();
+    let mut visit = |node: Rc<TLCell<Q, T>>, nodes: &mut Vec<Rc<TLCell<Q, T>>>| {
+        *index.entry(Rc::as_ptr(&node)).or_insert_with(|| {
+            nodes.push(node);
+            nodes.len() - 1
+        })
+    };
+
+    let roots = roots
+        .into_iter()
+        .map(|root| visit(root, &mut nodes))
+        .collect();
+    let mut values = Vec::new();
+    let mut edges = Vec::new();
+    // `nodes` grows as new edges are found
+    let mut i = 0;
+    while i < nodes.len() {
+        let value = mem::take(owner.rw(&nodes[i]));
+        let (plain, out) = split(value);
+        values.push(plain);
+        edges.push(out.into_iter().map(|e| visit(e, &mut nodes)).collect());
+        i += 1;
+    }
+    MigrationPackage {
+        values,
+        edges,
+        roots,
+    }
+}
+
+impl<P> MigrationPackage<P> {
+    /// Get the number of nodes in the package, which is the number
+    /// of distinct nodes that were reachable from the roots
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Test whether the package has no nodes
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Rebuild the graph in new cells for this thread's owner, and
+    /// return the new root nodes, in the same order as the roots
+    /// passed to [`tl_migrate`].
+    ///
+    /// A cell holding `T::default()` is first created for every node,
+    /// so that edges can refer to any node, including ones forming
+    /// cycles.  Then `join` is called with each node's plain value
+    /// and its edges as new `Rc` references, in the same order as
+    /// returned by `split`, to build the value stored in the node.
+    ///
+    /// [`tl_migrate`]: fn.tl_migrate.html
+    pub fn rebuild<Q, T>(
+        self,
+        owner: &mut TLCellOwner<Q>,
+        mut join: impl FnMut(P, Vec<Rc<TLCell<Q, T>>>) -> T,
+    ) -> Vec<Rc<TLCell<Q, T>>>
+    where
+        Q: TCellMarker,
+        T: Default,
+    {
+        let nodes: Vec<_> = (0..self.values.len())
+            .map(|_| Rc::new(owner.cell(T::default())))
+            .collect();
+        for ((node, plain), edges) in nodes.iter().zip(self.values).zip(self.edges) {
+            let edges = edges.into_iter().map(|e| nodes[e].clone()).collect();
+            *owner.rw(node) = join(plain, edges);
+        }
+        self.roots.into_iter().map(|r| nodes[r].clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tl_migrate, MigrationPackage};
+    use crate::{TLCell, TLCellOwner};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::thread;
+
+    crate::marker!(struct Marker;);
+
+    type NodeRef = Rc<TLCell<Marker, Node>>;
+
+    #[derive(Default)]
+    struct Node {
+        value: u32,
+        edges: Vec<NodeRef>,
+    }
+
+    // Adjacency lists plus values, with nodes numbered in order of
+    // first visit from the roots, and edges in order.  Two graphs
+    // reached from corresponding roots are isomorphic if their
+    // canonical forms are equal.
+    type Canonical = (Vec<usize>, Vec<(u32, Vec<usize>)>);
+
+    fn canonical(owner: &TLCellOwner<Marker>, roots: &[NodeRef]) -> Canonical {
+        let mut index = HashMap::new();
+        let mut order: Vec<NodeRef> = Vec::new();
+        let mut number = |n: &NodeRef, order: &mut Vec<NodeRef>| {
+            *index.entry(Rc::as_ptr(n)).or_insert_with(|| {
+                order.push(n.clone());
+                order.len() - 1
+            })
+        };
+        let roots = roots.iter().map(|r| number(r, &mut order)).collect();
+        let mut nodes = Vec::new();
+        let mut i = 0;
+        while i < order.len() {
+            let current = order[i].clone();
+            let node = owner.ro(&current);
+            let edges = node.edges.iter().map(|e| number(e, &mut order)).collect();
+            nodes.push((node.value, edges));
+            i += 1;
+        }
+        (roots, nodes)
+    }
+
+    fn build(
+        owner: &mut TLCellOwner<Marker>,
+        values: &[u32],
+        edges: &[(usize, usize)],
+    ) -> Vec<NodeRef> {
+        let nodes: Vec<NodeRef> = values
+            .iter()
+            .map(|&value| {
+                Rc::new(owner.cell(Node {
+                    value,
+                    edges: Vec::new(),
+                }))
+            })
+            .collect();
+        for &(from, to) in edges {
+            let to = nodes[to].clone();
+            owner.rw(&nodes[from]).edges.push(to);
+        }
+        nodes
+    }
+
+    // Break cycles so that the test doesn't leak
+    fn unlink(owner: &mut TLCellOwner<Marker>, roots: &[NodeRef]) {
+        let mut stack = roots.to_vec();
+        while let Some(node) = stack.pop() {
+            stack.append(&mut owner.rw(&node).edges);
+        }
+    }
+
+    fn migrate(owner: &mut TLCellOwner<Marker>, roots: Vec<NodeRef>) -> MigrationPackage<u32> {
+        tl_migrate(roots, owner, |node| (node.value, node.edges))
+    }
+
+    fn rebuild(owner: &mut TLCellOwner<Marker>, package: MigrationPackage<u32>) -> Vec<NodeRef> {
+        package.rebuild(owner, |value, edges| Node { value, edges })
+    }
+
+    #[test]
+    fn migrate_cyclic_graphs_across_threads() {
+        // Values, edges and roots
+        type Graph = (&'static [u32], &'static [(usize, usize)], &'static [usize]);
+        let graphs: &[Graph] = &[
+            (&[1], &[], &[0]),
+            (&[1], &[(0, 0), (0, 0)], &[0]),
+            (&[1, 2, 3], &[(0, 1), (1, 2), (2, 0)], &[0]),
+            (
+                &[1, 2, 3, 4],
+                &[(0, 1), (0, 2), (1, 3), (2, 3), (3, 0)],
+                &[0, 3, 0],
+            ),
+            (
+                &[5, 6, 7, 8, 9],
+                &[(0, 1), (1, 0), (2, 3), (3, 4), (4, 2), (4, 4)],
+                &[2, 1],
+            ),
+        ];
+        for &(values, edges, roots) in graphs {
+            let mut owner = TLCellOwner::<Marker>::new();
+            let nodes = build(&mut owner, values, edges);
+            let roots: Vec<_> = roots.iter().map(|&r| nodes[r].clone()).collect();
+            let expected = canonical(&owner, &roots);
+            let count = expected.1.len();
+
+            let package = migrate(&mut owner, roots);
+            assert_eq!(package.len(), count);
+            // The originals have been emptied, so the cycles are broken
+            for node in &nodes {
+                assert!(owner.ro(node).edges.is_empty());
+                assert_eq!(Rc::strong_count(node), 1);
+            }
+
+            let (expected, package) = thread::spawn(move || {
+                let mut owner = TLCellOwner::<Marker>::new();
+                let roots = rebuild(&mut owner, package);
+                assert_eq!(canonical(&owner, &roots), expected);
+                // And back again
+                let package = migrate(&mut owner, roots);
+                (expected, package)
+            })
+            .join()
+            .unwrap();
+
+            let roots = rebuild(&mut owner, package);
+            assert_eq!(canonical(&owner, &roots), expected);
+            unlink(&mut owner, &roots);
+        }
+    }
+
+    #[test]
+    fn migrate_unreachable_nodes_left_alone() {
+        let mut owner = TLCellOwner::<Marker>::new();
+        let nodes = build(&mut owner, &[1, 2, 3], &[(0, 1), (2, 0)]);
+        let package = migrate(&mut owner, vec![nodes[0].clone()]);
+        assert_eq!(package.len(), 2);
+        assert_eq!(owner.ro(&nodes[2]).value, 3);
+        assert_eq!(owner.ro(&nodes[0]).value, 0);
+        let roots = rebuild(&mut owner, package);
+        let next = owner.ro(&roots[0]).edges[0].clone();
+        assert_eq!((owner.ro(&roots[0]).value, owner.ro(&next).value), (1, 2));
+    }
+
+    #[test]
+    fn migrate_empty() {
+        let mut owner = TLCellOwner::<Marker>::new();
+        let package = migrate(&mut owner, Vec::new());
+        assert!(package.is_empty());
+        assert!(rebuild(&mut owner, package).is_empty());
+    }
+}
@@ -0,0 +1,441 @@
+// Support for the `rw!` and `rw_distinct!` macros, which borrow a
+// tuple of cells mutably at once.  The traits here are public so that
+// the macro expansions can name them, but are sealed and hidden from
+// the docs.
+
+use core::mem;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, rc::Rc, sync::Arc};
+
+use crate::panic_messages;
+use crate::qcell::bad_owner_panic;
+use crate::ro_slice::ValuePtr;
+use crate::sound;
+use crate::util::first_clash;
+#[cfg(feature = "alloc")]
+use crate::QCellOwner;
+use crate::{LCell, LCellOwner, QCell, QCellOwnerSeq, TCell, TCellMarker, TCellOwner};
+#[cfg(feature = "std")]
+use crate::{TLCell, TLCellOwner};
+
+mod sealed {
+    pub trait Sealed {}
+}
+use sealed::Sealed;
+
+/// A cell that can be borrowed by `rw!` from an owner of type `O`
+#[doc(hidden)]
+pub trait TupleCell<O>: Sealed {
+    /// Type of the cell contents
+    type Value: ?Sized;
+
+    /// Panic if this cell is not owned by `owner`
+    fn tuple_owner_check(&self, owner: &O);
+
+    /// Pointer to the cell contents, from `UnsafeCell::get`
+    fn tuple_value_ptr(&self) -> *const Self::Value;
+
+    /// Address and size of the cell itself, to check for clashes
+    fn tuple_cell_range(&self) -> (usize, usize);
+}
+
+impl<T: ?Sized> Sealed for QCell<T> {}
+impl<Q, T: ?Sized> Sealed for TCell<Q, T> {}
+#[cfg(feature = "std")]
+impl<Q, T: ?Sized> Sealed for TLCell<Q, T> {}
+impl<'id, T: ?Sized> Sealed for LCell<'id, T> {}
+
+macro_rules! tuple_cell {
+    ([$($gen:tt)*] $cell:ty, $owner:ty, |$c:pat_param, $o:pat_param| $check:block) => {
+        impl<$($gen)*> TupleCell<$owner> for $cell {
+            type Value = T;
+
+            #[inline]
+            fn tuple_owner_check(&self, owner: &$owner) {
+                let ($c, $o) = (self, owner);
+                $check
+            }
+
+            #[inline]
+            fn tuple_value_ptr(&self) -> *const T {
+                self.value_ptr()
+            }
+
+            #[inline]
+            fn tuple_cell_range(&self) -> (usize, usize) {
+                (self as *const Self as *const () as usize, mem::size_of_val(self))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+tuple_cell!([T: ?Sized] QCell<T>, QCellOwner, |c, owner| {
+    if !c.owner.matches(owner.id()) {
+        bad_owner_panic();
+    }
+});
+tuple_cell!([T: ?Sized] QCell<T>, QCellOwnerSeq, |c, owner| {
+    if !c.owner.matches(owner.id()) {
+        bad_owner_panic();
+    }
+});
+// Owned is implied by the marker type or the brand lifetime
+tuple_cell!([Q: TCellMarker, T: ?Sized] TCell<Q, T>, TCellOwner<Q>, |_, _| {});
+#[cfg(feature = "std")]
+tuple_cell!([Q: TCellMarker, T: ?Sized] TLCell<Q, T>, TLCellOwner<Q>, |_, _| {});
+tuple_cell!(['id, T: ?Sized] LCell<'id, T>, LCellOwner<'id>, |_, _| {});
+
+// Cells behind a reference or smart pointer are borrowed through it
+macro_rules! tuple_cell_ptr {
+    ($ptr:ty) => {
+        impl<C: Sealed + ?Sized> Sealed for $ptr {}
+
+        impl<O, C: TupleCell<O> + ?Sized> TupleCell<O> for $ptr {
+            type Value = C::Value;
+
+            #[inline]
+            fn tuple_owner_check(&self, owner: &O) {
+                (**self).tuple_owner_check(owner)
+            }
+
+            #[inline]
+            fn tuple_value_ptr(&self) -> *const C::Value {
+                (**self).tuple_value_ptr()
+            }
+
+            #[inline]
+            fn tuple_cell_range(&self) -> (usize, usize) {
+                (**self).tuple_cell_range()
+            }
+        }
+    };
+}
+
+tuple_cell_ptr!(&C);
+#[cfg(feature = "alloc")]
+tuple_cell_ptr!(Rc<C>);
+#[cfg(feature = "alloc")]
+tuple_cell_ptr!(Arc<C>);
+#[cfg(feature = "alloc")]
+tuple_cell_ptr!(Box<C>);
+
+/// Proof that the cells of a tuple were checked to be distinct, or
+/// that the caller of an unsafe function promised that they are
+#[doc(hidden)]
+pub struct Distinct(());
+
+/// A tuple of borrows of cells, all owned by an owner of type `O`
+#[doc(hidden)]
+pub trait RwTuple<'a, O>: Sealed {
+    /// Tuple of mutable borrows of the cell contents
+    type Output;
+
+    /// Panic if any two of the cells are the same cell, or overlap
+    fn tuple_distinct_check(&self);
+
+    /// Borrow the contents of all the cells mutably
+    fn tuple_rw(self, owner: &'a mut O, distinct: Distinct) -> Self::Output;
+}
+
+#[cold]
+#[inline(never)]
+fn duplicate_panic(index1: usize, index2: usize, same: bool) -> ! {
+    panic!(
+        "{}: cells {} and {} are {}",
+        panic_messages::RW_TUPLE_BORROWED_TWICE,
+        index1,
+        index2,
+        if same { "the same cell" } else { "overlapping cells" }
+    );
+}
+
+// Panic if any two of the cells, given as `(address, size)`, are the
+// same cell or overlap, as an `LCell` for a whole slice overlaps the
+// cells for its elements.  The tuples are short, so every pair is
+// checked.
+#[inline]
+fn distinct_check(ranges: &[(usize, usize)]) {
+    if let Some((i, j)) = first_clash(ranges.len(), |i| ranges[i]) {
+        duplicate_panic(i, j, ranges[i].0 == ranges[j].0);
+    }
+}
+
+macro_rules! rw_tuple {
+    ($($c:ident $i:tt),+) => {
+        impl<'a, $($c: ?Sized),+> Sealed for ($(&'a $c,)+) {}
+
+        impl<'a, O, $($c: TupleCell<O> + ?Sized),+> RwTuple<'a, O> for ($(&'a $c,)+)
+        where
+            $($c::Value: 'a),+
+        {
+            type Output = ($(&'a mut $c::Value,)+);
+
+            #[inline]
+            fn tuple_distinct_check(&self) {
+                distinct_check(&[$(self.$i.tuple_cell_range()),+]);
+            }
+
+            #[inline]
+            fn tuple_rw(self, owner: &'a mut O, _: Distinct) -> Self::Output {
+                $(self.$i.tuple_owner_check(owner);)+
+                // Safety: Owned (checked above), Distinct (by `Distinct`), and owner mutably borrowed
+                ($(unsafe { sound::deref_unique_ptr(self.$i.tuple_value_ptr()) },)+)
+            }
+        }
+    };
+}
+
+rw_tuple!(A 0);
+rw_tuple!(A 0, B 1);
+rw_tuple!(A 0, B 1, C 2);
+rw_tuple!(A 0, B 1, C 2, D 3);
+rw_tuple!(A 0, B 1, C 2, D 3, E 4);
+rw_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+rw_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+rw_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// Owners that `rw!` accepts.  The method lets the macro take either
+/// an owner or a mutable reference to one, through auto-deref.
+#[doc(hidden)]
+pub trait RwTupleOwner: Sized {
+    #[inline]
+    fn rw_tuple_owner(&mut self) -> &mut Self {
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl RwTupleOwner for QCellOwner {}
+impl RwTupleOwner for QCellOwnerSeq {}
+impl<Q: TCellMarker> RwTupleOwner for TCellOwner<Q> {}
+#[cfg(feature = "std")]
+impl<Q: TCellMarker> RwTupleOwner for TLCellOwner<Q> {}
+impl<'id> RwTupleOwner for LCellOwner<'id> {}
+
+#[doc(hidden)]
+#[inline]
+pub fn rw_tuple<'a, O, C: RwTuple<'a, O>>(owner: &'a mut O, cells: C) -> C::Output {
+    cells.tuple_distinct_check();
+    cells.tuple_rw(owner, Distinct(()))
+}
+
+/// # Safety
+///
+/// No two of the cells may be the same cell.  If `check` is true,
+/// this is checked anyway.
+#[doc(hidden)]
+#[inline]
+pub unsafe fn rw_tuple_unchecked<'a, O, C: RwTuple<'a, O>>(
+    owner: &'a mut O,
+    cells: C,
+    check: bool,
+) -> C::Output {
+    if check {
+        cells.tuple_distinct_check();
+    }
+    cells.tuple_rw(owner, Distinct(()))
+}
+
+/// Borrow the contents of up to eight cells mutably at once, with a
+/// runtime check that they are all different cells.
+///
+/// This is `rw2` and `rw3` for any number of cells from one to eight,
+/// and the cells may have different types.  It works with
+/// [`QCellOwner`], [`QCellOwnerSeq`], [`TCellOwner`], [`TLCellOwner`]
+/// and [`LCellOwner`], either directly or through a mutable
+/// reference.  Each cell may be a reference to the cell itself, or to
+/// a reference, `Rc`, `Arc` or `Box` holding it.  Returns a tuple of
+/// mutable references to the contents, in order.  Panics if any two
+/// of the cells are the same cell, or if any cell is not owned by the
+/// owner.
+///
+/// ```
+///# use qcell::{rw, QCell, QCellOwnerSeq};
+/// // Safety: No other owner is created with this ID
+/// let mut owner = unsafe { QCellOwnerSeq::new() };
+/// let name = QCell::new(&owner, String::from("a"));
+/// let count = QCell::new(&owner, 1_u32);
+/// let flags = QCell::new(&owner, [false; 4]);
+/// let (name, count, flags) = rw!(owner => &name, &count, &flags);
+/// name.push('b');
+/// *count += 1;
+/// flags[*count as usize] = true;
+/// ```
+///
+/// If the cells are known to be distinct, [`rw_distinct!`] can skip
+/// the check, but only inside an `unsafe` block.
+///
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`TCellOwner`]: struct.TCellOwner.html
+/// [`TLCellOwner`]: struct.TLCellOwner.html
+/// [`LCellOwner`]: struct.LCellOwner.html
+/// [`rw_distinct!`]: macro.rw_distinct.html
+#[macro_export]
+macro_rules! rw {
+    ($owner:expr => $($cell:expr),+ $(,)?) => {{
+        use $crate::__RwTupleOwner as _;
+        $crate::__rw_tuple($owner.rw_tuple_owner(), ($($cell,)+))
+    }};
+}
+
+/// Borrow the contents of up to eight cells mutably at once, like
+/// [`rw!`], but without the runtime check that they are different
+/// cells in release builds.
+///
+/// The check is just a comparison of the address range of each pair
+/// of cells, so it is cheap, but in a hot loop over a fixed set of
+/// cells it may still be worth removing.  The macro expands to a call of an `unsafe`
+/// function, so it has to be used inside an `unsafe` block, where the
+/// caller's promise is visible to reviewers and to lints such as
+/// `unsafe_code`.  The cells have to be given as `&` followed by a
+/// plain variable name, with no repeats.  Field accesses, indexing,
+/// method calls and other expressions are rejected at compile time,
+/// since the macro can't see whether they give distinct cells.
+///
+/// ```
+///# use qcell::{rw_distinct, LCellOwner};
+/// LCellOwner::scope(|mut owner| {
+///     let a = owner.cell(1);
+///     let b = owner.cell(2);
+///     // Safety: `a` and `b` were created just above as separate cells
+///     let (a, b) = unsafe { rw_distinct!(owner => &a, &b) };
+///     std::mem::swap(a, b);
+/// });
+/// ```
+///
+/// # Safety
+///
+/// Different variable names don't guarantee different cells.  Two
+/// variables can hold references to the same cell, or two `Rc`
+/// clones of it, and the macro can't tell.  So the caller must check
+/// that the cells are distinct, in the same way as for any other
+/// `unsafe` block.  If they are not, then this gives aliasing
+/// mutable references, which is undefined behaviour.  With debug
+/// assertions enabled, the cells are checked anyway, and it panics
+/// like [`rw!`], to catch mistakes during development and testing.
+///
+/// [`rw!`]: macro.rw.html
+#[macro_export]
+macro_rules! rw_distinct {
+    ($owner:expr => $(&$cell:ident),+ $(,)?) => {{
+        // Fails to compile if any name is repeated
+        #[allow(dead_code, unused_variables, clippy::too_many_arguments)]
+        fn distinct_names($($cell: ()),+) {}
+        use $crate::__RwTupleOwner as _;
+        let owner = $owner.rw_tuple_owner();
+        let cells = ($(&$cell,)+);
+        // No `unsafe` block here: the call must be inside the caller's
+        // own, where they promise that the cells are distinct
+        $crate::__rw_tuple_unchecked(owner, cells, cfg!(debug_assertions))
+    }};
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::panic_messages::RW_TUPLE_BORROWED_TWICE;
+    use crate::{LCellOwner, QCell, QCellOwner, QCellOwnerSeq, TCell, TCellOwner};
+    use std::rc::Rc;
+
+    #[test]
+    fn rw_macro_mixed_types() {
+        let mut owner = QCellOwner::new();
+        let a = QCell::new(&owner, 1_u8);
+        let b = Rc::new(QCell::new(&owner, String::new()));
+        let c = Box::new(QCell::new(&owner, vec![1]));
+        {
+            let (a, b, c) = rw!(owner => &a, &b, &c);
+            *a += 1;
+            b.push('x');
+            c.push(2);
+        }
+        let owner_ref = &mut owner;
+        let (x,) = rw!(owner_ref => &a);
+        *x += 1;
+        assert_eq!(owner.get(&a), 3);
+        assert_eq!(owner.ro(&b), "x");
+        assert_eq!(*owner.ro(&c), [1, 2]);
+    }
+
+    #[test]
+    fn rw_macro_eight() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let c: Vec<QCell<u32>> = (0..8).map(|i| owner.cell(i)).collect();
+        let (c0, c1, c2, c3, c4, c5, c6, c7) =
+            rw!(owner => &c[0], &c[1], &c[2], &c[3], &c[4], &c[5], &c[6], &c[7]);
+        for v in [c0, c1, c2, c3, c4, c5, c6, c7] {
+            *v *= 10;
+        }
+        assert_eq!(owner.get(&c[7]), 70);
+    }
+
+    #[test]
+    #[should_panic(expected = "cells 1 and 3 are the same cell")]
+    fn rw_macro_duplicate() {
+        let mut owner = QCellOwner::new();
+        let a = QCell::new(&owner, 0);
+        let b = Rc::new(QCell::new(&owner, 0));
+        let b2 = b.clone();
+        let c = QCell::new(&owner, 0);
+        rw!(owner => &a, &b, &c, &b2);
+    }
+
+    #[test]
+    #[should_panic(expected = "QCell accessed with incorrect owner")]
+    fn rw_macro_bad_owner() {
+        let mut owner1 = QCellOwner::new();
+        let owner2 = QCellOwner::new();
+        let a = QCell::new(&owner1, 0);
+        let b = QCell::new(&owner2, 0);
+        rw!(owner1 => &a, &b);
+    }
+
+    #[test]
+    fn rw_macro_tcell_lcell() {
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let a = TCell::new(1);
+        let b = TCell::new(2);
+        let (x, y) = rw!(owner => &a, &b);
+        std::mem::swap(x, y);
+        assert_eq!((owner.get(&a), owner.get(&b)), (2, 1));
+
+        LCellOwner::scope(|mut owner| {
+            let a = owner.cell(1);
+            let b = owner.cell(2);
+            // Safety: `a` and `b` are separate cells
+            let (x, y) = unsafe { rw_distinct!(owner => &a, &b) };
+            *x += *y;
+            assert_eq!(owner.get(&a), 3);
+        });
+    }
+
+    #[test]
+    fn rw_distinct_rc() {
+        let mut owner = QCellOwner::new();
+        let a = Rc::new(QCell::new(&owner, 1));
+        let b = Rc::new(QCell::new(&owner, 2));
+        // Safety: `a` and `b` are separate cells
+        let (x, y) = unsafe { rw_distinct!(&mut owner => &a, &b,) };
+        *x += *y;
+        assert_eq!(owner.get(&a), 3);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn rw_distinct_debug_check() {
+        let mut owner = QCellOwner::new();
+        let a = Rc::new(QCell::new(&owner, 1));
+        let b = a.clone();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            // Deliberately wrong, to check that debug builds catch it
+            let _ = unsafe { rw_distinct!(owner => &a, &b) };
+        }));
+        let payload = result.unwrap_err();
+        let msg = payload.downcast_ref::<String>().unwrap();
+        assert!(msg.starts_with(RW_TUPLE_BORROWED_TWICE));
+    }
+}
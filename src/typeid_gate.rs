@@ -0,0 +1,1017 @@
+//! At most one claim per type, process-wide or per thread.
+//!
+//! [`TypeGate`] keeps a process-wide set of claimed types.  Claiming
+//! a type returns a [`ClaimGuard`], and no other claim on that type
+//! can be made until the guard is dropped.  This is the registry
+//! which [`TCellOwner`] uses to ensure that there is only one owner
+//! for each marker type, exposed for use with other kinds of
+//! resource that must only have one manager at a time.
+//! [`LocalTypeGate`] is the same thing per thread, as used by
+//! [`TLCellOwner`].
+//!
+//! ```
+//!# use qcell::typeid_gate::TypeGate;
+//! struct AudioDevice;
+//!
+//! let claim = TypeGate::try_claim::<AudioDevice>().unwrap();
+//! assert!(TypeGate::is_claimed::<AudioDevice>());
+//! assert!(TypeGate::try_claim::<AudioDevice>().is_none());
+//! drop(claim);
+//! assert!(!TypeGate::is_claimed::<AudioDevice>());
+//! ```
+//!
+//! The claims are keyed by `TypeId`, and [`TCellOwner`] claims its
+//! marker type `Q` in the same set.  So claiming a marker type with
+//! [`TypeGate`] also stops owners for that marker from being created
+//! until the claim is released, and the other way round.  To keep
+//! claims for different purposes apart, claim private types, or
+//! types wrapping the type of interest, e.g. `Manager<T>` rather than
+//! `T`.
+//!
+//! Each claim records the source location of the call which made it,
+//! which [`TypeGate::claimed_at`] and [`LocalTypeGate::claimed_at`]
+//! return, and which the panic from creating a second [`TCellOwner`]
+//! or [`TLCellOwner`] gives.  With the **owner-backtrace** feature, a
+//! backtrace is captured too, and added to those panic messages.
+//!
+//! There are two backends, the same as for [`TCellOwner`].  By
+//! default the set is a hash map behind a mutex, split into shards to
+//! reduce contention.  With the **exclusion-set** feature, a
+//! lock-free set from the `exclusion-set` crate is used instead,
+//! which also works in `no_std` environments.  That set can't hold
+//! anything alongside each type, so with `std` the source locations
+//! are kept in a separate table, and without `std` they are not
+//! recorded at all.
+//!
+//! [`TCellOwner`]: ../struct.TCellOwner.html
+//! [`TLCellOwner`]: ../struct.TLCellOwner.html
+//! [`TypeGate`]: struct.TypeGate.html
+//! [`ClaimGuard`]: struct.ClaimGuard.html
+//! [`LocalTypeGate`]: struct.LocalTypeGate.html
+//! [`TypeGate::claimed_at`]: struct.TypeGate.html#method.claimed_at
+//! [`LocalTypeGate::claimed_at`]: struct.LocalTypeGate.html#method.claimed_at
+
+use core::any::TypeId;
+use core::fmt;
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::panic::Location;
+#[cfg(any(
+    all(feature = "std", feature = "exclusion-set"),
+    all(
+        feature = "std",
+        not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+    )
+))]
+use once_cell::sync::Lazy;
+#[cfg(feature = "owner-backtrace")]
+use std::backtrace::Backtrace;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::collections::hash_map::{Entry, HashMap};
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "owner-backtrace")]
+use std::sync::Arc;
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use std::sync::Condvar;
+#[cfg(any(
+    all(feature = "std", feature = "exclusion-set"),
+    all(
+        feature = "std",
+        not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+    )
+))]
+use std::sync::Mutex;
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use std::thread::{self, ThreadId};
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use std::time::Duration;
+
+use super::Invariant;
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use crate::panic_messages;
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+use crate::park::Park;
+#[cfg(feature = "exclusion-set")]
+use crate::sound;
+
+// Where a claim was made, recorded alongside it so that a failed
+// claim can report where the existing one came from.  The backtrace
+// uses `std::backtrace`, so needs Rust 1.65.
+#[derive(Clone)]
+#[cfg_attr(feature = "owner-backtrace", clippy::msrv = "1.65")]
+pub(crate) struct Claimant {
+    location: &'static Location<'static>,
+    // Shared, since the record is cloned out of the registry to
+    // report it after the registry is unlocked
+    #[cfg(feature = "owner-backtrace")]
+    backtrace: Arc<Backtrace>,
+}
+
+#[cfg_attr(feature = "owner-backtrace", clippy::msrv = "1.65")]
+impl Claimant {
+    #[track_caller]
+    #[inline]
+    pub(crate) fn here() -> Self {
+        Self {
+            location: Location::caller(),
+            #[cfg(feature = "owner-backtrace")]
+            backtrace: Arc::new(Backtrace::force_capture()),
+        }
+    }
+}
+
+impl fmt::Display for Claimant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.location)?;
+        #[cfg(feature = "owner-backtrace")]
+        write!(f, "\n\nbacktrace of its creation:\n{}", self.backtrace)?;
+        Ok(())
+    }
+}
+
+// Panic after failing to claim the marker type of a singleton owner,
+// saying where the existing owner was created, if known
+#[cold]
+#[inline(never)]
+pub(crate) fn second_owner_panic(
+    message: &str,
+    kind: &str,
+    marker: &str,
+    claimant: Option<Claimant>,
+) -> ! {
+    match claimant {
+        Some(claimant) => panic!(
+            "{}: existing {}<{}> created at {}",
+            message, kind, marker, claimant
+        ),
+        None => panic!("{}: existing {}<{}>", message, kind, marker),
+    }
+}
+
+// The registry is split into shards by a hash of the `TypeId`, so
+// that claims on different types rarely contend on the same mutex,
+// and releasing a claim only wakes up the threads in
+// `claim_blocking` whose type falls in the same shard.  Which shard
+// a type uses makes no difference to the claims themselves.
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+const SHARDS: usize = 32;
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+#[derive(Default)]
+struct Shard<P: Park = Condvar> {
+    claims: Mutex<HashMap<TypeId, Claim>>,
+    // Notified when a claim in this shard is released.  The tests
+    // use a `VirtualPark` here to control the order of events.
+    released: P,
+}
+// Each entry records the thread that made the claim, so that
+// `claim_blocking` can detect a wait that could never end, and where
+// it was made
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+struct Claim {
+    thread: ThreadId,
+    claimant: Claimant,
+}
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+impl<P: Park> Shard<P> {
+    fn insert(&self, id: TypeId, claimant: Claimant) -> bool {
+        let mut claims = self.claims.lock().unwrap();
+        match claims.entry(id) {
+            Entry::Vacant(entry) => {
+                entry.insert(Claim {
+                    thread: thread::current().id(),
+                    claimant,
+                });
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+
+    fn contains(&self, id: TypeId) -> bool {
+        self.claims.lock().unwrap().contains_key(&id)
+    }
+
+    fn claimant(&self, id: TypeId) -> Option<Claimant> {
+        let claims = self.claims.lock().unwrap();
+        claims.get(&id).map(|claim| claim.claimant.clone())
+    }
+
+    // Returns false without waiting if the claim is held by the
+    // calling thread, since the wait would most likely never end
+    fn wait_insert(&self, id: TypeId, claimant: Claimant) -> bool {
+        let current = thread::current().id();
+        let claims = self.claims.lock().unwrap();
+        if claims.get(&id).map(|claim| claim.thread) == Some(current) {
+            return false;
+        }
+        // The mutex is unlocked while waiting
+        let mut claims = self
+            .released
+            .wait_while(claims, |claims| claims.contains_key(&id));
+        let claim = Claim {
+            thread: current,
+            claimant,
+        };
+        let previous = claims.insert(id, claim);
+        assert!(previous.is_none());
+        true
+    }
+
+    // Same as `wait_insert`, but returns `None` if the claim is still
+    // held once `timeout` has passed.  Nothing makes timed claims
+    // yet, but the timeout path is tested here against a virtual
+    // clock, ready for when something does.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn wait_insert_timeout(
+        &self,
+        id: TypeId,
+        claimant: Claimant,
+        timeout: Duration,
+    ) -> Option<bool> {
+        let current = thread::current().id();
+        let claims = self.claims.lock().unwrap();
+        if claims.get(&id).map(|claim| claim.thread) == Some(current) {
+            return Some(false);
+        }
+        let (mut claims, timed_out) = self
+            .released
+            .wait_timeout_while(claims, timeout, |claims| claims.contains_key(&id));
+        if timed_out {
+            return None;
+        }
+        let claim = Claim {
+            thread: current,
+            claimant,
+        };
+        let previous = claims.insert(id, claim);
+        assert!(previous.is_none());
+        Some(true)
+    }
+
+    fn remove(&self, id: TypeId) {
+        self.claims.lock().unwrap().remove(&id);
+
+        // Wake up all threads waiting in `wait_insert` on this shard
+        // to check if their type was released.  Waiters for types in
+        // other shards aren't woken.
+        self.released.notify_all();
+    }
+}
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+static CLAIMS: Lazy<[Shard; SHARDS]> = Lazy::new(Default::default);
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn shard(id: &TypeId) -> &'static Shard {
+    // `DefaultHasher::new` always uses the same keys, so a type
+    // always maps to the same shard
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    &CLAIMS[(hasher.finish() % SHARDS as u64) as usize]
+}
+
+// On WASM without the "atomics" target feature there is only ever one
+// thread, so a thread-local set is equivalent to a global one, and
+// avoids pulling in the locking code.
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    all(target_arch = "wasm32", not(target_feature = "atomics"))
+))]
+std::thread_local! {
+    static CLAIMS_WASM: RefCell<HashMap<TypeId, Claimant>> = RefCell::new(HashMap::new());
+}
+#[cfg(feature = "exclusion-set")]
+static CLAIMS_SET: exclusion_set::Set<TypeId> = exclusion_set::Set::new();
+// Where each claim in `CLAIMS_SET` was made.  An entry is added just
+// after the type is inserted into the set, and removed just before
+// it is removed from it, so a lookup straight after a failed claim
+// may miss it, but never finds an entry for a different claim.
+#[cfg(all(feature = "std", feature = "exclusion-set"))]
+static CLAIMANTS: Lazy<Mutex<HashMap<TypeId, Claimant>>> = Lazy::new(Default::default);
+
+// Claims made through `LocalTypeGate`, separate for each thread
+#[cfg(feature = "std")]
+std::thread_local! {
+    static LOCAL_CLAIMS: RefCell<HashMap<TypeId, Claimant>> = RefCell::new(HashMap::new());
+}
+
+// Backend operations.  Each backend provides `insert`, `contains`,
+// `claimant` and `remove`, and where threads can block,
+// `wait_insert`.
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn insert(id: TypeId, claimant: Claimant) -> bool {
+    shard(&id).insert(id, claimant)
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn contains(id: TypeId) -> bool {
+    shard(&id).contains(id)
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn claimant(id: TypeId) -> Option<Claimant> {
+    shard(&id).claimant(id)
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn wait_insert(id: TypeId, claimant: Claimant) -> bool {
+    shard(&id).wait_insert(id, claimant)
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn remove(id: TypeId) {
+    shard(&id).remove(id)
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    all(target_arch = "wasm32", not(target_feature = "atomics"))
+))]
+fn insert(id: TypeId, claimant: Claimant) -> bool {
+    CLAIMS_WASM.with(|claims| match claims.borrow_mut().entry(id) {
+        Entry::Vacant(entry) => {
+            entry.insert(claimant);
+            true
+        }
+        Entry::Occupied(_) => false,
+    })
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    all(target_arch = "wasm32", not(target_feature = "atomics"))
+))]
+fn contains(id: TypeId) -> bool {
+    CLAIMS_WASM.with(|claims| claims.borrow().contains_key(&id))
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    all(target_arch = "wasm32", not(target_feature = "atomics"))
+))]
+fn claimant(id: TypeId) -> Option<Claimant> {
+    CLAIMS_WASM.with(|claims| claims.borrow().get(&id).cloned())
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "exclusion-set"),
+    all(target_arch = "wasm32", not(target_feature = "atomics"))
+))]
+fn remove(id: TypeId) {
+    // Ignore failure in case the thread-local has already been
+    // destroyed
+    let _ = CLAIMS_WASM.try_with(|claims| claims.borrow_mut().remove(&id));
+}
+
+#[cfg(feature = "exclusion-set")]
+fn insert(id: TypeId, claimant: Claimant) -> bool {
+    if CLAIMS_SET.try_insert(id) {
+        record_claimant(id, claimant);
+        true
+    } else {
+        false
+    }
+}
+
+// The set has no lookup, so test by claiming and releasing again,
+// without touching the table of claimants
+#[cfg(feature = "exclusion-set")]
+fn contains(id: TypeId) -> bool {
+    if CLAIMS_SET.try_insert(id) {
+        set_remove(id);
+        false
+    } else {
+        true
+    }
+}
+
+#[cfg(all(feature = "std", feature = "exclusion-set"))]
+fn claimant(id: TypeId) -> Option<Claimant> {
+    CLAIMANTS.lock().unwrap().get(&id).cloned()
+}
+
+#[cfg(all(not(feature = "std"), feature = "exclusion-set"))]
+fn claimant(_id: TypeId) -> Option<Claimant> {
+    None
+}
+
+#[cfg(all(feature = "std", feature = "exclusion-set"))]
+fn record_claimant(id: TypeId, claimant: Claimant) {
+    CLAIMANTS.lock().unwrap().insert(id, claimant);
+}
+
+#[cfg(all(not(feature = "std"), feature = "exclusion-set"))]
+fn record_claimant(_id: TypeId, _claimant: Claimant) {}
+
+// There is no record of the claiming thread, so a wait that can
+// never end is not detected
+#[cfg(all(
+    feature = "std",
+    feature = "exclusion-set",
+    not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+))]
+fn wait_insert(id: TypeId, claimant: Claimant) -> bool {
+    CLAIMS_SET.wait_to_insert(id);
+    record_claimant(id, claimant);
+    true
+}
+
+#[cfg(feature = "exclusion-set")]
+fn remove(id: TypeId) {
+    #[cfg(feature = "std")]
+    CLAIMANTS.lock().unwrap().remove(&id);
+    set_remove(id);
+}
+
+#[cfg(feature = "exclusion-set")]
+fn set_remove(id: TypeId) {
+    // Safety: No concurrent removal, since the only caller which may
+    // remove `id` is the holder of the claim
+    unsafe { sound::exclusion_set_remove(&CLAIMS_SET, &id) };
+}
+
+/// Process-wide set of claimed types.
+///
+/// At any one time there can be at most one claim on each type `T`,
+/// represented by a [`ClaimGuard`], which releases the claim when
+/// dropped.  See the [module documentation](index.html).
+///
+/// [`ClaimGuard`]: struct.ClaimGuard.html
+pub struct TypeGate {
+    _private: (),
+}
+
+impl TypeGate {
+    /// Claim type `T`, or return `None` if it is already claimed.
+    #[inline]
+    #[track_caller]
+    pub fn try_claim<T: 'static>() -> Option<ClaimGuard<T>> {
+        if insert(TypeId::of::<T>(), Claimant::here()) {
+            Some(ClaimGuard { typ: PhantomData })
+        } else {
+            None
+        }
+    }
+
+    /// Claim type `T`, blocking the thread until any existing claim
+    /// is released.
+    ///
+    /// Waiting for a claim that was made by the current thread would
+    /// usually deadlock, so in that case this panics instead of
+    /// blocking.  The check is best-effort: the thread recorded is
+    /// the one that made the claim, not the one that holds the guard
+    /// now.  With the **exclusion-set** feature there is no detection
+    /// at all.
+    ///
+    /// Not available on WASM without the "atomics" target feature,
+    /// since there is only one thread, so waiting would never end.
+    #[cfg(all(
+        feature = "std",
+        not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(
+            feature = "std",
+            not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+        )))
+    )]
+    #[track_caller]
+    pub fn claim_blocking<T: 'static>() -> ClaimGuard<T> {
+        match Self::try_claim_blocking() {
+            Some(claim) => claim,
+            None => panic!(
+                "{}: {}",
+                panic_messages::TYPE_GATE_CLAIM_DEADLOCK,
+                core::any::type_name::<T>()
+            ),
+        }
+    }
+
+    // Same as `claim_blocking`, but returning `None` instead of
+    // panicking if the claim is held by the calling thread, so that
+    // callers can give their own panic message
+    #[cfg(all(
+        feature = "std",
+        not(all(target_arch = "wasm32", not(target_feature = "atomics")))
+    ))]
+    #[track_caller]
+    pub(crate) fn try_claim_blocking<T: 'static>() -> Option<ClaimGuard<T>> {
+        if wait_insert(TypeId::of::<T>(), Claimant::here()) {
+            Some(ClaimGuard { typ: PhantomData })
+        } else {
+            None
+        }
+    }
+
+    /// Test whether type `T` is claimed right now.  Another thread
+    /// may claim or release it straight afterwards, so this is only a
+    /// snapshot.
+    ///
+    /// With the **exclusion-set** feature, the set has no way to look
+    /// up a type, so this claims `T` and releases it again.  Whilst it
+    /// does so, a [`TypeGate::try_claim`] of `T` on another thread
+    /// may fail.
+    ///
+    /// [`TypeGate::try_claim`]: #method.try_claim
+    #[inline]
+    pub fn is_claimed<T: 'static>() -> bool {
+        contains(TypeId::of::<T>())
+    }
+
+    /// Get the source location of the call which made the current
+    /// claim on type `T`, or `None` if it is not claimed.  For a
+    /// claim made by creating a [`TCellOwner`], this is where the
+    /// owner was created.  As for [`TypeGate::is_claimed`], this is
+    /// only a snapshot.
+    ///
+    /// With the **exclusion-set** feature but without `std`, the
+    /// locations are not recorded, so this always returns `None`.
+    ///
+    #[cfg_attr(
+        feature = "std",
+        doc = "
+ ```
+ # use qcell::typeid_gate::TypeGate;
+ qcell::marker!(struct AudioDevice;);
+
+ let claim = TypeGate::try_claim::<AudioDevice>().unwrap();
+ let location = TypeGate::claimed_at::<AudioDevice>().unwrap();
+ assert_eq!(location.line(), line!() - 2);
+ drop(claim);
+ assert!(TypeGate::claimed_at::<AudioDevice>().is_none());
+ ```
+"
+    )]
+    ///
+    /// [`TCellOwner`]: ../struct.TCellOwner.html
+    /// [`TypeGate::is_claimed`]: #method.is_claimed
+    pub fn claimed_at<T: 'static>() -> Option<&'static Location<'static>> {
+        claimant(TypeId::of::<T>()).map(|claimant| claimant.location)
+    }
+
+    // Where the current claim on `T` was made, for panic messages
+    pub(crate) fn claimant<T: 'static>() -> Option<Claimant> {
+        claimant(TypeId::of::<T>())
+    }
+}
+
+/// A claim on type `T` in the [`TypeGate`], which is released when
+/// this is dropped.
+///
+/// If the guard is leaked, e.g. with `mem::forget`, the type stays
+/// claimed for the rest of the program.
+///
+/// [`TypeGate`]: struct.TypeGate.html
+pub struct ClaimGuard<T: 'static> {
+    typ: PhantomData<Invariant<T>>,
+}
+
+impl<T: 'static> ClaimGuard<T> {
+    // A guard for a claim that was made by other means, for an owner
+    // that is never dropped.  Dropping this would release whichever
+    // claim on `T` is held, so the caller must make sure it never
+    // is.
+    #[cfg(target_has_atomic = "64")]
+    pub(crate) fn assume_claimed() -> Self {
+        Self { typ: PhantomData }
+    }
+}
+
+impl<T: 'static> Drop for ClaimGuard<T> {
+    fn drop(&mut self) {
+        remove(TypeId::of::<T>());
+    }
+}
+
+/// Per-thread set of claimed types.
+///
+/// This is the same as [`TypeGate`], except that the claims are
+/// separate for each thread, so the same type may be claimed once in
+/// each thread at the same time.  The [`LocalClaimGuard`] is neither
+/// `Send` nor `Sync`, so the claim is released on the thread it was
+/// made on.  There is no blocking claim, since only the current
+/// thread could release the claim.  This is the registry which
+/// [`TLCellOwner`] uses, and as with [`TypeGate`], the claims are
+/// shared with it.
+///
+/// ```
+///# use qcell::typeid_gate::LocalTypeGate;
+/// struct Scratch;
+///
+/// let claim = LocalTypeGate::try_claim::<Scratch>().unwrap();
+/// assert!(LocalTypeGate::try_claim::<Scratch>().is_none());
+/// std::thread::spawn(|| {
+///     assert!(!LocalTypeGate::is_claimed::<Scratch>());
+///     assert!(LocalTypeGate::try_claim::<Scratch>().is_some());
+/// })
+/// .join()
+/// .unwrap();
+/// drop(claim);
+/// ```
+///
+/// [`TypeGate`]: struct.TypeGate.html
+/// [`LocalClaimGuard`]: struct.LocalClaimGuard.html
+/// [`TLCellOwner`]: ../struct.TLCellOwner.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct LocalTypeGate {
+    _private: (),
+}
+
+#[cfg(feature = "std")]
+impl LocalTypeGate {
+    /// Claim type `T` for the current thread, or return `None` if it
+    /// is already claimed in this thread.
+    #[inline]
+    #[track_caller]
+    pub fn try_claim<T: 'static>() -> Option<LocalClaimGuard<T>> {
+        let claimant = Claimant::here();
+        let inserted = LOCAL_CLAIMS.with(|claims| {
+            match claims.borrow_mut().entry(TypeId::of::<T>()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(claimant);
+                    true
+                }
+                Entry::Occupied(_) => false,
+            }
+        });
+        if inserted {
+            Some(LocalClaimGuard {
+                typ: PhantomData,
+                not_send_or_sync: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Test whether type `T` is claimed in the current thread.
+    #[inline]
+    pub fn is_claimed<T: 'static>() -> bool {
+        LOCAL_CLAIMS.with(|claims| claims.borrow().contains_key(&TypeId::of::<T>()))
+    }
+
+    /// Get the source location of the call which made the claim on
+    /// type `T` in the current thread, or `None` if it is not claimed
+    /// in this thread.  For a claim made by creating a
+    /// [`TLCellOwner`], this is where the owner was created.
+    ///
+    /// [`TLCellOwner`]: ../struct.TLCellOwner.html
+    pub fn claimed_at<T: 'static>() -> Option<&'static Location<'static>> {
+        Self::claimant::<T>().map(|claimant| claimant.location)
+    }
+
+    // Where the claim on `T` in this thread was made, for panic
+    // messages
+    pub(crate) fn claimant<T: 'static>() -> Option<Claimant> {
+        LOCAL_CLAIMS.with(|claims| claims.borrow().get(&TypeId::of::<T>()).cloned())
+    }
+}
+
+/// A claim on type `T` in the [`LocalTypeGate`] of the current
+/// thread, which is released when this is dropped.
+///
+/// [`LocalTypeGate`]: struct.LocalTypeGate.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct LocalClaimGuard<T: 'static> {
+    typ: PhantomData<Invariant<T>>,
+    // Disable Send and Sync
+    not_send_or_sync: PhantomData<*const ()>,
+}
+
+#[cfg(feature = "std")]
+impl<T: 'static> Drop for LocalClaimGuard<T> {
+    fn drop(&mut self) {
+        // Ignore failure in case the thread-local has already been
+        // destroyed
+        let _ = LOCAL_CLAIMS.try_with(|claims| claims.borrow_mut().remove(&TypeId::of::<T>()));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{LocalTypeGate, TypeGate};
+    #[cfg(not(any(
+        feature = "exclusion-set",
+        all(target_arch = "wasm32", not(target_feature = "atomics"))
+    )))]
+    use super::Claimant;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn type_gate_claims() {
+        struct Resource;
+        struct Other;
+        assert!(!TypeGate::is_claimed::<Resource>());
+        let claim = TypeGate::try_claim::<Resource>().unwrap();
+        assert!(TypeGate::is_claimed::<Resource>());
+        assert!(TypeGate::try_claim::<Resource>().is_none());
+
+        // Other types, and the same type on other threads, are
+        // independent
+        let other = TypeGate::try_claim::<Other>().unwrap();
+        thread::spawn(|| assert!(TypeGate::try_claim::<Resource>().is_none()))
+            .join()
+            .unwrap();
+        drop(claim);
+        assert!(!TypeGate::is_claimed::<Resource>());
+        assert!(TypeGate::is_claimed::<Other>());
+        drop(other);
+
+        // The guard can be released on another thread
+        let claim = TypeGate::try_claim::<Resource>().unwrap();
+        thread::spawn(move || drop(claim)).join().unwrap();
+        assert!(TypeGate::try_claim::<Resource>().is_some());
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", not(target_feature = "atomics"))))]
+    #[test]
+    fn type_gate_claim_blocking() {
+        struct Resource;
+        let claim = TypeGate::try_claim::<Resource>().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let waiter = thread::spawn(move || {
+            let claim = TypeGate::claim_blocking::<Resource>();
+            tx.send(()).unwrap();
+            drop(claim);
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(rx.try_recv().is_err());
+        drop(claim);
+        rx.recv().unwrap();
+        waiter.join().unwrap();
+        assert!(!TypeGate::is_claimed::<Resource>());
+    }
+
+    // The `Shard` tests use a `VirtualPark`, which lets them wait
+    // until a thread has blocked, and decide when time passes, so
+    // they check the order of events without any real sleeps
+    #[cfg(not(any(
+        feature = "exclusion-set",
+        all(target_arch = "wasm32", not(target_feature = "atomics"))
+    )))]
+    type TestShard = super::Shard<crate::park::VirtualPark>;
+
+    #[cfg(not(any(
+        feature = "exclusion-set",
+        all(target_arch = "wasm32", not(target_feature = "atomics"))
+    )))]
+    #[test]
+    fn shard_waiter_blocks_until_release() {
+        use std::any::TypeId;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        struct Resource;
+        struct Other;
+        let id = TypeId::of::<Resource>();
+        let shard = Arc::new(TestShard::default());
+        let released = Arc::new(AtomicBool::new(false));
+        assert!(shard.insert(id, Claimant::here()));
+        let waiter = {
+            let shard = shard.clone();
+            let released = released.clone();
+            thread::spawn(move || {
+                assert!(shard.wait_insert(id, Claimant::here()));
+                assert!(released.load(Ordering::SeqCst));
+                shard.remove(id);
+            })
+        };
+        shard.released.wait_parks(1);
+
+        // Releasing another type wakes the waiter, but it goes back
+        // to waiting
+        assert!(shard.insert(TypeId::of::<Other>(), Claimant::here()));
+        shard.remove(TypeId::of::<Other>());
+        shard.released.wait_parks(2);
+        assert!(shard.contains(id));
+
+        released.store(true, Ordering::SeqCst);
+        shard.remove(id);
+        waiter.join().unwrap();
+        assert!(!shard.contains(id));
+    }
+
+    #[cfg(not(any(
+        feature = "exclusion-set",
+        all(target_arch = "wasm32", not(target_feature = "atomics"))
+    )))]
+    #[test]
+    fn shard_wait_timeout() {
+        use std::any::TypeId;
+        use std::sync::Arc;
+        struct Resource;
+        let id = TypeId::of::<Resource>();
+        let shard = Arc::new(TestShard::default());
+        let wait = |shard: &Arc<TestShard>| {
+            let shard = shard.clone();
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || {
+                tx.send(shard.wait_insert_timeout(id, Claimant::here(), Duration::from_secs(10)))
+                    .unwrap()
+            });
+            rx
+        };
+
+        // Time passing short of the deadline doesn't end the wait
+        assert!(shard.insert(id, Claimant::here()));
+        let rx = wait(&shard);
+        shard.released.wait_parks(1);
+        shard.released.advance(&shard.claims, Duration::from_secs(9));
+        shard.released.wait_parks(2);
+        assert!(rx.try_recv().is_err());
+
+        // Reaching the deadline does, without claiming
+        shard.released.advance(&shard.claims, Duration::from_secs(1));
+        assert_eq!(rx.recv().unwrap(), None);
+        assert!(shard.contains(id));
+
+        // A release before the deadline gives the waiter the claim
+        let rx = wait(&shard);
+        shard.released.wait_parks(3);
+        shard.released.advance(&shard.claims, Duration::from_secs(5));
+        shard.released.wait_parks(4);
+        shard.remove(id);
+        assert_eq!(rx.recv().unwrap(), Some(true));
+        assert!(shard.contains(id));
+
+        // Waiting on the thread's own claim returns straight away
+        shard.remove(id);
+        assert!(shard.insert(id, Claimant::here()));
+        assert_eq!(
+            shard.wait_insert_timeout(id, Claimant::here(), Duration::from_secs(10)),
+            Some(false)
+        );
+    }
+
+    #[cfg(not(any(
+        feature = "exclusion-set",
+        all(target_arch = "wasm32", not(target_feature = "atomics"))
+    )))]
+    #[test]
+    fn shard_waiters_take_turns() {
+        use std::any::TypeId;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+        const WAITERS: usize = 100;
+        struct Resource;
+        let id = TypeId::of::<Resource>();
+        let shard = Arc::new(TestShard::default());
+        let released = Arc::new(AtomicBool::new(false));
+        let held = Arc::new(AtomicBool::new(false));
+        let count = Arc::new(AtomicUsize::new(0));
+        assert!(shard.insert(id, Claimant::here()));
+        let waiters: Vec<_> = (0..WAITERS)
+            .map(|_| {
+                let (shard, released) = (shard.clone(), released.clone());
+                let (held, count) = (held.clone(), count.clone());
+                thread::spawn(move || {
+                    assert!(shard.wait_insert(id, Claimant::here()));
+                    assert!(released.load(Ordering::SeqCst));
+                    assert!(!held.swap(true, Ordering::SeqCst));
+                    count.fetch_add(1, Ordering::SeqCst);
+                    held.store(false, Ordering::SeqCst);
+                    shard.remove(id);
+                })
+            })
+            .collect();
+
+        // Nothing is released until they have all blocked
+        shard.released.wait_parks(WAITERS);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        released.store(true, Ordering::SeqCst);
+        shard.remove(id);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), WAITERS);
+        assert!(!shard.contains(id));
+    }
+
+    #[cfg(not(feature = "exclusion-set"))]
+    #[test]
+    fn type_gate_claim_blocking_same_thread_panics() {
+        struct Resource;
+        let _claim = TypeGate::try_claim::<Resource>().unwrap();
+        let result = std::panic::catch_unwind(TypeGate::claim_blocking::<Resource>);
+        assert!(result.is_err());
+        // Still claimed, and the mutex isn't poisoned
+        assert!(TypeGate::is_claimed::<Resource>());
+    }
+
+    #[test]
+    fn type_gate_shared_with_tcell_owner() {
+        use crate::TCellOwner;
+        crate::marker!(struct Marker;);
+        let claim = TypeGate::try_claim::<Marker>().unwrap();
+        assert!(TCellOwner::<Marker>::try_new().is_none());
+        drop(claim);
+        let owner = TCellOwner::<Marker>::new();
+        assert!(TypeGate::is_claimed::<Marker>());
+        drop(owner);
+        assert!(!TypeGate::is_claimed::<Marker>());
+    }
+
+    #[test]
+    fn local_type_gate_claims() {
+        use crate::TLCellOwner;
+        crate::marker!(struct Marker;);
+        let claim = LocalTypeGate::try_claim::<Marker>().unwrap();
+        assert!(LocalTypeGate::is_claimed::<Marker>());
+        assert!(LocalTypeGate::try_claim::<Marker>().is_none());
+        assert!(!TypeGate::is_claimed::<Marker>());
+        thread::spawn(|| {
+            let _owner = TLCellOwner::<Marker>::new();
+            assert!(LocalTypeGate::is_claimed::<Marker>());
+        })
+        .join()
+        .unwrap();
+        drop(claim);
+        let owner = TLCellOwner::<Marker>::new();
+        assert!(LocalTypeGate::try_claim::<Marker>().is_none());
+        drop(owner);
+        assert!(!LocalTypeGate::is_claimed::<Marker>());
+    }
+}
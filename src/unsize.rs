@@ -0,0 +1,342 @@
+//! Unsizing of cells on stable Rust using the `unsize` crate.
+//!
+//! Until `CoerceUnsized` is stable for user types, the [`unsize`]
+//! crate provides [`Coercion`] values, which describe how to unsize a
+//! pointer to a concrete type, such as `Coercion!(to dyn Trait)` or
+//! `Coercion::to_slice()`, and can be passed around and stored like
+//! any other value.  The functions here turn a `Coercion` for the
+//! contents of a cell into a `Coercion` for the cell itself, for each
+//! of the four cell types, and apply a `Coercion` to a `Box`, `Rc` or
+//! `Arc`.  So a cell holding a concrete type can be unsized to a cell
+//! holding a trait object or slice in a single expression, without a
+//! constructor function whose return type forces the coercion:
+//!
+//! ```
+//!# use qcell::{unsize, QCell, QCellOwner};
+//!# use std::rc::Rc;
+//! use ::unsize::{CoerceUnsize, Coercion};
+//! use std::fmt::Debug;
+//!
+//! let owner = QCellOwner::new();
+//! let cell = Rc::new(QCell::new(&owner, 123_u32));
+//! let cell = unsize::rc(cell, unsize::qcell(Coercion!(to dyn Debug)));
+//! assert_eq!(format!("{:?}", owner.ro(&cell)), "123");
+//!
+//! let cell = QCell::new(&owner, [1, 2, 3]);
+//! let slice = (&cell).unsize(unsize::qcell(Coercion::to_slice()));
+//! assert_eq!(owner.ro(slice), [1, 2, 3]);
+//! ```
+//!
+//! As shown above, references to cells can also be unsized directly
+//! with [`CoerceUnsize::unsize`], along with any other pointer type
+//! implementing [`CoerciblePtr`].
+//!
+//! `TCell`, `TLCell` and `LCell` are `repr(transparent)`, so their
+//! contents are at the start of the cell.  The contents of a `QCell`
+//! follow the owner ID, so the contents pointer is unsized and then
+//! moved back to the start of the cell, keeping its metadata.  This
+//! needs Rust 1.75.
+//!
+//! [`unsize`]: https://docs.rs/unsize
+//! [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+//! [`CoerceUnsize::unsize`]: https://docs.rs/unsize/1/unsize/trait.CoerceUnsize.html#method.unsize
+//! [`CoerciblePtr`]: https://docs.rs/unsize/1/unsize/trait.CoerciblePtr.html
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::mem;
+
+use ::unsize::{CoerceUnsize, Coercion};
+
+use crate::{sound, LCell, QCell, QCellOwnerID, TCell};
+#[cfg(feature = "std")]
+use crate::TLCell;
+
+// Offset of the contents within a `QCell<T>`.  `QCell` is `repr(C)`,
+// so this is the size of the owner ID rounded up to the alignment of
+// the contents.
+fn qcell_value_offset<T>() -> usize {
+    let align = mem::align_of::<T>();
+    (mem::size_of::<QCellOwnerID>() + align - 1) & !(align - 1)
+}
+
+/// Convert a [`Coercion`] for the contents of a [`QCell`] into one
+/// for the cell itself.
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`QCell`]: ../struct.QCell.html
+#[allow(clippy::type_complexity)]
+pub fn qcell<T, U: ?Sized, F>(
+    with: Coercion<T, U, F>,
+) -> Coercion<QCell<T>, QCell<U>, impl FnOnce(*const QCell<T>) -> *const QCell<U>>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let coerce = move |ptr: *const QCell<T>| {
+        let offset = qcell_value_offset::<T>();
+        let value = (ptr as *const u8).wrapping_add(offset) as *const T;
+        let value: *const U = value.unsize(with);
+        (value as *const QCell<U>).wrapping_byte_sub(offset)
+    };
+    // Safety: The contents pointer is only unsized by `with`, and
+    // then moved back by the same offset to the start of the cell
+    unsafe { sound::cell_coercion(coerce) }
+}
+
+/// Convert a [`Coercion`] for the contents of a [`TCell`] into one
+/// for the cell itself.
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`TCell`]: ../struct.TCell.html
+#[allow(clippy::type_complexity)]
+pub fn tcell<Q, T, U: ?Sized, F>(
+    with: Coercion<T, U, F>,
+) -> Coercion<TCell<Q, T>, TCell<Q, U>, impl FnOnce(*const TCell<Q, T>) -> *const TCell<Q, U>>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let coerce = move |ptr: *const TCell<Q, T>| {
+        let value: *const U = (ptr as *const T).unsize(with);
+        value as *const TCell<Q, U>
+    };
+    // Safety: `TCell` is `repr(transparent)`, and the pointer is only
+    // unsized by `with`
+    unsafe { sound::cell_coercion(coerce) }
+}
+
+/// Convert a [`Coercion`] for the contents of a [`TLCell`] into one
+/// for the cell itself.
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`TLCell`]: ../struct.TLCell.html
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[allow(clippy::type_complexity)]
+pub fn tlcell<Q, T, U: ?Sized, F>(
+    with: Coercion<T, U, F>,
+) -> Coercion<TLCell<Q, T>, TLCell<Q, U>, impl FnOnce(*const TLCell<Q, T>) -> *const TLCell<Q, U>>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let coerce = move |ptr: *const TLCell<Q, T>| {
+        let value: *const U = (ptr as *const T).unsize(with);
+        value as *const TLCell<Q, U>
+    };
+    // Safety: `TLCell` is `repr(transparent)`, and the pointer is
+    // only unsized by `with`
+    unsafe { sound::cell_coercion(coerce) }
+}
+
+/// Convert a [`Coercion`] for the contents of an [`LCell`] into one
+/// for the cell itself.
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`LCell`]: ../struct.LCell.html
+#[allow(clippy::type_complexity)]
+pub fn lcell<'id, T, U: ?Sized, F>(
+    with: Coercion<T, U, F>,
+) -> Coercion<
+    LCell<'id, T>,
+    LCell<'id, U>,
+    impl FnOnce(*const LCell<'id, T>) -> *const LCell<'id, U>,
+>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    let coerce = move |ptr: *const LCell<'id, T>| {
+        let value: *const U = (ptr as *const T).unsize(with);
+        value as *const LCell<'id, U>
+    };
+    // Safety: `LCell` is `repr(transparent)`, and the pointer is only
+    // unsized by `with`
+    unsafe { sound::cell_coercion(coerce) }
+}
+
+/// Unsize the value in a `Box` using a [`Coercion`], such as one
+/// made by [`qcell`] or [`tcell`].
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`qcell`]: fn.qcell.html
+/// [`tcell`]: fn.tcell.html
+#[inline]
+pub fn boxed<T, U: ?Sized, F>(b: Box<T>, with: Coercion<T, U, F>) -> Box<U>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    sound::unsize_box(b, with)
+}
+
+/// Unsize the value in an `Rc` using a [`Coercion`], such as one
+/// made by [`qcell`] or [`tcell`].
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`qcell`]: fn.qcell.html
+/// [`tcell`]: fn.tcell.html
+#[inline]
+pub fn rc<T, U: ?Sized, F>(rc: Rc<T>, with: Coercion<T, U, F>) -> Rc<U>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    sound::unsize_rc(rc, with)
+}
+
+/// Unsize the value in an `Arc` using a [`Coercion`], such as one
+/// made by [`qcell`] or [`tcell`].
+///
+/// [`Coercion`]: https://docs.rs/unsize/1/unsize/struct.Coercion.html
+/// [`qcell`]: fn.qcell.html
+/// [`tcell`]: fn.tcell.html
+#[inline]
+pub fn arc<T, U: ?Sized, F>(arc: Arc<T>, with: Coercion<T, U, F>) -> Arc<U>
+where
+    F: FnOnce(*const T) -> *const U,
+{
+    sound::unsize_arc(arc, with)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{LCellOwner, QCellOwner, TCellOwner, TLCellOwner};
+    use ::unsize::Coercion;
+
+    struct Squares(u32);
+    struct Integers(u64);
+    trait Series {
+        fn step(&mut self);
+        fn value(&self) -> u64;
+    }
+    impl Series for Squares {
+        fn step(&mut self) {
+            self.0 += 1;
+        }
+        fn value(&self) -> u64 {
+            (self.0 as u64) * (self.0 as u64)
+        }
+    }
+    impl Series for Integers {
+        fn step(&mut self) {
+            self.0 += 1;
+        }
+        fn value(&self) -> u64 {
+            self.0
+        }
+    }
+
+    // Same steps as the `*_unsized` tests for each cell type
+    macro_rules! exercise {
+        ($own:ident, $cell1:ident, $cell2:ident, $cell3:ident) => {
+            assert_eq!($cell1.ro($own).value(), 4);
+            $cell1.rw($own).step();
+            assert_eq!($cell1.ro($own).value(), 5);
+            assert_eq!($own.ro(&$cell2).value(), 49);
+            $own.rw(&$cell2).step();
+            assert_eq!($own.ro(&$cell2).value(), 64);
+            let (r1, r2, r3) = $own.rw3(&$cell1, &$cell2, &$cell3);
+            r1.step();
+            r2.step();
+            r3.step();
+            assert_eq!($cell1.ro($own).value(), 6);
+            assert_eq!($cell2.ro($own).value(), 81);
+            assert_eq!($cell3.ro($own).value(), 16);
+            let (r1, r2) = $own.rw2(&$cell1, &$cell2);
+            r1.step();
+            r2.step();
+            assert_eq!($cell1.ro($own).value(), 7);
+            assert_eq!($cell2.ro($own).value(), 100);
+        };
+    }
+
+    #[test]
+    fn unsize_qcell() {
+        let mut owner = QCellOwner::new();
+        let own = &mut owner;
+        let cell1: Box<QCell<dyn Series>> = boxed(
+            Box::new(QCell::new(&*own, Integers(4))),
+            qcell(Coercion!(to dyn Series)),
+        );
+        let cell2: Rc<QCell<dyn Series>> =
+            rc(Rc::new(own.cell(Squares(7))), qcell(Coercion!(to dyn Series)));
+        let cell3: Arc<QCell<dyn Series>> =
+            arc(Arc::new(own.cell(Squares(3))), qcell(Coercion!(to dyn Series)));
+        exercise!(own, cell1, cell2, cell3);
+    }
+
+    #[test]
+    fn unsize_qcell_offsets() {
+        // Contents more aligned than the owner ID, so that the offset
+        // differs from the size of the ID
+        #[repr(align(32))]
+        struct Aligned(u8);
+        impl Series for Aligned {
+            fn step(&mut self) {
+                self.0 += 1;
+            }
+            fn value(&self) -> u64 {
+                self.0 as u64
+            }
+        }
+        let mut owner = QCellOwner::new();
+        let cell = Box::new(owner.cell(Aligned(1)));
+        let value_addr = owner.ro(&cell) as *const Aligned as usize;
+        let cell_addr = &*cell as *const QCell<Aligned> as usize;
+        assert_eq!(value_addr - cell_addr, qcell_value_offset::<Aligned>());
+        assert_eq!(qcell_value_offset::<Aligned>(), 32);
+
+        let cell = boxed(cell, qcell(Coercion!(to dyn Series)));
+        assert_eq!(&*cell as *const QCell<dyn Series> as *const u8 as usize, cell_addr);
+        owner.rw(&cell).step();
+        assert_eq!(owner.ro(&cell).value(), 2);
+
+        let cell = owner.cell([1_u8, 2, 3]);
+        let slice: &QCell<[u8]> = (&cell).unsize(qcell(Coercion::to_slice()));
+        owner.rw(slice)[1] = 20;
+        assert_eq!(owner.ro(slice), [1, 20, 3]);
+        assert_eq!(core::mem::size_of_val(slice), core::mem::size_of_val(&cell));
+    }
+
+    #[test]
+    fn unsize_tcell() {
+        crate::marker!(struct Marker;);
+        type ACell<T> = TCell<Marker, T>;
+        let mut owner = TCellOwner::<Marker>::new();
+        let own = &mut owner;
+        let cell1: Box<ACell<dyn Series>> =
+            boxed(Box::new(ACell::new(Integers(4))), tcell(Coercion!(to dyn Series)));
+        let cell2: Rc<ACell<dyn Series>> =
+            rc(Rc::new(ACell::new(Squares(7))), tcell(Coercion!(to dyn Series)));
+        let cell3: Arc<ACell<dyn Series>> =
+            arc(Arc::new(ACell::new(Squares(3))), tcell(Coercion!(to dyn Series)));
+        exercise!(own, cell1, cell2, cell3);
+    }
+
+    #[test]
+    fn unsize_tlcell() {
+        crate::marker!(struct Marker;);
+        type ACell<T> = TLCell<Marker, T>;
+        let mut owner = TLCellOwner::<Marker>::new();
+        let own = &mut owner;
+        let cell1: Box<ACell<dyn Series>> =
+            boxed(Box::new(ACell::new(Integers(4))), tlcell(Coercion!(to dyn Series)));
+        let cell2: Rc<ACell<dyn Series>> =
+            rc(Rc::new(ACell::new(Squares(7))), tlcell(Coercion!(to dyn Series)));
+        let cell3: Box<ACell<dyn Series>> =
+            boxed(Box::new(ACell::new(Squares(3))), tlcell(Coercion!(to dyn Series)));
+        exercise!(own, cell1, cell2, cell3);
+    }
+
+    #[test]
+    fn unsize_lcell() {
+        LCellOwner::scope(|mut owner| {
+            let own = &mut owner;
+            let cell1: Box<LCell<'_, dyn Series>> =
+                boxed(Box::new(LCell::new(Integers(4))), lcell(Coercion!(to dyn Series)));
+            let cell2: Rc<LCell<'_, dyn Series>> =
+                rc(Rc::new(LCell::new(Squares(7))), lcell(Coercion!(to dyn Series)));
+            let cell3: Arc<LCell<'_, dyn Series>> =
+                arc(Arc::new(LCell::new(Squares(3))), lcell(Coercion!(to dyn Series)));
+            exercise!(own, cell1, cell2, cell3);
+        });
+    }
+}
@@ -0,0 +1,283 @@
+//! Helpers used by the owners, which are also useful directly.
+//!
+//! [`are_all_distinct`] and [`assert_all_distinct`] are the check that
+//! `rw_from_slice`, `rw_from_iter` and the `rw!` macro use to make
+//! sure that no cell is borrowed mutably twice, except that those
+//! calls also take the size of each cell into account, so that an
+//! `LCell` for a whole slice clashes with the cells from its
+//! `as_slice_of_cells`.  These compare addresses only, so can be used
+//! to check any set of references before handing out `&mut` to all
+//! of them at once.
+//!
+//! ```
+//!# use qcell::util::are_all_distinct;
+//! let values = [1, 2, 3];
+//! let ptrs = [&values[0], &values[2], &values[0]].map(|r| r as *const i32 as *const ());
+//! assert_eq!(are_all_distinct(&ptrs), Some((0, 2)));
+//! ```
+//!
+//! [`are_all_distinct`]: fn.are_all_distinct.html
+//! [`assert_all_distinct`]: fn.assert_all_distinct.html
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::panic_messages;
+
+/// Up to this many pointers, every pair is compared.  Above it, if
+/// the "alloc" feature is enabled, the pointers are sorted instead.
+/// See `benches/distinct.rs` for the measurements behind this value.
+pub const PAIRWISE_MAX: usize = 32;
+
+/// Check whether all the pointers are different.  Returns `None` if
+/// they are, or otherwise the first pair of indices `(i, j)` with
+/// `i < j` that hold the same pointer.  "First" means the lowest `i`,
+/// then the lowest `j`, whichever way the check was made.
+///
+/// Only the addresses are compared, so a pointer to a zero-sized
+/// value may collide with a pointer to something else.
+pub fn are_all_distinct(ptrs: &[*const ()]) -> Option<(usize, usize)> {
+    first_duplicate(ptrs.len(), |i| ptrs[i] as usize)
+}
+
+/// Panic if any two of the pointers are the same, giving the indices
+/// of the first pair as returned by [`are_all_distinct`].
+///
+/// [`are_all_distinct`]: fn.are_all_distinct.html
+pub fn assert_all_distinct(ptrs: &[*const ()]) {
+    if let Some((i, j)) = are_all_distinct(ptrs) {
+        not_distinct_panic(i, j);
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn not_distinct_panic(index1: usize, index2: usize) -> ! {
+    panic!(
+        "{}: ptrs[{}] and ptrs[{}]",
+        panic_messages::UTIL_NOT_DISTINCT,
+        index1,
+        index2
+    );
+}
+
+/// [`are_all_distinct`] always comparing every pair.  Only for
+/// benchmarks.
+///
+/// [`are_all_distinct`]: fn.are_all_distinct.html
+#[doc(hidden)]
+pub fn are_all_distinct_pairwise(ptrs: &[*const ()]) -> Option<(usize, usize)> {
+    pairwise(ptrs.len(), |i| ptrs[i] as usize)
+}
+
+/// [`are_all_distinct`] always sorting.  Only for benchmarks.
+///
+/// [`are_all_distinct`]: fn.are_all_distinct.html
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub fn are_all_distinct_sorted(ptrs: &[*const ()]) -> Option<(usize, usize)> {
+    sorted(ptrs.len(), |i| ptrs[i] as usize)
+}
+
+// The one implementation of the check, taking the address of each of
+// `len` entries from `addr`, so that callers don't have to collect
+// the addresses first.
+#[inline]
+pub(crate) fn first_duplicate(len: usize, addr: impl Fn(usize) -> usize) -> Option<(usize, usize)> {
+    #[cfg(feature = "alloc")]
+    if len > PAIRWISE_MAX {
+        return sorted(len, addr);
+    }
+    pairwise(len, addr)
+}
+
+#[inline]
+fn pairwise(len: usize, addr: impl Fn(usize) -> usize) -> Option<(usize, usize)> {
+    for i in 0..len {
+        let a = addr(i);
+        for j in i + 1..len {
+            if addr(j) == a {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+// Whether two cells, each given as `(address, size)`, can't both be
+// borrowed mutably: either they start at the same address, or their
+// bytes overlap.  Distinct cells of the same type never overlap, but
+// an `LCell` for a whole slice overlaps the cells for its elements.
+#[inline]
+pub(crate) fn clash((a, a_size): (usize, usize), (b, b_size): (usize, usize)) -> bool {
+    a == b || (a < b + b_size && b < a + a_size)
+}
+
+// The same as `first_duplicate`, but for cells given as `(address,
+// size)`, using `clash`.  For long lists, sorting finds out whether
+// there is any clash, and the pairs are only compared to find the
+// first one if so, since that is about to panic anyway.  Tuples are
+// short, so for them the length check is resolved at compile time.
+#[inline]
+pub(crate) fn first_clash(
+    len: usize,
+    range: impl Fn(usize) -> (usize, usize),
+) -> Option<(usize, usize)> {
+    #[cfg(feature = "alloc")]
+    if len > PAIRWISE_MAX && !any_clash_sorted(len, &range) {
+        return None;
+    }
+    for i in 0..len {
+        let a = range(i);
+        for j in i + 1..len {
+            if clash(a, range(j)) {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(feature = "alloc")]
+fn any_clash_sorted(len: usize, range: impl Fn(usize) -> (usize, usize)) -> bool {
+    let mut sorted: Vec<(usize, usize)> = (0..len).map(range).collect();
+    sorted.sort_unstable();
+    // Each cell clashes with an earlier one in the sorted order if it
+    // starts at the same address, or before the furthest end so far
+    let mut prev = None;
+    let mut reach = 0;
+    for (start, size) in sorted {
+        if prev == Some(start) || start < reach {
+            return true;
+        }
+        prev = Some(start);
+        reach = reach.max(start + size);
+    }
+    false
+}
+
+#[cfg(feature = "alloc")]
+fn sorted(len: usize, addr: impl Fn(usize) -> usize) -> Option<(usize, usize)> {
+    let mut sorted: Vec<(usize, usize)> = (0..len).map(|i| (addr(i), i)).collect();
+    sorted.sort_unstable();
+    // Entries with the same address are now adjacent and in index
+    // order, so the lowest adjacent pair is the first pair of the run
+    // that starts earliest, which is what `pairwise` finds
+    sorted
+        .windows(2)
+        .filter(|pair| pair[0].0 == pair[1].0)
+        .map(|pair| (pair[0].1, pair[1].1))
+        .min()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{are_all_distinct, assert_all_distinct, clash, first_clash, pairwise, sorted};
+    use super::PAIRWISE_MAX;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    // Compare both strategies against duplicates planted at known
+    // positions in random permutations of distinct addresses
+    #[test]
+    fn util_strategies_agree() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1736);
+        for round in 0..2000 {
+            let len = rng.gen_range(0..200);
+            let mut addrs: Vec<usize> = (1..=len).map(|a| a * 8).collect();
+            addrs.shuffle(&mut rng);
+            let planted = if len >= 2 && round % 4 != 0 {
+                let mut i = rng.gen_range(0..len);
+                let mut j = rng.gen_range(0..len);
+                while i == j {
+                    j = rng.gen_range(0..len);
+                }
+                if i > j {
+                    std::mem::swap(&mut i, &mut j);
+                }
+                addrs[j] = addrs[i];
+                // Sometimes plant a second pair too, which may come
+                // before or after the first
+                if round % 3 == 0 && len >= 4 {
+                    let k = rng.gen_range(0..len);
+                    let l = rng.gen_range(0..len);
+                    if ![i, j].contains(&k) && ![i, j].contains(&l) && k != l {
+                        addrs[l] = addrs[k];
+                    }
+                }
+                true
+            } else {
+                false
+            };
+            let expected = naive(&addrs);
+            assert_eq!(expected.is_some(), planted);
+            assert_eq!(pairwise(len, |i| addrs[i]), expected);
+            assert_eq!(sorted(len, |i| addrs[i]), expected);
+            let ptrs: Vec<*const ()> = addrs.iter().map(|&a| a as *const ()).collect();
+            assert_eq!(are_all_distinct(&ptrs), expected);
+        }
+    }
+
+    // The definition of the first pair, spelt out
+    fn naive(addrs: &[usize]) -> Option<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..addrs.len() {
+            for j in 0..addrs.len() {
+                if i < j && addrs[i] == addrs[j] {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs.into_iter().min()
+    }
+
+    // Compare `first_clash` against every pair, with random ranges
+    // that sometimes overlap, and some empty ones
+    #[test]
+    fn util_first_clash() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1697);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..PAIRWISE_MAX * 3);
+            let spread = rng.gen_range(1..20) * len + 1;
+            let ranges: Vec<(usize, usize)> = (0..len)
+                .map(|_| (rng.gen_range(0..spread) * 4, rng.gen_range(0..3) * 4))
+                .collect();
+            let mut expected = None;
+            'outer: for i in 0..len {
+                for j in i + 1..len {
+                    if clash(ranges[i], ranges[j]) {
+                        expected = Some((i, j));
+                        break 'outer;
+                    }
+                }
+            }
+            assert_eq!(first_clash(len, |i| ranges[i]), expected);
+        }
+        // A whole slice against its last element, and empty ranges
+        assert!(clash((100, 16), (112, 4)));
+        assert!(!clash((100, 16), (116, 4)));
+        assert!(clash((100, 0), (100, 0)));
+        assert!(!clash((100, 0), (104, 0)));
+    }
+
+    #[test]
+    fn util_first_pair_of_run() {
+        // Three copies, and a later pair whose higher index is lower
+        let addrs = [5, 1, 2, 5, 2, 5];
+        assert_eq!(pairwise(6, |i| addrs[i]), Some((0, 3)));
+        assert_eq!(sorted(6, |i| addrs[i]), Some((0, 3)));
+        let long: Vec<usize> = (0..PAIRWISE_MAX * 2).map(|i| i % PAIRWISE_MAX).collect();
+        let ptrs: Vec<*const ()> = long.iter().map(|&a| a as *const ()).collect();
+        assert_eq!(are_all_distinct(&ptrs), Some((0, PAIRWISE_MAX)));
+        assert_eq!(are_all_distinct(&[]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ptrs[1] and ptrs[2]")]
+    fn util_assert_all_distinct() {
+        let values = [0u8; 3];
+        let ptrs = [&values[0], &values[1], &values[1]].map(|r| r as *const u8 as *const ());
+        assert_all_distinct(&ptrs[..2]);
+        assert_all_distinct(&ptrs);
+    }
+}
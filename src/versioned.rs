@@ -0,0 +1,302 @@
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use crate::QCellOwner;
+use crate::{QCell, QCellOwnerID, QCellOwnerSeq};
+
+/// Error returned by `try_write_if` on the owner when the version of
+/// a [`VersionedCell`] is not the one expected.  This gives back the
+/// rejected value, along with the current version, so that the caller
+/// can re-read the cell and try again.
+///
+/// [`VersionedCell`]: struct.VersionedCell.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConflict<T> {
+    /// The value which was not written
+    pub value: T,
+    /// The version the cell had at the time of the attempt
+    pub current: u64,
+}
+
+impl<T> fmt::Display for VersionConflict<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cell modified since read (now at version {})", self.current)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<T: fmt::Debug> std::error::Error for VersionConflict<T> {}
+
+struct Versioned<T: ?Sized> {
+    version: u64,
+    value: T,
+}
+
+/// A [`QCell`] whose value carries a version number, for optimistic
+/// concurrency.
+///
+/// With a coarse `Mutex<QCellOwner>`, a long computation based on a
+/// cell's value doesn't want to hold the lock throughout.  Instead it
+/// can take a snapshot with its version, release the lock, do the
+/// work, and then lock again and write back with `try_write_if` on
+/// the owner.  That only succeeds if the version is still the one that
+/// was read, i.e. if no-one else has written to the cell in the
+/// meantime.  Otherwise a [`VersionConflict`] is returned, and the
+/// work can be redone on a fresh snapshot.
+///
+/// The version starts at 0, and goes up by one on every successful
+/// `try_write_if`, and also on every `rw_versioned` borrow, whether
+/// or not the value is actually changed through it.  The value can't
+/// be reached in any other way, so any write is always seen as a new
+/// version.  Reading with `read_versioned` or `snapshot_versioned`
+/// doesn't change the version.
+///
+/// Since it is the version that is compared and not the value, the
+/// "ABA" problem does not arise: if the value is changed from A to B
+/// and back to A again between the read and the write-back, the
+/// version will have moved on by two, so the write-back fails even
+/// though the value looks the same.  With 64 bits the version can't
+/// realistically wrap around.
+///
+/// This is implemented for [`QCellOwner`] and [`QCellOwnerSeq`].
+/// The contents may be unsized, e.g. `Box<VersionedCell<[u8]>>`,
+/// although then only `read_versioned` and `rw_versioned` apply.
+///
+#[cfg_attr(
+    feature = "alloc",
+    doc = "
+ ```
+ # use qcell::{QCellOwner, VersionedCell};
+ # use std::sync::{Arc, Mutex};
+ let owner = Arc::new(Mutex::new(QCellOwner::new()));
+ let cell = Arc::new(VersionedCell::new(&*owner.lock().unwrap(), 10_u64));
+
+ // Snapshot under the lock, then compute without holding it
+ let (version, value) = owner.lock().unwrap().snapshot_versioned(&cell);
+ let result = value * 2;
+
+ // Write back only if no-one else wrote in the meantime
+ let mut guard = owner.lock().unwrap();
+ assert!(guard.try_write_if(&cell, version, result).is_ok());
+ assert_eq!(guard.read_versioned(&cell), (version + 1, &20));
+ ```
+"
+)]
+///
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwner`]: struct.QCellOwner.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+/// [`VersionConflict`]: struct.VersionConflict.html
+pub struct VersionedCell<T: ?Sized> {
+    inner: QCell<Versioned<T>>,
+}
+
+impl<T> VersionedCell<T> {
+    /// Create a new cell at version 0, owned by the given owner ID,
+    /// like [`QCell::new`].
+    ///
+    /// [`QCell::new`]: struct.QCell.html#method.new
+    #[inline]
+    pub fn new(id: impl Into<QCellOwnerID>, value: T) -> Self {
+        Self {
+            inner: QCell::new(id, Versioned { version: 0, value }),
+        }
+    }
+
+    /// Destroy the cell and return the contained value
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().value
+    }
+}
+
+macro_rules! versioned_access {
+    ($owner:ty, $name:literal) => {
+        impl $owner {
+            #[doc = concat!("Borrow the contents of a [`VersionedCell`] immutably, along with its current version.  Panics if the cell is not owned by this `", $name, "`.")]
+            ///
+            /// [`VersionedCell`]: struct.VersionedCell.html
+            #[inline]
+            pub fn read_versioned<'a, T: ?Sized>(&'a self, cell: &'a VersionedCell<T>) -> (u64, &'a T) {
+                let inner = self.ro(&cell.inner);
+                (inner.version, &inner.value)
+            }
+
+            /// Get a clone of the contents of a [`VersionedCell`]
+            /// along with its current version, for use after the
+            /// owner has been released.  Panics if the cell is not
+            /// owned by this owner.
+            ///
+            /// [`VersionedCell`]: struct.VersionedCell.html
+            #[inline]
+            pub fn snapshot_versioned<T: Clone>(&self, cell: &VersionedCell<T>) -> (u64, T) {
+                let inner = self.ro(&cell.inner);
+                (inner.version, inner.value.clone())
+            }
+
+            /// Borrow the contents of a [`VersionedCell`] mutably.
+            /// The version is increased straight away, whether or not
+            /// the value is then changed.  Panics if the cell is not
+            /// owned by this owner.
+            ///
+            /// [`VersionedCell`]: struct.VersionedCell.html
+            #[inline]
+            pub fn rw_versioned<'a, T: ?Sized>(&'a mut self, cell: &'a VersionedCell<T>) -> &'a mut T {
+                let inner = self.rw(&cell.inner);
+                inner.version += 1;
+                &mut inner.value
+            }
+
+            /// Replace the contents of a [`VersionedCell`] if its
+            /// version is still `expected_version`, increasing the
+            /// version by one.  Otherwise the cell is left unchanged,
+            /// and the rejected value is returned along with the
+            /// current version.  Panics if the cell is not owned by
+            /// this owner.
+            ///
+            /// [`VersionedCell`]: struct.VersionedCell.html
+            pub fn try_write_if<T>(
+                &mut self,
+                cell: &VersionedCell<T>,
+                expected_version: u64,
+                new: T,
+            ) -> Result<(), VersionConflict<T>> {
+                let inner = self.rw(&cell.inner);
+                if inner.version != expected_version {
+                    return Err(VersionConflict {
+                        value: new,
+                        current: inner.version,
+                    });
+                }
+                inner.version += 1;
+                inner.value = new;
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+versioned_access!(QCellOwner, "QCellOwner");
+versioned_access!(QCellOwnerSeq, "QCellOwnerSeq");
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{VersionConflict, VersionedCell};
+    use crate::{QCellOwner, QCellOwnerSeq};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn versioned_success_bumps_once() {
+        let mut owner = QCellOwner::new();
+        let cell = VersionedCell::new(&owner, String::from("a"));
+        assert_eq!(owner.read_versioned(&cell), (0, &String::from("a")));
+        let (version, _) = owner.snapshot_versioned(&cell);
+        assert_eq!(version, 0);
+        assert_eq!(owner.try_write_if(&cell, version, "b".into()), Ok(()));
+        assert_eq!(owner.read_versioned(&cell).0, 1);
+        // Reads don't move the version
+        let _ = owner.read_versioned(&cell);
+        let _ = owner.snapshot_versioned(&cell);
+        assert_eq!(owner.read_versioned(&cell), (1, &String::from("b")));
+        // A second write with the old version fails
+        assert_eq!(
+            owner.try_write_if(&cell, version, "c".into()),
+            Err(VersionConflict {
+                value: String::from("c"),
+                current: 1
+            })
+        );
+        assert_eq!(owner.read_versioned(&cell), (1, &String::from("b")));
+        assert_eq!(cell.into_inner(), "b");
+    }
+
+    #[test]
+    fn versioned_rw_bumps() {
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = VersionedCell::new(&owner, 5);
+        *owner.rw_versioned(&cell) += 1;
+        // Even a borrow that doesn't change the value counts
+        let _ = owner.rw_versioned(&cell);
+        assert_eq!(owner.read_versioned(&cell), (2, &6));
+        assert!(owner.try_write_if(&cell, 1, 0).is_err());
+        assert!(owner.try_write_if(&cell, 2, 0).is_ok());
+    }
+
+    #[test]
+    fn versioned_unsized() {
+        let mut owner = QCellOwner::new();
+        let cell: Box<VersionedCell<[u32]>> = Box::new(VersionedCell::new(&owner, [1, 2, 3]));
+        owner.rw_versioned(&cell)[1] = 20;
+        assert_eq!(owner.read_versioned(&cell), (1, &[1, 20, 3][..]));
+    }
+
+    #[test]
+    fn versioned_aba() {
+        let mut owner = QCellOwner::new();
+        let cell = VersionedCell::new(&owner, 'A');
+        let (version, value) = owner.snapshot_versioned(&cell);
+
+        // Another writer changes A -> B -> A
+        *owner.rw_versioned(&cell) = 'B';
+        assert!(owner.try_write_if(&cell, version + 1, 'A').is_ok());
+
+        // The value is equal to the snapshot, but the version is not,
+        // so the write-back is rejected
+        assert_eq!(*owner.read_versioned(&cell).1, value);
+        let err = owner.try_write_if(&cell, version, 'C').unwrap_err();
+        assert_eq!(err.value, 'C');
+        assert_eq!(err.current, version + 2);
+        assert_eq!(err.to_string(), "cell modified since read (now at version 2)");
+        assert_eq!(*owner.read_versioned(&cell).1, 'A');
+    }
+
+    #[test]
+    fn versioned_conflict_across_threads() {
+        let owner = Arc::new(Mutex::new(QCellOwner::new()));
+        let cell = Arc::new(VersionedCell::new(&*owner.lock().unwrap(), 100_u64));
+        let (read_tx, read_rx) = channel();
+        let (wrote_tx, wrote_rx) = channel();
+
+        let writer = {
+            let owner = owner.clone();
+            let cell = cell.clone();
+            std::thread::spawn(move || {
+                read_rx.recv().unwrap();
+                *owner.lock().unwrap().rw_versioned(&cell) += 1;
+                wrote_tx.send(()).unwrap();
+            })
+        };
+
+        // Snapshot, then let the writer in before writing back
+        let (version, value) = owner.lock().unwrap().snapshot_versioned(&cell);
+        read_tx.send(()).unwrap();
+        wrote_rx.recv().unwrap();
+        let conflict = owner
+            .lock()
+            .unwrap()
+            .try_write_if(&cell, version, value * 2)
+            .unwrap_err();
+        assert_eq!(conflict, VersionConflict { value: 200, current: 1 });
+
+        // Retry on a fresh snapshot
+        let (version, value) = owner.lock().unwrap().snapshot_versioned(&cell);
+        assert_eq!(
+            owner.lock().unwrap().try_write_if(&cell, version, value * 2),
+            Ok(())
+        );
+        writer.join().unwrap();
+        assert_eq!(owner.lock().unwrap().read_versioned(&cell), (2, &202));
+    }
+
+    #[test]
+    #[should_panic]
+    fn versioned_wrong_owner() {
+        let owner1 = QCellOwner::new();
+        let mut owner2 = QCellOwner::new();
+        let cell = VersionedCell::new(&owner1, 0);
+        let _ = owner2.try_write_if(&cell, 0, 1); // Panic here
+    }
+}
@@ -0,0 +1,436 @@
+use core::fmt;
+use core::ops::{Index, IndexMut};
+use core::slice::{self, SliceIndex};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Read-only view onto the contents of a cell holding a slice, `Vec`,
+/// boxed slice or array, as returned by the `view` call on any of the
+/// owners.
+///
+/// This supports indexing, by position or by range, and iteration.
+/// The owner stays borrowed for as long as the view exists.
+///
+/// ```
+///# use qcell::LCellOwner;
+/// LCellOwner::scope(|owner| {
+///     let cell = owner.cell([1, 2, 3]);
+///     let view = owner.view(&cell);
+///     assert_eq!(view[0] + view[2], 4);
+///     assert_eq!(view[1..].iter().sum::<i32>(), 5);
+/// });
+/// ```
+pub struct SliceView<'a, T> {
+    slice: &'a [T],
+}
+
+impl<'a, T> SliceView<'a, T> {
+    #[inline]
+    pub(crate) fn new(slice: &'a [T]) -> Self {
+        Self { slice }
+    }
+
+    /// Return the number of elements
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// Test whether there are no elements
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slice.is_empty()
+    }
+
+    /// Get a reference to an element, or `None` if the index is out of
+    /// range
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&'a T> {
+        self.slice.get(index)
+    }
+
+    /// Iterate over the elements
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'a, T> {
+        self.slice.iter()
+    }
+
+    /// Divide into two views at an index.  Panics if `mid > len`.
+    #[inline]
+    pub fn split_at(&self, mid: usize) -> (SliceView<'a, T>, SliceView<'a, T>) {
+        let (a, b) = self.slice.split_at(mid);
+        (SliceView::new(a), SliceView::new(b))
+    }
+
+    /// Get the elements as a plain slice
+    #[inline]
+    pub fn as_slice(&self) -> &'a [T] {
+        self.slice
+    }
+}
+
+impl<'a, T> Clone for SliceView<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for SliceView<'a, T> {}
+
+impl<'a, T, I: SliceIndex<[T]>> Index<I> for SliceView<'a, T> {
+    type Output = I::Output;
+
+    #[inline]
+    fn index(&self, index: I) -> &I::Output {
+        &self.slice[index]
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for SliceView<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.slice.fmt(f)
+    }
+}
+
+impl<'a, T> IntoIterator for SliceView<'a, T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.iter()
+    }
+}
+
+// Methods shared by the two mutable views, which both reach their
+// elements through `as_slice` and `as_mut_slice`
+macro_rules! slice_view_mut_methods {
+    () => {
+        /// Return the number of elements
+        #[inline]
+        pub fn len(&self) -> usize {
+            self.as_slice().len()
+        }
+
+        /// Test whether there are no elements
+        #[inline]
+        pub fn is_empty(&self) -> bool {
+            self.as_slice().is_empty()
+        }
+
+        /// Iterate over the elements
+        #[inline]
+        pub fn iter(&self) -> slice::Iter<'_, T> {
+            self.as_slice().iter()
+        }
+
+        /// Iterate over the elements mutably
+        #[inline]
+        pub fn iter_mut(&mut self) -> slice::IterMut<'_, T> {
+            self.as_mut_slice().iter_mut()
+        }
+
+        /// Swap two elements.  Panics if either index is out of range.
+        #[inline]
+        pub fn swap(&mut self, a: usize, b: usize) {
+            self.as_mut_slice().swap(a, b);
+        }
+
+        /// Divide into two mutable views at an index, which may be
+        /// written independently.  Panics if `mid > len`.
+        #[inline]
+        pub fn split_at_mut(&mut self, mid: usize) -> (SliceViewMut<'_, T>, SliceViewMut<'_, T>) {
+            let (a, b) = self.as_mut_slice().split_at_mut(mid);
+            (SliceViewMut::new(a), SliceViewMut::new(b))
+        }
+    };
+}
+
+/// Mutable view onto the contents of a cell holding a slice, `Vec`,
+/// boxed slice or array, as returned by the `slice_view_mut` call on
+/// any of the owners.
+///
+/// This supports indexing, by position or by range, for reading and
+/// writing, so that array-style code reads naturally.  The owner
+/// stays mutably borrowed for as long as the view exists.  Use
+/// [`SliceViewMut::split_at_mut`] to write two parts independently.
+///
+/// ```
+///# use qcell::LCellOwner;
+/// LCellOwner::scope(|mut owner| {
+///     let cell = owner.cell([1, 2, 3, 4]);
+///     let mut view = owner.slice_view_mut(&cell);
+///     view[0] = view[1] + view[2];
+///     let (mut left, mut right) = view.split_at_mut(2);
+///     left[1] = 10;
+///     right[0] += left[0];
+///     assert_eq!(*owner.ro(&cell), [5, 10, 8, 4]);
+/// });
+/// ```
+///
+/// [`SliceViewMut::split_at_mut`]: struct.SliceViewMut.html#method.split_at_mut
+pub struct SliceViewMut<'a, T> {
+    slice: &'a mut [T],
+}
+
+impl<'a, T> SliceViewMut<'a, T> {
+    #[inline]
+    pub(crate) fn new(slice: &'a mut [T]) -> Self {
+        Self { slice }
+    }
+
+    /// Get the elements as a plain slice
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        self.slice
+    }
+
+    /// Get the elements as a plain mutable slice
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.slice
+    }
+
+    slice_view_mut_methods!();
+}
+
+impl<'a, T, I: SliceIndex<[T]>> Index<I> for SliceViewMut<'a, T> {
+    type Output = I::Output;
+
+    #[inline]
+    fn index(&self, index: I) -> &I::Output {
+        &self.slice[index]
+    }
+}
+
+impl<'a, T, I: SliceIndex<[T]>> IndexMut<I> for SliceViewMut<'a, T> {
+    #[inline]
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        &mut self.slice[index]
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for SliceViewMut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.slice.fmt(f)
+    }
+}
+
+/// Mutable view onto the contents of a cell holding a `Vec`, as
+/// returned by the `view_mut` call on any of the owners.
+///
+/// This has the same indexing and methods as [`SliceViewMut`], and
+/// can also add and remove elements.  The owner stays mutably
+/// borrowed for as long as the view exists.
+///
+/// ```
+///# use qcell::{QCell, QCellOwner};
+/// let mut owner = QCellOwner::new();
+/// let grid = QCell::new(&owner, vec![0; 4]);
+/// let mut view = owner.view_mut(&grid);
+/// for i in 1..view.len() {
+///     view[i] = view[i - 1] + i;
+/// }
+/// view.swap(0, 3);
+/// view.push(100);
+/// assert_eq!(*owner.ro(&grid), [6, 1, 3, 0, 100]);
+/// ```
+///
+/// [`SliceViewMut`]: struct.SliceViewMut.html
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub struct VecViewMut<'a, T> {
+    vec: &'a mut Vec<T>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T> VecViewMut<'a, T> {
+    #[inline]
+    pub(crate) fn new(vec: &'a mut Vec<T>) -> Self {
+        Self { vec }
+    }
+
+    /// Get the elements as a plain slice
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        self.vec
+    }
+
+    /// Get the elements as a plain mutable slice
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.vec
+    }
+
+    slice_view_mut_methods!();
+
+    /// Append an element
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.vec.push(value);
+    }
+
+    /// Remove the last element and return it, or `None` if empty
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.vec.pop()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T, I: SliceIndex<[T]>> Index<I> for VecViewMut<'a, T> {
+    type Output = I::Output;
+
+    #[inline]
+    fn index(&self, index: I) -> &I::Output {
+        &self.vec[index]
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T, I: SliceIndex<[T]>> IndexMut<I> for VecViewMut<'a, T> {
+    #[inline]
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        &mut self.vec[index]
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: fmt::Debug> fmt::Debug for VecViewMut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.vec.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LCellOwner, QCell, QCellOwnerSeq};
+
+    #[test]
+    fn view_index_and_range() {
+        // Safety: No ID collisions are being exploited
+        let owner = unsafe { QCellOwnerSeq::new() };
+        let arr = owner.cell([1, 2, 3, 4]);
+        let view = owner.view(&arr);
+        assert_eq!(view.len(), 4);
+        assert_eq!(view[3], 4);
+        assert_eq!(view[1..3], [2, 3]);
+        assert_eq!(view.get(4), None);
+        let (a, b) = view.split_at(1);
+        assert_eq!((a.as_slice(), b.len()), (&[1][..], 3));
+        assert_eq!(view.into_iter().copied().max(), Some(4));
+        assert_eq!(format!("{:?}", view), "[1, 2, 3, 4]");
+    }
+
+    #[test]
+    fn view_mut_indexed_writes() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell: QCell<Box<[u32]>> = owner.cell(Box::new([1, 2, 3]));
+        let mut view = owner.slice_view_mut(&cell);
+        view[0] = view[1] + view[2];
+        view[1..].copy_from_slice(&[7, 8]);
+        view.swap(1, 2);
+        for v in view.iter_mut() {
+            *v *= 2;
+        }
+        assert_eq!(owner.ro(&cell)[..], [10, 16, 14]);
+    }
+
+    #[test]
+    fn view_mut_split_disjoint() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = owner.cell([0; 6]);
+        let mut view = owner.slice_view_mut(&cell);
+        let (mut left, mut right) = view.split_at_mut(3);
+        for i in 0..3 {
+            left[i] = i;
+            right[i] = left[i] + 10;
+        }
+        assert!(!left.is_empty() && right.len() == 3);
+        assert_eq!(*owner.ro(&cell), [0, 1, 2, 10, 11, 12]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn view_mut_out_of_bounds() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = owner.cell([0; 2]);
+        owner.slice_view_mut(&cell)[2] = 1;
+    }
+
+    #[test]
+    #[should_panic]
+    fn view_wrong_owner() {
+        // Safety: No ID collisions are being exploited
+        let owner1 = unsafe { QCellOwnerSeq::new() };
+        // Safety: No ID collisions are being exploited
+        let owner2 = unsafe { QCellOwnerSeq::new() };
+        let cell = owner1.cell([0; 2]);
+        let _ = owner2.view(&cell);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn view_mut_vec_grow() {
+        // Safety: No ID collisions are being exploited
+        let mut owner = unsafe { QCellOwnerSeq::new() };
+        let cell = owner.cell(vec![1]);
+        let mut view = owner.view_mut(&cell);
+        view.push(2);
+        view.push(view[0] + view[1]);
+        assert_eq!(view.pop(), Some(3));
+        let (mut a, b) = view.split_at_mut(1);
+        a[0] = b[0] * 5;
+        assert_eq!(format!("{:?}", view), "[10, 2]");
+        assert_eq!(owner.view(&cell).as_slice(), [10, 2]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn view_pinned() {
+        use crate::QCellOwnerPinned;
+        use core::pin::Pin;
+        let mut owner = Box::pin(QCellOwnerPinned::new());
+        let cell = owner.as_ref().cell(vec![1, 2]);
+        Pin::as_mut(&mut owner).view_mut(&cell)[0] = 5;
+        Pin::as_mut(&mut owner).slice_view_mut(&cell)[1] += 1;
+        assert_eq!(owner.as_ref().view(&cell)[..], [5, 3]);
+    }
+
+    #[test]
+    fn view_lcell() {
+        LCellOwner::scope(|mut owner| {
+            let cell = owner.cell([1, 2, 3]);
+            let mut view = owner.slice_view_mut(&cell);
+            view[2] = view[0] + view[1];
+            assert_eq!(owner.view(&cell)[2], 3);
+        });
+    }
+
+    #[cfg(all(feature = "alloc", any(feature = "std", feature = "exclusion-set")))]
+    #[test]
+    fn view_tcell() {
+        use crate::{TCell, TCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TCellOwner::<Marker>::new();
+        let cell = TCell::new(vec![1, 2]);
+        owner.view_mut(&cell)[1] = 4;
+        owner.slice_view_mut(&cell).swap(0, 1);
+        assert_eq!(owner.view(&cell)[..], [4, 1]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn view_tlcell() {
+        use crate::{TLCell, TLCellOwner};
+        crate::marker!(struct Marker;);
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::new(vec![1, 2]);
+        owner.view_mut(&cell).push(3);
+        owner.slice_view_mut(&cell)[0] = 9;
+        assert_eq!(owner.view(&cell)[..], [9, 2, 3]);
+    }
+}
@@ -0,0 +1,663 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem;
+
+use crate::panic_messages;
+use crate::ro_slice::{writer_check, RoSlice, ValuePtr};
+use crate::sound;
+
+/// Source of owner IDs for [`XCell`] and [`XCellOwner`].
+///
+/// [`QCell`] uses IDs chosen by this crate.  An application which
+/// already has its own system of unforgeable tokens can instead
+/// implement this trait to have the cells checked against those
+/// tokens, so that the same token governs both the application's
+/// resources and the cells.  The scheme is just a type, usually a
+/// unit struct, and each [`XCellOwner`] gets its ID by calling
+/// [`OwnerIdScheme::new_id`] once, when it is created.
+///
+/// ```
+///# use qcell::{OwnerIdScheme, XCell, XCellOwner};
+///# use std::sync::atomic::{AtomicU64, Ordering};
+/// static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+///
+/// struct Tokens;
+///
+/// // Safety: Each call returns a different number, and u64 equality
+/// // is an equivalence.  The counter won't wrap in practice.
+/// unsafe impl OwnerIdScheme for Tokens {
+///     type Id = u64;
+///     fn new_id() -> u64 {
+///         NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+///     }
+/// }
+///
+/// let mut owner = XCellOwner::<Tokens>::new();
+/// let token = owner.id();  // Use this to guard other resources too
+/// let cell = XCell::<Tokens, _>::new(token, 100);
+/// *owner.rw(&cell) += 1;
+/// assert_eq!(*owner.ro(&cell), 101);
+/// ```
+///
+/// # Safety
+///
+/// The soundness of [`XCell`] depends entirely on no two owners with
+/// equal IDs existing at the same time, since the owner check is the
+/// only thing which stops two owners handing out `&mut` references to
+/// the same cell contents.  So the implementation must ensure that:
+///
+/// - `new_id` never returns an ID equal to one it returned earlier,
+///   at least whilst any owner or cell holding the earlier ID may
+///   still exist.  Equal here means according to `Self::Id`'s `Eq`
+///   implementation.
+///
+/// - `Eq` on `Self::Id` is a true equivalence, and comparing two IDs
+///   gives the same answer every time.  An `eq` which always returns
+///   `true` would make every owner match every cell.
+///
+/// Cells may be created with any ID, including tokens which the
+/// application mints by some other route, since a cell on its own
+/// gives no access.  Only the IDs given to owners need to be unique.
+///
+/// It's fine for IDs to be unique only with overwhelming
+/// probability, for example 128 random bits, in the same way that
+/// [`QCellOwnerSeq::new`] relies on a counter which can only
+/// realistically wrap if it is made to on purpose.
+///
+/// Like [`QCell`], a wrong owner is detected at runtime and causes a
+/// panic, but only if the IDs differ.  If the contract is broken and
+/// two owners get equal IDs, then each will pass the check for the
+/// other's cells, and if both are used to borrow the same cell
+/// mutably, or one mutably and one immutably, the result is
+/// undefined behaviour.  That can only happen through an incorrect
+/// `unsafe impl` of this trait.
+///
+/// [`XCell`]: struct.XCell.html
+/// [`XCellOwner`]: struct.XCellOwner.html
+/// [`QCell`]: struct.QCell.html
+/// [`QCellOwnerSeq::new`]: struct.QCellOwnerSeq.html#method.new
+/// [`OwnerIdScheme::new_id`]: trait.OwnerIdScheme.html#tymethod.new_id
+pub unsafe trait OwnerIdScheme: 'static {
+    /// The type of the owner ID.  This is stored in every cell, so
+    /// should be small.
+    type Id: Copy + Eq + fmt::Debug + Send + Sync + 'static;
+
+    /// Return a new ID, unique as described above
+    fn new_id() -> Self::Id;
+}
+
+#[cold]
+#[inline(never)]
+fn bad_owner_panic() -> ! {
+    panic!("{}", panic_messages::XCELL_INCORRECT_OWNER);
+}
+
+#[cold]
+#[inline(never)]
+fn not_distinct_panic() -> ! {
+    panic!("{}", panic_messages::XCELL_BORROWED_TWICE);
+}
+
+macro_rules! owner_check {
+    ($owner:expr $(, $xc:expr)+) => {
+        $(
+            if $xc.owner != $owner.id {
+                bad_owner_panic();
+            }
+        )+
+    }
+}
+
+macro_rules! distinct_check {
+    ($xc1:expr, $xc2:expr) => {{
+        let xc1 = $xc1 as *const _ as *const () as usize;
+        let xc2 = $xc2 as *const _ as *const () as usize;
+        if xc1 == xc2 {
+            not_distinct_panic();
+        }
+    }};
+    ($xc1:expr, $xc2:expr, $xc3:expr) => {{
+        let xc1 = $xc1 as *const _ as *const () as usize;
+        let xc2 = $xc2 as *const _ as *const () as usize;
+        let xc3 = $xc3 as *const _ as *const () as usize;
+        if xc1 == xc2 || xc2 == xc3 || xc3 == xc1 {
+            not_distinct_panic();
+        }
+    }};
+}
+
+/// Cell whose contents is owned (for borrowing purposes) by an
+/// [`XCellOwner`] with IDs from the [`OwnerIdScheme`] `S`.
+///
+/// This works the same way as [`QCell`], except that the owner ID is
+/// whatever type the scheme uses.  See [`OwnerIdScheme`] for an
+/// example.
+///
+/// [`XCellOwner`]: struct.XCellOwner.html
+/// [`OwnerIdScheme`]: trait.OwnerIdScheme.html
+/// [`QCell`]: struct.QCell.html
+pub struct XCell<S: OwnerIdScheme, T: ?Sized> {
+    owner: S::Id,
+    value: UnsafeCell<T>,
+}
+
+// Same reasoning as for `QCell`: Sync requires T: Send + Sync because
+// a &XCell<S, T> plus the owner gives a &mut T on another thread
+unsafe impl<S: OwnerIdScheme, T: Send + Sync + ?Sized> Sync for XCell<S, T> {}
+
+impl<S: OwnerIdScheme, T: ?Sized> ValuePtr for XCell<S, T> {
+    type Value = T;
+    fn value_ptr(&self) -> *const T {
+        self.value.get()
+    }
+}
+
+impl<S: OwnerIdScheme, T> XCell<S, T> {
+    /// Create a new [`XCell`] owned for borrowing purposes by the
+    /// owner with the given ID.  Any ID may be used, but only an
+    /// owner with an equal ID can access the contents.
+    ///
+    /// [`XCell`]: struct.XCell.html
+    #[inline]
+    pub fn new(owner: S::Id, value: T) -> XCell<S, T> {
+        XCell {
+            owner,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Destroy the cell and return the contained value
+    ///
+    /// Safety: Since this consumes the cell, there can be no other
+    /// references to the cell or the data at this point.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<S: OwnerIdScheme, T: ?Sized> XCell<S, T> {
+    /// Get the ID of the owner of this cell
+    #[inline]
+    pub fn owner_id(&self) -> S::Id {
+        self.owner
+    }
+
+    /// Convenience method to borrow a cell immutably.  Equivalent to
+    /// [`XCellOwner::ro`].
+    ///
+    /// [`XCellOwner::ro`]: struct.XCellOwner.html#method.ro
+    #[inline]
+    pub fn ro<'a>(&'a self, owner: &'a XCellOwner<S>) -> &'a T {
+        owner.ro(self)
+    }
+
+    /// Convenience method to borrow a cell mutably.  Equivalent to
+    /// [`XCellOwner::rw`].
+    ///
+    /// [`XCellOwner::rw`]: struct.XCellOwner.html#method.rw
+    #[inline]
+    pub fn rw<'a>(&'a self, owner: &'a mut XCellOwner<S>) -> &'a mut T {
+        owner.rw(self)
+    }
+
+    /// Returns a mutable reference to the underlying data
+    ///
+    /// Note that this is only useful at the beginning-of-life or
+    /// end-of-life of the cell when you have exclusive access to it.
+    /// Normally you'd use [`XCell::rw`] or [`XCellOwner::rw`] to get a
+    /// mutable reference to the contents of the cell.
+    ///
+    /// Safety: This call borrows `XCell` mutably which guarantees
+    /// that we possess the only reference.  This means that there can
+    /// be no active borrows of other forms, even ones obtained using
+    /// an immutable reference.
+    ///
+    /// [`XCell::rw`]: struct.XCell.html#method.rw
+    /// [`XCellOwner::rw`]: struct.XCellOwner.html#method.rw
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Borrowing-owner of zero or more [`XCell`] instances, with its ID
+/// from the [`OwnerIdScheme`] `S`.
+///
+/// This has the same API as [`QCellOwnerSeq`], but the ID comes from
+/// the scheme.  The owner can't be cloned, so the only way to get two
+/// owners with equal IDs is through an incorrect implementation of
+/// the scheme.  See [`OwnerIdScheme`] for an example.
+///
+/// [`XCell`]: struct.XCell.html
+/// [`OwnerIdScheme`]: trait.OwnerIdScheme.html
+/// [`QCellOwnerSeq`]: struct.QCellOwnerSeq.html
+pub struct XCellOwner<S: OwnerIdScheme> {
+    id: S::Id,
+}
+
+impl<S: OwnerIdScheme> Default for XCellOwner<S> {
+    fn default() -> Self {
+        XCellOwner::new()
+    }
+}
+
+impl<S: OwnerIdScheme> XCellOwner<S> {
+    /// Create an owner with a new ID from the scheme
+    #[inline]
+    pub fn new() -> Self {
+        Self { id: S::new_id() }
+    }
+
+    /// Get the ID of this owner.  This may be used to create
+    /// [`XCell`] instances without needing a borrow on the owner, or
+    /// to pass on to the rest of the application's token system.
+    ///
+    /// [`XCell`]: struct.XCell.html
+    #[inline]
+    pub fn id(&self) -> S::Id {
+        self.id
+    }
+
+    /// Create a new cell owned by this owner instance.  See also
+    /// [`XCell::new`].
+    ///
+    /// [`XCell::new`]: struct.XCell.html#method.new
+    #[inline]
+    pub fn cell<T>(&self, value: T) -> XCell<S, T> {
+        XCell::new(self.id, value)
+    }
+
+    /// Borrow contents of an [`XCell`] immutably (read-only).  Many
+    /// [`XCell`] instances can be borrowed immutably at the same time
+    /// from the same owner.  Panics if the [`XCell`] is not owned by
+    /// this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn ro<'a, T: ?Sized>(&'a self, xc: &'a XCell<S, T>) -> &'a T {
+        owner_check!(self, xc);
+        // Safety: Owned (owner ID checked above, unique by the scheme's contract), and owner borrowed for 'a
+        unsafe { sound::deref_shared(&xc.value) }
+    }
+
+    /// Borrow contents of an [`XCell`] mutably (read-write).  Only one
+    /// [`XCell`] at a time can be borrowed from the owner using this
+    /// call.  The returned reference must go out of scope before
+    /// another can be borrowed.  Panics if the [`XCell`] is not owned
+    /// by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn rw<'a, T: ?Sized>(&'a mut self, xc: &'a XCell<S, T>) -> &'a mut T {
+        owner_check!(self, xc);
+        // Safety: Owned (owner ID checked above, unique by the scheme's contract), and owner mutably borrowed for 'a
+        unsafe { sound::deref_unique(&xc.value) }
+    }
+
+    /// Borrow contents of two [`XCell`] instances mutably.  Panics if
+    /// the two [`XCell`] instances point to the same memory.  Panics
+    /// if either [`XCell`] is not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn rw2<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        xc1: &'a XCell<S, T>,
+        xc2: &'a XCell<S, U>,
+    ) -> (&'a mut T, &'a mut U) {
+        owner_check!(self, xc1, xc2);
+        distinct_check!(xc1, xc2);
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique2(&xc1.value, &xc2.value) }
+    }
+
+    /// Borrow contents of three [`XCell`] instances mutably.  Panics
+    /// if any pair of [`XCell`] instances point to the same memory.
+    /// Panics if any [`XCell`] is not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn rw3<'a, T: ?Sized, U: ?Sized, V: ?Sized>(
+        &'a mut self,
+        xc1: &'a XCell<S, T>,
+        xc2: &'a XCell<S, U>,
+        xc3: &'a XCell<S, V>,
+    ) -> (&'a mut T, &'a mut U, &'a mut V) {
+        owner_check!(self, xc1, xc2, xc3);
+        distinct_check!(xc1, xc2, xc3);
+        // Safety: Owned (owner ID checked above), Distinct, and owner mutably borrowed
+        unsafe { sound::deref_unique3(&xc1.value, &xc2.value, &xc3.value) }
+    }
+
+    /// Borrow contents of one [`XCell`] mutably, and the contents of
+    /// a slice of [`XCell`] instances immutably.  The readers may
+    /// repeat, but none may be the same cell as the writer.  Panics
+    /// with the position of the first reader that is the same as the
+    /// writer, or if any [`XCell`] is not owned by this
+    /// [`XCellOwner`].  The contents of the readers are fetched
+    /// lazily through the returned [`RoSlice`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    /// [`RoSlice`]: struct.RoSlice.html
+    #[inline]
+    pub fn rw1_ro_slice<'a, T: ?Sized, U: ?Sized>(
+        &'a mut self,
+        w: &'a XCell<S, T>,
+        readers: &'a [&'a XCell<S, U>],
+    ) -> (&'a mut T, RoSlice<'a, U>) {
+        owner_check!(self, w);
+        for r in readers {
+            owner_check!(self, r);
+        }
+        writer_check("XCell", w, readers);
+        // Safety: Owned (owner ID checked above), and writer is not a reader
+        let (w, readers) = unsafe { sound::deref_unique_with_readers(&w.value, readers) };
+        (w, RoSlice::new(readers))
+    }
+
+    /// Copy the contents of an [`XCell`] out, like `Cell::get`.  No
+    /// borrow is held after this returns.  Panics if the [`XCell`] is
+    /// not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn get<T: Copy>(&self, xc: &XCell<S, T>) -> T {
+        *self.ro(xc)
+    }
+
+    /// Clone the contents of an [`XCell`] out.  Panics if the
+    /// [`XCell`] is not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn get_cloned<T: Clone>(&self, xc: &XCell<S, T>) -> T {
+        self.ro(xc).clone()
+    }
+
+    /// Replace the contents of an [`XCell`] with `value`, like
+    /// `Cell::set`, dropping the old value.  Panics if the [`XCell`]
+    /// is not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn set<T>(&mut self, xc: &XCell<S, T>, value: T) {
+        *self.rw(xc) = value;
+    }
+
+    /// Replace the contents of an [`XCell`] with `value`, returning
+    /// the old value, like `Cell::replace`.  Panics if the [`XCell`]
+    /// is not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn replace<T>(&mut self, xc: &XCell<S, T>, value: T) -> T {
+        mem::replace(self.rw(xc), value)
+    }
+
+    /// Replace the contents of an [`XCell`] with the result of
+    /// calling `f` on a copy of the old contents, and return the new
+    /// value.  Panics if the [`XCell`] is not owned by this
+    /// [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn update<T: Copy>(&mut self, xc: &XCell<S, T>, f: impl FnOnce(T) -> T) -> T {
+        let value = self.rw(xc);
+        *value = f(*value);
+        *value
+    }
+
+    /// Borrow the contents of an [`XCell`] mutably alongside some
+    /// other data that is already borrowed mutably, pass both to `f`
+    /// and return its result.  Panics if the [`XCell`] is not owned
+    /// by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn rw_with<'a, T: ?Sized, U: ?Sized, R>(
+        &'a mut self,
+        xc: &'a XCell<S, T>,
+        other: &'a mut U,
+        f: impl FnOnce(&mut T, &mut U) -> R,
+    ) -> R {
+        f(self.rw(xc), other)
+    }
+
+    /// Same as `rw_with`, but borrows two [`XCell`] instances
+    /// alongside the other data.  Panics if the two are the same
+    /// cell, like `rw2`.  Also panics if either is not owned by this
+    /// [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn rw2_with<'a, T: ?Sized, U: ?Sized, V: ?Sized, R>(
+        &'a mut self,
+        xc1: &'a XCell<S, T>,
+        xc2: &'a XCell<S, U>,
+        other: &'a mut V,
+        f: impl FnOnce(&mut T, &mut U, &mut V) -> R,
+    ) -> R {
+        let (v1, v2) = self.rw2(xc1, xc2);
+        f(v1, v2, other)
+    }
+
+    /// Borrow the contents of an [`XCell`] immutably, pass them to `f`
+    /// and return its result.  The result can't borrow from the cell,
+    /// so the owner is free again once this returns, and the result
+    /// can be matched on with arms that borrow the owner mutably.
+    /// Matching on the reference from `ro` directly keeps the owner
+    /// borrowed in any arm which uses the bindings.  Panics if the
+    /// [`XCell`] is not owned by this [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn inspect<T: ?Sized, D>(
+        &self,
+        xc: &XCell<S, T>,
+        f: impl FnOnce(&T) -> D,
+    ) -> D {
+        f(self.ro(xc))
+    }
+
+    /// Same as `inspect`, but borrows two [`XCell`] instances, which
+    /// may be the same cell.  Panics if either is not owned by this
+    /// [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn inspect2<T: ?Sized, U: ?Sized, D>(
+        &self,
+        xc1: &XCell<S, T>,
+        xc2: &XCell<S, U>,
+        f: impl FnOnce(&T, &U) -> D,
+    ) -> D {
+        f(self.ro(xc1), self.ro(xc2))
+    }
+
+    /// Read the contents of an [`XCell`] with `decide`, and if it returns
+    /// `Some`, borrow the contents mutably and pass them to `apply`
+    /// along with the decision.  Returns true if `apply` was called.
+    /// The immutable borrow has ended before the mutable borrow is
+    /// made, so the read-then-maybe-write cycle needs no intermediate
+    /// binding.  Panics if the [`XCell`] is not owned by this
+    /// [`XCellOwner`].
+    ///
+    /// [`XCell`]: struct.XCell.html
+    /// [`XCellOwner`]: struct.XCellOwner.html
+    #[inline]
+    pub fn modify_if<T: ?Sized, D>(
+        &mut self,
+        xc: &XCell<S, T>,
+        decide: impl FnOnce(&T) -> Option<D>,
+        apply: impl FnOnce(&mut T, D),
+    ) -> bool {
+        match decide(self.ro(xc)) {
+            Some(decision) => {
+                apply(self.rw(xc), decision);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod tests {
+    use super::{OwnerIdScheme, XCell, XCellOwner};
+    use std::collections::hash_map::RandomState;
+    use std::collections::HashSet;
+    use std::hash::{BuildHasher, Hasher};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    // An alternative scheme, as an embedder's token system might
+    // provide: 128-bit random tokens.  The standard library's
+    // `RandomState` is randomly seeded, and a counter is mixed in so
+    // that successive tokens differ even from the same seed.
+    pub(crate) struct RandomTokens;
+
+    static MINTED: AtomicU64 = AtomicU64::new(0);
+
+    fn random_u64(salt: u64) -> u64 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(MINTED.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u64(salt);
+        hasher.finish()
+    }
+
+    // Safety: Two draws of 128 random bits are equal with probability
+    // 2^-128, so collisions can't happen by accident, and `u128`
+    // equality is an equivalence
+    unsafe impl OwnerIdScheme for RandomTokens {
+        type Id = u128;
+        fn new_id() -> u128 {
+            u128::from(random_u64(0)) << 64 | u128::from(random_u64(1))
+        }
+    }
+
+    #[test]
+    fn xcell_random_tokens() {
+        let mut owner = XCellOwner::<RandomTokens>::new();
+        let c1 = XCell::<RandomTokens, _>::new(owner.id(), 100_u32);
+        let c2 = owner.cell(200_u32);
+        let c3 = owner.cell(300_u32);
+        assert_eq!(c1.owner_id(), owner.id());
+
+        *owner.rw(&c1) += 1;
+        *c2.rw(&mut owner) += 2;
+        let (a, b, c) = owner.rw3(&c1, &c2, &c3);
+        std::mem::swap(a, c);
+        *b += 1;
+        assert_eq!(*owner.ro(&c1), 300);
+        assert_eq!(*c2.ro(&owner), 203);
+        assert_eq!(owner.get(&c3), 101);
+
+        owner.set(&c1, 1);
+        assert_eq!(owner.replace(&c1, 2), 1);
+        assert_eq!(owner.update(&c1, |v| v * 10), 20);
+        let mut other = 5;
+        let sum = owner.rw2_with(&c1, &c2, &mut other, |a, b, o| {
+            *o += 1;
+            *a + *b + *o
+        });
+        assert_eq!((sum, other), (229, 6));
+        assert_eq!(owner.rw_with(&c3, &mut other, |c, o| *c + *o), 107);
+
+        let readers = [&c2, &c3, &c2];
+        let (w, r) = owner.rw1_ro_slice(&c1, &readers);
+        *w = r.iter().sum();
+        assert_eq!(owner.get(&c1), 203 + 101 + 203);
+
+        let name = owner.cell(String::from("x"));
+        assert_eq!(owner.get_cloned(&name), "x");
+        assert_eq!(c3.into_inner(), 101);
+    }
+
+    #[test]
+    fn xcell_random_tokens_distinct() {
+        let ids: HashSet<u128> = (0..1000)
+            .map(|_| XCellOwner::<RandomTokens>::new().id())
+            .collect();
+        assert_eq!(ids.len(), 1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn xcell_wrong_owner() {
+        let owner1 = XCellOwner::<RandomTokens>::new();
+        let mut owner2 = XCellOwner::<RandomTokens>::new();
+        let cell = owner1.cell(1);
+        let _ = owner2.rw(&cell); // Panic here
+    }
+
+    #[test]
+    #[should_panic]
+    fn xcell_rw2_same() {
+        let mut owner = XCellOwner::<RandomTokens>::new();
+        let cell = owner.cell(1);
+        let _ = owner.rw2(&cell, &cell); // Panic here
+    }
+
+    #[test]
+    fn xcell_threads() {
+        let mut owner = XCellOwner::<RandomTokens>::new();
+        let shared = Arc::new(owner.cell(vec![1_u32]));
+        let shared2 = shared.clone();
+        let owner = std::thread::spawn(move || {
+            owner.rw(&shared2).push(2);
+            owner
+        })
+        .join()
+        .unwrap();
+        assert_eq!(*owner.ro(&shared), [1, 2]);
+    }
+
+    // A scheme which breaks the contract on purpose, by handing out
+    // the same ID every time
+    struct Broken;
+
+    // Safety: None.  This violates the contract of `OwnerIdScheme`, to
+    // show that only an incorrect `unsafe impl` can defeat the check.
+    unsafe impl OwnerIdScheme for Broken {
+        type Id = u8;
+        fn new_id() -> u8 {
+            7
+        }
+    }
+
+    #[test]
+    fn xcell_broken_scheme() {
+        let owner1 = XCellOwner::<Broken>::new();
+        let owner2 = XCellOwner::<Broken>::new();
+        let cell = owner1.cell(42);
+        // The second owner passes the check for the first owner's
+        // cell.  Only shared borrows are taken here, which is still
+        // sound, but `owner2.rw(&cell)` whilst this borrow was live
+        // would be undefined behaviour.  With a correct scheme, this
+        // would panic as in `xcell_wrong_owner`.
+        let (r1, r2) = (owner1.ro(&cell), owner2.ro(&cell));
+        assert!(std::ptr::eq(r1, r2));
+        // A cell with a different ID is still rejected
+        let foreign = XCell::<Broken, _>::new(8, 0);
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            owner1.ro(&foreign);
+        }))
+        .is_err());
+    }
+}
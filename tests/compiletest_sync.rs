@@ -0,0 +1,23 @@
+//! Checks that the `compile_fail` tests in `trybuild-qcell` match the
+//! `compile_fail` doc-tests they were copied from.  If this fails, run
+//! `cargo xtask sync-compiletests` to update them.
+
+use std::path::Path;
+
+#[path = "../xtask/src/compiletest.rs"]
+#[allow(dead_code)]
+mod compiletest;
+
+#[test]
+fn compiletests_in_sync() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let plan = compiletest::plan(root).unwrap();
+    assert!(
+        plan.is_empty(),
+        "The compile_fail tests in trybuild-qcell are out of date:\n{}\
+         Run `cargo xtask sync-compiletests` to update them, then \
+         `TRYBUILD=overwrite cargo test` in trybuild-qcell to create any \
+         missing .stderr files",
+        plan.report(root)
+    );
+}
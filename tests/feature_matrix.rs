@@ -0,0 +1,273 @@
+//! Checks of the supported feature combinations listed in
+//! `tests/feature_matrix.txt`.
+//!
+//! - `features_doc_matches_list` checks that the table in
+//!   `docs/features.md` was generated from the current list.  Run
+//!   with `UPDATE_FEATURES_DOC=1` set to regenerate it.
+//!
+//! - `feature_matrix` runs the smoke tests in this file once for
+//!   each combination, with the crate built with just those
+//!   features.  This takes a while, so it is ignored by default.  Run
+//!   it with `cargo test --test feature_matrix -- --ignored`.
+//!
+//! - The rest are smoke tests of the owner types that exist with the
+//!   features enabled for this build.
+
+use qcell::{LCell, LCellOwner, QCell, QCellOwnerPinned, QCellOwnerSeq};
+use std::path::Path;
+use std::process::Command;
+use std::{env, fs};
+
+struct Combination {
+    msrv: String,
+    features: String,
+    description: String,
+}
+
+fn combinations() -> Vec<Combination> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/feature_matrix.txt");
+    let list = fs::read_to_string(path).unwrap();
+    list.lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let mut field = || fields.next().expect("too few fields").to_string();
+            let msrv = field();
+            let features = field();
+            Combination {
+                msrv,
+                features: if features == "-" {
+                    String::new()
+                } else {
+                    features
+                },
+                description: field(),
+            }
+        })
+        .collect()
+}
+
+fn features_doc() -> String {
+    let mut doc = String::from(
+        "# Supported feature combinations\n\
+         \n\
+         These are the combinations of cargo features that are built and\n\
+         tested, with the minimum Rust version for each.  Other\n\
+         combinations are expected to work, but aren't tested.\n\
+         \n\
+         Generated from `tests/feature_matrix.txt` by\n\
+         `UPDATE_FEATURES_DOC=1 cargo test --test feature_matrix`.\n\
+         \n\
+         | Features | MSRV | Description |\n\
+         |---|---|---|\n",
+    );
+    for c in combinations() {
+        let features = if c.features.is_empty() {
+            String::from("*(none)*")
+        } else {
+            c.features
+                .split(',')
+                .map(|f| format!("`{}`", f))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        doc.push_str(&format!("| {} | {} | {} |\n", features, c.msrv, c.description));
+    }
+    doc
+}
+
+#[test]
+fn features_doc_matches_list() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("docs/features.md");
+    let expected = features_doc();
+    if env::var_os("UPDATE_FEATURES_DOC").is_some() {
+        fs::write(&path, &expected).unwrap();
+    }
+    let actual = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        actual == expected,
+        "docs/features.md is out of date; rerun with UPDATE_FEATURES_DOC=1 set"
+    );
+}
+
+#[test]
+#[ignore]
+fn feature_matrix() {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
+    let root = env!("CARGO_MANIFEST_DIR");
+    let mut failed = Vec::new();
+    for c in combinations() {
+        eprintln!("=== Features: {}", c.features);
+        let status = Command::new(&cargo)
+            .current_dir(root)
+            .args(["test", "--no-default-features", "--features"])
+            .arg(&c.features)
+            .args(["--test", "feature_matrix"])
+            // Separate from the outer build, which holds its lock
+            .args(["--target-dir", "target/feature-matrix"])
+            .status()
+            .unwrap();
+        if !status.success() {
+            failed.push(c.features);
+        }
+    }
+    assert!(failed.is_empty(), "Failed with features: {:?}", failed);
+}
+
+#[test]
+fn smoke_qcell_owner_seq() {
+    // Safety: No other owner is created with this ID
+    let mut owner = unsafe { QCellOwnerSeq::new() };
+    let c1 = QCell::new(&owner, 1);
+    let c2 = owner.cell(2);
+    let (a, b) = owner.rw2(&c1, &c2);
+    *a += *b;
+    assert_eq!(*owner.ro(&c1), 3);
+}
+
+#[test]
+fn smoke_qcell_owner_pinned() {
+    let mut owner = Box::pin(QCellOwnerPinned::new());
+    let cell = owner.as_ref().cell(1);
+    *owner.as_mut().rw(&cell) += 1;
+    assert_eq!(*owner.as_ref().ro(&cell), 2);
+}
+
+#[test]
+fn smoke_lcell() {
+    LCellOwner::scope(|mut owner| {
+        let cell = LCell::new(1);
+        *owner.rw(&cell) += 1;
+        assert_eq!(*owner.ro(&cell), 2);
+    });
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn smoke_qcell_owner() {
+    let mut owner = qcell::QCellOwner::new();
+    let cell = owner.cell(1);
+    *owner.rw(&cell) += 1;
+    assert_eq!(*owner.ro(&cell), 2);
+}
+
+#[cfg(any(feature = "std", feature = "exclusion-set"))]
+#[test]
+fn smoke_tcell() {
+    use qcell::{marker, TCell, TCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cell = TCell::new(1);
+    *owner.rw(&cell) += 1;
+    assert_eq!(*owner.ro(&cell), 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn smoke_tlcell() {
+    use qcell::{marker, TLCell, TLCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TLCellOwner::<Marker>::new();
+    let cell = TLCell::new(1);
+    *owner.rw(&cell) += 1;
+    assert_eq!(*owner.ro(&cell), 2);
+}
+
+#[cfg(feature = "generativity")]
+#[test]
+fn smoke_lcell_generativity() {
+    qcell::generativity::make_guard!(guard);
+    let mut owner = LCellOwner::new(guard);
+    let cell = owner.cell(1);
+    *owner.rw(&cell) += 1;
+    assert_eq!(*owner.ro(&cell), 2);
+}
+
+#[cfg(feature = "ghost-compat")]
+#[test]
+fn smoke_ghost_compat() {
+    use qcell::ghost_compat::{GhostCell, GhostToken};
+    GhostToken::new(|mut token| {
+        let cell = GhostCell::new(1);
+        *cell.borrow_mut(&mut token) += 1;
+        assert_eq!(*cell.borrow(&token), 2);
+    });
+}
+
+#[cfg(feature = "scoped-threads")]
+#[test]
+fn smoke_scope_chunks() {
+    use qcell::{marker, scope_chunks, TCell, TCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cells: Vec<_> = (0..8).map(TCell::new).collect();
+    scope_chunks(&mut owner, &cells, 3, |_, chunk| {
+        chunk.iter_mut().for_each(|v| *v *= 2);
+    });
+    assert_eq!(*owner.ro(&cells[7]), 14);
+}
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn smoke_diagnostics() {
+    let owner = qcell::QCellOwner::new_registered("smoke");
+    let live = qcell::diagnostics::snapshot();
+    assert!(live.iter().any(|info| info.label.as_deref() == Some("smoke")));
+    drop(owner);
+}
+
+#[cfg(feature = "measure")]
+#[test]
+fn smoke_measure() {
+    use std::rc::Rc;
+    let owner = qcell::QCellOwner::new();
+    let cell = Rc::new(owner.cell(String::with_capacity(16)));
+    let report = owner.measure_graph(vec![cell.clone(), cell], |_, _| ());
+    assert_eq!(report.cells, 1);
+    assert_eq!(report.by_type["alloc::string::String"].heap, 16);
+}
+
+// Measuring is opt-in, so mustn't be enabled by default
+#[test]
+fn measure_not_default() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let manifest = fs::read_to_string(path).unwrap();
+    let default = manifest
+        .lines()
+        .find(|line| line.starts_with("default ="))
+        .expect("no default features");
+    assert!(!default.contains("measure"), "{}", default);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn smoke_par_iter() {
+    use rayon::iter::ParallelIterator;
+    let owner = qcell::QCellOwner::new();
+    let cell = owner.cell((1..=10).collect::<Vec<u32>>());
+    assert_eq!(owner.par_iter(&cell).sum::<u32>(), 55);
+}
+
+#[cfg(feature = "async-tokio")]
+#[tokio::test]
+async fn smoke_async_owner() {
+    let owner = qcell::AsyncOwner::new(qcell::QCellOwner::new());
+    let cell = owner.with_ro(|o| o.cell(1)).await;
+    owner.with_cell(&cell, |v| *v += 1).await;
+    assert_eq!(owner.with_cell(&cell, |v| *v).await, 2);
+}
+
+#[cfg(feature = "ffi-host")]
+#[test]
+fn smoke_ffi_host() {
+    use qcell::ffi_host::{FfiHost, QCellStatus};
+    use std::sync::Arc;
+    let host = FfiHost::new();
+    let cell = Arc::new(QCell::new(host.owner_id(), 1_u64));
+    let handle = host.register(cell.clone());
+    let session = host.open_session();
+    let api = host.api();
+    assert_eq!((api.rw_set_u64)(session, handle, 2), QCellStatus::Ok);
+    assert_eq!(host.close_session(session), None);
+    assert_eq!(host.with_owner(|owner| *owner.ro(&cell)), 2);
+}
@@ -0,0 +1,315 @@
+//! Compile-only checks that the generic APIs can be instantiated with
+//! uninhabited types (`Infallible`, an empty enum), a zero-sized type
+//! (`()`) and, where `T: ?Sized` applies, `dyn Any`.  Generic code
+//! may instantiate cell types for every entry of a type-level list,
+//! including uninhabited marker types, and none of these APIs should
+//! need a `Debug`, `Default` or `Sized` bound that isn't essential to
+//! what they do.  Nothing here is run, since no value of an
+//! uninhabited type exists.
+#![allow(dead_code, unreachable_code, unused_must_use, clippy::needless_pass_by_value)]
+
+use std::any::Any;
+use std::convert::Infallible;
+
+use qcell::{LCell, LCellOwner, QCell, QCellOwnerPinned, QCellOwnerSeq, QCellOwnerSingle, QCellSingle};
+use qcell::{OwnerIdScheme, XCell, XCellOwner};
+#[cfg(feature = "alloc")]
+use qcell::{CellArc, CellPtr, OwnedCells, QCellNoId, QCellOwner, VersionedCell};
+#[cfg(feature = "std")]
+use qcell::{migration::QRefCell, PoisonCell, TCell, TCellOwner, TLCell, TLCellOwner};
+#[cfg(feature = "std")]
+use qcell::{TShardCell, TShardOwner};
+
+use std::pin::Pin;
+
+#[derive(Clone, Copy)]
+enum Never {}
+
+qcell::marker!(struct Marker;);
+
+struct Ids;
+unsafe impl OwnerIdScheme for Ids {
+    type Id = u64;
+    fn new_id() -> u64 {
+        unreachable!()
+    }
+}
+
+// Doesn't do anything, but shows up in the list to prove that this
+// file has compiled
+#[test]
+fn generic_instantiation() {}
+
+// Storage which doesn't need a value of `T`
+fn storage<C>() -> (Option<C>, Vec<C>, Option<Box<C>>) {
+    (None, Vec::new(), Default::default())
+}
+
+// The calls made for every sized `T`, through an owner with the usual
+// `&self`/`&mut self` API.  `$c`, `$d` and `$e` are distinct cells.
+macro_rules! sized_calls {
+    ($owner:ident, $c:ident, $d:ident, $e:ident) => {{
+        $owner.ro($c);
+        $owner.rw($c);
+        $owner.rw2($c, $d);
+        $owner.rw3($c, $d, $e);
+        $owner.rw1_ro_slice($c, &[$d, $e]);
+        $owner.get($c);
+        $owner.get_cloned($c);
+        $owner.set($c, $owner.get($d));
+        $owner.update($c, |v| v);
+        let mut out = [$owner.get($d), $owner.get($e)];
+        $owner.read_into(&[$c, $d], &mut out);
+        $owner.read_into_slice(&[$c, $d], &mut out);
+        $owner.write_from(&[$c, $d], &out);
+        $owner.write_from_slice(&[$c, $d], &out);
+        $owner.rw_with($c, &mut out, |_, _| ());
+        $owner.rw2_with($c, $d, &mut out, |_, _, _| ());
+        qcell::rw!($owner => $c, $d, $e);
+        // Safety: Never run, see above
+        unsafe { qcell::rw_distinct!($owner => &$c, &$d, &$e) };
+    }};
+}
+
+// The calls which work for unsized `T` too
+macro_rules! unsized_calls {
+    ($owner:ident, $c:ident, $d:ident, $e:ident) => {{
+        $owner.ro($c);
+        $owner.rw($c);
+        $owner.rw2($c, $d);
+        $owner.rw3($c, $d, $e);
+        $owner.rw1_ro_slice($c, &[$d, $e]);
+        $owner.rw_with($c, &mut 0, |_, _| ());
+        $owner.rw2_with($c, $d, &mut 0, |_, _, _| ());
+        qcell::rw!($owner => $c, $d, $e);
+        // Safety: Never run, see above
+        unsafe { qcell::rw_distinct!($owner => &$c, &$d, &$e) };
+    }};
+}
+
+macro_rules! instantiate {
+    ($($name:ident: $t:ty;)*) => {$(
+        mod $name {
+            use super::*;
+
+            type T = $t;
+
+            fn qcell_seq(owner: &mut QCellOwnerSeq, c: &QCell<T>, d: &QCell<T>, e: &QCell<T>) {
+                sized_calls!(owner, c, d, e);
+                let v = owner.get(d);
+                owner.replace(c, v);
+                storage::<QCell<T>>();
+            }
+
+            fn qcell_seq_into(mut c: QCell<T>) -> T {
+                c.get_mut();
+                c.into_inner()
+            }
+
+            fn qcell_pinned(mut owner: Pin<&mut QCellOwnerPinned>, c: &QCell<T>, d: &QCell<T>, e: &QCell<T>) {
+                owner.as_ref().ro(c);
+                owner.as_mut().rw(c);
+                owner.as_mut().rw2(c, d);
+                owner.as_mut().rw3(c, d, e);
+                owner.as_ref().get(c);
+                owner.as_ref().get_cloned(c);
+                let v = owner.as_ref().get(d);
+                owner.as_mut().set(c, v);
+                owner.as_mut().replace(c, v);
+            }
+
+            fn qcell_single(owner: &mut QCellOwnerSingle, c: &QCellSingle<T>, d: &QCellSingle<T>, e: &QCellSingle<T>) {
+                owner.ro(c);
+                owner.rw(c);
+                owner.rw2(c, d);
+                owner.rw3(c, d, e);
+                storage::<QCellSingle<T>>();
+            }
+
+            fn xcell(owner: &mut XCellOwner<Ids>, c: &XCell<Ids, T>, d: &XCell<Ids, T>, e: &XCell<Ids, T>) {
+                owner.ro(c);
+                owner.rw(c);
+                owner.rw2(c, d);
+                owner.rw3(c, d, e);
+                owner.rw1_ro_slice(c, &[d, e]);
+                owner.get(c);
+                owner.get_cloned(c);
+                let v = owner.get(d);
+                owner.set(c, v);
+                owner.replace(c, v);
+                owner.update(c, |v| v);
+                owner.rw_with(c, &mut 0, |_, _| ());
+                owner.rw2_with(c, d, &mut 0, |_, _, _| ());
+                storage::<XCell<Ids, T>>();
+            }
+
+            fn lcell<'id>(owner: &mut LCellOwner<'id>, c: &LCell<'id, T>, d: &LCell<'id, T>, e: &LCell<'id, T>) {
+                sized_calls!(owner, c, d, e);
+                storage::<LCell<'id, T>>();
+            }
+
+            #[cfg(feature = "alloc")]
+            fn qcell_owner(owner: &mut QCellOwner, c: &QCell<T>, d: &QCell<T>, e: &QCell<T>) {
+                sized_calls!(owner, c, d, e);
+                let v = owner.get(d);
+                owner.replace(c, v);
+                let cells: Vec<std::rc::Rc<QCell<T>>> = Vec::new();
+                owner.rw_from_slice(&cells);
+                owner.rw_from_iter(cells.iter());
+            }
+
+            #[cfg(feature = "alloc")]
+            fn owned_cells(owner: &mut QCellOwner, cells: &OwnedCells<T>) {
+                owner.ro_at(cells, 0);
+                owner.rw_at(cells, 0);
+                owner.rw2_at(cells, 0, 1);
+                owner.ro_iter(cells).count();
+                owner.rw_iter(cells).count();
+                storage::<OwnedCells<T>>();
+            }
+
+            #[cfg(feature = "alloc")]
+            fn versioned(owner: &mut QCellOwner, c: &VersionedCell<T>) {
+                owner.read_versioned(c);
+                owner.snapshot_versioned(c);
+                owner.rw_versioned(c);
+                let (version, value) = owner.snapshot_versioned(c);
+                owner.try_write_if(c, version, value);
+                storage::<VersionedCell<T>>();
+            }
+
+            #[cfg(feature = "std")]
+            fn tcell(owner: &mut TCellOwner<Marker>, c: &TCell<Marker, T>, d: &TCell<Marker, T>, e: &TCell<Marker, T>) {
+                sized_calls!(owner, c, d, e);
+                storage::<TCell<Marker, T>>();
+            }
+
+            #[cfg(feature = "std")]
+            fn tlcell(owner: &mut TLCellOwner<Marker>, c: &TLCell<Marker, T>, d: &TLCell<Marker, T>, e: &TLCell<Marker, T>) {
+                sized_calls!(owner, c, d, e);
+                storage::<TLCell<Marker, T>>();
+            }
+
+            #[cfg(feature = "std")]
+            fn poison(owner: &mut QCellOwner, c: &PoisonCell<T>) {
+                c.try_ro_poison(owner);
+                c.try_rw_poison(owner);
+                c.with_mut(owner, |_| ());
+                c.is_poisoned(owner);
+                c.clear_poison(owner);
+                storage::<PoisonCell<T>>();
+            }
+        }
+    )*};
+}
+
+instantiate! {
+    infallible: Infallible;
+    never: Never;
+    unit: ();
+}
+
+// Unsized contents, behind a `Box` or reference
+mod dyn_any {
+    use super::*;
+
+    type T = dyn Any;
+
+    fn qcell_seq(owner: &mut QCellOwnerSeq, c: &QCell<T>, d: &QCell<T>, e: &QCell<T>) {
+        unsized_calls!(owner, c, d, e);
+        storage::<Box<QCell<T>>>();
+    }
+
+    fn qcell_pinned(mut owner: Pin<&mut QCellOwnerPinned>, c: &QCell<T>, d: &QCell<T>, e: &QCell<T>) {
+        owner.as_ref().ro(c);
+        owner.as_mut().rw(c);
+        owner.as_mut().rw2(c, d);
+        owner.as_mut().rw3(c, d, e);
+    }
+
+    fn qcell_single(owner: &mut QCellOwnerSingle, c: &QCellSingle<T>, d: &QCellSingle<T>, e: &QCellSingle<T>) {
+        owner.ro(c);
+        owner.rw(c);
+        owner.rw2(c, d);
+        owner.rw3(c, d, e);
+    }
+
+    fn xcell(owner: &mut XCellOwner<Ids>, c: &XCell<Ids, T>, d: &XCell<Ids, T>, e: &XCell<Ids, T>) {
+        owner.ro(c);
+        owner.rw(c);
+        owner.rw2(c, d);
+        owner.rw3(c, d, e);
+        owner.rw1_ro_slice(c, &[d, e]);
+        c.owner_id();
+    }
+
+    fn lcell<'id>(owner: &mut LCellOwner<'id>, c: &LCell<'id, T>, d: &LCell<'id, T>, e: &LCell<'id, T>) {
+        unsized_calls!(owner, c, d, e);
+    }
+
+    fn get_mut(c: &mut QCell<T>, l: &mut LCell<'_, T>, s: &mut QCellSingle<T>, x: &mut XCell<Ids, T>) {
+        c.get_mut();
+        l.get_mut();
+        s.get_mut();
+        x.get_mut();
+    }
+
+    #[cfg(feature = "alloc")]
+    fn qcell_owner(owner: &mut QCellOwner, c: &QCell<T>, d: &QCell<T>, e: &QCell<T>) {
+        unsized_calls!(owner, c, d, e);
+        let cells: Vec<Box<QCell<T>>> = Vec::new();
+        owner.rw_from_slice(&cells);
+        owner.rw_from_iter(cells.iter());
+    }
+
+    #[cfg(feature = "std")]
+    fn tcell(owner: &mut TCellOwner<Marker>, c: &TCell<Marker, T>, d: &TCell<Marker, T>, e: &TCell<Marker, T>) {
+        unsized_calls!(owner, c, d, e);
+    }
+
+    #[cfg(feature = "std")]
+    fn tlcell(owner: &mut TLCellOwner<Marker>, c: &TLCell<Marker, T>, d: &TLCell<Marker, T>, e: &TLCell<Marker, T>) {
+        unsized_calls!(owner, c, d, e);
+    }
+
+    #[cfg(feature = "alloc")]
+    fn qcell_owner_wrappers(owner: &mut QCellOwner, v: &VersionedCell<T>, p: &CellPtr<T>, a: &CellArc<T>) {
+        owner.read_versioned(v);
+        owner.rw_versioned(v);
+        p.ro(owner);
+        p.rw(owner);
+        a.ro(owner);
+        a.rw(owner);
+        storage::<Box<VersionedCell<T>>>();
+    }
+
+    #[cfg(feature = "alloc")]
+    fn noid_get_mut(c: &mut QCellNoId<T>) {
+        c.get_mut();
+    }
+
+    #[cfg(feature = "std")]
+    fn tshard(owner: &mut TShardOwner<Marker>, c: &TShardCell<Marker, T>, d: &TShardCell<Marker, T>) {
+        owner.ro(c);
+        owner.rw(c);
+        owner.rw2(c, d);
+        c.shard();
+    }
+
+    #[cfg(feature = "std")]
+    fn qrefcell(c: &mut QRefCell<T>) {
+        c.borrow();
+        c.borrow_mut();
+        c.inner();
+        c.get_mut();
+    }
+
+    #[cfg(feature = "std")]
+    fn poison(owner: &mut QCellOwner, c: &PoisonCell<T>) {
+        c.try_ro_poison(owner);
+        c.try_rw_poison(owner);
+        c.with_mut(owner, |_| ());
+        c.is_poisoned(owner);
+        c.clear_poison(owner);
+    }
+}
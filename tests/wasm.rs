@@ -0,0 +1,62 @@
+//! Tests for the single-threaded `wasm32-unknown-unknown` target.
+//!
+//! Run with `./run-test-wasm`, which needs the target installed and
+//! `wasm-bindgen-test-runner` on the `PATH` (from `cargo install
+//! wasm-bindgen-cli`).
+
+#![cfg(target_arch = "wasm32")]
+
+use qcell::{marker, QCellOwner, TCell, TCellOwner, TLCell, TLCellOwner};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn qcell_owner_cycles() {
+    for i in 0..100 {
+        let mut owner = QCellOwner::new();
+        let c1 = owner.cell(i);
+        let c2 = owner.cell(i * 2);
+        let (v1, v2) = owner.rw2(&c1, &c2);
+        *v1 += *v2;
+        assert_eq!(*owner.ro(&c1), i * 3);
+    }
+}
+
+#[wasm_bindgen_test]
+fn tcell_owner_cycles() {
+    marker!(struct Marker;);
+    for i in 0..100 {
+        let mut owner = TCellOwner::<Marker>::new();
+        let cell = TCell::new(i);
+        *owner.rw(&cell) += 1;
+        assert_eq!(*owner.ro(&cell), i + 1);
+    }
+}
+
+#[wasm_bindgen_test]
+fn tcell_try_new() {
+    marker!(struct Marker;);
+    let owner = TCellOwner::<Marker>::try_new();
+    assert!(owner.is_some());
+    assert!(TCellOwner::<Marker>::try_new().is_none());
+    drop(owner);
+    assert!(TCellOwner::<Marker>::try_new().is_some());
+}
+
+#[wasm_bindgen_test]
+#[should_panic]
+fn tcell_double_new() {
+    marker!(struct Marker;);
+    let _owner1 = TCellOwner::<Marker>::new();
+    let _owner2 = TCellOwner::<Marker>::new();
+}
+
+#[wasm_bindgen_test]
+fn tlcell_owner_cycles() {
+    marker!(struct Marker;);
+    for i in 0..100 {
+        let mut owner = TLCellOwner::<Marker>::new();
+        let cell = TLCell::new(i);
+        *owner.rw(&cell) += 1;
+        assert_eq!(*owner.ro(&cell), i + 1);
+    }
+}
@@ -0,0 +1,9 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    struct Marker;
+    type ACellOwner = TCellOwner<Marker>;
+    let mut owner = ACellOwner::new();  // Compile fail
+}
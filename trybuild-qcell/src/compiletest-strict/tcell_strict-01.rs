@@ -0,0 +1,7 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    let mut owner = TCellOwner::<std::string::String>::new();  // Compile fail
+}
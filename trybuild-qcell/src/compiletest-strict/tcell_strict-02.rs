@@ -0,0 +1,9 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TLCell, TLCellOwner};
+    struct Marker;
+    type ACellOwner = TLCellOwner<Marker>;
+    let mut owner = ACellOwner::new();  // Compile fail
+}
@@ -0,0 +1,7 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::marker;
+    marker!(pub struct Marker;);  // Compile fail
+}
@@ -0,0 +1,7 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::marker;
+    marker!(struct Marker(u32););  // Compile fail
+}
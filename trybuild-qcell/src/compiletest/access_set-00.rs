@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{access_set, assert_compatible, marker, TCell};
+    marker!(struct World;);
+    static POSITION: TCell<World, f32> = TCell::new(0.0);
+    access_set! {
+        struct Movement { write: [POSITION] }
+        struct Teleport { write: [POSITION] }
+    }
+    assert_compatible!(Movement, Teleport);  // Compile fail
+}
@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{access_set, assert_compatible, marker, TCell};
+    marker!(struct World;);
+    static POSITION: TCell<World, f32> = TCell::new(0.0);
+    static VELOCITY: TCell<World, f32> = TCell::new(0.0);
+    access_set! {
+        struct Movement { read: [VELOCITY], write: [POSITION] }
+        struct Render { read: [POSITION] }
+    }
+    assert_compatible!(Movement, Render);  // Compile fail
+}
@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{access_set, marker, TCell};
+    marker!(struct World;);
+    static POSITION: TCell<World, f32> = TCell::new(0.0);
+    access_set! {
+        struct Movement { write: [POSITON] }  // Compile fail
+    }
+}
@@ -0,0 +1,15 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{AsyncOwner, QCellOwner};
+    #[tokio::main(flavor = "current_thread")]
+    async fn main() {
+    let owner = AsyncOwner::new(QCellOwner::new());
+    let cell = owner.with_ro(|o| o.cell(100u32)).await;
+    let value = owner.with_ro(|o| async move {  // Compile fail
+        tokio::task::yield_now().await;
+        *o.ro(&cell)
+    }).await.await;
+    }
+}
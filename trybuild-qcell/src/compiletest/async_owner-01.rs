@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{AsyncOwner, QCellOwner};
+    #[tokio::main(flavor = "current_thread")]
+    async fn main() {
+    let owner = AsyncOwner::new(QCellOwner::new());
+    let cell = owner.with_ro(|o| o.cell(100u32)).await;
+    let value = owner.with_ro(|o| o.ro(&cell)).await;  // Compile fail
+    tokio::task::yield_now().await;
+    assert_eq!(*value, 100);
+    }
+}
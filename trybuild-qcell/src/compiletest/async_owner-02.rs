@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{AsyncOwner, QCellOwner};
+    #[tokio::main(flavor = "current_thread")]
+    async fn main() {
+    let owner = AsyncOwner::new(QCellOwner::new());
+    let escaped = owner.with_rw(|o| o).await;  // Compile fail
+    }
+}
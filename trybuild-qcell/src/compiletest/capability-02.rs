@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let mut owner = QCellOwner::new();
+    let cell = QCell::new(&owner, 1);
+    let wo = owner.capability::<false, true>();
+    let old = wo.get(&cell);  // Compile fail
+}
@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let mut owner = QCellOwner::new();
+    let cell = QCell::new(&owner, 1);
+    let mut ro = owner.capability::<true, false>();
+    ro.set(&cell, 2);  // Compile fail
+}
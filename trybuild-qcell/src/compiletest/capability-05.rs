@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TCell, TCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cell = TCell::new(1);
+    let wo = owner.capability::<false, true>();
+    assert_eq!(*wo.ro(&cell), 1);  // Compile fail
+}
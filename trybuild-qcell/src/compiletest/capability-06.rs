@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::LCellOwner;
+    LCellOwner::scope(|mut owner| {
+        let cell = owner.cell(1);
+        let mut wo = owner.capability::<false, true>();
+        *wo.rw(&cell) += 1;  // Compile fail
+    });
+}
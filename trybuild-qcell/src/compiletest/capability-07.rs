@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let mut owner = QCellOwner::new();
+    let cell = QCell::new(&owner, 1);
+    let mut wo = owner.capability::<false, true>();
+    let value = owner.ro(&cell);  // Compile fail
+    wo.set(&cell, 2);
+}
@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{CellArena, QCellOwner};
+    let owner = QCellOwner::new();
+    let arena = CellArena::new(&owner);
+    let cell = arena.alloc(1);
+    let moved = arena;  // Compile fail
+    assert_eq!(*owner.ro(&cell), 1);
+}
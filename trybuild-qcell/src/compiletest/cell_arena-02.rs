@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{ArenaRef, CellArena, QCellOwner};
+    fn make(owner: &QCellOwner) -> ArenaRef<'static, u32> {
+        let arena = CellArena::new(owner);
+        arena.alloc(1)  // Compile fail
+    }
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{ArenaRef, CellArena, QCellOwner};
+    let owner = QCellOwner::new();
+    let mut saved: Vec<ArenaRef<'_, u32>> = Vec::new();
+    {
+        let arena = CellArena::new(&owner);
+        saved.push(arena.alloc(1));  // Compile fail
+    }
+    assert_eq!(*owner.ro(&saved[0]), 1);
+}
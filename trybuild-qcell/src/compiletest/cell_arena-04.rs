@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{CellArena, QCellOwner};
+    let owner = QCellOwner::new();
+    let arena = CellArena::<u32>::new(&owner);
+    crossbeam::scope(|s| {
+        s.spawn(|_| arena.alloc(1));  // Compile fail
+    }).unwrap();
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCellArena, LCellOwner};
+    let mut saved = None;
+    LCellOwner::scope(|owner| {
+        let arena = LCellArena::new();
+        assert_eq!(*owner.ro(arena.alloc(1)), 1);
+        saved = Some(arena);  // Compile fail
+    });
+}
@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCellArena, LCellOwner};
+    LCellOwner::scope(|owner1| {
+        let arena = LCellArena::new();
+        let cell = arena.alloc(1);
+        assert_eq!(*owner1.ro(cell), 1);
+        LCellOwner::scope(|owner2| {
+            assert_eq!(*owner2.ro(cell), 1);  // Compile fail
+        });
+    });
+}
@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCellArena, LCellOwner};
+    LCellOwner::scope(|owner| {
+        let cell;
+        {
+            let arena = LCellArena::new();
+            cell = arena.alloc(1);  // Compile fail
+        }
+        assert_eq!(*owner.ro(cell), 1);
+    });
+}
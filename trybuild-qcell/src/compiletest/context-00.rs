@@ -0,0 +1,19 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{context, QCellOwner};
+    context! {
+        struct Ctx {
+            owner: QCellOwner,
+            cells: CtxCells {
+                a: u32 => ro_a, rw_a;
+                b: u32 => ro_b, rw_b;
+            }
+        }
+    }
+    let mut ctx = Ctx::new(QCellOwner::new(), 1, 2);
+    let a = ctx.rw_a();
+    let b = ctx.rw_b();  // Compile fail
+    std::mem::swap(a, b);
+}
@@ -0,0 +1,18 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{context, QCellOwner};
+    context! {
+        struct Ctx {
+            owner: QCellOwner,
+            cells: CtxCells {
+                a: u32 => ro_a, rw_a;
+                b: u32 => ro_b, rw_b;
+            }
+            pairs {
+                rw_a_and_a(a, a);  // Compile fail
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{context, QCellOwner};
+    context! {
+        struct Ctx {
+            owner: QCellOwner,
+            cells: CtxCells {
+                a: u32 => ro_a, rw_a;
+            }
+            pairs {
+                rw_a_and_c(a, c);  // Compile fail
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{global, QCellOwner};
+    global::with_owner(|owner| std::mem::replace(&mut **owner, QCellOwner::new()));  // Compile fail
+}
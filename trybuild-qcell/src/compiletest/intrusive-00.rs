@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::intrusive::{Node, PinnedListHeader};
+    use pin_utils::pin_mut;
+    let list = PinnedListHeader::new();
+    pin_mut!(list);
+    let node = list.as_ref().node(100);
+    list.as_ref().push_back(&node);  // Compile fail
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::intrusive::{Node, PinnedListHeader};
+    use pin_utils::pin_mut;
+    let node: Node<u32>;
+    let list = PinnedListHeader::new();
+    pin_mut!(list);
+    node = list.as_ref().node(100);
+    list.as_ref().push_back(&node);
+    let moved = node;  // Compile fail
+}
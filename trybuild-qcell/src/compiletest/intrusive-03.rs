@@ -0,0 +1,9 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::intrusive::{Node, PinnedListHeader};
+    let node: Node<u32>;
+    let list = PinnedListHeader::new();
+    node = list.node(100);  // Compile fail
+}
@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    use std::sync::Arc;
+    let mut owner = QCellOwner::new();
+    let cells: Vec<Arc<QCell<u64>>> = (0..4).map(|n| Arc::new(owner.cell(n))).collect();
+    cells.iter().map(|c| owner.rw(c)).for_each(|n| *n += 1); // Compile fail
+}
@@ -0,0 +1,15 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    fn bump<'id>(owner: &mut LCellOwner<'id>, cell: &LCell<'id, u64>) {
+        owner.update(cell, |n| n + 1);
+    }
+    LCellOwner::scope(|mut owner| {
+        let hits = LCell::new(0u64);
+        let count = owner.rw(&hits);
+        bump(&mut owner, &hits); // Compile fail
+        *count += 1;
+    });
+}
@@ -0,0 +1,15 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let log = LCell::new(vec![1, 2]);
+        let other = LCell::new(3);
+        let mut buf = Vec::new();
+        owner.rw_with(&log, &mut buf, |log, buf| {
+            buf.push(*owner.ro(&other)); // Compile fail
+            buf.append(log);
+        });
+    });
+}
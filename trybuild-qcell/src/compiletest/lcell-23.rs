@@ -0,0 +1,15 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{BrandFamily, Branded, LCell, LCellOwner};
+    struct CellFamily;
+    impl<'id> BrandFamily<'id> for CellFamily {
+        type Of = LCell<'id, u32>;
+    }
+    let cell = LCellOwner::scope_build(
+        1,
+        |owner, n| Branded::<CellFamily>::new(owner.cell(n)),
+        |_, cell| cell, // Compile fail
+    );
+}
@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let cell = LCell::new(1);
+        owner.read_phase(|owner| {
+            std::thread::scope(|s| {
+                s.spawn(|| *owner.rw(&cell) += 1); // Compile fail
+            });
+        });
+    });
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let cell = LCell::new(1);
+        owner.read_phase(|reader| {
+            let value = reader.get(&cell);
+            owner.set(&cell, value + 1); // Compile fail
+        });
+    });
+}
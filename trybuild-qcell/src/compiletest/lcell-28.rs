@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let cell = LCell::new(1);
+        let value = owner.read_phase(|reader| reader.ro(&cell)); // Compile fail
+        *owner.rw(&cell) += 1;
+        assert_eq!(*value, 1);
+    });
+}
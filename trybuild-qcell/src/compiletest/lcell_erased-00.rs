@@ -0,0 +1,16 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    let token = LCellOwner::brand_token();
+    let erased = std::cell::RefCell::new(None);
+    LCellOwner::scope_with_token(token, |owner| {
+        *erased.borrow_mut() = Some(owner.erase(LCell::new(100u32)));
+    });
+    let erased = erased.into_inner().unwrap();
+    let mut escaped = None;
+    LCellOwner::scope_with_token(token, |owner| {
+        escaped = owner.restore(&erased);  // Compile fail
+    });
+}
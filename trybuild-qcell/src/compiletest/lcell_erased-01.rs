@@ -0,0 +1,16 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    let token = LCellOwner::brand_token();
+    let mut erased = None;
+    LCellOwner::scope_with_token(token, |owner| {
+        erased = Some(owner.erase(LCell::new(100u32)));
+    });
+    let mut escaped = None;
+    LCellOwner::scope_with_token(token, |owner| {
+        let cell = owner.restore(erased.as_ref().unwrap()).unwrap();
+        escaped = Some(owner.ro(cell));  // Compile fail
+    });
+}
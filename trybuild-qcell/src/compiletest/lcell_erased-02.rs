@@ -0,0 +1,17 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    let token = LCellOwner::brand_token();
+    let mut erased = None;
+    LCellOwner::scope_with_token(token, |owner| {
+        erased = Some(owner.erase(LCell::new(100u32)));
+    });
+    LCellOwner::scope_with_token(token, |owner1| {
+        let cell = owner1.restore(erased.as_ref().unwrap()).unwrap();
+        LCellOwner::scope(|mut owner2| {
+            *owner2.rw(cell) += 1;  // Compile fail
+        });
+    });
+}
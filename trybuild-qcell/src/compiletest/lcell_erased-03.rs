@@ -0,0 +1,18 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    let token = LCellOwner::brand_token();
+    let mut erased = None;
+    LCellOwner::scope_with_token(token, |owner| {
+        erased = Some(owner.erase(LCell::new(100u32)));
+    });
+    LCellOwner::scope_with_token(token, |mut owner| {
+        let c1 = owner.restore(erased.as_ref().unwrap()).unwrap();
+        let c2 = owner.restore(erased.as_ref().unwrap()).unwrap();
+        let r1 = owner.rw(c1);
+        let r2 = owner.rw(c2);  // Compile fail
+        *r1 += *r2;
+    });
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::QCellOwner;
+    use rayon::iter::ParallelIterator;
+    use std::collections::HashMap;
+    let mut owner = QCellOwner::new();
+    let cell = owner.cell((0..100).map(|i| (i, i * 2)).collect::<HashMap<u32, u32>>());
+    let iter = owner.par_values(&cell);
+    owner.rw(&cell).clear();  // Compile fail
+    assert_eq!(iter.sum::<u32>(), 9900);
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TCell, TCellOwner};
+    use rayon::iter::ParallelIterator;
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cell = TCell::new(vec![1, 2, 3]);
+    let iter = owner.par_iter(&cell);
+    owner.rw(&cell).push(4);  // Compile fail
+    assert_eq!(iter.sum::<i32>(), 6);
+}
@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::QCellOwner;
+    use rayon::iter::ParallelIterator;
+    use std::cell::Cell;
+    let owner = QCellOwner::new();
+    let cell = owner.cell(vec![Cell::new(1)]);
+    owner.par_iter(&cell).for_each(|c| c.set(2));  // Compile fail
+}
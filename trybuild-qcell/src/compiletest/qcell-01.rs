@@ -3,7 +3,11 @@ extern crate qcell;
 #[allow(warnings)]
 fn main() {
     use qcell::{QCell, QCellOwner};
-    use std::rc::Rc;
-    let mut owner1 = QCellOwner::new();
-    let owner2 = owner1.clone();  // Compile fail
+    let id = QCellOwner::new().id();
+    let cell: &'static QCell<i32> = Box::leak(Box::new(QCell::new(id, 1)));
+    let value = {
+        let owner = QCellOwner::new();
+        owner.ro(cell) // Compile fail
+    };
+    assert_eq!(*value, 1);
 }
@@ -2,12 +2,10 @@ extern crate qcell;
 
 #[allow(warnings)]
 fn main() {
-    use qcell::{QCell, QCellOwner};
-    use std::rc::Rc;
-    let mut owner = QCellOwner::new();
-    let c1 = Rc::new(QCell::new(&owner, 100u32));
-    let c2 = Rc::new(QCell::new(&owner, 200u32));
-    let c1ref = owner.ro(&c1);
-    let c1mutref = owner.rw(&c1);    // Compile error
-    println!("{}", *c1ref);
+    use qcell::QCellOwner;
+    let owner = QCellOwner::new();
+    let cell = owner.cell(0_u64);
+    let value = owner.ro(&cell);
+    let transfer = owner.into_transfer(); // Compile fail
+    assert_eq!(*value, 0);
 }
@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let mut owner = QCellOwner::new();
+    let grid = QCell::new(&owner, vec![1, 2, 3]);
+    let scale = QCell::new(&owner, 10);
+    let mut view = owner.view_mut(&grid);
+    view[0] *= *owner.ro(&scale); // Compile fail
+}
@@ -0,0 +1,17 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwnerPinned};
+    use std::pin::Pin;
+    use pin_utils::pin_mut;
+    fn bump(owner: Pin<&mut QCellOwnerPinned>, cell: &QCell<u64>) {
+        owner.update(cell, |n| n + 1);
+    }
+    let mut owner = QCellOwnerPinned::new();
+    pin_mut!(owner);
+    let hits = owner.as_ref().cell(0u64);
+    let count = owner.as_mut().rw(&hits);
+    bump(owner.as_mut(), &hits); // Compile fail
+    *count += 1;
+}
@@ -0,0 +1,16 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwnerPinned};
+    use pin_utils::pin_mut;
+    let mut owner = QCellOwnerPinned::new();
+    pin_mut!(owner);
+    let log = owner.as_ref().cell(vec![1, 2]);
+    let other = owner.as_ref().cell(3);
+    let mut buf = Vec::new();
+    owner.as_mut().rw_with(&log, &mut buf, |log, buf| {
+        buf.push(*owner.as_ref().ro(&other)); // Compile fail
+        buf.append(log);
+    });
+}
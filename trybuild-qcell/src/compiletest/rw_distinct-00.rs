@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{rw_distinct, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let a = owner.cell(1);
+        let b = owner.cell(2);
+        let (a, b) = rw_distinct!(owner => &a, &b);  // Compile fail
+    });
+}
@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{rw_distinct, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let a = owner.cell(1);
+        let (a1, a2) = unsafe { rw_distinct!(owner => &a, &a) };  // Compile fail
+    });
+}
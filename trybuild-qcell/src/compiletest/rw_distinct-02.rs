@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{rw_distinct, LCell, LCellOwner};
+    struct Pair<'id> {
+        a: LCell<'id, u32>,
+        b: LCell<'id, u32>,
+    }
+    LCellOwner::scope(|mut owner| {
+        let pair = Pair { a: owner.cell(1), b: owner.cell(2) };
+        let (a, b) = unsafe { rw_distinct!(owner => &pair.a, &pair.b) };  // Compile fail
+    });
+}
@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{rw_distinct, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let cells = vec![owner.cell(1), owner.cell(2)];
+        let (a, b) = unsafe { rw_distinct!(owner => cells.first().unwrap(), &cells[1]) };  // Compile fail
+    });
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{rw, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let a = owner.cell(1);
+        let b = owner.cell(2);
+        let (ra, rb) = rw!(owner => &a, &b);
+        let c = owner.get(&a);  // Compile fail
+        *ra += *rb;
+    });
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    #![forbid(unsafe_code)]
+    use qcell::{rw_distinct, LCellOwner};
+    LCellOwner::scope(|mut owner| {
+        let a = owner.cell(1);
+        let b = &a;
+        let (a, b) = rw_distinct!(owner => &a, &b);  // Compile fail
+    });
+}
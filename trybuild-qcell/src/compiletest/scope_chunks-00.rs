@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, scope_chunks, TCell, TCellOwner};
+    use std::rc::Rc;
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cells: Vec<_> = (0..10).map(|i| TCell::new(Rc::new(i))).collect();
+    scope_chunks(&mut owner, &cells, 3, |_, _| {});  // Compile fail
+}
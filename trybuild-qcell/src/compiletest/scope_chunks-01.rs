@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, scope_chunks, TCell, TCellOwner};
+    use std::sync::Mutex;
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cells: Vec<_> = (0..10).map(TCell::new).collect();
+    let escaped = Mutex::new(None);
+    scope_chunks(&mut owner, &cells, 3, |_, chunk| {
+        *escaped.lock().unwrap() = Some(chunk);  // Compile fail
+    });
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, scope_chunks, TCell, TCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cells: Vec<_> = (0..10).map(TCell::new).collect();
+    scope_chunks(&mut owner, &cells, 3, |_, _| {
+        let _ = owner.ro(&cells[0]);  // Compile fail
+    });
+}
@@ -0,0 +1,9 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let owner = QCellOwner::new();
+    let cell = QCell::new(&owner, 100);
+    let value: &u32 = &*cell; // Compile fail
+}
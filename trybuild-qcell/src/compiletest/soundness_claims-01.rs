@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let mut owner = QCellOwner::new();
+    let c1 = QCell::new(&owner, 100);
+    let c2 = QCell::new(&owner, 200);
+    let r1 = owner.ro(&c1);
+    let r2 = owner.rw(&c2); // Compile fail
+    assert_eq!(*r1, 100);
+}
@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let owner = QCellOwner::new();
+    let cell = QCell::new(&owner, 100);
+    let r = owner.ro(&cell);
+    drop(cell); // Compile fail
+    assert_eq!(*r, 100);
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    struct Marker;
+    let owner = TCellOwner::<Marker>::new();
+    let cell = TCell::<Marker, _>::new(100);
+    let r = owner.ro(&cell);
+    drop(cell); // Compile fail
+    assert_eq!(*r, 100);
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TLCell, TLCellOwner};
+    struct Marker;
+    let owner = TLCellOwner::<Marker>::new();
+    let cell = TLCell::<Marker, _>::new(100);
+    let r = owner.ro(&cell);
+    drop(cell); // Compile fail
+    assert_eq!(*r, 100);
+}
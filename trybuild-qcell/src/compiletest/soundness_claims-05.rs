@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    LCellOwner::scope(|owner| {
+        let cell = LCell::new(100);
+        let r = owner.ro(&cell);
+        drop(cell); // Compile fail
+        assert_eq!(*r, 100);
+    });
+}
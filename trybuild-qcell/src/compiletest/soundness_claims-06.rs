@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{QCell, QCellOwner};
+    let mut owner = QCellOwner::new();
+    let cell = QCell::new(&owner, 100);
+    let r = owner.rw(&cell);
+    let moved = cell; // Compile fail
+    *r += 1;
+}
@@ -0,0 +1,8 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::QCellOwnerPinned;
+    let owner = QCellOwnerPinned::new();
+    let cell = owner.cell(100); // Compile fail
+}
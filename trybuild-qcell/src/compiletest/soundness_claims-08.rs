@@ -0,0 +1,10 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::QCellOwnerPinned;
+    use std::pin::Pin;
+    let pinned = Box::pin(QCellOwnerPinned::new());
+    let cell = pinned.as_ref().cell(100);
+    let owner = *Pin::into_inner(pinned); // Compile fail
+}
@@ -0,0 +1,9 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    type MarkerA = fn(&());
+    type MarkerB = fn(&'static ());
+    let owner1 = TCellOwner::<MarkerA>::new() as TCellOwner<MarkerB>; // Compile fail
+}
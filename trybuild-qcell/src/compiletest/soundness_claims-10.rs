@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TLCell, TLCellOwner};
+    use std::sync::Arc;
+    struct Marker;
+    let cell = Arc::new(TLCell::<Marker, u32>::new(100));
+    let cell2 = cell.clone();
+    std::thread::spawn(move || { // Compile fail
+        let owner = TLCellOwner::<Marker>::new();
+        assert_eq!(*owner.ro(&cell2), 100);
+    });
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{LCell, LCellOwner};
+    LCellOwner::scope(|owner1| {
+        let cell = LCell::new(100);
+        assert_eq!(*owner1.ro(&cell), 100);
+        LCellOwner::scope(|owner2| {
+            let _ = owner2.ro(&cell); // Compile fail
+        });
+    });
+}
@@ -0,0 +1,17 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    struct Marker;
+    type ACell<T> = TCell<Marker, T>;
+    type ACellOwner = TCellOwner<Marker>;
+    let mut owner = ACellOwner::new();
+    let log = ACell::new(vec![1, 2]);
+    let other = ACell::new(3);
+    let mut buf = Vec::new();
+    owner.rw_with(&log, &mut buf, |log, buf| {
+        buf.push(*owner.ro(&other)); // Compile fail
+        buf.append(log);
+    });
+}
@@ -0,0 +1,8 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TCellOwner};
+    marker!(struct Marker = 64;); // Compile fail
+    let owner = TCellOwner::<Marker>::new_indexed();
+}
@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    struct Marker;
+    type ACellOwner = TCellOwner<Marker>;
+    type ACell<T> = TCell<Marker, T>;
+    let cell: &'static ACell<i32> = Box::leak(Box::new(ACell::new(1)));
+    let mut owner = ACellOwner::new();
+    let value = owner.ro(cell);
+    *owner.rw(cell) += 1; // Compile fail
+    assert_eq!(*value, 1);
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TCell, TCellOwner};
+    struct Marker;
+    type ACellOwner = TCellOwner<Marker>;
+    type ACell<T> = TCell<Marker, T>;
+    let cell = ACell::new(1);
+    let owner = ACellOwner::new();
+    let _ = std::panic::catch_unwind(|| *owner.ro(&cell)); // Compile fail
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{Guarded, TCell, TCellOwner};
+    qcell::marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let cell = TCell::<Marker, u32>::new(1);
+    let data = Guarded::<Marker, u32>::new(2);
+    let w = owner.witness_mut();
+    let c = owner.ro(&cell);  // Compile fail
+    *data.get_mut(w) += *c;
+}
@@ -0,0 +1,14 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{Guarded, TCellOwner};
+    qcell::marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let data = Guarded::<Marker, u32>::new(2);
+    let w1 = owner.witness_mut();
+    let w2 = owner.witness_mut();  // Compile fail
+    let a = data.get_mut(w1);
+    let b = data.get_mut(w2);
+    std::mem::swap(a, b);
+}
@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{Guarded, TCellOwner};
+    qcell::marker!(struct Marker;);
+    let mut owner = TCellOwner::<Marker>::new();
+    let data = Guarded::<Marker, u32>::new(2);
+    let mut w = owner.witness_mut();
+    let a = data.get_mut(w.reborrow());
+    let b = data.get_mut(w);  // Compile fail
+    std::mem::swap(a, b);
+}
@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{Guarded, TCellOwner};
+    qcell::marker!(struct Marker;);
+    let owner = TCellOwner::<Marker>::new();
+    let data = Guarded::<Marker, u32>::new(2);
+    let w = owner.witness();
+    drop(owner);  // Compile fail
+    assert_eq!(*data.get(w), 2);
+}
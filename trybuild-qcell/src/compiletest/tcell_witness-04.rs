@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{Guarded, TCellOwner};
+    qcell::marker!(struct MarkerA;);
+    qcell::marker!(struct MarkerB;);
+    let mut owner_a = TCellOwner::<MarkerA>::new();
+    let data = Guarded::<MarkerB, u32>::new(2);
+    *data.get_mut(owner_a.witness_mut()) += 1;  // Compile fail
+}
@@ -0,0 +1,17 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TLCell, TLCellOwner};
+    struct Marker;
+    type ACell<T> = TLCell<Marker, T>;
+    type ACellOwner = TLCellOwner<Marker>;
+    fn bump(owner: &mut ACellOwner, cell: &ACell<u64>) {
+        owner.update(cell, |n| n + 1);
+    }
+    let mut owner = ACellOwner::new();
+    let hits = ACell::new(0u64);
+    let count = owner.rw(&hits);
+    bump(&mut owner, &hits); // Compile fail
+    *count += 1;
+}
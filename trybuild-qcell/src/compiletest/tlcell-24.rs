@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{TLCellSlot, TLCellSlotOwner};
+    struct Marker;
+    let owner0 = TLCellSlotOwner::<Marker, 0>::new();
+    let owner1 = TLCellSlotOwner::<Marker, 1>::new();
+    let c1 = TLCellSlot::<Marker, 1, _>::new(1);
+    assert_eq!(*owner1.ro(&c1), 1);
+    let _ = owner0.ro(&c1); // Compile fail
+}
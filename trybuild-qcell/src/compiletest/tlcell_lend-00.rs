@@ -0,0 +1,12 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TLCell, TLCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TLCellOwner::<Marker>::new();
+    let cell = TLCell::new(vec![1, 2, 3]);
+    let lend = owner.lend_to_thread(&cell, |v| v.push(4));
+    assert!(owner.ro(&cell).is_empty());  // Compile fail
+    lend.join();
+}
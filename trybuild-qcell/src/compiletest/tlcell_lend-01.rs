@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TLCell, TLCellOwner};
+    use std::rc::Rc;
+    marker!(struct Marker;);
+    let mut owner = TLCellOwner::<Marker>::new();
+    let cell = TLCell::new(Rc::new(1));
+    owner.lend_to_thread(&cell, |_| ()).join();  // Compile fail
+}
@@ -0,0 +1,11 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TLCell, TLCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TLCellOwner::<Marker>::new();
+    let cell = TLCell::new(String::new());
+    let suffix = String::from("abc");
+    owner.lend_to_thread(&cell, |s| s.push_str(&suffix)).join();  // Compile fail
+}
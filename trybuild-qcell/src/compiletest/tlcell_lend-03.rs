@@ -0,0 +1,13 @@
+extern crate qcell;
+
+#[allow(warnings)]
+fn main() {
+    use qcell::{marker, TLCell, TLCellOwner};
+    marker!(struct Marker;);
+    let mut owner = TLCellOwner::<Marker>::new();
+    let lend = {
+        let cell = TLCell::new(0u32);
+        owner.lend_to_thread(&cell, |v| *v += 1)  // Compile fail
+    };
+    lend.join();
+}
@@ -1,9 +1,20 @@
 #[cfg(test)]
 pub mod compiletest {
+    #[cfg(not(feature = "strict-markers"))]
     #[rustversion::all(stable, since(1.71), before(1.72))]
     #[test]
     fn ui() {
         let t = trybuild::TestCases::new();
         t.compile_fail("src/compiletest/*.rs");
+        t.compile_fail("../src/errors/*.rs");
+        t.compile_fail("../src/lcell_brands/*.rs");
+    }
+
+    #[cfg(feature = "strict-markers")]
+    #[rustversion::all(stable, since(1.71), before(1.72))]
+    #[test]
+    fn ui_strict() {
+        let t = trybuild::TestCases::new();
+        t.compile_fail("src/compiletest-strict/*.rs");
     }
 }
@@ -0,0 +1,526 @@
+//! Keeps the `compile_fail` doc-tests in sync with the copies in
+//! `trybuild-qcell`, which `trybuild` uses to check that they fail
+//! for the expected reason.
+//!
+//! Each `compile_fail` block in a `src/doctest_*.rs` module, or in one
+//! of the [`OTHER_MODULES`], becomes `<name>-NN.rs` in
+//! `trybuild-qcell/src/compiletest/`, where `<name>` is the module name
+//! without the `doctest_` prefix.  Modules which are only compiled
+//! with the "strict-markers" feature go in `compiletest-strict/`
+//! instead.  This is decided from the module's `#[cfg]` in
+//! `src/lib.rs`, evaluated with the features that `trybuild-qcell`
+//! enables, with and without its own "strict-markers" feature.
+//!
+//! Existing files whose contents still match a block keep their
+//! numbers, so that their `.stderr` files stay valid.  Files which no
+//! longer match are deleted along with their `.stderr` files, and new
+//! blocks are written to the lowest free numbers.  So the result only
+//! depends on the current sources and the files already there, and
+//! running it twice changes nothing the second time.
+//!
+//! This is shared between `cargo xtask sync-compiletests` and the
+//! `compiletest_sync` test in the main crate, which fails if anything
+//! would be changed.  That test only compares the `.rs` files, so
+//! unlike the `trybuild` tests themselves it doesn't depend on the
+//! compiler version.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Modules other than `doctest_*` whose `compile_fail` blocks are
+/// checked with `trybuild`.  The `errors` and `lcell_brands` modules
+/// aren't listed, since their examples are already separate files
+/// which `trybuild` tests directly.
+pub const OTHER_MODULES: &[&str] = &["soundness_claims", "iter", "global"];
+
+const DIR_NORMAL: &str = "trybuild-qcell/src/compiletest";
+const DIR_STRICT: &str = "trybuild-qcell/src/compiletest-strict";
+
+/// A `compile_fail` block that should exist as a file
+pub struct Expected {
+    pub source: PathBuf,
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Changes needed to bring `trybuild-qcell` up to date
+#[derive(Default)]
+pub struct Plan {
+    /// Files to delete, which no longer match any block
+    pub delete: Vec<PathBuf>,
+    /// Files to write, for blocks which have no file yet
+    pub write: Vec<Expected>,
+    /// Files which are up to date, but have no `.stderr` yet
+    pub no_stderr: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// Test whether the files are already in sync
+    pub fn is_empty(&self) -> bool {
+        self.delete.is_empty() && self.write.is_empty() && self.no_stderr.is_empty()
+    }
+
+    /// Describe the changes needed, relative to `root`
+    pub fn report(&self, root: &Path) -> String {
+        let rel = |p: &Path| p.strip_prefix(root).unwrap_or(p).display().to_string();
+        let mut out = String::new();
+        for path in &self.delete {
+            out.push_str(&format!("  stale: {}\n", rel(path)));
+        }
+        for exp in &self.write {
+            out.push_str(&format!(
+                "  missing: {} (from {})\n",
+                rel(&exp.path),
+                rel(&exp.source)
+            ));
+        }
+        for path in &self.no_stderr {
+            out.push_str(&format!("  no .stderr: {}\n", rel(path)));
+        }
+        out
+    }
+
+    /// Delete and write the files, printing each change
+    pub fn apply(&self, root: &Path) -> Result<(), String> {
+        let rel = |p: &Path| p.strip_prefix(root).unwrap_or(p).display().to_string();
+        for path in &self.delete {
+            println!("Deleting {} ...", rel(path));
+            match fs::remove_file(path) {
+                // Already gone with its `.rs` file
+                Err(e) if e.kind() == ErrorKind::NotFound => (),
+                Err(e) => return Err(format!("Can't delete {}: {}", rel(path), e)),
+                Ok(()) => (),
+            }
+            if path.extension().and_then(|e| e.to_str()) == Some("rs") {
+                // Also delete error file if present
+                let _ = fs::remove_file(path.with_extension("stderr"));
+            }
+        }
+        for exp in &self.write {
+            println!("Writing {} ...", rel(&exp.path));
+            fs::write(&exp.path, &exp.contents)
+                .map_err(|e| format!("Can't write {}: {}", rel(&exp.path), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Work out what needs changing in `trybuild-qcell` under the crate
+/// base directory `root`.  Nothing is modified.
+pub fn plan(root: &Path) -> Result<Plan, String> {
+    let sources = sources(root)?;
+    let mut plan = Plan::default();
+    for dir in &[DIR_NORMAL, DIR_STRICT] {
+        let dir = root.join(dir);
+        let files = list_dir(&dir)?;
+        let mut prefixes = BTreeSet::new();
+        for src in sources.iter().filter(|s| s.dir == dir) {
+            prefixes.insert(src.prefix.clone());
+            let tests = extract(&read(&src.path)?);
+            plan_prefix(&mut plan, &dir, &files, src, tests)?;
+        }
+
+        // Files left over from modules which no longer have any
+        // blocks, or which have moved to the other folder
+        for name in &files {
+            let stem = name.trim_end_matches(".rs").trim_end_matches(".stderr");
+            let known = match stem.rsplit_once('-') {
+                Some((prefix, _)) => prefixes.contains(prefix),
+                None => false,
+            };
+            let orphan = name.ends_with(".stderr") && !files.contains(&format!("{}.rs", stem));
+            if !known || orphan {
+                plan.delete.push(dir.join(name));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+fn plan_prefix(
+    plan: &mut Plan,
+    dir: &Path,
+    files: &BTreeSet<String>,
+    src: &Source,
+    tests: Vec<String>,
+) -> Result<(), String> {
+    // Keep the files which are still identical to a test still
+    // required, else delete them
+    let mut pending: Vec<Option<String>> = tests.into_iter().map(Some).collect();
+    let mut used = BTreeSet::new();
+    for name in files {
+        match name.strip_suffix(".rs").and_then(|s| s.rsplit_once('-')) {
+            Some((prefix, _)) if prefix == src.prefix => (),
+            _ => continue,
+        }
+        let path = dir.join(name);
+        let data = read(&path)?;
+        match pending.iter_mut().find(|t| t.as_deref() == Some(&*data)) {
+            Some(slot) => {
+                *slot = None;
+                used.insert(name.clone());
+                if !files.contains(&format!("{}.stderr", name.trim_end_matches(".rs"))) {
+                    plan.no_stderr.push(path);
+                }
+            }
+            None => plan.delete.push(path),
+        }
+    }
+
+    // Output the rest in a sequential order, filling any gaps
+    let mut cnt = 0;
+    for contents in pending.into_iter().flatten() {
+        let name = loop {
+            let name = format!("{}-{:02}.rs", src.prefix, cnt);
+            cnt += 1;
+            if !used.contains(&name) {
+                break name;
+            }
+        };
+        plan.write.push(Expected {
+            source: src.path.clone(),
+            path: dir.join(&name),
+            contents,
+        });
+        used.insert(name);
+    }
+    Ok(())
+}
+
+/// Extract the `compile_fail` blocks from a source file, each
+/// wrapped up as a complete test program
+pub fn extract(data: &str) -> Vec<String> {
+    let mut tests = Vec::new();
+    let mut rest = data;
+    while let Some(start) = rest.find("```compile_fail") {
+        rest = &rest[start + "```compile_fail".len()..];
+        let end = match rest.find("```") {
+            Some(end) => end,
+            None => break,
+        };
+        let block = &rest[..end];
+        rest = &rest[end + 3..];
+
+        // Strip the comment markers, including the `#` of hidden
+        // lines, and indent by 3 more spaces
+        let mut test = String::new();
+        for line in strip_markers(block).split('\n') {
+            test.push_str("   ");
+            test.push_str(line);
+            test.push('\n');
+        }
+        // Remove initial empty line, i.e. the rest of the opening
+        // line, and trailing space
+        if let Some(nl) = test.find('\n') {
+            if test[..nl].trim_matches(|c| c == ' ' || c == '\t').is_empty() {
+                test.drain(..=nl);
+            }
+        }
+        let test = test.trim_end();
+
+        tests.push(format!(
+            "extern crate qcell;\n\n#[allow(warnings)]\nfn main() {{\n{}\n}}\n",
+            test
+        ));
+    }
+    tests
+}
+
+fn strip_markers(block: &str) -> String {
+    let mut out = String::new();
+    let mut rest = block;
+    loop {
+        let pos = [rest.find("//!"), rest.find("///")]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+        match pos {
+            None => break,
+            Some(pos) => {
+                out.push_str(&rest[..pos]);
+                rest = &rest[pos + 3..];
+                rest = rest.strip_prefix('#').unwrap_or(rest);
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A module containing `compile_fail` blocks
+struct Source {
+    path: PathBuf,
+    prefix: String,
+    dir: PathBuf,
+}
+
+fn sources(root: &Path) -> Result<Vec<Source>, String> {
+    let mut modules: Vec<String> = list_dir(&root.join("src"))?
+        .into_iter()
+        .filter(|n| n.starts_with("doctest_") && n.ends_with(".rs"))
+        .map(|n| n.trim_end_matches(".rs").to_string())
+        .collect();
+    modules.extend(OTHER_MODULES.iter().map(|m| m.to_string()));
+
+    let cfgs = module_cfgs(&read(&root.join("src/lib.rs"))?)?;
+    let (normal, strict) = trybuild_features(root)?;
+    let mut sources = Vec::new();
+    for module in modules {
+        let cfg = cfgs
+            .get(&module)
+            .ok_or_else(|| format!("Module `{}` is not declared in src/lib.rs", module))?;
+        let dir = if cfg.iter().all(|c| c.eval(&normal)) {
+            DIR_NORMAL
+        } else if cfg.iter().all(|c| c.eval(&strict)) {
+            DIR_STRICT
+        } else {
+            return Err(format!(
+                "Module `{}` is not compiled in trybuild-qcell with or without \
+                 \"strict-markers\": enable the features it needs there",
+                module
+            ));
+        };
+        sources.push(Source {
+            path: root.join("src").join(format!("{}.rs", module)),
+            prefix: module.trim_start_matches("doctest_").to_string(),
+            dir: root.join(dir),
+        });
+    }
+    Ok(sources)
+}
+
+/// Find the `#[cfg]` conditions on each module declared in `lib.rs`
+fn module_cfgs(lib: &str) -> Result<BTreeMap<String, Vec<Cfg>>, String> {
+    let mut map = BTreeMap::new();
+    let mut cfgs = Vec::new();
+    for line in lib.lines() {
+        let line = line.trim();
+        if let Some(cfg) = line.strip_prefix("#[cfg(") {
+            let cfg = cfg
+                .strip_suffix(")]")
+                .ok_or_else(|| format!("Can't parse multi-line attribute: {}", line))?;
+            cfgs.push(Cfg::parse(cfg)?);
+        } else if line.starts_with("#[") || (line.starts_with("//") && !line.starts_with("//!")) {
+            // Other attributes and comments don't end the declaration
+        } else {
+            let decl = line.strip_prefix("pub ").unwrap_or(line);
+            if let Some(name) = decl.strip_prefix("mod ").and_then(|d| d.strip_suffix(';')) {
+                map.insert(name.to_string(), std::mem::take(&mut cfgs));
+            }
+            cfgs.clear();
+        }
+    }
+    Ok(map)
+}
+
+/// Get the `qcell` features enabled by `trybuild-qcell`, without and
+/// with its "strict-markers" feature
+fn trybuild_features(root: &Path) -> Result<(BTreeSet<String>, BTreeSet<String>), String> {
+    let mut table = BTreeMap::new();
+    for line in toml_section(&read(&root.join("Cargo.toml"))?, "features") {
+        if let Some((name, list)) = line.split_once('=') {
+            table.insert(name.trim().to_string(), string_list(list));
+        }
+    }
+
+    let manifest = read(&root.join("trybuild-qcell/Cargo.toml"))?;
+    let dep = toml_section(&manifest, "dependencies")
+        .into_iter()
+        .find(|l| l.starts_with("qcell "))
+        .ok_or("No `qcell` dependency in trybuild-qcell/Cargo.toml")?;
+    let mut enabled = dep
+        .match_indices("features")
+        .find(|(pos, _)| !dep[..*pos].ends_with("default-"))
+        .map_or_else(Vec::new, |(pos, _)| string_list(&dep[pos..]));
+    let no_default = dep.split_whitespace().collect::<String>().contains("default-features=false");
+    if !no_default {
+        enabled.push("default".to_string());
+    }
+
+    let normal = feature_closure(enabled.clone(), &table);
+    // trybuild-qcell's "strict-markers" enables "qcell/strict-markers"
+    enabled.push("strict-markers".to_string());
+    let strict = feature_closure(enabled, &table);
+    Ok((normal, strict))
+}
+
+fn feature_closure(mut todo: Vec<String>, table: &BTreeMap<String, Vec<String>>) -> BTreeSet<String> {
+    let mut set = BTreeSet::new();
+    while let Some(f) = todo.pop() {
+        // Skip optional dependencies and features of dependencies
+        if f.contains(':') || f.contains('/') || !set.insert(f.clone()) {
+            continue;
+        }
+        if let Some(list) = table.get(&f) {
+            todo.extend(list.iter().cloned());
+        }
+    }
+    set
+}
+
+fn toml_section<'a>(text: &'a str, name: &str) -> Vec<&'a str> {
+    let header = format!("[{}]", name);
+    text.lines()
+        .map(str::trim)
+        .skip_while(|l| *l != header)
+        .skip(1)
+        .take_while(|l| !l.starts_with('['))
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+fn string_list(text: &str) -> Vec<String> {
+    let start = text.find('[').map_or(text.len(), |p| p + 1);
+    let end = text[start..].find(']').map_or(text.len(), |p| start + p);
+    text[start..end]
+        .split(',')
+        .map(|s| s.trim().trim_matches('"'))
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// A parsed `#[cfg]` condition
+#[derive(Debug, PartialEq)]
+enum Cfg {
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+    Feature(String),
+    Const(bool),
+}
+
+impl Cfg {
+    fn parse(text: &str) -> Result<Self, String> {
+        let err = || format!("Can't parse cfg: {}", text);
+        let mut tokens = Vec::new();
+        let mut rest = text.trim_start();
+        while let Some(c) = rest.chars().next() {
+            let len = if c == '"' {
+                rest[1..].find('"').ok_or_else(err)? + 2
+            } else if c.is_alphanumeric() || c == '_' {
+                rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len())
+            } else if "(),=".contains(c) {
+                1
+            } else {
+                return Err(err());
+            };
+            tokens.push(&rest[..len]);
+            rest = rest[len..].trim_start();
+        }
+        let mut pos = 0;
+        let cfg = Self::parse_pred(&tokens, &mut pos).ok_or_else(err)?;
+        if pos != tokens.len() {
+            return Err(err());
+        }
+        Ok(cfg)
+    }
+
+    fn parse_pred(tokens: &[&str], pos: &mut usize) -> Option<Self> {
+        let name = *tokens.get(*pos)?;
+        *pos += 1;
+        match tokens.get(*pos) {
+            Some(&"(") => {
+                *pos += 1;
+                let mut list = Vec::new();
+                while tokens.get(*pos) != Some(&")") {
+                    list.push(Self::parse_pred(tokens, pos)?);
+                    match *tokens.get(*pos)? {
+                        "," => *pos += 1,
+                        ")" => (),
+                        _ => return None,
+                    }
+                }
+                *pos += 1;
+                match name {
+                    "all" => Some(Cfg::All(list)),
+                    "any" => Some(Cfg::Any(list)),
+                    "not" if list.len() == 1 => Some(Cfg::Not(Box::new(list.pop()?))),
+                    _ => None,
+                }
+            }
+            Some(&"=") => {
+                let value = tokens.get(*pos + 1)?.strip_prefix('"')?.strip_suffix('"')?;
+                *pos += 2;
+                match name {
+                    "feature" => Some(Cfg::Feature(value.to_string())),
+                    // Assume a 64-bit host
+                    "target_has_atomic" => Some(Cfg::Const(true)),
+                    _ => None,
+                }
+            }
+            _ => match name {
+                "test" | "docsrs" | "miri" => Some(Cfg::Const(false)),
+                _ => None,
+            },
+        }
+    }
+
+    fn eval(&self, features: &BTreeSet<String>) -> bool {
+        match self {
+            Cfg::All(list) => list.iter().all(|c| c.eval(features)),
+            Cfg::Any(list) => list.iter().any(|c| c.eval(features)),
+            Cfg::Not(c) => !c.eval(features),
+            Cfg::Feature(f) => features.contains(f),
+            Cfg::Const(v) => *v,
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))
+}
+
+fn list_dir(dir: &Path) -> Result<BTreeSet<String>, String> {
+    let mut names = BTreeSet::new();
+    let entries = fs::read_dir(dir).map_err(|e| format!("Can't read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Can't read {}: {}", dir.display(), e))?;
+        if let Some(name) = entry.file_name().to_str() {
+            names.insert(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, module_cfgs, Cfg};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn extract_block() {
+        let src = "//! Text\n//!\n//! ```compile_fail\n//!# use a::B;\n//! let x = B;\n//!\n//! x.y(); // Compile fail\n//! ```\n//! More\n";
+        assert_eq!(
+            extract(src),
+            ["extern crate qcell;\n\n#[allow(warnings)]\nfn main() {\n    use a::B;\n    let x = B;\n   \n    x.y(); // Compile fail\n}\n"]
+        );
+        let src = "    /// ```compile_fail\n    ///# f();\n    /// ```\n    /// ```\n    /// ok();\n    /// ```\n";
+        assert_eq!(
+            extract(src),
+            ["extern crate qcell;\n\n#[allow(warnings)]\nfn main() {\n        f();\n}\n"]
+        );
+    }
+
+    #[test]
+    fn cfg_eval() {
+        let lib = "#[cfg(feature = \"std\")]\n// Comment\n#[cfg_attr(docsrs, doc(cfg(feature = \"std\")))]\npub mod a;\nmod b;\n#[cfg(all(feature = \"std\", not(feature = \"strict-markers\")))]\n\nmod c;\n";
+        let cfgs = module_cfgs(lib).unwrap();
+        assert_eq!(cfgs["a"], [Cfg::Feature("std".into())]);
+        assert!(cfgs["b"].is_empty());
+        // The blank line ends the attributes
+        assert!(cfgs["c"].is_empty());
+
+        let cfg = Cfg::parse("all(feature = \"std\", not(feature = \"strict-markers\"))").unwrap();
+        let mut features: BTreeSet<String> = ["alloc", "std"].iter().map(|s| s.to_string()).collect();
+        assert!(cfg.eval(&features));
+        features.insert("strict-markers".into());
+        assert!(!cfg.eval(&features));
+        assert!(Cfg::parse("any(feature = \"x\", test)").is_ok());
+        assert!(Cfg::parse("unix").is_err());
+        assert!(Cfg::parse("not(feature = \"x\", test)").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! Maintenance tasks for the `qcell` crate.  Run from the crate base
+//! directory:
+//!
+//! - `cargo xtask sync-compiletests`: Regenerate the `compile_fail`
+//!   tests in `trybuild-qcell` from the doc-tests.  Run this after
+//!   making any modification to `compile_fail` tests, then run
+//!   `TRYBUILD=overwrite cargo test` in `trybuild-qcell` to create the
+//!   `.stderr` files for any new ones, and check them in.
+//!
+//! - `cargo xtask sync-compiletests --check`: Report any differences
+//!   without changing anything, failing if there are any.  The
+//!   `compiletest_sync` test in the main crate does the same check.
+
+mod compiletest;
+
+use std::path::Path;
+use std::process::exit;
+
+const USAGE: &str = "Usage: cargo xtask sync-compiletests [--check]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let check = match args[..] {
+        ["sync-compiletests"] => false,
+        ["sync-compiletests", "--check"] => true,
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in a subdirectory");
+    if let Err(e) = sync_compiletests(root, check) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn sync_compiletests(root: &Path, check: bool) -> Result<(), String> {
+    let plan = compiletest::plan(root)?;
+    if plan.is_empty() {
+        return Ok(());
+    }
+    if check {
+        return Err(format!(
+            "The compile_fail tests in trybuild-qcell are out of date:\n{}\
+             Run `cargo xtask sync-compiletests` to update them",
+            plan.report(root)
+        ));
+    }
+    plan.apply(root)?;
+    let new: Vec<_> = plan.write.iter().map(|e| e.path.clone()).collect();
+    if !new.is_empty() || !plan.no_stderr.is_empty() {
+        println!(
+            "Now run `TRYBUILD=overwrite cargo test` in trybuild-qcell to create the \
+             missing .stderr files, and check that each fails for the intended reason"
+        );
+    }
+    Ok(())
+}